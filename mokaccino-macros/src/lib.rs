@@ -0,0 +1,35 @@
+//! Compile-time queries for [mokaccino](https://docs.rs/mokaccino).
+
+use proc_macro::TokenStream;
+use syn::{LitStr, parse_macro_input};
+
+/// A [`Query`](https://docs.rs/mokaccino/latest/mokaccino/prelude/struct.Query.html)
+/// parsed at compile time.
+///
+/// It is the same query as parsing the string with the default
+/// parser options, without any parsing at run time.
+/// An invalid query is a compile error.
+///
+/// Example:
+///
+/// ```
+/// use mokaccino::prelude::*;
+/// use mokaccino_macros::query;
+///
+/// let q = query!("colour:blue AND price<100");
+/// assert_eq!(q, "colour:blue AND price<100".parse::<Query>().unwrap());
+/// ```
+///
+/// ```compile_fail
+/// use mokaccino_macros::query;
+///
+/// let q = query!("colour:blue AND");
+/// ```
+#[proc_macro]
+pub fn query(input: TokenStream) -> TokenStream {
+    let s = parse_macro_input!(input as LitStr);
+    match mokaccino::prelude::parsing::macro_support::query_code(&s.value()) {
+        Ok(code) => code.parse().expect("Valid Rust code"),
+        Err(e) => syn::Error::new(s.span(), e).to_compile_error().into(),
+    }
+}
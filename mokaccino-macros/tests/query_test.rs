@@ -0,0 +1,57 @@
+use mokaccino::prelude::*;
+use mokaccino_macros::query;
+
+#[test]
+fn test_same_as_parsed() {
+    let pairs = [
+        (query!("colour:blue"), "colour:blue"),
+        (
+            query!("colour:blue AND price<100"),
+            "colour:blue AND price<100",
+        ),
+        (
+            query!("NOT colour IN {red, blue} OR price BETWEEN 1 AND 2.5"),
+            "NOT colour IN {red, blue} OR price BETWEEN 1 AND 2.5",
+        ),
+        (
+            query!("name:Jo* OR name:*ne OR name:*oh* OR name:\"with space\""),
+            "name:Jo* OR name:*ne OR name:*oh* OR name:\"with space\"",
+        ),
+        (
+            query!("colour:blue^2.5 AND taste:sweet^0.1"),
+            "colour:blue^2.5 AND taste:sweet^0.1",
+        ),
+        (query!("price>=1.5 AND size:*"), "price>=1.5 AND size:*"),
+        (
+            query!("location H3IN 87194d106ffffff"),
+            "location H3IN 87194d106ffffff",
+        ),
+        (
+            query!("location H3IN {87194d106ffffff, 861f09b27ffffff}"),
+            "location H3IN {87194d106ffffff, 861f09b27ffffff}",
+        ),
+        (
+            query!("position LLWITHIN {48.864716,2.349014,1000; -54.35,18.66,500}"),
+            "position LLWITHIN {48.864716,2.349014,1000; -54.35,18.66,500}",
+        ),
+        (
+            query!("position LLBETWEEN 48.864716,2.349014,1000,3000"),
+            "position LLBETWEEN 48.864716,2.349014,1000,3000",
+        ),
+    ];
+    for (q, s) in pairs {
+        assert_eq!(q, s.parse::<Query>().unwrap(), "{}", s);
+    }
+}
+
+#[test]
+fn test_percolate() {
+    let mut p = Percolator::default();
+    let qid = p.add_query(query!("colour:blue AND price<100"));
+    let _ = p.add_query(query!("colour:red"));
+    assert_eq!(
+        p.percolate(&[("colour", "blue"), ("price", "50")].into())
+            .collect::<Vec<_>>(),
+        vec![qid]
+    );
+}
@@ -0,0 +1,236 @@
+//! Compare percolator configurations on your own queries and documents.
+//!
+//! This is a lightweight alternative to the criterion benches of this
+//! crate, to choose parameters like `n_clause_matchers` or `prefix_sizes`
+//! from code, for instance in a tuning job over a sample of production data.
+//!
+//! Timings are wall clock time in the current thread, so run this in release mode,
+//! on a quiet machine and with enough documents for the figures to be meaningful.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::models::percolator_core::PercolatorConfig;
+use crate::prelude::{Document, Percolator, Query};
+
+/// How one configuration did in [`compare`]
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    config: PercolatorConfig,
+    n_queries: usize,
+    n_rejected: usize,
+    n_docs: usize,
+    n_matches: usize,
+    build_time: Duration,
+    percolate_time: Duration,
+    // Sorted.
+    latencies: Vec<Duration>,
+    index_bytes: usize,
+}
+
+impl BenchReport {
+    /// The configuration this is about.
+    pub fn config(&self) -> &PercolatorConfig {
+        &self.config
+    }
+
+    /// How many queries the percolator got built with.
+    pub fn n_queries(&self) -> usize {
+        self.n_queries
+    }
+
+    /// How many queries the percolator refused, for instance because
+    /// of [`PercolatorConfig::max_clauses`]. They are not in the other figures.
+    pub fn n_rejected(&self) -> usize {
+        self.n_rejected
+    }
+
+    /// How many documents were percolated.
+    pub fn n_docs(&self) -> usize {
+        self.n_docs
+    }
+
+    /// The total number of matches over all the documents.
+    /// This should be the same for all configurations.
+    pub fn n_matches(&self) -> usize {
+        self.n_matches
+    }
+
+    /// The time it took to add all the queries.
+    pub fn build_time(&self) -> Duration {
+        self.build_time
+    }
+
+    /// Percolated documents per second.
+    pub fn throughput(&self) -> f64 {
+        self.n_docs as f64 / self.percolate_time.as_secs_f64()
+    }
+
+    /// The mean time to percolate one document and collect its matches.
+    pub fn mean_latency(&self) -> Duration {
+        self.percolate_time
+            .checked_div(self.n_docs as u32)
+            .unwrap_or_default()
+    }
+
+    /// The time under which this share (from 0.0 to 1.0) of the
+    /// documents got percolated. For instance, `latency(0.99)` is the p99.
+    pub fn latency(&self, share: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let i = ((self.latencies.len() - 1) as f64 * share.clamp(0.0, 1.0)).round() as usize;
+        self.latencies[i]
+    }
+
+    /// The longest time to percolate a document.
+    pub fn max_latency(&self) -> Duration {
+        self.latencies.last().copied().unwrap_or_default()
+    }
+
+    /// An estimate of the memory taken by the indexes, in bytes.
+    /// The queries themselves are not counted, as all configurations keep them alike.
+    pub fn index_bytes(&self) -> usize {
+        self.index_bytes
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "n_clause_matchers={} prefix_sizes={:?}: {:.0} docs/s, mean {:?}, p50 {:?}, p99 {:?}, max {:?}, index ~{}KiB, built in {:?}",
+            self.config.n_clause_matchers(),
+            self.config.prefix_sizes(),
+            self.throughput(),
+            self.mean_latency(),
+            self.latency(0.5),
+            self.latency(0.99),
+            self.max_latency(),
+            self.index_bytes / 1024,
+            self.build_time,
+        )
+    }
+}
+
+fn run(config: &PercolatorConfig, queries: &[Query], docs: &[Document]) -> BenchReport {
+    let start = Instant::now();
+    let mut p = Percolator::builder().with_config(config.clone()).build();
+    let n_rejected = queries
+        .iter()
+        .filter(|q| p.safe_add_query((*q).clone()).is_err())
+        .count();
+    let build_time = start.elapsed();
+
+    let mut latencies = Vec::with_capacity(docs.len());
+    let mut n_matches = 0;
+    let start = Instant::now();
+    for d in docs {
+        let doc_start = Instant::now();
+        n_matches += p.percolate(d).count();
+        latencies.push(doc_start.elapsed());
+    }
+    let percolate_time = start.elapsed();
+    latencies.sort();
+
+    BenchReport {
+        config: config.clone(),
+        n_queries: queries.len() - n_rejected,
+        n_rejected,
+        n_docs: docs.len(),
+        n_matches,
+        build_time,
+        percolate_time,
+        latencies,
+        index_bytes: p.index_heap_bytes(),
+    }
+}
+
+/// Builds a percolator with each configuration and the queries, then percolates all the documents
+/// through it. Returns a report for each configuration, in the same order.
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+/// use std::num::NonZeroUsize;
+///
+/// let queries = vec!["name".has_prefix("mok"), "name".has_value("java") | "price".i64_lt(10)];
+/// let docs: Vec<Document> = vec![[("name", "mokaccino")].into(), [("price", "5")].into()];
+///
+/// let configs = [
+///     Percolator::builder().config().clone(),
+///     Percolator::builder()
+///         .n_clause_matchers(NonZeroUsize::new(1).unwrap())
+///         .prefix_sizes(vec![3])
+///         .config()
+///         .clone(),
+/// ];
+///
+/// let reports = mokaccino::bench::compare(&configs, &queries, &docs);
+/// assert_eq!(reports.len(), 2);
+/// assert!(reports.iter().all(|r| r.n_matches() == 2));
+/// for r in reports {
+///     println!("{}", r);
+/// }
+/// ```
+pub fn compare(
+    configs: &[PercolatorConfig],
+    queries: &[Query],
+    docs: &[Document],
+) -> Vec<BenchReport> {
+    configs.iter().map(|c| run(c, queries, docs)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::CNFQueryable;
+    use std::num::NonZeroUsize;
+
+    #[test]
+    fn test_compare() {
+        let queries = (0..100)
+            .map(|i| format!("name{}", i).has_prefix(format!("{:03}", i)))
+            .chain(std::iter::once(
+                "a".has_value("1") & "b".has_value("2") & "c".has_value("3"),
+            ))
+            .collect::<Vec<_>>();
+        let docs = (0..50)
+            .map(|i| [(format!("name{}", i), format!("{:03}suffix", i))].into())
+            .collect::<Vec<Document>>();
+
+        let configs = [
+            PercolatorConfig::default(),
+            Percolator::builder()
+                .prefix_compression(true)
+                .max_clauses(2)
+                .n_clause_matchers(NonZeroUsize::new(1).unwrap())
+                .config()
+                .clone(),
+        ];
+        let reports = compare(&configs, &queries, &docs);
+        assert_eq!(reports.len(), 2);
+
+        assert_eq!(reports[0].n_queries(), 101);
+        assert_eq!(reports[0].n_rejected(), 0);
+        assert_eq!(reports[1].n_queries(), 100);
+        assert_eq!(reports[1].n_rejected(), 1);
+        assert_eq!(reports[1].config().n_clause_matchers().get(), 1);
+
+        for r in &reports {
+            assert_eq!(r.n_docs(), 50);
+            assert_eq!(r.n_matches(), 50);
+            assert!(r.throughput() > 0.0);
+            assert!(r.latency(0.0) <= r.latency(0.5));
+            assert!(r.latency(0.5) <= r.latency(0.99));
+            assert_eq!(r.latency(1.0), r.max_latency());
+            assert!(r.index_bytes() > 0);
+            assert!(r.to_string().contains("docs/s"));
+        }
+
+        let empty = compare(&configs, &[], &[]);
+        assert_eq!(empty[0].n_matches(), 0);
+        assert_eq!(empty[0].mean_latency(), Duration::ZERO);
+        assert_eq!(empty[0].max_latency(), Duration::ZERO);
+    }
+}
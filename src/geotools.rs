@@ -1,6 +1,7 @@
 use std::fmt::Display;
 
 use h3o::{CellIndex, LatLng, Resolution};
+use itertools::Itertools;
 use nonempty::{NonEmpty, nonempty};
 
 // Average edge lengths (meters) for H3 resolutions 0..=15.
@@ -92,10 +93,354 @@ pub(crate) fn disk_covering(
     }
 }
 
+/// A polygon on the WGS84 lat/lng plane, made of an exterior
+/// ring and some holes.
+///
+/// Rings are lists of vertices. Closing the ring by repeating
+/// the first vertex at the end is allowed but not required.
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+/// use h3o::LatLng;
+///
+/// let ll = |lat, lng| LatLng::new(lat, lng).unwrap();
+/// let donut = Polygon::new(vec![ll(0.0, 0.0), ll(0.0, 1.0), ll(1.0, 1.0), ll(1.0, 0.0)])
+///     .with_hole(vec![ll(0.4, 0.4), ll(0.4, 0.6), ll(0.6, 0.6), ll(0.6, 0.4)]);
+/// assert_eq!(donut.holes().len(), 1);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Polygon {
+    exterior: Vec<LatLng>,
+    holes: Vec<Vec<LatLng>>,
+}
+
+fn assert_ring(ring: &[LatLng]) {
+    assert!(ring.len() >= 3, "A ring needs at least 3 vertices");
+}
+
+impl Polygon {
+    /// A polygon without holes.
+    ///
+    /// # Panics
+    ///
+    /// If the ring has less than 3 vertices.
+    pub fn new(exterior: Vec<LatLng>) -> Self {
+        assert_ring(&exterior);
+        Self {
+            exterior,
+            holes: vec![],
+        }
+    }
+
+    /// Adds a hole to this polygon.
+    ///
+    /// # Panics
+    ///
+    /// If the ring has less than 3 vertices.
+    pub fn with_hole(mut self, hole: Vec<LatLng>) -> Self {
+        assert_ring(&hole);
+        self.holes.push(hole);
+        self
+    }
+
+    /// The exterior ring
+    pub fn exterior(&self) -> &[LatLng] {
+        &self.exterior
+    }
+
+    /// The holes
+    pub fn holes(&self) -> &[Vec<LatLng>] {
+        &self.holes
+    }
+}
+
+/// Is the given lat/lng inside the ring?
+/// This uses a simple ray casting on the lat/lng plane,
+/// which is fine for rings that do not cross the antimeridian.
+pub(crate) fn ring_contains(ring: &[LatLng], ll: LatLng) -> bool {
+    let (x, y) = (ll.lng(), ll.lat());
+    ring.iter()
+        .zip(ring.iter().cycle().skip(1))
+        .filter(|(a, b)| {
+            let (ax, ay, bx, by) = (a.lng(), a.lat(), b.lng(), b.lat());
+            (ay > y) != (by > y) && x < (bx - ax) * (y - ay) / (by - ay) + ax
+        })
+        .count()
+        % 2
+        == 1
+}
+
+// Do the segments [a,b] and [c,d] cross on the lat/lng plane?
+fn segments_cross(a: LatLng, b: LatLng, c: LatLng, d: LatLng) -> bool {
+    let orient = |p: LatLng, q: LatLng, r: LatLng| {
+        ((q.lng() - p.lng()) * (r.lat() - p.lat()) - (q.lat() - p.lat()) * (r.lng() - p.lng()))
+            .signum()
+    };
+    orient(a, b, c) != orient(a, b, d) && orient(c, d, a) != orient(c, d, b)
+}
+
+/// Does the ring share some area with the given cell?
+pub(crate) fn ring_intersects_cell(ring: &[LatLng], cell: CellIndex) -> bool {
+    let boundary = cell.boundary();
+    let edges = |vs: &[LatLng]| {
+        vs.iter()
+            .copied()
+            .zip(vs.iter().copied().cycle().skip(1))
+            .collect::<Vec<_>>()
+    };
+
+    ring_contains(ring, LatLng::from(cell))
+        || boundary.iter().any(|&v| ring_contains(ring, v))
+        || ring.iter().any(|v| v.to_cell(cell.resolution()) == cell)
+        || edges(ring).iter().any(|&(a, b)| {
+            edges(&boundary)
+                .iter()
+                .any(|&(c, d)| segments_cross(a, b, c, d))
+        })
+}
+
+/// Generates a set of H3 cells covering a ring.
+/// The resolution is chosen like for a disk covering
+/// the ring, clamped to `max_res`.
+pub(crate) fn ring_covering(ring: &[LatLng], max_res: Resolution) -> NonEmpty<CellIndex> {
+    let (_, radius) = enclosing_disk(ring);
+    let res = resolution_within_k(Meters(radius.ceil() as u64), 4).min(max_res);
+
+    // A disk around a ring wider than a half turn can miss parts of it,
+    // so such rings are covered in strips no wider than that.
+    let (min_lng, max_lng) = ring
+        .iter()
+        .map(|v| v.lng())
+        .fold((f64::MAX, f64::MIN), |(lo, hi), lng| {
+            (lo.min(lng), hi.max(lng))
+        });
+    let cells = if max_lng - min_lng > 180.0 {
+        let mid = (min_lng + max_lng) / 2.0;
+        [clip_ring(ring, min_lng, mid), clip_ring(ring, mid, max_lng)]
+            .iter()
+            .filter(|strip| strip.len() >= 3)
+            .flat_map(|strip| ring_cells(strip, res))
+            .sorted()
+            .dedup()
+            .collect()
+    } else {
+        ring_cells(ring, res)
+    };
+
+    NonEmpty::from_vec(cells).unwrap_or_else(|| nonempty![ring[0].to_cell(res)])
+}
+
+// The disk centered on the vertices barycenter that contains the ring.
+// The edges are sampled every degree, as points along them can be
+// further from the center than their ends.
+fn enclosing_disk(ring: &[LatLng]) -> (LatLng, f64) {
+    let n = ring.len() as f64;
+    let center = LatLng::new(
+        ring.iter().map(|v| v.lat()).sum::<f64>() / n,
+        ring.iter().map(|v| v.lng()).sum::<f64>() / n,
+    )
+    .unwrap_or(ring[0]);
+    let radius = ring
+        .iter()
+        .zip(ring.iter().cycle().skip(1))
+        .flat_map(|(a, b)| {
+            let steps = (a.lat() - b.lat())
+                .abs()
+                .max((a.lng() - b.lng()).abs())
+                .ceil()
+                .max(1.0);
+            (0..steps as usize).filter_map(move |i| {
+                let t = i as f64 / steps;
+                LatLng::new(
+                    a.lat() + t * (b.lat() - a.lat()),
+                    a.lng() + t * (b.lng() - a.lng()),
+                )
+                .ok()
+            })
+        })
+        .map(|v| center.distance_m(v))
+        .fold(0.0, f64::max);
+    (center, radius)
+}
+
+// The cells at res sharing some area with a ring no wider than a half turn.
+fn ring_cells(ring: &[LatLng], res: Resolution) -> Vec<CellIndex> {
+    let (center, radius) = enclosing_disk(ring);
+    // With some margin, as the disk covering only keeps cells
+    // with their center within the radius.
+    let margin = res.edge_length_m() * 2.0;
+    disk_covering(center, Meters((radius + margin).ceil() as u64), res)
+        .into_iter()
+        .filter(|&cell| ring_intersects_cell(ring, cell))
+        .collect()
+}
+
+// The part of the ring between the min_lng and max_lng meridians,
+// clipped on the lat/lng plane one side after the other.
+fn clip_ring(ring: &[LatLng], min_lng: f64, max_lng: f64) -> Vec<LatLng> {
+    let clip = |ring: Vec<LatLng>, inside: &dyn Fn(f64) -> bool, lng: f64| {
+        let crossing = |a: LatLng, b: LatLng| {
+            let t = (lng - a.lng()) / (b.lng() - a.lng());
+            LatLng::new(a.lat() + t * (b.lat() - a.lat()), lng).expect("Between two lat/lngs")
+        };
+        let mut clipped = vec![];
+        for (&a, &b) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+            match (inside(a.lng()), inside(b.lng())) {
+                (true, true) => clipped.push(b),
+                (true, false) => clipped.push(crossing(a, b)),
+                (false, true) => clipped.extend([crossing(a, b), b]),
+                (false, false) => {}
+            }
+        }
+        clipped
+    };
+    let ring = clip(ring.to_vec(), &|lng| lng >= min_lng, min_lng);
+    clip(ring, &|lng| lng <= max_lng, max_lng)
+}
+
+/// Compacts a set of cells of the same resolution,
+/// replacing complete sets of children by their parent.
+/// The result can mix resolutions, and covers exactly the same cells.
+pub(crate) fn compact_cells(cells: NonEmpty<CellIndex>) -> NonEmpty<CellIndex> {
+    let mut compacted: Vec<_> = cells.iter().copied().collect();
+    match CellIndex::compact(&mut compacted) {
+        Ok(()) => NonEmpty::from_vec(compacted).unwrap_or(cells),
+        // Heterogeneous resolutions. Leave as is.
+        Err(_) => cells,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_compact_cells() {
+        let parent: CellIndex = "86194d107ffffff".parse().unwrap();
+        let children = NonEmpty::from_vec(parent.children(Resolution::Seven).collect()).unwrap();
+        assert_eq!(children.len(), 7);
+        assert_eq!(compact_cells(children.clone()), nonempty![parent]);
+
+        // An incomplete set of children stays the same.
+        let some_children = NonEmpty::from_vec(children.iter().copied().skip(1).collect()).unwrap();
+        assert_eq!(compact_cells(some_children.clone()).len(), 6);
+
+        // Mixed resolutions are left alone.
+        let mixed = nonempty![parent, children[0]];
+        assert_eq!(compact_cells(mixed.clone()), mixed);
+
+        // A disk covering at a fine resolution compacts to less cells.
+        let center = LatLng::new(48.864716, 2.349014).unwrap();
+        let disk = disk_covering(center, Meters(1000), Resolution::Eleven);
+        let compacted = compact_cells(disk.clone());
+        assert!(compacted.len() < disk.len());
+        assert!(
+            compacted
+                .iter()
+                .any(|c| c.resolution() < Resolution::Eleven)
+        );
+    }
+
+    #[test]
+    fn test_ring_contains() {
+        let ll = |lat, lng| LatLng::new(lat, lng).unwrap();
+        let square = vec![ll(0.0, 0.0), ll(0.0, 1.0), ll(1.0, 1.0), ll(1.0, 0.0)];
+        assert!(ring_contains(&square, ll(0.5, 0.5)));
+        assert!(ring_contains(&square, ll(0.01, 0.99)));
+        assert!(!ring_contains(&square, ll(1.5, 0.5)));
+        assert!(!ring_contains(&square, ll(-0.5, 0.5)));
+
+        // Closed rings work the same.
+        let mut closed = square.clone();
+        closed.push(square[0]);
+        assert!(ring_contains(&closed, ll(0.5, 0.5)));
+        assert!(!ring_contains(&closed, ll(1.5, 0.5)));
+
+        // A concave one. U shape.
+        let u = vec![
+            ll(0.0, 0.0),
+            ll(0.0, 3.0),
+            ll(3.0, 3.0),
+            ll(3.0, 2.0),
+            ll(1.0, 2.0),
+            ll(1.0, 1.0),
+            ll(3.0, 1.0),
+            ll(3.0, 0.0),
+        ];
+        assert!(ring_contains(&u, ll(2.0, 0.5)));
+        assert!(!ring_contains(&u, ll(2.0, 1.5)));
+        assert!(ring_contains(&u, ll(0.5, 1.5)));
+    }
+
+    #[test]
+    fn test_ring_covering() {
+        let ll = |lat, lng| LatLng::new(lat, lng).unwrap();
+        // Around Paris centre.
+        let ring = vec![
+            ll(48.85, 2.33),
+            ll(48.85, 2.36),
+            ll(48.87, 2.36),
+            ll(48.87, 2.33),
+        ];
+        let cells = ring_covering(&ring, Resolution::Fifteen);
+        let res = cells.first().resolution();
+        assert!(cells.iter().all(|c| c.resolution() == res));
+
+        // Any point inside the ring is in one of the cells.
+        for i in 0..=10 {
+            for j in 0..=10 {
+                let p = ll(48.85 + 0.002 * i as f64, 2.33 + 0.003 * j as f64);
+                assert!(cells.contains(&p.to_cell(res)));
+            }
+        }
+        // Far points are not.
+        assert!(!cells.contains(&ll(48.80, 2.33).to_cell(res)));
+
+        // Clamped resolution
+        let cells = ring_covering(&ring, Resolution::Five);
+        assert!(cells.iter().all(|c| c.resolution() == Resolution::Five));
+    }
+
+    #[test]
+    fn test_wide_ring_covering() {
+        let ll = |lat, lng| LatLng::new(lat, lng).unwrap();
+        let ring = vec![
+            ll(-60.0, -170.0),
+            ll(-60.0, 170.0),
+            ll(60.0, 170.0),
+            ll(60.0, -170.0),
+        ];
+        assert_eq!(
+            clip_ring(&ring, 0.0, 170.0),
+            vec![
+                ll(-60.0, 170.0),
+                ll(60.0, 170.0),
+                ll(60.0, 0.0),
+                ll(-60.0, 0.0)
+            ]
+        );
+
+        // Any point inside the ring is in one of the cells,
+        // even the ones far from the center.
+        let cells = ring_covering(&ring, Resolution::Fifteen);
+        let res = cells.first().resolution();
+        for lat in (-59..=59).step_by(2) {
+            for lng in (-169..=169).step_by(2) {
+                let p = ll(lat as f64, lng as f64);
+                assert!(cells.contains(&p.to_cell(res)), "{:?}", p);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_polygon_too_small() {
+        let ll = |lat, lng| LatLng::new(lat, lng).unwrap();
+        Polygon::new(vec![ll(0.0, 0.0), ll(0.0, 1.0)]);
+    }
+
     #[test]
     fn test_meters() {
         assert_eq!(Meters(0).to_string(), "0m");
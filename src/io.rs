@@ -0,0 +1,184 @@
+//! Readers streaming [`Document`]s out of files, for bulk testing
+//! and offline percolation runs.
+//!
+//! Both readers yield one `Result` per document, so a bad line or
+//! record can be reported and skipped without stopping the run.
+//!
+//! Example:
+//! ```
+//! use mokaccino::prelude::*;
+//! use mokaccino::io;
+//!
+//! let mut p = Percolator::default();
+//! let qid = p.add_query("colour".has_value("blue"));
+//!
+//! let csv = "colour,price\nblue,12\nred,5\n";
+//! let matches = io::csv_documents(csv.as_bytes())
+//!     .map(|d| p.percolate(&d.unwrap()).collect::<Vec<_>>())
+//!     .collect::<Vec<_>>();
+//! assert_eq!(matches, vec![vec![qid], vec![]]);
+//! ```
+
+use std::io::{BufRead, Read};
+
+use serde_json::{Map, Value};
+
+use crate::models::document::Document;
+
+/// The documents of CSV data, whose header row gives the field names.
+/// Empty cells give no value, so a document only has the fields
+/// filled in its record.
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+///
+/// let csv = "colour,price\nblue,12\n,5\n";
+/// let docs = mokaccino::io::csv_documents(csv.as_bytes())
+///     .collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+/// assert_eq!(
+///     docs,
+///     vec![
+///         [("colour", "blue"), ("price", "12")].into(),
+///         [("price", "5")].into()
+///     ]
+/// );
+/// ```
+pub fn csv_documents<R: Read>(reader: R) -> impl Iterator<Item = Result<Document, String>> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let (headers, mut error) = match reader.headers() {
+        Ok(h) => (h.clone(), None),
+        Err(e) => (csv::StringRecord::new(), Some(format!("CSV header: {}", e))),
+    };
+    // No records after a bad header row.
+    let mut records = error.is_none().then(|| reader.into_records());
+
+    std::iter::from_fn(move || {
+        if let Some(e) = error.take() {
+            return Some(Err(e));
+        }
+        let record = records.as_mut()?.next()?;
+        Some(record.map_err(|e| format!("CSV record: {}", e)).map(|r| {
+            headers
+                .iter()
+                .zip(r.iter())
+                .filter(|(_, v)| !v.is_empty())
+                .fold(Document::default(), |d, (f, v)| d.with_value(f, v))
+        }))
+    })
+}
+
+/// The documents of NDJSON data, one JSON object per line. Blank lines are skipped.
+///
+/// Strings, numbers and booleans are values, arrays give several values
+/// to their field, and `null`s give none. Objects, alone or in arrays, are
+/// nested elements, see [`Document::with_nested`].
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+///
+/// let ndjson = r#"{"colour": ["blue", "red"], "price": 12, "items": [{"sku": "A1"}]}
+///
+/// {"colour": "green", "price": null}
+/// "#;
+/// let docs = mokaccino::io::ndjson_documents(ndjson.as_bytes())
+///     .collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+/// assert_eq!(docs[0].values("colour"), vec!["blue".into(), "red".into()]);
+/// assert_eq!(docs[0].nested("items"), &[[("sku", "A1")].into()]);
+/// assert_eq!(docs[1], [("colour", "green")].into());
+///
+/// let errors = mokaccino::io::ndjson_documents("[1, 2]\n{\"a\":".as_bytes())
+///     .filter_map(Result::err)
+///     .collect::<Vec<_>>();
+/// assert_eq!(errors.len(), 2);
+/// ```
+pub fn ndjson_documents<R: BufRead>(reader: R) -> impl Iterator<Item = Result<Document, String>> {
+    reader
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| l.as_ref().map_or(true, |l| !l.trim().is_empty()))
+        .map(|(n, l)| {
+            let line = l.map_err(|e| format!("Line {}: {}", n + 1, e))?;
+            match serde_json::from_str(&line) {
+                Ok(Value::Object(o)) => Ok(from_object(o)),
+                Ok(_) => Err(format!("Line {}: not a JSON object", n + 1)),
+                Err(e) => Err(format!("Line {}: {}", n + 1, e)),
+            }
+        })
+}
+
+fn from_object(o: Map<String, Value>) -> Document {
+    o.into_iter()
+        .fold(Document::default(), |d, (f, v)| with_json_value(d, &f, v))
+}
+
+fn with_json_value(d: Document, field: &str, v: Value) -> Document {
+    match v {
+        Value::Null => d,
+        Value::String(s) => d.with_value(field, s),
+        Value::Number(n) => d.with_value(field, n.to_string()),
+        Value::Bool(b) => d.with_value(field, b.to_string()),
+        Value::Array(vs) => vs.into_iter().fold(d, |d, v| with_json_value(d, field, v)),
+        Value::Object(o) => d.with_nested(field, from_object(o)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_csv() {
+        let csv = "name,colour,colour\nbob,blue,red\n\"Smith, Alice\",,green\n";
+        let docs = csv_documents(csv.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            docs,
+            vec![
+                [("name", "bob"), ("colour", "blue"), ("colour", "red")].into(),
+                [("name", "Smith, Alice"), ("colour", "green")].into(),
+            ]
+        );
+
+        assert_eq!(csv_documents("".as_bytes()).count(), 0);
+        assert_eq!(csv_documents("name,colour\n".as_bytes()).count(), 0);
+        let results = csv_documents(&b"name,\xff\nbob,blue\n"[..]).collect::<Vec<_>>();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].as_ref().unwrap_err().starts_with("CSV header:"));
+
+        // Records must have as many cells as the header.
+        let results =
+            csv_documents("name,colour\nbob\nalice,blue\n".as_bytes()).collect::<Vec<_>>();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].as_ref().unwrap_err().starts_with("CSV record:"));
+        assert_eq!(
+            results[1],
+            Ok([("name", "alice"), ("colour", "blue")].into())
+        );
+    }
+
+    #[test]
+    fn test_ndjson() {
+        let ndjson = r#"{"a": true, "b": 1.5, "c": [1, [2, null]], "d": {"e": "f"}}
+
+"not an object"
+{"a":
+{}"#;
+        let results = ndjson_documents(ndjson.as_bytes()).collect::<Vec<_>>();
+        assert_eq!(results.len(), 4);
+        let d = results[0].as_ref().unwrap();
+        assert_eq!(d.values("a"), vec!["true".into()]);
+        assert_eq!(d.values("b"), vec!["1.5".into()]);
+        assert_eq!(d.values("c"), vec!["1".into(), "2".into()]);
+        assert_eq!(d.nested("d"), &[[("e", "f")].into()]);
+        assert_eq!(d.values("d.e"), vec!["f".into()]);
+
+        assert_eq!(results[1], Err("Line 3: not a JSON object".to_string()));
+        assert!(results[2].as_ref().unwrap_err().starts_with("Line 4:"));
+        assert_eq!(results[3], Ok(Document::default()));
+    }
+}
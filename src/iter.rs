@@ -0,0 +1,219 @@
+//! Lazy set operations on sorted iterators, like streams of ids.
+//!
+//! All input iterators must yield their items in strictly ascending order.
+//! The output is then in strictly ascending order too.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// The items present in all the given iterators.
+///
+/// It leapfrogs through the iterators, so it only
+/// reads as much of each as it needs.
+///
+/// Example:
+/// ```
+/// use mokaccino::iter::ConjunctionIterator;
+///
+/// let i = ConjunctionIterator::new(vec![
+///     vec![1, 3, 5, 7, 9].into_iter(),
+///     vec![3, 4, 5, 9, 12].into_iter(),
+///     vec![0, 3, 9].into_iter(),
+/// ]);
+/// assert_eq!(i.collect::<Vec<_>>(), vec![3, 9]);
+/// ```
+pub struct ConjunctionIterator<T, I>
+where
+    T: Ord,
+    I: Iterator<Item = T>,
+{
+    // Emptied as soon as one of them is exhausted.
+    iterators: Vec<I>,
+}
+
+impl<T, I> ConjunctionIterator<T, I>
+where
+    T: Ord,
+    I: Iterator<Item = T>,
+{
+    /// Constructor. The conjunction of no iterators is empty.
+    pub fn new(iterators: Vec<I>) -> Self {
+        Self { iterators }
+    }
+}
+
+impl<T, I> Iterator for ConjunctionIterator<T, I>
+where
+    T: Ord,
+    I: Iterator<Item = T>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let n = self.iterators.len();
+        let Some(mut candidate) = self.iterators.first_mut().and_then(|i| i.next()) else {
+            self.iterators.clear();
+            return None;
+        };
+
+        // How many iterators in a row are positioned on the candidate.
+        let mut agreeing = 1;
+        let mut i = 1 % n;
+        while agreeing < n {
+            match self.iterators[i].find(|v| *v >= candidate) {
+                None => {
+                    self.iterators.clear();
+                    return None;
+                }
+                Some(v) if v == candidate => agreeing += 1,
+                Some(v) => {
+                    candidate = v;
+                    agreeing = 1;
+                }
+            }
+            i = (i + 1) % n;
+        }
+        Some(candidate)
+    }
+}
+
+/// The items present in any of the given iterators, without duplicates.
+///
+/// Example:
+/// ```
+/// use mokaccino::iter::DisjunctionIterator;
+///
+/// let i = DisjunctionIterator::new(vec![
+///     vec![1, 5, 9].into_iter(),
+///     vec![3, 5, 12].into_iter(),
+///     vec![].into_iter(),
+/// ]);
+/// assert_eq!(i.collect::<Vec<_>>(), vec![1, 3, 5, 9, 12]);
+/// ```
+pub struct DisjunctionIterator<T, I>
+where
+    T: Ord,
+    I: Iterator<Item = T>,
+{
+    iterators: Vec<I>,
+    // The next item of each iterator, with the iterator's index.
+    heads: BinaryHeap<Reverse<(T, usize)>>,
+    started: bool,
+}
+
+impl<T, I> DisjunctionIterator<T, I>
+where
+    T: Ord,
+    I: Iterator<Item = T>,
+{
+    /// Constructor. Nothing is read from the iterators until
+    /// the first call to `next`.
+    pub fn new(iterators: Vec<I>) -> Self {
+        Self {
+            heads: BinaryHeap::with_capacity(iterators.len()),
+            iterators,
+            started: false,
+        }
+    }
+
+    fn advance(&mut self, i: usize) {
+        if let Some(v) = self.iterators[i].next() {
+            self.heads.push(Reverse((v, i)));
+        }
+    }
+}
+
+impl<T, I> Iterator for DisjunctionIterator<T, I>
+where
+    T: Ord,
+    I: Iterator<Item = T>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            (0..self.iterators.len()).for_each(|i| self.advance(i));
+        }
+
+        let Reverse((v, i)) = self.heads.pop()?;
+        self.advance(i);
+        // Skip the same item from the other iterators.
+        while self.heads.peek().is_some_and(|h| h.0.0 == v) {
+            if let Some(Reverse((_, j))) = self.heads.pop() {
+                self.advance(j);
+            }
+        }
+        Some(v)
+    }
+}
+
+#[cfg(test)]
+mod test_iter {
+    use super::*;
+    use rand::Rng;
+    use std::collections::BTreeSet;
+
+    fn random_sets<R: Rng>(rng: &mut R) -> Vec<BTreeSet<u32>> {
+        let n_sets = rng.random_range(0..5);
+        (0..n_sets)
+            .map(|_| {
+                let n = rng.random_range(0..30);
+                (0..n).map(|_| rng.random_range(0..40)).collect()
+            })
+            .collect()
+    }
+
+    fn iterators(sets: &[BTreeSet<u32>]) -> Vec<std::vec::IntoIter<u32>> {
+        sets.iter()
+            .map(|s| s.iter().copied().collect::<Vec<_>>().into_iter())
+            .collect()
+    }
+
+    #[test]
+    fn test_conjunction() {
+        let mut rng = rand::rng();
+        for _ in 0..1000 {
+            let sets = random_sets(&mut rng);
+            let expected: Vec<u32> = match sets.split_first() {
+                None => vec![],
+                Some((first, rest)) => first
+                    .iter()
+                    .copied()
+                    .filter(|v| rest.iter().all(|s| s.contains(v)))
+                    .collect(),
+            };
+            let mut i = ConjunctionIterator::new(iterators(&sets));
+            assert_eq!(i.by_ref().collect::<Vec<_>>(), expected, "{:?}", sets);
+            assert_eq!(i.next(), None);
+        }
+    }
+
+    #[test]
+    fn test_disjunction() {
+        let mut rng = rand::rng();
+        for _ in 0..1000 {
+            let sets = random_sets(&mut rng);
+            let expected: Vec<u32> = sets
+                .iter()
+                .flatten()
+                .copied()
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect();
+            let mut i = DisjunctionIterator::new(iterators(&sets));
+            assert_eq!(i.by_ref().collect::<Vec<_>>(), expected, "{:?}", sets);
+            assert_eq!(i.next(), None);
+        }
+    }
+
+    #[test]
+    fn test_laziness() {
+        // Works on infinite iterators.
+        let i = ConjunctionIterator::new(vec![(0u64..).step_by(2), (0..).step_by(3)]);
+        assert_eq!(i.take(3).collect::<Vec<_>>(), vec![0, 6, 12]);
+
+        let i = DisjunctionIterator::new(vec![(0..).step_by(2), (0..).step_by(3)]);
+        assert_eq!(i.take(6).collect::<Vec<_>>(), vec![0, 2, 3, 4, 6, 8]);
+    }
+}
@@ -103,10 +103,75 @@ pub(crate) fn fibo_ceil<T: PartialOrd + Fiboable>(n: T) -> T {
     }
 }
 
+/// The smallest of 0, 1, 2, 4, 8.. (or their negations)
+/// that is greater than or equal to x.
+/// This is the float equivalent of `fibo_ceil`, as floats
+/// can be way out of the i64 range.
+pub(crate) fn pow2_ceil(x: f64) -> f64 {
+    if x > 1.0 {
+        let b = 2f64.powi(x.log2().ceil() as i32);
+        // log2 can be slightly off for large values.
+        if b < x { b * 2.0 } else { b }
+    } else if x > 0.0 {
+        1.0
+    } else if x == 0.0 {
+        0.0
+    } else if x < 0.0 {
+        // Not just a negation, to avoid returning -0.0
+        0.0 - pow2_floor(-x)
+    } else {
+        x // NaN
+    }
+}
+
+/// The largest of 0, 1, 2, 4, 8.. (or their negations)
+/// that is lower than or equal to x.
+pub(crate) fn pow2_floor(x: f64) -> f64 {
+    if x >= 1.0 {
+        let b = 2f64.powi(x.log2().floor() as i32);
+        if b > x { b / 2.0 } else { b }
+    } else if x >= 0.0 {
+        0.0
+    } else if x < 0.0 {
+        0.0 - pow2_ceil(-x)
+    } else {
+        x // NaN
+    }
+}
+
 #[cfg(test)]
 mod test_itertools {
     use super::*;
 
+    #[test]
+    fn test_pow2_bounds() {
+        assert_eq!(pow2_ceil(0.0), 0.0);
+        assert_eq!(pow2_floor(0.0), 0.0);
+        assert_eq!(pow2_ceil(0.3), 1.0);
+        assert_eq!(pow2_floor(0.3), 0.0);
+        assert_eq!(pow2_ceil(1.0), 1.0);
+        assert_eq!(pow2_floor(1.0), 1.0);
+        assert_eq!(pow2_ceil(1.5), 2.0);
+        assert_eq!(pow2_floor(1.5), 1.0);
+        assert_eq!(pow2_ceil(8.0), 8.0);
+        assert_eq!(pow2_floor(8.0), 8.0);
+        assert_eq!(pow2_ceil(1000.25), 1024.0);
+        assert_eq!(pow2_floor(1000.25), 512.0);
+
+        assert_eq!(pow2_ceil(-0.3), 0.0);
+        assert!(pow2_ceil(-0.3).is_sign_positive());
+        assert_eq!(pow2_floor(-0.3), -1.0);
+        assert_eq!(pow2_ceil(-1000.25), -512.0);
+        assert_eq!(pow2_floor(-1000.25), -1024.0);
+
+        assert!(pow2_ceil(1e300) >= 1e300);
+        assert!(pow2_floor(1e300) <= 1e300);
+        assert_eq!(pow2_ceil(f64::INFINITY), f64::INFINITY);
+        assert_eq!(pow2_floor(f64::NEG_INFINITY), f64::NEG_INFINITY);
+        assert!(pow2_ceil(f64::NAN).is_nan());
+        assert!(pow2_floor(f64::NAN).is_nan());
+    }
+
     #[test]
     fn test_fibo_bounds() {
         // See https://www.math.net/list-of-fibonacci-numbers
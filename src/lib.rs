@@ -1,7 +1,12 @@
 #![doc = include_str!("../README.md")]
 
+pub mod bench;
 pub(crate) mod geotools;
+#[cfg(feature = "io")]
+pub mod io;
+pub mod iter;
 pub(crate) mod itertools;
+pub(crate) mod logging;
 pub mod models;
 pub mod prelude;
 pub mod testing;
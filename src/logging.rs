@@ -0,0 +1,14 @@
+// Warnings about queries or configurations that silently make
+// percolation slower or less precise. They go to the log crate
+// with the `log` feature, and nowhere otherwise.
+macro_rules! log_warn {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        log::warn!($($arg)+);
+        // Still type check the arguments.
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
+
+pub(crate) use log_warn;
@@ -1,7 +1,10 @@
+pub(crate) mod bytes;
 pub mod cnf;
 pub mod document;
 pub(crate) mod index;
 pub mod percolator;
 pub mod percolator_core;
+pub mod percolator_linear;
 pub(crate) mod queries;
+pub mod schema;
 pub(crate) mod types;
@@ -0,0 +1,79 @@
+//! Bytes values, like binary identifiers, are kept as strings in their
+//! unpadded URL safe base64 encoding: 4 characters for 3 bytes, against
+//! 6 in hexadecimal. Each sequence of bytes has a single encoding, so they
+//! are compared and indexed exactly like terms.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+pub(crate) fn encode_bytes(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity((bytes.len() * 4).div_ceil(3));
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (u32::from(*b) << (16 - 8 * i)));
+        // One more character than bytes in the chunk.
+        for i in 0..=chunk.len() {
+            s.push(char::from(ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize]));
+        }
+    }
+    s
+}
+
+fn sextet(c: u8) -> Option<u32> {
+    ALPHABET.iter().position(|a| *a == c).map(|p| p as u32)
+}
+
+// None for anything encode_bytes would not have written,
+// including non-zero trailing bits.
+pub(crate) fn decode_bytes(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(s.len() * 3 / 4);
+    for chunk in s.as_bytes().chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let n = chunk
+            .iter()
+            .enumerate()
+            .try_fold(0u32, |n, (i, c)| Some(n | (sextet(*c)? << (18 - 6 * i))))?;
+        let n_bytes = chunk.len() - 1;
+        if n & ((1 << (24 - 8 * n_bytes)) - 1) != 0 {
+            return None;
+        }
+        bytes.extend((0..n_bytes).map(|i| (n >> (16 - 8 * i)) as u8));
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trips() {
+        assert_eq!(encode_bytes(&[]), "");
+        assert_eq!(encode_bytes(b"M"), "TQ");
+        assert_eq!(encode_bytes(b"Ma"), "TWE");
+        assert_eq!(encode_bytes(b"Man"), "TWFu");
+        assert_eq!(encode_bytes(&[0xfb, 0xff]), "-_8");
+        assert_eq!(encode_bytes(&[0u8; 16]).len(), 22);
+
+        for len in 0..50 {
+            let bytes = (0..len).map(|i| (i * 37 + 11) as u8).collect::<Vec<_>>();
+            assert_eq!(decode_bytes(&encode_bytes(&bytes)), Some(bytes));
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(decode_bytes(""), Some(vec![]));
+        // Not base64.
+        assert_eq!(decode_bytes("TW=u"), None);
+        assert_eq!(decode_bytes("TWF+"), None);
+        // A single character is not a byte.
+        assert_eq!(decode_bytes("TWFuT"), None);
+        // Non canonical trailing bits.
+        assert_eq!(decode_bytes("TR"), None);
+        assert_eq!(decode_bytes("TWF"), None);
+    }
+}
@@ -1,37 +1,67 @@
+#[cfg(feature = "elasticsearch")]
+mod elasticsearch;
+#[cfg(feature = "geojson")]
+mod geojson;
 mod literal;
 pub mod parsing;
+pub mod patterns;
+#[cfg(feature = "sql")]
+mod sql;
+pub mod template;
 
 use literal::*;
 
-use crate::geotools::Meters;
+use crate::geotools::{Meters, Polygon};
+use crate::models::queries::latlng_between::LatLngBetweenQuery;
+use crate::models::queries::latlng_in_polygon::LatLngInPolygonQuery;
 use crate::models::queries::latlng_within::LatLngWithinQuery;
+use crate::models::queries::latlng_within_any::LatLngWithinAnyQuery;
 use crate::models::{
-    document::Document,
+    bytes::encode_bytes,
+    document::{Document, DocumentView, MATCH_ALL},
     index::{DocId, Index},
     queries::{
+        contains::ContainsQuery,
+        count::CountGeQuery,
+        exists::ExistsQuery,
         h3_inside::H3InsideQuery,
+        h3_inside_any::H3InsideAnyQuery,
+        ip_in::IpInQuery,
+        nested::NestedQuery,
         ordered::{OrderedQuery, Ordering},
         prefix::PrefixQuery,
+        range::RangeQuery,
+        regex::RegexQuery,
+        semver::SemverGeQuery,
+        str_range::StrRangeQuery,
+        suffix::SuffixQuery,
         term::TermQuery,
+        term_set::TermSetQuery,
+        text_match::{Analyzer, MatchQuery},
     },
 };
 
 //use fixedbitset::FixedBitSet;
 use h3o::{CellIndex, LatLng};
+use ipnet::IpNet;
 use itertools::Itertools;
+use regex::Regex;
 use roaring::MultiOps;
 
-use std::fmt;
+use std::hash::{DefaultHasher, Hasher};
+use std::ops::Bound;
+use std::{fmt, iter};
 
-use crate::models::types::OurStr;
+use crate::models::types::{OurRc, OurStr};
 
-#[derive(Debug, Clone, Eq, PartialEq, Default)]
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Clause {
     literals: Vec<Literal>,
 }
 
 impl Clause {
+    #[cfg(test)]
     pub(crate) fn from_termqueries(ts: Vec<TermQuery>) -> Self {
         Self {
             literals: ts
@@ -45,6 +75,7 @@ impl Clause {
         self.literals.iter().map(|l| l.cost()).sum()
     }
 
+    #[cfg(test)]
     fn term_queries_iter(&self) -> impl Iterator<Item = &TermQuery> {
         self.literals
             .iter()
@@ -59,15 +90,12 @@ impl Clause {
             .filter_map(|lq| lq.prefix_query())
     }
 
+    #[cfg(test)]
     pub(crate) fn add_termquery(&mut self, query: TermQuery) {
         self.literals
             .push(Literal::new(false, LitQuery::Term(query)));
     }
 
-    pub(crate) fn append_literals(&mut self, mut ls: Vec<Literal>) {
-        self.literals.append(&mut ls);
-    }
-
     /// The literals making this clause
     pub(crate) fn literals(&self) -> &[Literal] {
         &self.literals
@@ -89,9 +117,33 @@ impl Clause {
 
     /// Does this clause matches the given document?
     pub fn matches(&self, d: &Document) -> bool {
+        self.matches_view(d)
+    }
+
+    // Same, for any document.
+    pub(crate) fn matches_view(&self, d: &impl DocumentView) -> bool {
         self.literals.iter().any(|q| q.matches(d))
     }
 
+    // The highest weight of the literals matching the document, if any.
+    fn score(&self, d: &Document) -> Option<f32> {
+        self.literals
+            .iter()
+            .filter(|l| l.matches(d))
+            .map(|l| l.weight())
+            .reduce(f32::max)
+    }
+
+    // The (field, value)s of the document matching its literals.
+    fn highlights<'a>(&'a self, d: &'a Document) -> impl Iterator<Item = (OurStr, OurStr)> + 'a {
+        self.literals.iter().flat_map(move |l| {
+            let field = l.query().sort_field();
+            l.matching_values(d)
+                .into_iter()
+                .map(move |v| (field.clone(), v))
+        })
+    }
+
     /// Applies De Morgan's first law to produce a CNFQuery representing
     /// this negated Clause.
     pub fn negate(self) -> Query {
@@ -108,19 +160,50 @@ impl Clause {
             literals: self.literals.into_iter().unique().collect(),
         }
     }
+
+    // Writes this clause in the query parser syntax.
+    // Literals are written in stored order, which queries keep sorted.
+    fn write_to<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        match self.literals.as_slice() {
+            [] => w.write_str("MATCH_NONE"),
+            [l] => write!(w, "{}", l),
+            ls => {
+                w.write_char('(')?;
+                for (n, l) in ls.iter().enumerate() {
+                    if n > 0 {
+                        w.write_str(" OR ")?;
+                    }
+                    write!(w, "{}", l)?;
+                }
+                w.write_char(')')
+            }
+        }
+    }
 }
 
 impl fmt::Display for Clause {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "(OR {})",
-            self.literals
-                .iter()
-                .sorted()
-                .map(|l| l.to_string())
-                .join(" ")
-        )
+        self.write_to(f)
+    }
+}
+
+// Feeds everything written to it into a Hasher.
+struct HashWriter<H: Hasher>(H);
+
+impl<H: Hasher> fmt::Write for HashWriter<H> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+// Counts the bytes written, without keeping them.
+struct LenWriter(usize);
+
+impl fmt::Write for LenWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
     }
 }
 
@@ -152,16 +235,39 @@ impl fmt::Display for Clause {
 ///
 /// See also <https://www.cs.jhu.edu/~jason/tutorials/convert-to-CNF.html>
 ///
-#[derive(Debug, Default, Clone, PartialEq)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Queries keep their clauses and literals sorted, so queries built
+/// in different orders are equal, and hash, serialise and display the same.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "Vec<Clause>")
+)]
 pub struct Query(Vec<Clause>);
+
+/// A query shared between an application and percolators, to
+/// keep one copy of large query sets. This is an `Arc` with the
+/// `send` feature, and an `Rc` otherwise.
+/// See [`crate::prelude::Percolator::add_shared_query`]
+pub type SharedQuery = OurRc<Query>;
+
+// In canonical order.
+impl From<Vec<Clause>> for Query {
+    fn from(mut clauses: Vec<Clause>) -> Self {
+        for c in clauses.iter_mut() {
+            c.literals.sort();
+        }
+        clauses.sort();
+        Self(clauses)
+    }
+}
+// A Query displays in the query parser syntax, so it can be
+// parsed back. [`Query::match_all`] is `MATCH_ALL`, and
+// [`Query::match_none`] is `MATCH_NONE`.
 impl fmt::Display for Query {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "(AND {})",
-            self.0.iter().map(|c| c.to_string()).join(" ")
-        )
+        self.write_to(f)
     }
 }
 
@@ -169,21 +275,105 @@ impl std::str::FromStr for Query {
     type Err = String; // A newline delimited string, with all parsing errors.
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        use chumsky::Parser;
-        let p = parsing::query_parser();
-        p.parse(s)
-            .into_result()
-            .map_err(|e| {
-                e.iter()
-                    .map(|e| e.to_string())
-                    .collect::<Vec<_>>()
-                    .join("\n")
-            })
-            .map(|astq| astq.to_cnf())
+        Self::parse_with(s, &parsing::ParserOptions::default())
     }
 }
 
 impl Query {
+    /// Writes this query like `Display` does, without building
+    /// intermediate strings. Use this to log queries on hot paths.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let q = "colour".has_value("blue") & "taste".has_value("sweet");
+    /// let mut s = String::new();
+    /// q.write_to(&mut s).unwrap();
+    /// assert_eq!(s, "colour:blue AND taste:sweet");
+    /// ```
+    pub fn write_to<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        if self.0.is_empty() {
+            return w.write_str("MATCH_ALL");
+        }
+        for (n, c) in self.0.iter().enumerate() {
+            if n > 0 {
+                w.write_str(" AND ")?;
+            }
+            c.write_to(w)?;
+        }
+        Ok(())
+    }
+
+    /// A cheap identifier for this query, for logging.
+    /// This is the hash of its `Display` string, computed without
+    /// building it, so queries that display the same have the same digest.
+    /// It is stable across processes, but not across Rust releases.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let q1 = "colour".has_value("blue") | "taste".has_value("sweet");
+    /// let q2 = "taste".has_value("sweet") | "colour".has_value("blue");
+    /// assert_eq!(q1.digest(), q2.digest());
+    /// ```
+    pub fn digest(&self) -> u64 {
+        let mut w = HashWriter(DefaultHasher::new());
+        self.write_to(&mut w)
+            .expect("Writing to a hasher cannot fail");
+        w.0.finish()
+    }
+
+    // Roughly how many bytes this takes on the heap. The values
+    // count for the length of their text, like in `Display`.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        let mut w = LenWriter(0);
+        self.write_to(&mut w).expect("Counting bytes cannot fail");
+        self.0.capacity() * size_of::<Clause>()
+            + self
+                .0
+                .iter()
+                .map(|c| c.literals.capacity() * size_of::<Literal>())
+                .sum::<usize>()
+            + w.0
+    }
+
+    /// Parses a query like `FromStr` does, with the given options.
+    /// See [`parsing::ParserOptions`]
+    pub fn parse_with(s: &str, options: &parsing::ParserOptions) -> Result<Self, String> {
+        parsing::parse_ast(s, options).and_then(|astq| astq.to_cnf_checked(options))
+    }
+
+    /// Parses a query like [`Query::parse_with`], but recovers from errors
+    /// to give a query from what could be parsed, a list of errors and
+    /// a suggestion of a valid query string. Use it to help rule authors
+    /// fix their queries, not to index them blindly.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let options = parsing::ParserOptions::default();
+    /// let p = Query::parse_lenient("colour:blue AND (taste:\"sweet OR taste:bitter", &options);
+    /// assert!(!p.is_ok());
+    /// assert_eq!(
+    ///     p.errors(),
+    ///     &[
+    ///         "Missing closing double quote at 45".to_string(),
+    ///         "Missing closing parenthesis at 45".to_string()
+    ///     ]
+    /// );
+    /// assert_eq!(p.suggestion(), Some("( colour:blue AND taste:\"sweet OR taste:bitter\" )"));
+    /// assert_eq!(
+    ///     p.query(),
+    ///     Some(&("colour".has_value("blue") & "taste".has_value("sweet OR taste:bitter")))
+    /// );
+    /// ```
+    pub fn parse_lenient(s: &str, options: &parsing::ParserOptions) -> parsing::LenientParse {
+        parsing::parse_lenient(s, options)
+    }
+
     /// Builds a one term query from a T and U.
     /// Example:
     /// ```
@@ -215,6 +405,185 @@ impl Query {
         Self::from_prefixquery(PrefixQuery::new(field, value))
     }
 
+    /// Builds a suffix query from a T and U
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::Query;
+    ///
+    /// let q = Query::suffix("field", ".jpg");
+    /// ```
+    pub fn suffix<T, U>(field: T, value: U) -> Self
+    where
+        T: Into<OurStr>,
+        U: Into<OurStr>,
+    {
+        Self::from_literal(Literal::new(
+            false,
+            LitQuery::Suffix(SuffixQuery::new(field, value)),
+        ))
+    }
+
+    /// Builds a substring query from a T and U
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::Query;
+    ///
+    /// let q = Query::contains("field", "cat");
+    /// ```
+    pub fn contains<T, U>(field: T, value: U) -> Self
+    where
+        T: Into<OurStr>,
+        U: Into<OurStr>,
+    {
+        Self::from_literal(Literal::new(
+            false,
+            LitQuery::Contains(ContainsQuery::new(field, value)),
+        ))
+    }
+
+    // The single word queries of the analyzed text. With no words,
+    // like for a blank text, only the field is needed.
+    fn word_queries(field: OurStr, text: &str, analyzer: Analyzer) -> Option<Vec<Self>> {
+        let queries = analyzer
+            .tokens(text)
+            .into_iter()
+            .unique()
+            .map(|t| {
+                Self::from_literal(Literal::new(
+                    false,
+                    LitQuery::Match(MatchQuery::new(field.clone(), t, analyzer)),
+                ))
+            })
+            .collect_vec();
+        (!queries.is_empty()).then_some(queries)
+    }
+
+    /// Builds a free text query, matching documents where the field has all the words
+    /// of the text, as split by the analyzer. Each value of the field is analyzed the same way,
+    /// and the words can come from different values.
+    /// A text without any word matches documents having the field.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let q = Query::words("title", "Brown FOX", Analyzer::default());
+    /// assert!(q.matches(&[("title", "The quick brown fox")].into()));
+    /// assert!(!q.matches(&[("title", "The quick red fox")].into()));
+    ///
+    /// let q = Query::words("title", "Brown FOX", Analyzer::Whitespace);
+    /// assert!(!q.matches(&[("title", "The quick brown fox")].into()));
+    /// ```
+    pub fn words<T, U>(field: T, text: U, analyzer: Analyzer) -> Self
+    where
+        T: Into<OurStr>,
+        U: AsRef<str>,
+    {
+        let field = field.into();
+        Self::word_queries(field.clone(), text.as_ref(), analyzer)
+            .map_or_else(|| field.has_field(), Self::from_and)
+    }
+
+    /// Like [`Query::words`], but matching documents where the field has any of the words.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let q = Query::any_word("title", "red blue", Analyzer::default());
+    /// assert!(q.matches(&[("title", "Blue suede shoes")].into()));
+    /// assert!(!q.matches(&[("title", "Green onions")].into()));
+    /// ```
+    pub fn any_word<T, U>(field: T, text: U, analyzer: Analyzer) -> Self
+    where
+        T: Into<OurStr>,
+        U: AsRef<str>,
+    {
+        let field = field.into();
+        Self::word_queries(field.clone(), text.as_ref(), analyzer)
+            .map_or_else(|| field.has_field(), Self::from_or)
+    }
+
+    /// A Query matching documents where the field has at least `min` distinct values,
+    /// like at least 3 tags. Indexed by buckets of minimums, so it does not match
+    /// all the documents before checking them.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let q = Query::field_count_ge("tags", 2);
+    /// assert!(q.matches(&[("tags", "red"), ("tags", "blue")].into()));
+    /// assert!(!q.matches(&[("tags", "red"), ("tags", "red")].into()));
+    /// assert_eq!(Query::field_count_ge("tags", 1), "tags".has_field());
+    /// ```
+    pub fn field_count_ge<T: Into<OurStr>>(field: T, min: usize) -> Self {
+        match min {
+            1 => field.has_field(),
+            _ => Self::from_literal(Literal::new(
+                false,
+                LitQuery::CountGe(CountGeQuery::new(field, min)),
+            )),
+        }
+    }
+
+    /// A Query matching documents where the field is a semantic version at least
+    /// as recent as `version`, like `2.3.0`. Versions compare by precedence, so
+    /// "10.0.0" is after "9.0.0". Values that are not semantic versions, like "v2.3.0",
+    /// never match. Indexed by major version.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let q = Query::semver_ge("app_version", "2.3.0").unwrap();
+    /// assert!(q.matches(&[("app_version", "10.0.0")].into()));
+    /// assert!(!q.matches(&[("app_version", "2.3.0-beta")].into()));
+    /// assert!(Query::semver_ge("app_version", "2.3").is_err());
+    /// ```
+    pub fn semver_ge<T: Into<OurStr>>(field: T, version: &str) -> Result<Self, String> {
+        let version = semver::Version::parse(version)
+            .map_err(|e| format!("Invalid version '{}': {}", version, e))?;
+        let q = SemverGeQuery::new(field, version);
+        Ok(Self::from_literal(Literal::new(
+            false,
+            LitQuery::SemverGe(q),
+        )))
+    }
+
+    /// A Query matching documents with an element under the nested path
+    /// matching the whole query, whose fields are relative to the path.
+    /// Unlike a query on the flattened fields like `items.price`, the literals
+    /// cannot match different elements. See [`Document::with_nested`].
+    ///
+    /// Percolators check all the documents with elements under the path,
+    /// so use it next to more selective literals.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let cheap_blue = "colour".has_value("blue") & "price".i64_lt(10);
+    /// let q = Query::nested("items", cheap_blue);
+    /// let d = Document::default()
+    ///     .with_nested("items", [("colour", "blue"), ("price", "12")].into())
+    ///     .with_nested("items", [("colour", "red"), ("price", "5")].into());
+    /// assert!(!q.matches(&d));
+    /// assert!(("items.colour".has_value("blue") & "items.price".i64_lt(10)).matches(&d));
+    ///
+    /// let d = d.with_nested("items", [("colour", "blue"), ("price", "8")].into());
+    /// assert!(q.matches(&d));
+    /// ```
+    pub fn nested<T: Into<OurStr>>(path: T, query: Query) -> Self {
+        Self::from_literal(Literal::new(
+            false,
+            LitQuery::Nested(NestedQuery::new(path, query)),
+        ))
+    }
+
     /// A new CNFQuery from a plain TermQuery
     pub(crate) fn from_termquery(q: TermQuery) -> Self {
         Self::from_literal(Literal::new(false, LitQuery::Term(q)))
@@ -224,6 +593,10 @@ impl Query {
         Self::from_literal(Literal::new(false, LitQuery::Prefix(q)))
     }
 
+    pub(crate) fn from_regexquery(q: RegexQuery) -> Self {
+        Self::from_literal(Literal::new(false, LitQuery::Regex(q)))
+    }
+
     pub(crate) fn from_literal(l: Literal) -> Self {
         Self(vec![Clause { literals: vec![l] }])
     }
@@ -235,36 +608,225 @@ impl Query {
         Self::from_or(clause_negations.collect()).cleanse()
     }
 
+    /// Like [`Query::negation`], but fails as soon as the negation
+    /// has more than `max_clauses` clauses, without building them all.
+    /// Negating a conjunction distributes its clauses over each other.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let q = ("a".has_value("1") | "b".has_value("2")) & ("c".has_value("3") | "d".has_value("4"));
+    /// assert_eq!(Query::try_negation(q.clone(), 4), Ok(!q.clone()));
+    /// assert_eq!(
+    ///     Query::try_negation(q, 3),
+    ///     Err("Too many clauses. Maximum is 3".to_string())
+    /// );
+    /// ```
+    pub fn try_negation(q: Query, max_clauses: usize) -> Result<Self, String> {
+        let clause_negations = q.0.into_iter().map(|c| c.negate());
+        Self::try_from_or(clause_negations.collect(), max_clauses).map(Self::cleanse)
+    }
+
     fn cleanse(self) -> Self {
-        Self(self.0.into_iter().map(|c| c.cleanse()).collect())
+        Self::from(self.0.into_iter().map(|c| c.cleanse()).collect_vec())
+    }
+
+    /// The query matching all documents. It has no clauses,
+    /// so it is the neutral element of `&` and absorbs `|`.
+    /// Its negation is [`Query::match_none`].
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let filters = vec!["colour".has_value("blue"), "taste".has_value("sweet")];
+    /// let q = filters.into_iter().fold(Query::match_all(), |acc, f| acc & f);
+    /// assert_eq!(q, "colour".has_value("blue") & "taste".has_value("sweet"));
+    ///
+    /// assert!(Query::match_all().matches(&Document::default()));
+    /// assert_eq!(!Query::match_all(), Query::match_none());
+    /// ```
+    pub fn match_all() -> Self {
+        Self(vec![])
+    }
+
+    /// The query matching no document. It has a single empty clause,
+    /// so it is the neutral element of `|` and absorbs `&`.
+    /// Its negation is [`Query::match_all`].
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let filters = vec!["colour".has_value("blue"), "taste".has_value("sweet")];
+    /// let q = filters.into_iter().fold(Query::match_none(), |acc, f| acc | f);
+    /// assert_eq!(q, "colour".has_value("blue") | "taste".has_value("sweet"));
+    ///
+    /// assert!(!Query::match_none().matches(&[("colour", "blue")].into()));
+    /// assert_eq!(!Query::match_none(), Query::match_all());
+    /// ```
+    pub fn match_none() -> Self {
+        Self(vec![Clause::default()])
     }
 
     /// conjunction of all the given CNFQueries
     pub fn from_and(qs: Vec<Query>) -> Self {
-        Self(qs.into_iter().flat_map(|q| q.0).collect())
+        Self::from(qs.into_iter().flat_map(|q| q.0).collect_vec())
     }
 
     /// Disjunction of all the given CNFQueries
     /// Applies distributivity of Conjunctions over disjunctions
     /// <https://proofwiki.org/wiki/Rule_of_Distribution#Conjunction_Distributes_over_Disjunction>g
     pub fn from_or(qs: Vec<Query>) -> Self {
-        // Combine all CNF queries into a single CNF query
-        Self(
-            qs.into_iter()
-                .map(|q| q.0.into_iter())
-                .multi_cartesian_product()
-                .map(|cs| {
-                    // Combine the clauses into one
-                    Clause::from_clauses(cs)
-                })
-                .collect(),
-        )
+        Self::from(Self::or_clauses(qs).collect_vec())
+    }
+
+    /// Like [`Query::from_or`], but fails as soon as the disjunction
+    /// has more than `max_clauses` clauses, without building them all.
+    /// The number of clauses is the product of the numbers of clauses
+    /// of the queries, so it grows quickly.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let two = "a".has_value("1") & "b".has_value("2");
+    /// assert!(Query::try_from_or(vec![two.clone(), two.clone()], 4).is_ok());
+    /// assert_eq!(
+    ///     Query::try_from_or(vec![two.clone(), two.clone(), two], 4),
+    ///     Err("Too many clauses. Maximum is 4".to_string())
+    /// );
+    /// ```
+    pub fn try_from_or(qs: Vec<Query>, max_clauses: usize) -> Result<Self, String> {
+        let clauses = Self::or_clauses(qs)
+            .take(max_clauses.saturating_add(1))
+            .collect_vec();
+        if clauses.len() > max_clauses {
+            return Err(format!("Too many clauses. Maximum is {}", max_clauses));
+        }
+        Ok(Self::from(clauses))
+    }
+
+    // The clauses of the disjunction, one at a time.
+    // The disjunction of no queries is false, so the single empty clause.
+    fn or_clauses(qs: Vec<Query>) -> impl Iterator<Item = Clause> {
+        let none = qs.is_empty().then(Clause::default);
+        qs.into_iter()
+            .map(|q| q.0.into_iter())
+            .multi_cartesian_product()
+            // Combine the clauses into one
+            .map(Clause::from_clauses)
+            .chain(none)
     }
 
     ///
     /// Does this query match a document?
     pub fn matches(&self, d: &Document) -> bool {
-        self.0.iter().all(|c| c.matches(d))
+        self.matches_view(d)
+    }
+
+    // Same, for any document, owned or borrowed.
+    pub(crate) fn matches_view(&self, d: &impl DocumentView) -> bool {
+        self.0.iter().all(|c| c.matches_view(d))
+    }
+
+    /// This query with the weights of all its literals multiplied by the given weight.
+    /// Literals weigh 1 unless boosted, like with `field:value^2.5` in the query parser.
+    /// Weights should be positive.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let q = "colour".has_value("blue").boost(2.5) | "colour".has_value("green");
+    /// assert_eq!(q, "colour:blue^2.5 OR colour:green".parse().unwrap());
+    /// ```
+    pub fn boost(self, weight: f32) -> Self {
+        Self::from(
+            self.0
+                .into_iter()
+                .map(|c| Clause {
+                    literals: c
+                        .literals
+                        .into_iter()
+                        .map(|l| {
+                            let w = l.weight() * weight;
+                            l.with_weight(w)
+                        })
+                        .collect(),
+                })
+                .collect_vec(),
+        )
+    }
+
+    /// How well the document matches this query, or None if it does not match.
+    /// This is the sum over the clauses of the highest weight of their matching literals,
+    /// so it is the number of clauses without boosts.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let q: Query = "(colour:blue^2 OR taste:sweet^3) AND shape:round".parse().unwrap();
+    /// assert_eq!(q.score(&[("colour", "blue"), ("shape", "round")].into()), Some(3.0));
+    /// let d = [("colour", "blue"), ("taste", "sweet"), ("shape", "round")].into();
+    /// assert_eq!(q.score(&d), Some(4.0));
+    /// assert_eq!(q.score(&[("shape", "round")].into()), None);
+    /// ```
+    pub fn score(&self, d: &Document) -> Option<f32> {
+        self.0.iter().map(|c| c.score(d)).sum()
+    }
+
+    /// The (field, value)s of the document satisfying the positive literals
+    /// of this query, in lexicographic order. Empty if it does not match.
+    /// Negated literals are satisfied by the document not having things,
+    /// so they give none.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let q: Query = "(colour:blue OR taste:sw*) AND NOT shape:round".parse().unwrap();
+    /// let d = [("colour", "blue"), ("taste", "sweet"), ("taste", "sour")].into();
+    /// assert_eq!(
+    ///     q.highlights(&d),
+    ///     vec![
+    ///         ("colour".to_string(), "blue".to_string()),
+    ///         ("taste".to_string(), "sweet".to_string())
+    ///     ]
+    /// );
+    /// assert!(q.highlights(&[("shape", "round")].into()).is_empty());
+    /// ```
+    pub fn highlights(&self, d: &Document) -> Vec<(String, String)> {
+        if !self.matches(d) {
+            return vec![];
+        }
+        self.0
+            .iter()
+            .flat_map(|c| c.highlights(d))
+            .map(|(f, v)| (f.to_string(), v.to_string()))
+            .sorted()
+            .dedup()
+            .collect()
+    }
+
+    // This query with its plain term queries matching the trimmed
+    // and case folded values, if it has any.
+    pub(crate) fn with_normalized_terms(&self) -> Option<Self> {
+        let normalized = Self::from(
+            self.0
+                .iter()
+                .map(|c| Clause {
+                    literals: c
+                        .literals
+                        .iter()
+                        .map(|l| l.with_normalized_term())
+                        .collect(),
+                })
+                .collect_vec(),
+        );
+        (normalized != *self).then_some(normalized)
     }
 
     /// The clauses of this CNFQuery
@@ -287,25 +849,215 @@ impl Query {
     pub(crate) fn prefix_queries(&self) -> impl Iterator<Item = &PrefixQuery> {
         self.0.iter().flat_map(|c| c.prefix_queries_iter())
     }
+
+    // The fields of all the literals, synthetic ones aside.
+    pub(crate) fn fields(&self) -> impl Iterator<Item = OurStr> + '_ {
+        self.0
+            .iter()
+            .flat_map(|c| c.literals())
+            .flat_map(|l| match l.query() {
+                LitQuery::Nested(nq) => nq.fields(),
+                q => vec![q.sort_field()],
+            })
+            .filter(|f| **f != *MATCH_ALL.0)
+    }
+
+    // The fields compared as integers.
+    pub(crate) fn int_fields(&self) -> impl Iterator<Item = OurStr> + '_ {
+        self.0
+            .iter()
+            .flat_map(|c| c.literals())
+            .filter_map(|l| l.query().int_field())
+    }
 }
 
 pub trait CNFQueryable: Into<OurStr> {
     /// A Query where `"field".has_value("the_value")``
     fn has_value<T: Into<OurStr>>(self, v: T) -> Query;
 
+    /// A Query where the field has any of the values.
+    /// Unlike `has_value(a) | has_value(b)`, this is a single literal,
+    /// however many values there are.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let q = "colour".has_any_value(["red", "blue"]);
+    /// assert_eq!(q.to_string(), "colour IN {blue, red}");
+    /// assert!(q.matches(&[("colour", "red")].into()));
+    /// assert_eq!("colour".has_any_value(["red"]), "colour".has_value("red"));
+    /// ```
+    fn has_any_value<T: Into<OurStr>>(self, vs: impl IntoIterator<Item = T>) -> Query;
+
+    /// A Query where the field has exactly these bytes, like a binary
+    /// identifier added with [`Document::with_bytes`]. This is a term query
+    /// on their unpadded URL safe base64 encoding, so it is as fast.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let id = [0x12, 0x34, 0x56, 0x78];
+    /// let q = "id".has_bytes(&id);
+    /// assert_eq!(q, "id".has_value("EjRWeA"));
+    /// assert!(q.matches(&Document::default().with_bytes("id", &id)));
+    /// assert!(!q.matches(&Document::default().with_bytes("id", &id[..3])));
+    /// ```
+    fn has_bytes(self, bytes: &[u8]) -> Query;
+
     /// A Query where `"field".has_prefix("/some/prefix")`
     fn has_prefix<T: Into<OurStr>>(self, v: T) -> Query;
 
+    /// A Query where `"field".has_value_ci("Blue")`, case insensitive, like `field:~Blue`.
+    /// Both the value and the document values are compared
+    /// with Unicode case folding.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let q = "colour".has_value_ci("Blue");
+    /// assert_eq!(q.to_string(), "colour:~blue");
+    /// assert!(q.matches(&[("colour", "BLUE")].into()));
+    /// assert!(!q.matches(&[("colour", "blues")].into()));
+    /// ```
+    fn has_value_ci<T: Into<OurStr>>(self, v: T) -> Query;
+
+    /// A Query where `"field".has_prefix_ci("/Some/Prefix")`, case insensitive
+    /// like [`CNFQueryable::has_value_ci`]. Same as `field:~/Some/Prefix*`.
+    fn has_prefix_ci<T: Into<OurStr>>(self, v: T) -> Query;
+
+    /// A Query where `"field".has_suffix(".jpg")`.
+    /// Indexed like a prefix of the reversed values.
+    fn has_suffix<T: Into<OurStr>>(self, v: T) -> Query;
+
+    /// A Query where `"field".has_substring("cat")`, like `field:*cat*`.
+    /// Indexed with the n-grams of the values.
+    /// See [`crate::models::percolator_core::PercolatorConfig::ngram_size`]
+    fn has_substring<T: Into<OurStr>>(self, v: T) -> Query;
+
+    /// A Query where `"field".has_words("Quick fox")`: the field has all the words, whatever
+    /// their case and order. See [`Query::words`] to split the words differently.
+    fn has_words<T: AsRef<str>>(self, text: T) -> Query;
+
+    /// A Query where `"field".has_any_word("red blue")`: the field has any of the words,
+    /// whatever their case. See [`Query::any_word`]
+    fn has_any_word<T: AsRef<str>>(self, text: T) -> Query;
+
+    /// A Query where `"field".has_field()`, whatever its value.
+    fn has_field(self) -> Query;
+
+    /// A Query where `"field".lacks_field()`. Same as `!"field".has_field()`.
+    fn lacks_field(self) -> Query;
+
+    /// A Query where `"field".has_regex(&Regex::new("foo.*bar").unwrap())`.
+    /// The regex must match the whole value.
+    fn has_regex(self, regex: &Regex) -> Query;
+
     /// A Query where the field represents an H3 cell index
     /// that is contained within the given `cell`.
     /// Use this for geographic queries.
     fn h3in(self, cell: CellIndex) -> Query;
 
+    /// A Query where the field represents an H3 cell index
+    /// that is contained within any of the given `cells`, for instance
+    /// a compacted covering of an area. This is one literal, however
+    /// many cells there are.
+    ///
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let cells = ["87194d106ffffff", "86194d107ffffff"].map(|c| c.parse().unwrap());
+    /// let q = "location".h3in_any(&cells);
+    /// assert!(q.matches(&[("location", "89194d10693ffff")].into()));
+    /// assert!(!q.matches(&[("location", "87195d106ffffff")].into()));
+    /// assert_eq!("location".h3in_any(&cells[..1]), "location".h3in(cells[0]));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If there are no `cells`.
+    fn h3in_any(self, cells: &[CellIndex]) -> Query;
+
     /// A Query where the field represents a `h3o::coord::latlng`
     /// ( for instance 54.35499723397377,18.662987684795226 )
     /// with must be in a disk defined by `center` and `radius`.
     fn latlng_within(self, center: LatLng, radius: Meters) -> Query;
 
+    /// A Query where the field represents a `h3o::coord::latlng`
+    /// which must be in any of the disks defined by a center and a radius,
+    /// for instance within 500m of any of a set of shops. This is one
+    /// literal, however many disks there are.
+    ///
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use h3o::LatLng;
+    ///
+    /// let shops = [
+    ///     (LatLng::new(48.864716, 2.349014).unwrap(), Meters(500)),
+    ///     (LatLng::new(48.85, 2.29).unwrap(), Meters(500)),
+    /// ];
+    /// let q = "position".latlng_within_any(&shops);
+    /// assert!(q.matches(&[("position", "48.8501,2.2901")].into()));
+    /// assert!(!q.matches(&[("position", "48.88,2.37")].into()));
+    /// assert_eq!(
+    ///     "position".latlng_within_any(&shops[..1]),
+    ///     "position".latlng_within(shops[0].0, shops[0].1)
+    /// );
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If there are no `disks`.
+    fn latlng_within_any(self, disks: &[(LatLng, Meters)]) -> Query;
+
+    /// A Query where the field represents a `h3o::coord::latlng`
+    /// which must be in the ring around `center`: further than `min_radius`
+    /// from it, and within `max_radius` of it.
+    ///
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use h3o::LatLng;
+    ///
+    /// let center = LatLng::new(48.864716, 2.349014).unwrap();
+    /// let q = "position".latlng_between(center, Meters(1000), Meters(3000));
+    /// assert!(q.matches(&[("position", "48.88,2.37")].into()));
+    /// assert!(!q.matches(&[("position", "48.864716,2.349014")].into()));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `min_radius` is not less than `max_radius`.
+    fn latlng_between(self, center: LatLng, min_radius: Meters, max_radius: Meters) -> Query;
+
+    /// A Query where the field represents a `h3o::coord::latlng`
+    /// which must be inside the box from the south west corner `min`
+    /// to the north east corner `max`. A box where `min` is east of `max`
+    /// crosses the antimeridian.
+    ///
+    /// # Panics
+    ///
+    /// If `min` is north of `max`.
+    fn latlng_in_bbox(self, min: LatLng, max: LatLng) -> Query;
+
+    /// A Query where the field represents a `h3o::coord::latlng`
+    /// which must be inside the `polygon`, but not inside any of its holes.
+    fn latlng_in_polygon(self, polygon: &Polygon) -> Query;
+
+    /// A Query where the field represents a `h3o::coord::latlng`
+    /// which must be inside any of the `polygons`.
+    ///
+    /// # Panics
+    ///
+    /// If there are no polygons.
+    fn latlng_in_multipolygon(self, polygons: &[Polygon]) -> Query;
+
+    /// A Query where the field represents an IPv4 or IPv6 address
+    /// in the network `net`, like `"client_ip".ip_in("10.0.0.0/8".parse().unwrap())`.
+    /// The host bits of `net` are ignored.
+    fn ip_in(self, net: IpNet) -> Query;
+
     /// A query where the field can represents a signed integer
     /// that has a value strictly lower than `v`.
     fn i64_lt(self, v: i64) -> Query;
@@ -321,6 +1073,109 @@ pub trait CNFQueryable: Into<OurStr> {
     /// A query where the field can represents a signed integer
     /// that has a value strictly greater than `v`.
     fn i64_gt(self, v: i64) -> Query;
+
+    /// A query where the field can represents an unsigned integer
+    /// that has a value strictly lower than `v`.
+    ///
+    /// Values are compared as 128 bits integers, so the whole
+    /// u64 range is covered, like with ids or nanosecond timestamps.
+    ///
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let q = "id".u64_lt(u64::MAX);
+    /// assert!(q.matches(&Document::new().with_value("id", (u64::MAX - 1).to_string())));
+    /// assert!(!q.matches(&Document::new().with_value("id", u64::MAX.to_string())));
+    /// ```
+    fn u64_lt(self, v: u64) -> Query;
+    /// A query where the field can represents an unsigned integer
+    /// that has a value lower than or equal to `v`.
+    fn u64_le(self, v: u64) -> Query;
+    /// A query where the field can represents an unsigned integer
+    /// that has a value equal to `v`.
+    fn u64_eq(self, v: u64) -> Query;
+    /// A query where the field can represents an unsigned integer
+    /// that has a value greater than or equal to `v`.
+    fn u64_ge(self, v: u64) -> Query;
+    /// A query where the field can represents an unsigned integer
+    /// that has a value strictly greater than `v`.
+    fn u64_gt(self, v: u64) -> Query;
+
+    /// A query where the field can represents a signed integer
+    /// that has a value strictly lower than `v`.
+    /// Like `i64_lt`, for values out of the i64 range.
+    fn i128_lt(self, v: i128) -> Query;
+    /// A query where the field can represents a signed integer
+    /// that has a value lower than or equal to `v`.
+    fn i128_le(self, v: i128) -> Query;
+    /// A query where the field can represents a signed integer
+    /// that has a value equal to `v`.
+    fn i128_eq(self, v: i128) -> Query;
+    /// A query where the field can represents a signed integer
+    /// that has a value greater than or equal to `v`.
+    fn i128_ge(self, v: i128) -> Query;
+    /// A query where the field can represents a signed integer
+    /// that has a value strictly greater than `v`.
+    fn i128_gt(self, v: i128) -> Query;
+
+    /// A query where the field can represents a floating point number
+    /// that has a value strictly lower than `v`.
+    fn f64_lt(self, v: f64) -> Query;
+    /// A query where the field can represents a floating point number
+    /// that has a value lower than or equal to `v`.
+    fn f64_le(self, v: f64) -> Query;
+    /// A query where the field can represents a floating point number
+    /// that has a value equal to `v`.
+    fn f64_eq(self, v: f64) -> Query;
+    /// A query where the field can represents a floating point number
+    /// that has a value greater than or equal to `v`.
+    fn f64_ge(self, v: f64) -> Query;
+    /// A query where the field can represents a floating point number
+    /// that has a value strictly greater than `v`.
+    fn f64_gt(self, v: f64) -> Query;
+
+    /// A query where the field can represents a signed integer
+    /// that has a value between `low` and `high`, both included.
+    /// Unlike `i64_ge(low) & i64_le(high)`, this is a single literal.
+    fn i64_between(self, low: i64, high: i64) -> Query;
+    /// A query where the field can represents a floating point number
+    /// that has a value between `low` and `high`, both included.
+    fn f64_between(self, low: f64, high: f64) -> Query;
+
+    /// A query where the field can represents a signed integer
+    /// within the bounds. With both ends bounded, this is a single literal,
+    /// like [`CNFQueryable::i64_between`].
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use std::ops::Bound;
+    ///
+    /// let q = "price".i64_range(Bound::Excluded(10), Bound::Included(100));
+    /// assert_eq!(q, "price".i64_between(11, 100));
+    /// assert_eq!("price".i64_range(Bound::Excluded(10), Bound::Unbounded), "price".i64_gt(10));
+    /// ```
+    fn i64_range(self, low: Bound<i64>, high: Bound<i64>) -> Query;
+    /// A query where the field can represents a floating point number
+    /// within the bounds. Excluded bounds are turned into the
+    /// next included floating point numbers.
+    fn f64_range(self, low: Bound<f64>, high: Bound<f64>) -> Query;
+
+    /// A query where the field is between the strings `low` and `high`, both included.
+    /// Strings compare by Unicode code point, so this is case sensitive, and `"fa"`
+    /// is after `"f"`. Documents are checked against the bounds when preheated, so
+    /// candidates need no final check.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let q = "name".str_between("a", "f");
+    /// assert!(q.matches(&[("name", "carol")].into()));
+    /// assert!(q.matches(&[("name", "f")].into()));
+    /// assert!(!q.matches(&[("name", "frank")].into()));
+    /// ```
+    fn str_between<U: Into<OurStr>, V: Into<OurStr>>(self, low: U, high: V) -> Query;
 }
 
 impl<T> CNFQueryable for T
@@ -332,21 +1187,151 @@ where
         Query::from_termquery(tq)
     }
 
+    fn has_any_value<U: Into<OurStr>>(self, vs: impl IntoIterator<Item = U>) -> Query {
+        let tsq = TermSetQuery::new(self, vs);
+        match tsq.terms() {
+            [t] => Query::from_termquery(TermQuery::new(tsq.field(), t.clone())),
+            _ => Query::from_literal(Literal::new(false, LitQuery::TermSet(tsq))),
+        }
+    }
+
+    fn has_bytes(self, bytes: &[u8]) -> Query {
+        self.has_value(encode_bytes(bytes))
+    }
+
     fn has_prefix<U: Into<OurStr>>(self, v: U) -> Query {
         let pq = PrefixQuery::new(self, v);
         Query::from_prefixquery(pq)
     }
 
+    fn has_value_ci<U: Into<OurStr>>(self, v: U) -> Query {
+        Query::from_termquery(TermQuery::case_folded(self, v.into()))
+    }
+
+    fn has_prefix_ci<U: Into<OurStr>>(self, v: U) -> Query {
+        Query::from_prefixquery(PrefixQuery::case_folded(self, v.into()))
+    }
+
+    fn has_suffix<U: Into<OurStr>>(self, v: U) -> Query {
+        Query::suffix(self, v)
+    }
+
+    fn has_substring<U: Into<OurStr>>(self, v: U) -> Query {
+        Query::contains(self, v)
+    }
+
+    fn has_words<U: AsRef<str>>(self, text: U) -> Query {
+        Query::words(self, text, Analyzer::default())
+    }
+
+    fn has_any_word<U: AsRef<str>>(self, text: U) -> Query {
+        Query::any_word(self, text, Analyzer::default())
+    }
+
+    fn has_field(self) -> Query {
+        let eq = ExistsQuery::new(self);
+        Query::from_literal(Literal::new(false, LitQuery::Exists(eq)))
+    }
+
+    fn lacks_field(self) -> Query {
+        !self.has_field()
+    }
+
+    fn has_regex(self, regex: &Regex) -> Query {
+        // A valid regex stays valid once anchored.
+        let rq = RegexQuery::new(self, regex.as_str()).expect("Valid regex");
+        Query::from_regexquery(rq)
+    }
+
     fn h3in(self, cell: CellIndex) -> Query {
         let q = H3InsideQuery::new(self, cell);
         Query::from_literal(Literal::new(false, LitQuery::H3Inside(q)))
     }
 
+    fn h3in_any(self, cells: &[CellIndex]) -> Query {
+        assert!(!cells.is_empty(), "At least one cell");
+        let q = H3InsideAnyQuery::new(self, cells.iter().copied());
+        match q.cells() {
+            [cell] => q.field().h3in(*cell),
+            _ => Query::from_literal(Literal::new(false, LitQuery::H3InsideAny(q))),
+        }
+    }
+
     fn latlng_within(self, center: LatLng, radius: Meters) -> Query {
         let q = LatLngWithinQuery::new(self, center, radius);
         Query::from_literal(Literal::new(false, LitQuery::LatLngWithin(q)))
     }
 
+    fn latlng_within_any(self, disks: &[(LatLng, Meters)]) -> Query {
+        match disks {
+            [] => panic!("At least one disk"),
+            [(center, radius)] => self.latlng_within(*center, *radius),
+            _ => {
+                let q = LatLngWithinAnyQuery::new(self, disks);
+                Query::from_literal(Literal::new(false, LitQuery::LatLngWithinAny(q)))
+            }
+        }
+    }
+
+    fn latlng_between(self, center: LatLng, min_radius: Meters, max_radius: Meters) -> Query {
+        assert!(
+            min_radius.0 < max_radius.0,
+            "min_radius must be less than max_radius"
+        );
+        let q = LatLngBetweenQuery::new(self, center, min_radius, max_radius);
+        Query::from_literal(Literal::new(false, LitQuery::LatLngBetween(q)))
+    }
+
+    fn latlng_in_bbox(self, min: LatLng, max: LatLng) -> Query {
+        assert!(min.lat() <= max.lat(), "min must be south of max");
+        // A box is a polygon on the lat/lng plane.
+        let bbox = |min_lng: f64, max_lng: f64| {
+            let ll = |lat, lng| LatLng::new(lat, lng).expect("Valid box corner");
+            Polygon::new(vec![
+                ll(min.lat(), min_lng),
+                ll(min.lat(), max_lng),
+                ll(max.lat(), max_lng),
+                ll(max.lat(), min_lng),
+            ])
+        };
+        if min.lng() <= max.lng() {
+            self.latlng_in_polygon(&bbox(min.lng(), max.lng()))
+        } else {
+            // Split on both sides of the antimeridian.
+            self.latlng_in_multipolygon(&[bbox(min.lng(), 180.0), bbox(-180.0, max.lng())])
+        }
+    }
+
+    fn latlng_in_polygon(self, polygon: &Polygon) -> Query {
+        let field: OurStr = self.into();
+        let in_ring = |ring: &[LatLng]| {
+            let q = LatLngInPolygonQuery::new(field.clone(), ring.to_vec());
+            Query::from_literal(Literal::new(false, LitQuery::LatLngInPolygon(q)))
+        };
+        // Inside the exterior AND NOT inside any hole.
+        Query::from_and(
+            iter::once(in_ring(polygon.exterior()))
+                .chain(polygon.holes().iter().map(|h| !in_ring(h)))
+                .collect(),
+        )
+    }
+
+    fn latlng_in_multipolygon(self, polygons: &[Polygon]) -> Query {
+        assert!(!polygons.is_empty(), "A multipolygon needs polygons");
+        let field: OurStr = self.into();
+        Query::from_or(
+            polygons
+                .iter()
+                .map(|p| field.clone().latlng_in_polygon(p))
+                .collect(),
+        )
+    }
+
+    fn ip_in(self, net: IpNet) -> Query {
+        let q = IpInQuery::new(self, net);
+        Query::from_literal(Literal::new(false, LitQuery::IpIn(q)))
+    }
+
     fn i64_lt(self, v: i64) -> Query {
         let q = OrderedQuery::<i64>::new(self, v, Ordering::LT);
         Query::from_literal(Literal::new(false, LitQuery::IntQuery(q)))
@@ -371,6 +1356,144 @@ where
         let q = OrderedQuery::<i64>::new(self, v, Ordering::GT);
         Query::from_literal(Literal::new(false, LitQuery::IntQuery(q)))
     }
+
+    fn u64_lt(self, v: u64) -> Query {
+        let q = OrderedQuery::<i128>::new(self, i128::from(v), Ordering::LT);
+        Query::from_literal(Literal::new(false, LitQuery::BigIntQuery(q)))
+    }
+
+    fn u64_le(self, v: u64) -> Query {
+        let q = OrderedQuery::<i128>::new(self, i128::from(v), Ordering::LE);
+        Query::from_literal(Literal::new(false, LitQuery::BigIntQuery(q)))
+    }
+
+    fn u64_eq(self, v: u64) -> Query {
+        let q = OrderedQuery::<i128>::new(self, i128::from(v), Ordering::EQ);
+        Query::from_literal(Literal::new(false, LitQuery::BigIntQuery(q)))
+    }
+
+    fn u64_ge(self, v: u64) -> Query {
+        let q = OrderedQuery::<i128>::new(self, i128::from(v), Ordering::GE);
+        Query::from_literal(Literal::new(false, LitQuery::BigIntQuery(q)))
+    }
+
+    fn u64_gt(self, v: u64) -> Query {
+        let q = OrderedQuery::<i128>::new(self, i128::from(v), Ordering::GT);
+        Query::from_literal(Literal::new(false, LitQuery::BigIntQuery(q)))
+    }
+
+    fn i128_lt(self, v: i128) -> Query {
+        let q = OrderedQuery::<i128>::new(self, v, Ordering::LT);
+        Query::from_literal(Literal::new(false, LitQuery::BigIntQuery(q)))
+    }
+
+    fn i128_le(self, v: i128) -> Query {
+        let q = OrderedQuery::<i128>::new(self, v, Ordering::LE);
+        Query::from_literal(Literal::new(false, LitQuery::BigIntQuery(q)))
+    }
+
+    fn i128_eq(self, v: i128) -> Query {
+        let q = OrderedQuery::<i128>::new(self, v, Ordering::EQ);
+        Query::from_literal(Literal::new(false, LitQuery::BigIntQuery(q)))
+    }
+
+    fn i128_ge(self, v: i128) -> Query {
+        let q = OrderedQuery::<i128>::new(self, v, Ordering::GE);
+        Query::from_literal(Literal::new(false, LitQuery::BigIntQuery(q)))
+    }
+
+    fn i128_gt(self, v: i128) -> Query {
+        let q = OrderedQuery::<i128>::new(self, v, Ordering::GT);
+        Query::from_literal(Literal::new(false, LitQuery::BigIntQuery(q)))
+    }
+
+    fn f64_lt(self, v: f64) -> Query {
+        let q = OrderedQuery::<f64>::new(self, v, Ordering::LT);
+        Query::from_literal(Literal::new(false, LitQuery::FloatQuery(q)))
+    }
+
+    fn f64_le(self, v: f64) -> Query {
+        let q = OrderedQuery::<f64>::new(self, v, Ordering::LE);
+        Query::from_literal(Literal::new(false, LitQuery::FloatQuery(q)))
+    }
+
+    fn f64_eq(self, v: f64) -> Query {
+        let q = OrderedQuery::<f64>::new(self, v, Ordering::EQ);
+        Query::from_literal(Literal::new(false, LitQuery::FloatQuery(q)))
+    }
+
+    fn f64_ge(self, v: f64) -> Query {
+        let q = OrderedQuery::<f64>::new(self, v, Ordering::GE);
+        Query::from_literal(Literal::new(false, LitQuery::FloatQuery(q)))
+    }
+
+    fn f64_gt(self, v: f64) -> Query {
+        let q = OrderedQuery::<f64>::new(self, v, Ordering::GT);
+        Query::from_literal(Literal::new(false, LitQuery::FloatQuery(q)))
+    }
+
+    fn i64_between(self, low: i64, high: i64) -> Query {
+        let q = RangeQuery::<i64>::new(self, low, high);
+        Query::from_literal(Literal::new(false, LitQuery::IntRange(q)))
+    }
+
+    fn f64_between(self, low: f64, high: f64) -> Query {
+        let q = RangeQuery::<f64>::new(self, low, high);
+        Query::from_literal(Literal::new(false, LitQuery::FloatRange(q)))
+    }
+
+    fn i64_range(self, low: Bound<i64>, high: Bound<i64>) -> Query {
+        match (low, high) {
+            (Bound::Included(l), Bound::Unbounded) => self.i64_ge(l),
+            (Bound::Excluded(l), Bound::Unbounded) => self.i64_gt(l),
+            (Bound::Unbounded, Bound::Included(h)) => self.i64_le(h),
+            (Bound::Unbounded, Bound::Excluded(h)) => self.i64_lt(h),
+            (low, high) => {
+                let low = match low {
+                    Bound::Included(l) => Some(l),
+                    Bound::Excluded(l) => l.checked_add(1),
+                    Bound::Unbounded => Some(i64::MIN),
+                };
+                let high = match high {
+                    Bound::Included(h) => Some(h),
+                    Bound::Excluded(h) => h.checked_sub(1),
+                    Bound::Unbounded => Some(i64::MAX),
+                };
+                match low.zip(high) {
+                    Some((l, h)) => self.i64_between(l, h),
+                    // Nothing is beyond the extremes.
+                    None => self.i64_between(i64::MAX, i64::MIN),
+                }
+            }
+        }
+    }
+
+    fn f64_range(self, low: Bound<f64>, high: Bound<f64>) -> Query {
+        match (low, high) {
+            (Bound::Included(l), Bound::Unbounded) => self.f64_ge(l),
+            (Bound::Excluded(l), Bound::Unbounded) => self.f64_gt(l),
+            (Bound::Unbounded, Bound::Included(h)) => self.f64_le(h),
+            (Bound::Unbounded, Bound::Excluded(h)) => self.f64_lt(h),
+            (low, high) => {
+                let low = match low {
+                    Bound::Included(l) => l,
+                    Bound::Excluded(l) => l.next_up(),
+                    Bound::Unbounded => f64::NEG_INFINITY,
+                };
+                let high = match high {
+                    Bound::Included(h) => h,
+                    Bound::Excluded(h) => h.next_down(),
+                    Bound::Unbounded => f64::INFINITY,
+                };
+                self.f64_between(low, high)
+            }
+        }
+    }
+
+    fn str_between<U: Into<OurStr>, V: Into<OurStr>>(self, low: U, high: V) -> Query {
+        let q = StrRangeQuery::new(self, low, high);
+        Query::from_literal(Literal::new(false, LitQuery::StrRange(q)))
+    }
 }
 
 impl std::ops::BitAnd for Query {
@@ -402,40 +1525,220 @@ mod test {
         use super::CNFQueryable;
         let q = "bla".has_value("foo");
 
-        assert_eq!(q.to_string(), "(AND (OR bla=foo))");
-        assert_eq!((!q).to_string(), "(AND (OR ~bla=foo))");
+        assert_eq!(q.to_string(), "bla:foo");
+        assert_eq!((!q).to_string(), "NOT bla:foo");
 
         let bla = "taste".to_string();
         let foo = "sweet".to_string();
         let q = bla.has_value(foo);
-        assert_eq!(q.to_string(), "(AND (OR taste=sweet))");
+        assert_eq!(q.to_string(), "taste:sweet");
 
         let q = "path".has_prefix("/bla");
         assert!(q.prefix_queries().next().is_some());
-        assert_eq!(q.to_string(), "(AND (OR path=/bla*))");
+        assert_eq!(q.to_string(), "path:\"/bla\"*");
+
+        let q = "colour".has_field();
+        assert_eq!(q.to_string(), "colour:*");
+        assert!(q.matches(&[("colour", "blue")].into()));
+        assert!(!q.matches(&[("taste", "sweet")].into()));
+        assert!((!q).matches(&[("taste", "sweet")].into()));
+
+        let q = "colour".lacks_field();
+        assert_eq!(q.to_string(), "NOT colour:*");
+        assert_eq!(q, !"colour".has_field());
+        assert_eq!(!q.clone(), "colour".has_field());
+        assert!(!q.matches(&[("colour", "blue")].into()));
+        assert!(q.matches(&[("taste", "sweet")].into()));
+
+        let q = "path".has_regex(&regex::Regex::new("/bla/.*").unwrap());
+        assert_eq!(q.to_string(), "path:/\\/bla\\/.*/");
+        assert!(q.matches(&[("path", "/bla/foo")].into()));
+        assert!(!q.matches(&[("path", "/foo/bla/foo")].into()));
+        // Verbose mode comments do not break the anchoring.
+        let q = "path".has_regex(&regex::Regex::new("(?x)a # comment").unwrap());
+        assert!(q.matches(&[("path", "a")].into()));
+        assert!(!q.matches(&[("path", "ab")].into()));
 
         let q = "some_num".i64_eq(1234);
-        assert_eq!(q.to_string(), "(AND (OR some_num==1234))");
+        assert_eq!(q.to_string(), "some_num=1234");
 
         let q = "some_num".i64_lt(1234);
-        assert_eq!(q.to_string(), "(AND (OR some_num<1234))");
+        assert_eq!(q.to_string(), "some_num<1234");
 
         let q = "some_num".i64_le(1234);
-        assert_eq!(q.to_string(), "(AND (OR some_num<=1234))");
+        assert_eq!(q.to_string(), "some_num<=1234");
 
         let q = "some_num".i64_ge(1234);
-        assert_eq!(q.to_string(), "(AND (OR some_num>=1234))");
+        assert_eq!(q.to_string(), "some_num>=1234");
 
         let q = "some_num".i64_gt(1234);
-        assert_eq!(q.to_string(), "(AND (OR some_num>1234))");
+        assert_eq!(q.to_string(), "some_num>1234");
+
+        let q = "price".f64_lt(1.5);
+        assert_eq!(q.to_string(), "price<1.5");
+
+        let q = "price".f64_le(1.5);
+        assert_eq!(q.to_string(), "price<=1.5");
+
+        let q = "price".f64_eq(-0.25);
+        assert_eq!(q.to_string(), "price=-0.25");
+
+        let q = "price".f64_ge(1.5);
+        assert_eq!(q.to_string(), "price>=1.5");
+
+        let q = "price".f64_gt(1.5);
+        assert_eq!(q.to_string(), "price>1.5");
+    }
+
+    #[test]
+    fn test_polygons() {
+        use super::*;
+        let ll = |lat, lng| LatLng::new(lat, lng).unwrap();
+        let square = |lat: f64, lng: f64, size: f64| {
+            vec![
+                ll(lat, lng),
+                ll(lat, lng + size),
+                ll(lat + size, lng + size),
+                ll(lat + size, lng),
+            ]
+        };
+
+        let donut = Polygon::new(square(0.0, 0.0, 1.0)).with_hole(square(0.4, 0.4, 0.2));
+        let q = "p".latlng_in_polygon(&donut);
+        // Inclusion AND NOT exclusion
+        assert_eq!(q.clauses().len(), 2);
+        assert!(q.clauses()[1].literals()[0].is_negated());
+
+        assert!(q.matches(&[("p", "0.1,0.1")].into()));
+        assert!(!q.matches(&[("p", "0.5,0.5")].into()));
+        assert!(!q.matches(&[("p", "1.5,0.5")].into()));
+
+        let island = Polygon::new(square(0.45, 0.45, 0.1));
+        let q = "p".latlng_in_multipolygon(&[donut, island]);
+        assert!(q.matches(&[("p", "0.1,0.1")].into()));
+        assert!(q.matches(&[("p", "0.5,0.5")].into()));
+        assert!(!q.matches(&[("p", "0.42,0.42")].into()));
+        assert!(!q.matches(&[("p", "1.5,0.5")].into()));
     }
 
     #[test]
     fn test_empty() {
         use super::*;
         let cnf = Query::default();
-        assert_eq!(cnf.to_string(), "(AND )");
-        assert_eq!((!cnf).to_string(), "(AND )");
+        assert_eq!(cnf.to_string(), "MATCH_ALL");
+        assert_eq!((!cnf).to_string(), "MATCH_NONE");
+    }
+
+    #[test]
+    fn test_write_to_and_digest() {
+        use super::*;
+        let q = ("c".has_value("b") | "a".has_prefix("x") | !"c".has_value("a"))
+            & "d".i64_gt(3)
+            & "c".has_value("a");
+        let mut s = String::new();
+        q.write_to(&mut s).unwrap();
+        assert_eq!(s, "(a:x* OR NOT c:a OR c:b) AND c:a AND d>3");
+        assert_eq!(s, q.to_string());
+
+        // Duplicate literals are all written, in stored order
+        let c = Clause::from_termqueries(vec![
+            TermQuery::new("f", "v"),
+            TermQuery::new("f", "a"),
+            TermQuery::new("f", "v"),
+        ]);
+        assert_eq!(c.to_string(), "(f:v OR f:a OR f:v)");
+
+        let q2 = ("a".has_prefix("x") | !"c".has_value("a") | "c".has_value("b"))
+            & "d".i64_gt(3)
+            & "c".has_value("a");
+        assert_eq!(q.digest(), q2.digest());
+        assert_eq!(q.digest(), q.clone().digest());
+        assert_ne!(q.digest(), ("d".i64_gt(3) & "c".has_value("a")).digest());
+        assert_ne!(q.digest(), Query::default().digest());
+    }
+
+    #[test]
+    fn test_ranges() {
+        use super::*;
+        use std::ops::Bound::*;
+        let d = |v: &str| Document::default().with_value("f", v);
+
+        let q = "f".i64_range(Excluded(1), Excluded(3));
+        assert_eq!(q, "f".i64_between(2, 2));
+        assert!(q.matches(&d("2")) && !q.matches(&d("3")));
+        assert_eq!("f".i64_range(Unbounded, Included(3)), "f".i64_le(3));
+        assert_eq!(
+            "f".i64_range(Unbounded, Unbounded),
+            "f".i64_between(i64::MIN, i64::MAX)
+        );
+        // Nothing is greater than the maximum.
+        let q = "f".i64_range(Excluded(i64::MAX), Included(i64::MAX));
+        assert!(!q.matches(&d(&i64::MAX.to_string())));
+
+        let q = "f".f64_range(Excluded(1.0), Included(2.0));
+        assert!(!q.matches(&d("1")) && q.matches(&d("1.0000001")) && q.matches(&d("2")));
+        assert_eq!("f".f64_range(Included(1.0), Unbounded), "f".f64_ge(1.0));
+    }
+
+    #[test]
+    fn test_highlights() {
+        use super::*;
+        let q = "d".i64_gt(3) & ("c".has_value("b") | "e".has_field()) & !"c".has_value("a");
+        let d: Document = [("c", "b"), ("d", "2"), ("d", "4"), ("d", "x"), ("e", "")].into();
+        assert_eq!(
+            q.highlights(&d),
+            vec![
+                ("c".to_string(), "b".to_string()),
+                ("d".to_string(), "4".to_string()),
+                ("e".to_string(), "".to_string())
+            ]
+        );
+        assert!(q.highlights(&d.clone().with_value("c", "a")).is_empty());
+        assert!(Query::default().highlights(&d).is_empty());
+    }
+
+    #[test]
+    fn test_boost_and_score() {
+        use super::*;
+        let q = ("c".has_value("b").boost(2.0) | !"c".has_value("a").boost(3.0))
+            & "d".i64_gt(3).boost(0.5)
+            & "e".has_field();
+        assert_eq!(q.to_string(), "(NOT c:a^3 OR c:b^2) AND d>3^0.5 AND e:*");
+        assert_eq!(
+            q.to_string().parse::<Query>().unwrap().to_string(),
+            q.to_string()
+        );
+        // Boosts multiply, and survive negations.
+        assert_eq!(
+            q.clone().boost(2.0).to_string(),
+            "(NOT c:a^6 OR c:b^4) AND d>3 AND e:*^2"
+        );
+        assert_eq!((!"c".has_value("a").boost(3.0)).to_string(), "NOT c:a^3");
+        assert_eq!((!!"c".has_value("a").boost(3.0)).to_string(), "c:a^3");
+        assert_eq!((!"e".has_field().boost(2.0)).to_string(), "NOT e:*^2");
+
+        assert_eq!(
+            q.score(&[("c", "b"), ("d", "4"), ("e", "")].into()),
+            Some(4.5)
+        );
+        assert_eq!(
+            q.score(&[("c", "x"), ("d", "4"), ("e", "")].into()),
+            Some(4.5)
+        );
+        assert_eq!(q.score(&[("c", "a"), ("d", "4"), ("e", "")].into()), None);
+        assert_eq!(Query::default().score(&Document::default()), Some(0.0));
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&q).unwrap();
+            assert_eq!(serde_json::from_str::<Query>(&json).unwrap(), q);
+            // Default weights are not serialised.
+            assert!(
+                !serde_json::to_string(&"c".has_value("b"))
+                    .unwrap()
+                    .contains("weight")
+            );
+        }
     }
 
     #[test]
@@ -444,7 +1747,7 @@ mod test {
         let cnf_query = "field".has_value("value");
         assert_eq!(cnf_query.0.len(), 1);
         assert_eq!(cnf_query.0[0].literals.len(), 1);
-        assert_eq!(cnf_query.to_string(), "(AND (OR field=value))");
+        assert_eq!(cnf_query.to_string(), "field:value");
     }
 
     #[test]
@@ -454,16 +1757,13 @@ mod test {
         assert_eq!(combined.0.len(), 2);
         assert_eq!(combined.0[0].literals.len(), 1);
         // Structure would be:
-        assert_eq!(
-            combined.to_string(),
-            "(AND (OR field1=value1) (OR field2=value2))"
-        );
+        assert_eq!(combined.to_string(), "field1:value1 AND field2:value2");
 
         // De Morgan law
         // NOT (AND C1 C2) = (OR (NOT C1) (NOT C2))
         assert_eq!(
             (!combined).to_string(),
-            "(AND (OR ~field1=value1 ~field2=value2))"
+            "(NOT field1:value1 OR NOT field2:value2)"
         );
     }
 
@@ -474,29 +1774,29 @@ mod test {
         assert_eq!(combined.0.len(), 1); // Only one clause in the top level and.
         assert_eq!(combined.0[0].literals.len(), 2); // Two litteral in the clause.
         // In this shape: AND (OR field1:value1 field2:value2)
-        assert_eq!(combined.to_string(), "(AND (OR X=x Y=y))");
+        assert_eq!(combined.to_string(), "(X:x OR Y:y)");
         // Second De Morgan law
         // NOT A OR B = NOT A AND NOT B
-        assert_eq!((!combined).to_string(), "(AND (OR ~X=x) (OR ~Y=y))");
+        assert_eq!((!combined).to_string(), "NOT X:x AND NOT Y:y");
 
         // (x AND Y) OR (NOT Z):
         let q = ("X".has_value("x") & "Y".has_value("y")) | (!"Z".has_value("z"));
 
-        assert_eq!(q.to_string(), "(AND (OR X=x ~Z=z) (OR Y=y ~Z=z))");
+        assert_eq!(q.to_string(), "(X:x OR NOT Z:z) AND (Y:y OR NOT Z:z)");
         assert_eq!(
             (!q.clone()).to_string(),
-            "(AND (OR ~X=x ~Y=y) (OR ~X=x Z=z) (OR ~Y=y Z=z) (OR Z=z))"
+            "(NOT X:x OR NOT Y:y) AND (NOT X:x OR Z:z) AND (NOT Y:y OR Z:z) AND Z:z"
         );
 
         // (X OR Y) OR Z
         let q = ("X".has_value("x") | "Y".has_value("y")) | "Z".has_value("z");
         // Note how the parentheses are removed magically
-        assert_eq!(q.to_string(), "(AND (OR X=x Y=y Z=z))");
+        assert_eq!(q.to_string(), "(X:x OR Y:y OR Z:z)");
 
         // X AND (Y OR (Z AND W))
         let q =
             "X".has_value("x") & ("Y".has_value("y") | ("Z".has_value("z") & "W".has_value("w")));
-        assert_eq!(q.to_string(), "(AND (OR X=x) (OR Y=y Z=z) (OR W=w Y=y))");
+        assert_eq!(q.to_string(), "(W:w OR Y:y) AND X:x AND (Y:y OR Z:z)");
 
         // ( X AND Y ) OR ( Z AND W )
         // Turns into
@@ -504,10 +1804,89 @@ mod test {
             ("X".has_value("x") & "Y".has_value("y")) | ("Z".has_value("z") & "W".has_value("w"));
         assert_eq!(
             q.to_string(),
-            "(AND (OR X=x Z=z) (OR W=w X=x) (OR Y=y Z=z) (OR W=w Y=y))"
+            "(W:w OR X:x) AND (W:w OR Y:y) AND (X:x OR Z:z) AND (Y:y OR Z:z)"
         )
     }
 
+    #[test]
+    fn test_canonical_order() {
+        use super::*;
+        let (a, b, c) = (
+            "a".has_value("1"),
+            "b".i64_gt(2) | !"b".has_value("2"),
+            "c".has_prefix("x") | "a".has_value("1"),
+        );
+        let q1 = a.clone() & b.clone() & c.clone();
+        let q2 = c.clone() & (a.clone() & b.clone());
+        assert_eq!(q1, q2);
+        assert_eq!(q1.clauses(), q2.clauses());
+        assert_eq!(q1.digest(), q2.digest());
+        assert_eq!(a.clone() | b.clone(), b.clone() | a.clone());
+
+        // Literals only differing by negation, operator or weight.
+        let q1 = "p".i64_lt(1) | "p".i64_gt(1) | !"p".has_value("1") | "p".has_value("1");
+        let q2 = "p".has_value("1") | !"p".has_value("1") | "p".i64_gt(1) | "p".i64_lt(1);
+        assert_eq!(q1, q2);
+        assert_eq!(q1.to_string(), q2.to_string());
+        let q1 = "p".has_value("1").boost(2.0) | "p".has_value("1");
+        let q2 = "p".has_value("1") | "p".has_value("1").boost(2.0);
+        assert_eq!(q1, q2);
+
+        #[cfg(feature = "serde")]
+        {
+            // Deserialised queries are sorted too.
+            let json = serde_json::to_string(&(c & a)).unwrap();
+            let reversed: Vec<Clause> = serde_json::from_str(&json).unwrap();
+            let reversed =
+                serde_json::to_string(&reversed.into_iter().rev().collect_vec()).unwrap();
+            assert_ne!(json, reversed);
+            let q: Query = serde_json::from_str(&reversed).unwrap();
+            assert_eq!(serde_json::to_string(&q).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn test_try_from_or() {
+        use super::*;
+        let xy = "X".has_value("x") & "Y".has_value("y");
+        let zw = "Z".has_value("z") & "W".has_value("w");
+        assert_eq!(
+            Query::try_from_or(vec![xy.clone(), zw.clone()], 4),
+            Ok(xy.clone() | zw.clone())
+        );
+        assert_eq!(
+            Query::try_from_or(vec![xy.clone(), zw], 3),
+            Err("Too many clauses. Maximum is 3".to_string())
+        );
+
+        // 2^40 clauses are never built.
+        let qs = (0..40).map(|_| xy.clone()).collect();
+        assert!(Query::try_from_or(qs, 1000).is_err());
+        // The empty disjunction is a single empty clause.
+        assert_eq!(Query::try_from_or(vec![], 1), Ok(Query::match_none()));
+        assert!(Query::try_from_or(vec![], 0).is_err());
+    }
+
+    #[test]
+    fn test_try_negation() {
+        use super::*;
+        let xy = "X".has_value("x") & "Y".has_value("y");
+        assert_eq!(Query::try_negation(xy.clone(), 1), Ok(!xy.clone()));
+        assert_eq!(Query::try_negation(!xy.clone(), 2), Ok(xy));
+
+        // 2^40 clauses are never built.
+        let q = Query::from_and(
+            (0..40)
+                .map(|i| "X".has_value(i.to_string()) | "Y".has_value(i.to_string()))
+                .collect(),
+        );
+        assert!(Query::try_negation(q, 1000).is_err());
+        assert_eq!(
+            Query::try_negation(Query::match_all(), 1),
+            Ok(Query::match_none())
+        );
+    }
+
     // Different values OR
     #[test]
     fn test_or_with_multiple_values() {
@@ -517,7 +1896,7 @@ mod test {
         let combined = xsq & "Y".has_value("y");
         assert_eq!(
             combined.to_string(),
-            "(AND (OR X=x_0 X=x_1 X=x_2 X=x_3 X=x_4) (OR Y=y))"
+            "(X:x_0 OR X:x_1 OR X:x_2 OR X:x_3 OR X:x_4) AND Y:y"
         );
     }
 }
@@ -741,4 +2120,40 @@ mod test_queries {
         assert_eq!(doc_ids.next(), None);
         assert_eq!(doc_ids.next(), None);
     }
+
+    #[test]
+    fn test_match_all_none() {
+        use super::*;
+        let all = Query::match_all();
+        let none = Query::match_none();
+        let q = "colour".has_value("blue") | "taste".has_value("sweet");
+        let d: Document = [("colour", "blue")].into();
+
+        assert!(all.matches(&d));
+        assert!(all.matches(&Document::default()));
+        assert!(!none.matches(&d));
+        assert!(!none.matches(&Document::default()));
+
+        assert_eq!(!all.clone(), none);
+        assert_eq!(!none.clone(), all);
+        assert_eq!(!!all.clone(), all);
+        assert_eq!(!!none.clone(), none);
+
+        // Neutral elements
+        assert_eq!(q.clone() & all.clone(), q);
+        assert_eq!(q.clone() | none.clone(), q);
+        assert_eq!(Query::from_and(vec![]), all);
+        assert_eq!(Query::from_or(vec![]), none);
+
+        // Absorbing elements
+        assert_eq!(q.clone() | all.clone(), all);
+        assert!(!(q.clone() & none.clone()).matches(&d));
+        assert_eq!(none.clone() | none.clone(), none);
+        assert_eq!(all.clone() & all.clone(), all);
+
+        assert_eq!(all.to_string(), "MATCH_ALL");
+        assert_eq!(none.to_string(), "MATCH_NONE");
+        assert_eq!("MATCH_ALL".parse::<Query>().unwrap(), all);
+        assert_eq!("NOT MATCH_ALL".parse::<Query>().unwrap(), none);
+    }
 }
@@ -0,0 +1,488 @@
+use std::ops::Bound;
+
+use h3o::LatLng;
+use serde_json::{Number, Value};
+
+use crate::geotools::Meters;
+use crate::models::cnf::{CNFQueryable, Query};
+
+// Elasticsearch distance units, in meters.
+const DISTANCE_UNITS: [(&str, f64); 19] = [
+    ("mi", 1609.344),
+    ("miles", 1609.344),
+    ("yd", 0.9144),
+    ("yards", 0.9144),
+    ("ft", 0.3048),
+    ("feet", 0.3048),
+    ("in", 0.0254),
+    ("inch", 0.0254),
+    ("km", 1000.0),
+    ("kilometers", 1000.0),
+    ("m", 1.0),
+    ("meters", 1.0),
+    ("cm", 0.01),
+    ("centimeters", 0.01),
+    ("mm", 0.001),
+    ("millimeters", 0.001),
+    ("NM", 1852.0),
+    ("nmi", 1852.0),
+    ("nauticalmiles", 1852.0),
+];
+
+// The only key and value of a JSON object.
+fn single_entry<'a>(v: &'a Value, what: &str) -> Result<(&'a str, &'a Value), String> {
+    match v
+        .as_object()
+        .map(|o| o.iter().collect::<Vec<_>>())
+        .as_deref()
+    {
+        Some([(k, v)]) => Ok((k.as_str(), v)),
+        _ => Err(format!(
+            "{} needs an object with exactly one key, got {}",
+            what, v
+        )),
+    }
+}
+
+// The value of a term like query, given directly or as `{"value": ..}`.
+fn term_value(v: &Value) -> Result<String, String> {
+    match v.get("value").unwrap_or(v) {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        other => Err(format!("Invalid value {}", other)),
+    }
+}
+
+// Whether a term like query is `{"value": .., "case_insensitive": true}`.
+fn case_insensitive(v: &Value) -> bool {
+    v.get("case_insensitive")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+// The sub queries of a bool query occurence, given as one query or an array of queries.
+fn occurence(body: &Value, key: &str) -> Result<Vec<Query>, String> {
+    match body.get(key) {
+        None => Ok(vec![]),
+        Some(Value::Array(qs)) => qs.iter().map(es_query).collect(),
+        Some(q) => Ok(vec![es_query(q)?]),
+    }
+}
+
+fn bool_query(body: &Value) -> Result<Query, String> {
+    if let Some(k) = body.as_object().and_then(|o| {
+        o.keys().find(|k| {
+            ![
+                "must",
+                "filter",
+                "should",
+                "must_not",
+                "minimum_should_match",
+                "boost",
+                "_name",
+            ]
+            .contains(&k.as_str())
+        })
+    }) {
+        return Err(format!("Unsupported bool option {}", k));
+    }
+
+    // Filters do not score, which makes no difference when matching.
+    let mut musts = occurence(body, "must")?;
+    musts.extend(occurence(body, "filter")?);
+    let shoulds = occurence(body, "should")?;
+
+    // Like in Elasticsearch, should clauses are optional next to must clauses.
+    let min_should_match = match body.get("minimum_should_match") {
+        None => u64::from(musts.is_empty() && !shoulds.is_empty()),
+        Some(v) => v
+            .as_u64()
+            .or_else(|| v.as_str().and_then(|s| s.parse().ok()))
+            .filter(|n| *n <= 1)
+            .ok_or_else(|| format!("Unsupported minimum_should_match {}", v))?,
+    };
+    if min_should_match == 1 {
+        if shoulds.is_empty() {
+            return Err("minimum_should_match needs should clauses".to_string());
+        }
+        musts.push(Query::from_or(shoulds));
+    }
+
+    musts.extend(occurence(body, "must_not")?.into_iter().map(|q| !q));
+    if musts.is_empty() {
+        return Err("Empty bool query".to_string());
+    }
+    Ok(Query::from_and(musts))
+}
+
+fn range_query(body: &Value) -> Result<Query, String> {
+    let (field, bounds) = single_entry(body, "range")?;
+    let bounds = bounds
+        .as_object()
+        .ok_or_else(|| format!("Invalid range bounds {}", bounds))?;
+    let bounds = bounds
+        .iter()
+        .filter(|(k, _)| k.as_str() != "boost")
+        .map(|(k, v)| match v {
+            Value::Number(n) => Ok((k.as_str(), n)),
+            _ => Err(format!(
+                "Only numeric ranges are supported, got {} {}",
+                k, v
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if bounds.is_empty() {
+        return Err(format!("No bounds in range on {}", field));
+    }
+
+    // Both ends in one range literal.
+    let (mut low, mut high) = (Bound::Unbounded, Bound::Unbounded);
+    for (k, n) in bounds {
+        let (end, bound) = match k {
+            "gt" => (&mut low, Bound::Excluded(n)),
+            "gte" => (&mut low, Bound::Included(n)),
+            "lt" => (&mut high, Bound::Excluded(n)),
+            "lte" => (&mut high, Bound::Included(n)),
+            _ => return Err(format!("Unsupported range bound {}", k)),
+        };
+        if *end != Bound::Unbounded {
+            return Err(format!("Conflicting range bounds on {}", field));
+        }
+        *end = bound;
+    }
+
+    let all_integers = [low, high]
+        .iter()
+        .all(|b| !matches!(b, Bound::Included(n) | Bound::Excluded(n) if !n.is_i64()));
+    if all_integers {
+        Ok(field.i64_range(
+            number_bound(low, Number::as_i64)?,
+            number_bound(high, Number::as_i64)?,
+        ))
+    } else {
+        Ok(field.f64_range(
+            number_bound(low, Number::as_f64)?,
+            number_bound(high, Number::as_f64)?,
+        ))
+    }
+}
+
+fn number_bound<T>(
+    bound: Bound<&Number>,
+    convert: fn(&Number) -> Option<T>,
+) -> Result<Bound<T>, String> {
+    match bound {
+        Bound::Included(n) => convert(n).map(Bound::Included),
+        Bound::Excluded(n) => convert(n).map(Bound::Excluded),
+        Bound::Unbounded => Some(Bound::Unbounded),
+    }
+    .ok_or_else(|| format!("Invalid range bound {:?}", bound))
+}
+
+fn to_latlng(v: &Value) -> Result<LatLng, String> {
+    let coords = match v {
+        Value::Object(o) => o
+            .get("lat")
+            .and_then(Value::as_f64)
+            .zip(o.get("lon").and_then(Value::as_f64)),
+        // GeoJSON order, longitude first.
+        Value::Array(a) => match a.as_slice() {
+            [lon, lat] => lat.as_f64().zip(lon.as_f64()),
+            _ => None,
+        },
+        Value::String(s) => s
+            .split_once(',')
+            .and_then(|(lat, lon)| lat.trim().parse().ok().zip(lon.trim().parse().ok())),
+        _ => None,
+    };
+    coords
+        .ok_or_else(|| format!("Invalid location {}", v))
+        .and_then(|(lat, lng)| LatLng::new(lat, lng).map_err(|e| e.to_string()))
+}
+
+fn to_meters(v: &Value) -> Result<Meters, String> {
+    let meters = match v {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => {
+            let (n, unit) =
+                s.split_at(s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len()));
+            let factor = match unit {
+                "" => Some(1.0),
+                u => DISTANCE_UNITS
+                    .iter()
+                    .find(|(n, _)| *n == u)
+                    .map(|(_, f)| *f),
+            };
+            n.trim().parse::<f64>().ok().zip(factor).map(|(n, f)| n * f)
+        }
+        _ => None,
+    };
+    meters
+        .filter(|m| m.is_finite() && *m >= 0.0)
+        .map(|m| Meters(m.round() as u64))
+        .ok_or_else(|| format!("Invalid distance {}", v))
+}
+
+fn geo_distance_query(body: &Value) -> Result<Query, String> {
+    let distance = body
+        .get("distance")
+        .ok_or_else(|| "geo_distance needs a distance".to_string())
+        .and_then(to_meters)?;
+    let locations = body
+        .as_object()
+        .map(|o| {
+            o.iter()
+                .filter(|(k, _)| {
+                    ![
+                        "distance",
+                        "distance_type",
+                        "validation_method",
+                        "boost",
+                        "_name",
+                    ]
+                    .contains(&k.as_str())
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    match locations.as_slice() {
+        [(field, location)] => Ok(field.as_str().latlng_within(to_latlng(location)?, distance)),
+        _ => Err(format!(
+            "geo_distance needs exactly one field, got {}",
+            body
+        )),
+    }
+}
+
+fn es_query(v: &Value) -> Result<Query, String> {
+    let (kind, body) = single_entry(v, "A query")?;
+    match kind {
+        "bool" => bool_query(body),
+        "term" => {
+            let (field, value) = single_entry(body, "term")?;
+            let term = term_value(value)?;
+            Ok(match case_insensitive(value) {
+                true => field.has_value_ci(term),
+                false => field.has_value(term),
+            })
+        }
+        "terms" => {
+            let (field, values) = single_entry(body, "terms")?;
+            match values.as_array() {
+                Some(vs) if !vs.is_empty() => vs
+                    .iter()
+                    .map(|v| term_value(v).map(|v| field.has_value(v)))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(Query::from_or),
+                _ => Err(format!("terms needs a non empty array, got {}", values)),
+            }
+        }
+        "prefix" => {
+            let (field, value) = single_entry(body, "prefix")?;
+            let prefix = term_value(value)?;
+            Ok(match case_insensitive(value) {
+                true => field.has_prefix_ci(prefix),
+                false => field.has_prefix(prefix),
+            })
+        }
+        "exists" => body
+            .get("field")
+            .and_then(Value::as_str)
+            .map(|f| f.has_field())
+            .ok_or_else(|| format!("exists needs a field, got {}", body)),
+        "range" => range_query(body),
+        "geo_distance" => geo_distance_query(body),
+        other => Err(format!("Unsupported query {}", other)),
+    }
+}
+
+impl Query {
+    /// Builds a query from a subset of the Elasticsearch query DSL,
+    /// for instance to migrate queries from the Elasticsearch percolator.
+    ///
+    /// Supported queries:
+    /// - `bool`, with `must`, `filter`, `should`, `must_not` and
+    ///   a `minimum_should_match` of 0 or 1.
+    /// - `term` and `terms`. Values are matched as strings.
+    /// - `prefix`
+    /// - `case_insensitive` on `term` and `prefix`. See [`CNFQueryable::has_value_ci`]
+    /// - `exists`
+    /// - `range`, with numeric `gt`, `gte`, `lt` and `lte` bounds.
+    /// - `geo_distance`. See [`CNFQueryable::latlng_within`]
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let dsl = serde_json::json!({"bool": {
+    ///     "must": {"term": {"colour": "blue"}},
+    ///     "must_not": [{"range": {"price": {"gte": 100}}}]
+    /// }});
+    /// let q = Query::from_elasticsearch(&dsl).unwrap();
+    /// assert_eq!(q, "colour".has_value("blue") & !"price".i64_ge(100));
+    /// ```
+    pub fn from_elasticsearch(dsl: &Value) -> Result<Query, String> {
+        es_query(dsl)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn es(v: Value) -> Result<Query, String> {
+        Query::from_elasticsearch(&v)
+    }
+
+    #[test]
+    fn test_leaves() {
+        assert_eq!(
+            es(json!({"term": {"colour": "blue"}})).unwrap(),
+            "colour".has_value("blue")
+        );
+        assert_eq!(
+            es(json!({"term": {"size": {"value": 42, "boost": 2.0}}})).unwrap(),
+            "size".has_value("42")
+        );
+        assert_eq!(
+            es(json!({"terms": {"colour": ["blue", "red"]}})).unwrap(),
+            "colour".has_value("blue") | "colour".has_value("red")
+        );
+        assert_eq!(
+            es(json!({"prefix": {"path": {"value": "/bla"}}})).unwrap(),
+            "path".has_prefix("/bla")
+        );
+        assert_eq!(
+            es(json!({"term": {"colour": {"value": "Blue", "case_insensitive": true}}})).unwrap(),
+            "colour".has_value_ci("blue")
+        );
+        assert_eq!(
+            es(json!({"prefix": {"path": {"value": "/Bla", "case_insensitive": false}}})).unwrap(),
+            "path".has_prefix("/Bla")
+        );
+        assert_eq!(
+            es(json!({"prefix": {"path": {"value": "/Bla", "case_insensitive": true}}})).unwrap(),
+            "path".has_prefix_ci("/bla")
+        );
+        assert_eq!(
+            es(json!({"exists": {"field": "colour"}})).unwrap(),
+            "colour".has_field()
+        );
+
+        assert!(es(json!({"terms": {"colour": []}})).is_err());
+        assert!(es(json!({"term": {"colour": "blue", "taste": "sweet"}})).is_err());
+        assert!(es(json!({"term": {"colour": ["blue"]}})).is_err());
+        assert!(es(json!({"match": {"title": "blue"}})).is_err());
+        assert!(es(json!({})).is_err());
+    }
+
+    #[test]
+    fn test_range() {
+        assert_eq!(
+            es(json!({"range": {"age": {"gte": 10, "lt": 20}}})).unwrap(),
+            "age".i64_between(10, 19)
+        );
+        assert_eq!(
+            es(json!({"range": {"price": {"gt": 1, "lte": 2.5, "boost": 1}}})).unwrap(),
+            "price".f64_between(1f64.next_up(), 2.5)
+        );
+        assert_eq!(
+            es(json!({"range": {"age": {"gt": 10}}})).unwrap(),
+            "age".i64_gt(10)
+        );
+        assert!(es(json!({"range": {"age": {"gt": 10, "gte": 11}}})).is_err());
+        assert!(es(json!({"range": {"age": {}}})).is_err());
+        assert!(es(json!({"range": {"date": {"gte": "now-1d"}}})).is_err());
+        assert!(es(json!({"range": {"age": {"eq": 3}}})).is_err());
+    }
+
+    #[test]
+    fn test_geo_distance() {
+        let paris = LatLng::new(48.864716, 2.349014).unwrap();
+        for location in [
+            json!({"lat": 48.864716, "lon": 2.349014}),
+            json!([2.349014, 48.864716]),
+            json!("48.864716, 2.349014"),
+        ] {
+            assert_eq!(
+                es(json!({"geo_distance": {"distance": "2km", "pin": location}})).unwrap(),
+                "pin".latlng_within(paris, Meters(2000))
+            );
+        }
+        assert_eq!(
+            es(json!({"geo_distance": {"distance": "1.5mi", "distance_type": "arc", "pin": [2.349014, 48.864716]}}))
+                .unwrap(),
+            "pin".latlng_within(paris, Meters(2414))
+        );
+        assert_eq!(
+            es(json!({"geo_distance": {"distance": 300, "pin": [2.349014, 48.864716]}})).unwrap(),
+            "pin".latlng_within(paris, Meters(300))
+        );
+
+        assert!(
+            es(json!({"geo_distance": {"distance": "2 parsecs", "pin": [2.3, 48.8]}})).is_err()
+        );
+        assert!(es(json!({"geo_distance": {"pin": [2.3, 48.8]}})).is_err());
+        assert!(es(json!({"geo_distance": {"distance": "2km", "pin": "u09tvw0"}})).is_err());
+        assert!(es(json!({"geo_distance": {"distance": "2km"}})).is_err());
+    }
+
+    #[test]
+    fn test_bool() {
+        let blue = || "colour".has_value("blue");
+        let red = || "colour".has_value("red");
+        let sweet = || "taste".has_value("sweet");
+
+        assert_eq!(
+            es(json!({"bool": {"must": [{"term": {"colour": "blue"}}], "filter": {"term": {"taste": "sweet"}}}}))
+                .unwrap(),
+            blue() & sweet()
+        );
+        assert_eq!(
+            es(json!({"bool": {"should": [{"term": {"colour": "blue"}}, {"term": {"colour": "red"}}]}})).unwrap(),
+            blue() | red()
+        );
+        // Should clauses are optional next to must clauses.
+        assert_eq!(
+            es(json!({"bool": {"must": {"term": {"taste": "sweet"}}, "should": {"term": {"colour": "blue"}}}}))
+                .unwrap(),
+            sweet()
+        );
+        assert_eq!(
+            es(json!({"bool": {
+                "must": {"term": {"taste": "sweet"}},
+                "should": [{"term": {"colour": "blue"}}, {"term": {"colour": "red"}}],
+                "minimum_should_match": "1"
+            }}))
+            .unwrap(),
+            sweet() & (blue() | red())
+        );
+        assert_eq!(
+            es(json!({"bool": {"must_not": [{"term": {"colour": "blue"}}, {"term": {"colour": "red"}}]}})).unwrap(),
+            !blue() & !red()
+        );
+
+        let nested = es(json!({"bool": {
+            "should": [
+                {"bool": {"must": [{"term": {"colour": "blue"}}, {"term": {"taste": "sweet"}}]}},
+                {"bool": {"must_not": {"exists": {"field": "colour"}}}}
+            ]
+        }}))
+        .unwrap();
+        assert!(nested.matches(&[("colour", "blue"), ("taste", "sweet")].into()));
+        assert!(nested.matches(&[("taste", "bitter")].into()));
+        assert!(!nested.matches(&[("colour", "blue"), ("taste", "bitter")].into()));
+
+        assert!(es(json!({"bool": {}})).is_err());
+        assert!(es(json!({"bool": {"should": [], "minimum_should_match": 1}})).is_err());
+        assert!(
+            es(
+                json!({"bool": {"should": {"term": {"colour": "blue"}}, "minimum_should_match": 2}})
+            )
+            .is_err()
+        );
+        assert!(es(json!({"bool": {"must": {"term": {"colour": "blue"}}, "must_nt": {"term": {"colour": "red"}}}})).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use geojson::{Geometry, GeometryValue, Position};
+use h3o::LatLng;
+
+use crate::geotools::{Meters, Polygon};
+use crate::models::cnf::{CNFQueryable, Query};
+use crate::models::types::OurStr;
+
+fn to_latlng(p: &Position) -> Result<LatLng, String> {
+    match p.as_slice() {
+        // GeoJSON positions are longitude first.
+        [lng, lat, ..] => LatLng::new(*lat, *lng).map_err(|e| e.to_string()),
+        _ => Err(format!("Invalid position {:?}", p.as_slice())),
+    }
+}
+
+fn to_ring(ps: &[Position]) -> Result<Vec<LatLng>, String> {
+    let mut ring = ps.iter().map(to_latlng).collect::<Result<Vec<_>, _>>()?;
+    // GeoJSON rings are closed. We don't need that.
+    if ring.len() > 1 && ring.first() == ring.last() {
+        ring.pop();
+    }
+    if ring.len() < 3 {
+        return Err(format!(
+            "A ring needs at least 3 positions, got {}",
+            ring.len()
+        ));
+    }
+    Ok(ring)
+}
+
+fn to_polygon(rings: &[Vec<Position>]) -> Result<Polygon, String> {
+    let (exterior, holes) = rings
+        .split_first()
+        .ok_or_else(|| "A polygon needs an exterior ring".to_string())?;
+    holes
+        .iter()
+        .try_fold(Polygon::new(to_ring(exterior)?), |p, h| {
+            Ok(p.with_hole(to_ring(h)?))
+        })
+}
+
+impl Query {
+    /// Builds a geographic query from a GeoJSON geometry, where the field
+    /// represents a `h3o::coord::latlng`.
+    ///
+    /// Supported geometries:
+    /// - `Point`, with a `radius` foreign member in meters. See [`CNFQueryable::latlng_within`]
+    /// - `Polygon`. See [`CNFQueryable::latlng_in_polygon`]
+    /// - `MultiPolygon`. See [`CNFQueryable::latlng_in_multipolygon`]
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let geometry: geojson::Geometry = r#"{
+    ///     "type": "Point", "coordinates": [2.349014, 48.864716], "radius": 1000
+    /// }"#.parse().unwrap();
+    /// let q = Query::from_geojson("position", &geometry).unwrap();
+    /// assert!(q.matches(&[("position", "48.859430,2.354946")].into()));
+    /// ```
+    pub fn from_geojson<T: Into<OurStr>>(field: T, geometry: &Geometry) -> Result<Query, String> {
+        match &geometry.value {
+            GeometryValue::Point { coordinates } => {
+                let radius = geometry
+                    .foreign_members
+                    .as_ref()
+                    .and_then(|fm| fm.get("radius"))
+                    .and_then(|r| r.as_f64())
+                    .filter(|r| r.is_finite() && *r >= 0.0)
+                    .ok_or_else(|| "A Point needs a positive radius in meters".to_string())?;
+                Ok(field
+                    .into()
+                    .latlng_within(to_latlng(coordinates)?, Meters(radius.round() as u64)))
+            }
+            GeometryValue::Polygon { coordinates } => {
+                Ok(field.into().latlng_in_polygon(&to_polygon(coordinates)?))
+            }
+            GeometryValue::MultiPolygon { coordinates } if !coordinates.is_empty() => {
+                let polygons = coordinates
+                    .iter()
+                    .map(|p| to_polygon(p))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(field.into().latlng_in_multipolygon(&polygons))
+            }
+            GeometryValue::MultiPolygon { .. } => Err("Empty MultiPolygon".to_string()),
+            other => Err(format!("Unsupported geometry {}", other.type_name())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometry(s: &str) -> Geometry {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_point() {
+        let q = Query::from_geojson(
+            "p",
+            &geometry(r#"{"type": "Point", "coordinates": [2.349014, 48.864716], "radius": 1000}"#),
+        )
+        .unwrap();
+        assert_eq!(
+            q,
+            "p".latlng_within(LatLng::new(48.864716, 2.349014).unwrap(), Meters(1000))
+        );
+
+        assert!(
+            Query::from_geojson(
+                "p",
+                &geometry(r#"{"type": "Point", "coordinates": [2.349014, 48.864716]}"#)
+            )
+            .is_err()
+        );
+        assert!(
+            Query::from_geojson(
+                "p",
+                &geometry(r#"{"type": "Point", "coordinates": [2.3, 48.8], "radius": -1}"#)
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_polygons() {
+        let donut = geometry(
+            r#"{"type": "Polygon", "coordinates": [
+                [[0, 0], [1, 0], [1, 1], [0, 1], [0, 0]],
+                [[0.4, 0.4], [0.6, 0.4], [0.6, 0.6], [0.4, 0.6], [0.4, 0.4]]
+            ]}"#,
+        );
+        let q = Query::from_geojson("p", &donut).unwrap();
+        assert!(q.matches(&[("p", "0.1,0.2")].into()));
+        assert!(!q.matches(&[("p", "0.5,0.5")].into()));
+
+        let multi = geometry(
+            r#"{"type": "MultiPolygon", "coordinates": [
+                [[[0, 0], [1, 0], [1, 1], [0, 1], [0, 0]]],
+                [[[10, 10], [11, 10], [11, 11], [10, 11], [10, 10]]]
+            ]}"#,
+        );
+        let q = Query::from_geojson("p", &multi).unwrap();
+        assert!(q.matches(&[("p", "0.1,0.2")].into()));
+        assert!(q.matches(&[("p", "10.5,10.5")].into()));
+        assert!(!q.matches(&[("p", "5,5")].into()));
+
+        let bad = geometry(r#"{"type": "Polygon", "coordinates": [[[0, 0], [1, 0], [0, 0]]]}"#);
+        assert!(Query::from_geojson("p", &bad).is_err());
+        let empty = geometry(r#"{"type": "MultiPolygon", "coordinates": []}"#);
+        assert!(Query::from_geojson("p", &empty).is_err());
+        let line = geometry(r#"{"type": "LineString", "coordinates": [[0, 0], [1, 1]]}"#);
+        assert!(Query::from_geojson("p", &line).is_err());
+    }
+}
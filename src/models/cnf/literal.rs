@@ -1,35 +1,58 @@
 use std::{
     fmt::{self, Display},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
     str::FromStr,
 };
 
 use crate::models::{
     queries::{
         h3_inside::H3InsideQuery,
+        h3_inside_any::H3InsideAnyQuery,
+        ip_in::IpInQuery,
+        latlng_between::LatLngBetweenQuery,
+        latlng_in_polygon::LatLngInPolygonQuery,
         latlng_within::{LatLngWithinQuery, parse_latlng},
+        latlng_within_any::LatLngWithinAnyQuery,
     },
     types::{OurRc, OurStr},
 };
 
-use h3o::CellIndex;
+use h3o::{CellIndex, LatLng};
+use ipnet::IpNet;
+use rand::seq::IteratorRandom;
+
+use crate::geotools::ring_contains;
 use itertools::Itertools;
+use nonempty::NonEmpty;
 use roaring::RoaringBitmap;
 
 use crate::{
-    itertools::{fibo_ceil, fibo_floor},
+    itertools::{fibo_ceil, fibo_floor, pow2_ceil, pow2_floor},
     models::{
-        cnf::Clause,
-        document::Document,
+        cnf::parsing::{escape_quote, escape_quote_field, escape_quote_term},
+        document::{Document, DocumentView},
         index::Index,
         percolator_core::{
             PercolatorConfig,
-            tools::{ClauseExpander, PreHeater},
+            tools::{ClauseExpander, PreHeater, field_terms},
         },
         queries::{
-            common::DocMatcher,
-            ordered::{I64Query, OrderedQuery, Ordering},
+            common::{DocMatcher, case_fold, normalize},
+            contains::ContainsQuery,
+            count::CountGeQuery,
+            exists::ExistsQuery,
+            missing::MissingQuery,
+            nested::NestedQuery,
+            ordered::{F64Query, I64Query, I128Query, OrderedQuery, Ordering},
             prefix::PrefixQuery,
+            range::{F64RangeQuery, I64RangeQuery},
+            regex::RegexQuery,
+            semver::SemverGeQuery,
+            str_range::StrRangeQuery,
+            suffix::SuffixQuery,
             term::TermQuery,
+            term_set::TermSetQuery,
+            text_match::{Analyzer, MatchQuery},
         },
     },
 };
@@ -49,72 +72,239 @@ fn safe_prefix(s: &str, len: usize) -> std::borrow::Cow<'_, str> {
         ))
 }
 
-fn latlngwithin_preheater(llq: &LatLngWithinQuery) -> PreHeater {
-    let qfield = llq.field();
-    let resolution = llq.resolution();
-
-    let litfield: OurStr = format!("__H3_IN_{}_{}", qfield, resolution).into();
+// Preheater for lat,lng queries indexed as a set of cells
+// (see h3_cells_to_fvs). Shared by all lat,lng shapes
+// with the same field and cell resolutions.
+fn latlng_cells_preheater(qfield: OurStr, cells: &NonEmpty<CellIndex>) -> PreHeater {
+    // The indexed cells are compacted, so they can be
+    // of any resolution up to the finest one.
+    let litfields = cells
+        .iter()
+        .map(|cell| cell.resolution())
+        .unique()
+        .sorted()
+        .map(|res| (res, OurStr::from(format!("__H3_IN_{}_{}", qfield, res))))
+        .collect_vec();
+    // Safe, as cells is not empty.
+    let resolution = litfields.last().map(|(res, _)| *res).unwrap();
+
+    let id_preheater = format!(
+        "LATLNG_AT_RES_{}__{}",
+        qfield,
+        litfields.iter().map(|(res, _)| res).join("_")
+    )
+    .into();
 
     // We are going to run what looks like a lat,lng field
-    // into a h3 cell at the given resolution
-    let expander = move |mut c: Clause| {
-        let new_literals = c
-            .term_queries_iter()
-            .filter_map(|tq| {
-                (tq.field() == qfield) // Good original field.
-                    .then_some(tq.term()) // Focus on the term
-                    .and_then(|v| parse_latlng(v.as_ref())) // Parse as lat,lng if possible.
-            }) // Ok we have LatLng from the good field.
-            .map(|ll| ll.to_cell(resolution)) // Map to a cell at the same resolution of the index.
-            // Then make a new Term query with the right format
-            .map(|ci| TermQuery::new(litfield.clone(), ci.to_string()))
-            .map(|q| Literal::new(false, LitQuery::Term(q)))
-            .collect_vec();
-        c.append_literals(new_literals);
-        c
+    // into a h3 cell at the finest resolution, and then its
+    // ancestors at the other resolutions.
+    let expander = move |terms: &[(&str, &str)]| {
+        field_terms(terms, &qfield) // Good original field.
+            .filter_map(parse_latlng) // Parse as lat,lng if possible.
+            // Ok we have LatLng from the good field.
+            .map(|ll| ll.to_cell(resolution)) // Map to a cell at the finest resolution of the index.
+            .flat_map(|ci| {
+                litfields.iter().filter_map(move |(res, litfield)| {
+                    ci.parent(*res)
+                        .map(|ancestor| TermQuery::new(litfield.clone(), ancestor.to_string()))
+                })
+            })
+            .collect_vec()
     };
 
-    let id_preheater = format!("LATLNGWITHIN_AT_RES_{}__{}", llq.field(), resolution).into();
     // We want must filter to do some exact matching.
     PreHeater::new(id_preheater, ClauseExpander::new(OurRc::new(expander))).with_must_filter(true)
 }
 
-fn h3in_query_preheater(h3i: &H3InsideQuery) -> PreHeater {
-    let qfield = h3i.field();
-    let qcell = h3i.cell();
-    let litfield: OurStr = format!("__H3_IN_{}_{}", qfield, qcell.resolution()).into();
+fn latlngwithin_preheater(llq: &LatLngWithinQuery, config: &PercolatorConfig) -> PreHeater {
+    latlng_cells_preheater(llq.field(), &llq.h3_cells(config.h3_max_resolution()))
+}
 
-    // The expander looks at each of the litteral values of the clause
-    // for the field and adds the new Term litterals
-    // to match the __H3IN_.. indexed field at the right resolution.
-    let expander = move |mut c: Clause| {
-        let new_literals = c
-            .term_queries_iter()
-            // Filter the right field
-            // and parse term to CellIndex
-            .filter_map(|tq| {
-                (tq.field() == qfield)
-                    .then_some(tq.term())
-                    .and_then(|v| v.parse::<CellIndex>().ok())
-            })
-            // Then upgrade the cell to the resolution of the potential parent
-            .filter_map(|ci| ci.parent(qcell.resolution()))
-            // Then make a new Term query with the right format
-            .map(|upgraded_ci| TermQuery::new(litfield.clone(), upgraded_ci.to_string()))
-            .map(|q| Literal::new(false, LitQuery::Term(q)))
-            .collect_vec();
+fn latlngwithinany_preheater(llw: &LatLngWithinAnyQuery, config: &PercolatorConfig) -> PreHeater {
+    latlng_cells_preheater(llw.field(), &llw.h3_cells(config.h3_max_resolution()))
+}
 
-        c.append_literals(new_literals);
-        c
+fn latlngbetween_preheater(llb: &LatLngBetweenQuery, config: &PercolatorConfig) -> PreHeater {
+    latlng_cells_preheater(llb.field(), &llb.h3_cells(config.h3_max_resolution()))
+}
+
+fn latlnginpolygon_preheater(llp: &LatLngInPolygonQuery, config: &PercolatorConfig) -> PreHeater {
+    latlng_cells_preheater(llp.field(), &llp.h3_cells(config.h3_max_resolution()))
+}
+
+// The synthetic field and value indexing exists queries.
+fn exists_indexed_fv(field: &OurStr) -> (OurStr, OurStr) {
+    (format!("__EXISTS__{}", field).into(), "true".into())
+}
+
+fn exists_query_preheater(eq: &ExistsQuery) -> PreHeater {
+    let qfield = eq.field();
+    let (synth_field, synth_value) = exists_indexed_fv(&qfield);
+    let id_preheater = synth_field.clone();
+
+    // Any value for the field means it exists.
+    let expander = move |terms: &[(&str, &str)]| {
+        if field_terms(terms, &qfield).next().is_some() {
+            vec![TermQuery::new(synth_field.clone(), synth_value.clone())]
+        } else {
+            vec![]
+        }
     };
 
-    let id_preheater = format!("H3IN_{}__{}", h3i.field(), qcell.resolution()).into();
+    // This is exact.
+    PreHeater::new(id_preheater, ClauseExpander::new(OurRc::new(expander))).with_must_filter(false)
+}
 
+// The synthetic field and value indexing missing queries.
+fn missing_indexed_fv(field: &OurStr) -> (OurStr, OurStr) {
+    (format!("__MISSING__{}", field).into(), "true".into())
+}
+
+fn missing_query_preheater(mq: &MissingQuery) -> PreHeater {
+    let qfield = mq.field();
+    let (synth_field, synth_value) = missing_indexed_fv(&qfield);
+    let id_preheater = synth_field.clone();
+
+    // No value at all for the field means it is missing.
+    let expander = move |terms: &[(&str, &str)]| {
+        if field_terms(terms, &qfield).next().is_none() {
+            vec![TermQuery::new(synth_field.clone(), synth_value.clone())]
+        } else {
+            vec![]
+        }
+    };
+
+    // This is exact too.
+    PreHeater::new(id_preheater, ClauseExpander::new(OurRc::new(expander))).with_must_filter(false)
+}
+
+// The synthetic field indexing count queries at their bucket.
+fn count_ge_indexed_name(field: &OurStr, bucket: usize) -> OurStr {
+    format!("__COUNT_GE_{}__{}", bucket, field).into()
+}
+
+fn count_ge_query_preheater(cq: &CountGeQuery) -> PreHeater {
+    let qfield = cq.field();
+    let bucket = cq.bucket();
+    let synth_field = count_ge_indexed_name(&qfield, bucket);
+    let id_preheater = format!("COUNT_GE_{}__{}", bucket, qfield).into();
+
+    // Documents with at least bucket distinct values for the field.
+    let expander = move |terms: &[(&str, &str)]| {
+        let count = field_terms(terms, &qfield).unique().count();
+        if count >= bucket {
+            vec![TermQuery::new(synth_field.clone(), "true")]
+        } else {
+            vec![]
+        }
+    };
+
+    // Exact only for minimums that are their own bucket.
+    PreHeater::new(id_preheater, ClauseExpander::new(OurRc::new(expander)))
+        .with_must_filter(bucket != cq.min())
+}
+
+// Case insensitive queries are indexed on the folded
+// values of the field, under their own synthetic field.
+fn casefold_field(field: &OurStr) -> OurStr {
+    format!("__CASEFOLD__{}", field).into()
+}
+
+// Same for normalized term queries, with the trimmed and folded values.
+fn normalized_field(field: &OurStr) -> OurStr {
+    format!("__NORMALIZED__{}", field).into()
+}
+
+fn normalized_term_preheater(tq: &TermQuery) -> PreHeater {
+    let qfield = tq.field();
+    let synth_field = normalized_field(&qfield);
+    let id_preheater = synth_field.clone();
+
+    let expander = move |terms: &[(&str, &str)]| {
+        field_terms(terms, &qfield)
+            .map(|v| TermQuery::new(synth_field.clone(), normalize(v)))
+            .collect_vec()
+    };
+
+    // Normalizing both sides is exact.
     PreHeater::new(id_preheater, ClauseExpander::new(OurRc::new(expander))).with_must_filter(false)
 }
 
+fn casefold_term_preheater(tq: &TermQuery) -> PreHeater {
+    let qfield = tq.field();
+    let synth_field = casefold_field(&qfield);
+    let id_preheater = synth_field.clone();
+
+    let expander = move |terms: &[(&str, &str)]| {
+        field_terms(terms, &qfield)
+            .map(|v| TermQuery::new(synth_field.clone(), case_fold(v)))
+            .collect_vec()
+    };
+
+    // Folding both sides is exact.
+    PreHeater::new(id_preheater, ClauseExpander::new(OurRc::new(expander))).with_must_filter(false)
+}
+
+fn h3in_query_preheater(h3i: &H3InsideQuery, config: &PercolatorConfig) -> PreHeater {
+    // A cell finer than the max resolution is indexed as its ancestor,
+    // so the candidates will need a final check.
+    let qcell = h3i.cell_upto(config.h3_max_resolution());
+    h3_cells_preheater(h3i.field(), &[qcell], qcell != h3i.cell())
+}
+
+fn h3in_any_query_preheater(h3a: &H3InsideAnyQuery, config: &PercolatorConfig) -> PreHeater {
+    let qcells = h3a.cells_upto(config.h3_max_resolution());
+    h3_cells_preheater(h3a.field(), &qcells, qcells != h3a.cells())
+}
+
+// Preheater for H3 cell queries indexed at the resolutions of
+// the given cells (see h3_cells_to_fvs).
+fn h3_cells_preheater(qfield: OurStr, qcells: &[CellIndex], must_filter: bool) -> PreHeater {
+    let litfields = qcells
+        .iter()
+        .map(|cell| cell.resolution())
+        .unique()
+        .sorted()
+        .map(|res| (res, OurStr::from(format!("__H3_IN_{}_{}", qfield, res))))
+        .collect_vec();
+
+    let id_preheater = format!(
+        "H3IN_{}__{}",
+        qfield,
+        litfields.iter().map(|(res, _)| res).join("_")
+    )
+    .into();
+
+    // The expander looks at each of the litteral values of the clause
+    // for the field and adds the new Term litterals
+    // to match the __H3IN_.. indexed fields at the right resolutions.
+    let expander = move |terms: &[(&str, &str)]| {
+        field_terms(terms, &qfield)
+            // Parse the values of the right field to CellIndex
+            .filter_map(|v| v.parse::<CellIndex>().ok())
+            // Then upgrade the cell to the resolutions of the potential parents
+            // and make new Term queries with the right format
+            .flat_map(|ci| {
+                litfields.iter().filter_map(move |(res, litfield)| {
+                    ci.parent(*res).map(|upgraded_ci| {
+                        TermQuery::new(litfield.clone(), upgraded_ci.to_string())
+                    })
+                })
+            })
+            .collect_vec()
+    };
+
+    PreHeater::new(id_preheater, ClauseExpander::new(OurRc::new(expander)))
+        .with_must_filter(must_filter)
+}
+
 // Preheater for interger comparison queries.
-fn intcmp_query_preheater(oq: &I64Query) -> PreHeater {
+// The kind keeps the i64 and i128 comparisons apart, as
+// they do not parse the same values.
+fn intcmp_query_preheater<T>(kind: &'static str, oq: &OrderedQuery<T>) -> PreHeater
+where
+    T: PartialOrd + FromStr + crate::itertools::Fiboable + Display + Send + Sync + 'static,
+{
     // ["LT", "EQ", "GT"]
     // synth_field: Rc<str> = format!("__INT_{}_{}__{}", c, oq.cmp_point(), oq.field()).into();
     let oq_field = oq.field();
@@ -125,21 +315,16 @@ fn intcmp_query_preheater(oq: &I64Query) -> PreHeater {
     };
     let indexed_name: OurStr = match oq_ord {
         Ordering::LT | Ordering::LE | Ordering::EQ => {
-            format!("__INT_LE_{}__{}", cmp_point, oq_field)
+            format!("__{}_LE_{}__{}", kind, cmp_point, oq_field)
         }
-        Ordering::GT | Ordering::GE => format!("__INT_GE_{}__{}", cmp_point, oq_field),
+        Ordering::GT | Ordering::GE => format!("__{}_GE_{}__{}", kind, cmp_point, oq_field),
     }
     .into();
 
-    let expander = move |mut c: Clause| {
-        // This clause comes from a document. Find the right field
-        let new_literals = c
-            .term_queries_iter()
-            .filter_map(|tq| {
-                (tq.field() == oq_field)
-                    .then_some(tq.term())
-                    .and_then(|v| v.parse::<i64>().ok())
-            })
+    let expander = move |terms: &[(&str, &str)]| {
+        // These terms come from a document. Find the right field
+        field_terms(terms, &oq_field)
+            .filter_map(|v| v.parse::<T>().ok())
             // At this point, we have a parseable integer value
             // from the right field.
             .filter_map(|iv|
@@ -154,15 +339,107 @@ fn intcmp_query_preheater(oq: &I64Query) -> PreHeater {
                     _ => None,
                 })
             .map(|indexed_name| TermQuery::new(indexed_name, "true"))
-            .map(|q| Literal::new(false, LitQuery::Term(q)))
-            .collect_vec();
+            .collect_vec()
+    };
 
-        c.append_literals(new_literals);
-        c
+    // INT_COMPARE is the name of the preheater. It must carry the direction,
+    // as LE and GE preheaters can share the same comparison point.
+    let id_field = format!(
+        "{}_COMPARE_{}_{}__{}",
+        kind,
+        cmp_dir(oq_ord),
+        cmp_point,
+        oq.field()
+    )
+    .into();
+    PreHeater::new(id_field, ClauseExpander::new(OurRc::new(expander))).with_must_filter(true)
+}
+
+// The direction of the synthetic fields for ordered queries.
+fn cmp_dir(ord: Ordering) -> &'static str {
+    match ord {
+        Ordering::LT | Ordering::LE | Ordering::EQ => "LE",
+        Ordering::GT | Ordering::GE => "GE",
+    }
+}
+
+// The bucket a float comparison point falls in.
+// Floats can be way out of the i64 range, so they
+// are bucketed on powers of two instead of Fibonacci numbers.
+fn float_bucket(oq: &F64Query) -> f64 {
+    match oq.cmp_ord() {
+        Ordering::LT | Ordering::LE | Ordering::EQ => pow2_ceil(*oq.cmp_point()),
+        Ordering::GT | Ordering::GE => pow2_floor(*oq.cmp_point()),
+    }
+}
+
+fn float_indexed_name(oq: &F64Query) -> OurStr {
+    format!(
+        "__FLT_{}_{}__{}",
+        cmp_dir(oq.cmp_ord()),
+        float_bucket(oq),
+        oq.field()
+    )
+    .into()
+}
+
+// Preheater for float comparison queries.
+// Same principle as the integer one, with different buckets.
+fn floatcmp_query_preheater(oq: &F64Query) -> PreHeater {
+    let oq_field = oq.field();
+    let oq_ord = oq.cmp_ord();
+    let bucket = float_bucket(oq);
+    let indexed_name = float_indexed_name(oq);
+
+    let expander = move |terms: &[(&str, &str)]| {
+        field_terms(terms, &oq_field)
+            .filter_map(|v| v.parse::<f64>().ok())
+            .filter(|fv| match oq_ord {
+                Ordering::LT | Ordering::LE | Ordering::EQ => *fv <= bucket,
+                Ordering::GT | Ordering::GE => *fv >= bucket,
+            })
+            .map(|_| TermQuery::new(indexed_name.clone(), "true"))
+            .collect_vec()
+    };
+
+    let id_field = format!("FLT_COMPARE_{}_{}__{}", cmp_dir(oq_ord), bucket, oq.field()).into();
+    PreHeater::new(id_field, ClauseExpander::new(OurRc::new(expander))).with_must_filter(true)
+}
+
+// The buckets integer range queries are indexed between.
+fn int_range_bounds(rq: &I64RangeQuery) -> (i64, i64) {
+    (fibo_floor(*rq.low()), fibo_ceil(*rq.high()))
+}
+
+// Same with the float buckets.
+fn float_range_bounds(rq: &F64RangeQuery) -> (f64, f64) {
+    (pow2_floor(*rq.low()), pow2_ceil(*rq.high()))
+}
+
+// A range query is indexed as one synthetic field for both
+// its bucketed bounds, so it needs only one preheater.
+fn range_indexed_name<T: Display>(kind: &str, bounds: (T, T), field: &OurStr) -> OurStr {
+    format!("__{}_RANGE_{}_{}__{}", kind, bounds.0, bounds.1, field).into()
+}
+
+// Preheater for range queries. Any value of the field between
+// the bucketed bounds makes the document a candidate.
+fn range_query_preheater<T>(kind: &str, bounds: (T, T), rq_field: OurStr) -> PreHeater
+where
+    T: PartialOrd + FromStr + Display + Copy + Send + Sync + 'static,
+{
+    let indexed_name = range_indexed_name(kind, bounds, &rq_field);
+    let id_field = format!("{}_RANGE_{}_{}__{}", kind, bounds.0, bounds.1, rq_field).into();
+    let (low, high) = bounds;
+
+    let expander = move |terms: &[(&str, &str)]| {
+        field_terms(terms, &rq_field)
+            .filter_map(|v| v.parse::<T>().ok())
+            .filter(|v| low <= *v && *v <= high)
+            .map(|_| TermQuery::new(indexed_name.clone(), "true"))
+            .collect_vec()
     };
 
-    // INT_COMPARE is the name of the preheater.
-    let id_field = format!("INT_COMPARE_{}__{}", cmp_point, oq.field()).into();
     PreHeater::new(id_field, ClauseExpander::new(OurRc::new(expander))).with_must_filter(true)
 }
 
@@ -170,41 +447,293 @@ fn prefix_query_preheater(allowed_size: &[usize], pq: &PrefixQuery) -> PreHeater
     let clipped_len = clip_prefix_len(allowed_size, pq.prefix().len());
 
     let pfield = pq.field().clone();
-    let synth_field: OurStr = format!("__PREFIX{}__{}", clipped_len, pq.field()).into();
+    let fold = pq.is_case_folded();
+    let synth_field: OurStr = format!("__PREFIX{}__{}", clipped_len, prefix_field(pq)).into();
     let id_field = synth_field.clone();
 
-    let expander = move |mut c: Clause| {
-        // Find all term queries with the given field, where the term is actually at least
+    let expander = move |terms: &[(&str, &str)]| {
+        // Find all the values of the given field, where the value is actually at least
         // as long as the prefix
         // Then turn them into term queries with the synthetic field name
-        let new_literals = c
-            .term_queries_iter()
-            .filter(|&tq| tq.field() == pfield && tq.term().len() >= clipped_len)
-            .map(|tq| {
-                TermQuery::new(
-                    synth_field.clone(),
-                    safe_prefix(tq.term().as_ref(), clipped_len),
-                )
+        field_terms(terms, &pfield)
+            .map(|v| match fold {
+                true => std::borrow::Cow::Owned(case_fold(v)),
+                false => std::borrow::Cow::Borrowed(v),
             })
-            .map(|q| Literal::new(false, LitQuery::Term(q)))
-            .collect_vec();
-
-        c.append_literals(new_literals);
-        c
+            .filter(|t| t.len() >= clipped_len)
+            .map(|t| TermQuery::new(synth_field.clone(), safe_prefix(t.as_ref(), clipped_len)))
+            .collect_vec()
     };
 
     PreHeater::new(id_field, ClauseExpander::new(OurRc::new(expander)))
         .with_must_filter(clipped_len < pq.prefix().len())
 }
 
+// The suffix, reversed, and clipped like a prefix to the last
+// characters of the suffix.
+fn reversed_suffix(s: &str, len: usize) -> String {
+    s.chars().rev().take(len).collect()
+}
+
+fn suffix_query_preheater(allowed_size: &[usize], sq: &SuffixQuery) -> PreHeater {
+    let suffix_len = sq.suffix().chars().count();
+    let clipped_len = clip_prefix_len(allowed_size, suffix_len);
+
+    let sfield = sq.field().clone();
+    let synth_field: OurStr = format!("__SUFFIX{}__{}", clipped_len, sq.field()).into();
+    let id_field = synth_field.clone();
+
+    let expander = move |terms: &[(&str, &str)]| {
+        // Like prefixes, on the reversed values of the field.
+        field_terms(terms, &sfield)
+            .filter(|v| v.chars().count() >= clipped_len)
+            .map(|v| TermQuery::new(synth_field.clone(), reversed_suffix(v, clipped_len)))
+            .collect_vec()
+    };
+
+    PreHeater::new(id_field, ClauseExpander::new(OurRc::new(expander)))
+        .with_must_filter(clipped_len < suffix_len)
+}
+
+// All the windows of len characters of s, or just
+// an empty one when len is 0.
+fn ngrams(s: &str, len: usize) -> Vec<String> {
+    if len == 0 {
+        return vec![String::new()];
+    }
+    s.chars()
+        .collect_vec()
+        .windows(len)
+        .map(|w| w.iter().collect())
+        .collect()
+}
+
+// The n-grams are as long as the substring when it is shorter.
+fn ngram_len(cq: &ContainsQuery, config: &PercolatorConfig) -> usize {
+    cq.substring()
+        .chars()
+        .count()
+        .min(config.ngram_size().get())
+}
+
+fn contains_query_preheater(cq: &ContainsQuery, config: &PercolatorConfig) -> PreHeater {
+    let gram_len = ngram_len(cq, config);
+
+    let cfield = cq.field().clone();
+    let synth_field: OurStr = format!("__NGRAM{}__{}", gram_len, cq.field()).into();
+    let id_field = synth_field.clone();
+
+    let expander = move |terms: &[(&str, &str)]| {
+        // Every distinct n-gram of the values of the field.
+        field_terms(terms, &cfield)
+            .flat_map(|v| ngrams(v, gram_len))
+            .unique()
+            .map(|g| TermQuery::new(synth_field.clone(), g))
+            .collect_vec()
+    };
+
+    // Only a substring as long as its n-grams is one of them.
+    PreHeater::new(id_field, ClauseExpander::new(OurRc::new(expander)))
+        .with_must_filter(gram_len < cq.substring().chars().count())
+}
+
+// Where the words of the field values are indexed.
+fn word_field(field: &OurStr, analyzer: Analyzer) -> OurStr {
+    format!("__WORD_{}__{}", analyzer, field).into()
+}
+
+fn match_query_preheater(mq: &MatchQuery) -> PreHeater {
+    let mfield = mq.field();
+    let analyzer = mq.analyzer();
+    let synth_field = word_field(&mfield, analyzer);
+    let id_field = synth_field.clone();
+
+    let expander = move |terms: &[(&str, &str)]| {
+        // Every distinct word of the values of the field.
+        field_terms(terms, &mfield)
+            .flat_map(|v| analyzer.tokens(v))
+            .unique()
+            .map(|w| TermQuery::new(synth_field.clone(), w))
+            .collect_vec()
+    };
+
+    // The query word is one of the analyzed words, so this is exact.
+    PreHeater::new(id_field, ClauseExpander::new(OurRc::new(expander))).with_must_filter(false)
+}
+
+// The mask lengths IP networks are indexed at, like the prefix sizes of strings.
+const IPV4_MASK_LENS: [usize; 4] = [8, 16, 24, 32];
+const IPV6_MASK_LENS: [usize; 5] = [16, 32, 48, 64, 128];
+
+// The mask length an IP network query is indexed at,
+// and the synthetic field of its family at that length.
+fn ip_indexing(iq: &IpInQuery) -> (u8, OurStr) {
+    let net = iq.net();
+    let (family, lens) = match net {
+        IpNet::V4(_) => (4, &IPV4_MASK_LENS[..]),
+        IpNet::V6(_) => (6, &IPV6_MASK_LENS[..]),
+    };
+    let len = clip_prefix_len(lens, net.prefix_len() as usize) as u8;
+    (
+        len,
+        format!("__IP{}_{}__{}", family, len, iq.field()).into(),
+    )
+}
+
+// The network of this length the address is in, as an indexed term.
+fn ip_network(addr: IpAddr, len: u8) -> OurStr {
+    IpNet::new(addr, len)
+        .expect("Mask length should fit the address family")
+        .network()
+        .to_string()
+        .into()
+}
+
+fn ip_in_query_preheater(iq: &IpInQuery) -> PreHeater {
+    let qfield = iq.field();
+    let is_ipv4 = iq.net().addr().is_ipv4();
+    let (len, synth_field) = ip_indexing(iq);
+    // The network is indexed at a shorter mask length,
+    // so the candidates will need a final check.
+    let must_filter = len < iq.net().prefix_len();
+    let id_preheater = synth_field.clone();
+
+    let expander = move |terms: &[(&str, &str)]| {
+        field_terms(terms, &qfield)
+            .filter_map(|v| v.parse::<IpAddr>().ok())
+            .filter(|a| a.is_ipv4() == is_ipv4)
+            .map(|a| TermQuery::new(synth_field.clone(), ip_network(a, len)))
+            .collect_vec()
+    };
+
+    PreHeater::new(id_preheater, ClauseExpander::new(OurRc::new(expander)))
+        .with_must_filter(must_filter)
+}
+
+// Semantic versions are indexed by major version.
+fn semver_indexed_name(field: &OurStr, major: u64) -> OurStr {
+    format!("__SEMVER_GE_{}__{}", major, field).into()
+}
+
+fn semver_query_preheater(sq: &SemverGeQuery) -> PreHeater {
+    let qfield = sq.field();
+    let major = sq.version().major;
+    let synth_field = semver_indexed_name(&qfield, major);
+    let id_preheater = synth_field.clone();
+
+    // Versions of this major version or later ones,
+    // including the pre-releases of its first one.
+    let expander = move |terms: &[(&str, &str)]| {
+        if field_terms(terms, &qfield)
+            .filter_map(|v| semver::Version::parse(v).ok())
+            .any(|v| v.major >= major)
+        {
+            vec![TermQuery::new(synth_field.clone(), "true")]
+        } else {
+            vec![]
+        }
+    };
+
+    // Earlier versions of the same major version are candidates too.
+    PreHeater::new(id_preheater, ClauseExpander::new(OurRc::new(expander))).with_must_filter(true)
+}
+
+// The field of a string range is the same for all the queries
+// with the same bounds. Quoting the bounds keeps them apart.
+fn str_range_indexed_name(sq: &StrRangeQuery) -> OurStr {
+    format!("__STR_RANGE_{:?}_{:?}__{}", sq.low(), sq.high(), sq.field()).into()
+}
+
+// The expander compares the values with the bounds, so
+// the candidates need no final check.
+fn str_range_query_preheater(sq: &StrRangeQuery) -> PreHeater {
+    let sq = sq.clone();
+    let synth_field = str_range_indexed_name(&sq);
+    let id_preheater = synth_field.clone();
+    let qfield = sq.field();
+
+    let expander = move |terms: &[(&str, &str)]| {
+        if field_terms(terms, &qfield).any(|v| sq.contains(v)) {
+            vec![TermQuery::new(synth_field.clone(), "true")]
+        } else {
+            vec![]
+        }
+    };
+
+    PreHeater::new(id_preheater, ClauseExpander::new(OurRc::new(expander)))
+}
+
+// All the queries on the same path share the field, and
+// the preheater finds documents with elements there.
+fn nested_indexed_name(path: &OurStr) -> OurStr {
+    format!("__NESTED__{}", path).into()
+}
+
+// Any flattened value of an element makes the document a candidate.
+// The elements are only in the document, so candidates need checking.
+fn nested_query_preheater(nq: &NestedQuery) -> PreHeater {
+    let synth_field = nested_indexed_name(&nq.path());
+    let id_preheater = synth_field.clone();
+    let prefix = format!("{}.", nq.path());
+
+    let expander = move |terms: &[(&str, &str)]| {
+        if terms.iter().any(|(f, _)| f.starts_with(&prefix)) {
+            vec![TermQuery::new(synth_field.clone(), "true")]
+        } else {
+            vec![]
+        }
+    };
+
+    PreHeater::new(id_preheater, ClauseExpander::new(OurRc::new(expander))).with_must_filter(true)
+}
+
+// An address of the network, with random host bits.
+fn random_ip_in<R: rand::Rng>(rng: &mut R, net: &IpNet) -> IpAddr {
+    match net {
+        IpNet::V4(n) => IpAddr::V4(Ipv4Addr::from(
+            u32::from(n.network()) | (rng.random::<u32>() & u32::from(n.hostmask())),
+        )),
+        IpNet::V6(n) => IpAddr::V6(Ipv6Addr::from(
+            u128::from(n.network()) | (rng.random::<u128>() & u128::from(n.hostmask())),
+        )),
+    }
+}
+
+fn random_word<R: rand::Rng>(rng: &mut R, len: std::ops::Range<usize>) -> String {
+    let len = rng.random_range(len);
+    (0..len)
+        .map(|_| char::from(rng.sample(rand::distr::Alphanumeric)))
+        .collect()
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) enum LitQuery {
     Term(TermQuery),
+    TermSet(TermSetQuery),
     Prefix(PrefixQuery),
+    Suffix(SuffixQuery),
+    Contains(ContainsQuery),
+    Match(MatchQuery),
+    Regex(RegexQuery),
+    Exists(ExistsQuery),
+    Missing(MissingQuery),
+    CountGe(CountGeQuery),
     IntQuery(I64Query),
+    FloatQuery(F64Query),
+    BigIntQuery(I128Query),
+    IntRange(I64RangeQuery),
+    FloatRange(F64RangeQuery),
     H3Inside(H3InsideQuery),
+    H3InsideAny(H3InsideAnyQuery),
     LatLngWithin(LatLngWithinQuery),
+    LatLngWithinAny(LatLngWithinAnyQuery),
+    LatLngBetween(LatLngBetweenQuery),
+    LatLngInPolygon(LatLngInPolygonQuery),
+    IpIn(IpInQuery),
+    SemverGe(SemverGeQuery),
+    StrRange(StrRangeQuery),
+    Nested(NestedQuery),
 }
 
 impl LitQuery {
@@ -213,25 +742,68 @@ impl LitQuery {
     /// Prefix and IntQuery are expansive
     fn cost(&self) -> u32 {
         match self {
-            LitQuery::Term(_) => 10,
-            LitQuery::Prefix(_) => 1000,   // Will have some preheating
-            LitQuery::IntQuery(_) => 1000, // Will have some preheating
-            LitQuery::H3Inside(_) => 900,  // Will have some preheating, but faster than others.
+            // Like an exists query.
+            LitQuery::Term(tq) if tq.is_case_folded() || tq.is_normalized() => 500,
+            // A term set is indexed like terms, one per value.
+            LitQuery::Term(_) | LitQuery::TermSet(_) => 10,
+            LitQuery::Prefix(_) => 1000,     // Will have some preheating
+            LitQuery::Suffix(_) => 1000,     // Same
+            LitQuery::Contains(_) => 1200,   // Preheats all the n-grams, plus a post check
+            LitQuery::Match(_) => 600,       // Preheats all the words, but no check.
+            LitQuery::Regex(_) => 1100,      // Like a prefix, plus a post check
+            LitQuery::Exists(_) => 500,      // Will have some preheating, but no check.
+            LitQuery::Missing(_) => 500,     // Same
+            LitQuery::CountGe(_) => 1000,    // Will have some preheating, sometimes a post check
+            LitQuery::IntQuery(_) => 1000,   // Will have some preheating
+            LitQuery::FloatQuery(_) => 1000, // Will have some preheating
+            LitQuery::BigIntQuery(_) => 1000, // Same
+            LitQuery::IntRange(_) => 1000,   // Same, but only one literal for both bounds
+            LitQuery::FloatRange(_) => 1000, // Same
+            LitQuery::H3Inside(_) => 900,    // Will have some preheating, but faster than others.
+            LitQuery::H3InsideAny(_) => 900, // Same, one preheater for all the cells.
             LitQuery::LatLngWithin(_) => 1000, // Will have some preheating, but will have some post check
+            LitQuery::LatLngWithinAny(_) => 1000, // Same, one preheater for all the disks.
+            LitQuery::LatLngBetween(_) => 1000, // Same
+            LitQuery::LatLngInPolygon(_) => 1000, // Same
+            LitQuery::IpIn(_) => 1000,         // Like a prefix
+            LitQuery::SemverGe(_) => 1000,     // Like an integer comparison
+            LitQuery::StrRange(_) => 500,      // Like an exists query
+            LitQuery::Nested(_) => 1500,       // Always checked
         }
     }
 
     // Simple delegation.
-    fn matches(&self, d: &Document) -> bool {
+    fn matches(&self, d: &impl DocumentView) -> bool {
         match self {
             LitQuery::Term(tq) => tq.matches(d),
+            LitQuery::TermSet(tsq) => tsq.matches(d),
             LitQuery::Prefix(pq) => pq.matches(d),
+            LitQuery::Suffix(sq) => sq.matches(d),
+            LitQuery::Contains(cq) => cq.matches(d),
+            LitQuery::Match(mq) => mq.matches(d),
+            LitQuery::Regex(rq) => rq.matches(d),
+            LitQuery::Exists(eq) => eq.matches(d),
+            LitQuery::Missing(mq) => mq.matches(d),
+            LitQuery::CountGe(cq) => cq.matches(d),
             LitQuery::IntQuery(oq) => oq.matches(d),
+            LitQuery::FloatQuery(oq) => oq.matches(d),
+            LitQuery::BigIntQuery(oq) => oq.matches(d),
+            LitQuery::IntRange(rq) => rq.matches(d),
+            LitQuery::FloatRange(rq) => rq.matches(d),
             LitQuery::H3Inside(h3i) => h3i.matches(d),
+            LitQuery::H3InsideAny(h3a) => h3a.matches(d),
             LitQuery::LatLngWithin(llq) => llq.matches(d),
+            LitQuery::LatLngWithinAny(llw) => llw.matches(d),
+            LitQuery::LatLngBetween(llb) => llb.matches(d),
+            LitQuery::LatLngInPolygon(llp) => llp.matches(d),
+            LitQuery::IpIn(iq) => iq.matches(d),
+            LitQuery::SemverGe(sq) => sq.matches(d),
+            LitQuery::StrRange(sq) => sq.matches(d),
+            LitQuery::Nested(nq) => nq.matches(d),
         }
     }
 
+    #[cfg(test)]
     pub fn term_query(&self) -> Option<&TermQuery> {
         match self {
             LitQuery::Term(tq) => Some(tq),
@@ -239,6 +811,31 @@ impl LitQuery {
         }
     }
 
+    // The (field, value)s of a term or term set query, any of which
+    // a document can have to match it.
+    pub(crate) fn exact_field_values(&self) -> Option<Vec<(OurStr, OurStr)>> {
+        match self {
+            LitQuery::Term(tq) if tq.is_case_folded() || tq.is_normalized() => None,
+            LitQuery::Term(tq) => Some(vec![(tq.field(), tq.term())]),
+            LitQuery::TermSet(tsq) => Some(
+                tsq.terms()
+                    .iter()
+                    .map(|t| (tsq.field(), t.clone()))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    // The field of an integer comparison or range.
+    pub(crate) fn int_field(&self) -> Option<OurStr> {
+        match self {
+            LitQuery::IntQuery(oq) => Some(oq.field()),
+            LitQuery::IntRange(rq) => Some(rq.field()),
+            _ => None,
+        }
+    }
+
     pub fn prefix_query(&self) -> Option<&PrefixQuery> {
         match self {
             LitQuery::Prefix(pq) => Some(pq),
@@ -246,25 +843,225 @@ impl LitQuery {
         }
     }
 
-    // Just to order Litteral for display.
-    fn sort_field(&self) -> OurStr {
+    // A short name for the kind of query, for reports.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            LitQuery::Term(_) => "term",
+            LitQuery::TermSet(_) => "term_set",
+            LitQuery::Prefix(_) => "prefix",
+            LitQuery::Suffix(_) => "suffix",
+            LitQuery::Contains(_) => "contains",
+            LitQuery::Match(_) => "match",
+            LitQuery::Regex(_) => "regex",
+            LitQuery::Exists(_) => "exists",
+            LitQuery::Missing(_) => "missing",
+            LitQuery::CountGe(_) => "count_ge",
+            LitQuery::IntQuery(_) => "integer",
+            LitQuery::FloatQuery(_) => "float",
+            LitQuery::BigIntQuery(_) => "big_integer",
+            LitQuery::IntRange(_) => "integer_range",
+            LitQuery::FloatRange(_) => "float_range",
+            LitQuery::H3Inside(_) => "h3in",
+            LitQuery::H3InsideAny(_) => "h3in_any",
+            LitQuery::LatLngWithin(_) => "llwithin",
+            LitQuery::LatLngWithinAny(_) => "llwithin_any",
+            LitQuery::LatLngBetween(_) => "llbetween",
+            LitQuery::LatLngInPolygon(_) => "llinpolygon",
+            LitQuery::IpIn(_) => "ip_in",
+            LitQuery::SemverGe(_) => "semver_ge",
+            LitQuery::StrRange(_) => "str_range",
+            LitQuery::Nested(_) => "nested",
+        }
+    }
+
+    // A (field, value) a document can have to match this, if any.
+    // Only a candidate: comparisons at the edges of the number types
+    // or far away from the equator may not match it.
+    pub(crate) fn example_value<R: rand::Rng>(&self, rng: &mut R) -> Option<(OurStr, String)> {
+        let value = match self {
+            LitQuery::Term(tq) => tq.term().to_string(),
+            LitQuery::TermSet(tsq) => tsq.terms().iter().choose(rng)?.to_string(),
+            LitQuery::Prefix(pq) => format!("{}{}", pq.prefix(), random_word(rng, 0..5)),
+            LitQuery::Suffix(sq) => format!("{}{}", random_word(rng, 0..5), sq.suffix()),
+            LitQuery::Contains(cq) => format!(
+                "{}{}{}",
+                random_word(rng, 0..5),
+                cq.substring(),
+                random_word(rng, 0..5)
+            ),
+            LitQuery::Match(mq) => format!(
+                "{} {} {}",
+                random_word(rng, 0..5),
+                mq.token(),
+                random_word(rng, 0..5)
+            ),
+            LitQuery::Regex(rq) => rq.example_value(rng)?,
+            LitQuery::Exists(_) => random_word(rng, 1..9),
+            // One value is not enough.
+            LitQuery::Missing(_) | LitQuery::CountGe(_) => return None,
+            LitQuery::IntQuery(oq) => int_example_value(oq, rng.random_range(1..=100))?,
+            LitQuery::BigIntQuery(oq) => int_example_value(oq, rng.random_range(1..=100))?,
+            LitQuery::FloatQuery(oq) => {
+                let delta = rng.random_range(1.0..=100.0);
+                let point = *oq.cmp_point();
+                match oq.cmp_ord() {
+                    Ordering::GT | Ordering::GE => point + delta,
+                    Ordering::LT | Ordering::LE => point - delta,
+                    Ordering::EQ => point,
+                }
+                .to_string()
+            }
+            LitQuery::IntRange(rq) if rq.low() <= rq.high() => {
+                rng.random_range(*rq.low()..=*rq.high()).to_string()
+            }
+            LitQuery::FloatRange(rq) if rq.low() <= rq.high() => {
+                rng.random_range(*rq.low()..=*rq.high()).to_string()
+            }
+            LitQuery::IntRange(_) | LitQuery::FloatRange(_) => return None,
+            LitQuery::H3Inside(h3i) => {
+                let cell = h3i.cell();
+                let resolution = cell.resolution().succ().unwrap_or(cell.resolution());
+                cell.children(resolution).choose(rng)?.to_string()
+            }
+            LitQuery::H3InsideAny(h3a) => {
+                let cell = h3a.cells().iter().choose(rng)?;
+                let resolution = cell.resolution().succ().unwrap_or(cell.resolution());
+                cell.children(resolution).choose(rng)?.to_string()
+            }
+            LitQuery::LatLngWithin(llq) => {
+                // Close enough to the center, on a locally flat Earth.
+                let distance = rng.random_range(0.0..=0.9) * llq.within().0 as f64;
+                random_latlng_at(rng, llq.latlng(), distance)
+            }
+            LitQuery::LatLngWithinAny(llw) => {
+                let (center, within) = llw.disks().iter().choose(rng)?;
+                let distance = rng.random_range(0.0..=0.9) * within.0 as f64;
+                random_latlng_at(rng, *center, distance)
+            }
+            LitQuery::LatLngBetween(llb) => {
+                // Well inside the ring.
+                let (min, max) = (llb.min().0 as f64, llb.max().0 as f64);
+                let distance = min + rng.random_range(0.1..=0.9) * (max - min);
+                random_latlng_at(rng, llb.latlng(), distance)
+            }
+            LitQuery::LatLngInPolygon(llp) => {
+                let ring = llp.ring();
+                let (lats, lngs) = (
+                    ring.iter().map(|ll| ll.lat()),
+                    ring.iter().map(|ll| ll.lng()),
+                );
+                let (lat_min, lat_max) = lats.minmax().into_option()?;
+                let (lng_min, lng_max) = lngs.minmax().into_option()?;
+                // Points of the bounding box, until one is inside.
+                (0..100)
+                    .map(|_| {
+                        (
+                            rng.random_range(lat_min..=lat_max),
+                            rng.random_range(lng_min..=lng_max),
+                        )
+                    })
+                    .find(|(lat, lng)| {
+                        LatLng::new(*lat, *lng).is_ok_and(|ll| ring_contains(ring, ll))
+                    })
+                    .map(|(lat, lng)| format!("{},{}", lat, lng))?
+            }
+            LitQuery::IpIn(iq) => random_ip_in(rng, &iq.net()).to_string(),
+            LitQuery::SemverGe(sq) => {
+                let v = sq.version();
+                let patch = v.patch.checked_add(rng.random_range(0..=10))?;
+                semver::Version::new(v.major, v.minor, patch).to_string()
+            }
+            // The low bound, sometimes followed by more characters.
+            LitQuery::StrRange(sq) => {
+                let longer = format!("{}{}", sq.low(), random_word(rng, 0..5));
+                [longer, sq.low().to_string()]
+                    .into_iter()
+                    .find(|v| sq.contains(v))?
+            }
+            // The values of a single field cannot make an element.
+            LitQuery::Nested(_) => return None,
+        };
+        Some((self.sort_field(), value))
+    }
+
+    pub(crate) fn is_geo(&self) -> bool {
+        matches!(
+            self,
+            LitQuery::H3Inside(_)
+                | LitQuery::H3InsideAny(_)
+                | LitQuery::LatLngWithin(_)
+                | LitQuery::LatLngWithinAny(_)
+                | LitQuery::LatLngBetween(_)
+                | LitQuery::LatLngInPolygon(_)
+        )
+    }
+
+    // To order Litterals for display, and for reports.
+    pub(crate) fn sort_field(&self) -> OurStr {
         match self {
             LitQuery::Term(tq) => tq.field(),
+            LitQuery::TermSet(tsq) => tsq.field(),
             LitQuery::Prefix(pq) => pq.field(),
+            LitQuery::Suffix(sq) => sq.field(),
+            LitQuery::Contains(cq) => cq.field(),
+            LitQuery::Match(mq) => mq.field(),
+            LitQuery::Regex(rq) => rq.field(),
+            LitQuery::Exists(eq) => eq.field(),
+            LitQuery::Missing(mq) => mq.field(),
+            LitQuery::CountGe(cq) => cq.field(),
             LitQuery::IntQuery(oq) => oq.field(),
+            LitQuery::FloatQuery(oq) => oq.field(),
+            LitQuery::BigIntQuery(oq) => oq.field(),
+            LitQuery::IntRange(rq) => rq.field(),
+            LitQuery::FloatRange(rq) => rq.field(),
             LitQuery::H3Inside(h3i) => h3i.field(),
+            LitQuery::H3InsideAny(h3a) => h3a.field(),
             LitQuery::LatLngWithin(llq) => llq.field(),
+            LitQuery::LatLngWithinAny(llw) => llw.field(),
+            LitQuery::LatLngBetween(llb) => llb.field(),
+            LitQuery::LatLngInPolygon(llp) => llp.field(),
+            LitQuery::IpIn(iq) => iq.field(),
+            LitQuery::SemverGe(sq) => sq.field(),
+            LitQuery::StrRange(sq) => sq.field(),
+            LitQuery::Nested(nq) => nq.path(),
         }
     }
 
     // To sort the term of the query in lexicographic order
-    fn sort_term(&self) -> OurStr {
+    pub(crate) fn sort_term(&self) -> OurStr {
         match self {
             LitQuery::Term(tq) => tq.term(),
+            LitQuery::TermSet(tsq) => tsq.terms().iter().join(",").into(),
             LitQuery::Prefix(pq) => pq.prefix(),
+            LitQuery::Suffix(sq) => sq.suffix(),
+            LitQuery::Contains(cq) => cq.substring(),
+            LitQuery::Match(mq) => mq.token(),
+            LitQuery::Regex(rq) => rq.pattern(),
+            LitQuery::Exists(_) => "".into(),
+            LitQuery::Missing(_) => "".into(),
+            LitQuery::CountGe(cq) => cq.min().to_string().into(),
             LitQuery::IntQuery(oq) => oq.cmp_point().to_string().into(),
+            LitQuery::FloatQuery(oq) => oq.cmp_point().to_string().into(),
+            LitQuery::BigIntQuery(oq) => oq.cmp_point().to_string().into(),
+            LitQuery::IntRange(rq) => format!("{},{}", rq.low(), rq.high()).into(),
+            LitQuery::FloatRange(rq) => format!("{},{}", rq.low(), rq.high()).into(),
             LitQuery::H3Inside(h3i) => h3i.cell().to_string().into(),
+            LitQuery::H3InsideAny(h3a) => h3a.cells().iter().join(",").into(),
             LitQuery::LatLngWithin(llq) => format!("{},{}", llq.latlng(), llq.within()).into(),
+            LitQuery::LatLngWithinAny(llw) => llw
+                .disks()
+                .iter()
+                .map(|(ll, within)| format!("{},{}", ll, within))
+                .join(",")
+                .into(),
+            LitQuery::LatLngBetween(llb) => {
+                format!("{},{},{}", llb.latlng(), llb.min(), llb.max()).into()
+            }
+            LitQuery::LatLngInPolygon(llp) => llp.ring().iter().join(",").into(),
+            LitQuery::IpIn(iq) => iq.net().to_string().into(),
+            LitQuery::SemverGe(sq) => sq.version().to_string().into(),
+            LitQuery::StrRange(sq) => sq.low(),
+            LitQuery::Nested(nq) => nq.query().to_string().into(),
         }
     }
 }
@@ -272,38 +1069,202 @@ impl LitQuery {
 impl fmt::Display for LitQuery {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            LitQuery::Term(tq) => write!(f, "{}={}", tq.field(), tq.term()),
-            LitQuery::Prefix(pq) => write!(f, "{}={}*", pq.field(), pq.prefix()),
+            LitQuery::Term(tq) if tq.is_normalized() => write!(
+                f,
+                "NORM({}):{}",
+                escape_quote_field(&tq.field()),
+                escape_quote_term(&tq.term())
+            ),
+            LitQuery::Term(tq) => write!(
+                f,
+                "{}:{}{}",
+                escape_quote_field(&tq.field()),
+                if tq.is_case_folded() { "~" } else { "" },
+                escape_quote_term(&tq.term())
+            ),
+            LitQuery::TermSet(tsq) => tsq.fmt(f),
+            LitQuery::Prefix(pq) => write!(
+                f,
+                "{}:{}{}*",
+                escape_quote_field(&pq.field()),
+                if pq.is_case_folded() { "~" } else { "" },
+                escape_quote(&pq.prefix())
+            ),
+            LitQuery::Suffix(sq) => write!(
+                f,
+                "{}:*{}",
+                escape_quote_field(&sq.field()),
+                escape_quote(&sq.suffix())
+            ),
+            LitQuery::Contains(cq) => write!(
+                f,
+                "{}:*{}*",
+                escape_quote_field(&cq.field()),
+                escape_quote(&cq.substring())
+            ),
+            LitQuery::Match(mq) => write!(
+                f,
+                "{} WORD({}) {}",
+                escape_quote_field(&mq.field()),
+                mq.analyzer(),
+                escape_quote_term(&mq.token())
+            ),
+            LitQuery::Regex(rq) => rq.fmt(f),
+            LitQuery::Exists(eq) => write!(f, "{}:*", escape_quote_field(&eq.field())),
+            LitQuery::Missing(mq) => write!(f, "NOT {}:*", escape_quote_field(&mq.field())),
+            LitQuery::CountGe(cq) => write!(
+                f,
+                "COUNT({})>={}",
+                escape_quote_field(&cq.field()),
+                cq.min()
+            ),
             LitQuery::IntQuery(oq) => oq.fmt(f),
+            LitQuery::FloatQuery(oq) => oq.fmt(f),
+            // In the i64 range, `field>=5` would parse as an i64 comparison.
+            LitQuery::BigIntQuery(oq) if i64::try_from(*oq.cmp_point()).is_ok() => write!(
+                f,
+                "BIGINT({}){}{}",
+                escape_quote_field(&oq.field()),
+                oq.cmp_ord(),
+                oq.cmp_point()
+            ),
+            LitQuery::BigIntQuery(oq) => oq.fmt(f),
+            LitQuery::IntRange(rq) => rq.fmt(f),
+            LitQuery::FloatRange(rq) => rq.fmt(f),
             LitQuery::H3Inside(h3i) => h3i.fmt(f),
+            LitQuery::H3InsideAny(h3a) => h3a.fmt(f),
             LitQuery::LatLngWithin(llq) => llq.fmt(f),
+            LitQuery::LatLngWithinAny(llw) => llw.fmt(f),
+            LitQuery::LatLngBetween(llb) => llb.fmt(f),
+            LitQuery::LatLngInPolygon(llp) => llp.fmt(f),
+            LitQuery::IpIn(iq) => write!(
+                f,
+                "{} IPIN {}",
+                escape_quote_field(&iq.field()),
+                escape_quote_term(&iq.net().to_string())
+            ),
+            LitQuery::SemverGe(sq) => write!(
+                f,
+                "SEMVER({})>={}",
+                escape_quote_field(&sq.field()),
+                sq.version()
+            ),
+            LitQuery::StrRange(sq) => write!(
+                f,
+                "STR({}) BETWEEN {} AND {}",
+                escape_quote_field(&sq.field()),
+                escape_quote_term(&sq.low()),
+                escape_quote_term(&sq.high())
+            ),
+            LitQuery::Nested(nq) => {
+                write!(f, "NESTED({})(", escape_quote_field(&nq.path()))?;
+                nq.query().write_to(f)?;
+                write!(f, ")")
+            }
         }
     }
 }
 
+// A "lat,lng" about distance meters away from center in a random
+// direction, on a locally flat Earth.
+fn random_latlng_at<R: rand::Rng>(rng: &mut R, center: LatLng, distance: f64) -> String {
+    let bearing = rng.random_range(0.0..std::f64::consts::TAU);
+    let lat = center.lat() + (distance * bearing.cos() / 111_320.0);
+    let lng =
+        center.lng() + (distance * bearing.sin() / (111_320.0 * center.lat().to_radians().cos()));
+    format!("{},{}", lat, lng)
+}
+
 // Turns a LatLngWithin query into a vector of
 // indexed fields.
-fn llq_to_fvs(llq: &LatLngWithinQuery) -> Vec<(OurStr, OurStr)> {
-    // We are going to have a collection of H3 cells to index.
-    let cells = llq.h3_cells();
-    // They are all going to be of the same resolution.
-    let resolution = llq.resolution();
+fn llq_to_fvs(llq: &LatLngWithinQuery, config: &PercolatorConfig) -> Vec<(OurStr, OurStr)> {
+    h3_cells_to_fvs(llq.field(), llq.h3_cells(config.h3_max_resolution()))
+}
+
+// Turns a LatLngInPolygon query into a vector of
+// indexed fields.
+fn llp_to_fvs(llp: &LatLngInPolygonQuery, config: &PercolatorConfig) -> Vec<(OurStr, OurStr)> {
+    h3_cells_to_fvs(llp.field(), llp.h3_cells(config.h3_max_resolution()))
+}
 
+fn h3_cells_to_fvs(
+    field: OurStr,
+    cells: impl IntoIterator<Item = CellIndex>,
+) -> Vec<(OurStr, OurStr)> {
+    // We are going to have a collection of H3 cells to index,
+    // each under the field for its own resolution.
     cells
         .into_iter()
         .map(|cell| {
             (
-                format!("__H3_IN_{}_{}", llq.field(), resolution).into(),
+                format!("__H3_IN_{}_{}", field, cell.resolution()).into(),
                 cell.to_string().into(),
             )
         })
         .collect()
 }
 
+// The field a prefix query is a prefix of the values of.
+fn prefix_field(pq: &PrefixQuery) -> OurStr {
+    match pq.is_case_folded() {
+        true => casefold_field(&pq.field()),
+        false => pq.field(),
+    }
+}
+
+// Turns a prefix query into a vector of indexed fields.
+fn pq_to_fvs(pq: &PrefixQuery, config: &PercolatorConfig) -> Vec<(OurStr, OurStr)> {
+    // Logic to index prefix query:
+    // clip the prefix to a fixed set of sizes,
+    // knowing we will use the same set of sizes for the preheaters
+    // and do a last match check on the document.
+    let clipped_len = clip_prefix_len(config.prefix_sizes(), pq.prefix().len());
+
+    vec![(
+        format!("__PREFIX{}__{}", clipped_len, prefix_field(pq)).into(),
+        pq.prefix()
+            .chars()
+            .take(clipped_len)
+            .collect::<String>()
+            .into(),
+    )]
+}
+
+// Turns a suffix query into a vector of indexed fields,
+// like a prefix query on reversed strings.
+fn sq_to_fvs(sq: &SuffixQuery, config: &PercolatorConfig) -> Vec<(OurStr, OurStr)> {
+    let clipped_len = clip_prefix_len(config.prefix_sizes(), sq.suffix().chars().count());
+
+    vec![(
+        format!("__SUFFIX{}__{}", clipped_len, sq.field()).into(),
+        reversed_suffix(&sq.suffix(), clipped_len).into(),
+    )]
+}
+
+// Turns a contains query into a vector of indexed fields:
+// its first n-gram, which any matching value has too.
+fn cq_to_fvs(cq: &ContainsQuery, config: &PercolatorConfig) -> Vec<(OurStr, OurStr)> {
+    let gram_len = ngram_len(cq, config);
+
+    vec![(
+        format!("__NGRAM{}__{}", gram_len, cq.field()).into(),
+        cq.substring()
+            .chars()
+            .take(gram_len)
+            .collect::<String>()
+            .into(),
+    )]
+}
+
+// The prefix query any value matching the regex query also matches.
+fn regex_prefix_query(rq: &RegexQuery) -> PrefixQuery {
+    PrefixQuery::new(rq.field(), rq.literal_prefix())
+}
+
 // Turns an H3Inside query into a vector of indexed
 // fields.
-fn h3i_to_fvs(h3i: &H3InsideQuery) -> Vec<(OurStr, OurStr)> {
-    let cell = h3i.cell();
+fn h3i_to_fvs(h3i: &H3InsideQuery, config: &PercolatorConfig) -> Vec<(OurStr, OurStr)> {
+    let cell = h3i.cell_upto(config.h3_max_resolution());
     vec![(
         // We need the field and the resolution,
         // as we will preheat with the resolution.
@@ -316,6 +1277,7 @@ fn h3i_to_fvs(h3i: &H3InsideQuery) -> Vec<(OurStr, OurStr)> {
 // Turns an ordered query into a vector of field/values
 // for the purpose of indexing the query in the percolator.
 fn oq_to_fvs<T: PartialOrd + FromStr + crate::itertools::Fiboable + Display>(
+    kind: &str,
     oq: &OrderedQuery<T>,
 ) -> Vec<(OurStr, OurStr)> {
     match oq.cmp_ord() {
@@ -324,7 +1286,7 @@ fn oq_to_fvs<T: PartialOrd + FromStr + crate::itertools::Fiboable + Display>(
             // as something that is <= ceil is also potentially <= than the original value.
             let ceil_value = fibo_ceil(*oq.cmp_point());
             vec![(
-                format!("__INT_LE_{}__{}", ceil_value, oq.field()).into(),
+                format!("__{}_LE_{}__{}", kind, ceil_value, oq.field()).into(),
                 "true".into(),
             )]
         }
@@ -333,22 +1295,93 @@ fn oq_to_fvs<T: PartialOrd + FromStr + crate::itertools::Fiboable + Display>(
             // as something that is >= floor is potentially also >= than the original value.
             let floor_value = fibo_floor(*oq.cmp_point());
             vec![(
-                format!("__INT_GE_{}__{}", floor_value, oq.field()).into(),
+                format!("__{}_GE_{}__{}", kind, floor_value, oq.field()).into(),
                 "true".into(),
             )]
         }
     }
 }
 
+// A value matching an integer comparison, `delta` away from its point.
+// None when that would overflow.
+fn int_example_value<T: num_traits::PrimInt + FromStr + Display>(
+    oq: &OrderedQuery<T>,
+    delta: T,
+) -> Option<String> {
+    let point = *oq.cmp_point();
+    let value = match oq.cmp_ord() {
+        Ordering::GT => point.checked_add(&delta)?,
+        Ordering::GE => point.checked_add(&(delta - T::one()))?,
+        Ordering::LT => point.checked_sub(&delta)?,
+        Ordering::LE => point.checked_sub(&(delta - T::one()))?,
+        Ordering::EQ => point,
+    };
+    Some(value.to_string())
+}
+
+// The weight of a literal when scoring, 1 by default.
+// Kept as bits, so literals can stay Eq and Hash.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "f32", into = "f32")
+)]
+pub(crate) struct Weight(u32);
+
+impl Weight {
+    fn is_one(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Default for Weight {
+    fn default() -> Self {
+        Self::from(1.0)
+    }
+}
+
+impl From<f32> for Weight {
+    fn from(w: f32) -> Self {
+        Self(w.to_bits())
+    }
+}
+
+impl From<Weight> for f32 {
+    fn from(w: Weight) -> Self {
+        f32::from_bits(w.0)
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) struct Literal {
     negated: bool,
     query: LitQuery,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Weight::is_one")
+    )]
+    weight: Weight,
 }
 impl Literal {
     pub(crate) fn new(negated: bool, query: LitQuery) -> Self {
-        Self { negated, query }
+        Self {
+            negated,
+            query,
+            weight: Weight::default(),
+        }
+    }
+
+    pub(crate) fn weight(&self) -> f32 {
+        self.weight.into()
+    }
+
+    pub(crate) fn with_weight(self, weight: f32) -> Self {
+        Self {
+            weight: weight.into(),
+            ..self
+        }
     }
 
     pub(crate) fn cost(&self) -> u32 {
@@ -378,44 +1411,145 @@ impl Literal {
         config: &PercolatorConfig,
     ) -> Vec<(OurStr, OurStr)> {
         match &self.query {
+            LitQuery::Term(tq) if tq.is_case_folded() => {
+                vec![(casefold_field(&tq.field()), tq.term())]
+            }
+            LitQuery::Term(tq) if tq.is_normalized() => {
+                vec![(normalized_field(&tq.field()), tq.term())]
+            }
             LitQuery::Term(tq) => vec![(tq.field(), tq.term())],
-            LitQuery::Prefix(pq) => {
-                // Logic to index prefix query:
-                // clip the prefix to a fixed set of sizes,
-                // knowing we will use the same set of sizes for the preheaters
-                // and do a last match check on the document.
-                let clipped_len = clip_prefix_len(config.prefix_sizes(), pq.prefix().len());
-
-                vec![(
-                    format!("__PREFIX{}__{}", clipped_len, pq.field()).into(),
-                    pq.prefix()
-                        .chars()
-                        .take(clipped_len)
-                        .collect::<String>()
-                        .into(),
-                )]
+            // Any of the values can match, like a clause of terms.
+            LitQuery::TermSet(tsq) => tsq
+                .terms()
+                .iter()
+                .map(|t| (tsq.field(), t.clone()))
+                .collect(),
+            LitQuery::Prefix(pq) => pq_to_fvs(pq, config),
+            LitQuery::Suffix(sq) => sq_to_fvs(sq, config),
+            LitQuery::Contains(cq) => cq_to_fvs(cq, config),
+            LitQuery::Match(mq) => vec![(word_field(&mq.field(), mq.analyzer()), mq.token())],
+            // A regex is indexed like a prefix query on its literal prefix.
+            LitQuery::Regex(rq) => pq_to_fvs(&regex_prefix_query(rq), config),
+            LitQuery::Exists(eq) => vec![exists_indexed_fv(&eq.field())],
+            LitQuery::Missing(mq) => vec![missing_indexed_fv(&mq.field())],
+            LitQuery::CountGe(cq) => vec![(
+                count_ge_indexed_name(&cq.field(), cq.bucket()),
+                "true".into(),
+            )],
+            LitQuery::IntQuery(oq) => oq_to_fvs("INT", oq),
+            LitQuery::FloatQuery(oq) => vec![(float_indexed_name(oq), "true".into())],
+            LitQuery::BigIntQuery(oq) => oq_to_fvs("BIG", oq),
+            LitQuery::IntRange(rq) => vec![(
+                range_indexed_name("INT", int_range_bounds(rq), &rq.field()),
+                "true".into(),
+            )],
+            LitQuery::FloatRange(rq) => vec![(
+                range_indexed_name("FLT", float_range_bounds(rq), &rq.field()),
+                "true".into(),
+            )],
+            LitQuery::H3Inside(h3i) => h3i_to_fvs(h3i, config),
+            LitQuery::H3InsideAny(h3a) => {
+                h3_cells_to_fvs(h3a.field(), h3a.cells_upto(config.h3_max_resolution()))
+            }
+            LitQuery::LatLngWithin(llq) => llq_to_fvs(llq, config),
+            LitQuery::LatLngWithinAny(llw) => {
+                h3_cells_to_fvs(llw.field(), llw.h3_cells(config.h3_max_resolution()))
+            }
+            LitQuery::LatLngBetween(llb) => {
+                h3_cells_to_fvs(llb.field(), llb.h3_cells(config.h3_max_resolution()))
             }
-            LitQuery::IntQuery(oq) => oq_to_fvs(oq),
-            LitQuery::H3Inside(h3i) => h3i_to_fvs(h3i),
-            LitQuery::LatLngWithin(llq) => llq_to_fvs(llq),
+            LitQuery::LatLngInPolygon(llp) => llp_to_fvs(llp, config),
+            LitQuery::IpIn(iq) => {
+                let (len, synth_field) = ip_indexing(iq);
+                vec![(synth_field, ip_network(iq.net().network(), len))]
+            }
+            LitQuery::SemverGe(sq) => vec![(
+                semver_indexed_name(&sq.field(), sq.version().major),
+                "true".into(),
+            )],
+            LitQuery::StrRange(sq) => vec![(str_range_indexed_name(sq), "true".into())],
+            LitQuery::Nested(nq) => vec![(nested_indexed_name(&nq.path()), "true".into())],
         }
     }
 
     pub(crate) fn preheater(&self, config: &PercolatorConfig) -> Option<PreHeater> {
         match &self.query {
+            LitQuery::Term(tq) if tq.is_case_folded() => Some(casefold_term_preheater(tq)),
+            LitQuery::Term(tq) if tq.is_normalized() => Some(normalized_term_preheater(tq)),
             LitQuery::Prefix(pq) => Some(prefix_query_preheater(config.prefix_sizes(), pq)),
-            LitQuery::IntQuery(oq) => Some(intcmp_query_preheater(oq)),
-            LitQuery::H3Inside(h3i) => Some(h3in_query_preheater(h3i)),
-            LitQuery::LatLngWithin(llq) => Some(latlngwithin_preheater(llq)),
+            LitQuery::Suffix(sq) => Some(suffix_query_preheater(config.prefix_sizes(), sq)),
+            LitQuery::Contains(cq) => Some(contains_query_preheater(cq, config)),
+            LitQuery::Match(mq) => Some(match_query_preheater(mq)),
+            LitQuery::Exists(eq) => Some(exists_query_preheater(eq)),
+            LitQuery::Missing(mq) => Some(missing_query_preheater(mq)),
+            LitQuery::CountGe(cq) => Some(count_ge_query_preheater(cq)),
+            // Same preheater as the prefix, but the regex always needs checking.
+            LitQuery::Regex(rq) => Some(
+                prefix_query_preheater(config.prefix_sizes(), &regex_prefix_query(rq))
+                    .with_must_filter(true),
+            ),
+            LitQuery::IntQuery(oq) => Some(intcmp_query_preheater("INT", oq)),
+            LitQuery::FloatQuery(oq) => Some(floatcmp_query_preheater(oq)),
+            LitQuery::BigIntQuery(oq) => Some(intcmp_query_preheater("BIG", oq)),
+            LitQuery::IntRange(rq) => Some(range_query_preheater(
+                "INT",
+                int_range_bounds(rq),
+                rq.field(),
+            )),
+            LitQuery::FloatRange(rq) => Some(range_query_preheater(
+                "FLT",
+                float_range_bounds(rq),
+                rq.field(),
+            )),
+            LitQuery::H3Inside(h3i) => Some(h3in_query_preheater(h3i, config)),
+            LitQuery::H3InsideAny(h3a) => Some(h3in_any_query_preheater(h3a, config)),
+            LitQuery::LatLngWithin(llq) => Some(latlngwithin_preheater(llq, config)),
+            LitQuery::LatLngWithinAny(llw) => Some(latlngwithinany_preheater(llw, config)),
+            LitQuery::LatLngBetween(llb) => Some(latlngbetween_preheater(llb, config)),
+            LitQuery::LatLngInPolygon(llp) => Some(latlnginpolygon_preheater(llp, config)),
+            LitQuery::IpIn(iq) => Some(ip_in_query_preheater(iq)),
+            LitQuery::SemverGe(sq) => Some(semver_query_preheater(sq)),
+            LitQuery::StrRange(sq) => Some(str_range_query_preheater(sq)),
+            LitQuery::Nested(nq) => Some(nested_query_preheater(nq)),
             _ => None,
         }
     }
 
-    /// The negation of this literal, which is also a literal
+    /// The negation of this literal, which is also a literal.
+    /// Exists and Missing are each other's negation, so they
+    /// stay positive and can be indexed.
     pub(crate) fn negate(self) -> Self {
-        Self {
-            negated: !self.negated,
-            query: self.query,
+        let weight = self.weight;
+        match self.query {
+            LitQuery::Exists(eq) if !self.negated => Self {
+                weight,
+                ..Self::new(false, LitQuery::Missing(MissingQuery::new(eq.field())))
+            },
+            LitQuery::Missing(mq) if !self.negated => Self {
+                weight,
+                ..Self::new(false, LitQuery::Exists(ExistsQuery::new(mq.field())))
+            },
+            query => Self {
+                negated: !self.negated,
+                query,
+                weight,
+            },
+        }
+    }
+
+    /// The same literal, matching the trimmed and case folded values
+    /// if this is a plain term query.
+    pub(crate) fn with_normalized_term(&self) -> Self {
+        match &self.query {
+            LitQuery::Term(tq)
+                if !tq.is_case_folded() && !tq.is_normalized() && *tq != TermQuery::match_all() =>
+            {
+                Self {
+                    query: LitQuery::Term(TermQuery::normalized(tq.field(), tq.term())),
+                    ..self.clone()
+                }
+            }
+            _ => self.clone(),
         }
     }
 
@@ -424,10 +1558,37 @@ impl Literal {
         self.negated
     }
 
-    pub(crate) fn matches(&self, d: &Document) -> bool {
+    pub(crate) fn matches(&self, d: &impl DocumentView) -> bool {
         self.negated ^ self.query.matches(d)
     }
 
+    // The values of the document matching this on their own. Documents
+    // match negated literals by not having things, so they have none.
+    pub(crate) fn matching_values(&self, d: &Document) -> Vec<OurStr> {
+        if self.negated {
+            return vec![];
+        }
+        let field = self.query.sort_field();
+        d.values_iter(&field)
+            .into_iter()
+            .flatten()
+            .filter(|v| {
+                self.query
+                    .matches(&Document::default().with_value(field.clone(), v.clone()))
+            })
+            .collect()
+    }
+
+    // A (field, value) for a document to match this. Documents
+    // match negated literals by not having things, so they have none.
+    pub(crate) fn example_value<R: rand::Rng>(&self, rng: &mut R) -> Option<(OurStr, String)> {
+        if self.negated {
+            None
+        } else {
+            self.query.example_value(rng)
+        }
+    }
+
     // Only used at percolation time
     // The should Never be a prefix query in here.
     pub(crate) fn percolate_docs_from_idx<'a>(&self, index: &'a Index) -> &'a RoaringBitmap {
@@ -438,12 +1599,18 @@ impl Literal {
     }
 }
 
+// A total order, so queries can keep their literals in a canonical order.
 impl Ord for Literal {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.query
             .sort_field()
             .cmp(&other.query.sort_field())
             .then_with(|| self.query.sort_term().cmp(&other.query.sort_term()))
+            .then_with(|| self.negated.cmp(&other.negated))
+            .then_with(|| self.query.kind().cmp(other.query.kind()))
+            // Like `price<10` and `price>10`. Rare enough to afford strings.
+            .then_with(|| self.query.to_string().cmp(&other.query.to_string()))
+            .then_with(|| self.weight.0.cmp(&other.weight.0))
     }
 }
 
@@ -458,9 +1625,13 @@ impl fmt::Display for Literal {
         write!(
             f,
             "{}{}",
-            if self.is_negated() { "~" } else { "" },
+            if self.is_negated() { "NOT " } else { "" },
             self.query
-        )
+        )?;
+        if !self.weight.is_one() {
+            write!(f, "^{}", f32::from(self.weight))?;
+        }
+        Ok(())
     }
 }
 
@@ -497,7 +1668,7 @@ mod test {
             Ordering::GT,
         ] {
             let q = OrderedQuery::new("field", 42, ordering);
-            assert!(!oq_to_fvs(&q).is_empty());
+            assert!(!oq_to_fvs("INT", &q).is_empty());
         }
     }
 
@@ -671,10 +1842,18 @@ mod tests_literal {
     #[test]
     fn test_display() {
         let lit = Literal::new(false, LitQuery::Term(TermQuery::new("f", "v")));
-        assert_eq!(format!("{}", lit), "f=v");
+        assert_eq!(format!("{}", lit), "f:v");
 
         let lit_neg = lit.negate();
-        assert_eq!(format!("{}", lit_neg), "~f=v");
+        assert_eq!(format!("{}", lit_neg), "NOT f:v");
+
+        // Values are quoted when needed.
+        let lit = Literal::new(false, LitQuery::Term(TermQuery::new("f", "a b")));
+        assert_eq!(format!("{}", lit), "f:\"a b\"");
+        let lit = Literal::new(false, LitQuery::Term(TermQuery::new("f", "12")));
+        assert_eq!(format!("{}", lit), "f:\"12\"");
+        let lit = Literal::new(false, LitQuery::Prefix(PrefixQuery::new("f", "")));
+        assert_eq!(format!("{}", lit), "f:\"\"*");
     }
 
     #[test]
@@ -697,10 +1876,27 @@ mod tests_literal_preheater {
     use super::*;
     use crate::models::cnf::Clause;
     use crate::models::queries::{
-        ordered::{I64Query, Ordering},
+        ordered::{F64Query, I64Query, I128Query, Ordering},
         prefix::PrefixQuery,
+        range::{F64RangeQuery, I64RangeQuery},
     };
 
+    // The clause with the synthetic terms the preheater adds for its terms.
+    fn expand_clause(ph: &PreHeater, mut c: Clause) -> Clause {
+        let fvs = c
+            .term_queries_iter()
+            .map(|tq| (tq.field(), tq.term()))
+            .collect_vec();
+        let terms = fvs
+            .iter()
+            .map(|(f, v)| (f.as_ref(), v.as_ref()))
+            .collect_vec();
+        for tq in ph.expand(&terms) {
+            c.add_termquery(tq);
+        }
+        c
+    }
+
     // Testing logic of intcmp_query_preheater
     #[test]
     fn test_intcmp_preheater_logic() {
@@ -715,11 +1911,11 @@ mod tests_literal_preheater {
         // And preheater expander should produce that term if the document value <= 13.
 
         let q = I64Query::new("f", 10, Ordering::LE);
-        let ph = intcmp_query_preheater(&q);
+        let ph = intcmp_query_preheater("INT", &q);
 
         // Document with value 10 (should match)
         let clause = Clause::from_termqueries(vec![TermQuery::new("f", "10")]);
-        let expanded = ph.expand_clause(clause);
+        let expanded = expand_clause(&ph, clause);
         // Should contain __INT_LE_13__f=true
         assert!(expanded.literals().iter().any(|l| {
             l.query()
@@ -731,7 +1927,7 @@ mod tests_literal_preheater {
 
         // Document with value 14 (should NOT match) -> 14 > 13
         let clause = Clause::from_termqueries(vec![TermQuery::new("f", "14")]);
-        let expanded = ph.expand_clause(clause);
+        let expanded = expand_clause(&ph, clause);
         // Should NOT contain the synthetic field
         assert!(!expanded.literals().iter().any(|l| {
             l.query()
@@ -747,11 +1943,11 @@ mod tests_literal_preheater {
         // Preheater expander should produce that term if doc value >= 8.
 
         let q = I64Query::new("f", 10, Ordering::GE);
-        let ph = intcmp_query_preheater(&q);
+        let ph = intcmp_query_preheater("INT", &q);
 
         // Document with value 10 (should match)
         let clause = Clause::from_termqueries(vec![TermQuery::new("f", "10")]);
-        let expanded = ph.expand_clause(clause);
+        let expanded = expand_clause(&ph, clause);
         assert!(expanded.literals().iter().any(|l| {
             l.query()
                 .term_query()
@@ -762,7 +1958,7 @@ mod tests_literal_preheater {
 
         // Document with value 7 (should NOT match) -> 7 < 8
         let clause = Clause::from_termqueries(vec![TermQuery::new("f", "7")]);
-        let expanded = ph.expand_clause(clause);
+        let expanded = expand_clause(&ph, clause);
         assert!(!expanded.literals().iter().any(|l| {
             l.query()
                 .term_query()
@@ -772,6 +1968,144 @@ mod tests_literal_preheater {
         }));
     }
 
+    #[test]
+    fn test_floatcmp_preheater_logic() {
+        let has_synth = |c: &Clause| {
+            c.literals().iter().any(|l| {
+                l.query()
+                    .term_query()
+                    .unwrap()
+                    .field()
+                    .starts_with("__FLT_")
+            })
+        };
+
+        // LE 1.5 is bucketed at 2
+        let q = F64Query::new("f", 1.5, Ordering::LE);
+        let lit = Literal::new(false, LitQuery::FloatQuery(q.clone()));
+        assert_eq!(
+            lit.percolate_doc_field_values(&PercolatorConfig::default()),
+            vec![("__FLT_LE_2__f".into(), "true".into())]
+        );
+        let ph = floatcmp_query_preheater(&q);
+        assert!(ph.must_filter);
+        assert!(has_synth(&expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("f", "1.9")])
+        )));
+        assert!(!has_synth(&expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("f", "2.1")])
+        )));
+        assert!(!has_synth(&expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("f", "not a float")])
+        )));
+
+        // GT -3.5 is bucketed at -4
+        let q = F64Query::new("f", -3.5, Ordering::GT);
+        let ph = floatcmp_query_preheater(&q);
+        assert!(has_synth(&expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("f", "-4")])
+        )));
+        assert!(!has_synth(&expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("f", "-4.1")])
+        )));
+
+        // Same point, different directions: different preheaters.
+        assert_ne!(
+            floatcmp_query_preheater(&F64Query::new("f", 4.0, Ordering::LE)).id,
+            floatcmp_query_preheater(&F64Query::new("f", 4.0, Ordering::GE)).id
+        );
+        assert_ne!(
+            intcmp_query_preheater("INT", &I64Query::new("f", 5, Ordering::LT)).id,
+            intcmp_query_preheater("INT", &I64Query::new("f", 5, Ordering::GT)).id
+        );
+    }
+
+    #[test]
+    fn test_big_intcmp_preheater() {
+        let big = i128::from(u64::MAX);
+        let q = I128Query::new("f", big, Ordering::GE);
+        let ph = intcmp_query_preheater("BIG", &q);
+        // Not the same preheater as an i64 comparison on the same point.
+        assert_ne!(
+            ph.id,
+            intcmp_query_preheater("INT", &I64Query::new("f", 5, Ordering::GE)).id
+        );
+        assert_eq!(
+            Literal::new(false, LitQuery::BigIntQuery(q.clone()))
+                .percolate_doc_field_values(&PercolatorConfig::default()),
+            oq_to_fvs("BIG", &q)
+        );
+
+        let has_synth = |v: &str| {
+            let clause = Clause::from_termqueries(vec![TermQuery::new("f", v)]);
+            expand_clause(&ph, clause).literals().iter().any(|l| {
+                l.query()
+                    .term_query()
+                    .unwrap()
+                    .field()
+                    .starts_with("__BIG_GE_")
+            })
+        };
+        // Values out of the i64 range still parse.
+        assert!(has_synth(&u64::MAX.to_string()));
+        assert!(!has_synth("10"));
+        assert!(!has_synth("sausage"));
+    }
+
+    #[test]
+    fn test_range_preheater_logic() {
+        let has_synth = |c: &Clause| {
+            c.literals()
+                .iter()
+                .any(|l| l.query().term_query().unwrap().field().contains("_RANGE_"))
+        };
+        let config = PercolatorConfig::default();
+
+        // 10..=100 is bucketed between 8 and 144, in one field.
+        let lit = Literal::new(false, LitQuery::IntRange(I64RangeQuery::new("f", 10, 100)));
+        assert_eq!(
+            lit.percolate_doc_field_values(&config),
+            vec![("__INT_RANGE_8_144__f".into(), "true".into())]
+        );
+        let ph = lit.preheater(&config).unwrap();
+        assert!(ph.must_filter);
+        for (v, expected) in [("8", true), ("144", true), ("7", false), ("145", false)] {
+            assert_eq!(
+                has_synth(&expand_clause(
+                    &ph,
+                    Clause::from_termqueries(vec![TermQuery::new("f", v)])
+                )),
+                expected
+            );
+        }
+
+        // -0.5..=1.5 is bucketed between -1 and 2.
+        let lit = Literal::new(
+            false,
+            LitQuery::FloatRange(F64RangeQuery::new("f", -0.5, 1.5)),
+        );
+        assert_eq!(
+            lit.percolate_doc_field_values(&config),
+            vec![("__FLT_RANGE_-1_2__f".into(), "true".into())]
+        );
+        let ph = lit.preheater(&config).unwrap();
+        assert!(has_synth(&expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("f", "-1")])
+        )));
+        assert!(!has_synth(&expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("f", "2.5")])
+        )));
+        assert_eq!(lit.query().kind(), "float_range");
+        assert_eq!(lit.to_string(), "f BETWEEN -0.5 AND 1.5");
+    }
+
     // Testing logic of prefix_query_preheater
     #[test]
     fn test_prefix_preheater_must_filter() {
@@ -797,7 +2131,7 @@ mod tests_literal_preheater {
 
         // Doc value "abc" (len 3) < 4. Should NOT expand.
         let clause = Clause::from_termqueries(vec![TermQuery::new("f", "abc")]);
-        let expanded = ph.expand_clause(clause);
+        let expanded = expand_clause(&ph, clause);
         // Check no new literals added (or at least no synthetic prefix one)
         assert!(!expanded.literals().iter().any(|l| {
             l.query()
@@ -809,7 +2143,7 @@ mod tests_literal_preheater {
 
         // Doc value "abcde" (len 5) >= 4. Should expand.
         let clause = Clause::from_termqueries(vec![TermQuery::new("f", "abcde")]);
-        let expanded = ph.expand_clause(clause);
+        let expanded = expand_clause(&ph, clause);
         assert!(expanded.literals().iter().any(|l| {
             l.query()
                 .term_query()
@@ -818,4 +2152,625 @@ mod tests_literal_preheater {
                 .starts_with("__PREFIX")
         }));
     }
+
+    #[test]
+    fn test_suffix_preheater() {
+        let sizes = vec![2, 4];
+        let config = PercolatorConfig {
+            prefix_sizes: sizes.clone(),
+            ..Default::default()
+        };
+
+        // Indexed on the reversed suffix, exact when its length is a size.
+        let lit = Literal::new(false, LitQuery::Suffix(SuffixQuery::new("f", ".jpg")));
+        assert_eq!(lit.cost(), 1000);
+        assert_eq!(lit.to_string(), "f:*.jpg");
+        assert_eq!(
+            lit.percolate_doc_field_values(&config),
+            vec![("__SUFFIX4__f".into(), "gpj.".into())]
+        );
+        let ph = lit.preheater(&config).unwrap();
+        assert_eq!(ph.id, "__SUFFIX4__f".into());
+        assert!(!ph.must_filter);
+
+        let expanded = expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("f", "cat.jpg")]),
+        );
+        assert!(
+            expanded
+                .term_queries_iter()
+                .any(|tq| tq.field() == "__SUFFIX4__f".into() && tq.term() == "gpj.".into())
+        );
+        // Too short.
+        let expanded = expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("f", "jpg")]),
+        );
+        assert_eq!(expanded.literals().len(), 1);
+
+        // Clipped to the last characters, so it needs checking.
+        let q = SuffixQuery::new("f", "_thumb.jpg");
+        assert!(suffix_query_preheater(&sizes, &q).must_filter);
+        let lit = Literal::new(false, LitQuery::Suffix(q));
+        assert_eq!(
+            lit.percolate_doc_field_values(&config),
+            vec![("__SUFFIX4__f".into(), "gpj.".into())]
+        );
+        // Characters, not bytes.
+        assert_eq!(reversed_suffix("café", 2), "éf");
+    }
+
+    #[test]
+    fn test_casefold_preheaters() {
+        let config = PercolatorConfig {
+            prefix_sizes: vec![2, 4],
+            ..Default::default()
+        };
+
+        let lit = Literal::new(false, LitQuery::Term(TermQuery::case_folded("f", "Blue")));
+        assert_eq!(lit.cost(), 500);
+        assert_eq!(lit.to_string(), "f:~blue");
+        assert_eq!(lit.query().exact_field_values(), None);
+        assert_eq!(
+            lit.percolate_doc_field_values(&config),
+            vec![("__CASEFOLD__f".into(), "blue".into())]
+        );
+        let ph = lit.preheater(&config).unwrap();
+        assert_eq!(ph.id, "__CASEFOLD__f".into());
+        assert!(!ph.must_filter);
+        let expanded = expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![
+                TermQuery::new("f", "BLUE"),
+                TermQuery::new("g", "Red"),
+            ]),
+        );
+        assert_eq!(
+            expanded
+                .term_queries_iter()
+                .filter(|tq| tq.field() == "__CASEFOLD__f".into())
+                .map(|tq| tq.term())
+                .collect_vec(),
+            vec!["blue".into()]
+        );
+
+        // Prefixes of the folded values.
+        let lit = Literal::new(
+            false,
+            LitQuery::Prefix(PrefixQuery::case_folded("f", "BLu")),
+        );
+        assert_eq!(lit.to_string(), "f:~blu*");
+        assert_eq!(
+            lit.percolate_doc_field_values(&config),
+            vec![("__PREFIX2____CASEFOLD__f".into(), "bl".into())]
+        );
+        let ph = lit.preheater(&config).unwrap();
+        assert!(ph.must_filter);
+        let expanded = expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("f", "BLUE")]),
+        );
+        assert!(expanded.term_queries_iter().any(|tq| {
+            tq.field() == "__PREFIX2____CASEFOLD__f".into() && tq.term() == "bl".into()
+        }));
+
+        // Normalized terms, trimmed too.
+        let lit = Literal::new(false, LitQuery::Term(TermQuery::new("f", " Blue")));
+        let lit = lit.with_normalized_term();
+        assert_eq!(lit.cost(), 500);
+        assert_eq!(lit.to_string(), "NORM(f):blue");
+        assert_eq!(lit.query().exact_field_values(), None);
+        assert_eq!(
+            lit.percolate_doc_field_values(&config),
+            vec![("__NORMALIZED__f".into(), "blue".into())]
+        );
+        let ph = lit.preheater(&config).unwrap();
+        assert!(!ph.must_filter);
+        let expanded = expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("f", "BLUE\n")]),
+        );
+        assert!(
+            expanded
+                .term_queries_iter()
+                .any(|tq| { tq.field() == "__NORMALIZED__f".into() && tq.term() == "blue".into() })
+        );
+        assert_eq!(lit.with_normalized_term(), lit);
+        let match_all = Literal::new(false, LitQuery::Term(TermQuery::match_all()));
+        assert_eq!(match_all.with_normalized_term(), match_all);
+
+        // Case sensitive prefixes are indexed apart.
+        let lit = Literal::new(false, LitQuery::Prefix(PrefixQuery::new("f", "bl")));
+        assert_eq!(lit.preheater(&config).unwrap().id, "__PREFIX2__f".into());
+    }
+
+    #[test]
+    fn test_contains_preheater() {
+        let config = PercolatorConfig::default();
+        assert_eq!(ngrams("abcd", 3), vec!["abc", "bcd"]);
+        assert_eq!(ngrams("ab", 3), Vec::<String>::new());
+        assert_eq!(ngrams("ab", 0), vec![""]);
+        assert_eq!(ngrams("héhé", 2), vec!["hé", "éh", "hé"]);
+
+        // Indexed on its first trigram, so it needs checking.
+        let lit = Literal::new(false, LitQuery::Contains(ContainsQuery::new("f", "cats")));
+        assert_eq!(lit.cost(), 1200);
+        assert_eq!(lit.to_string(), "f:*cats*");
+        assert_eq!(
+            lit.percolate_doc_field_values(&config),
+            vec![("__NGRAM3__f".into(), "cat".into())]
+        );
+        let ph = lit.preheater(&config).unwrap();
+        assert_eq!(ph.id, "__NGRAM3__f".into());
+        assert!(ph.must_filter);
+
+        // All the distinct trigrams of the field values.
+        let expanded = expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![
+                TermQuery::new("f", "concat"),
+                TermQuery::new("f", "cat"),
+                TermQuery::new("g", "dog"),
+            ]),
+        );
+        let grams = expanded
+            .term_queries_iter()
+            .filter(|tq| tq.field() == "__NGRAM3__f".into())
+            .map(|tq| tq.term())
+            .collect_vec();
+        assert_eq!(
+            grams,
+            vec!["con".into(), "onc".into(), "nca".into(), "cat".into()]
+        );
+
+        // Shorter substrings are their own n-gram, exactly.
+        let lit = Literal::new(false, LitQuery::Contains(ContainsQuery::new("f", "at")));
+        assert_eq!(
+            lit.percolate_doc_field_values(&config),
+            vec![("__NGRAM2__f".into(), "at".into())]
+        );
+        assert!(!lit.preheater(&config).unwrap().must_filter);
+        let lit = Literal::new(false, LitQuery::Contains(ContainsQuery::new("f", "")));
+        assert_eq!(
+            lit.percolate_doc_field_values(&config),
+            vec![("__NGRAM0__f".into(), "".into())]
+        );
+    }
+
+    #[test]
+    fn test_match_preheater() {
+        let config = PercolatorConfig::default();
+        let lit = Literal::new(
+            false,
+            LitQuery::Match(MatchQuery::new("f", "fox", Analyzer::default())),
+        );
+        assert_eq!(lit.cost(), 600);
+        assert_eq!(lit.to_string(), "f WORD(lowercase) fox");
+        assert_eq!(
+            lit.percolate_doc_field_values(&config),
+            vec![("__WORD_lowercase__f".into(), "fox".into())]
+        );
+        let ph = lit.preheater(&config).unwrap();
+        assert_eq!(ph.id, "__WORD_lowercase__f".into());
+        assert!(!ph.must_filter);
+
+        // All the distinct words of the field values.
+        let expanded = expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![
+                TermQuery::new("f", "The Fox and the dog"),
+                TermQuery::new("f", "fox"),
+                TermQuery::new("g", "cat"),
+            ]),
+        );
+        let words = expanded
+            .term_queries_iter()
+            .filter(|tq| tq.field() == "__WORD_lowercase__f".into())
+            .map(|tq| tq.term())
+            .collect_vec();
+        assert_eq!(
+            words,
+            vec!["the".into(), "fox".into(), "and".into(), "dog".into()]
+        );
+
+        // Each analyzer has its own words.
+        let lit = Literal::new(
+            false,
+            LitQuery::Match(MatchQuery::new("f", "Fox", Analyzer::Whitespace)),
+        );
+        assert_eq!(
+            lit.preheater(&config).unwrap().id,
+            "__WORD_whitespace__f".into()
+        );
+    }
+
+    #[test]
+    fn test_ip_in_preheater() {
+        let config = PercolatorConfig::default();
+        let ip_in = |net: &str| {
+            Literal::new(
+                false,
+                LitQuery::IpIn(IpInQuery::new("ip", net.parse().unwrap())),
+            )
+        };
+
+        let lit = ip_in("10.1.0.0/16");
+        assert_eq!(lit.cost(), 1000);
+        assert_eq!(lit.to_string(), "ip IPIN 10.1.0.0/16");
+        assert_eq!(
+            lit.percolate_doc_field_values(&config),
+            vec![("__IP4_16__ip".into(), "10.1.0.0".into())]
+        );
+        let ph = lit.preheater(&config).unwrap();
+        assert_eq!(ph.id, "__IP4_16__ip".into());
+        assert!(!ph.must_filter);
+
+        // Only the addresses of the same family.
+        let expanded = expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![
+                TermQuery::new("ip", "10.1.2.3"),
+                TermQuery::new("ip", "2001:db8::1"),
+                TermQuery::new("ip", "not an ip"),
+                TermQuery::new("other", "10.1.2.3"),
+            ]),
+        );
+        let networks = expanded
+            .term_queries_iter()
+            .filter(|tq| tq.field() == "__IP4_16__ip".into())
+            .map(|tq| tq.term())
+            .collect_vec();
+        assert_eq!(networks, vec!["10.1.0.0".into()]);
+
+        // Masks in between are indexed at the shorter length, and need checking.
+        let lit = ip_in("10.1.128.0/20");
+        assert_eq!(
+            lit.percolate_doc_field_values(&config),
+            vec![("__IP4_16__ip".into(), "10.1.0.0".into())]
+        );
+        assert!(lit.preheater(&config).unwrap().must_filter);
+
+        // Too short masks are indexed as they are.
+        let lit = ip_in("10.0.0.0/6");
+        assert_eq!(
+            lit.percolate_doc_field_values(&config),
+            vec![("__IP4_6__ip".into(), "8.0.0.0".into())]
+        );
+        assert!(!lit.preheater(&config).unwrap().must_filter);
+
+        let lit = ip_in("2001:db8:1::/56");
+        assert_eq!(
+            lit.percolate_doc_field_values(&config),
+            vec![("__IP6_48__ip".into(), "2001:db8:1::".into())]
+        );
+        let ph = lit.preheater(&config).unwrap();
+        assert!(ph.must_filter);
+        let expanded = expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("ip", "2001:db8:1:2::5")]),
+        );
+        assert!(
+            expanded
+                .term_queries_iter()
+                .any(|tq| tq.field() == "__IP6_48__ip".into() && tq.term() == "2001:db8:1::".into())
+        );
+
+        // Examples are in the network.
+        let mut rng = rand::rng();
+        for net in [
+            "10.1.128.0/20",
+            "2001:db8:1::/56",
+            "0.0.0.0/0",
+            "1.2.3.4/32",
+        ] {
+            let lit = ip_in(net);
+            let (field, value) = lit.query().example_value(&mut rng).unwrap();
+            assert!(lit.matches(&Document::from([(field, value)])));
+        }
+    }
+
+    #[test]
+    fn test_count_ge_preheater() {
+        let q = CountGeQuery::new("tags", 3);
+        let lit = Literal::new(false, LitQuery::CountGe(q.clone()));
+        assert_eq!(lit.to_string(), "COUNT(tags)>=3");
+        assert_eq!(
+            lit.percolate_doc_field_values(&PercolatorConfig::default()),
+            vec![("__COUNT_GE_2__tags".into(), "true".into())]
+        );
+
+        // Documents with 2 distinct tags are candidates.
+        let ph = count_ge_query_preheater(&q);
+        assert!(ph.must_filter);
+        let has_synth = |d: &Document| {
+            ph.expand(&d.terms(false))
+                .iter()
+                .any(|tq| tq.field() == "__COUNT_GE_2__tags".into())
+        };
+        assert!(!has_synth(&[("tags", "a"), ("tags", "a")].into()));
+        assert!(has_synth(&[("tags", "a"), ("tags", "b")].into()));
+        assert!(!lit.matches(&Document::from([("tags", "a"), ("tags", "b")])));
+
+        // Powers of two are exact.
+        assert!(!count_ge_query_preheater(&CountGeQuery::new("tags", 4)).must_filter);
+    }
+
+    #[test]
+    fn test_semver_preheater() {
+        let q = SemverGeQuery::new("v", semver::Version::parse("2.3.0").unwrap());
+        let lit = Literal::new(false, LitQuery::SemverGe(q.clone()));
+        assert_eq!(lit.to_string(), "SEMVER(v)>=2.3.0");
+        assert_eq!(
+            lit.percolate_doc_field_values(&PercolatorConfig::default()),
+            vec![("__SEMVER_GE_2__v".into(), "true".into())]
+        );
+
+        // Versions from 2.0.0-0 are candidates.
+        let ph = semver_query_preheater(&q);
+        assert!(ph.must_filter);
+        let has_synth = |v: &str| {
+            ph.expand(&Document::from([("v", v)]).terms(false))
+                .iter()
+                .any(|tq| tq.field() == "__SEMVER_GE_2__v".into())
+        };
+        assert!(!has_synth("1.9.0"));
+        assert!(!has_synth("1.99.0"));
+        assert!(!has_synth("not a version"));
+        assert!(has_synth("2.0.0-rc.1"));
+        assert!(has_synth("2.0.0"));
+        assert!(has_synth("10.1.0"));
+
+        let mut rng = rand::rng();
+        let (field, value) = lit.query().example_value(&mut rng).unwrap();
+        assert!(lit.matches(&Document::from([(field, value)])));
+    }
+
+    #[test]
+    fn test_str_range_preheater() {
+        let q = StrRangeQuery::new("name", "a", "f");
+        let lit = Literal::new(false, LitQuery::StrRange(q.clone()));
+        assert_eq!(lit.to_string(), "STR(name) BETWEEN a AND f");
+        assert_eq!(lit.cost(), 500);
+        assert_eq!(
+            lit.percolate_doc_field_values(&PercolatorConfig::default()),
+            vec![("__STR_RANGE_\"a\"_\"f\"__name".into(), "true".into())]
+        );
+        // Bounds with underscores do not share fields.
+        assert_ne!(
+            str_range_indexed_name(&StrRangeQuery::new("n", "a_b", "c")),
+            str_range_indexed_name(&StrRangeQuery::new("n", "a", "b_c"))
+        );
+
+        let ph = str_range_query_preheater(&q);
+        assert!(!ph.must_filter);
+        let has_synth = |v: &str| {
+            ph.expand(&Document::from([("name", v)]).terms(false))
+                .iter()
+                .any(|tq| tq.field().starts_with("__STR_RANGE_"))
+        };
+        assert!(has_synth("a"));
+        assert!(has_synth("bob"));
+        assert!(has_synth("f"));
+        assert!(!has_synth("frank"));
+        assert!(!has_synth("Bob"));
+
+        let mut rng = rand::rng();
+        let (field, value) = lit.query().example_value(&mut rng).unwrap();
+        assert!(lit.matches(&Document::from([(field, value)])));
+        let empty = LitQuery::StrRange(StrRangeQuery::new("name", "f", "a"));
+        assert!(empty.example_value(&mut rng).is_none());
+    }
+
+    #[test]
+    fn test_nested_preheater() {
+        use crate::prelude::CNFQueryable;
+
+        let q = NestedQuery::new("items", "colour".has_value("blue"));
+        let lit = Literal::new(false, LitQuery::Nested(q.clone()));
+        assert_eq!(lit.to_string(), "NESTED(items)(colour:blue)");
+        assert_eq!(lit.cost(), 1500);
+        assert_eq!(
+            lit.percolate_doc_field_values(&PercolatorConfig::default()),
+            vec![("__NESTED__items".into(), "true".into())]
+        );
+
+        let ph = nested_query_preheater(&q);
+        assert!(ph.must_filter);
+        let has_synth = |d: Document| {
+            ph.expand(&d.terms(false))
+                .iter()
+                .any(|tq| tq.field() == "__NESTED__items".into())
+        };
+        assert!(has_synth(
+            Document::default().with_nested("items", [("colour", "red")].into())
+        ));
+        assert!(!has_synth([("items", "blue")].into()));
+        assert!(!has_synth([("itemsx.colour", "blue")].into()));
+
+        let mut rng = rand::rng();
+        assert!(lit.query().example_value(&mut rng).is_none());
+    }
+
+    #[test]
+    fn test_missing_preheater() {
+        let q = MissingQuery::new("colour");
+        let lit = Literal::new(false, LitQuery::Missing(q.clone()));
+        assert_eq!(lit.cost(), 500);
+        assert_eq!(lit.to_string(), "NOT colour:*");
+        assert_eq!(
+            lit.percolate_doc_field_values(&PercolatorConfig::default()),
+            vec![("__MISSING__colour".into(), "true".into())]
+        );
+
+        let ph = missing_query_preheater(&q);
+        assert!(!ph.must_filter);
+        let c = expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("taste", "sweet")]),
+        );
+        assert_eq!(c.literals().len(), 2);
+        assert!(
+            c.literals()
+                .iter()
+                .any(|l| { l.query().term_query().unwrap().field() == "__MISSING__colour".into() })
+        );
+        let c = expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("colour", "blue")]),
+        );
+        assert_eq!(c.literals().len(), 1);
+        let c = expand_clause(&ph, Clause::default());
+        assert_eq!(c.literals().len(), 1);
+    }
+
+    #[test]
+    fn test_exists_missing_negation() {
+        let exists = Literal::new(false, LitQuery::Exists(ExistsQuery::new("colour")));
+        let missing = exists.clone().negate();
+        assert!(!missing.is_negated());
+        assert_eq!(
+            missing,
+            Literal::new(false, LitQuery::Missing(MissingQuery::new("colour")))
+        );
+        assert_eq!(missing.clone().negate(), exists);
+
+        for d in [Document::default(), [("colour", "blue")].into()] {
+            assert_ne!(exists.matches(&d), missing.matches(&d));
+        }
+    }
+
+    #[test]
+    fn test_exists_preheater() {
+        let q = ExistsQuery::new("colour");
+        let lit = Literal::new(false, LitQuery::Exists(q.clone()));
+        assert_eq!(
+            lit.percolate_doc_field_values(&PercolatorConfig::default()),
+            vec![("__EXISTS__colour".into(), "true".into())]
+        );
+
+        let ph = exists_query_preheater(&q);
+        // Index driven only.
+        assert!(!ph.must_filter);
+        let c = expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![
+                TermQuery::new("colour", "blue"),
+                TermQuery::new("colour", "red"),
+            ]),
+        );
+        assert_eq!(c.literals().len(), 3);
+        assert!(
+            c.literals()
+                .iter()
+                .any(|l| { l.query().term_query().unwrap().field() == "__EXISTS__colour".into() })
+        );
+        let c = expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("taste", "sweet")]),
+        );
+        assert_eq!(c.literals().len(), 1);
+    }
+
+    #[test]
+    fn test_h3_max_resolution() {
+        use h3o::{LatLng, Resolution};
+
+        let default_config = PercolatorConfig::default();
+        let coarse_config = PercolatorConfig {
+            h3_max_resolution: Resolution::Seven,
+            ..Default::default()
+        };
+
+        // H3Inside at res 9 is indexed as its res 7 ancestor.
+        let q = H3InsideQuery::new("f", "89194d10693ffff".parse().unwrap());
+        assert_eq!(
+            h3i_to_fvs(&q, &default_config),
+            vec![("__H3_IN_f_9".into(), "89194d10693ffff".into())]
+        );
+        assert!(!h3in_query_preheater(&q, &default_config).must_filter);
+        assert_eq!(
+            h3i_to_fvs(&q, &coarse_config),
+            vec![("__H3_IN_f_7".into(), "87194d106ffffff".into())]
+        );
+        let ph = h3in_query_preheater(&q, &coarse_config);
+        assert!(ph.must_filter);
+        // A document cell at res 8 now finds its res 7 parent.
+        let c = expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("f", "88194d1069fffff")]),
+        );
+        assert!(
+            c.literals()
+                .iter()
+                .any(|l| { l.query().term_query().unwrap().field() == "__H3_IN_f_7".into() })
+        );
+
+        // LatLngWithin: a small radius would be at res 15.
+        let center = LatLng::new(48.864716, 2.349014).unwrap();
+        let llq = LatLngWithinQuery::new("f", center, crate::geotools::Meters(2));
+        assert_eq!(llq.resolution(), Resolution::Fifteen);
+        let fvs = llq_to_fvs(&llq, &coarse_config);
+        assert!(fvs.iter().all(|(f, _)| f.as_ref() == "__H3_IN_f_7"));
+
+        // A fine one is compacted to several resolutions,
+        // and the preheater finds the indexed cell of the center.
+        let llq = LatLngWithinQuery::new("f", center, crate::geotools::Meters(1000));
+        let fvs = llq_to_fvs(&llq, &default_config);
+        assert!(fvs.iter().map(|(f, _)| f).unique().count() > 1);
+        let ph = latlngwithin_preheater(&llq, &default_config);
+        assert!(ph.must_filter);
+        let c = expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("f", "48.864716,2.349014")]),
+        );
+        assert!(c.literals().iter().any(|l| {
+            let tq = l.query().term_query().unwrap();
+            fvs.contains(&(tq.field(), tq.term()))
+        }));
+    }
+
+    #[test]
+    fn test_h3in_any_preheater() {
+        let config = PercolatorConfig::default();
+        let q = H3InsideAnyQuery::new(
+            "f",
+            ["87195d106ffffff", "89194d10693ffff"].map(|c| c.parse().unwrap()),
+        );
+        let l = Literal::new(false, LitQuery::H3InsideAny(q.clone()));
+        assert_eq!(
+            l.percolate_doc_field_values(&config),
+            vec![
+                ("__H3_IN_f_9".into(), "89194d10693ffff".into()),
+                ("__H3_IN_f_7".into(), "87195d106ffffff".into())
+            ]
+        );
+        let ph = h3in_any_query_preheater(&q, &config);
+        assert_eq!(ph.id.as_ref(), "H3IN_f__7_9");
+        assert!(!ph.must_filter);
+
+        // A document cell at res 10 finds its ancestors at both resolutions.
+        let c10 = q.cells()[0].children(h3o::Resolution::Ten).next().unwrap();
+        let c = expand_clause(
+            &ph,
+            Clause::from_termqueries(vec![TermQuery::new("f", c10.to_string())]),
+        );
+        assert_eq!(
+            c.literals()
+                .iter()
+                .skip(1)
+                .map(|l| l.query().term_query().unwrap().field())
+                .collect_vec(),
+            vec!["__H3_IN_f_7".into(), "__H3_IN_f_9".into()]
+        );
+
+        // Clipped to a coarser max resolution, it needs checking.
+        let coarse_config = PercolatorConfig {
+            h3_max_resolution: h3o::Resolution::Seven,
+            ..Default::default()
+        };
+        let ph = h3in_any_query_preheater(&q, &coarse_config);
+        assert_eq!(ph.id.as_ref(), "H3IN_f__7");
+        assert!(ph.must_filter);
+    }
 }
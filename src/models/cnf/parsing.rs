@@ -1,9 +1,11 @@
-use std::{borrow::Cow, fmt::Display};
+use std::{borrow::Cow, fmt::Display, net::IpAddr, ops::Bound};
 
 // Parsing CNF queries
-use chumsky::{container::Seq, prelude::*};
+use chumsky::{container::Seq, error::RichPattern, prelude::*};
 use h3o::CellIndex;
 use h3o::{LatLng, Resolution};
+use hashbrown::HashMap;
+use ipnet::IpNet;
 
 use rand::distr::Alphanumeric;
 use rand::prelude::IteratorRandom;
@@ -11,77 +13,728 @@ use rand::prelude::IteratorRandom;
 use strum::EnumIter;
 use strum::IntoEnumIterator;
 
+use crate::geotools::{Meters, Polygon};
+use crate::logging::log_warn;
+use crate::models::queries::latlng_between::parse_latlng_between;
+use crate::models::queries::latlng_in_polygon::parse_ring;
 use crate::models::queries::latlng_within::parse_latlng_within;
+use crate::models::queries::regex::RegexQuery;
+use crate::models::queries::term::TermQuery;
+use crate::models::queries::text_match::Analyzer;
 use crate::{models::cnf, prelude::CNFQueryable};
 
+#[doc(hidden)]
+pub mod macro_support;
+
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) enum QueryAST {
     Neg(Box<QueryAST>),
     Atom(String, OperatorAST, FieldValueAST),
+    // Both bounds are numbers, and never unbounded.
+    Range(String, Bound<FieldValueAST>, Bound<FieldValueAST>),
+    // Exact terms, like `field IN {a, b}`.
+    Set(String, Vec<String>),
+    // The words of a text, like `field WORD(lowercase) "brown fox"`.
+    Words(String, Analyzer, String),
+    // At least this many distinct values, like `COUNT(tags)>=3`.
+    Count(String, usize),
+    // A 128 bits integer comparison, like `BIGINT(id)>=5`.
+    BigInt(String, OperatorAST, i128),
+    // Inside any of the cells, like `location H3IN {861f09b27ffffff, 871f09b20ffffff}`.
+    H3InAny(String, Vec<CellIndex>),
+    // Within any of the disks, like `position LLWITHIN {48.86,2.34,500; 48.85,2.29,500}`.
+    LatLngWithinAny(String, Vec<(LatLng, Meters)>),
+    // Further than min and within max, like `position LLBETWEEN 48.86,2.34,1000,3000`.
+    LatLngBetween(String, LatLng, Meters, Meters),
+    // A semantic version at least this one, like `SEMVER(version)>=2.3.0`.
+    Semver(String, semver::Version),
+    // Strings between two included ones, like `STR(name) BETWEEN a AND f`.
+    StrRange(String, String, String),
+    // A query on the elements under a path, like `NESTED(items)(colour:blue)`.
+    Nested(String, Box<QueryAST>),
+    // A term matching trimmed and case folded values, like `NORM(colour):blue`.
+    Norm(String, String),
+    // The queries matching all documents, and none.
+    MatchAll,
+    MatchNone,
     And(Box<QueryAST>, Box<QueryAST>),
     Or(Box<QueryAST>, Box<QueryAST>),
+    Boost(Box<QueryAST>, f32),
 }
 
 impl Display for QueryAST {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             QueryAST::Neg(query_ast) => write!(f, "NOT {}", query_ast),
-            QueryAST::Atom(field, operator_ast, field_value_ast) => {
-                write!(f, "{}{}{}", field, operator_ast, field_value_ast)
+            QueryAST::Nested(path, query_ast) => {
+                write!(f, "NESTED({})({})", escape_quote_field(path), query_ast)
+            }
+            QueryAST::Atom(field, operator_ast, field_value_ast) => write!(
+                f,
+                "{}{}{}",
+                escape_quote_field(field),
+                operator_ast,
+                field_value_ast
+            ),
+            QueryAST::Range(field, Bound::Included(low), Bound::Included(high)) => write!(
+                f,
+                "{} BETWEEN {} AND {}",
+                escape_quote_field(field),
+                low,
+                high
+            ),
+            QueryAST::Set(field, terms) => write!(
+                f,
+                "{} IN {{{}}}",
+                escape_quote_field(field),
+                terms
+                    .iter()
+                    .map(|t| escape_quote_set_term(t))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            QueryAST::LatLngWithinAny(field, disks) => write!(
+                f,
+                "{} LLWITHIN {{{}}}",
+                escape_quote_field(field),
+                disks
+                    .iter()
+                    .map(|(ll, radius)| format!("{},{},{}", ll.lat(), ll.lng(), radius.0))
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ),
+            QueryAST::LatLngBetween(field, ll, min, max) => write!(
+                f,
+                "{} LLBETWEEN {},{},{},{}",
+                escape_quote_field(field),
+                ll.lat(),
+                ll.lng(),
+                min.0,
+                max.0
+            ),
+            QueryAST::Words(field, analyzer, text) => write!(
+                f,
+                "{} WORD({}) {}",
+                escape_quote_field(field),
+                analyzer,
+                escape_quote_term(text)
+            ),
+            QueryAST::Count(field, min) => {
+                write!(f, "COUNT({})>={}", escape_quote_field(field), min)
+            }
+            QueryAST::H3InAny(field, cells) => write!(
+                f,
+                "{} H3IN {{{}}}",
+                escape_quote_field(field),
+                cells
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            QueryAST::Semver(field, version) => {
+                write!(f, "SEMVER({})>={}", escape_quote_field(field), version)
+            }
+            QueryAST::BigInt(field, operator, i) => {
+                write!(f, "BIGINT({}){}{}", escape_quote_field(field), operator, i)
+            }
+            QueryAST::MatchAll => write!(f, "MATCH_ALL"),
+            QueryAST::MatchNone => write!(f, "MATCH_NONE"),
+            QueryAST::Norm(field, term) => write!(
+                f,
+                "NORM({}):{}",
+                escape_quote_field(field),
+                escape_quote_term(term)
+            ),
+            QueryAST::StrRange(field, low, high) => write!(
+                f,
+                "STR({}) BETWEEN {} AND {}",
+                escape_quote_field(field),
+                escape_quote_term(low),
+                escape_quote_term(high)
+            ),
+            QueryAST::Range(field, low, high) => {
+                let (low_op, low) = chain_bound(low);
+                let (high_op, high) = chain_bound(high);
+                write!(
+                    f,
+                    "{}{}{}{}{}",
+                    low,
+                    low_op,
+                    escape_quote_field(field),
+                    high_op,
+                    high
+                )
             }
             QueryAST::And(query_ast, query_ast1) => {
                 write!(f, "( {} AND {} )", query_ast, query_ast1)
             }
             QueryAST::Or(query_ast, query_ast1) => write!(f, "( {} OR {} )", query_ast, query_ast1),
+            QueryAST::Boost(query_ast, weight) => write!(f, "{}^{}", query_ast, weight),
         }
     }
 }
 
+// For geographic and IP operators, which cannot compare numbers.
+fn number_as_term<T: Display>(field: &str, operator: &OperatorAST, n: &T) -> cnf::Query {
+    log_warn!(
+        "Number {} for {}{}is not a valid value. Using a term query instead.",
+        n,
+        field,
+        operator
+    );
+    field.has_value(n.to_string())
+}
+
 fn atom_to_cnf(field: &str, operator: &OperatorAST, field_value: &FieldValueAST) -> cnf::Query {
     match (&operator, &field_value) {
+        (OperatorAST::CaseFold, FieldValueAST::Term(t)) => field.has_value_ci(t.clone()),
+        (OperatorAST::CaseFold, FieldValueAST::Prefix(p)) => field.has_prefix_ci(p.clone()),
+        // Numbers have no case, but are still terms.
+        (OperatorAST::CaseFold, FieldValueAST::Integer(i)) => field.has_value_ci(i.to_string()),
+        (OperatorAST::CaseFold, FieldValueAST::Float(x)) => field.has_value_ci(x.to_string()),
+
         // A prefix ALWAYS give a prefix, regardless of operator used.
         // It is a bit dirty, but will fix in the future.
-        (OperatorAST::H3Inside, FieldValueAST::Term(t)) => t
-            .parse::<CellIndex>()
-            .map_or_else(|_err| field.has_value(t.clone()), |ci| field.h3in(ci)),
+        (OperatorAST::H3Inside, FieldValueAST::Term(t)) => t.parse::<CellIndex>().map_or_else(
+            |err| {
+                log_warn!(
+                    "Invalid H3 cell {:?} for {}: {}. Using a term query instead.",
+                    t,
+                    field,
+                    err
+                );
+                field.has_value(t.clone())
+            },
+            |ci| field.h3in(ci),
+        ),
         // Cannot do H3 on integers..
-        (OperatorAST::H3Inside, FieldValueAST::Integer(i)) => field.has_value(i.to_string()),
+        (OperatorAST::H3Inside, FieldValueAST::Integer(i)) => number_as_term(field, operator, i),
+        (OperatorAST::H3Inside, FieldValueAST::Float(x)) => number_as_term(field, operator, x),
 
         (OperatorAST::LatLngWithin, FieldValueAST::Term(t)) => parse_latlng_within(t).map_or_else(
-            || field.has_value(t.clone()),
+            || {
+                log_warn!(
+                    "Invalid LLWITHIN value {:?} for {}. Using a term query instead.",
+                    t,
+                    field
+                );
+                field.has_value(t.clone())
+            },
             |(ll, radius)| field.latlng_within(ll, radius),
         ),
         // Cannot do LL WITHIN on integers..
-        (OperatorAST::LatLngWithin, FieldValueAST::Integer(i)) => field.has_value(i.to_string()),
+        (OperatorAST::LatLngWithin, FieldValueAST::Integer(i)) => {
+            number_as_term(field, operator, i)
+        }
+        (OperatorAST::LatLngWithin, FieldValueAST::Float(x)) => number_as_term(field, operator, x),
+
+        (OperatorAST::LatLngInPolygon, FieldValueAST::Term(t)) => parse_ring(t).map_or_else(
+            || {
+                log_warn!(
+                    "Invalid LLINPOLYGON value {:?} for {}. Using a term query instead.",
+                    t,
+                    field
+                );
+                field.has_value(t.clone())
+            },
+            |ring| field.latlng_in_polygon(&Polygon::new(ring)),
+        ),
+        // Cannot do LL IN POLYGON on numbers either.
+        (OperatorAST::LatLngInPolygon, FieldValueAST::Integer(i)) => {
+            number_as_term(field, operator, i)
+        }
+        (OperatorAST::LatLngInPolygon, FieldValueAST::Float(x)) => {
+            number_as_term(field, operator, x)
+        }
+
+        // A single address is the network of just itself.
+        (OperatorAST::IpIn, FieldValueAST::Term(t)) => t
+            .parse::<IpNet>()
+            .ok()
+            .or_else(|| t.parse::<IpAddr>().ok().map(IpNet::from))
+            .map_or_else(
+                || {
+                    log_warn!(
+                        "Invalid IPIN network {:?} for {}. Using a term query instead.",
+                        t,
+                        field
+                    );
+                    field.has_value(t.clone())
+                },
+                |net| field.ip_in(net),
+            ),
+        (OperatorAST::IpIn, FieldValueAST::Integer(i)) => number_as_term(field, operator, i),
+        (OperatorAST::IpIn, FieldValueAST::Float(x)) => number_as_term(field, operator, x),
 
         (_, FieldValueAST::Prefix(p)) => field.has_prefix(p.clone()),
+        (_, FieldValueAST::Suffix(p)) => field.has_suffix(p.clone()),
+        (_, FieldValueAST::Contains(p)) => field.has_substring(p.clone()),
+        // And for field presence.
+        (_, FieldValueAST::Exists) => field.has_field(),
+        // Same for regexes. Invalid ones fallback to a term.
+        (_, FieldValueAST::Regex(r)) => RegexQuery::new(field, r.clone()).map_or_else(
+            |err| {
+                log_warn!(
+                    "Invalid regex /{}/ for {}: {}. Using a term query instead.",
+                    r,
+                    field,
+                    err
+                );
+                field.has_value(r.clone())
+            },
+            cnf::Query::from_regexquery,
+        ),
         (_, FieldValueAST::Term(t)) => field.has_value(t.clone()),
+        // Templates are bound before this, so this is like
+        // the same placeholder parsed outside of a template.
+        (_, FieldValueAST::Placeholder(name)) => field.has_value(format!("${}", name)),
+        (_, FieldValueAST::Integer(i)) => int_to_cnf(field, operator, *i),
+        // Same for floats.
+        (OperatorAST::Colon, FieldValueAST::Float(x)) => field.has_value(x.to_string()),
+        (OperatorAST::Lt, FieldValueAST::Float(x)) => field.f64_lt(*x),
+        (OperatorAST::Le, FieldValueAST::Float(x)) => field.f64_le(*x),
+        (OperatorAST::Eq, FieldValueAST::Float(x)) => field.f64_eq(*x),
+        (OperatorAST::Ge, FieldValueAST::Float(x)) => field.f64_ge(*x),
+        (OperatorAST::Gt, FieldValueAST::Float(x)) => field.f64_gt(*x),
+    }
+}
+
+// Integers compare as i64 when they fit, and as 128 bits
+// integers otherwise, like unsigned ones beyond i64::MAX.
+fn int_to_cnf(field: &str, operator: &OperatorAST, i: i128) -> cnf::Query {
+    match (operator, i64::try_from(i)) {
+        (OperatorAST::Lt, Ok(i)) => field.i64_lt(i),
+        (OperatorAST::Le, Ok(i)) => field.i64_le(i),
+        (OperatorAST::Eq, Ok(i)) => field.i64_eq(i),
+        (OperatorAST::Ge, Ok(i)) => field.i64_ge(i),
+        (OperatorAST::Gt, Ok(i)) => field.i64_gt(i),
+        (_, Err(_)) => big_int_to_cnf(field, operator, i),
         // Fallback to term style query in case there is ':123'
-        (OperatorAST::Colon, FieldValueAST::Integer(i)) => field.has_value(i.to_string()),
-        (OperatorAST::Lt, FieldValueAST::Integer(i)) => field.i64_lt(*i),
-        (OperatorAST::Le, FieldValueAST::Integer(i)) => field.i64_le(*i),
-        (OperatorAST::Eq, FieldValueAST::Integer(i)) => field.i64_eq(*i),
-        (OperatorAST::Ge, FieldValueAST::Integer(i)) => field.i64_ge(*i),
-        (OperatorAST::Gt, FieldValueAST::Integer(i)) => field.i64_gt(*i),
+        _ => field.has_value(i.to_string()),
+    }
+}
+
+// A 128 bits integers comparison, whatever the value, like `BIGINT(field)>=5`.
+fn big_int_to_cnf(field: &str, operator: &OperatorAST, i: i128) -> cnf::Query {
+    match operator {
+        OperatorAST::Lt => field.i128_lt(i),
+        OperatorAST::Le => field.i128_le(i),
+        OperatorAST::Eq => field.i128_eq(i),
+        OperatorAST::Ge => field.i128_ge(i),
+        OperatorAST::Gt => field.i128_gt(i),
+        _ => field.has_value(i.to_string()),
+    }
+}
+
+// The operator and value of a bound in `low < field <= high`.
+fn chain_bound(bound: &Bound<FieldValueAST>) -> (&'static str, &FieldValueAST) {
+    match bound {
+        Bound::Included(v) => ("<=", v),
+        Bound::Excluded(v) => ("<", v),
+        Bound::Unbounded => unreachable!("Parsed ranges are bounded"),
+    }
+}
+
+// Integer bounds give an integer range, unless the field
+// is a float. Any float bound gives a float range.
+fn range_to_cnf(
+    field: &str,
+    low: &Bound<FieldValueAST>,
+    high: &Bound<FieldValueAST>,
+    field_type: Option<&FieldType>,
+) -> cnf::Query {
+    let is_integer = |b: &Bound<FieldValueAST>| {
+        matches!(
+            b,
+            Bound::Included(FieldValueAST::Integer(_)) | Bound::Excluded(FieldValueAST::Integer(_))
+        )
+    };
+    let as_f64 = |v: &FieldValueAST| match v {
+        FieldValueAST::Integer(i) => *i as f64,
+        FieldValueAST::Float(x) => *x,
+        // Not a number, so nothing is in range.
+        _ => f64::NAN,
+    };
+    if is_integer(low) && is_integer(high) && field_type != Some(&FieldType::Float) {
+        let as_i128 = |v: &FieldValueAST| match v {
+            FieldValueAST::Integer(i) => *i,
+            _ => unreachable!("Checked to be integers"),
+        };
+        let (low, high) = (low.as_ref().map(as_i128), high.as_ref().map(as_i128));
+        let as_i64 = |b: Bound<i128>| match b {
+            Bound::Included(i) => i64::try_from(i).map(Bound::Included),
+            Bound::Excluded(i) => i64::try_from(i).map(Bound::Excluded),
+            Bound::Unbounded => Ok(Bound::Unbounded),
+        };
+        match (as_i64(low), as_i64(high)) {
+            (Ok(l), Ok(h)) => field.i64_range(l, h),
+            // Out of the i64 range, both bounds are 128 bits comparisons.
+            _ => big_int_bound(field, low, true) & big_int_bound(field, high, false),
+        }
+    } else {
+        field.f64_range(low.as_ref().map(as_f64), high.as_ref().map(as_f64))
+    }
+}
+
+// One bound of an integer range out of the i64 range.
+fn big_int_bound(field: &str, bound: Bound<i128>, is_low: bool) -> cnf::Query {
+    match (bound, is_low) {
+        (Bound::Included(i), true) => field.i128_ge(i),
+        (Bound::Excluded(i), true) => field.i128_gt(i),
+        (Bound::Included(i), false) => field.i128_le(i),
+        (Bound::Excluded(i), false) => field.i128_lt(i),
+        (Bound::Unbounded, _) => unreachable!("Parsed ranges are bounded"),
     }
 }
 
 impl QueryAST {
     pub fn to_cnf(&self) -> cnf::Query {
+        self.to_cnf_with(&ParserOptions::default())
+    }
+
+    pub(crate) fn to_cnf_with(&self, options: &ParserOptions) -> cnf::Query {
         match &self {
-            QueryAST::Neg(query) => !query.to_cnf(),
+            QueryAST::Neg(query) => !query.to_cnf_with(options),
+            QueryAST::Nested(path, query) => {
+                cnf::Query::nested(path.as_str(), query.to_cnf_with(options))
+            }
             QueryAST::Atom(field, operator, field_value) => {
-                atom_to_cnf(field, operator, field_value)
+                match options.schema_atom(field, operator, field_value) {
+                    Some((operator, field_value)) => atom_to_cnf(field, &operator, &field_value),
+                    None => atom_to_cnf(field, operator, field_value),
+                }
+            }
+            QueryAST::Range(field, low, high) => {
+                range_to_cnf(field, low, high, options.schema.get(field.as_str()))
+            }
+            QueryAST::Set(field, terms) => field.as_str().has_any_value(terms.clone()),
+            QueryAST::Words(field, analyzer, text) => {
+                cnf::Query::words(field.as_str(), text, *analyzer)
+            }
+            QueryAST::Count(field, min) => cnf::Query::field_count_ge(field.as_str(), *min),
+            QueryAST::H3InAny(field, cells) => field.as_str().h3in_any(cells),
+            QueryAST::LatLngWithinAny(field, disks) => field.as_str().latlng_within_any(disks),
+            QueryAST::LatLngBetween(field, ll, min, max) => {
+                field.as_str().latlng_between(*ll, *min, *max)
+            }
+            QueryAST::Semver(field, version) => {
+                cnf::Query::semver_ge(field.as_str(), &version.to_string()).expect("Parsed version")
+            }
+            QueryAST::BigInt(field, operator, i) => big_int_to_cnf(field, operator, *i),
+            QueryAST::StrRange(field, low, high) => {
+                field.as_str().str_between(low.clone(), high.clone())
+            }
+            QueryAST::MatchAll => cnf::Query::match_all(),
+            QueryAST::MatchNone => cnf::Query::match_none(),
+            QueryAST::Norm(field, term) => {
+                cnf::Query::from_termquery(TermQuery::normalized(field.as_str(), term))
+            }
+            QueryAST::And(query, query1) => {
+                query.to_cnf_with(options) & query1.to_cnf_with(options)
+            }
+            QueryAST::Or(query, query1) => query.to_cnf_with(options) | query1.to_cnf_with(options),
+            QueryAST::Boost(query, weight) => query.to_cnf_with(options).boost(*weight),
+        }
+    }
+
+    // The CNF of this query, within the maximum number of clauses of the options.
+    pub(crate) fn to_cnf_checked(&self, options: &ParserOptions) -> Result<cnf::Query, String> {
+        match options.max_clauses {
+            Some(max) => self.try_to_cnf_with(options, max),
+            None => Ok(self.to_cnf_with(options)),
+        }
+    }
+
+    // Like to_cnf_with, but fails as soon as a part of the query has
+    // more than max_clauses clauses, before building the rest.
+    fn try_to_cnf_with(&self, options: &ParserOptions, max: usize) -> Result<cnf::Query, String> {
+        let q = match &self {
+            QueryAST::Neg(query) => {
+                cnf::Query::try_negation(query.try_to_cnf_with(options, max)?, max)?
+            }
+            QueryAST::Nested(path, query) => {
+                cnf::Query::nested(path.as_str(), query.try_to_cnf_with(options, max)?)
+            }
+            QueryAST::And(query, query1) => {
+                query.try_to_cnf_with(options, max)? & query1.try_to_cnf_with(options, max)?
+            }
+            QueryAST::Or(query, query1) => cnf::Query::try_from_or(
+                vec![
+                    query.try_to_cnf_with(options, max)?,
+                    query1.try_to_cnf_with(options, max)?,
+                ],
+                max,
+            )?,
+            QueryAST::Boost(query, weight) => query.try_to_cnf_with(options, max)?.boost(*weight),
+            // Atoms like words can have several clauses too.
+            _ => self.to_cnf_with(options),
+        };
+        if q.clauses().len() > max {
+            return Err(format!("Too many clauses. Maximum is {}", max));
+        }
+        Ok(q)
+    }
+
+    // The names of the placeholders, in order of first appearance.
+    pub(crate) fn placeholders(&self) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        let mut add = |v: &FieldValueAST| {
+            if let FieldValueAST::Placeholder(name) = v
+                && !names.contains(name)
+            {
+                names.push(name.clone());
+            }
+        };
+        let mut stack = vec![self];
+        while let Some(q) = stack.pop() {
+            match q {
+                QueryAST::Atom(_, _, v) => add(v),
+                QueryAST::Range(_, low, high) => {
+                    for b in [low, high] {
+                        if let Bound::Included(v) | Bound::Excluded(v) = b {
+                            add(v);
+                        }
+                    }
+                }
+                QueryAST::Set(..)
+                | QueryAST::Words(..)
+                | QueryAST::Count(..)
+                | QueryAST::Semver(..)
+                | QueryAST::BigInt(..)
+                | QueryAST::H3InAny(..)
+                | QueryAST::LatLngWithinAny(..)
+                | QueryAST::LatLngBetween(..)
+                | QueryAST::StrRange(..)
+                | QueryAST::Norm(..)
+                | QueryAST::MatchAll
+                | QueryAST::MatchNone => {}
+                QueryAST::Neg(query) | QueryAST::Boost(query, _) | QueryAST::Nested(_, query) => {
+                    stack.push(query)
+                }
+                // Right first, so the left one is popped first.
+                QueryAST::And(query, query1) | QueryAST::Or(query, query1) => {
+                    stack.push(query1);
+                    stack.push(query);
+                }
+            }
+        }
+        names
+    }
+
+    // This query with its placeholders replaced by their values.
+    // Values are exact terms, or numbers where numbers are expected.
+    pub(crate) fn bind(
+        &self,
+        values: &HashMap<&str, &str>,
+        options: &ParserOptions,
+    ) -> Result<QueryAST, String> {
+        // A number, a term, or None for a number if it looks like one.
+        let value = |name: &str, numeric: Option<bool>| {
+            let v = *values
+                .get(name)
+                .ok_or_else(|| format!("Missing value for ${}", name))?;
+            match (numeric, naked_value(v)) {
+                (Some(false), _) => Ok(FieldValueAST::Term(v.to_string())),
+                (Some(true), FieldValueAST::Term(_)) => {
+                    Err(format!("Value {:?} for ${} is not a number", v, name))
+                }
+                (_, n) => Ok(n),
+            }
+        };
+        let bound = |b: &Bound<FieldValueAST>| match b {
+            Bound::Included(FieldValueAST::Placeholder(name)) => {
+                value(name, Some(true)).map(Bound::Included)
+            }
+            Bound::Excluded(FieldValueAST::Placeholder(name)) => {
+                value(name, Some(true)).map(Bound::Excluded)
+            }
+            _ => Ok(b.clone()),
+        };
+        Ok(match self {
+            QueryAST::Atom(field, operator, FieldValueAST::Placeholder(name)) => {
+                let numeric = match (operator, options.schema.get(field.as_str())) {
+                    (OperatorAST::Colon, Some(FieldType::Integer | FieldType::Float)) => Some(true),
+                    (OperatorAST::Colon, None) if options.prefer_numbers => None,
+                    (OperatorAST::Colon, _) => Some(false),
+                    (
+                        OperatorAST::CaseFold
+                        | OperatorAST::H3Inside
+                        | OperatorAST::LatLngWithin
+                        | OperatorAST::LatLngInPolygon
+                        | OperatorAST::IpIn,
+                        _,
+                    ) => Some(false),
+                    _ => Some(true),
+                };
+                QueryAST::Atom(field.clone(), operator.clone(), value(name, numeric)?)
+            }
+            QueryAST::Atom(..)
+            | QueryAST::Set(..)
+            | QueryAST::Words(..)
+            | QueryAST::Count(..)
+            | QueryAST::Semver(..)
+            | QueryAST::BigInt(..)
+            | QueryAST::H3InAny(..)
+            | QueryAST::LatLngWithinAny(..)
+            | QueryAST::LatLngBetween(..)
+            | QueryAST::StrRange(..)
+            | QueryAST::Norm(..)
+            | QueryAST::MatchAll
+            | QueryAST::MatchNone => self.clone(),
+            QueryAST::Range(field, low, high) => {
+                QueryAST::Range(field.clone(), bound(low)?, bound(high)?)
+            }
+            QueryAST::Neg(query) => QueryAST::Neg(Box::new(query.bind(values, options)?)),
+            QueryAST::Nested(path, query) => {
+                QueryAST::Nested(path.clone(), Box::new(query.bind(values, options)?))
+            }
+            QueryAST::And(query, query1) => QueryAST::And(
+                Box::new(query.bind(values, options)?),
+                Box::new(query1.bind(values, options)?),
+            ),
+            QueryAST::Or(query, query1) => QueryAST::Or(
+                Box::new(query.bind(values, options)?),
+                Box::new(query1.bind(values, options)?),
+            ),
+            QueryAST::Boost(query, weight) => {
+                QueryAST::Boost(Box::new(query.bind(values, options)?), *weight)
+            }
+        })
+    }
+
+    // The number of atoms, the nesting depth of this query, and an upper bound
+    // of the number of clauses of its CNF, which disjunctions multiply.
+    // A chain of the same operator like `a AND b AND c` is only one level.
+    // This does not recurse, as the query can be arbitrarily deep.
+    fn measure(&self) -> (usize, usize, usize) {
+        let (mut n_atoms, mut depth) = (0, 0);
+        // The clauses of the CNF of the measured queries, and their literals.
+        let mut clauses: Vec<(usize, usize)> = vec![];
+        // The queries, and whether their sub queries are measured already.
+        let mut stack = vec![(self, 1, false)];
+        while let Some((q, level, subs_measured)) = stack.pop() {
+            if subs_measured {
+                let (a, b) = match q {
+                    // Distributing the negated clauses over each other.
+                    QueryAST::Neg(_) => {
+                        let (c, l) = clauses.pop().expect("Measured sub query");
+                        let c_exp = u32::try_from(c).unwrap_or(u32::MAX);
+                        clauses.push((l.saturating_pow(c_exp), c));
+                        continue;
+                    }
+                    // A single literal, whatever the sub query.
+                    QueryAST::Nested(..) => {
+                        clauses.pop().expect("Measured sub query");
+                        clauses.push((1, 1));
+                        continue;
+                    }
+                    _ => (
+                        clauses.pop().expect("Measured sub query"),
+                        clauses.pop().expect("Measured sub query"),
+                    ),
+                };
+                clauses.push(match q {
+                    QueryAST::And(..) => (a.0.saturating_add(b.0), a.1.max(b.1)),
+                    _ => (a.0.saturating_mul(b.0), a.1.saturating_add(b.1)),
+                });
+                continue;
+            }
+            depth = depth.max(level);
+            match q {
+                QueryAST::Atom(..)
+                | QueryAST::Range(..)
+                | QueryAST::Set(..)
+                | QueryAST::Count(..)
+                | QueryAST::Semver(..)
+                | QueryAST::BigInt(..)
+                | QueryAST::H3InAny(..)
+                | QueryAST::LatLngWithinAny(..)
+                | QueryAST::LatLngBetween(..)
+                | QueryAST::StrRange(..)
+                | QueryAST::Norm(..) => {
+                    n_atoms += 1;
+                    clauses.push((1, 1));
+                }
+                // No clause, and a single empty one.
+                QueryAST::MatchAll => {
+                    n_atoms += 1;
+                    clauses.push((0, 0));
+                }
+                QueryAST::MatchNone => {
+                    n_atoms += 1;
+                    clauses.push((1, 0));
+                }
+                // One clause per word.
+                QueryAST::Words(_, analyzer, text) => {
+                    n_atoms += 1;
+                    clauses.push((analyzer.tokens(text).len().max(1), 1));
+                }
+                QueryAST::Neg(query) | QueryAST::Nested(_, query) => {
+                    stack.push((q, level, true));
+                    stack.push((query, level + 1, false));
+                }
+                QueryAST::Boost(query, _) => stack.push((query, level, false)),
+                QueryAST::And(query, query1) | QueryAST::Or(query, query1) => {
+                    stack.push((q, level, true));
+                    for sub in [query, query1] {
+                        let same_op =
+                            std::mem::discriminant(sub.as_ref()) == std::mem::discriminant(q);
+                        stack.push((sub, if same_op { level } else { level + 1 }, false));
+                    }
+                }
+            }
+        }
+        let (n_clauses, _) = clauses.pop().expect("Measured query");
+        (n_atoms, depth, n_clauses)
+    }
+}
+
+// The default drop is recursive, and would overflow
+// the stack on very deep queries.
+impl Drop for QueryAST {
+    fn drop(&mut self) {
+        fn take_subs(q: &mut QueryAST, stack: &mut Vec<QueryAST>) {
+            let placeholder =
+                || QueryAST::Atom(String::new(), OperatorAST::Colon, FieldValueAST::Exists);
+            match q {
+                QueryAST::Atom(..)
+                | QueryAST::Range(..)
+                | QueryAST::Set(..)
+                | QueryAST::Words(..)
+                | QueryAST::Count(..)
+                | QueryAST::Semver(..)
+                | QueryAST::BigInt(..)
+                | QueryAST::H3InAny(..)
+                | QueryAST::LatLngWithinAny(..)
+                | QueryAST::LatLngBetween(..)
+                | QueryAST::StrRange(..)
+                | QueryAST::Norm(..)
+                | QueryAST::MatchAll
+                | QueryAST::MatchNone => {}
+                QueryAST::Neg(query) | QueryAST::Boost(query, _) | QueryAST::Nested(_, query) => {
+                    stack.push(std::mem::replace(query, placeholder()))
+                }
+                QueryAST::And(query, query1) | QueryAST::Or(query, query1) => {
+                    stack.push(std::mem::replace(query, placeholder()));
+                    stack.push(std::mem::replace(query1, placeholder()));
+                }
             }
-            QueryAST::And(query, query1) => query.to_cnf() & query1.to_cnf(),
-            QueryAST::Or(query, query1) => query.to_cnf() | query1.to_cnf(),
+        }
+        let mut stack = vec![];
+        take_subs(self, &mut stack);
+        while let Some(mut q) = stack.pop() {
+            take_subs(&mut q, &mut stack);
         }
     }
 }
 
+#[doc(hidden)]
 #[derive(Debug, PartialEq, Clone, EnumIter)]
-pub(crate) enum OperatorAST {
+pub enum OperatorAST {
     Colon,
+    // `:~`, to compare values case insensitively.
+    CaseFold,
     Lt,
     Le,
     Eq,
@@ -89,12 +742,15 @@ pub(crate) enum OperatorAST {
     Gt,
     H3Inside,
     LatLngWithin,
+    LatLngInPolygon,
+    IpIn,
 }
 
 impl Display for OperatorAST {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             OperatorAST::Colon => write!(f, ":"),
+            OperatorAST::CaseFold => write!(f, ":~"),
             OperatorAST::Lt => write!(f, "<"),
             OperatorAST::Le => write!(f, "<="),
             OperatorAST::Eq => write!(f, "="),
@@ -102,25 +758,42 @@ impl Display for OperatorAST {
             OperatorAST::Gt => write!(f, ">"),
             OperatorAST::H3Inside => write!(f, " H3IN "),
             OperatorAST::LatLngWithin => write!(f, " LLWITHIN "),
+            OperatorAST::LatLngInPolygon => write!(f, " LLINPOLYGON "),
+            OperatorAST::IpIn => write!(f, " IPIN "),
         }
     }
 }
 
+#[doc(hidden)]
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) enum FieldValueAST {
+pub enum FieldValueAST {
     Term(String),
     Prefix(String),
-    Integer(i64),
+    Suffix(String),
+    Contains(String),
+    Integer(i128),
+    Float(f64),
+    Regex(String),
+    Exists,
+    // A `$name` to bind in a template. See QueryTemplate.
+    Placeholder(String),
 }
 
-static NON_IDENTIFIERS: [char; 12] = [
-    '\\', ' ', '\t', '\n', '"', '(', ')', ':', '*', '<', '>', '=',
+static NON_IDENTIFIERS: [char; 13] = [
+    '\\', ' ', '\t', '\n', '"', '(', ')', ':', '*', '<', '>', '=', '^',
 ];
 
+// Cannot be in naked values of a set.
+static SET_SEPARATORS: [char; 3] = [',', '{', '}'];
+
 // Returns the string if it doesnt contain any NON_IDENTIFIERS characters.
 // Returns the string with NON_IDENTIFIERS characters escaped with a \ instead.
-fn _escape_quote(s: &str) -> Cow<'_, str> {
-    match s.contains(NON_IDENTIFIERS) {
+// Empty strings are quoted too, and so are strings starting with a ~,
+// which would make a `:` before them a `:~`, and strings starting with a /,
+// which could start a regex ending at a later slash, and strings starting
+// with a {, which would make a `H3IN` or `LLWITHIN` before them a list.
+pub(crate) fn escape_quote(s: &str) -> Cow<'_, str> {
+    match s.is_empty() || s.starts_with(['~', '/', '{']) || s.contains(NON_IDENTIFIERS) {
         false => Cow::Borrowed(s),
         true => {
             // 2. We found a special character. We must allocate a new String.
@@ -142,21 +815,67 @@ fn _escape_quote(s: &str) -> Cow<'_, str> {
     }
 }
 
+// Like escape_quote, but terms that look like a number or a regex
+// are also quoted, or they would not be parsed back as terms.
+pub(crate) fn escape_quote_term(s: &str) -> Cow<'_, str> {
+    if s.starts_with('/') || !matches!(naked_value(s), FieldValueAST::Term(_)) {
+        Cow::Owned(format!(
+            "\"{}\"",
+            s.replace('\\', "\\\\").replace('"', "\\\"")
+        ))
+    } else {
+        escape_quote(s)
+    }
+}
+
+// Like escape_quote_term, but terms with the separators
+// of a set like `field IN {a, b}` are also quoted.
+pub(crate) fn escape_quote_set_term(s: &str) -> Cow<'_, str> {
+    if s.contains(SET_SEPARATORS) {
+        Cow::Owned(format!(
+            "\"{}\"",
+            s.replace('\\', "\\\\").replace('"', "\\\"")
+        ))
+    } else {
+        escape_quote_term(s)
+    }
+}
+
+// Like escape_quote, but keywords are quoted too,
+// so a field named like one is not mistaken for it.
+pub(crate) fn escape_quote_field(s: &str) -> Cow<'_, str> {
+    if RESERVED_WORDS.iter().any(|w| w.eq_ignore_ascii_case(s)) {
+        Cow::Owned(format!("\"{}\"", s))
+    } else {
+        escape_quote(s)
+    }
+}
+
 impl Display for FieldValueAST {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            FieldValueAST::Term(s) => write!(f, "{}", _escape_quote(s)),
-            FieldValueAST::Prefix(s) => write!(f, "{}*", _escape_quote(s)),
+            // A term that looks like a number must be quoted,
+            // or it would be parsed back as a number.
+            FieldValueAST::Term(s) => write!(f, "{}", escape_quote_term(s)),
+            FieldValueAST::Prefix(s) => write!(f, "{}*", escape_quote(s)),
+            FieldValueAST::Suffix(s) => write!(f, "*{}", escape_quote(s)),
+            FieldValueAST::Contains(s) => write!(f, "*{}*", escape_quote(s)),
             FieldValueAST::Integer(i) => write!(f, "{}", i),
+            // Debug always has a dot or an exponent, so this cannot be
+            // mistaken for an integer.
+            FieldValueAST::Float(x) => write!(f, "{:?}", x),
+            FieldValueAST::Regex(r) => write!(f, "/{}/", r.replace('/', "\\/")),
+            FieldValueAST::Exists => write!(f, "*"),
+            FieldValueAST::Placeholder(name) => write!(f, "${}", name),
         }
     }
 }
 
 pub(crate) fn random_query<T: rand::Rng>(rng: &mut T, max_depth: usize) -> QueryAST {
     match (rng.random_range(0..4), max_depth) {
-        (_, 0) => _random_atom(rng), // Reached max depth. do not go deeper.
+        (_, 0) => _random_boosted_atom(rng), // Reached max depth. do not go deeper.
         (0, _) => QueryAST::Neg(Box::new(random_query(rng, max_depth - 1))),
-        (1, _) => _random_atom(rng),
+        (1, _) => _random_boosted_atom(rng),
         (2, _) => QueryAST::And(
             Box::new(random_query(rng, max_depth - 1)),
             Box::new(random_query(rng, max_depth - 1)),
@@ -169,22 +888,708 @@ pub(crate) fn random_query<T: rand::Rng>(rng: &mut T, max_depth: usize) -> Query
     }
 }
 
-type MyParseError<'src> = extra::Err<Rich<'src, char>>;
+pub(crate) type MyParseError<'src> = extra::Err<Rich<'src, char>>;
+
+/// The type of a field, to interpret `field:value` in queries.
+/// See [`ParserOptions::field_type`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FieldType {
+    /// Exact values. `field:value` is a term query, even if the value looks like a number.
+    Keyword,
+    /// `field:123` is the same as `field=123`.
+    Integer,
+    /// `field:1.5` is the same as `field=1.5`.
+    Float,
+    /// `field:lat,lng,meters` is the same as `field LLWITHIN lat,lng,meters`.
+    LatLng,
+    /// `field:cell` is the same as `field H3IN cell`.
+    H3Cell,
+}
+
+/// Operators and kinds of values that can be disabled.
+/// See [`ParserOptions::disable_operator`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    /// `<`, `<=`, `=`, `>=`, `>`, `BETWEEN`, chained comparisons like `1 < field <= 2`,
+    /// and `BIGINT(field)>=5`.
+    Comparison,
+    /// `field H3IN cell` and `field H3IN {cell, cell}`.
+    H3Inside,
+    /// `field LLWITHIN lat,lng,meters`, `field LLWITHIN(lat, lng, distance)`
+    /// `field LLWITHIN {lat,lng,meters; lat,lng,meters}` and `field LLBETWEEN lat,lng,min,max`.
+    LatLngWithin,
+    /// `field LLINPOLYGON lat,lng;lat,lng;...`.
+    LatLngInPolygon,
+    /// `field IPIN 10.0.0.0/8`.
+    IpIn,
+    /// Prefix values like `field:value*`.
+    Prefix,
+    /// Suffix values like `field:*value`.
+    Suffix,
+    /// Substring values like `field:*value*`.
+    Contains,
+    /// Regex values like `field:/value.*/`.
+    Regex,
+    /// `field IN {a, b, c}`.
+    TermSet,
+    /// Case insensitive values like `field:~value`.
+    CaseFold,
+    /// `field WORD(lowercase) "brown fox"`.
+    Words,
+    /// `COUNT(field)>=3`.
+    Count,
+    /// `SEMVER(field)>=2.3.0`.
+    Semver,
+    /// `STR(field) BETWEEN a AND f`.
+    StrRange,
+    /// `NESTED(path)(query)`.
+    Nested,
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operator::Comparison => write!(f, "Comparison"),
+            Operator::H3Inside => write!(f, "H3IN"),
+            Operator::LatLngWithin => write!(f, "LLWITHIN"),
+            Operator::LatLngInPolygon => write!(f, "LLINPOLYGON"),
+            Operator::IpIn => write!(f, "IPIN"),
+            Operator::Prefix => write!(f, "Prefix"),
+            Operator::Suffix => write!(f, "Suffix"),
+            Operator::Contains => write!(f, "Contains"),
+            Operator::Regex => write!(f, "Regex"),
+            Operator::TermSet => write!(f, "IN"),
+            Operator::CaseFold => write!(f, ":~"),
+            Operator::Words => write!(f, "WORD"),
+            Operator::Count => write!(f, "COUNT"),
+            Operator::Semver => write!(f, "SEMVER"),
+            Operator::StrRange => write!(f, "STR BETWEEN"),
+            Operator::Nested => write!(f, "NESTED"),
+        }
+    }
+}
+
+/// Options to parse queries with. See [`cnf::Query::parse_with`].
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+///
+/// let options = parsing::ParserOptions::default().case_insensitive_keywords(true);
+/// let q = Query::parse_with("not colour:blue and taste:sweet", &options).unwrap();
+/// assert_eq!(q, "NOT colour:blue AND taste:sweet".parse().unwrap());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ParserOptions {
+    case_insensitive_keywords: bool,
+    max_depth: Option<usize>,
+    max_atoms: Option<usize>,
+    max_clauses: Option<usize>,
+    schema: HashMap<String, FieldType>,
+    default_fields: Vec<String>,
+    placeholders: bool,
+    strict_h3_cells: bool,
+    strict_latlng_within: bool,
+    reserved_words: Vec<String>,
+    disabled_operators: Vec<Operator>,
+    prefer_numbers: bool,
+}
+
+impl ParserOptions {
+    /// Accept the keywords `AND`, `OR`, `NOT`, `BETWEEN`, `IN`, `H3IN`,
+    /// `LLWITHIN`, `LLINPOLYGON` and `IPIN` in any case. Defaults to false.
+    pub fn case_insensitive_keywords(mut self, b: bool) -> Self {
+        self.case_insensitive_keywords = b;
+        self
+    }
+
+    /// Reject queries nested deeper than this. A chain of the same operator
+    /// like `a:a AND b:b AND c:c` is one level, and each `NOT` or
+    /// change of operator adds one. Unlimited by default.
+    ///
+    /// Use with [`ParserOptions::max_clauses`] to safely parse untrusted queries,
+    /// as turning a query into its conjunctive normal form can explode.
+    pub fn max_depth(mut self, n: usize) -> Self {
+        self.max_depth = Some(n);
+        self
+    }
+
+    /// Reject queries with more than this number of `field:value` like atoms.
+    /// Unlimited by default.
+    pub fn max_atoms(mut self, n: usize) -> Self {
+        self.max_atoms = Some(n);
+        self
+    }
+
+    /// Reject queries whose conjunctive normal form could have more than this
+    /// number of clauses, before building it. Disjunctions multiply the clauses
+    /// of their sides, so a few atoms like `(a:1 AND b:1) OR (a:2 AND b:2) OR ...`
+    /// can make a huge query. The other queries are built within the same maximum,
+    /// failing at the first part with too many clauses, even if a `MATCH_ALL`
+    /// would absorb it. Use the same maximum as the percolator builder's `max_clauses`.
+    /// Unlimited by default.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let options = parsing::ParserOptions::default().max_clauses(4);
+    /// assert!(Query::parse_with("(a:1 AND b:1) OR (a:2 AND b:2)", &options).is_ok());
+    /// assert_eq!(
+    ///     Query::parse_with("(a:1 AND b:1) OR (a:2 AND b:2) OR (a:3 AND b:3)", &options),
+    ///     Err("Too many clauses. Maximum is 4".to_string())
+    /// );
+    /// assert!(
+    ///     Query::parse_with("(a:1 AND b:1) OR (a:2 AND b:2) OR (a:3 AND b:3) OR MATCH_ALL", &options)
+    ///         .is_err()
+    /// );
+    /// ```
+    pub fn max_clauses(mut self, n: usize) -> Self {
+        self.max_clauses = Some(n);
+        self
+    }
+
+    /// Interpret `field:value` atoms according to the type of the field.
+    /// Other operators are left as they are, and so are fields without a type.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use mokaccino::prelude::parsing::{FieldType, ParserOptions};
+    ///
+    /// let options = ParserOptions::default()
+    ///     .field_type("price", FieldType::Integer)
+    ///     .field_type("position", FieldType::LatLng);
+    /// let q = Query::parse_with("price:10 AND position:48.86,2.34,1000", &options).unwrap();
+    /// assert_eq!(q, "price=10 AND position LLWITHIN 48.86,2.34,1000".parse().unwrap());
+    /// ```
+    pub fn field_type<T: Into<String>>(mut self, field: T, field_type: FieldType) -> Self {
+        self.schema.insert(field.into(), field_type);
+        self
+    }
+
+    /// Accept bare values without a `field:`, like in a search box,
+    /// and look for them in this field. Call it again to look in
+    /// several fields, in which case any of them can match.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use mokaccino::prelude::parsing::ParserOptions;
+    ///
+    /// let options = ParserOptions::default().default_field("title");
+    /// let q = Query::parse_with("blue AND NOT sweet* AND taste:bitter", &options).unwrap();
+    /// assert_eq!(q, "title:blue AND NOT title:sweet* AND taste:bitter".parse().unwrap());
+    ///
+    /// let options = options.default_field("body");
+    /// let q = Query::parse_with("blue", &options).unwrap();
+    /// assert_eq!(q, "title:blue OR body:blue".parse().unwrap());
+    /// ```
+    pub fn default_field<T: Into<String>>(mut self, field: T) -> Self {
+        self.default_fields.push(field.into());
+        self
+    }
+
+    /// Reject `H3IN` values that are not valid H3 cells, instead of
+    /// turning them into plain terms that never match geographically.
+    /// This includes `field:value` atoms of [`FieldType::H3Cell`] fields.
+    /// Defaults to false.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use mokaccino::prelude::parsing::ParserOptions;
+    ///
+    /// let options = ParserOptions::default().strict_h3_cells(true);
+    /// assert!(Query::parse_with("location H3IN 861f09b27ffffff", &options).is_ok());
+    ///
+    /// let err = Query::parse_with("location H3IN 861f09b27fffff0", &options).unwrap_err();
+    /// assert_eq!(
+    ///     err,
+    ///     "Invalid H3 cell \"861f09b27fffff0\" for location: invalid unused direction pattern. \
+    ///      Expected a hexadecimal H3 cell index like 861f09b27ffffff"
+    /// );
+    /// ```
+    pub fn strict_h3_cells(mut self, b: bool) -> Self {
+        self.strict_h3_cells = b;
+        self
+    }
+
+    /// Reject `LLWITHIN` values that are not like `lat,lng,meters`, instead
+    /// of turning them into plain terms that never match geographically.
+    /// This includes `field:value` atoms of [`FieldType::LatLng`] fields.
+    /// Defaults to false.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use mokaccino::prelude::parsing::ParserOptions;
+    ///
+    /// let options = ParserOptions::default().strict_latlng_within(true);
+    /// assert!(Query::parse_with("position LLWITHIN 48.86,2.34,1000", &options).is_ok());
+    ///
+    /// let err = Query::parse_with("position LLWITHIN 48.86,2.34,1.5", &options).unwrap_err();
+    /// assert_eq!(
+    ///     err,
+    ///     "Invalid LLWITHIN value \"48.86,2.34,1.5\" for position: \
+    ///      the distance is not a whole number of meters. \
+    ///      Expected lat,lng,meters like 48.86,2.34,1000"
+    /// );
+    /// ```
+    pub fn strict_latlng_within(mut self, b: bool) -> Self {
+        self.strict_latlng_within = b;
+        self
+    }
+
+    /// Reserve a word, like a keyword of your domain, so it cannot be used as a
+    /// naked field name or bare value (see [`ParserOptions::default_field`]).
+    /// It follows [`ParserOptions::case_insensitive_keywords`], and
+    /// can still be used quoted. Words must be like identifiers.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use mokaccino::prelude::parsing::ParserOptions;
+    ///
+    /// let options = ParserOptions::default().reserved_word("NEAR");
+    /// assert!(Query::parse_with("NEAR:here", &options).is_err());
+    /// assert!(Query::parse_with("\"NEAR\":here AND NEARBY:here", &options).is_ok());
+    /// ```
+    pub fn reserved_word<T: Into<String>>(mut self, word: T) -> Self {
+        self.reserved_words.push(word.into());
+        self
+    }
+
+    /// Reject queries using this operator, or kind of value.
+    /// All are allowed by default.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use mokaccino::prelude::parsing::{Operator, ParserOptions};
+    ///
+    /// let options = ParserOptions::default()
+    ///     .disable_operator(Operator::H3Inside)
+    ///     .disable_operator(Operator::Regex);
+    /// assert_eq!(
+    ///     Query::parse_with("location H3IN 861f09b27ffffff", &options),
+    ///     Err("H3IN queries are not allowed".to_string())
+    /// );
+    /// assert!(Query::parse_with("name:/bl.*/", &options).is_err());
+    /// assert!(Query::parse_with("location:861f09b27ffffff", &options).is_ok());
+    /// ```
+    pub fn disable_operator(mut self, operator: Operator) -> Self {
+        if !self.disabled_operators.contains(&operator) {
+            self.disabled_operators.push(operator);
+        }
+        self
+    }
+
+    /// Interpret numbers in `field:value` atoms as numbers for fields without
+    /// a type, so `field:10` is `field=10` instead of the term `10`.
+    /// Defaults to false. See [`ParserOptions::field_type`]
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use mokaccino::prelude::parsing::{FieldType, ParserOptions};
+    ///
+    /// let options = ParserOptions::default()
+    ///     .prefer_numbers(true)
+    ///     .field_type("zip", FieldType::Keyword);
+    /// let q = Query::parse_with("price:10 AND zip:75001 AND colour:blue", &options).unwrap();
+    /// assert_eq!(q, "price=10 AND zip:\"75001\" AND colour:blue".parse().unwrap());
+    /// ```
+    pub fn prefer_numbers(mut self, b: bool) -> Self {
+        self.prefer_numbers = b;
+        self
+    }
+
+    // Parse naked `$name` values as placeholders, for templates.
+    pub(crate) fn placeholders(mut self, b: bool) -> Self {
+        self.placeholders = b;
+        self
+    }
+
+    // What a field:value atom means according to the schema,
+    // if it means something else than what was parsed.
+    fn schema_atom(
+        &self,
+        field: &str,
+        operator: &OperatorAST,
+        value: &FieldValueAST,
+    ) -> Option<(OperatorAST, FieldValueAST)> {
+        if *operator != OperatorAST::Colon {
+            return None;
+        }
+        let Some(field_type) = self.schema.get(field) else {
+            return match value {
+                FieldValueAST::Integer(_) | FieldValueAST::Float(_) if self.prefer_numbers => {
+                    Some((OperatorAST::Eq, value.clone()))
+                }
+                _ => None,
+            };
+        };
+        match (field_type, value) {
+            (FieldType::Integer, FieldValueAST::Integer(_) | FieldValueAST::Float(_)) => {
+                Some((OperatorAST::Eq, value.clone()))
+            }
+            (FieldType::Float, FieldValueAST::Integer(i)) => {
+                Some((OperatorAST::Eq, FieldValueAST::Float(*i as f64)))
+            }
+            (FieldType::Float, FieldValueAST::Float(_)) => Some((OperatorAST::Eq, value.clone())),
+            (FieldType::LatLng, FieldValueAST::Term(_)) => {
+                Some((OperatorAST::LatLngWithin, value.clone()))
+            }
+            (FieldType::H3Cell, FieldValueAST::Term(_)) => {
+                Some((OperatorAST::H3Inside, value.clone()))
+            }
+            // Only hexadecimal digits look like integers.
+            (FieldType::H3Cell, FieldValueAST::Integer(i)) => {
+                Some((OperatorAST::H3Inside, FieldValueAST::Term(i.to_string())))
+            }
+            _ => None,
+        }
+    }
+
+    // Checks the parsed query is within the limits.
+    fn check_limits(&self, q: &QueryAST) -> Result<(), String> {
+        let (n_atoms, depth, n_clauses) = q.measure();
+        if let Some(max) = self.max_clauses.filter(|max| n_clauses > *max) {
+            return Err(format!("Too many clauses. Maximum is {}", max));
+        }
+        if let Some(max) = self.max_atoms.filter(|max| n_atoms > *max) {
+            return Err(format!("Too many atoms. Maximum is {}", max));
+        }
+        if let Some(max) = self.max_depth.filter(|max| depth > *max) {
+            return Err(format!("Query too deep. Maximum depth is {}", max));
+        }
+        Ok(())
+    }
+
+    // Checks the query only uses allowed operators.
+    fn check_operators(&self, q: &QueryAST) -> Result<(), String> {
+        if self.disabled_operators.is_empty() {
+            return Ok(());
+        }
+        let mut stack = vec![q];
+        while let Some(q) = stack.pop() {
+            let used = match q {
+                QueryAST::Atom(_, operator, value) => match (operator, value) {
+                    (_, FieldValueAST::Prefix(_)) => Some(Operator::Prefix),
+                    (_, FieldValueAST::Suffix(_)) => Some(Operator::Suffix),
+                    (_, FieldValueAST::Contains(_)) => Some(Operator::Contains),
+                    (_, FieldValueAST::Regex(_)) => Some(Operator::Regex),
+                    (OperatorAST::CaseFold, _) => Some(Operator::CaseFold),
+                    (OperatorAST::H3Inside, _) => Some(Operator::H3Inside),
+                    (OperatorAST::LatLngWithin, _) => Some(Operator::LatLngWithin),
+                    (OperatorAST::LatLngInPolygon, _) => Some(Operator::LatLngInPolygon),
+                    (OperatorAST::IpIn, _) => Some(Operator::IpIn),
+                    (OperatorAST::Colon, _) => None,
+                    _ => Some(Operator::Comparison),
+                },
+                QueryAST::Range(..) | QueryAST::BigInt(..) => Some(Operator::Comparison),
+                QueryAST::H3InAny(..) => Some(Operator::H3Inside),
+                QueryAST::LatLngWithinAny(..) | QueryAST::LatLngBetween(..) => {
+                    Some(Operator::LatLngWithin)
+                }
+                QueryAST::Set(..) => Some(Operator::TermSet),
+                QueryAST::Words(..) => Some(Operator::Words),
+                QueryAST::Count(..) => Some(Operator::Count),
+                QueryAST::Semver(..) => Some(Operator::Semver),
+                QueryAST::StrRange(..) => Some(Operator::StrRange),
+                // A term, like `field:value`.
+                QueryAST::Norm(..) | QueryAST::MatchAll | QueryAST::MatchNone => None,
+                QueryAST::Nested(_, query) => {
+                    stack.push(query);
+                    Some(Operator::Nested)
+                }
+                QueryAST::Neg(query) | QueryAST::Boost(query, _) => {
+                    stack.push(query);
+                    None
+                }
+                QueryAST::And(query, query1) | QueryAST::Or(query, query1) => {
+                    stack.push(query1);
+                    stack.push(query);
+                    None
+                }
+            };
+            if let Some(op) = used.filter(|op| self.disabled_operators.contains(op)) {
+                return Err(format!("{} queries are not allowed", op));
+            }
+        }
+        Ok(())
+    }
+
+    // All the checks of a parsed query.
+    pub(crate) fn check(&self, q: &QueryAST) -> Result<(), String> {
+        self.check_limits(q)?;
+        self.check_operators(q)?;
+        self.check_geo_values(q)
+    }
+
+    // Checks the H3IN and LLWITHIN values of the query, in strict modes.
+    pub(crate) fn check_geo_values(&self, q: &QueryAST) -> Result<(), String> {
+        if !self.strict_h3_cells && !self.strict_latlng_within {
+            return Ok(());
+        }
+        let mut stack = vec![q];
+        while let Some(q) = stack.pop() {
+            match q {
+                QueryAST::Atom(field, operator, value) => {
+                    let (operator, value) = self
+                        .schema_atom(field, operator, value)
+                        .unwrap_or_else(|| (operator.clone(), value.clone()));
+                    let text = match value {
+                        FieldValueAST::Term(t) => t,
+                        FieldValueAST::Integer(i) => i.to_string(),
+                        FieldValueAST::Float(x) => x.to_string(),
+                        _ => continue,
+                    };
+                    match operator {
+                        OperatorAST::H3Inside if self.strict_h3_cells => {
+                            if let Err(e) = text.parse::<CellIndex>() {
+                                // Only keep the reason, after the raw parsed value.
+                                let e = e.to_string();
+                                let reason = e.rsplit(": ").next().unwrap_or(&e);
+                                return Err(format!(
+                                    "Invalid H3 cell {:?} for {}: {}. \
+                                     Expected a hexadecimal H3 cell index like 861f09b27ffffff",
+                                    text, field, reason
+                                ));
+                            }
+                        }
+                        OperatorAST::LatLngWithin
+                            if self.strict_latlng_within
+                                && parse_latlng_within(&text).is_none() =>
+                        {
+                            return Err(format!(
+                                "Invalid LLWITHIN value {:?} for {}: {}. \
+                                 Expected lat,lng,meters like 48.86,2.34,1000",
+                                text,
+                                field,
+                                latlng_within_problem(&text)
+                            ));
+                        }
+                        _ => {}
+                    }
+                }
+                QueryAST::Range(..)
+                | QueryAST::Set(..)
+                | QueryAST::Words(..)
+                | QueryAST::Count(..)
+                | QueryAST::Semver(..)
+                | QueryAST::BigInt(..)
+                | QueryAST::H3InAny(..)
+                | QueryAST::LatLngWithinAny(..)
+                | QueryAST::LatLngBetween(..)
+                | QueryAST::StrRange(..)
+                | QueryAST::Norm(..)
+                | QueryAST::MatchAll
+                | QueryAST::MatchNone => {}
+                QueryAST::Neg(query) | QueryAST::Boost(query, _) | QueryAST::Nested(_, query) => {
+                    stack.push(query)
+                }
+                QueryAST::And(query, query1) | QueryAST::Or(query, query1) => {
+                    stack.push(query1);
+                    stack.push(query);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Why a value is not a valid lat,lng,meters.
+fn latlng_within_problem(t: &str) -> &'static str {
+    let number = |s: &str| s.parse::<f64>().ok().filter(|x| x.is_finite());
+    match t.split(',').collect::<Vec<_>>().as_slice() {
+        [lat, _, _] if number(lat).is_none() => "invalid latitude",
+        [_, lng, _] if number(lng).is_none() => "invalid longitude",
+        [_, _, m] if m.parse::<u64>().is_err() => "the distance is not a whole number of meters",
+        [_, _, _] => "invalid value",
+        _ => "expected three comma separated values",
+    }
+}
+
+// Parses a query string into its AST, within the limits of the options.
+pub(crate) fn parse_ast(s: &str, options: &ParserOptions) -> Result<QueryAST, String> {
+    query_parser_with(options)
+        .parse(s)
+        .into_result()
+        .map_err(|e| {
+            e.iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .and_then(|astq| options.check(&astq).map(|_| astq))
+}
+
+/// What [`cnf::Query::parse_lenient`] could make of a query string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LenientParse {
+    query: Option<cnf::Query>,
+    errors: Vec<String>,
+    suggestion: Option<String>,
+}
+
+impl LenientParse {
+    /// The query from what could be parsed, if anything.
+    pub fn query(&self) -> Option<&cnf::Query> {
+        self.query.as_ref()
+    }
+
+    /// What is wrong with the query string, with positions
+    /// as byte offsets. Empty if it is valid.
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    /// A valid query string for what could be parsed,
+    /// when the query string is not valid.
+    pub fn suggestion(&self) -> Option<&str> {
+        self.suggestion.as_deref()
+    }
+
+    /// True if the query string is valid.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+// A parse error, in words a rule author would understand.
+fn describe_error(e: &Rich<'_, char>) -> String {
+    let at = e.span().start;
+    let expects = |label: &str| {
+        e.expected()
+            .any(|p| matches!(p, RichPattern::Label(l) if l == label))
+    };
+    if e.found().is_none() && expects("closing double quote") {
+        format!("Missing closing double quote at {}", at)
+    } else if e.found().is_none() && expects("closing parenthesis") {
+        format!("Missing closing parenthesis at {}", at)
+    } else {
+        format!("{} at {}", e, at)
+    }
+}
+
+// Parses as much as possible of a query string.
+pub(crate) fn parse_lenient(s: &str, options: &ParserOptions) -> LenientParse {
+    let (ast, errors) = query_parser_with(options).parse(s).into_output_errors();
+    let mut errors = errors.iter().map(describe_error).collect::<Vec<_>>();
+    // Recoveries can report the same furthest error.
+    errors.dedup();
+    let suggestion = ast
+        .as_ref()
+        .filter(|_| !errors.is_empty())
+        .map(|ast| ast.to_string());
+    let query = ast.and_then(|ast| {
+        options
+            .check(&ast)
+            .and_then(|_| ast.to_cnf_checked(options))
+            .map_err(|e| errors.push(e))
+            .ok()
+    });
+    LenientParse {
+        query,
+        errors,
+        suggestion,
+    }
+}
+
+// A keyword, optionally in any case.
+pub(crate) fn keyword_parser<'src>(
+    word: &'static str,
+    any_case: bool,
+) -> Boxed<'src, 'src, &'src str, (), MyParseError<'src>> {
+    if any_case {
+        text::ascii::ident()
+            .filter(move |s: &&str| s.eq_ignore_ascii_case(word))
+            .ignored()
+            .boxed()
+    } else {
+        text::ascii::keyword(word).ignored().boxed()
+    }
+}
+
+// Any of the given words as a whole, optionally in any case.
+fn reserved_words_parser<'src>(
+    words: Vec<String>,
+    any_case: bool,
+) -> Boxed<'src, 'src, &'src str, (), MyParseError<'src>> {
+    text::ascii::ident()
+        .filter(move |s: &&str| {
+            words.iter().any(|w| {
+                if any_case {
+                    w.eq_ignore_ascii_case(s)
+                } else {
+                    w == s
+                }
+            })
+        })
+        .then_ignore(
+            none_of(NON_IDENTIFIERS)
+                .filter(|c: &char| !c.is_whitespace())
+                .not(),
+        )
+        .ignored()
+        .boxed()
+}
 
+#[cfg(test)]
 pub(crate) fn query_parser<'src>() -> impl Parser<'src, &'src str, QueryAST, MyParseError<'src>> {
-    recursive(|expr| {
-        let recursive_atom = atom_parser()
-            .or(expr.delimited_by(just('('), just(')')))
+    query_parser_with(&ParserOptions::default())
+}
+
+pub(crate) fn query_parser_with<'src>(
+    options: &ParserOptions,
+) -> impl Parser<'src, &'src str, QueryAST, MyParseError<'src>> + use<'src> {
+    let any_case = options.case_insensitive_keywords;
+    let placeholders = options.placeholders;
+    let default_fields = options.default_fields.clone();
+    let reserved = reserved_words_parser(options.reserved_words.clone(), any_case);
+    recursive(move |expr| {
+        let atom = if default_fields.is_empty() {
+            atom_parser(any_case, placeholders, reserved.clone()).boxed()
+        } else {
+            atom_parser(any_case, placeholders, reserved.clone())
+                .or(bare_atom_parser(
+                    default_fields.clone(),
+                    any_case,
+                    placeholders,
+                    reserved.clone(),
+                ))
+                .boxed()
+        };
+        // Recovers for lenient parsing, as if it was closed.
+        let closing = just(')')
+            .labelled("closing parenthesis")
+            .recover_with(via_parser(empty().to(')')));
+        // A whole query on the elements under a path.
+        let nested = keyword_parser("NESTED", any_case)
+            .ignore_then(identifier_parser().delimited_by(just('('), just(')')))
+            .then(expr.clone().delimited_by(just('('), closing))
+            .map(|(path, query)| QueryAST::Nested(path, Box::new(query)))
             .padded();
+        let atom = nested
+            .or(atom)
+            .then(boost_parser().or_not())
+            .map(|(atom, boost)| match boost {
+                Some(weight) => QueryAST::Boost(Box::new(atom), weight),
+                None => atom,
+            });
+        let recursive_atom = atom.or(expr.delimited_by(just('('), closing)).padded();
 
-        let unary = text::ascii::keyword("NOT")
+        let unary = keyword_parser("NOT", any_case)
             .padded()
             .repeated()
             .foldr(recursive_atom, |_op, rhs| QueryAST::Neg(Box::new(rhs)))
             .boxed();
 
         let product = unary.clone().foldl(
-            text::ascii::keyword("AND")
+            keyword_parser("AND", any_case)
                 .to(QueryAST::And as fn(_, _) -> _)
                 .then(unary)
                 .repeated(),
@@ -194,7 +1599,7 @@ pub(crate) fn query_parser<'src>() -> impl Parser<'src, &'src str, QueryAST, MyP
         // sum has less precedence than product. So flat queries
         // is a sum of products.
         product.clone().foldl(
-            text::ascii::keyword("OR")
+            keyword_parser("OR", any_case)
                 .to(QueryAST::Or as fn(_, _) -> _)
                 .then(product)
                 .repeated(),
@@ -202,9 +1607,11 @@ pub(crate) fn query_parser<'src>() -> impl Parser<'src, &'src str, QueryAST, MyP
         )
     })
     .padded()
+    // Recovers for lenient parsing, ignoring what cannot be parsed.
+    .then_ignore(end().recover_with(skip_until(any().ignored(), end(), || ())))
 }
 
-fn _random_h3cell<T: rand::Rng>(rng: &mut T) -> h3o::CellIndex {
+pub(crate) fn random_h3cell<T: rand::Rng>(rng: &mut T) -> h3o::CellIndex {
     // 1. Generate a random Longitude: [-180, 180]
     let lng_deg = rng.random_range(-180.0..180.0);
 
@@ -218,65 +1625,509 @@ fn _random_h3cell<T: rand::Rng>(rng: &mut T) -> h3o::CellIndex {
     coord.to_cell(Resolution::Nine)
 }
 
-fn _random_atom<T: rand::Rng>(rng: &mut T) -> QueryAST {
+fn _random_range<T: rand::Rng>(rng: &mut T) -> QueryAST {
+    let mut bound = || {
+        let v = if rng.random_bool(0.5) {
+            _random_field_int_value(rng)
+        } else {
+            _random_field_float_value(rng)
+        };
+        if rng.random_bool(0.5) {
+            Bound::Included(v)
+        } else {
+            Bound::Excluded(v)
+        }
+    };
+    let (low, high) = (bound(), bound());
+    QueryAST::Range(_random_field(rng), low, high)
+}
+
+fn _random_set<T: rand::Rng>(rng: &mut T) -> QueryAST {
+    let n = rng.random_range(1..5);
+    let terms = (0..n).map(|_| _random_messy_string(rng)).collect();
+    QueryAST::Set(_random_field(rng), terms)
+}
+
+fn _random_boosted_atom<T: rand::Rng>(rng: &mut T) -> QueryAST {
+    let atom = match rng.random_range(0..20) {
+        0 => _random_range(rng),
+        1 => _random_set(rng),
+        _ => random_atom(rng),
+    };
+    if rng.random_bool(0.1) {
+        let weight = [0.5, 2.0, 2.5, 10.0].into_iter().choose(rng).unwrap();
+        QueryAST::Boost(Box::new(atom), weight)
+    } else {
+        atom
+    }
+}
+
+pub(crate) fn random_atom<T: rand::Rng>(rng: &mut T) -> QueryAST {
     let op = _random_operator(rng);
     let be_correct = rng.random_bool(0.95);
     match (op, be_correct) {
-        (op, false) => QueryAST::Atom(_random_identifier(rng), op, _random_field_value(rng)),
+        (op, false) => QueryAST::Atom(_random_field(rng), op, _random_field_value(rng)),
         (OperatorAST::Colon, true) => QueryAST::Atom(
-            _random_identifier(rng),
+            _random_field(rng),
             OperatorAST::Colon,
             _random_field_value(rng),
         ),
+        (OperatorAST::CaseFold, true) => QueryAST::Atom(
+            _random_field(rng),
+            OperatorAST::CaseFold,
+            if rng.random_bool(0.5) {
+                FieldValueAST::Term(_random_messy_string(rng))
+            } else {
+                FieldValueAST::Prefix(_random_messy_string(rng))
+            },
+        ),
         (OperatorAST::H3Inside, true) => QueryAST::Atom(
-            _random_identifier(rng),
+            _random_field(rng),
             OperatorAST::H3Inside,
-            FieldValueAST::Term(_random_h3cell(rng).to_string()),
+            FieldValueAST::Term(random_h3cell(rng).to_string()),
         ),
         (OperatorAST::LatLngWithin, true) => {
-            let ll = LatLng::from(_random_h3cell(rng));
+            let ll = LatLng::from(random_h3cell(rng));
             let distance = rng.random_range::<u64, _>(0..10_000_000);
 
             QueryAST::Atom(
-                _random_identifier(rng),
+                _random_field(rng),
                 OperatorAST::LatLngWithin,
                 FieldValueAST::Term(format!("{},{},{}", ll.lat(), ll.lng(), distance)),
             )
         }
+        (OperatorAST::LatLngInPolygon, true) => {
+            // The boundary of a random cell is a nice ring.
+            let ring = random_h3cell(rng)
+                .boundary()
+                .iter()
+                .map(|ll| format!("{},{}", ll.lat(), ll.lng()))
+                .collect::<Vec<_>>();
+            QueryAST::Atom(
+                _random_field(rng),
+                OperatorAST::LatLngInPolygon,
+                FieldValueAST::Term(ring.join(";")),
+            )
+        }
+        (OperatorAST::IpIn, true) => {
+            let net = if rng.random_bool(0.5) {
+                IpNet::new(
+                    IpAddr::from(rng.random::<[u8; 4]>()),
+                    rng.random_range(0..=32),
+                )
+            } else {
+                IpNet::new(
+                    IpAddr::from(rng.random::<[u8; 16]>()),
+                    rng.random_range(0..=128),
+                )
+            };
+            QueryAST::Atom(
+                _random_field(rng),
+                OperatorAST::IpIn,
+                FieldValueAST::Term(net.expect("Valid mask length").trunc().to_string()),
+            )
+        }
         // all these other ones are comparison things..
-        (op, true) => QueryAST::Atom(_random_identifier(rng), op, _random_field_int_value(rng)),
+        (op, true) => {
+            let v = if rng.random_bool(0.5) {
+                _random_field_int_value(rng)
+            } else {
+                _random_field_float_value(rng)
+            };
+            QueryAST::Atom(_random_field(rng), op, v)
+        }
     }
 }
 
-fn atom_parser<'src>() -> impl Parser<'src, &'src str, QueryAST, MyParseError<'src>> {
-    identifier_parser()
-        .then(operator_parser())
-        .then(field_value_parser())
-        .map(|((s, o), v)| QueryAST::Atom(s, o, v))
+fn atom_parser<'src>(
+    any_case: bool,
+    placeholders: bool,
+    reserved: Boxed<'src, 'src, &'src str, (), MyParseError<'src>>,
+) -> impl Parser<'src, &'src str, QueryAST, MyParseError<'src>> {
+    // Reserved words can only be quoted field names.
+    let identifier_parser = move || reserved.clone().not().ignore_then(identifier_parser());
+    let llwithin_call = identifier_parser()
+        .then_ignore(word_operator_parser("LLWITHIN", any_case).padded())
+        .then(llwithin_args_parser())
+        .map(|(s, v)| QueryAST::Atom(s, OperatorAST::LatLngWithin, v));
+
+    let bound = || {
+        value_parser(placeholders).try_map(|v, span| match non_finite_float(v) {
+            v @ (FieldValueAST::Integer(_)
+            | FieldValueAST::Float(_)
+            | FieldValueAST::Placeholder(_)) => Ok(v),
+            v => Err(Rich::custom(
+                span,
+                format!("Range bound {} is not a number", v),
+            )),
+        })
+    };
+    let between = identifier_parser()
+        .then_ignore(keyword_parser("BETWEEN", any_case).padded())
+        .then(bound())
+        .then_ignore(keyword_parser("AND", any_case))
+        .then(bound())
+        .map(|((s, low), high)| QueryAST::Range(s, Bound::Included(low), Bound::Included(high)));
+
+    // Like `10 <= price < 100`
+    let lower = || {
+        choice((
+            just("<=").to(Bound::Included as fn(_) -> _),
+            just('<').to(Bound::Excluded as fn(_) -> _),
+        ))
         .padded()
-}
+    };
+    let chained = bound()
+        .then(lower())
+        .then(identifier_parser())
+        .then(lower())
+        .then(bound())
+        .map(|((((low, low_op), s), high_op), high)| {
+            QueryAST::Range(s, low_op(low), high_op(high))
+        });
 
-fn _random_operator<T: rand::Rng>(rng: &mut T) -> OperatorAST {
-    OperatorAST::iter().choose(rng).unwrap()
-}
-fn operator_parser<'src>() -> impl Parser<'src, &'src str, OperatorAST, MyParseError<'src>> {
-    choice((
-        just(':').to(OperatorAST::Colon),
-        just("H3IN").to(OperatorAST::H3Inside),
-        just("LLWITHIN").to(OperatorAST::LatLngWithin),
-        just("<=").to(OperatorAST::Le),
-        just(">=").to(OperatorAST::Ge),
-        just('<').to(OperatorAST::Lt),
-        just('>').to(OperatorAST::Gt),
-        just('=').to(OperatorAST::Eq),
-    ))
-    .padded()
-}
+    let set = identifier_parser()
+        .then_ignore(keyword_parser("IN", any_case).padded())
+        .then(set_parser())
+        .map(|(s, terms)| QueryAST::Set(s, terms));
 
-static RESERVED_WORDS: [&str; 5] = ["AND", "OR", "NOT", "H3IN", "LLWITHIN"];
+    let h3in_any = identifier_parser()
+        .then_ignore(word_operator_parser("H3IN", any_case).padded())
+        .then(set_parser().try_map(|cells, span| {
+            cells
+                .iter()
+                .map(|c| {
+                    c.parse::<CellIndex>()
+                        .map_err(|e| Rich::custom(span, format!("Invalid H3 cell {:?}: {}", c, e)))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        }))
+        .map(|(s, cells)| QueryAST::H3InAny(s, cells));
 
-fn _random_identifier<T: rand::Rng>(rng: &mut T) -> String {
-    // Pick a random value between 1 and 20
+    let llwithin_any = identifier_parser()
+        .then_ignore(word_operator_parser("LLWITHIN", any_case).padded())
+        .then(
+            none_of(";{}")
+                .repeated()
+                .at_least(1)
+                .to_slice()
+                .try_map(|s: &str, span| {
+                    parse_latlng_within(s.trim()).ok_or_else(|| {
+                        Rich::custom(span, format!("Invalid LLWITHIN value {:?}", s.trim()))
+                    })
+                })
+                .separated_by(just(';'))
+                .at_least(1)
+                .collect::<Vec<_>>()
+                .delimited_by(just('{'), just('}').labelled("closing brace")),
+        )
+        .map(|(s, disks)| QueryAST::LatLngWithinAny(s, disks));
+
+    let llbetween = identifier_parser()
+        .then_ignore(word_operator_parser("LLBETWEEN", any_case).padded())
+        .then(
+            any()
+                .filter(|c: &char| c.is_ascii_digit() || [',', '+', '-', '.', 'e', 'E'].contains(c))
+                .repeated()
+                .at_least(1)
+                .to_slice()
+                .try_map(|s: &str, span| {
+                    parse_latlng_between(s).ok_or_else(|| {
+                        Rich::custom(
+                            span,
+                            format!(
+                                "Invalid LLBETWEEN value {:?}. Expected lat,lng,min,max \
+                                 like 48.86,2.34,1000,3000, with min less than max",
+                                s
+                            ),
+                        )
+                    })
+                }),
+        )
+        .map(|(s, (ll, min, max))| QueryAST::LatLngBetween(s, ll, min, max));
+
+    let words = identifier_parser()
+        .then_ignore(keyword_parser("WORD", any_case).padded())
+        .then(analyzer_parser())
+        .then(term_parser())
+        .map(|((s, analyzer), text)| QueryAST::Words(s, analyzer, text));
+
+    let count = keyword_parser("COUNT", any_case)
+        .ignore_then(identifier_parser().delimited_by(just('('), just(')')))
+        .then_ignore(just(">=").padded())
+        .then(text::int(10).try_map(|s: &str, span| {
+            s.parse::<usize>()
+                .map_err(|_| Rich::custom(span, format!("Invalid count {}", s)))
+        }))
+        .map(|(s, min)| QueryAST::Count(s, min));
+
+    let semver = keyword_parser("SEMVER", any_case)
+        .ignore_then(identifier_parser().delimited_by(just('('), just(')')))
+        .then_ignore(just(">=").padded())
+        .then(term_parser().try_map(|v, span| {
+            semver::Version::parse(&v)
+                .map_err(|e| Rich::custom(span, format!("Invalid version '{}': {}", v, e)))
+        }))
+        .map(|(s, version)| QueryAST::Semver(s, version));
+
+    let big_int = keyword_parser("BIGINT", any_case)
+        .ignore_then(identifier_parser().delimited_by(just('('), just(')')))
+        .then(
+            choice((
+                just("<=").to(OperatorAST::Le),
+                just(">=").to(OperatorAST::Ge),
+                just('<').to(OperatorAST::Lt),
+                just('>').to(OperatorAST::Gt),
+                just('=').to(OperatorAST::Eq),
+            ))
+            .padded(),
+        )
+        .then(
+            just('-')
+                .or_not()
+                .then(text::int(10))
+                .to_slice()
+                .try_map(|s: &str, span| {
+                    s.parse::<i128>()
+                        .map_err(|_| Rich::custom(span, format!("Invalid integer {}", s)))
+                }),
+        )
+        .map(|((s, operator), i)| QueryAST::BigInt(s, operator, i));
+
+    let str_range = keyword_parser("STR", any_case)
+        .ignore_then(identifier_parser().delimited_by(just('('), just(')')))
+        .then_ignore(keyword_parser("BETWEEN", any_case).padded())
+        .then(term_parser())
+        .then_ignore(keyword_parser("AND", any_case))
+        .then(term_parser())
+        .map(|((s, low), high)| QueryAST::StrRange(s, low, high));
+
+    let norm = keyword_parser("NORM", any_case)
+        .ignore_then(identifier_parser().delimited_by(just('('), just(')')))
+        .then_ignore(just(':'))
+        .then(term_parser())
+        .map(|(s, term)| QueryAST::Norm(s, term));
+
+    let match_all = keyword_parser("MATCH_ALL", any_case).to(QueryAST::MatchAll);
+    let match_none = keyword_parser("MATCH_NONE", any_case).to(QueryAST::MatchNone);
+
+    let atom = identifier_parser()
+        .then(operator_parser(any_case))
+        .then(value_parser(placeholders))
+        .map(|((s, o), v)| match o {
+            OperatorAST::Lt
+            | OperatorAST::Le
+            | OperatorAST::Eq
+            | OperatorAST::Ge
+            | OperatorAST::Gt => QueryAST::Atom(s, o, non_finite_float(v)),
+            _ => QueryAST::Atom(s, o, v),
+        });
+
+    llwithin_call
+        .or(between)
+        .or(chained)
+        .or(set)
+        .or(h3in_any)
+        .or(llwithin_any)
+        .or(llbetween)
+        .or(words)
+        .or(count)
+        .or(semver)
+        .or(big_int)
+        .or(str_range)
+        .or(norm)
+        .or(atom)
+        .or(match_all)
+        .or(match_none)
+        .padded()
+}
+
+// A positive weight like `^2.5`, after an atom.
+fn boost_parser<'src>() -> impl Parser<'src, &'src str, f32, MyParseError<'src>> {
+    just('^')
+        .ignore_then(
+            text::digits(10)
+                .then(just('.').then(text::digits(10)).or_not())
+                .to_slice(),
+        )
+        .try_map(|s: &str, span| {
+            s.parse::<f32>()
+                .ok()
+                .filter(|w| w.is_finite())
+                .ok_or_else(|| Rich::custom(span, format!("Invalid boost {}", s)))
+        })
+        .labelled("boost")
+}
+
+// A value without a field, looked for in any of the default fields.
+// Keywords and reserved words are not values, and neither is a lone star.
+fn bare_atom_parser<'src>(
+    fields: Vec<String>,
+    any_case: bool,
+    placeholders: bool,
+    reserved: Boxed<'src, 'src, &'src str, (), MyParseError<'src>>,
+) -> impl Parser<'src, &'src str, QueryAST, MyParseError<'src>> {
+    let keyword = choice((
+        keyword_parser("AND", any_case),
+        keyword_parser("OR", any_case),
+        keyword_parser("NOT", any_case),
+        reserved,
+    ));
+
+    keyword
+        .not()
+        .ignore_then(value_parser(placeholders))
+        .filter(|v| *v != FieldValueAST::Exists)
+        .map(move |v| {
+            fields
+                .iter()
+                .map(|f| QueryAST::Atom(f.clone(), OperatorAST::Colon, v.clone()))
+                .reduce(|lhs, rhs| QueryAST::Or(Box::new(lhs), Box::new(rhs)))
+                .expect("At least one default field")
+        })
+        .padded()
+}
+
+// The arguments of LLWITHIN(lat, lng, distance), where the distance
+// can have a unit. Gives the same term as `LLWITHIN lat,lng,meters`.
+fn llwithin_args_parser<'src>() -> impl Parser<'src, &'src str, FieldValueAST, MyParseError<'src>> {
+    let double = || {
+        any()
+            .filter(|c: &char| c.is_ascii_digit() || ['+', '-', '.', 'e', 'E'].contains(c))
+            .repeated()
+            .at_least(1)
+            .to_slice()
+            .try_map(|s: &str, span| {
+                s.parse::<f64>()
+                    .map_err(|_| Rich::custom(span, format!("Invalid number {}", s)))
+            })
+            .padded()
+    };
+    let unit = choice((
+        just("km").to(1000.0),
+        just("mi").to(1609.344),
+        just('m').to(1.0),
+    ))
+    .or_not()
+    .map(|u| u.unwrap_or(1.0));
+
+    let distance = double()
+        .then(unit)
+        .padded()
+        .map(|(d, u)| d * u)
+        .filter(|m: &f64| m.is_finite() && *m >= 0.0)
+        .labelled("distance");
+
+    double()
+        .then_ignore(just(','))
+        .then(double())
+        .then_ignore(just(','))
+        .then(distance)
+        .delimited_by(just('('), just(')'))
+        .map(|((lat, lng), meters)| {
+            FieldValueAST::Term(format!("{},{},{}", lat, lng, meters.round() as u64))
+        })
+}
+
+// Exact terms like `{a, "b c", 10}`, naked or quoted. Naked
+// values that look like numbers are terms too.
+fn set_parser<'src>() -> impl Parser<'src, &'src str, Vec<String>, MyParseError<'src>> {
+    let quoted = just('"')
+        .ignore_then(
+            just('\\')
+                .ignore_then(any())
+                .or(none_of('"'))
+                .repeated()
+                .collect::<String>(),
+        )
+        .then_ignore(just('"').labelled("closing double quote"));
+
+    let naked = none_of(NON_IDENTIFIERS)
+        .filter(|c: &char| !c.is_whitespace() && !SET_SEPARATORS.contains(c))
+        .repeated()
+        .at_least(1)
+        .collect::<String>();
+
+    quoted
+        .or(naked)
+        .padded()
+        .separated_by(just(','))
+        .at_least(1)
+        .collect::<Vec<_>>()
+        .delimited_by(just('{'), just('}').labelled("closing brace"))
+}
+
+// The analyzer of `WORD(lowercase)`, by its displayed name.
+fn analyzer_parser<'src>() -> impl Parser<'src, &'src str, Analyzer, MyParseError<'src>> {
+    choice((
+        just("lowercase").to(Analyzer::Lowercase),
+        just("whitespace").to(Analyzer::Whitespace),
+        just("alphanumeric").to(Analyzer::Alphanumeric),
+    ))
+    .padded()
+    .delimited_by(just('('), just(')'))
+    .labelled("analyzer")
+}
+
+// A value that can only be text, naked or quoted.
+// Naked integers are their digits.
+fn term_parser<'src>() -> impl Parser<'src, &'src str, String, MyParseError<'src>> {
+    field_value_parser().try_map(|v, span| match v {
+        FieldValueAST::Term(t) => Ok(t),
+        FieldValueAST::Integer(i) => Ok(i.to_string()),
+        _ => Err(Rich::custom(span, format!("{} is not a plain value", v))),
+    })
+}
+
+fn _random_operator<T: rand::Rng>(rng: &mut T) -> OperatorAST {
+    OperatorAST::iter().choose(rng).unwrap()
+}
+// In exact case, the value can directly follow the operator.
+fn word_operator_parser<'src>(
+    word: &'static str,
+    any_case: bool,
+) -> Boxed<'src, 'src, &'src str, (), MyParseError<'src>> {
+    if any_case {
+        keyword_parser(word, true)
+    } else {
+        just(word).ignored().boxed()
+    }
+}
+
+fn operator_parser<'src>(
+    any_case: bool,
+) -> impl Parser<'src, &'src str, OperatorAST, MyParseError<'src>> {
+    choice((
+        just(":~").to(OperatorAST::CaseFold),
+        just(':').to(OperatorAST::Colon),
+        word_operator_parser("H3IN", any_case).to(OperatorAST::H3Inside),
+        word_operator_parser("LLWITHIN", any_case).to(OperatorAST::LatLngWithin),
+        word_operator_parser("LLINPOLYGON", any_case).to(OperatorAST::LatLngInPolygon),
+        word_operator_parser("IPIN", any_case).to(OperatorAST::IpIn),
+        just("<=").to(OperatorAST::Le),
+        just(">=").to(OperatorAST::Ge),
+        just('<').to(OperatorAST::Lt),
+        just('>').to(OperatorAST::Gt),
+        just('=').to(OperatorAST::Eq),
+    ))
+    .padded()
+}
+
+static RESERVED_WORDS: [&str; 10] = [
+    "AND",
+    "OR",
+    "NOT",
+    "BETWEEN",
+    "IN",
+    "H3IN",
+    "LLWITHIN",
+    "LLBETWEEN",
+    "LLINPOLYGON",
+    "IPIN",
+];
+
+fn _random_identifier<T: rand::Rng>(rng: &mut T) -> String {
+    // Pick a random value between 1 and 20
     let len = rng.random_range(2..20);
     let s = (0..len)
         .map(|_| rng.sample(Alphanumeric))
@@ -289,6 +2140,15 @@ fn _random_identifier<T: rand::Rng>(rng: &mut T) -> String {
     }
 }
 
+// Mostly plain identifiers, and sometimes names that need quoting.
+fn _random_field<T: rand::Rng>(rng: &mut T) -> String {
+    if rng.random_bool(0.05) {
+        _random_messy_string(rng)
+    } else {
+        _random_identifier(rng)
+    }
+}
+
 fn _random_messy_string<T: rand::Rng>(rng: &mut T) -> String {
     let len = rng.random_range(1..20);
     let dist = rand::distr::Uniform::new_inclusive(32u8, 126u8).unwrap();
@@ -298,64 +2158,188 @@ fn _random_messy_string<T: rand::Rng>(rng: &mut T) -> String {
         .collect::<String>()
 }
 
+// A field name, quoted like "weird field:name" if it is not a plain identifier.
 fn identifier_parser<'src>() -> impl Parser<'src, &'src str, String, MyParseError<'src>> {
-    none_of(NON_IDENTIFIERS)
+    let quoted = just('"')
+        .ignore_then(
+            just('\\')
+                .ignore_then(any())
+                .or(none_of('"'))
+                .repeated()
+                .collect::<String>(),
+        )
+        .then_ignore(just('"').labelled("closing double quote"))
+        .labelled("Quote enclosed identifier");
+
+    let naked = none_of(NON_IDENTIFIERS)
         .filter(|c: &char| !c.is_whitespace())
         .repeated()
         .at_least(1)
-        .collect::<String>()
-        .padded()
+        .collect::<String>();
+
+    quoted.or(naked).padded()
 }
 
 fn _random_field_int_value<T: rand::Rng>(rng: &mut T) -> FieldValueAST {
     FieldValueAST::Integer(rng.random_range(-1000..1000))
 }
 
+fn _random_field_float_value<T: rand::Rng>(rng: &mut T) -> FieldValueAST {
+    FieldValueAST::Float(rng.random_range(-1000.0..1000.0))
+}
+
+fn _random_field_regex_value<T: rand::Rng>(rng: &mut T) -> FieldValueAST {
+    FieldValueAST::Regex(format!("{}/.*", _random_identifier(rng)))
+}
+
 fn _random_field_value<T: rand::Rng>(rng: &mut T) -> FieldValueAST {
-    match rng.random_range(0..3) {
+    match rng.random_range(0..8) {
         0 => FieldValueAST::Term(_random_messy_string(rng)),
         1 => FieldValueAST::Prefix(_random_messy_string(rng)),
         2 => _random_field_int_value(rng),
+        3 => _random_field_float_value(rng),
+        4 => _random_field_regex_value(rng),
+        5 => FieldValueAST::Exists,
+        6 => FieldValueAST::Suffix(_random_messy_string(rng)),
+        7 => FieldValueAST::Contains(_random_messy_string(rng)),
         _ => unimplemented!(), // This is never hit
     }
 }
 
+// Interprets a naked (not quoted) value.
+// Signed integers first, then finite floats, and
+// fallback to just a string.
+// Integers are 128 bits, so unsigned 64 bits ones are still integers.
+fn naked_value(t: &str) -> FieldValueAST {
+    t.parse::<i128>()
+        .map(FieldValueAST::Integer)
+        .ok()
+        .or_else(|| {
+            t.parse::<f64>()
+                .ok()
+                .filter(|x| x.is_finite())
+                .map(FieldValueAST::Float)
+        })
+        .unwrap_or_else(|| FieldValueAST::Term(t.to_string()))
+}
+
+// Values like `inf` or `NaN` are terms, except where a number is expected,
+// like in comparisons. This is how non-finite floats display.
+fn non_finite_float(v: FieldValueAST) -> FieldValueAST {
+    match v {
+        FieldValueAST::Term(t) => match t.parse::<f64>() {
+            Ok(x) if !x.is_finite() => FieldValueAST::Float(x),
+            _ => FieldValueAST::Term(t),
+        },
+        v => v,
+    }
+}
+
 fn field_value_parser<'src>() -> impl Parser<'src, &'src str, FieldValueAST, MyParseError<'src>> {
     let term_char = just('\\')
         .ignore_then(any()) // After backslash, accept any character
         .or(none_of('"')); // Or any character that's not a quote, as this is meant to use into a phrase parser.
 
-    let phrase = just('"')
+    let quoted = just('"')
         .ignore_then(term_char.repeated().collect::<String>())
-        .then_ignore(just('"').labelled("closing double quote"))
-        .labelled("Quote enclosed phrase")
-        .then(just('*').or_not())
-        .map(|(t, wc)| {
-            if wc.is_some() {
-                FieldValueAST::Prefix(t)
-            } else {
-                FieldValueAST::Term(t)
-            }
-        });
+        // Recovers for lenient parsing, as if it was closed.
+        .then_ignore(
+            just('"')
+                .labelled("closing double quote")
+                .recover_with(via_parser(empty().to('"'))),
+        )
+        .labelled("Quote enclosed phrase");
+
+    let phrase = quoted.then(just('*').or_not()).map(|(t, wc)| {
+        if wc.is_some() {
+            FieldValueAST::Prefix(t)
+        } else {
+            FieldValueAST::Term(t)
+        }
+    });
 
-    let naked_string = none_of(NON_IDENTIFIERS)
+    let naked_text = none_of(NON_IDENTIFIERS)
         .filter(|c: &char| !c.is_whitespace())
         .repeated()
         .at_least(1)
-        .collect::<String>()
+        .collect::<String>();
+
+    let naked_string = naked_text.then(just('*').or_not()).map(|(t, wc)| {
+        if wc.is_some() {
+            FieldValueAST::Prefix(t) // With a wild char, this is ALWAYS a word
+        } else {
+            naked_value(&t)
+        }
+    });
+
+    // A regex is between slashes, with \/ for a literal slash.
+    // It must end the value, so a path like /usr/bin stays a naked string.
+    let regex_char = just('\\')
+        .then(any())
+        .map(|(_, c)| match c {
+            '/' => "/".to_string(),
+            c => format!("\\{}", c),
+        })
+        .or(none_of('/').map(String::from));
+
+    let regex = just('/')
+        .ignore_then(regex_char.repeated().collect::<Vec<_>>())
+        .then_ignore(just('/'))
+        .then_ignore(
+            end()
+                .or(any()
+                    .filter(|c: &char| c.is_whitespace() || *c == ')' || *c == '^')
+                    .ignored())
+                .rewind(),
+        )
+        .map(|parts| FieldValueAST::Regex(parts.concat()))
+        .labelled("Slash enclosed regex");
+
+    // A leading star, like `*.jpg` or `*" jpg"`.
+    // With a trailing star too, like `*cat*`, a substring.
+    let suffix = just('*')
+        .ignore_then(quoted.or(naked_text))
         .then(just('*').or_not())
         .map(|(t, wc)| {
             if wc.is_some() {
-                FieldValueAST::Prefix(t) // With a wild char, this is ALWAYS a word
+                FieldValueAST::Contains(t)
             } else {
-                // Attempt to parse as i64. If fail, fallback to just string.
-                t.parse::<i64>()
-                    .map(FieldValueAST::Integer)
-                    .unwrap_or(FieldValueAST::Term(t))
+                FieldValueAST::Suffix(t)
             }
         });
 
-    choice((phrase, naked_string)).padded()
+    // A lone star: the field exists.
+    let exists = just('*').to(FieldValueAST::Exists);
+
+    choice((phrase, regex, suffix, exists, naked_string)).padded()
+}
+
+// A field value, or a naked `$name` placeholder when parsing a template.
+// A quoted "$name" is always a term.
+fn value_parser<'src>(
+    placeholders: bool,
+) -> Boxed<'src, 'src, &'src str, FieldValueAST, MyParseError<'src>> {
+    if !placeholders {
+        return field_value_parser().boxed();
+    }
+    let placeholder = just('$')
+        .ignore_then(
+            any()
+                .filter(|c: &char| c.is_ascii_alphanumeric() || *c == '_')
+                .repeated()
+                .at_least(1)
+                .collect::<String>(),
+        )
+        // Like `$name*` or `$name.x`, which are plain values.
+        .then_ignore(
+            none_of(NON_IDENTIFIERS)
+                .filter(|c: &char| !c.is_whitespace())
+                .or(just('*'))
+                .not(),
+        )
+        .map(FieldValueAST::Placeholder)
+        .padded();
+    placeholder.or(field_value_parser()).boxed()
 }
 
 #[cfg(test)]
@@ -375,10 +2359,82 @@ mod tests {
             let p = query_parser();
             let pres = p.parse(&s);
             assert!(pres.has_output());
+            // And that it gives the same query back.
+            assert_eq!(pres.output(), Some(&q));
             println!("{}", pres.output().unwrap().to_cnf())
         }
     }
 
+    #[test]
+    fn test_cnf_display_round_trip() {
+        let mut rng = rand::rng();
+        for _ in 0..2000 {
+            let q = random_query(&mut rng, 3).to_cnf();
+            // The CNF query displays as something the parser understands,
+            // and gives back the same query.
+            let s = q.to_string();
+            let q2 = s
+                .parse::<cnf::Query>()
+                .unwrap_or_else(|e| panic!("Cannot parse {}: {}", s, e));
+            assert_eq!(q2.to_string(), s);
+        }
+
+        // Same for the literals only the builder creates,
+        // alone or mixed with parsed ones.
+        let built = vec![
+            cnf::Query::words("title", "Brown FOX", Analyzer::default()),
+            cnf::Query::words("title", "C++ (rocks)", Analyzer::Alphanumeric),
+            cnf::Query::words("title", "42 Brown", Analyzer::Whitespace),
+            cnf::Query::field_count_ge("tags", 3),
+            cnf::Query::field_count_ge("COUNT", 0),
+            cnf::Query::semver_ge("app_version", "2.3.0").unwrap(),
+            cnf::Query::semver_ge("app version", "1.0.0-rc.1+build.5").unwrap(),
+            "name".str_between("a", "f"),
+            "name".str_between("AND", "a \"quoted\" 10"),
+            cnf::Query::nested("items", "colour".has_value("blue") & "price".i64_lt(10)),
+            cnf::Query::nested(
+                "order items",
+                cnf::Query::nested("parts", !"ref".has_prefix("x")).boost(2.0),
+            ),
+            cnf::Query::nested("items", random_query(&mut rng, 2).to_cnf()),
+            cnf::Query::from_termquery(TermQuery::normalized("colour", " Light BLUE ")),
+            cnf::Query::from_termquery(TermQuery::normalized("NORM", "42")),
+            cnf::Query::match_all(),
+            cnf::Query::match_none(),
+            cnf::Query::nested("items", cnf::Query::match_all()),
+            "MATCH_ALL".has_value("MATCH_NONE"),
+            "id".u64_ge(5),
+            "id".i128_eq(-3),
+            "id".u64_gt(u64::MAX),
+            "price".f64_eq(f64::NAN),
+            "price".f64_lt(f64::INFINITY),
+            "price".f64_range(Bound::Unbounded, Bound::Unbounded),
+            "price".f64_between(f64::NAN, 1.5),
+            "location"
+                .h3in_any(&["87194d106ffffff", "861f09b27ffffff"].map(|c| c.parse().unwrap())),
+            "position".latlng_within_any(&[
+                (LatLng::new(48.864716, 2.349014).unwrap(), Meters(1000)),
+                (LatLng::new(-54.35, 18.66).unwrap(), Meters(0)),
+            ]),
+            "position".latlng_between(
+                LatLng::new(48.864716, 2.349014).unwrap(),
+                Meters(1000),
+                Meters(3000),
+            ),
+            "LLBETWEEN".latlng_between(LatLng::new(-1.5, -170.0).unwrap(), Meters(0), Meters(1)),
+        ];
+        for b in built {
+            let q = random_query(&mut rng, 2).to_cnf();
+            for q in [b.clone(), !b.clone(), b.clone() | q.clone(), !b & q] {
+                let s = q.to_string();
+                let q2 = s
+                    .parse::<cnf::Query>()
+                    .unwrap_or_else(|e| panic!("Cannot parse {}: {}", s, e));
+                assert_eq!(q2.to_string(), s);
+            }
+        }
+    }
+
     #[test]
     fn test_query_parser() {
         let p = query_parser();
@@ -416,7 +2472,7 @@ mod tests {
                 .unwrap()
                 .to_cnf()
                 .to_string(),
-            "(AND (OR location=abc))"
+            "location:abc"
         );
 
         assert_eq!(
@@ -425,7 +2481,7 @@ mod tests {
                 .unwrap()
                 .to_cnf()
                 .to_string(),
-            "(AND (OR location=1234))"
+            "location:\"1234\""
         );
 
         assert_eq!(
@@ -434,7 +2490,7 @@ mod tests {
                 .unwrap()
                 .to_cnf()
                 .to_string(),
-            "(AND (OR location=invalidh3))"
+            "location:invalidh3"
         );
 
         // And now a valid h3
@@ -444,12 +2500,12 @@ mod tests {
                 .unwrap()
                 .to_cnf()
                 .to_string(),
-            "(AND (OR location=H3IN=861f09b27ffffff))"
+            "location H3IN 861f09b27ffffff"
         );
 
         assert_eq!(
             p.parse("name:abc").output().unwrap().to_cnf().to_string(),
-            "(AND (OR name=abc))"
+            "name:abc"
         );
 
         assert_eq!(
@@ -474,7 +2530,7 @@ mod tests {
                 .unwrap()
                 .to_cnf()
                 .to_string(),
-            "(AND (OR name=abc) (OR price<=123))"
+            "name:abc AND price<=123"
         );
 
         assert_eq!(
@@ -507,7 +2563,7 @@ mod tests {
                 .unwrap()
                 .to_cnf()
                 .to_string(),
-            "(AND (OR colour=blue* name=abc) (OR colour=blue* ~price<=123))"
+            "(colour:blue* OR name:abc) AND (colour:blue* OR NOT price<=123)"
         );
 
         // AND has precedence
@@ -541,7 +2597,7 @@ mod tests {
                 .unwrap()
                 .to_cnf()
                 .to_string(),
-            "(AND (OR colour=blue* name=abc) (OR colour=blue* ~price<=123))"
+            "(colour:blue* OR name:abc) AND (colour:blue* OR NOT price<=123)"
         );
 
         // Beat precedence with parenthesis
@@ -575,7 +2631,7 @@ mod tests {
                 .unwrap()
                 .to_cnf()
                 .to_string(),
-            "(AND (OR colour=blue* name=abc) (OR ~price<=123))"
+            "(colour:blue* OR name:abc) AND NOT price<=123"
         );
 
         // Try a distributed NOT
@@ -585,7 +2641,7 @@ mod tests {
                 .unwrap()
                 .to_cnf()
                 .to_string(),
-            "(AND (OR ~colour=blue*) (OR ~name=abc) (OR price<=123))"
+            "NOT colour:blue* AND NOT name:abc AND price<=123"
         );
 
         // This rounds trips with the string representation of the query.
@@ -595,7 +2651,7 @@ mod tests {
                 .unwrap()
                 .to_cnf()
                 .to_string(),
-            "(AND (OR ~colour=blue* ~name=abc price<=123))"
+            "(NOT colour:blue* OR NOT name:abc OR price<=123)"
         );
         assert_eq!(
             p.parse("NOT (colour:blue* AND name:abc) OR price<=123")
@@ -608,7 +2664,7 @@ mod tests {
 
     #[test]
     fn test_atom_parser() {
-        let p = atom_parser();
+        let p = atom_parser(false, false, reserved_words_parser(vec![], false));
         assert_eq!(
             p.parse("  name:abc  ").output(),
             Some(&QueryAST::Atom(
@@ -666,6 +2722,17 @@ mod tests {
 
         assert_eq!(p.parse("ab-cd-<").output(), None);
 
+        assert_eq!(
+            p.parse("\"weird field:name\"").output(),
+            Some(&"weird field:name".to_string())
+        );
+        assert_eq!(
+            p.parse(r#""a \"b\" \\c""#).output(),
+            Some(&r#"a "b" \c"#.to_string())
+        );
+        assert_eq!(p.parse("\"\"").output(), Some(&"".to_string()));
+        assert_eq!(p.parse("\"abc").output(), None);
+
         // Test a 100 times the random generation
         let mut rng = rand::rng();
         for _ in 0..100 {
@@ -677,7 +2744,7 @@ mod tests {
 
     #[test]
     fn test_operator_parser() {
-        let p = operator_parser();
+        let p = operator_parser(false);
         assert_eq!(p.parse(":").output(), Some(&OperatorAST::Colon));
         assert_eq!(p.parse(":").output().unwrap().to_string(), ":");
         assert_eq!(p.parse("<").output(), Some(&OperatorAST::Lt));
@@ -695,6 +2762,9 @@ mod tests {
 
         assert_eq!(p.parse("  H3IN   ").output(), Some(&OperatorAST::H3Inside));
         assert_eq!(p.parse("  H3IN   ").output().unwrap().to_string(), " H3IN ");
+
+        assert_eq!(p.parse(" IPIN ").output(), Some(&OperatorAST::IpIn));
+        assert_eq!(p.parse(" IPIN ").output().unwrap().to_string(), " IPIN ");
     }
 
     #[test]
@@ -761,7 +2831,11 @@ mod tests {
             parser.parse("\"123\"").output(),
             Some(&FieldValueAST::Term("123".to_string()))
         );
-        assert_eq!(parser.parse("\"123\"").output().unwrap().to_string(), "123");
+        // Stays quoted, so it is parsed back as a term.
+        assert_eq!(
+            parser.parse("\"123\"").output().unwrap().to_string(),
+            "\"123\""
+        );
 
         assert_eq!(
             parser.parse("123").output(),
@@ -775,6 +2849,27 @@ mod tests {
         );
         assert_eq!(parser.parse("123*").output().unwrap().to_string(), "123*");
 
+        assert_eq!(
+            parser.parse("1.5").output(),
+            Some(&FieldValueAST::Float(1.5))
+        );
+        assert_eq!(parser.parse("1.5").output().unwrap().to_string(), "1.5");
+        assert_eq!(
+            parser.parse("-0.25").output(),
+            Some(&FieldValueAST::Float(-0.25))
+        );
+        assert_eq!(parser.parse("1e3").output().unwrap().to_string(), "1000.0");
+
+        // Non finite floats are just terms.
+        assert_eq!(
+            parser.parse("inf").output(),
+            Some(&FieldValueAST::Term("inf".to_string()))
+        );
+        assert_eq!(
+            parser.parse("NaN").output(),
+            Some(&FieldValueAST::Term("NaN".to_string()))
+        );
+
         assert_eq!(
             parser.parse("-123abc").output(),
             Some(&FieldValueAST::Term("-123abc".to_string()))
@@ -791,21 +2886,21 @@ mod tests_parsing {
 
     #[test]
     fn test_escape_quote() {
-        assert_eq!(_escape_quote("abc"), Cow::Borrowed("abc"));
+        assert_eq!(escape_quote("abc"), Cow::Borrowed("abc"));
         assert_eq!(
-            _escape_quote("abc\"def"),
+            escape_quote("abc\"def"),
             Cow::Owned::<str>("\"abc\\\"def\"".into())
         );
         assert_eq!(
-            _escape_quote("abc\\def"),
+            escape_quote("abc\\def"),
             Cow::Owned::<str>("\"abc\\\\def\"".into())
         );
         assert_eq!(
-            _escape_quote("abc def"),
+            escape_quote("abc def"),
             Cow::Owned::<str>("\"abc def\"".into())
         );
         assert_eq!(
-            _escape_quote("abc:def"),
+            escape_quote("abc:def"),
             Cow::Owned::<str>("\"abc:def\"".into())
         );
     }
@@ -857,33 +2952,1031 @@ mod tests_parsing {
             "\"p space\"*"
         );
         assert_eq!(format!("{}", FieldValueAST::Integer(42)), "42");
+        assert_eq!(format!("{}", FieldValueAST::Integer(-42)), "-42");
+        assert_eq!(format!("{}", FieldValueAST::Term("-42".into())), "\"-42\"");
+        assert_eq!(
+            format!("{}", FieldValueAST::Term("+4.2".into())),
+            "\"+4.2\""
+        );
+        assert_eq!(
+            format!("{}", FieldValueAST::Term("-42abc".into())),
+            "-42abc"
+        );
+        assert_eq!(format!("{}", FieldValueAST::Float(42.0)), "42.0");
+        assert_eq!(format!("{}", FieldValueAST::Float(-0.5)), "-0.5");
+    }
+
+    #[test]
+    fn test_signed_values() {
+        let atom = |s: &str| query_parser().parse(s).into_result().unwrap();
+
+        assert_eq!(
+            atom("price<-5"),
+            QueryAST::Atom("price".into(), OperatorAST::Lt, FieldValueAST::Integer(-5))
+        );
+        assert_eq!(atom("price<-5").to_cnf(), "price".i64_lt(-5));
+        assert_eq!(atom("price >= -5").to_cnf(), "price".i64_ge(-5));
+        assert_eq!(atom("price<+5").to_cnf(), "price".i64_lt(5));
+        assert_eq!(
+            atom(&format!("price>{}", i64::MIN)).to_cnf(),
+            "price".i64_gt(i64::MIN)
+        );
+        // Beyond the i64 range, they are still integers.
+        assert_eq!(
+            atom(&format!("id>{}", u64::MAX)).to_cnf(),
+            "id".u64_gt(u64::MAX)
+        );
+        assert_eq!(
+            atom(&format!("id<={}", i128::MIN)).to_cnf(),
+            "id".i128_le(i128::MIN)
+        );
+        assert_eq!(
+            atom(&format!("id:{}", u64::MAX)).to_cnf(),
+            "id".has_value(u64::MAX.to_string())
+        );
+        // And BIGINT compares as 128 bits integers within the i64 range too.
+        let q = atom("BIGINT(id)>=5").to_cnf();
+        assert_eq!(q, "id".u64_ge(5));
+        assert_eq!(q.to_string(), "BIGINT(id)>=5");
+        assert!(q.matches(&[("id", u64::MAX.to_string().as_str())].into()));
+        assert_eq!(atom("BIGINT(\"my id\") < -3").to_cnf(), "my id".i128_lt(-3));
+        assert!(
+            query_parser()
+                .parse("BIGINT(id)>=5.5")
+                .into_result()
+                .is_err()
+        );
+
+        // Not numbers.
+        assert_eq!(
+            atom("price:-123abc"),
+            QueryAST::Atom(
+                "price".into(),
+                OperatorAST::Colon,
+                FieldValueAST::Term("-123abc".into())
+            )
+        );
+        assert_eq!(
+            atom("price<--5"),
+            QueryAST::Atom(
+                "price".into(),
+                OperatorAST::Lt,
+                FieldValueAST::Term("--5".into())
+            )
+        );
+
+        // Round trip.
+        for s in [
+            "price<-5",
+            "price<=-5",
+            "price=-5",
+            "price:\"-5\"",
+            "price>-0.5",
+        ] {
+            assert_eq!(atom(s).to_string(), s);
+        }
+    }
+
+    #[test]
+    fn test_suffix_values() {
+        let parse = |s: &str| field_value_parser().parse(s).into_result().ok();
+
+        assert_eq!(parse("*.jpg"), Some(FieldValueAST::Suffix(".jpg".into())));
+        assert_eq!(
+            parse("*\" j\\\"pg\""),
+            Some(FieldValueAST::Suffix(" j\"pg".into()))
+        );
+        assert_eq!(parse("*10"), Some(FieldValueAST::Suffix("10".into())));
+        assert_eq!(parse("*"), Some(FieldValueAST::Exists));
+        assert_eq!(FieldValueAST::Suffix(".jpg".into()).to_string(), "*.jpg");
+        assert_eq!(
+            FieldValueAST::Suffix(" jpg".into()).to_string(),
+            "*\" jpg\""
+        );
+
+        let parse = |s: &str| s.parse::<cnf::Query>();
+        let q = parse("file:*.jpg AND NOT file:*\"_thumb.jpg\"").unwrap();
+        assert_eq!(
+            q,
+            "file".has_suffix(".jpg") & !"file".has_suffix("_thumb.jpg")
+        );
+        assert_eq!(parse(&q.to_string()), Ok(q));
+        assert_eq!(
+            parse("file:* AND name:*x").unwrap(),
+            "file".has_field() & "name".has_suffix("x")
+        );
+
+        let options = ParserOptions::default().disable_operator(Operator::Suffix);
+        assert_eq!(
+            cnf::Query::parse_with("file:*.jpg", &options),
+            Err("Suffix queries are not allowed".to_string())
+        );
+        assert!(cnf::Query::parse_with("file:*", &options).is_ok());
+    }
+
+    #[test]
+    fn test_case_fold_values() {
+        let parse = |s: &str| s.parse::<cnf::Query>();
+
+        assert_eq!(parse("colour:~Blue"), Ok("colour".has_value_ci("blue")));
+        assert_eq!(
+            parse("colour:~\"Light Blue\""),
+            Ok("colour".has_value_ci("light blue"))
+        );
+        assert_eq!(parse("path:~/Usr*"), Ok("path".has_prefix_ci("/usr")));
+        assert_eq!(parse("size:~42"), Ok("size".has_value_ci("42")));
+        // A quoted ~ is part of the value.
+        assert_eq!(parse("colour:\"~Blue\""), Ok("colour".has_value("~Blue")));
+        assert_eq!(parse("colour: ~Blue"), Ok("colour".has_value("~Blue")));
+        assert_eq!("colour".has_value("~Blue").to_string(), "colour:\"~Blue\"");
+        assert_eq!("colour".has_prefix("~B").to_string(), "colour:\"~B\"*");
+
+        let q = parse("colour:~Blue AND NOT path:~/Usr* AND name:~\"~x\"").unwrap();
+        assert_eq!(parse(&q.to_string()), Ok(q));
+
+        let options = ParserOptions::default().disable_operator(Operator::CaseFold);
+        assert_eq!(
+            cnf::Query::parse_with("colour:~Blue", &options),
+            Err(":~ queries are not allowed".to_string())
+        );
+        assert!(cnf::Query::parse_with("colour:Blue", &options).is_ok());
+    }
+
+    #[test]
+    fn test_contains_values() {
+        let parse = |s: &str| field_value_parser().parse(s).into_result().ok();
+
+        assert_eq!(parse("*cat*"), Some(FieldValueAST::Contains("cat".into())));
+        assert_eq!(
+            parse("*\"a \\\"cat\"*"),
+            Some(FieldValueAST::Contains("a \"cat".into()))
+        );
+        assert_eq!(parse("*10*"), Some(FieldValueAST::Contains("10".into())));
+        assert_eq!(parse("**"), None);
+        assert_eq!(FieldValueAST::Contains("cat".into()).to_string(), "*cat*");
+        assert_eq!(
+            FieldValueAST::Contains("a cat".into()).to_string(),
+            "*\"a cat\"*"
+        );
+
+        let parse = |s: &str| s.parse::<cnf::Query>();
+        let q = parse("title:*cat* AND (NOT title:*\"hot dog\"* OR title:*.jpg)").unwrap();
+        assert_eq!(
+            q,
+            "title".has_substring("cat")
+                & (!"title".has_substring("hot dog") | "title".has_suffix(".jpg"))
+        );
+        assert_eq!(parse(&q.to_string()), Ok(q));
+
+        let options = ParserOptions::default().disable_operator(Operator::Contains);
+        assert_eq!(
+            cnf::Query::parse_with("title:*cat*", &options),
+            Err("Contains queries are not allowed".to_string())
+        );
+        assert!(cnf::Query::parse_with("title:*cat", &options).is_ok());
+    }
+
+    #[test]
+    fn test_regex_values() {
+        let parse = |s: &str| field_value_parser().parse(s).into_result().ok();
+
+        assert_eq!(
+            parse("/foo.*bar/"),
+            Some(FieldValueAST::Regex("foo.*bar".into()))
+        );
+        assert_eq!(
+            parse("/a\\/b\\d+/"),
+            Some(FieldValueAST::Regex("a/b\\d+".into()))
+        );
+        assert_eq!(
+            FieldValueAST::Regex("a/b\\d+".into()).to_string(),
+            "/a\\/b\\d+/"
+        );
+        // Paths are not regexes.
+        assert_eq!(
+            parse("/usr/bin"),
+            Some(FieldValueAST::Term("/usr/bin".into()))
+        );
+        assert_eq!(parse("/usr/*"), Some(FieldValueAST::Prefix("/usr/".into())));
+        // But need quoting to stay terms.
+        assert_eq!(FieldValueAST::Term("/usr/".into()).to_string(), "\"/usr/\"");
+        // And so do prefixes, or a later slash could end a regex.
+        let q = "p".has_prefix("/a") | "q".has_value("b/");
+        assert_eq!(q.to_string(), "(p:\"/a\"* OR q:b/)");
+        assert_eq!(q.to_string().parse(), Ok(q));
+
+        let q = query_parser()
+            .parse("name:/foo.*bar/ AND (path:/usr/bin OR path:/b[ai]n/)")
+            .into_result()
+            .unwrap()
+            .to_cnf();
+        assert!(q.matches(&[("name", "foo and bar"), ("path", "ban")].into()));
+        assert!(q.matches(&[("name", "foobar"), ("path", "/usr/bin")].into()));
+        assert!(!q.matches(&[("name", "foobar"), ("path", "bon")].into()));
+
+        // Invalid regexes are just terms.
+        let q = atom_to_cnf("f", &OperatorAST::Colon, &FieldValueAST::Regex("a(".into()));
+        assert_eq!(q.to_string(), "f:\"a(\"");
+    }
+
+    #[test]
+    fn test_case_insensitive_keywords() {
+        let strict = query_parser();
+        let any_case = query_parser_with(&ParserOptions::default().case_insensitive_keywords(true));
+
+        let q = "not a:b and (c:d Or e:f) AND loc h3in 861f09b27ffffff OR pos LlWithin 0,0,10";
+        assert!(strict.parse(q).has_errors());
+        assert_eq!(
+            any_case.parse(q).into_result().unwrap(),
+            strict
+                .parse(
+                    "NOT a:b AND (c:d OR e:f) AND loc H3IN 861f09b27ffffff OR pos LLWITHIN 0,0,10"
+                )
+                .into_result()
+                .unwrap()
+        );
+
+        // Keywords are whole words.
+        assert!(any_case.parse("a:b andc:d").has_errors());
+        // Fields can still look like keywords.
+        assert_eq!(
+            any_case.parse("android:b").into_result().unwrap(),
+            strict.parse("android:b").into_result().unwrap()
+        );
+        // Upper case still works the same.
+        assert_eq!(
+            any_case.parse("a:b AND NOT c:d").into_result().unwrap(),
+            strict.parse("a:b AND NOT c:d").into_result().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_measure() {
+        let measure = |s: &str| query_parser().parse(s).into_result().unwrap().measure();
+        assert_eq!(measure("a:a"), (1, 1, 1));
+        assert_eq!(measure("((a:a))"), (1, 1, 1));
+        assert_eq!(measure("NOT a:a"), (1, 2, 1));
+        assert_eq!(measure("a:a AND b:b AND c:c"), (3, 2, 3));
+        assert_eq!(measure("a:a OR b:b AND c:c"), (3, 3, 2));
+        assert_eq!(measure("a:a OR (b:b OR c:c)"), (3, 2, 1));
+        assert_eq!(measure("a:a AND NOT (b:b OR NOT c:c)"), (3, 5, 3));
+        assert_eq!(measure("NOT (a:a AND b:b) AND (c:c OR d:d^2)"), (4, 4, 2));
+        assert_eq!(measure("(a:a AND b:b) OR (c:c AND d:d AND e:e)"), (5, 3, 6));
+        assert_eq!(measure("NOT ((a:a OR b:b) AND (c:c OR d:d))"), (4, 4, 4));
+
+        // As many clauses as the CNF, or more.
+        let mut rng = rand::rng();
+        for _ in 0..300 {
+            let q = random_query(&mut rng, 3);
+            assert!(q.to_cnf().clauses().len() <= q.measure().2);
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let options = ParserOptions::default().max_depth(3).max_atoms(4);
+        let parse = |s: &str| cnf::Query::parse_with(s, &options);
+
+        assert!(parse("a:a AND b:b AND c:c AND d:d").is_ok());
+        assert!(parse("a:a OR b:b AND NOT c:c").is_err());
+        assert!(parse("a:a AND b:b AND c:c AND d:d AND e:e").is_err());
+
+        // Pathological queries are rejected before
+        // being turned into CNF.
+        let explosive = (0..40)
+            .map(|i| format!("(a:{} AND b:{})", i, i))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        assert!(
+            cnf::Query::parse_with(&explosive, &ParserOptions::default().max_atoms(50)).is_err()
+        );
+        // Few atoms, but an explosive CNF.
+        let options = ParserOptions::default()
+            .max_depth(3)
+            .max_atoms(32)
+            .max_clauses(1000);
+        let explosive = (0..16)
+            .map(|i| format!("(a:{} AND b:{})", i, i))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        assert_eq!(
+            cnf::Query::parse_with(&explosive, &options),
+            Err("Too many clauses. Maximum is 1000".to_string())
+        );
+        assert!(cnf::Query::parse_with("(a:1 AND b:1) OR (a:2 AND b:2)", &options).is_ok());
+        let deep = format!("{}a:a", "NOT ".repeat(100_000));
+        assert!(cnf::Query::parse_with(&deep, &options).is_err());
+    }
+
+    #[test]
+    fn test_strict_h3_cells() {
+        let lax = ParserOptions::default();
+        let strict = ParserOptions::default()
+            .strict_h3_cells(true)
+            .field_type("cell", FieldType::H3Cell);
+
+        let q = "colour:blue AND NOT location H3IN 861f09b27ffffff";
+        assert!(cnf::Query::parse_with(q, &strict).is_ok());
+
+        // Lax, bad cells are terms.
+        let q = "colour:blue AND NOT location H3IN blabla";
+        assert_eq!(
+            cnf::Query::parse_with(q, &lax),
+            Ok("colour".has_value("blue") & !"location".has_value("blabla"))
+        );
+        assert_eq!(
+            cnf::Query::parse_with(q, &strict),
+            Err(
+                "Invalid H3 cell \"blabla\" for location: invalid 64-bit hex number. \
+                 Expected a hexadecimal H3 cell index like 861f09b27ffffff"
+                    .to_string()
+            )
+        );
+        assert!(cnf::Query::parse_with("location H3IN 0^2", &strict).is_err());
+        assert!(cnf::Query::parse_with("location H3IN 1.5", &strict).is_err());
+        // Schema cells too.
+        assert!(cnf::Query::parse_with("cell:861f09b27ffffff", &strict).is_ok());
+        assert!(cnf::Query::parse_with("cell:8f1f09b27ffffff", &strict).is_err());
+        // Other operators are not concerned.
+        assert!(cnf::Query::parse_with("location:blabla AND location H3IN b*", &strict).is_ok());
+    }
+
+    #[test]
+    fn test_strict_latlng_within() {
+        let strict = ParserOptions::default()
+            .strict_latlng_within(true)
+            .field_type("position", FieldType::LatLng);
+        let parse = |s: &str| cnf::Query::parse_with(s, &strict);
+
+        assert!(parse("p LLWITHIN 48.86,2.34,1000 AND p LLWITHIN(48.86, 2.34, 1km)").is_ok());
+        assert!(parse("position:48.86,2.34,1000").is_ok());
+        // Only LLWITHIN values are checked.
+        assert!(parse("p:48.86 AND p H3IN blabla").is_ok());
+        assert_eq!(
+            cnf::Query::parse_with("p LLWITHIN 48.86,2.34", &ParserOptions::default()),
+            Ok("p".has_value("48.86,2.34"))
+        );
+
+        let problem = |s: &str| {
+            let e = parse(s).unwrap_err();
+            e.split(": ")
+                .nth(1)
+                .unwrap()
+                .split(". ")
+                .next()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            parse("NOT p LLWITHIN 48.86,2.34"),
+            Err("Invalid LLWITHIN value \"48.86,2.34\" for p: \
+                 expected three comma separated values. \
+                 Expected lat,lng,meters like 48.86,2.34,1000"
+                .to_string())
+        );
+        assert_eq!(problem("p LLWITHIN north,2.34,10"), "invalid latitude");
+        assert_eq!(problem("p LLWITHIN 48.86,inf,10"), "invalid longitude");
+        assert_eq!(
+            problem("p LLWITHIN 48.86,2.34,-10"),
+            "the distance is not a whole number of meters"
+        );
+        assert_eq!(
+            problem("position:48.86,2.34,10km"),
+            "the distance is not a whole number of meters"
+        );
+    }
+
+    #[test]
+    fn test_parse_lenient() {
+        let options = ParserOptions::default();
+        let lenient = |s: &str| cnf::Query::parse_lenient(s, &options);
+
+        let p = lenient("colour:blue AND NOT taste:sweet");
+        assert!(p.is_ok());
+        assert_eq!(p.suggestion(), None);
+        assert_eq!(
+            p.query(),
+            "colour:blue AND NOT taste:sweet".parse().ok().as_ref()
+        );
+
+        // What cannot be parsed is left out.
+        let p = lenient("colour:blue) AND taste:sweet");
+        assert_eq!(p.errors().len(), 1);
+        assert!(p.errors()[0].ends_with(" at 11"), "{:?}", p.errors());
+        assert_eq!(p.suggestion(), Some("colour:blue"));
+        assert_eq!(p.query(), Some(&"colour".has_value("blue")));
+
+        let p = lenient("(colour:blue OR colour:red AND taste:");
+        // The furthest error is reported once.
+        assert_eq!(p.errors().len(), 1);
+        assert!(p.errors()[0].starts_with("found end of input expected"));
+        assert!(p.errors()[0].ends_with(" at 37"));
+        assert_eq!(p.suggestion(), Some("( colour:blue OR colour:red )"));
+
+        // Nothing to recover.
+        let p = lenient("AND colour:blue");
+        assert!(!p.is_ok());
+        assert_eq!((p.query(), p.suggestion()), (None, None));
+
+        // Recovered queries are still checked.
+        let options = ParserOptions::default().max_atoms(1);
+        let p = cnf::Query::parse_lenient("(a:a OR b:b", &options);
+        assert_eq!(p.suggestion(), Some("( a:a OR b:b )"));
+        assert_eq!(p.query(), None);
+        assert_eq!(
+            p.errors(),
+            &[
+                "Missing closing parenthesis at 11".to_string(),
+                "Too many atoms. Maximum is 1".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parser_configuration() {
+        let options = ParserOptions::default()
+            .reserved_word("NEAR")
+            .default_field("text");
+        let parse = |s: &str| cnf::Query::parse_with(s, &options);
+        assert!(parse("NEAR:a").is_err());
+        assert!(parse("a:b AND NEAR").is_err());
+        assert!(parse("NEAR < 2").is_err());
+        assert_eq!(parse("near:a AND NEARBY:a"), "near:a AND NEARBY:a".parse());
+        assert_eq!(parse("\"NEAR\":a"), Ok("NEAR".has_value("a")));
+        assert_eq!(parse("\"NEAR\""), Ok("text".has_value("NEAR")));
+        assert_eq!(parse("a:NEAR"), Ok("a".has_value("NEAR")));
+        let options = options.case_insensitive_keywords(true);
+        assert!(cnf::Query::parse_with("near:a", &options).is_err());
+
+        let options = ParserOptions::default()
+            .disable_operator(Operator::Comparison)
+            .disable_operator(Operator::Prefix)
+            .disable_operator(Operator::Prefix)
+            .field_type("price", FieldType::Integer);
+        let parse = |s: &str| cnf::Query::parse_with(s, &options);
+        for s in [
+            "NOT a:b AND price>=1",
+            "price BETWEEN 1 AND 2",
+            "1 < price < 2",
+        ] {
+            assert_eq!(parse(s), Err("Comparison queries are not allowed".into()));
+        }
+        assert_eq!(parse("a:b*"), Err("Prefix queries are not allowed".into()));
+        // Only written operators are checked.
+        assert_eq!(parse("price:1"), Ok("price".i64_eq(1)));
+        assert!(parse("a:* AND a:/b.*/").is_ok());
+        assert!(
+            cnf::Query::parse_lenient("(a:b* OR c:d", &options)
+                .query()
+                .is_none()
+        );
+
+        let options = ParserOptions::default()
+            .prefer_numbers(true)
+            .field_type("zip", FieldType::Keyword);
+        let parse = |s: &str| cnf::Query::parse_with(s, &options);
+        assert_eq!(
+            parse("price:10 OR weight:-1.5 OR zip:10 OR name:10a"),
+            Ok("price".i64_eq(10)
+                | "weight".f64_eq(-1.5)
+                | "zip".has_value("10")
+                | "name".has_value("10a"))
+        );
+        assert_eq!("price:10".parse(), Ok("price".has_value("10")));
+        let t = cnf::template::QueryTemplate::parse_with("price:$p AND zip:$p", &options).unwrap();
+        assert_eq!(
+            t.bind(&[("p", "10")]),
+            Ok("price".i64_eq(10) & "zip".has_value("10"))
+        );
+        assert_eq!(
+            t.bind(&[("p", "ten")]),
+            Ok("price".has_value("ten") & "zip".has_value("ten"))
+        );
+    }
+
+    #[test]
+    fn test_schema() {
+        let options = ParserOptions::default()
+            .field_type("name", FieldType::Keyword)
+            .field_type("price", FieldType::Integer)
+            .field_type("weight", FieldType::Float)
+            .field_type("position", FieldType::LatLng)
+            .field_type("location", FieldType::H3Cell);
+        let parse = |s: &str| cnf::Query::parse_with(s, &options).unwrap();
+        let parse_plain = |s: &str| s.parse::<cnf::Query>().unwrap();
+
+        assert_eq!(parse("name:123"), "name".has_value("123"));
+        assert_eq!(parse("price:123"), "price".i64_eq(123));
+        assert_eq!(parse("price:1.5"), "price".f64_eq(1.5));
+        assert_eq!(parse("weight:12"), "weight".f64_eq(12.0));
+        assert_eq!(parse("weight:-1.5"), "weight".f64_eq(-1.5));
+        assert_eq!(
+            parse("position:48.86,2.34,1000"),
+            parse_plain("position LLWITHIN 48.86,2.34,1000")
+        );
+        assert_eq!(
+            parse("location:861f09b27ffffff"),
+            parse_plain("location H3IN 861f09b27ffffff")
+        );
+
+        // Other operators and values are untouched.
+        assert_eq!(parse("price<123"), "price".i64_lt(123));
+        assert_eq!(parse("price:12*"), "price".has_prefix("12"));
+        assert_eq!(parse("price:*"), "price".has_field());
+        assert_eq!(parse("price:abc"), "price".has_value("abc"));
+        assert_eq!(parse("position:nowhere"), "position".has_value("nowhere"));
+        // So are fields without a type.
+        assert_eq!(parse("colour:123"), "colour".has_value("123"));
+        assert_eq!(
+            parse("NOT price:10 OR colour:blue"),
+            !"price".i64_eq(10) | "colour".has_value("blue")
+        );
+    }
+
+    #[test]
+    fn test_boost() {
+        let parse = |s: &str| s.parse::<cnf::Query>();
+        assert_eq!(
+            parse("colour:blue^2.5 AND taste:sweet").unwrap(),
+            "colour".has_value("blue").boost(2.5) & "taste".has_value("sweet")
+        );
+        assert_eq!(
+            parse("name:bl*^2 OR name:/bl.*/^3 OR NOT name:\"dark blue\"^0.5").unwrap(),
+            "name".has_prefix("bl").boost(2.0)
+                | "name"
+                    .has_regex(&regex::Regex::new("bl.*").unwrap())
+                    .boost(3.0)
+                | !"name".has_value("dark blue").boost(0.5)
+        );
+        assert_eq!(parse("price>10^2").unwrap(), "price".i64_gt(10).boost(2.0));
+        assert_eq!(parse("price:*^2").unwrap(), "price".has_field().boost(2.0));
+        // A caret in a value must be quoted.
+        assert_eq!(parse("name:\"a^2\"").unwrap(), "name".has_value("a^2"));
+        assert_eq!("name".has_value("a^2").to_string(), "name:\"a^2\"");
+
+        assert!(parse("colour:blue^").is_err());
+        assert!(parse("colour:blue^-1").is_err());
+        assert!(parse("colour:blue^x").is_err());
+        assert!(parse("(colour:blue)^2").is_err());
+
+        // Bare values can be boosted too.
+        let options = ParserOptions::default().default_field("text");
+        assert_eq!(
+            cnf::Query::parse_with("blue^2 AND sweet", &options).unwrap(),
+            "text".has_value("blue").boost(2.0) & "text".has_value("sweet")
+        );
+    }
+
+    #[test]
+    fn test_between() {
+        let parse = |s: &str| s.parse::<cnf::Query>();
+        let q = parse("price BETWEEN 10 AND 100").unwrap();
+        assert_eq!(q, "price".i64_between(10, 100));
+        // One literal, that displays as it parses.
+        assert_eq!(q.clauses().len(), 1);
+        assert_eq!(q.to_string(), "price BETWEEN 10 AND 100");
+
+        assert_eq!(
+            parse("price BETWEEN -1.5 AND 2 AND colour:blue").unwrap(),
+            "price".f64_between(-1.5, 2.0) & "colour".has_value("blue")
+        );
+        assert_eq!(
+            parse("NOT price BETWEEN 1 AND 2^2 OR size BETWEEN 3 AND 4").unwrap(),
+            !"price".i64_between(1, 2).boost(2.0) | "size".i64_between(3, 4)
+        );
+        assert_eq!(
+            parse("(price BETWEEN 1 AND 2)").unwrap(),
+            "price".i64_between(1, 2)
+        );
+
+        // Non-finite floats too, like the builder can make.
+        assert_eq!(
+            parse("price BETWEEN -inf AND NaN").unwrap(),
+            "price".f64_between(f64::NEG_INFINITY, f64::NAN)
+        );
+        assert_eq!(parse("price<inf").unwrap(), "price".f64_lt(f64::INFINITY));
+        assert_eq!(parse("price=NaN").unwrap(), "price".f64_eq(f64::NAN));
+        // But they are terms otherwise.
+        assert_eq!(parse("price:inf").unwrap(), "price".has_value("inf"));
+
+        // Bounds must be numbers.
+        assert!(parse("price BETWEEN a AND 100").is_err());
+        assert!(parse("price BETWEEN 10 AND 1*").is_err());
+        assert!(parse("price BETWEEN 10").is_err());
+        assert!(parse("price between 10 and 100").is_err());
+
+        let options = ParserOptions::default()
+            .case_insensitive_keywords(true)
+            .field_type("weight", FieldType::Float);
+        assert_eq!(
+            cnf::Query::parse_with("price between 10 and 100", &options).unwrap(),
+            "price".i64_between(10, 100)
+        );
+        assert_eq!(
+            cnf::Query::parse_with("weight BETWEEN 1 AND 2", &options).unwrap(),
+            "weight".f64_between(1.0, 2.0)
+        );
+
+        // Chained comparisons are single literals too.
+        assert_eq!(
+            parse("10 <= price <= 100").unwrap(),
+            "price".i64_between(10, 100)
+        );
+        assert_eq!(parse("10<price<100").unwrap(), "price".i64_between(11, 99));
+        assert_eq!(
+            parse(&format!("0 <= id < {}", u64::MAX)).unwrap(),
+            "id".u64_ge(0) & "id".u64_lt(u64::MAX)
+        );
+        assert_eq!(
+            parse("1.5 < price <= 2").unwrap(),
+            "price".f64_between(1.5f64.next_up(), 2.0)
+        );
+        assert_eq!(
+            parse("-1 < price <= 2 AND colour:blue")
+                .unwrap()
+                .to_string(),
+            "colour:blue AND price BETWEEN 0 AND 2"
+        );
+        assert_eq!(
+            query_parser()
+                .parse("1 < price <= 2.5")
+                .into_result()
+                .unwrap()
+                .to_string(),
+            "1<price<=2.5"
+        );
+        // Not to be confused with fields that look like numbers.
+        assert_eq!(parse("10<=x").unwrap(), "10".has_value("x"));
+        assert!(parse("a < price < 100").is_err());
+        assert!(parse("10 > price > 1").is_err());
+
+        assert_eq!(
+            query_parser()
+                .parse("price BETWEEN 10 AND 100")
+                .into_result()
+                .unwrap()
+                .measure(),
+            (1, 1, 1)
+        );
+    }
+
+    #[test]
+    fn test_term_set() {
+        let parse = |s: &str| s.parse::<cnf::Query>();
+        let q = parse("colour IN {red, \"light blue\", 10}").unwrap();
+        assert_eq!(q, "colour".has_any_value(["red", "light blue", "10"]));
+        // One literal, that displays as it parses.
+        assert_eq!(q.clauses().len(), 1);
+        assert_eq!(q.clauses()[0].literals().len(), 1);
+        assert_eq!(q.to_string(), "colour IN {\"10\", \"light blue\", red}");
+        assert_eq!(parse(&q.to_string()), Ok(q));
+
+        assert_eq!(
+            parse("NOT colour IN{red,blue}^2 AND taste:sweet").unwrap(),
+            !"colour".has_any_value(["red", "blue"]).boost(2.0) & "taste".has_value("sweet")
+        );
+        assert_eq!(
+            parse("colour IN {\"a,b\", \"}\"}").unwrap().to_string(),
+            "colour IN {\"a,b\", \"}\"}"
+        );
+        assert_eq!(parse("colour IN {red}"), Ok("colour".has_value("red")));
+        assert_eq!(parse("\"IN\":red").unwrap().to_string(), "\"IN\":red");
+
+        assert!(parse("colour IN {}").is_err());
+        assert!(parse("colour IN {red,}").is_err());
+        assert!(parse("colour IN {red").is_err());
+        assert!(parse("colour IN red").is_err());
+        assert!(parse("colour in {red}").is_err());
+
+        let options = ParserOptions::default().case_insensitive_keywords(true);
+        assert_eq!(
+            cnf::Query::parse_with("colour in {red, blue}", &options),
+            Ok("colour".has_any_value(["red", "blue"]))
+        );
+        let options = ParserOptions::default().disable_operator(Operator::TermSet);
+        assert_eq!(
+            cnf::Query::parse_with("colour IN {red, blue}", &options),
+            Err("IN queries are not allowed".to_string())
+        );
+
+        assert_eq!(
+            query_parser()
+                .parse("colour IN {red, blue, green}")
+                .into_result()
+                .unwrap()
+                .measure(),
+            (1, 1, 1)
+        );
+    }
+
+    #[test]
+    fn test_h3in_any() {
+        let parse = |s: &str| s.parse::<cnf::Query>();
+        let cells = ["87194d106ffffff", "861f09b27ffffff"].map(|c| c.parse().unwrap());
+        let q = parse("location H3IN {87194d106ffffff, 861f09b27ffffff}").unwrap();
+        assert_eq!(q, "location".h3in_any(&cells));
+        // One literal, that displays as it parses.
+        assert_eq!(q.clauses().len(), 1);
+        assert_eq!(q.clauses()[0].literals().len(), 1);
+        assert_eq!(
+            q.to_string(),
+            "location H3IN {87194d106ffffff, 861f09b27ffffff}"
+        );
+        assert_eq!(parse(&q.to_string()), Ok(q.clone()));
+        assert_eq!(parse(&(!q.clone()).to_string()), Ok(!q));
+
+        assert_eq!(
+            parse("location H3IN {861f09b27ffffff}"),
+            Ok("location".h3in(cells[1]))
+        );
+        // Lax, bad lists are terms like bad cells.
+        assert_eq!(
+            parse("location H3IN {861f09b27ffffff,bad}"),
+            Ok("location".has_value("{861f09b27ffffff,bad}"))
+        );
+        let strict = ParserOptions::default().strict_h3_cells(true);
+        assert!(cnf::Query::parse_with("location H3IN {861f09b27ffffff,bad}", &strict).is_err());
+        // Such terms are quoted, so they are not lists.
+        assert_eq!(
+            "location".has_value("{861f09b27ffffff}").to_string(),
+            "location:\"{861f09b27ffffff}\""
+        );
+
+        let options = ParserOptions::default().disable_operator(Operator::H3Inside);
+        assert_eq!(
+            cnf::Query::parse_with("location H3IN {87194d106ffffff, 861f09b27ffffff}", &options),
+            Err("H3IN queries are not allowed".to_string())
+        );
+    }
+
+    #[test]
+    fn test_default_fields() {
+        let options = ParserOptions::default().default_field("text");
+        let parse = |s: &str, o: &ParserOptions| cnf::Query::parse_with(s, o);
+
+        assert_eq!(
+            parse("blue AND sweet", &options).unwrap(),
+            "text".has_value("blue") & "text".has_value("sweet")
+        );
+        assert_eq!(
+            parse("\"dark blue\" OR NOT bl* OR (colour:red AND 42)", &options).unwrap(),
+            "text".has_value("dark blue")
+                | !"text".has_prefix("bl")
+                | ("colour".has_value("red") & "text".has_value("42"))
+        );
+        // Keywords and lone stars are not values.
+        assert!(parse("AND", &options).is_err());
+        assert!(parse("blue AND OR sweet", &options).is_err());
+        assert!(parse("*", &options).is_err());
+        assert!(
+            parse(
+                "blue and sweet",
+                &options.clone().case_insensitive_keywords(true)
+            )
+            .is_ok()
+        );
+        // Without a default field, a bare value is an error.
+        assert!(parse("blue", &ParserOptions::default()).is_err());
+
+        // Several fields, with a schema.
+        let options = options
+            .default_field("size")
+            .field_type("size", FieldType::Integer);
+        assert_eq!(
+            parse("42", &options).unwrap(),
+            "text".has_value("42") | "size".i64_eq(42)
+        );
+        assert_eq!(
+            parse("blue AND NOT 42", &options).unwrap(),
+            ("text".has_value("blue") | "size".has_value("blue"))
+                & !"text".has_value("42")
+                & !"size".i64_eq(42)
+        );
+    }
+
+    #[test]
+    fn test_llwithin_call() {
+        use crate::geotools::Meters;
+
+        let parse = |s: &str| s.parse::<cnf::Query>();
+        let paris = LatLng::new(48.86, 2.34).unwrap();
+
+        assert_eq!(
+            parse("location LLWITHIN(48.86, 2.34, 1km)").unwrap(),
+            "location".latlng_within(paris, Meters(1000))
+        );
+        assert_eq!(
+            parse("location LLWITHIN ( 48.86,2.34,250 )").unwrap(),
+            "location".latlng_within(paris, Meters(250))
+        );
+        assert_eq!(
+            parse("location LLWITHIN(48.86, 2.34, 250.4m) AND colour:blue").unwrap(),
+            "location".latlng_within(paris, Meters(250)) & "colour".has_value("blue")
+        );
+        assert_eq!(
+            parse("location LLWITHIN(48.86, 2.34, 1.5 mi)").unwrap(),
+            "location".latlng_within(paris, Meters(2414))
+        );
+        assert_eq!(
+            parse("NOT (location LLWITHIN(48.86,2.34,1km))").unwrap(),
+            !"location".latlng_within(paris, Meters(1000))
+        );
+        // Same as the plain syntax.
+        assert_eq!(
+            query_parser()
+                .parse("location LLWITHIN(-48.86, 2.34, 1km)")
+                .into_result()
+                .unwrap(),
+            query_parser()
+                .parse("location LLWITHIN -48.86,2.34,1000")
+                .into_result()
+                .unwrap()
+        );
+        assert_eq!(
+            cnf::Query::parse_with(
+                "location llwithin(48.86, 2.34, 1km)",
+                &ParserOptions::default().case_insensitive_keywords(true)
+            )
+            .unwrap(),
+            "location".latlng_within(paris, Meters(1000))
+        );
+
+        assert!(parse("location LLWITHIN(48.86, 2.34, -1km)").is_err());
+        assert!(parse("location LLWITHIN(48.86, 2.34, 1 parsec)").is_err());
+        assert!(parse("location LLWITHIN(48.86, 2.34)").is_err());
+    }
+
+    #[test]
+    fn test_llwithin_any() {
+        let parse = |s: &str| s.parse::<cnf::Query>();
+        let paris = LatLng::new(48.864716, 2.349014).unwrap();
+        let gdansk = LatLng::new(54.35499723397377, 18.662987684795226).unwrap();
+        let disks = [(paris, Meters(1000)), (gdansk, Meters(500))];
+
+        let q = parse(
+            "position LLWITHIN { 48.864716,2.349014,1000 ;54.35499723397377,18.662987684795226,500}",
+        )
+        .unwrap();
+        assert_eq!(q, "position".latlng_within_any(&disks));
+        // One literal, that displays as it parses.
+        assert_eq!(q.clauses().len(), 1);
+        assert_eq!(q.clauses()[0].literals().len(), 1);
+        assert_eq!(
+            q.to_string(),
+            "position LLWITHIN {48.864716,2.349014,1000; 54.35499723397377,18.662987684795226,500}"
+        );
+        assert_eq!(parse(&q.to_string()), Ok(q.clone()));
+        assert_eq!(parse(&(!q.clone()).to_string()), Ok(!q));
+
+        assert_eq!(
+            parse("position LLWITHIN {48.864716,2.349014,1000}"),
+            Ok("position".latlng_within(paris, Meters(1000)))
+        );
+        // Lax, bad lists are terms like bad values.
+        assert_eq!(
+            parse("position LLWITHIN {48.86,2.34;1,2,3}"),
+            Ok("position".has_value("{48.86,2.34;1,2,3}"))
+        );
+        let strict = ParserOptions::default().strict_latlng_within(true);
+        assert!(cnf::Query::parse_with("position LLWITHIN {48.86,2.34;1,2,3}", &strict).is_err());
+
+        let options = ParserOptions::default().disable_operator(Operator::LatLngWithin);
+        assert_eq!(
+            cnf::Query::parse_with("position LLWITHIN {48.86,2.34,10; 1,2,3}", &options),
+            Err("LLWITHIN queries are not allowed".to_string())
+        );
+    }
+
+    #[test]
+    fn test_llbetween() {
+        let parse = |s: &str| s.parse::<cnf::Query>();
+        let center = LatLng::new(48.864716, -2.349014).unwrap();
+
+        let q = parse("position LLBETWEEN 48.864716,-2.349014,1000,3000").unwrap();
+        assert_eq!(
+            q,
+            "position".latlng_between(center, Meters(1000), Meters(3000))
+        );
+        // One literal, that displays as it parses.
+        assert_eq!(q.clauses().len(), 1);
+        assert_eq!(q.clauses()[0].literals().len(), 1);
+        assert_eq!(
+            q.to_string(),
+            "position LLBETWEEN 48.864716,-2.349014,1000,3000"
+        );
+        assert_eq!(parse(&q.to_string()), Ok(q.clone()));
+        assert_eq!(parse(&(!q.clone()).to_string()), Ok(!q));
+        assert_eq!(
+            parse("(position LLBETWEEN 48.864716,-2.349014,0,1)"),
+            Ok("position".latlng_between(center, Meters(0), Meters(1)))
+        );
+        assert_eq!(
+            "LLBETWEEN"
+                .latlng_between(center, Meters(0), Meters(1))
+                .to_string(),
+            "\"LLBETWEEN\" LLBETWEEN 48.864716,-2.349014,0,1"
+        );
+
+        assert!(parse("position LLBETWEEN 48.86,2.34,3000,1000").is_err());
+        assert!(parse("position LLBETWEEN 48.86,2.34,1000").is_err());
+        assert!(parse("position LLBETWEEN 48.86,2.34,1km,3km").is_err());
+        assert!(parse("position llbetween 48.86,2.34,1000,3000").is_err());
+
+        let options = ParserOptions::default().case_insensitive_keywords(true);
+        assert_eq!(
+            cnf::Query::parse_with("position llbetween 48.864716,-2.349014,1000,3000", &options),
+            Ok("position".latlng_between(center, Meters(1000), Meters(3000)))
+        );
+        let options = ParserOptions::default().disable_operator(Operator::LatLngWithin);
+        assert_eq!(
+            cnf::Query::parse_with("position LLBETWEEN 48.86,2.34,1000,3000", &options),
+            Err("LLWITHIN queries are not allowed".to_string())
+        );
+    }
+
+    #[test]
+    fn test_exists() {
+        let q = query_parser()
+            .parse("colour:* AND NOT taste:*")
+            .into_result()
+            .unwrap();
+        assert_eq!(q.to_string(), "( colour:* AND NOT taste:* )");
+        assert_eq!(q.to_cnf(), "colour".has_field() & !"taste".has_field());
+        // Different from the empty prefix.
+        assert_eq!(
+            query_parser().parse("colour:\"\"*").into_result().unwrap(),
+            QueryAST::Atom(
+                "colour".into(),
+                OperatorAST::Colon,
+                FieldValueAST::Prefix("".into())
+            )
+        );
+    }
+
+    #[test]
+    fn test_quoted_fields() {
+        let q = query_parser()
+            .parse(r#""weird field:name":value AND "NOT":* AND 1 < "a b" <= 2.5"#)
+            .into_result()
+            .unwrap();
+        assert_eq!(
+            q.to_string(),
+            r#"( ( "weird field:name":value AND "NOT":* ) AND 1<"a b"<=2.5 )"#
+        );
+        assert_eq!(
+            q.to_cnf(),
+            "weird field:name".has_value("value")
+                & "NOT".has_field()
+                & "a b".f64_range(Bound::Excluded(1.0), Bound::Included(2.5))
+        );
+
+        // All kinds of literals display with quoted fields, and parse back.
+        let f = "a \"b\":c";
+        let qs = [
+            f.has_value("v"),
+            f.has_prefix("v"),
+            f.has_field(),
+            !f.has_field(),
+            f.i64_lt(3),
+            f.f64_between(0.5, 1.5),
+            f.h3in("861f09b27ffffff".parse().unwrap()),
+            format!("{} LLWITHIN 48.86,2.34,1000", escape_quote_field(f))
+                .parse()
+                .unwrap(),
+            format!("{}:/v.*/", escape_quote_field(f)).parse().unwrap(),
+        ];
+        for q in qs {
+            let s = q.to_string();
+            assert!(str::contains(&s, r#""a \"b\":c""#), "{}", s);
+            assert_eq!(s.parse::<cnf::Query>().map(|q| q.to_string()), Ok(s));
+        }
     }
 
     #[test]
     fn test_atom_to_cnf() {
         // Term
         let cnf = atom_to_cnf("f", &OperatorAST::Colon, &FieldValueAST::Term("v".into()));
-        assert_eq!(cnf.to_string(), "(AND (OR f=v))");
+        assert_eq!(cnf.to_string(), "f:v");
 
         // Prefix
         let cnf = atom_to_cnf("f", &OperatorAST::Colon, &FieldValueAST::Prefix("p".into()));
-        assert_eq!(cnf.to_string(), "(AND (OR f=p*))");
+        assert_eq!(cnf.to_string(), "f:p*");
 
         // Int
         let cnf = atom_to_cnf("f", &OperatorAST::Lt, &FieldValueAST::Integer(10));
-        assert_eq!(cnf.to_string(), "(AND (OR f<10))");
+        assert_eq!(cnf.to_string(), "f<10");
 
         let cnf = atom_to_cnf("f", &OperatorAST::Le, &FieldValueAST::Integer(10));
-        assert_eq!(cnf.to_string(), "(AND (OR f<=10))");
+        assert_eq!(cnf.to_string(), "f<=10");
 
         let cnf = atom_to_cnf("f", &OperatorAST::Eq, &FieldValueAST::Integer(10));
-        assert_eq!(cnf.to_string(), "(AND (OR f==10))");
+        assert_eq!(cnf.to_string(), "f=10");
 
         let cnf = atom_to_cnf("f", &OperatorAST::Ge, &FieldValueAST::Integer(10));
-        assert_eq!(cnf.to_string(), "(AND (OR f>=10))");
+        assert_eq!(cnf.to_string(), "f>=10");
 
         let cnf = atom_to_cnf("f", &OperatorAST::Gt, &FieldValueAST::Integer(10));
-        assert_eq!(cnf.to_string(), "(AND (OR f>10))");
+        assert_eq!(cnf.to_string(), "f>10");
 
         // H3
         let cell = "87194d106ffffff";
@@ -892,7 +3985,7 @@ mod tests_parsing {
             &OperatorAST::H3Inside,
             &FieldValueAST::Term(cell.into()),
         );
-        assert_eq!(cnf.to_string(), format!("(AND (OR f=H3IN={}))", cell));
+        assert_eq!(cnf.to_string(), format!("f H3IN {}", cell));
 
         // H3 with invalid cell -> Term
         let cnf = atom_to_cnf(
@@ -900,22 +3993,42 @@ mod tests_parsing {
             &OperatorAST::H3Inside,
             &FieldValueAST::Term("invalid".into()),
         );
-        assert_eq!(cnf.to_string(), "(AND (OR f=invalid))");
+        assert_eq!(cnf.to_string(), "f:invalid");
+
+        // IP networks, single addresses, and invalid ones -> Term
+        let ip = |v: &str| atom_to_cnf("f", &OperatorAST::IpIn, &FieldValueAST::Term(v.into()));
+        assert_eq!(ip("10.1.2.3/8").to_string(), "f IPIN 10.0.0.0/8");
+        assert_eq!(ip("10.1.2.3").to_string(), "f IPIN 10.1.2.3/32");
+        assert_eq!(ip("2001:DB8::/32").to_string(), "f IPIN \"2001:db8::/32\"");
+        assert_eq!(ip("10.0.0.0/33").to_string(), "f:10.0.0.0/33");
+        let cnf = atom_to_cnf("f", &OperatorAST::IpIn, &FieldValueAST::Integer(10));
+        assert_eq!(cnf.to_string(), "f:\"10\"");
 
         // Fallback int with colon
         let cnf = atom_to_cnf("f", &OperatorAST::Colon, &FieldValueAST::Integer(123));
-        assert_eq!(cnf.to_string(), "(AND (OR f=123))");
+        assert_eq!(cnf.to_string(), "f:\"123\"");
+
+        // Float
+        let cnf = atom_to_cnf("f", &OperatorAST::Lt, &FieldValueAST::Float(1.5));
+        assert_eq!(cnf.to_string(), "f<1.5");
+
+        let cnf = atom_to_cnf("f", &OperatorAST::Ge, &FieldValueAST::Float(-1.5));
+        assert_eq!(cnf.to_string(), "f>=-1.5");
+
+        // Fallback float with colon
+        let cnf = atom_to_cnf("f", &OperatorAST::Colon, &FieldValueAST::Float(1.5));
+        assert_eq!(cnf.to_string(), "f:\"1.5\"");
     }
 
     #[test]
     fn test_random_generators_coverage() {
         let mut rng = rand::rng();
 
-        // _random_h3cell
-        let _ = _random_h3cell(&mut rng);
+        // random_h3cell
+        let _ = random_h3cell(&mut rng);
 
-        // _random_atom
-        let _ = _random_atom(&mut rng);
+        // random_atom
+        let _ = random_atom(&mut rng);
 
         // _random_operator
         let _ = _random_operator(&mut rng);
@@ -929,6 +4042,9 @@ mod tests_parsing {
         // _random_field_int_value
         let _ = _random_field_int_value(&mut rng);
 
+        // _random_field_float_value
+        let _ = _random_field_float_value(&mut rng);
+
         // _random_field_value
         let _ = _random_field_value(&mut rng);
 
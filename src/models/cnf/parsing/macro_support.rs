@@ -0,0 +1,227 @@
+//! What the `query!` macro of the `mokaccino-macros` crate expands to.
+//! Not part of the public API.
+
+use std::ops::Bound;
+
+use h3o::{CellIndex, LatLng};
+use itertools::Itertools;
+
+use crate::geotools::Meters;
+use crate::models::cnf::Query;
+use crate::models::queries::term::TermQuery;
+use crate::prelude::CNFQueryable;
+
+pub use super::{FieldValueAST, OperatorAST};
+use super::{ParserOptions, QueryAST, atom_to_cnf, big_int_to_cnf, parse_ast, range_to_cnf};
+
+// Where the generated code finds this module.
+const HERE: &str = "::mokaccino::prelude::parsing::macro_support";
+
+/// An atom, like the parser builds it.
+pub fn atom(field: &str, operator: OperatorAST, field_value: FieldValueAST) -> Query {
+    atom_to_cnf(field, &operator, &field_value)
+}
+
+/// A range, like the parser builds it.
+pub fn range(field: &str, low: Bound<FieldValueAST>, high: Bound<FieldValueAST>) -> Query {
+    range_to_cnf(field, &low, &high, None)
+}
+
+/// A 128 bits integer comparison, like the parser builds it.
+pub fn big_int(field: &str, operator: OperatorAST, i: i128) -> Query {
+    big_int_to_cnf(field, &operator, i)
+}
+
+/// Inside any of the cells, like the parser builds it.
+pub fn h3in_any(field: &str, cells: &[u64]) -> Query {
+    let cells = cells
+        .iter()
+        .map(|c| CellIndex::try_from(*c).expect("Parsed cell"))
+        .collect::<Vec<_>>();
+    field.h3in_any(&cells)
+}
+
+/// Within any of the disks, like the parser builds it. Lats and
+/// lngs are the bits of their radians, to be exact.
+pub fn latlng_within_any(field: &str, disks: &[(u64, u64, u64)]) -> Query {
+    let disks = disks
+        .iter()
+        .map(|(lat, lng, radius)| {
+            let ll = LatLng::from_radians(f64::from_bits(*lat), f64::from_bits(*lng))
+                .expect("Parsed lat,lng");
+            (ll, Meters(*radius))
+        })
+        .collect::<Vec<_>>();
+    field.latlng_within_any(&disks)
+}
+
+/// Further than min and within max of the center, like the parser
+/// builds it. The lat and lng are the bits of their radians.
+pub fn latlng_between(field: &str, lat: u64, lng: u64, min: u64, max: u64) -> Query {
+    let ll =
+        LatLng::from_radians(f64::from_bits(lat), f64::from_bits(lng)).expect("Parsed lat,lng");
+    field.latlng_between(ll, Meters(min), Meters(max))
+}
+
+/// A term matching trimmed and case folded values, like the parser builds it.
+pub fn normalized(field: &str, term: &str) -> Query {
+    Query::from_termquery(TermQuery::normalized(field, term))
+}
+
+/// The Rust code building the query, or the parsing errors.
+pub fn query_code(s: &str) -> Result<String, String> {
+    parse_ast(s, &ParserOptions::default()).map(|ast| ast_code(&ast))
+}
+
+fn ast_code(ast: &QueryAST) -> String {
+    match ast {
+        QueryAST::Neg(q) => format!("(!{})", ast_code(q)),
+        QueryAST::Atom(field, operator, value) => format!(
+            "{}::atom({:?}, {}::OperatorAST::{:?}, {})",
+            HERE,
+            field,
+            HERE,
+            operator,
+            value_code(value)
+        ),
+        QueryAST::Range(field, low, high) => format!(
+            "{}::range({:?}, {}, {})",
+            HERE,
+            field,
+            bound_code(low),
+            bound_code(high)
+        ),
+        QueryAST::Set(field, terms) => format!(
+            "::mokaccino::prelude::CNFQueryable::has_any_value({:?}, [{}])",
+            field,
+            terms.iter().map(|t| format!("{:?}", t)).join(", ")
+        ),
+        QueryAST::Words(field, analyzer, text) => format!(
+            "::mokaccino::prelude::Query::words({:?}, {:?}, ::mokaccino::prelude::Analyzer::{:?})",
+            field, text, analyzer
+        ),
+        QueryAST::Count(field, min) => format!(
+            "::mokaccino::prelude::Query::field_count_ge({:?}, {}usize)",
+            field, min
+        ),
+        QueryAST::Semver(field, version) => format!(
+            "::mokaccino::prelude::Query::semver_ge({:?}, {:?}).expect(\"Parsed version\")",
+            field,
+            version.to_string()
+        ),
+        QueryAST::BigInt(field, operator, i) => format!(
+            "{}::big_int({:?}, {}::OperatorAST::{:?}, {}i128)",
+            HERE, field, HERE, operator, i
+        ),
+        QueryAST::H3InAny(field, cells) => format!(
+            "{}::h3in_any({:?}, &[{}])",
+            HERE,
+            field,
+            cells
+                .iter()
+                .map(|c| format!("{}u64", u64::from(*c)))
+                .join(", ")
+        ),
+        QueryAST::LatLngWithinAny(field, disks) => format!(
+            "{}::latlng_within_any({:?}, &[{}])",
+            HERE,
+            field,
+            disks
+                .iter()
+                .map(|(ll, radius)| format!(
+                    "({}u64, {}u64, {}u64)",
+                    ll.lat_radians().to_bits(),
+                    ll.lng_radians().to_bits(),
+                    radius.0
+                ))
+                .join(", ")
+        ),
+        QueryAST::LatLngBetween(field, ll, min, max) => format!(
+            "{}::latlng_between({:?}, {}u64, {}u64, {}u64, {}u64)",
+            HERE,
+            field,
+            ll.lat_radians().to_bits(),
+            ll.lng_radians().to_bits(),
+            min.0,
+            max.0
+        ),
+        QueryAST::StrRange(field, low, high) => format!(
+            "::mokaccino::prelude::CNFQueryable::str_between({:?}, {:?}, {:?})",
+            field, low, high
+        ),
+        QueryAST::Nested(path, q) => format!(
+            "::mokaccino::prelude::Query::nested({:?}, {})",
+            path,
+            ast_code(q)
+        ),
+        QueryAST::Norm(field, term) => format!("{}::normalized({:?}, {:?})", HERE, field, term),
+        QueryAST::MatchAll => "::mokaccino::prelude::Query::match_all()".to_string(),
+        QueryAST::MatchNone => "::mokaccino::prelude::Query::match_none()".to_string(),
+        QueryAST::And(q1, q2) => format!("({} & {})", ast_code(q1), ast_code(q2)),
+        QueryAST::Or(q1, q2) => format!("({} | {})", ast_code(q1), ast_code(q2)),
+        QueryAST::Boost(q, weight) => format!(
+            "{}.boost(f32::from_bits({}))",
+            ast_code(q),
+            weight.to_bits()
+        ),
+    }
+}
+
+fn value_code(value: &FieldValueAST) -> String {
+    let variant = match value {
+        FieldValueAST::Term(s) => format!("Term({:?}.to_string())", s),
+        FieldValueAST::Prefix(s) => format!("Prefix({:?}.to_string())", s),
+        FieldValueAST::Suffix(s) => format!("Suffix({:?}.to_string())", s),
+        FieldValueAST::Contains(s) => format!("Contains({:?}.to_string())", s),
+        FieldValueAST::Integer(i) => format!("Integer({}i128)", i),
+        // Exact, whatever the float.
+        FieldValueAST::Float(x) => format!("Float(f64::from_bits({}))", x.to_bits()),
+        FieldValueAST::Regex(s) => format!("Regex({:?}.to_string())", s),
+        FieldValueAST::Exists => "Exists".to_string(),
+        // Default options parse $name as a term.
+        FieldValueAST::Placeholder(_) => unreachable!("No placeholders"),
+    };
+    format!("{}::FieldValueAST::{}", HERE, variant)
+}
+
+fn bound_code(bound: &Bound<FieldValueAST>) -> String {
+    match bound {
+        Bound::Included(v) => format!("::std::ops::Bound::Included({})", value_code(v)),
+        Bound::Excluded(v) => format!("::std::ops::Bound::Excluded({})", value_code(v)),
+        Bound::Unbounded => "::std::ops::Bound::Unbounded".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_code() {
+        assert_eq!(
+            query_code("colour:blue").unwrap(),
+            "::mokaccino::prelude::parsing::macro_support::atom(\"colour\", \
+             ::mokaccino::prelude::parsing::macro_support::OperatorAST::Colon, \
+             ::mokaccino::prelude::parsing::macro_support::FieldValueAST::Term(\"blue\".to_string()))"
+        );
+        let code = query_code("NOT colour IN {red, blue} OR price BETWEEN 1 AND 2.5").unwrap();
+        assert!(
+            code.starts_with("((!::mokaccino::prelude::CNFQueryable::has_any_value(\"colour\", [")
+        );
+        assert!(code.contains("::range(\"price\", ::std::ops::Bound::Included("));
+
+        assert_eq!(
+            query_code("title WORD(alphanumeric) fox").unwrap(),
+            "::mokaccino::prelude::Query::words(\"title\", \"fox\", \
+             ::mokaccino::prelude::Analyzer::Alphanumeric)"
+        );
+
+        assert!(query_code("colour:").is_err());
+        // Not a placeholder.
+        assert!(
+            query_code("colour:$c")
+                .unwrap()
+                .ends_with("Term(\"$c\".to_string()))")
+        );
+    }
+}
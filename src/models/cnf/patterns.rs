@@ -0,0 +1,152 @@
+//! Constructors for common alerting rules.
+//!
+//! They build the same queries as writing them by hand, shaped so that
+//! the percolator indexes them well: one clause of positive literals where
+//! possible, and negations only next to a positive literal.
+//!
+//! Rules about changes, like [`value_change`] and [`geofence_entry`], expect
+//! the documents to carry the previous state of the watched field in another
+//! field, like `status` and `previous_status`.
+
+use h3o::LatLng;
+
+use crate::geotools::Meters;
+use crate::models::cnf::{CNFQueryable, Query};
+use crate::models::queries::text_match::Analyzer;
+use crate::models::types::OurStr;
+
+/// Values of the field out of the `low..=high` band, as floats.
+/// An infinite bound is not watched, so `f64::INFINITY` as `high`
+/// only alerts on values below `low`.
+///
+/// # Panics
+///
+/// If both bounds are infinite, as nothing could breach them.
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+///
+/// let q = patterns::threshold_breach("cpu", 0.05, 0.95);
+/// assert_eq!(q, "cpu".f64_lt(0.05) | "cpu".f64_gt(0.95));
+/// assert!(q.matches(&[("cpu", "0.99")].into()));
+/// assert!(!q.matches(&[("cpu", "0.5")].into()));
+///
+/// let q = patterns::threshold_breach("cpu", f64::NEG_INFINITY, 0.95);
+/// assert_eq!(q, "cpu".f64_gt(0.95));
+/// ```
+pub fn threshold_breach<T: Into<OurStr>>(field: T, low: f64, high: f64) -> Query {
+    let field: OurStr = field.into();
+    let sides = [
+        low.is_finite().then(|| field.clone().f64_lt(low)),
+        high.is_finite().then(|| field.clone().f64_gt(high)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+    assert!(!sides.is_empty(), "A threshold needs a finite bound");
+    Query::from_or(sides)
+}
+
+/// The field now has the value `to`, and the previous field does not.
+/// A missing previous field counts as a change, like for a new entity.
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+///
+/// let q = patterns::value_change("previous_status", "status", "down");
+/// assert!(q.matches(&[("previous_status", "up"), ("status", "down")].into()));
+/// assert!(q.matches(&[("status", "down")].into()));
+/// assert!(!q.matches(&[("previous_status", "down"), ("status", "down")].into()));
+/// ```
+pub fn value_change<T, U, V>(previous_field: T, field: U, to: V) -> Query
+where
+    T: Into<OurStr>,
+    U: Into<OurStr>,
+    V: Into<OurStr>,
+{
+    let to: OurStr = to.into();
+    field.has_value(to.clone()) & !previous_field.has_value(to)
+}
+
+/// The location in the field is within `radius` of `center`, and the
+/// previous location was not. Like [`value_change`], a missing previous
+/// location counts as an entry.
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+/// use h3o::LatLng;
+///
+/// let paris = LatLng::new(48.864716, 2.349014).unwrap();
+/// let q = patterns::geofence_entry("previous_position", "position", paris, Meters(1000));
+/// assert!(q.matches(
+///     &[("previous_position", "48.0,2.0"), ("position", "48.8647,2.349")].into()
+/// ));
+/// assert!(!q.matches(
+///     &[("previous_position", "48.8646,2.349"), ("position", "48.8647,2.349")].into()
+/// ));
+/// ```
+pub fn geofence_entry<T, U>(previous_field: T, field: U, center: LatLng, radius: Meters) -> Query
+where
+    T: Into<OurStr>,
+    U: Into<OurStr>,
+{
+    field.latlng_within(center, radius) & !previous_field.latlng_within(center, radius)
+}
+
+/// Any of the words of `keywords` in any of the fields, whatever their case.
+/// This is a single clause, however many fields and words there are.
+/// Like [`Query::any_word`], keywords without any word match
+/// documents having the fields.
+///
+/// # Panics
+///
+/// If there are no fields.
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+///
+/// let q = patterns::keyword_watch(["title", "body"], "outage incident");
+/// assert!(q.matches(&[("title", "Major OUTAGE in Paris")].into()));
+/// assert!(q.matches(&[("body", "Incident report")].into()));
+/// assert!(!q.matches(&[("title", "All good")].into()));
+/// ```
+pub fn keyword_watch<T: Into<OurStr>>(
+    fields: impl IntoIterator<Item = T>,
+    keywords: &str,
+) -> Query {
+    let queries = fields
+        .into_iter()
+        .map(|f| Query::any_word(f, keywords, Analyzer::default()))
+        .collect::<Vec<_>>();
+    assert!(!queries.is_empty(), "A keyword watch needs fields");
+    Query::from_or(queries)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_single_clauses() {
+        assert_eq!(threshold_breach("f", 1.0, 2.0).clauses().len(), 1);
+        assert_eq!(keyword_watch(["a", "b"], "x y z").clauses().len(), 1);
+        // The negation is next to a positive literal.
+        assert_eq!(value_change("p", "f", "v").clauses().len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "A threshold needs a finite bound")]
+    fn test_unbounded_threshold() {
+        threshold_breach("f", f64::NEG_INFINITY, f64::INFINITY);
+    }
+
+    #[test]
+    #[should_panic(expected = "A keyword watch needs fields")]
+    fn test_keyword_watch_no_fields() {
+        keyword_watch(Vec::<&str>::new(), "x");
+    }
+}
@@ -0,0 +1,313 @@
+use chumsky::prelude::*;
+use regex::Regex;
+
+use crate::models::cnf::parsing::{MyParseError, keyword_parser};
+use crate::models::cnf::{CNFQueryable, Query};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SqlOperator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SqlValue {
+    Str(String),
+    Int(i64),
+    Float(f64),
+}
+
+// SQL keywords are case insensitive.
+fn kw<'src>(word: &'static str) -> impl Parser<'src, &'src str, (), MyParseError<'src>> + Clone {
+    keyword_parser(word, true).padded()
+}
+
+// A column name, like `price` or `"unit price"`.
+fn sql_identifier<'src>() -> impl Parser<'src, &'src str, String, MyParseError<'src>> + Clone {
+    let bare = text::ascii::ident()
+        .separated_by(just('.'))
+        .at_least(1)
+        .to_slice()
+        .map(|s: &str| s.to_string());
+    let quoted = none_of('"')
+        .repeated()
+        .at_least(1)
+        .collect::<String>()
+        .delimited_by(just('"'), just('"'));
+    quoted.or(bare).padded()
+}
+
+// A 'string', where '' is a quote.
+fn sql_string<'src>() -> impl Parser<'src, &'src str, String, MyParseError<'src>> + Clone {
+    choice((just("''").to('\''), none_of('\'')))
+        .repeated()
+        .collect::<String>()
+        .delimited_by(just('\''), just('\''))
+        .padded()
+}
+
+fn sql_value<'src>() -> impl Parser<'src, &'src str, SqlValue, MyParseError<'src>> + Clone {
+    let number = any()
+        .filter(|c: &char| c.is_ascii_digit() || ['+', '-', '.', 'e', 'E'].contains(c))
+        .repeated()
+        .at_least(1)
+        .to_slice()
+        .try_map(|s: &str, span| {
+            s.parse::<i64>()
+                .map(SqlValue::Int)
+                .or_else(|_| s.parse::<f64>().map(SqlValue::Float))
+                .map_err(|_| Rich::custom(span, format!("Invalid number {}", s)))
+        });
+    sql_string().map(SqlValue::Str).or(number).padded()
+}
+
+fn comparison(field: &str, op: SqlOperator, value: SqlValue) -> Result<Query, String> {
+    Ok(match (op, value) {
+        (SqlOperator::Eq, SqlValue::Str(s)) => field.has_value(s),
+        (SqlOperator::Ne, SqlValue::Str(s)) => !field.has_value(s),
+        (_, SqlValue::Str(s)) => return Err(format!("Cannot compare {} with '{}'", field, s)),
+        (SqlOperator::Eq, SqlValue::Int(i)) => field.i64_eq(i),
+        (SqlOperator::Ne, SqlValue::Int(i)) => !field.i64_eq(i),
+        (SqlOperator::Lt, SqlValue::Int(i)) => field.i64_lt(i),
+        (SqlOperator::Le, SqlValue::Int(i)) => field.i64_le(i),
+        (SqlOperator::Gt, SqlValue::Int(i)) => field.i64_gt(i),
+        (SqlOperator::Ge, SqlValue::Int(i)) => field.i64_ge(i),
+        (SqlOperator::Eq, SqlValue::Float(x)) => field.f64_eq(x),
+        (SqlOperator::Ne, SqlValue::Float(x)) => !field.f64_eq(x),
+        (SqlOperator::Lt, SqlValue::Float(x)) => field.f64_lt(x),
+        (SqlOperator::Le, SqlValue::Float(x)) => field.f64_le(x),
+        (SqlOperator::Gt, SqlValue::Float(x)) => field.f64_gt(x),
+        (SqlOperator::Ge, SqlValue::Float(x)) => field.f64_ge(x),
+    })
+}
+
+// A LIKE pattern with only a trailing % is a prefix query.
+// Other patterns are turned into a regex.
+fn like(field: &str, pattern: &str) -> Result<Query, String> {
+    let wildcards = |s: &str| s.contains(['%', '_']);
+    if !wildcards(pattern) {
+        return Ok(field.has_value(pattern));
+    }
+    if let Some(prefix) = pattern.strip_suffix('%').filter(|p| !wildcards(p)) {
+        return Ok(field.has_prefix(prefix));
+    }
+
+    let regex = pattern
+        .split_inclusive(['%', '_'])
+        .map(|part| match part.char_indices().last() {
+            Some((i, '%')) => format!("{}.*", regex::escape(&part[..i])),
+            Some((i, '_')) => format!("{}.", regex::escape(&part[..i])),
+            _ => regex::escape(part),
+        })
+        .collect::<String>();
+    Regex::new(&format!("(?s){}", regex))
+        .map(|re| field.has_regex(&re))
+        .map_err(|e| e.to_string())
+}
+
+fn predicate_parser<'src>() -> impl Parser<'src, &'src str, Query, MyParseError<'src>> + Clone {
+    let operator = choice((
+        just("<=").to(SqlOperator::Le),
+        just(">=").to(SqlOperator::Ge),
+        just("<>").to(SqlOperator::Ne),
+        just("!=").to(SqlOperator::Ne),
+        just('<').to(SqlOperator::Lt),
+        just('>').to(SqlOperator::Gt),
+        just('=').to(SqlOperator::Eq),
+    ))
+    .padded();
+
+    let is_null = sql_identifier()
+        .then_ignore(kw("IS"))
+        .then(kw("NOT").or_not())
+        .then_ignore(kw("NULL"))
+        .map(|(f, not)| match not {
+            Some(_) => f.has_field(),
+            None => f.lacks_field(),
+        });
+
+    let in_list = sql_identifier()
+        .then(kw("NOT").or_not())
+        .then_ignore(kw("IN"))
+        .then(
+            sql_value()
+                .separated_by(just(','))
+                .at_least(1)
+                .collect::<Vec<_>>()
+                .delimited_by(just('('), just(')'))
+                .padded(),
+        )
+        .try_map(|((f, not), values), span| {
+            values
+                .into_iter()
+                .map(|v| comparison(&f, SqlOperator::Eq, v))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Query::from_or)
+                .map(|q| if not.is_some() { !q } else { q })
+                .map_err(|e| Rich::custom(span, e))
+        });
+
+    let like_pattern = sql_identifier()
+        .then(kw("NOT").or_not())
+        .then_ignore(kw("LIKE"))
+        .then(sql_string())
+        .try_map(|((f, not), pattern), span| {
+            like(&f, &pattern)
+                .map(|q| if not.is_some() { !q } else { q })
+                .map_err(|e| Rich::custom(span, e))
+        });
+
+    let compare = sql_identifier()
+        .then(operator)
+        .then(sql_value())
+        .try_map(|((f, op), v), span| comparison(&f, op, v).map_err(|e| Rich::custom(span, e)));
+
+    choice((is_null, in_list, like_pattern, compare))
+}
+
+fn where_parser<'src>() -> impl Parser<'src, &'src str, Query, MyParseError<'src>> {
+    let expr = recursive(|expr| {
+        let atom = predicate_parser()
+            .or(expr.delimited_by(just('('), just(')')))
+            .padded();
+
+        let unary = kw("NOT").repeated().foldr(atom, |_op, q| !q).boxed();
+
+        let product = unary
+            .clone()
+            .foldl(kw("AND").ignore_then(unary).repeated(), |lhs, rhs| {
+                lhs & rhs
+            });
+
+        product
+            .clone()
+            .foldl(kw("OR").ignore_then(product).repeated(), |lhs, rhs| {
+                lhs | rhs
+            })
+    });
+    kw("WHERE").or_not().ignore_then(expr).padded()
+}
+
+impl Query {
+    /// Parses a simple SQL `WHERE` expression, for instance
+    /// `colour = 'blue' AND price < 100 OR name LIKE 'bl%'`.
+    ///
+    /// Supports `AND`, `OR`, `NOT`, parentheses and these predicates:
+    /// - `=`, `<>` (or `!=`) with a 'string' or a number.
+    /// - `<`, `<=`, `>`, `>=` with a number.
+    /// - `LIKE` and `NOT LIKE`. A pattern with only a trailing `%` is a prefix query,
+    ///   other patterns are matched as a regex.
+    /// - `IN` and `NOT IN` with a list of values.
+    /// - `IS NULL` and `IS NOT NULL`, to test the absence or presence of a field.
+    ///
+    /// There is no three valued logic: a missing field is just
+    /// not equal to anything.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let q = Query::from_sql_where("colour = 'blue' AND (price < 100 OR name LIKE 'bl%')").unwrap();
+    /// assert_eq!(q, "colour".has_value("blue") & ("price".i64_lt(100) | "name".has_prefix("bl")));
+    /// ```
+    pub fn from_sql_where(s: &str) -> Result<Query, String> {
+        where_parser().parse(s).into_result().map_err(|e| {
+            e.iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sql(s: &str) -> Query {
+        Query::from_sql_where(s).unwrap_or_else(|e| panic!("Cannot parse {}: {}", s, e))
+    }
+
+    #[test]
+    fn test_predicates() {
+        assert_eq!(sql("colour = 'blue'"), "colour".has_value("blue"));
+        assert_eq!(sql("colour='it''s'"), "colour".has_value("it's"));
+        assert_eq!(
+            sql("\"the colour\" <> 'blue'"),
+            !"the colour".has_value("blue")
+        );
+        assert_eq!(
+            sql("address.city != 'Paris'"),
+            !"address.city".has_value("Paris")
+        );
+        assert_eq!(sql("price < 100"), "price".i64_lt(100));
+        assert_eq!(sql("price >= -1.5"), "price".f64_ge(-1.5));
+        assert_eq!(sql("price = 3"), "price".i64_eq(3));
+        assert_eq!(sql("price <= 1e3"), "price".f64_le(1000.0));
+        assert_eq!(sql("colour is null"), "colour".lacks_field());
+        assert_eq!(sql("colour IS NOT NULL"), "colour".has_field());
+        assert_eq!(
+            sql("colour IN ('blue', 'red')"),
+            "colour".has_value("blue") | "colour".has_value("red")
+        );
+        assert_eq!(
+            sql("size NOT IN (1, 2.5)"),
+            !("size".i64_eq(1) | "size".f64_eq(2.5))
+        );
+
+        assert!(Query::from_sql_where("price < 'cheap'").is_err());
+        assert!(Query::from_sql_where("price < ").is_err());
+        assert!(Query::from_sql_where("colour IN ()").is_err());
+        assert!(Query::from_sql_where("colour == 'blue'").is_err());
+        assert!(Query::from_sql_where("colour = 'blue").is_err());
+    }
+
+    #[test]
+    fn test_like() {
+        assert_eq!(sql("name LIKE 'blue'"), "name".has_value("blue"));
+        assert_eq!(sql("name LIKE 'bl%'"), "name".has_prefix("bl"));
+        assert_eq!(sql("name NOT LIKE 'bl%'"), !"name".has_prefix("bl"));
+        assert_eq!(sql("name LIKE '%'"), "name".has_prefix(""));
+
+        let q = sql("name like 'b_u%e.'");
+        assert!(q.matches(&[("name", "blue.")].into()));
+        assert!(q.matches(&[("name", "bluuuue.")].into()));
+        assert!(!q.matches(&[("name", "blue!")].into()));
+        assert!(!q.matches(&[("name", "bue.")].into()));
+
+        let q = sql("name LIKE '%[a]%'");
+        assert!(q.matches(&[("name", "x[a]x")].into()));
+        assert!(!q.matches(&[("name", "a")].into()));
+    }
+
+    #[test]
+    fn test_boolean_logic() {
+        let blue = || "colour".has_value("blue");
+        let cheap = || "price".i64_lt(100);
+        let bl = || "name".has_prefix("bl");
+
+        assert_eq!(
+            sql("colour = 'blue' AND price < 100 OR name LIKE 'bl%'"),
+            (blue() & cheap()) | bl()
+        );
+        assert_eq!(
+            sql("WHERE colour = 'blue' and (price < 100 or name like 'bl%')"),
+            blue() & (cheap() | bl())
+        );
+        assert_eq!(sql("NOT NOT colour = 'blue'"), !!blue());
+        assert_eq!(
+            sql(" not (colour = 'blue' OR price < 100) "),
+            !(blue() | cheap())
+        );
+
+        let q = sql("colour = 'blue' AND NOT price >= 100");
+        assert!(q.matches(&[("colour", "blue"), ("price", "10")].into()));
+        assert!(!q.matches(&[("colour", "blue"), ("price", "100")].into()));
+        // No three valued logic.
+        assert!(q.matches(&[("colour", "blue")].into()));
+    }
+}
@@ -0,0 +1,138 @@
+use hashbrown::HashMap;
+
+use crate::models::cnf::{
+    Query,
+    parsing::{self, ParserOptions, QueryAST},
+};
+
+/// A query string with `$name` placeholders, to build many
+/// queries that only differ by some values.
+///
+/// Bound values are exact terms, or numbers after comparison operators,
+/// in ranges and for numeric fields. They are never parsed as query syntax,
+/// so there is no need to quote or escape them. A quoted `"$name"` is not a
+/// placeholder, but the term `$name`.
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+///
+/// let t = QueryTemplate::parse("city:$city AND price<$max").unwrap();
+/// assert_eq!(t.placeholders(), vec!["city", "max"]);
+///
+/// let q = t.bind(&[("city", "New York (NY)"), ("max", "100")]).unwrap();
+/// assert_eq!(q, "city".has_value("New York (NY)") & "price".i64_lt(100));
+///
+/// assert!(t.bind(&[("city", "Paris"), ("max", "cheap")]).is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct QueryTemplate {
+    ast: QueryAST,
+    options: ParserOptions,
+}
+
+impl QueryTemplate {
+    /// Parses a template with the default parser options.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::parse_with(s, &ParserOptions::default())
+    }
+
+    /// Parses a template with the given options, which
+    /// also apply to the queries it builds.
+    pub fn parse_with(s: &str, options: &ParserOptions) -> Result<Self, String> {
+        let options = options.clone().placeholders(true);
+        parsing::parse_ast(s, &options).map(|ast| Self { ast, options })
+    }
+
+    /// The names of the placeholders, in order of first appearance.
+    pub fn placeholders(&self) -> Vec<String> {
+        self.ast.placeholders()
+    }
+
+    /// Builds a query with the given values of the placeholders.
+    /// All placeholders need a value, and all values need a placeholder.
+    pub fn bind(&self, values: &[(&str, &str)]) -> Result<Query, String> {
+        let names = self.placeholders();
+        if let Some((name, _)) = values.iter().find(|(n, _)| !names.iter().any(|p| p == n)) {
+            return Err(format!("Unknown placeholder ${}", name));
+        }
+        let values = values.iter().copied().collect::<HashMap<_, _>>();
+        let ast = self.ast.bind(&values, &self.options)?;
+        self.options.check_geo_values(&ast)?;
+        ast.to_cnf_checked(&self.options)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::{CNFQueryable, parsing::FieldType};
+
+    #[test]
+    fn test_template() {
+        let t = QueryTemplate::parse(
+            "NOT city:$city AND (price<=$max OR $low < price < $high) AND name:\"$city\"",
+        )
+        .unwrap();
+        assert_eq!(t.placeholders(), vec!["city", "max", "low", "high"]);
+
+        // Values that look like query syntax stay values.
+        let q = t
+            .bind(&[
+                ("city", "x OR y:*"),
+                ("max", "10"),
+                ("low", "1.5"),
+                ("high", "20"),
+            ])
+            .unwrap();
+        assert_eq!(
+            q,
+            "NOT city:\"x OR y:*\" AND (price<=10 OR 1.5 < price < 20) AND name:\"$city\""
+                .parse()
+                .unwrap()
+        );
+
+        assert_eq!(
+            t.bind(&[("city", "a"), ("max", "1"), ("low", "2")]),
+            Err("Missing value for $high".to_string())
+        );
+        assert_eq!(
+            t.bind(&[("city", "a"), ("max", "1"), ("low", "2"), ("lo", "2")]),
+            Err("Unknown placeholder $lo".to_string())
+        );
+        assert_eq!(
+            t.bind(&[("city", "a"), ("max", "1e"), ("low", "2"), ("high", "3")]),
+            Err("Value \"1e\" for $max is not a number".to_string())
+        );
+
+        // Not placeholders.
+        let t = QueryTemplate::parse("a:$a* OR b:$b.c OR c:$").unwrap();
+        assert!(t.placeholders().is_empty());
+        assert_eq!(t.bind(&[]), "a:$a* OR b:$b.c OR c:$".parse());
+
+        assert!(QueryTemplate::parse("a:$a AND").is_err());
+        // Outside of templates, placeholders are plain terms.
+        assert_eq!("a:$a".parse(), Ok("a".has_value("$a")));
+    }
+
+    #[test]
+    fn test_template_options() {
+        let options = ParserOptions::default()
+            .field_type("price", FieldType::Integer)
+            .field_type("city", FieldType::Keyword)
+            .default_field("title")
+            .max_atoms(3);
+        let t = QueryTemplate::parse_with("price:$p AND city:$p AND $q", &options).unwrap();
+        assert_eq!(t.placeholders(), vec!["p", "q"]);
+        assert_eq!(
+            t.bind(&[("p", "10"), ("q", "blue")]),
+            Ok("price".i64_eq(10) & "city".has_value("10") & "title".has_value("blue"))
+        );
+        assert!(QueryTemplate::parse_with("a:$a AND b:$b AND c:$c AND d:$d", &options).is_err());
+
+        let options = ParserOptions::default().strict_h3_cells(true);
+        let t = QueryTemplate::parse_with("location H3IN $cell", &options).unwrap();
+        assert!(t.bind(&[("cell", "861f09b27ffffff")]).is_ok());
+        assert!(t.bind(&[("cell", "blabla")]).is_err());
+    }
+}
@@ -1,10 +1,12 @@
-use hashbrown::HashMap;
+use h3o::CellIndex;
+use std::borrow::Cow;
+use std::hash::{DefaultHasher, Hash, Hasher};
 
-use itertools::Itertools;
+use itertools::{Either, Itertools};
 
-use crate::models::cnf::Clause;
-use crate::models::queries::term::TermQuery;
-use crate::models::types::OurStr;
+use crate::models::bytes::{decode_bytes, encode_bytes};
+use crate::models::schema::Schema;
+use crate::models::types::{OurHashMap, OurStr};
 
 /// A Document is what you build to percolate through the set of queries
 /// using a Percolator. A document is simply a multimap of (field,value)
@@ -32,8 +34,11 @@ use crate::models::types::OurStr;
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct Document {
     // Fields representing the document's content
-    fields: HashMap<OurStr, Vec<OurStr>>,
+    fields: OurHashMap<OurStr, Vec<OurStr>>,
     fvs_count: usize,
+    // Sub-documents by path, like the items of an order,
+    // kept apart for nested queries.
+    nested: OurHashMap<OurStr, Vec<Document>>,
 }
 
 type FieldValue = (OurStr, OurStr);
@@ -64,12 +69,59 @@ impl Document {
         self.fvs_count
     }
 
-    pub(crate) fn to_clause(&self) -> Clause {
-        Clause::from_termqueries(
-            self.field_values()
-                .map(|(f, v)| TermQuery::new(f, v))
-                .collect(),
-        )
+    /// Removes the duplicate values of each field,
+    /// keeping the first one.
+    ///
+    /// # Example:
+    /// ```
+    /// use mokaccino::models::document::Document;
+    ///
+    /// let mut d: Document = [("A", "a"), ("A", "b"), ("A", "a"), ("B", "a")].into();
+    /// d.dedup();
+    /// assert_eq!(d.values("A"), vec!["a".into(), "b".into()]);
+    /// assert_eq!(d.fv_count(), 3);
+    /// ```
+    pub fn dedup(&mut self) {
+        for values in self.fields.values_mut() {
+            *values = values.iter().unique().cloned().collect();
+        }
+        self.fvs_count = self.fields.values().map(|vs| vs.len()).sum();
+    }
+
+    /// A hash of the content of this document: its (field,value) tuples
+    /// and nested elements, independent of the order they were added in.
+    /// It is stable across processes, but not across Rust releases.
+    ///
+    /// # Example:
+    /// ```
+    /// use mokaccino::models::document::Document;
+    ///
+    /// let d1: Document = [("colour", "blue"), ("taste", "sweet")].into();
+    /// let d2: Document = [("taste", "sweet"), ("colour", "blue")].into();
+    /// assert_eq!(d1.content_hash(), d2.content_hash());
+    /// assert_ne!(d1.content_hash(), Document::default().content_hash());
+    /// ```
+    pub fn content_hash(&self) -> u64 {
+        DocumentView::content_hash(self)
+    }
+
+    // Same content as the other document, whatever the order
+    // of the (field,value) tuples and nested elements.
+    pub(crate) fn same_content(&self, other: &impl DocumentView) -> bool {
+        let hashes = |es: &[Document]| es.iter().map(|e| e.content_hash()).sorted().collect_vec();
+        // With as many values, the same values for each field leave no room for other fields.
+        self.fvs_count == other.str_field_values().count()
+            && self.fields.iter().all(|(f, vs)| {
+                other
+                    .str_values(f)
+                    .is_some_and(|ovs| vs.iter().map(|v| v.as_ref()).sorted().eq(ovs.sorted()))
+            })
+            && self.nested.values().map(|es| es.len()).sum::<usize>()
+                == other.nested_elements().count()
+            && self
+                .nested
+                .iter()
+                .all(|(path, es)| hashes(es) == hashes(other.nested(path)))
     }
 
     /// An iterator on all the (field,value) tuples of this document.
@@ -97,10 +149,19 @@ impl Document {
     /// ```
     pub fn merge_with(&self, other: &Self) -> Self {
         // Find all the (key,value) of a document.
-        self.field_values()
+        let mut merged = self
+            .field_values()
             .chain(other.field_values())
             .unique()
-            .fold(Document::new(), |a, (f, v)| a.with_value(f, v.clone()))
+            .fold(Document::new(), |a, (f, v)| a.with_value(f, v.clone()));
+        for (path, elements) in self.nested.iter().chain(other.nested.iter()) {
+            merged
+                .nested
+                .entry(path.clone())
+                .or_default()
+                .extend(elements.iter().cloned());
+        }
+        merged
     }
 
     /// This document with a new field,value
@@ -144,6 +205,167 @@ impl Document {
         self.fvs_count += 1;
     }
 
+    /// Replaces all the values of the field with this one,
+    /// adding the field if needed.
+    /// # Example:
+    /// ```
+    /// use mokaccino::models::document::Document;
+    /// let mut d: Document = [("status", "up"), ("status", "starting")].into();
+    /// d.set_value("status", "down");
+    /// assert_eq!(d.values("status"), vec!["down".into()]);
+    /// assert_eq!(d.fv_count(), 1);
+    /// ```
+    ///
+    pub fn set_value<T, U>(&mut self, field: T, value: U)
+    where
+        T: Into<OurStr>,
+        U: Into<OurStr>,
+    {
+        let values = self.fields.entry(field.into()).or_default();
+        self.fvs_count = self.fvs_count - values.len() + 1;
+        *values = vec![value.into()];
+    }
+
+    /// Removes all the occurrences of this value from the field,
+    /// and the field if it has no values left.
+    /// False if the field did not have this value.
+    /// # Example:
+    /// ```
+    /// use mokaccino::models::document::Document;
+    /// let mut d: Document = [("colour", "blue"), ("colour", "red")].into();
+    /// assert!(d.remove_value("colour", "blue"));
+    /// assert!(!d.remove_value("colour", "blue"));
+    /// assert_eq!(d, [("colour", "red")].into());
+    /// ```
+    ///
+    pub fn remove_value(&mut self, field: &str, value: &str) -> bool {
+        let Some(values) = self.fields.get_mut(field) else {
+            return false;
+        };
+        let before = values.len();
+        values.retain(|v| **v != *value);
+        let removed = before - values.len();
+        if values.is_empty() {
+            self.fields.remove(field);
+        }
+        self.fvs_count -= removed;
+        removed > 0
+    }
+
+    /// Removes the field, returning its values.
+    /// # Example:
+    /// ```
+    /// use mokaccino::models::document::Document;
+    /// let mut d: Document = [("colour", "blue"), ("taste", "sweet")].into();
+    /// assert_eq!(d.remove_field("colour"), vec!["blue".into()]);
+    /// assert!(d.remove_field("colour").is_empty());
+    /// assert_eq!(d, [("taste", "sweet")].into());
+    /// ```
+    ///
+    pub fn remove_field(&mut self, field: &str) -> Vec<OurStr> {
+        let values = self.fields.remove(field).unwrap_or_default();
+        self.fvs_count -= values.len();
+        values
+    }
+
+    /// This document with a new bytes value, like a binary identifier,
+    /// written the way `has_bytes` queries expect to read it, without
+    /// going through hexadecimal.
+    ///
+    /// # Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let d = Document::default().with_bytes("hash", &[0xde, 0xad, 0xbe, 0xef]);
+    /// assert_eq!(d.bytes_values("hash"), vec![vec![0xde, 0xad, 0xbe, 0xef]]);
+    /// assert!("hash".has_bytes(&[0xde, 0xad, 0xbe, 0xef]).matches(&d));
+    /// ```
+    pub fn with_bytes<T: Into<OurStr>>(self, field: T, bytes: &[u8]) -> Self {
+        self.with_value(field, encode_bytes(bytes))
+    }
+
+    /// All the bytes values of the field, added with [`Document::with_bytes`].
+    /// Values that are not bytes are left out.
+    pub fn bytes_values(&self, field: &str) -> Vec<Vec<u8>> {
+        self.values_iter(field)
+            .into_iter()
+            .flatten()
+            .filter_map(|v| decode_bytes(&v))
+            .collect()
+    }
+
+    /// This document with a new element under the nested path, like an
+    /// item of an order under `items`. [`crate::prelude::Query::nested`]
+    /// queries match the elements one by one, so `price<10 AND colour:blue`
+    /// needs an item with both.
+    ///
+    /// The values of the element are also in this document, flattened
+    /// under the path like `items.price`, for the other queries. Elements
+    /// are found through these values, so elements without any are ignored
+    /// by percolators. Changing the flattened values does not change the elements.
+    ///
+    /// # Example:
+    /// ```
+    /// use mokaccino::models::document::Document;
+    ///
+    /// let d = Document::default()
+    ///     .with_nested("items", [("colour", "blue"), ("price", "12")].into())
+    ///     .with_nested("items", [("colour", "red"), ("price", "5")].into());
+    /// assert_eq!(d.nested("items").len(), 2);
+    /// assert_eq!(d.values("items.colour"), vec!["blue".into(), "red".into()]);
+    /// ```
+    pub fn with_nested<T: Into<OurStr>>(mut self, path: T, element: Document) -> Self {
+        let path: OurStr = path.into();
+        for (f, v) in element.field_values() {
+            self.with_value_mut(format!("{}.{}", path, f), v);
+        }
+        self.nested.entry(path).or_default().push(element);
+        self
+    }
+
+    /// The elements under the nested path, in the order they were added.
+    pub fn nested(&self, path: &str) -> &[Document] {
+        self.nested.get(path).map(|es| es.as_slice()).unwrap_or(&[])
+    }
+
+    /// This document with a new latitude,longitude value, in degrees,
+    /// written the way `latlng_within` and `latlng_in_polygon` queries
+    /// expect to read it.
+    ///
+    /// # Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use h3o::LatLng;
+    ///
+    /// let d = Document::default().with_latlng("position", 48.859430, 2.354946);
+    /// assert_eq!(d.values("position"), vec!["48.85943,2.354946".into()]);
+    ///
+    /// let q = "position".latlng_within(LatLng::new(48.864716, 2.349014).unwrap(), Meters(1000));
+    /// assert!(q.matches(&d));
+    /// ```
+    pub fn with_latlng<T: Into<OurStr>>(self, field: T, lat: f64, lng: f64) -> Self {
+        self.with_value(field, format!("{},{}", lat, lng))
+    }
+
+    /// This document with a new H3 cell value, written the way
+    /// `h3in` queries expect to read it.
+    ///
+    /// # Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use h3o::CellIndex;
+    ///
+    /// let cell: CellIndex = "8a1f09b2079ffff".parse().unwrap();
+    /// let d = Document::default().with_h3_cell("location", cell);
+    /// assert_eq!(d.values("location"), vec!["8a1f09b2079ffff".into()]);
+    ///
+    /// let q = "location".h3in("861f09b27ffffff".parse().unwrap());
+    /// assert!(q.matches(&d));
+    /// ```
+    pub fn with_h3_cell<T: Into<OurStr>>(self, field: T, cell: CellIndex) -> Self {
+        self.with_value(field, cell.to_string())
+    }
+
     pub fn has_field(&self, f: &str) -> bool {
         self.fields.contains_key(f)
     }
@@ -164,12 +386,30 @@ impl Document {
         self.fields.get(field).cloned().unwrap_or_default()
     }
 
+    /// Checks this document against the schema: all its fields are in it,
+    /// with the right number of values of the right type. The error lists
+    /// all the problems, separated by `; `. See [`Schema`] for an example.
+    pub fn validate(&self, schema: &Schema) -> Result<(), String> {
+        let problems = schema.problems(self);
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+
     /// All values of the field if it exists
     pub fn values_iter(&self, field: &str) -> Option<impl Iterator<Item = OurStr> + '_ + use<'_>> {
         self.fields.get(field).map(|v| v.iter().cloned())
     }
 }
 
+fn hash_of<T: Hash>(t: &T) -> u64 {
+    let mut state = DefaultHasher::new();
+    t.hash(&mut state);
+    state.finish()
+}
+
 impl<K, V, const N: usize> From<[(K, V); N]> for Document
 where
     K: Into<OurStr>,
@@ -181,6 +421,239 @@ where
     }
 }
 
+// What percolating reads of a document, owned or borrowed,
+// without copying its fields and values.
+pub(crate) trait DocumentView {
+    // All the (field,value) tuples, in no particular order.
+    fn str_field_values(&self) -> impl Iterator<Item = (&str, &str)>;
+
+    // All the values of the field, if it exists.
+    fn str_values(&self, field: &str) -> Option<impl Iterator<Item = &str>>;
+
+    // The elements under the nested path.
+    fn nested(&self, path: &str) -> &[Document];
+
+    // All the nested elements, with their paths.
+    fn nested_elements(&self) -> impl Iterator<Item = (&str, &Document)>;
+
+    // The document itself, owned, for what outlives the percolation.
+    fn to_owned_document(&self) -> Cow<'_, Document>;
+
+    fn has_field(&self, field: &str) -> bool {
+        self.str_values(field).is_some()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.str_field_values().next().is_none()
+    }
+
+    // See Document::content_hash.
+    fn content_hash(&self) -> u64 {
+        let values = self.str_field_values().map(|fv| hash_of(&fv));
+        let elements = self
+            .nested_elements()
+            .map(|(path, e)| hash_of(&(path, e.content_hash())));
+        values.chain(elements).fold(0, u64::wrapping_add)
+    }
+
+    // The (field,value) terms to look up in the indexes.
+    // Duplicate terms are left out when dedup is true.
+    fn terms(&self, dedup: bool) -> Vec<(&str, &str)> {
+        if dedup {
+            self.str_field_values().unique().collect()
+        } else {
+            self.str_field_values().collect()
+        }
+    }
+}
+
+impl DocumentView for Document {
+    fn str_field_values(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields
+            .iter()
+            .flat_map(|(field, values)| values.iter().map(move |value| (&**field, &**value)))
+    }
+
+    fn str_values(&self, field: &str) -> Option<impl Iterator<Item = &str>> {
+        self.fields.get(field).map(|vs| vs.iter().map(|v| &**v))
+    }
+
+    fn nested(&self, path: &str) -> &[Document] {
+        Document::nested(self, path)
+    }
+
+    fn nested_elements(&self) -> impl Iterator<Item = (&str, &Document)> {
+        self.nested
+            .iter()
+            .flat_map(|(path, es)| es.iter().map(move |e| (&**path, e)))
+    }
+
+    fn to_owned_document(&self) -> Cow<'_, Document> {
+        Cow::Borrowed(self)
+    }
+
+    fn has_field(&self, field: &str) -> bool {
+        Document::has_field(self, field)
+    }
+
+    fn is_empty(&self) -> bool {
+        Document::is_empty(self)
+    }
+}
+
+// A document or its clamped copy, for the integer comparisons.
+impl<D: DocumentView> DocumentView for Either<&D, Document> {
+    fn str_field_values(&self) -> impl Iterator<Item = (&str, &str)> {
+        match self {
+            Either::Left(d) => Either::Left(d.str_field_values()),
+            Either::Right(d) => Either::Right(d.str_field_values()),
+        }
+    }
+
+    fn str_values(&self, field: &str) -> Option<impl Iterator<Item = &str>> {
+        match self {
+            Either::Left(d) => d.str_values(field).map(Either::Left),
+            Either::Right(d) => d.str_values(field).map(Either::Right),
+        }
+    }
+
+    fn nested(&self, path: &str) -> &[Document] {
+        match self {
+            Either::Left(d) => d.nested(path),
+            Either::Right(d) => d.nested(path),
+        }
+    }
+
+    fn nested_elements(&self) -> impl Iterator<Item = (&str, &Document)> {
+        match self {
+            Either::Left(d) => Either::Left(d.nested_elements()),
+            Either::Right(d) => Either::Right(d.nested_elements()),
+        }
+    }
+
+    fn to_owned_document(&self) -> Cow<'_, Document> {
+        match self {
+            Either::Left(d) => d.to_owned_document(),
+            Either::Right(d) => Cow::Borrowed(d),
+        }
+    }
+
+    fn has_field(&self, field: &str) -> bool {
+        match self {
+            Either::Left(d) => d.has_field(field),
+            Either::Right(d) => d.has_field(field),
+        }
+    }
+}
+
+/// A borrowed view of a document, for callers already owning the strings
+/// of their fields and values, like the fields of a parsed log line.
+///
+/// Percolating it with [`crate::prelude::PercolatorUid::percolate_borrowed`]
+/// looks its fields and values up and matches them where they are, where
+/// building a [`Document`] copies every field and value. Only a percolator
+/// retaining documents or caching recent matches copies it, to keep it.
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+///
+/// let line = String::from("colour=blue size=42");
+/// let d = line
+///     .split(' ')
+///     .filter_map(|kv| kv.split_once('='))
+///     .collect::<DocumentRef>();
+/// assert_eq!(d.to_document(), [("colour", "blue"), ("size", "42")].into());
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DocumentRef<'a> {
+    fvs: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> DocumentRef<'a> {
+    /// Alias for default. An empty document.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fvs.is_empty()
+    }
+
+    /// This document with a new field,value.
+    pub fn with_value(mut self, field: &'a str, value: &'a str) -> Self {
+        self.with_value_mut(field, value);
+        self
+    }
+
+    /// This document with a new field,value, by mutable reference.
+    pub fn with_value_mut(&mut self, field: &'a str, value: &'a str) {
+        self.fvs.push((field, value));
+    }
+
+    /// All the field,values, in the order they were added.
+    pub fn field_values(&self) -> impl Iterator<Item = (&'a str, &'a str)> + use<'_, 'a> {
+        self.fvs.iter().copied()
+    }
+
+    /// The owned document with the same field,values.
+    pub fn to_document(&self) -> Document {
+        let mut d = Document::default();
+        for (field, value) in self.field_values() {
+            // Only the first value of a field copies its name.
+            d.fields.entry_ref(field).or_default().push(value.into());
+            d.fvs_count += 1;
+        }
+        d
+    }
+}
+
+impl DocumentView for DocumentRef<'_> {
+    fn str_field_values(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.field_values()
+    }
+
+    fn str_values(&self, field: &str) -> Option<impl Iterator<Item = &str>> {
+        let mut values = self
+            .fvs
+            .iter()
+            .filter(move |(f, _)| *f == field)
+            .map(|(_, v)| *v)
+            .peekable();
+        values.peek().is_some().then_some(values)
+    }
+
+    fn nested(&self, _path: &str) -> &[Document] {
+        &[]
+    }
+
+    fn nested_elements(&self) -> impl Iterator<Item = (&str, &Document)> {
+        std::iter::empty()
+    }
+
+    fn to_owned_document(&self) -> Cow<'_, Document> {
+        Cow::Owned(self.to_document())
+    }
+
+    fn is_empty(&self) -> bool {
+        DocumentRef::is_empty(self)
+    }
+}
+
+impl<'a, const N: usize> From<[(&'a str, &'a str); N]> for DocumentRef<'a> {
+    fn from(arr: [(&'a str, &'a str); N]) -> Self {
+        arr.into_iter().collect()
+    }
+}
+
+impl<'a> FromIterator<(&'a str, &'a str)> for DocumentRef<'a> {
+    fn from_iter<I: IntoIterator<Item = (&'a str, &'a str)>>(iter: I) -> Self {
+        Self {
+            fvs: iter.into_iter().collect(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::prelude::Document;
@@ -221,7 +694,7 @@ mod test {
     }
 
     #[test]
-    fn test_document_to_clause() {
+    fn test_document_terms() {
         use super::*;
         let d = Document::default()
             .with_value("colour", "blue")
@@ -230,14 +703,109 @@ mod test {
         assert!(!d.is_empty());
         assert!(!d.is_match_all());
 
-        let clause = d.to_clause();
+        // The terms are in the order of the fields, which is not sorted.
+        let terms = vec![("colour", "blue"), ("taste", "bitter"), ("taste", "sweet")];
+        assert_eq!(d.terms(false).into_iter().sorted().collect_vec(), terms);
+
+        let d = d.with_value("colour", "blue");
+        assert_eq!(d.terms(false).len(), 4);
+        assert_eq!(d.terms(true).into_iter().sorted().collect_vec(), terms);
+
+        assert!(Document::default().terms(false).is_empty());
+    }
+
+    #[test]
+    fn test_dedup() {
+        let mut d = Document::default()
+            .with_value("colour", "blue")
+            .with_value("colour", "red")
+            .with_value("taste", "bitter")
+            .with_value("colour", "blue");
+        d.dedup();
+        assert_eq!(d.values("colour"), vec!["blue".into(), "red".into()]);
+        assert_eq!(d.values("taste"), vec!["bitter".into()]);
+        assert_eq!(d.fv_count(), 3);
+
+        let mut empty = Document::default();
+        empty.dedup();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let d1 = Document::default()
+            .with_value("colour", "blue")
+            .with_value("taste", "bitter");
+        let d2 = Document::default()
+            .with_value("taste", "bitter")
+            .with_value("colour", "blue");
+        assert_eq!(d1.content_hash(), d2.content_hash());
+        assert!(d1.same_content(&d2));
+        assert_ne!(d1.content_hash(), Document::default().content_hash());
+        assert!(!d1.same_content(&Document::default()));
+        assert_ne!(
+            d1.content_hash(),
+            d1.clone().with_value("colour", "blue").content_hash()
+        );
+        assert!(!d1.same_content(&d1.clone().with_value("colour", "blue")));
+        assert!(!d1.same_content(&Document::from([("colour", "blue"), ("colour", "bitter")])));
+
+        let item = |colour: &str| Document::from([("colour", colour)]);
+        let n1 = d1
+            .clone()
+            .with_nested("items", item("red"))
+            .with_nested("items", item("blue"));
+        let n2 = d2
+            .clone()
+            .with_nested("items", item("blue"))
+            .with_nested("items", item("red"));
+        assert_eq!(n1.content_hash(), n2.content_hash());
+        assert!(n1.same_content(&n2));
+        // Same flattened values, different elements.
+        let n3 = d2.with_nested(
+            "items",
+            Document::from([("colour", "blue"), ("colour", "red")]),
+        );
+        assert_ne!(n1.content_hash(), n3.content_hash());
+        assert!(!n1.same_content(&n3));
+    }
+
+    #[test]
+    fn test_geo_values() {
+        use crate::models::queries::latlng_within::parse_latlng;
+        use h3o::{CellIndex, LatLng};
+
+        let d = Document::default()
+            .with_latlng("p", 48.864716, -2.349014)
+            .with_latlng("p", 1e-7, 0.0);
         assert_eq!(
-            clause.to_string(),
-            "(OR colour=blue taste=bitter taste=sweet)"
+            d.values("p")
+                .iter()
+                .map(|v| parse_latlng(v).unwrap())
+                .collect::<Vec<_>>(),
+            vec![
+                LatLng::new(48.864716, -2.349014).unwrap(),
+                LatLng::new(1e-7, 0.0).unwrap()
+            ]
         );
 
-        let d = Document::default();
-        assert_eq!(d.to_clause().to_string(), "(OR )");
+        let cell: CellIndex = "861f09b27ffffff".parse().unwrap();
+        let d = Document::default().with_h3_cell("c", cell);
+        assert_eq!(d.values("c")[0].parse::<CellIndex>().unwrap(), cell);
+    }
+
+    #[cfg(feature = "deterministic")]
+    #[test]
+    fn test_deterministic_order() {
+        let build = || {
+            (0..100).fold(Document::default(), |d, i| {
+                d.with_value(format!("field{}", i), "value")
+            })
+        };
+        assert_eq!(
+            build().fields().collect::<Vec<_>>(),
+            build().fields().collect::<Vec<_>>()
+        );
     }
 
     #[test]
@@ -248,4 +816,105 @@ mod test {
         assert!(d.values_ref("field").is_none());
         assert_eq!(d.fields().count(), 0);
     }
+
+    #[test]
+    fn test_document_ref() {
+        use super::*;
+        let d =
+            DocumentRef::from([("colour", "blue"), ("taste", "sweet")]).with_value("colour", "red");
+        assert!(!d.is_empty());
+        assert!(DocumentRef::new().is_empty());
+        assert_eq!(
+            d.field_values().collect::<Vec<_>>(),
+            vec![("colour", "blue"), ("taste", "sweet"), ("colour", "red")]
+        );
+        let owned = d.to_document();
+        assert_eq!(owned.values("colour"), vec!["blue".into(), "red".into()]);
+        assert_eq!(owned.fv_count(), 3);
+        assert_eq!(
+            owned,
+            [("colour", "blue"), ("taste", "sweet"), ("colour", "red")].into()
+        );
+
+        // The borrowed view reads the same as the owned document.
+        assert!(d.has_field("taste"));
+        assert!(!d.has_field("smell"));
+        assert_eq!(
+            d.str_values("colour").unwrap().collect_vec(),
+            vec!["blue", "red"]
+        );
+        assert!(d.str_values("smell").is_none());
+        assert_eq!(DocumentView::content_hash(&d), owned.content_hash());
+        assert!(owned.same_content(&d));
+    }
+
+    #[test]
+    fn test_mutations() {
+        let mut d = Document::default()
+            .with_value("colour", "blue")
+            .with_value("colour", "red")
+            .with_value("colour", "blue")
+            .with_value("taste", "bitter");
+
+        assert!(!d.remove_value("size", "big"));
+        assert!(!d.remove_value("colour", "green"));
+        assert!(d.remove_value("colour", "blue"));
+        assert_eq!(d.values("colour"), vec!["red".into()]);
+        assert_eq!(d.fv_count(), 2);
+
+        d.set_value("taste", "sweet");
+        d.set_value("size", "big");
+        assert_eq!(d.values("taste"), vec!["sweet".into()]);
+        assert_eq!(d.fv_count(), 3);
+
+        assert!(d.remove_value("colour", "red"));
+        assert!(!d.has_field("colour"));
+        assert_eq!(d.remove_field("size"), vec!["big".into()]);
+        assert_eq!(d, Document::default().with_value("taste", "sweet"));
+
+        assert_eq!(d.remove_field("taste").len(), 1);
+        assert!(d.is_empty());
+        assert_eq!(d, Document::default());
+    }
+
+    #[test]
+    fn test_nested() {
+        let item = |colour: &str| Document::from([("colour", colour)]);
+        let d = Document::default()
+            .with_value("customer", "bob")
+            .with_nested("items", item("blue"))
+            .with_nested("items", item("red"))
+            .with_nested("items", Document::default());
+        assert_eq!(
+            d.nested("items"),
+            &[item("blue"), item("red"), Document::default()]
+        );
+        assert!(d.nested("customer").is_empty());
+        assert_eq!(d.values("items.colour"), vec!["blue".into(), "red".into()]);
+        assert_eq!(d.fv_count(), 3);
+        assert_ne!(
+            d,
+            [
+                ("customer", "bob"),
+                ("items.colour", "blue"),
+                ("items.colour", "red")
+            ]
+            .into()
+        );
+
+        // Deeper elements are flattened all the way.
+        let order = Document::default().with_nested(
+            "items",
+            item("blue").with_nested("parts", [("size", "M")].into()),
+        );
+        assert_eq!(order.values("items.parts.size"), vec!["M".into()]);
+        assert_eq!(order.nested("items")[0].nested("parts").len(), 1);
+
+        let merged = d.merge_with(&order);
+        assert_eq!(merged.nested("items").len(), 4);
+        assert_eq!(
+            merged.values("items.colour"),
+            vec!["blue".into(), "red".into()]
+        );
+    }
 }
@@ -1,11 +1,14 @@
-use hashbrown::HashMap;
 use std::hash::{BuildHasher, Hash, Hasher};
 use std::sync::LazyLock;
 
+use itertools::Either;
 use roaring::RoaringBitmap;
 
 use super::document::Document;
-use crate::models::types::OurStr;
+use crate::models::types::{OurHashMap, OurStr};
+
+mod terms;
+use terms::CompactTermIdxs;
 
 pub type DocId = u32;
 
@@ -14,29 +17,87 @@ pub(crate) struct Index {
     // Remember the documents
     //documents: Vec<Document>,
     // The inverted indices for each ( field,  value)
-    term_idxs: HashMap<(OurStr, OurStr), RoaringBitmap>,
+    term_idxs: TermIdxs,
     //empty_bs: RoaringBitmap,
     n_documents: DocId,
 }
 
+#[derive(Debug)]
+enum TermIdxs {
+    Plain(OurHashMap<(OurStr, OurStr), RoaringBitmap>),
+    // Smaller, but slower to look up.
+    Compact(CompactTermIdxs),
+}
+
+impl Default for TermIdxs {
+    fn default() -> Self {
+        TermIdxs::Plain(Default::default())
+    }
+}
+
 static EMPTY_BITMAP: LazyLock<RoaringBitmap> = LazyLock::new(RoaringBitmap::new);
 
 impl Index {
+    /// An empty index storing its values prefix compressed.
+    pub(crate) fn with_prefix_compression() -> Self {
+        Self {
+            term_idxs: TermIdxs::Compact(Default::default()),
+            n_documents: 0,
+        }
+    }
+
     /// How many documents were indexed.
     pub(crate) fn len(&self) -> usize {
         self.n_documents as usize
     }
 
+    /// Roughly how many bytes this takes on the heap.
+    /// Bitmaps count for their serialized size.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        match &self.term_idxs {
+            TermIdxs::Plain(term_idxs) => {
+                term_idxs.capacity() * size_of::<((OurStr, OurStr), RoaringBitmap)>()
+                    + term_idxs
+                        .iter()
+                        .map(|((f, v), b)| f.len() + v.len() + b.serialized_size())
+                        .sum::<usize>()
+            }
+            TermIdxs::Compact(term_idxs) => term_idxs.heap_bytes(),
+        }
+    }
+
+    /// The indexed fields and values, with the documents having them.
+    /// In no particular order.
+    pub(crate) fn terms_iter(&self) -> impl Iterator<Item = (OurStr, OurStr, &RoaringBitmap)> {
+        let terms = match &self.term_idxs {
+            TermIdxs::Plain(term_idxs) => Either::Left(
+                term_idxs
+                    .iter()
+                    .map(|((f, v), b)| (f.clone(), v.clone(), b)),
+            ),
+            TermIdxs::Compact(term_idxs) => Either::Right(term_idxs.iter()),
+        };
+        // Compact indices keep the values of unindexed documents.
+        terms.filter(|(_, _, b)| !b.is_empty())
+    }
+
     /// A RoaringBitmap of doc IDs matching the field value.
     pub(crate) fn docs_from_fv(&self, field: &str, value: &str) -> &RoaringBitmap {
+        let term_idxs = match &self.term_idxs {
+            TermIdxs::Plain(term_idxs) => term_idxs,
+            TermIdxs::Compact(term_idxs) => {
+                return term_idxs.get(field, value).unwrap_or(&EMPTY_BITMAP);
+            }
+        };
+
         let hash = {
-            let mut state = self.term_idxs.hasher().build_hasher();
+            let mut state = term_idxs.hasher().build_hasher();
             field.hash(&mut state);
             value.hash(&mut state);
             state.finish()
         };
 
-        self.term_idxs
+        term_idxs
             .raw_entry()
             .from_hash(hash, |(k_field, k_value)| {
                 k_field.as_ref() == field && k_value.as_ref() == value
@@ -50,11 +111,37 @@ impl Index {
     #[allow(dead_code)]
     pub(crate) fn unindex_docid(&mut self, doc_id: DocId) {
         // Remove the docID for all the bitmaps.
-        self.term_idxs.values_mut().for_each(|b| {
+        let bitmaps: Box<dyn Iterator<Item = &mut RoaringBitmap>> = match &mut self.term_idxs {
+            TermIdxs::Plain(term_idxs) => Box::new(term_idxs.values_mut()),
+            TermIdxs::Compact(term_idxs) => Box::new(term_idxs.bitmaps_mut()),
+        };
+        bitmaps.for_each(|b| {
             b.remove(doc_id);
         });
     }
 
+    /// Make the given document, indexed with this DocID, unfindable.
+    /// Unlike unindex_docid, this only touches the document values.
+    pub(crate) fn unindex_document(&mut self, doc_id: DocId, d: &Document) {
+        for (field, value) in d.field_values() {
+            match &mut self.term_idxs {
+                TermIdxs::Plain(term_idxs) => {
+                    let key = (field, value);
+                    if let Some(bitmap) = term_idxs.get_mut(&key) {
+                        bitmap.remove(doc_id);
+                        // Do not keep values that are gone for good.
+                        if bitmap.is_empty() {
+                            term_idxs.remove(&key);
+                        }
+                    }
+                }
+                TermIdxs::Compact(term_idxs) => {
+                    term_idxs.entry(field, &value).remove(doc_id);
+                }
+            }
+        }
+    }
+
     /// Index a document in this index. Returns a new DocID
     pub(crate) fn index_document(&mut self, d: &Document) -> DocId {
         let new_doc_id = self.n_documents;
@@ -66,10 +153,80 @@ impl Index {
 
         // Update the right inverted indices.
         for (field, value) in d.field_values() {
-            self.term_idxs
-                .entry((field, value))
-                .or_default()
-                .insert(new_doc_id);
+            let bitmap = match &mut self.term_idxs {
+                TermIdxs::Plain(term_idxs) => term_idxs.entry((field, value)).or_default(),
+                TermIdxs::Compact(term_idxs) => term_idxs.entry(field, &value),
+            };
+            bitmap.insert(new_doc_id);
+        }
+        new_doc_id
+    }
+
+    /// An empty batch of documents to index with the next DocIDs.
+    pub(crate) fn batch(&self) -> IndexBatch {
+        IndexBatch {
+            terms: OurHashMap::default(),
+            first_doc_id: self.n_documents,
+            n_documents: 0,
+        }
+    }
+
+    /// Index the documents of the batch, merging each of its value
+    /// bitmaps in the index once. Returns the first DocID of the batch.
+    pub(crate) fn index_batch(&mut self, batch: IndexBatch) -> DocId {
+        assert_eq!(
+            batch.first_doc_id, self.n_documents,
+            "Batch from another state of the index"
+        );
+        self.n_documents = self
+            .n_documents
+            .checked_add(batch.n_documents)
+            .expect("Too many documents. Max is u32::MAX");
+
+        for ((field, value), docs) in batch.terms {
+            let bitmap = match &mut self.term_idxs {
+                TermIdxs::Plain(term_idxs) => term_idxs.entry((field, value)).or_default(),
+                TermIdxs::Compact(term_idxs) => term_idxs.entry(field, &value),
+            };
+            *bitmap |= docs;
+        }
+        batch.first_doc_id
+    }
+
+    /// Index a document again with a DocID this index already gave,
+    /// once unindexed with unindex_docid.
+    pub(crate) fn index_document_at(&mut self, doc_id: DocId, d: &Document) {
+        assert!(doc_id < self.n_documents, "DocID {} not given yet", doc_id);
+        for (field, value) in d.field_values() {
+            let bitmap = match &mut self.term_idxs {
+                TermIdxs::Plain(term_idxs) => term_idxs.entry((field, value)).or_default(),
+                TermIdxs::Compact(term_idxs) => term_idxs.entry(field, &value),
+            };
+            bitmap.insert(doc_id);
+        }
+    }
+}
+
+/// Documents to index at once, with consecutive DocIDs.
+/// See Index::batch
+#[derive(Debug)]
+pub(crate) struct IndexBatch {
+    // The documents of each ( field, value ) in the batch.
+    terms: OurHashMap<(OurStr, OurStr), RoaringBitmap>,
+    first_doc_id: DocId,
+    n_documents: DocId,
+}
+
+impl IndexBatch {
+    /// Adds a document to the batch, with the next DocID. Returns it.
+    pub(crate) fn add_document(&mut self, d: &Document) -> DocId {
+        let new_doc_id = self
+            .first_doc_id
+            .checked_add(self.n_documents)
+            .expect("Too many documents. Max is u32::MAX");
+        self.n_documents += 1;
+        for fv in d.field_values() {
+            self.terms.entry(fv).or_default().insert(new_doc_id);
         }
         new_doc_id
     }
@@ -90,6 +247,119 @@ mod test {
         assert!(index.docs_from_fv("field", "value").is_empty());
     }
 
+    #[test]
+    fn test_heap_bytes() {
+        use super::*;
+
+        for mut index in [Index::default(), Index::with_prefix_compression()] {
+            let empty = index.heap_bytes();
+            for i in 0..100 {
+                index.index_document(&[("url", format!("https://example.com/{}", i))].into());
+            }
+            assert!(index.heap_bytes() > empty + 100 * "https://example.com/".len() / 10);
+        }
+    }
+
+    #[test]
+    fn test_unindex_document() {
+        use super::*;
+
+        for mut index in [Index::default(), Index::with_prefix_compression()] {
+            let d1: Document = [("colour", "blue"), ("taste", "sweet")].into();
+            let d2: Document = [("colour", "blue")].into();
+            let id1 = index.index_document(&d1);
+            let id2 = index.index_document(&d2);
+
+            index.unindex_document(id1, &d1);
+            assert_eq!(
+                index
+                    .docs_from_fv("colour", "blue")
+                    .iter()
+                    .collect::<Vec<_>>(),
+                vec![id2]
+            );
+            assert!(index.docs_from_fv("taste", "sweet").is_empty());
+        }
+    }
+
+    #[test]
+    fn test_index_document_at() {
+        use super::*;
+
+        for mut index in [Index::default(), Index::with_prefix_compression()] {
+            let id = index.index_document(&[("colour", "blue")].into());
+            index.unindex_docid(id);
+            index.index_document_at(id, &[("colour", "red")].into());
+
+            assert_eq!(index.len(), 1);
+            assert!(index.docs_from_fv("colour", "blue").is_empty());
+            assert_eq!(
+                index
+                    .docs_from_fv("colour", "red")
+                    .iter()
+                    .collect::<Vec<_>>(),
+                vec![id]
+            );
+        }
+    }
+
+    #[test]
+    fn test_index_batch() {
+        use super::*;
+
+        for mut index in [Index::default(), Index::with_prefix_compression()] {
+            index.index_document(&[("colour", "blue")].into());
+            let mut batch = index.batch();
+            assert_eq!(
+                batch.add_document(&[("colour", "blue"), ("taste", "sweet")].into()),
+                1
+            );
+            assert_eq!(batch.add_document(&Document::default()), 2);
+            assert_eq!(batch.add_document(&[("colour", "blue")].into()), 3);
+            // Nothing indexed yet.
+            assert_eq!(index.len(), 1);
+            assert!(index.docs_from_fv("taste", "sweet").is_empty());
+
+            assert_eq!(index.index_batch(batch), 1);
+            assert_eq!(index.len(), 4);
+            assert_eq!(index.index_batch(index.batch()), 4);
+            assert_eq!(
+                index
+                    .docs_from_fv("colour", "blue")
+                    .iter()
+                    .collect::<Vec<_>>(),
+                vec![0, 1, 3]
+            );
+            assert_eq!(
+                index
+                    .docs_from_fv("taste", "sweet")
+                    .iter()
+                    .collect::<Vec<_>>(),
+                vec![1]
+            );
+        }
+    }
+
+    #[test]
+    fn test_terms_iter() {
+        use super::*;
+        use itertools::Itertools;
+
+        for mut index in [Index::default(), Index::with_prefix_compression()] {
+            let d1: Document = [("colour", "blue"), ("taste", "sweet")].into();
+            let d2: Document = [("colour", "blue")].into();
+            let id1 = index.index_document(&d1);
+            index.index_document(&d2);
+            index.unindex_document(id1, &d1);
+
+            let terms = index
+                .terms_iter()
+                .map(|(f, v, b)| (f, v, b.len()))
+                .collect_vec();
+            assert_eq!(terms, vec![("colour".into(), "blue".into(), 1)]);
+        }
+    }
+
     #[test]
     fn test_few_docs() {
         use super::*;
@@ -143,37 +413,13 @@ mod test {
                 .is_none()
         );
 
-        assert!(
-            index
-                .docs_from_fv(&colour, "blue")
-                .iter()
-                .next()
-                .is_some()
-        );
+        assert!(index.docs_from_fv(&colour, "blue").iter().next().is_some());
 
-        assert!(
-            index
-                .docs_from_fv(&colour, "blue")
-                .iter()
-                .next()
-                .is_some()
-        );
+        assert!(index.docs_from_fv(&colour, "blue").iter().next().is_some());
 
-        assert!(
-            index
-                .docs_from_fv(&taste, "sweet")
-                .iter()
-                .next()
-                .is_some()
-        );
+        assert!(index.docs_from_fv(&taste, "sweet").iter().next().is_some());
 
-        assert!(
-            index
-                .docs_from_fv(&taste, "sweet")
-                .iter()
-                .next()
-                .is_some()
-        );
+        assert!(index.docs_from_fv(&taste, "sweet").iter().next().is_some());
 
         let sweet_docs = index
             .docs_from_fv(&taste, "sweet")
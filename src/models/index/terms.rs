@@ -0,0 +1,236 @@
+use std::hash::BuildHasher;
+
+use roaring::RoaringBitmap;
+
+use crate::models::types::{OurHashMap, OurHasher, OurStr};
+
+// Restart the front coding every so many values,
+// so decoding a value never walks back further.
+const BLOCK_SIZE: usize = 16;
+
+// The values of one field in insertion order, each stored as the length
+// of the prefix it shares with the previous value, followed by the rest.
+// Long values with common prefixes, like URLs or paths, take much less
+// room than on their own.
+#[derive(Debug, Default)]
+struct FrontCodedValues {
+    suffixes: String,
+    // Where each suffix starts, and the length of the shared prefix.
+    entries: Vec<(u32, u32)>,
+}
+
+impl FrontCodedValues {
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn suffix(&self, i: usize) -> &str {
+        let start = self.entries[i].0 as usize;
+        let end = self
+            .entries
+            .get(i + 1)
+            .map_or(self.suffixes.len(), |e| e.0 as usize);
+        &self.suffixes[start..end]
+    }
+
+    fn shared(&self, i: usize) -> usize {
+        self.entries[i].1 as usize
+    }
+
+    // Adds the value, returning its id.
+    fn push(&mut self, value: &str) -> u32 {
+        let id = self.len();
+        let shared = if id.is_multiple_of(BLOCK_SIZE) {
+            0
+        } else {
+            let previous = self.get((id - 1) as u32);
+            let mut n = previous
+                .bytes()
+                .zip(value.bytes())
+                .take_while(|(a, b)| a == b)
+                .count();
+            while !value.is_char_boundary(n) {
+                n -= 1;
+            }
+            n
+        };
+        let start =
+            u32::try_from(self.suffixes.len()).expect("Too many term bytes. Max is u32::MAX");
+        self.entries.push((start, shared as u32));
+        self.suffixes.push_str(&value[shared..]);
+        id as u32
+    }
+
+    fn get(&self, id: u32) -> String {
+        let id = id as usize;
+        let block_start = id - id % BLOCK_SIZE;
+        let mut value = self.suffix(block_start).to_string();
+        for i in block_start + 1..=id {
+            value.truncate(self.shared(i));
+            value.push_str(self.suffix(i));
+        }
+        value
+    }
+
+    // Is the value with this id equal to the given one?
+    // Does not decode it, so it does not allocate.
+    fn equals(&self, id: u32, value: &str) -> bool {
+        let mut i = id as usize;
+        if self.shared(i) + self.suffix(i).len() != value.len() {
+            return false;
+        }
+        // The bytes of value still to check, from the start.
+        let mut unchecked = value.len();
+        loop {
+            let shared = self.shared(i);
+            if unchecked > shared {
+                let suffix = &self.suffix(i).as_bytes()[..unchecked - shared];
+                if suffix != &value.as_bytes()[shared..unchecked] {
+                    return false;
+                }
+                unchecked = shared;
+            }
+            if unchecked == 0 {
+                return true;
+            }
+            i -= 1;
+        }
+    }
+}
+
+// The inverted indices, with fields and values coded as ids.
+#[derive(Debug, Default)]
+pub(crate) struct CompactTermIdxs {
+    field_ids: OurHashMap<OurStr, u32>,
+    values: Vec<FrontCodedValues>,
+    // Hashed by field id and value string, not by ids.
+    bitmaps: OurHashMap<(u32, u32), RoaringBitmap>,
+}
+
+fn hash_fv(hasher: &OurHasher, field_id: u32, value: &str) -> u64 {
+    hasher.hash_one((field_id, value))
+}
+
+impl CompactTermIdxs {
+    pub(crate) fn get(&self, field: &str, value: &str) -> Option<&RoaringBitmap> {
+        let field_id = *self.field_ids.get(field)?;
+        let values = &self.values[field_id as usize];
+        let hash = hash_fv(self.bitmaps.hasher(), field_id, value);
+        self.bitmaps
+            .raw_entry()
+            .from_hash(hash, |(f, v)| *f == field_id && values.equals(*v, value))
+            .map(|(_, b)| b)
+    }
+
+    pub(crate) fn entry(&mut self, field: OurStr, value: &str) -> &mut RoaringBitmap {
+        let n_fields = self.values.len() as u32;
+        let field_id = *self.field_ids.entry(field).or_insert(n_fields);
+        if field_id == n_fields {
+            self.values.push(FrontCodedValues::default());
+        }
+
+        let Self {
+            values, bitmaps, ..
+        } = self;
+        let hasher = bitmaps.hasher().clone();
+        let hash = hash_fv(&hasher, field_id, value);
+        let field_values = &values[field_id as usize];
+        match bitmaps.raw_entry_mut().from_hash(hash, |(f, v)| {
+            *f == field_id && field_values.equals(*v, value)
+        }) {
+            hashbrown::hash_map::RawEntryMut::Occupied(e) => e.into_mut(),
+            hashbrown::hash_map::RawEntryMut::Vacant(e) => {
+                let value_id = values[field_id as usize].push(value);
+                let values = &*values;
+                e.insert_with_hasher(
+                    hash,
+                    (field_id, value_id),
+                    RoaringBitmap::new(),
+                    |&(f, v)| hash_fv(&hasher, f, &values[f as usize].get(v)),
+                )
+                .1
+            }
+        }
+    }
+
+    pub(crate) fn bitmaps_mut(&mut self) -> impl Iterator<Item = &mut RoaringBitmap> {
+        self.bitmaps.values_mut()
+    }
+
+    // The fields and decoded values, with their bitmaps.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (OurStr, OurStr, &RoaringBitmap)> {
+        let mut fields = vec![OurStr::from(""); self.values.len()];
+        for (field, &id) in &self.field_ids {
+            fields[id as usize] = field.clone();
+        }
+        self.bitmaps.iter().map(move |(&(f, v), b)| {
+            let value = self.values[f as usize].get(v);
+            (fields[f as usize].clone(), value.into(), b)
+        })
+    }
+
+    // Roughly how many bytes this takes on the heap.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        self.field_ids.keys().map(|f| f.len()).sum::<usize>()
+            + self.field_ids.capacity() * size_of::<(OurStr, u32)>()
+            + self
+                .values
+                .iter()
+                .map(|v| v.suffixes.capacity() + v.entries.capacity() * size_of::<(u32, u32)>())
+                .sum::<usize>()
+            + self.bitmaps.capacity() * size_of::<((u32, u32), RoaringBitmap)>()
+            + self
+                .bitmaps
+                .values()
+                .map(|b| b.serialized_size())
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_front_coding() {
+        let mut rng = rand::rng();
+        let mut fc = FrontCodedValues::default();
+        let mut expected = Vec::new();
+        for _ in 0..1000 {
+            // Values sharing prefixes, some of them not ascii.
+            let value: String = (0..rng.random_range(0..8))
+                .map(|_| ["/", "a", "b", "é", "€", "http://"][rng.random_range(0..6)])
+                .collect();
+            assert_eq!(fc.push(&value) as usize, expected.len());
+            expected.push(value);
+        }
+        for (id, value) in expected.iter().enumerate() {
+            assert_eq!(&fc.get(id as u32), value);
+            assert!(fc.equals(id as u32, value));
+            assert!(!fc.equals(id as u32, &format!("{}a", value)));
+        }
+        assert!(fc.suffixes.len() < expected.iter().map(|v| v.len()).sum());
+    }
+
+    #[test]
+    fn test_compact_term_idxs() {
+        let mut idxs = CompactTermIdxs::default();
+        // Enough values to grow the map.
+        for i in 0..200 {
+            idxs.entry("url".into(), &format!("https://example.com/{}", i))
+                .insert(i);
+            idxs.entry("path".into(), &format!("/usr/{}", i % 10))
+                .insert(i);
+        }
+        assert_eq!(idxs.values.len(), 2);
+        assert_eq!(idxs.values[1].len(), 10);
+
+        let docs = |f: &str, v: &str| idxs.get(f, v).map(|b| b.iter().collect::<Vec<_>>());
+        assert_eq!(docs("url", "https://example.com/42"), Some(vec![42]));
+        assert_eq!(docs("path", "/usr/2"), Some((2..200).step_by(10).collect()));
+        assert_eq!(docs("url", "https://example.com/"), None);
+        assert_eq!(docs("url", "/usr/2"), None);
+        assert_eq!(docs("colour", "blue"), None);
+    }
+}
@@ -1,19 +1,49 @@
-use std::{fmt::Display, num::NonZeroUsize};
+use std::{
+    borrow::Borrow, collections::BTreeMap, fmt::Display, hash::Hash, num::NonZeroUsize,
+    time::SystemTime,
+};
+
+use h3o::Resolution;
+use roaring::RoaringBitmap;
 
 use crate::{
-    models::percolator_core::{PercolatorConfig, PercolatorCore, PercolatorError, PercolatorStats},
-    prelude::{Document, Qid, Query},
+    models::percolator_core::{
+        CorpusReport, EmptyDocuments, Explanation, IntOverflow, MemoryReport, PercolationTrace,
+        PercolatorConfig, PercolatorCore, PercolatorError, PercolatorStats, QualityThresholds,
+    },
+    models::types::OurRc,
+    prelude::{Document, DocumentRef, Qid, Query, Schema, SharedQuery},
 };
 
+#[cfg(feature = "send")]
+use crate::models::percolator_core::Backfill;
+
+mod uid_store;
+pub use uid_store::{BoxedUidStore, MemoryUidStore, UidMappingStore};
+
+mod handlers;
+use handlers::Handlers;
+pub use handlers::MatchHandler;
+
+#[cfg(feature = "send")]
+mod frozen;
+#[cfg(feature = "send")]
+pub use frozen::FrozenPercolator;
+
+#[cfg(feature = "tokio")]
+mod asynchronous;
+#[cfg(feature = "tokio")]
+pub use asynchronous::AsyncPercolator;
+
 /// A builder should you want to build a percolator
 /// with different parameters.
-pub struct PercBuilder<T> {
-    // There's a generic T, as this should be able to build a PercolatorUid<T>
+pub struct PercBuilder<T, P = ()> {
+    // There's a generic T, as this should be able to build a PercolatorUid<T, P>
     config: PercolatorConfig,
-    _marker: std::marker::PhantomData<T>,
+    _marker: std::marker::PhantomData<(T, P)>,
 }
 
-impl<T> Default for PercBuilder<T> {
+impl<T, P> Default for PercBuilder<T, P> {
     fn default() -> Self {
         Self {
             config: PercolatorConfig::default(),
@@ -22,7 +52,7 @@ impl<T> Default for PercBuilder<T> {
     }
 }
 
-impl<T> PercBuilder<T>
+impl<T, P> PercBuilder<T, P>
 where
     T: std::cmp::Eq + std::hash::Hash,
 {
@@ -33,13 +63,32 @@ where
         }
     }
 
-    pub fn build(self) -> PercolatorUid<T> {
-        PercolatorUid::<T> {
+    pub fn build(self) -> PercolatorUid<T, P> {
+        PercolatorUid::<T, P> {
             perc: PercolatorCore::from_config(self.config),
             qid_uid: bimap::BiMap::<Qid, T>::new(),
+            packs: BTreeMap::new(),
+            payloads: BTreeMap::new(),
+            handlers: Handlers::default(),
+            uid_store: None,
+            next_uid: 0,
         }
     }
 
+    /// The configuration percolators get built with,
+    /// for instance to compare some with [`crate::bench::compare`]
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let config = Percolator::builder().prefix_sizes(vec![2, 5]).config().clone();
+    /// assert_eq!(config.prefix_sizes(), &[2, 5]);
+    /// ```
+    pub fn config(&self) -> &PercolatorConfig {
+        &self.config
+    }
+
     /// Sets the expected number of clauses of indexed queries
     /// to the given value. This help minimizing the number of post-match
     /// checks the percolator has to do.
@@ -75,34 +124,370 @@ where
         self.config.prefix_sizes = sizes;
         self
     }
+
+    /// Keeps the last `size` percolations in memory,
+    /// for postmortem analysis. See [`PercolatorUid::recent_percolations`]
+    ///
+    /// The default is 0 (no tracing).
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let p = Percolator::builder().trace_size(100).build();
+    /// ```
+    pub fn trace_size(mut self, size: usize) -> Self {
+        self.config.trace_size = size;
+        self
+    }
+
+    /// Sets the finest H3 resolution geographic queries
+    /// are indexed at. Coarser indexing means fewer synthetic
+    /// terms and lookups, at the price of more candidate queries
+    /// needing a final check.
+    ///
+    /// The default is [`h3o::Resolution::Fifteen`].
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use h3o::Resolution;
+    /// let p = Percolator::builder()
+    ///     .h3_max_resolution(Resolution::Ten)
+    ///     .build();
+    /// ```
+    pub fn h3_max_resolution(mut self, resolution: Resolution) -> Self {
+        self.config.h3_max_resolution = resolution;
+        self
+    }
+
+    /// Percolates documents by checking every query, without
+    /// using the index, while there are fewer than `n` live queries.
+    /// For tiny sets of queries, this is faster than looking
+    /// the document up in the index. See also [`crate::prelude::LinearPercolator`]
+    ///
+    /// The default is 0 (always use the index).
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let p = Percolator::builder().linear_threshold(100).build();
+    /// ```
+    pub fn linear_threshold(mut self, n: usize) -> Self {
+        self.config.linear_threshold = n;
+        self
+    }
+
+    /// Stores the indexed values prefix compressed, which takes
+    /// much less memory for large sets of queries on long values
+    /// sharing prefixes, like URLs or paths, at the price of
+    /// slower percolation.
+    ///
+    /// The default is false.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::builder().prefix_compression(true).build();
+    /// let qid = p.add_query("url".has_value("https://example.com/blue"));
+    /// p.add_query("url".has_value("https://example.com/black"));
+    /// assert_eq!(
+    ///     p.percolate(&[("url", "https://example.com/blue")].into()).collect::<Vec<_>>(),
+    ///     vec![qid]
+    /// );
+    /// ```
+    pub fn prefix_compression(mut self, b: bool) -> Self {
+        self.config.prefix_compression = b;
+        self
+    }
+
+    /// Sets the number of recent percolated documents to keep,
+    /// so [`PercolatorUid::search`] can find which of them new
+    /// queries would have matched. The oldest ones are forgotten first.
+    ///
+    /// The default is 0 (no documents kept).
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let p = Percolator::builder().retain_size(1000).build();
+    /// ```
+    pub fn retain_size(mut self, size: usize) -> Self {
+        self.config.retain_size = size;
+        self
+    }
+
+    /// Rejects queries with more than `n` clauses in their Conjunctive
+    /// Normal Form, with [`PercolatorError::TooManyClauses`], before
+    /// indexing anything. Each clause takes memory and percolation time.
+    /// Build big disjunctions with [`Query::try_from_or`] and negations with
+    /// [`Query::try_negation`], or parse query strings with
+    /// [`crate::prelude::parsing::ParserOptions::max_clauses`], to stay within the same
+    /// budget without building them first.
+    ///
+    /// The default is 0 (no maximum).
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::builder().max_clauses(2).build();
+    /// assert!(p.safe_add_query("a".has_value("1") & "b".has_value("2")).is_ok());
+    /// let q = "a".has_value("1") & "b".has_value("2") & "c".has_value("3");
+    /// assert!(p.safe_add_query(q).is_err());
+    /// ```
+    pub fn max_clauses(mut self, n: usize) -> Self {
+        self.config.max_clauses = n;
+        self
+    }
+
+    /// Skip the duplicate (field,value) tuples of percolated documents,
+    /// like the ones upstream joins leave, so they do not make the
+    /// preheaters and the index do the same work twice.
+    /// Defaults to false. See also [`Document::dedup`]
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::builder().dedup_values(true).build();
+    /// let qid = p.add_query("colour".has_prefix("bl"));
+    /// let d: Document = [("colour", "blue"), ("colour", "blue")].into();
+    /// assert_eq!(p.percolate(&d).collect::<Vec<_>>(), vec![qid]);
+    /// ```
+    pub fn dedup_values(mut self, b: bool) -> Self {
+        self.config.dedup_values = b;
+        self
+    }
+
+    /// Make term queries match the document values trimmed and case folded,
+    /// like `" Blue\n"` for `colour:blue`, to handle messy upstream data
+    /// without changing its producers. Defaults to false.
+    ///
+    /// The percolator keeps the queries with their terms normalized,
+    /// instead of the ones added. Other queries, like prefixes
+    /// or term sets, are not changed.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::builder().normalize_terms(true).build();
+    /// let qid = p.add_query("colour".has_value("Blue"));
+    /// let d: Document = [("colour", " BLUE\n")].into();
+    /// assert_eq!(p.percolate(&d).collect::<Vec<_>>(), vec![qid]);
+    /// ```
+    pub fn normalize_terms(mut self, b: bool) -> Self {
+        self.config.normalize_terms = b;
+        self
+    }
+
+    /// Sets the length of the n-grams substring queries are indexed with.
+    /// See [`PercolatorConfig::ngram_size`] for details.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use std::num::NonZeroUsize;
+    /// let p = Percolator::builder().ngram_size(NonZeroUsize::new(4).unwrap()).build();
+    /// ```
+    pub fn ngram_size(mut self, n: NonZeroUsize) -> Self {
+        self.config.ngram_size = n;
+        self
+    }
+
+    /// Sets what integer comparisons make of percolated values
+    /// overflowing an `i64`. See [`IntOverflow`] for details.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use mokaccino::models::percolator_core::IntOverflow;
+    ///
+    /// let mut p = Percolator::builder().int_overflow(IntOverflow::Clamp).build();
+    /// let qid = p.add_query("bytes".i64_gt(1000));
+    /// let d: Document = [("bytes", "18446744073709551615")].into();
+    /// assert_eq!(p.percolate(&d).collect::<Vec<_>>(), vec![qid]);
+    /// ```
+    pub fn int_overflow(mut self, semantics: IntOverflow) -> Self {
+        self.config.int_overflow = semantics;
+        self
+    }
+
+    /// Sets what percolating an empty document matches.
+    /// See [`EmptyDocuments`] for details.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use mokaccino::models::percolator_core::EmptyDocuments;
+    ///
+    /// let mut p = Percolator::builder().empty_documents(EmptyDocuments::NoMatch).build();
+    /// p.add_query(!"colour".has_value("blue"));
+    /// assert_eq!(p.percolate(&Document::new()).count(), 0);
+    /// assert_eq!(p.percolate(&[("colour", "red")].into()).count(), 1);
+    /// ```
+    pub fn empty_documents(mut self, semantics: EmptyDocuments) -> Self {
+        self.config.empty_documents = semantics;
+        self
+    }
+
+    /// Rejects queries about fields that are not in the schema, with
+    /// [`PercolatorError::UnknownField`], before indexing anything.
+    /// Such queries are usually typos, and would never match valid documents.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use mokaccino::prelude::parsing::FieldType;
+    ///
+    /// let schema = Schema::default().field("colour", FieldType::Keyword, Multiplicity::Many);
+    /// let mut p = Percolator::builder().schema(schema).build();
+    /// assert!(p.safe_add_query("colour".has_value("blue")).is_ok());
+    /// assert!(p.safe_add_query("color".has_value("blue")).is_err());
+    /// ```
+    pub fn schema(mut self, schema: Schema) -> Self {
+        self.config.schema = Some(schema);
+        self
+    }
+
+    /// Remembers the matches of the last `size` distinct documents, so
+    /// percolating a document with the same content again, like a retried
+    /// event, does not percolate it again. Documents have the same content
+    /// when they have the same (field,value) tuples and nested elements,
+    /// in any order. See [`Document::content_hash`].
+    ///
+    /// Cached percolations are not traced, and the cache is cleared
+    /// whenever queries are added or removed. The matches are found at
+    /// once instead of lazily, and each cached document is kept in memory.
+    ///
+    /// The default is 0 (no cache).
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::builder().match_cache_size(1000).build();
+    /// let qid = p.add_query("colour".has_value("blue"));
+    /// let d: Document = [("colour", "blue"), ("taste", "sweet")].into();
+    /// assert_eq!(p.percolate(&d).collect::<Vec<_>>(), vec![qid]);
+    /// // From the cache.
+    /// let same: Document = [("taste", "sweet"), ("colour", "blue")].into();
+    /// assert_eq!(p.percolate(&same).collect::<Vec<_>>(), vec![qid]);
+    /// ```
+    pub fn match_cache_size(mut self, size: usize) -> Self {
+        self.config.match_cache_size = size;
+        self
+    }
+
+    /// Make new queries reuse the Qids of removed ones, lowest first, instead
+    /// of always taking new ones. This bounds the memory of percolators with
+    /// many short lived queries, like geofences, without compacting them.
+    /// Defaults to false.
+    ///
+    /// With a [`Percolator`], a removed Qid can then identify a new query,
+    /// so forget the Qids of the queries you remove.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::builder().recycle_qids(true).build();
+    /// let blue = p.add_query("colour".has_value("blue"));
+    /// p.add_query("colour".has_value("green"));
+    /// p.remove_qid(blue);
+    ///
+    /// let red = p.add_query("colour".has_value("red"));
+    /// assert_eq!(red, blue);
+    /// assert_eq!(p.percolate(&[("colour", "blue")].into()).count(), 0);
+    /// assert_eq!(p.percolate(&[("colour", "red")].into()).collect::<Vec<_>>(), vec![red]);
+    /// ```
+    pub fn recycle_qids(mut self, b: bool) -> Self {
+        self.config.recycle_qids = b;
+        self
+    }
+}
+
+/// The operations common to all percolator implementations,
+/// so applications can swap them behind generics or trait objects.
+///
+/// Queries are identified by a user supplied ID, like in
+/// [`PercolatorUid::index_query_uid`].
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+///
+/// fn blue_things(p: &mut dyn Percolate<u64>) -> Vec<u64> {
+///     p.add("colour".has_value("blue"), 1).unwrap();
+///     p.add("colour".has_value("red"), 2).unwrap();
+///     p.percolate(&[("colour", "blue")].into()).copied().collect()
+/// }
+///
+/// assert_eq!(blue_things(&mut PercolatorUid::<u64>::default()), vec![1]);
+/// assert_eq!(blue_things(&mut LinearPercolator::default()), vec![1]);
+/// ```
+pub trait Percolate<T> {
+    /// Adds the query with the given ID, replacing
+    /// any query with the same ID.
+    fn add(&mut self, q: Query, uid: T) -> Result<T, PercolatorError>;
+
+    /// Removes the query with the given ID.
+    /// False if there was no such query.
+    fn remove(&mut self, uid: &T) -> bool;
+
+    /// The query with the given ID, if any.
+    fn get(&self, uid: &T) -> Option<&Query>;
+
+    /// The IDs of the queries matching the document.
+    fn percolate<'a>(&'a self, d: &'a Document) -> Box<dyn Iterator<Item = &'a T> + 'a>;
+
+    /// Statistics about the queries added to this percolator.
+    fn stats(&self) -> &PercolatorStats;
 }
 
 /// A Percolator type, with an API compatible with the previous version.
 pub type Percolator = PercolatorUid<Qid>;
 
 /// A percolator that allows identifying queries
-/// by a stable user supplied ID (must be a Copy + Eq + Hash type)
+/// by a stable user supplied ID (must be an Eq + Hash type)
+///
+/// Like with a `HashMap`, methods looking up an ID take any borrowed
+/// form of it, like a `&str` for `String` IDs.
 ///
 /// This allow removing queries, compacting the percolator,
 /// serialising and deserialising it while keeping the same
 /// user supplied identifiers.
+///
+/// Queries can also carry a payload of type `P`, like the action
+/// to take or the owner of a saved search.
+/// See [`PercolatorUid::index_query_with_payload`]
 #[derive(Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(
     feature = "serde",
     serde(bound(
-        serialize = "T: serde::Serialize + std::cmp::Eq + std::hash::Hash",
-        deserialize = "T: serde::Deserialize<'de> + std::cmp::Eq + std::hash::Hash",
+        serialize = "T: serde::Serialize + std::cmp::Eq + std::hash::Hash, P: serde::Serialize",
+        deserialize = "T: serde::Deserialize<'de> + std::cmp::Eq + std::hash::Hash, P: serde::Deserialize<'de>",
     ))
 )]
-pub struct PercolatorUid<T> {
+pub struct PercolatorUid<T, P = ()> {
     perc: PercolatorCore,
     qid_uid: bimap::BiMap<Qid, T>,
+    // The IDs of the queries of each named pack.
+    #[cfg_attr(feature = "serde", serde(default))]
+    packs: BTreeMap<String, Vec<T>>,
+    // The payloads of the queries that have one.
+    #[cfg_attr(feature = "serde", serde(default))]
+    payloads: BTreeMap<Qid, P>,
+    // The functions to call with the documents the queries match.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    handlers: Handlers<T>,
+    // Where the mapping is written through, if anywhere.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    uid_store: Option<BoxedUidStore<T>>,
+    // Where to look for IDs no query has, when Qids cannot be the IDs.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    next_uid: Qid,
 }
 
 // We cannot derive Default, because we dont
 // want to force T to implement Default.
-impl<T> std::default::Default for PercolatorUid<T>
+impl<T, P> std::default::Default for PercolatorUid<T, P>
 where
     T: std::cmp::Eq + std::hash::Hash,
 {
@@ -110,11 +495,16 @@ where
         Self {
             perc: PercolatorCore::default(),
             qid_uid: bimap::BiMap::<Qid, T>::new(),
+            packs: BTreeMap::new(),
+            payloads: BTreeMap::new(),
+            handlers: Handlers::default(),
+            uid_store: None,
+            next_uid: 0,
         }
     }
 }
 
-impl<T> Display for PercolatorUid<T> {
+impl<T, P> Display for PercolatorUid<T, P> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.perc.fmt(f)
     }
@@ -122,7 +512,7 @@ impl<T> Display for PercolatorUid<T> {
 
 /// When the type used is Qid, just use this
 /// and keep the same interface as the PercolatorCore
-impl PercolatorUid<Qid> {
+impl<P> PercolatorUid<Qid, P> {
     // The unsafe version of `safe_add_query`
     pub fn add_query(&mut self, q: Query) -> Qid {
         self.safe_add_query(q).unwrap()
@@ -141,19 +531,215 @@ impl PercolatorUid<Qid> {
     /// }
     /// ```
     pub fn safe_add_query(&mut self, q: Query) -> Result<Qid, PercolatorError> {
+        self.safe_add_shared_query(q.into())
+    }
+
+    // The unsafe version of `safe_add_queries`
+    pub fn add_queries(&mut self, qs: impl IntoIterator<Item = Query>) -> Vec<Qid> {
+        self.safe_add_queries(qs).unwrap()
+    }
+
+    /// Adds all these queries, and returns their Qids in the same order.
+    /// The queries with new Qids are indexed in one go, which is faster
+    /// than adding them one by one when loading a large corpus.
+    ///
+    /// This is all or nothing: if any of the queries cannot be added,
+    /// for instance because it has too many clauses, none of them are.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::default();
+    /// let qids = p.add_queries((0..1000).map(|i| "id".has_value(i.to_string())));
+    /// assert_eq!(qids.len(), 1000);
+    /// assert_eq!(p.percolate(&[("id", "42")].into()).collect::<Vec<_>>(), vec![qids[42]]);
+    /// ```
+    pub fn safe_add_queries(
+        &mut self,
+        qs: impl IntoIterator<Item = Query>,
+    ) -> Result<Vec<Qid>, PercolatorError> {
+        let qids = self.perc.safe_add_queries(qs.into_iter().collect())?;
+        Ok(qids
+            .into_iter()
+            .map(|qid| self.insert_fresh_uid(qid))
+            .collect())
+    }
+
+    // The unsafe version of `safe_add_shared_query`
+    pub fn add_shared_query(&mut self, q: SharedQuery) -> Qid {
+        self.safe_add_shared_query(q).unwrap()
+    }
+
+    /// Like [`PercolatorUid::safe_add_query`], but the percolator keeps the
+    /// given shared query instead of its own copy, for applications
+    /// keeping their queries around anyway.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// // The application's own copy of the rules.
+    /// let rules: [SharedQuery; 2] = ["colour".has_value("blue").into(), "price".i64_lt(10).into()];
+    ///
+    /// let mut p = Percolator::default();
+    /// let qids = rules
+    ///     .iter()
+    ///     .map(|q| p.add_shared_query(q.clone()))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(p.get_query(&qids[1]), rules[1].as_ref());
+    /// ```
+    pub fn safe_add_shared_query(&mut self, q: SharedQuery) -> Result<Qid, PercolatorError> {
         let qid = self.perc.safe_add_query(q)?;
-        self.qid_uid.insert(qid, qid);
-        Ok(qid)
+        Ok(self.insert_fresh_uid(qid))
+    }
+
+    // Maps the new qid to an ID no other query has, and returns it.
+    // This is the qid itself, unless compacting left another query with this ID.
+    fn insert_fresh_uid(&mut self, qid: Qid) -> Qid {
+        let mut uid = qid;
+        if self.qid_uid.contains_right(&uid) {
+            uid = self.next_uid.max(qid);
+            while self.qid_uid.contains_right(&uid) {
+                uid += 1;
+            }
+            self.next_uid = uid + 1;
+        }
+        self.qid_uid
+            .insert_no_overwrite(qid, uid)
+            .expect("New qid and unused uid");
+        if let Some(store) = self.uid_store.as_mut() {
+            store.insert(qid, &uid);
+        }
+        uid
+    }
+
+    /// The matching Qids given the Document, among the given ones only,
+    /// like the queries of one tenant. The other queries are left out
+    /// before checking any candidate against the document.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use roaring::RoaringBitmap;
+    /// let mut p = Percolator::default();
+    /// let alice = p.add_query("colour".has_value("blue"));
+    /// let bob = p.add_query(!"colour".has_value("red"));
+    ///
+    /// let d = [("colour", "blue")].into();
+    /// let alice_qids = RoaringBitmap::from_iter([alice]);
+    /// assert_eq!(p.percolate_subset(&d, &alice_qids).collect::<Vec<_>>(), vec![alice]);
+    /// assert_eq!(p.percolate(&d).collect::<Vec<_>>(), vec![alice, bob]);
+    /// ```
+    pub fn percolate_subset<'b>(
+        &self,
+        d: &'b Document,
+        qids: &RoaringBitmap,
+    ) -> impl Iterator<Item = Qid> + use<'b, '_, P> {
+        // The IDs are not the Qids after compaction.
+        let qids = qids
+            .iter()
+            .filter_map(|uid| self.qid_uid.get_by_right(&uid).copied())
+            .collect::<RoaringBitmap>();
+        self.perc
+            .percolate_subset(d, &qids)
+            .map(|qid| *self.qid_uid.get_by_left(&qid).expect("Live qid"))
     }
 
-    // Remove the given Qid from this Percolator.
-    // This is just a shortcut to remove_uid where T = Qid
+    /// Removes the query with this ID, as the `add_*` methods returned it.
+    /// This is just a shortcut to remove_uid where T = Qid.
+    ///
+    /// The IDs are the internal Qids until [`PercolatorUid::compact`]
+    /// renumbers the queries. They keep their IDs, so both differ from then on,
+    /// and this still takes the ID.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::default();
+    /// let blue = p.add_query("colour".has_value("blue"));
+    /// let red = p.add_query("colour".has_value("red"));
+    /// p.remove_qid(blue);
+    /// p.compact();
+    ///
+    /// assert!(p.remove_qid(red));
+    /// assert!(p.is_empty());
+    /// ```
     pub fn remove_qid(&mut self, qid: Qid) -> bool {
-        self.remove_uid(qid)
+        self.remove_uid(&qid)
+    }
+
+    /// Adds a named pack of queries, like the rules of one deployment,
+    /// and returns their Qids in the same order.
+    ///
+    /// This is all or nothing: if any of the queries cannot be added, or
+    /// there is already a pack with this name, the percolator is left
+    /// unchanged. See [`PercolatorUid::swap_pack`] to replace a pack.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let mut p = Percolator::builder().max_clauses(2).build();
+    /// let qids = p
+    ///     .load_pack("fruits", vec!["fruit".has_value("apple"), "fruit".has_value("pear")])
+    ///     .unwrap();
+    /// assert_eq!(p.pack("fruits"), Some(qids.as_slice()));
+    ///
+    /// // Too many clauses in the second query: none of them are added.
+    /// let too_big = "a".has_value("1") & "b".has_value("2") & "c".has_value("3");
+    /// assert!(p.load_pack("more", vec!["colour".has_value("blue"), too_big]).is_err());
+    /// assert_eq!(p.pack("more"), None);
+    /// assert_eq!(p.percolate(&[("colour", "blue")].into()).count(), 0);
+    /// ```
+    pub fn load_pack(
+        &mut self,
+        name: &str,
+        queries: Vec<Query>,
+    ) -> Result<Vec<Qid>, PercolatorError> {
+        if self.packs.contains_key(name) {
+            return Err(PercolatorError::DuplicatePack(name.to_string()));
+        }
+        self.perc.check_queries(&queries)?;
+        self.add_pack(name, queries)
+    }
+
+    /// Replaces the queries of a pack with new ones, for instance with a
+    /// new version of its rules, and returns the new Qids in the same order.
+    /// A pack that is not loaded yet is just loaded.
+    ///
+    /// This is all or nothing: if any of the new queries cannot be added,
+    /// the old ones stay in place.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let mut p = Percolator::default();
+    /// p.load_pack("rules", vec!["colour".has_value("blue")]).unwrap();
+    /// let v2 = p.swap_pack("rules", vec!["colour".has_value("red")]).unwrap();
+    ///
+    /// assert_eq!(p.percolate(&[("colour", "blue")].into()).count(), 0);
+    /// assert_eq!(p.percolate(&[("colour", "red")].into()).collect::<Vec<_>>(), v2);
+    /// ```
+    pub fn swap_pack(
+        &mut self,
+        name: &str,
+        queries: Vec<Query>,
+    ) -> Result<Vec<Qid>, PercolatorError> {
+        self.perc.check_queries(&queries)?;
+        self.remove_pack(name);
+        self.add_pack(name, queries)
+    }
+
+    // Once the queries are checked.
+    fn add_pack(&mut self, name: &str, queries: Vec<Query>) -> Result<Vec<Qid>, PercolatorError> {
+        let qids = self.safe_add_queries(queries)?;
+        self.packs.insert(name.to_string(), qids.clone());
+        Ok(qids)
     }
 }
 
-impl<T> PercolatorUid<T>
+impl<T, P> PercolatorUid<T, P>
 where
     T: std::cmp::Eq + std::hash::Hash,
 {
@@ -165,8 +751,8 @@ where
     /// let mut p = PercolatorUid::<u64>::builder().build();
     ///
     /// ```
-    pub fn builder() -> PercBuilder<T> {
-        PercBuilder::<T>::default()
+    pub fn builder() -> PercBuilder<T, P> {
+        PercBuilder::<T, P>::default()
     }
 
     /// Returns an automatically optimised and compacted Percolator
@@ -187,7 +773,7 @@ where
     ///
     /// let mut p = Percolator::default();
     /// p.index_query_uid("field".has_value("value"), 1);
-    /// p.remove_uid(1);
+    /// p.remove_uid(&1);
     ///
     /// assert!( p.holes_ratio() == 1.0 ); // As many removals as added.
     ///
@@ -199,19 +785,16 @@ where
     pub fn optimized(&self) -> Self
     where
         T: Clone,
+        P: Clone,
     {
         let mut new_self = Self::builder()
+            .with_config(self.perc.config.clone())
             .n_clause_matchers(self.perc.stats().recommended_cmcount())
             .prefix_sizes(self.perc.stats().recommended_prefix_sizes())
             .build();
 
         // And reindex all queries, effectively doing compaction.
-        // Index all queries
-        for (uid, q) in self.queries() {
-            new_self
-                .index_query_uid(q.clone(), uid)
-                .expect("Can index same query");
-        }
+        self.reindex_into(&mut new_self);
         new_self
     }
 
@@ -224,7 +807,7 @@ where
     /// use mokaccino::prelude::*;
     /// let mut p = Percolator::default();
     /// p.index_query_uid("field".has_value("value"), 1);
-    /// p.remove_uid(1);
+    /// p.remove_uid(&1);
     ///
     /// assert!( p.holes_ratio() == 1.0 ); // As many removals as added.
     ///
@@ -235,20 +818,71 @@ where
     pub fn compacted(&self) -> Self
     where
         T: Clone,
+        P: Clone,
     {
         let mut new_self = Self::builder()
             .with_config(self.perc.config.clone())
             .build();
 
         // Index all queries
-        for (uid, q) in self.queries() {
-            new_self
-                .index_query_uid(q.clone(), uid)
-                .expect("Can index same query");
-        }
+        self.reindex_into(&mut new_self);
         new_self
     }
 
+    /// Rebuilds this percolator in place without the queries removed from it,
+    /// which otherwise keep taking memory, and returns how the Qids of the
+    /// others changed, from the old ones to the new ones, like in the uid store.
+    ///
+    /// The IDs of the queries stay the same, including the ones a [`Percolator`]
+    /// gave, which then differ from their Qids. New queries get IDs no other
+    /// query has. Unlike [`PercolatorUid::compacted`], this keeps the uid store
+    /// and the recent percolations.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = PercolatorUid::<u64>::default();
+    /// p.index_query_uid("colour".has_value("blue"), 1).unwrap();
+    /// p.index_query_uid("colour".has_value("red"), 2).unwrap();
+    /// p.remove_uid(&1);
+    ///
+    /// let remapping = p.compact();
+    /// assert_eq!(remapping.into_iter().collect::<Vec<_>>(), vec![(1, 0)]);
+    /// assert_eq!(p.holes_ratio(), 0.0); // No holes left.
+    /// assert_eq!(p.percolate(&[("colour", "red")].into()).collect::<Vec<_>>(), vec![2]);
+    /// ```
+    pub fn compact(&mut self) -> BTreeMap<Qid, Qid> {
+        let remapping = self.perc.compact();
+        self.qid_uid = std::mem::take(&mut self.qid_uid)
+            .into_iter()
+            .map(|(qid, uid)| (remapping[&qid], uid))
+            .collect();
+        self.payloads = std::mem::take(&mut self.payloads)
+            .into_iter()
+            .map(|(qid, payload)| (remapping[&qid], payload))
+            .collect();
+        self.handlers.remap(&remapping);
+        if let Some(store) = self.uid_store.take() {
+            self.set_uid_store(store);
+        }
+        remapping
+    }
+
+    /// A read only snapshot of this percolator, to share between reader threads.
+    /// Like [`PercolatorUid::compacted`], this indexes the same queries again,
+    /// and this percolator can change without the snapshot seeing it.
+    /// Use [`FrozenPercolator::from`] to freeze a percolator without copying it.
+    ///
+    /// See [`FrozenPercolator`]
+    #[cfg(feature = "send")]
+    pub fn freeze(&self) -> FrozenPercolator<T, P>
+    where
+        T: Clone,
+        P: Clone,
+    {
+        self.compacted().into()
+    }
+
     /// A ratio of the number of removals/number of additions.
     ///
     /// Will be `is_nan()` when no addition have ever been made
@@ -273,29 +907,253 @@ where
     ///    Ok(uid) => println!("Added query with id {}", uid),
     ///   Err(e) => println!("Failed to add query: {:?}", e),
     /// }
-    /// let q = p.get_query(1);
-    /// assert_eq!(q.to_string(), "(AND (OR field=value))");
+    /// let q = p.get_query(&1);
+    /// assert_eq!(q.to_string(), "field:value");
     ///
     /// // You can overwrite the query with the same UID:
     /// p.index_query_uid("other".has_value("query"), 1 as u64);
-    /// let q = p.get_query(1);
-    /// assert_eq!(q.to_string(), "(AND (OR other=query))");
+    /// let q = p.get_query(&1);
+    /// assert_eq!(q.to_string(), "other:query");
     ///
     /// ```
     pub fn index_query_uid(&mut self, q: Query, uid: T) -> Result<T, PercolatorError>
     where
         T: Clone,
     {
-        let qid = self.perc.safe_add_query(q)?;
-        if let bimap::Overwritten::Right(old_qid, _) = self.qid_uid.insert(qid, uid.clone()) {
-            // Remove old QID, as this was an overwrite.
-            self.perc.remove_qid(old_qid);
-        }
-        Ok(uid)
+        self.index_query_uid_requiring(q, uid, &[])
     }
 
-    /// Removes the given User provided ID from
-    /// this percolator. True if it was effectively removed.
+    /// Like [`PercolatorUid::index_query_uid`], but documents lacking any of the
+    /// `required_fields` will never match this query, whatever the query says.
+    ///
+    /// Such documents skip the query before it is even a candidate,
+    /// which saves checking it against sparse documents.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = PercolatorUid::<u64>::default();
+    /// p.index_query_uid_requiring(!"colour".has_value("blue"), 1, &["colour"])
+    ///     .unwrap();
+    ///
+    /// assert_eq!(p.percolate(&[("colour", "red")].into()).collect::<Vec<_>>(), vec![1]);
+    /// // Not blue, but it has no colour at all.
+    /// assert_eq!(p.percolate(&[("taste", "sweet")].into()).count(), 0);
+    /// assert_eq!(p.required_fields(&1), vec!["colour"]);
+    /// ```
+    pub fn index_query_uid_requiring(
+        &mut self,
+        q: Query,
+        uid: T,
+        required_fields: &[&str],
+    ) -> Result<T, PercolatorError>
+    where
+        T: Clone,
+    {
+        self.index_shared_query_uid_requiring(q.into(), uid, required_fields)
+    }
+
+    /// Like [`PercolatorUid::index_query_uid`], but the query is skipped
+    /// at percolation from the given time on, and removed by
+    /// [`PercolatorUid::expire_before`]. Good for saved searches or
+    /// geofences with a natural lifetime.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let mut p = PercolatorUid::<u64>::default();
+    /// let now = SystemTime::now();
+    /// p.index_query_uid_expiring("colour".has_value("blue"), 1, now - Duration::from_secs(1))
+    ///     .unwrap();
+    /// p.index_query_uid_expiring("colour".has_value("blue"), 2, now + Duration::from_secs(3600))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(p.percolate(&[("colour", "blue")].into()).collect::<Vec<_>>(), vec![2]);
+    /// assert_eq!(p.expire_before(now), vec![1]);
+    /// assert!(p.safe_get_query(&1).is_none());
+    /// ```
+    pub fn index_query_uid_expiring(
+        &mut self,
+        q: Query,
+        uid: T,
+        expires_at: SystemTime,
+    ) -> Result<T, PercolatorError>
+    where
+        T: Clone,
+    {
+        let uid = self.index_query_uid(q, uid)?;
+        self.set_expiry(&uid, expires_at);
+        Ok(uid)
+    }
+
+    /// Makes the query with this ID expire at the given time, like with
+    /// [`PercolatorUid::index_query_uid_expiring`], replacing any previous expiry.
+    /// False if there is no such query.
+    pub fn set_expiry<Q>(&mut self, uid: &Q, expires_at: SystemTime) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        match self.qid_uid.get_by_right(uid) {
+            Some(qid) => {
+                self.perc.set_expiry(*qid, expires_at);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// When the query with this ID expires, if it does.
+    pub fn expiry<Q>(&self, uid: &Q) -> Option<SystemTime>
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.perc.expiry(*self.qid_uid.get_by_right(uid)?)
+    }
+
+    /// Removes the queries expiring strictly before the given time,
+    /// and returns their IDs. Expired queries are skipped at percolation
+    /// anyway: this reclaims them.
+    /// See [`PercolatorUid::index_query_uid_expiring`]
+    pub fn expire_before(&mut self, at: SystemTime) -> Vec<T> {
+        self.perc
+            .expiring_before(at)
+            .iter()
+            .filter_map(|qid| self.remove_by_qid(qid))
+            .collect()
+    }
+
+    /// Like [`PercolatorUid::index_query_uid`], with a payload for the query,
+    /// like the action to take or the owner of a saved search.
+    /// Overwriting the query with the same UID drops its payload.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = PercolatorUid::<u64, &str>::default();
+    /// p.index_query_with_payload("colour".has_value("blue"), 1, "alice")
+    ///     .unwrap();
+    /// p.index_query_uid("colour".has_value("red"), 2).unwrap();
+    ///
+    /// assert_eq!(p.payload(&1), Some(&"alice"));
+    /// assert_eq!(p.payload(&2), None);
+    /// let d = [("colour", "blue"), ("colour", "red")].into();
+    /// assert_eq!(p.percolate_with_payloads(&d).collect::<Vec<_>>(), vec![(&1, &"alice")]);
+    /// ```
+    pub fn index_query_with_payload(
+        &mut self,
+        q: Query,
+        uid: T,
+        payload: P,
+    ) -> Result<T, PercolatorError>
+    where
+        T: Clone,
+    {
+        let uid = self.index_query_uid(q, uid)?;
+        let qid = *self.qid_uid.get_by_right(&uid).expect("Just indexed");
+        self.payloads.insert(qid, payload);
+        Ok(uid)
+    }
+
+    /// Like [`PercolatorUid::index_query_uid`], but the percolator keeps the
+    /// given shared query instead of its own copy.
+    /// See [`PercolatorUid::safe_add_shared_query`]
+    pub fn index_shared_query_uid(&mut self, q: SharedQuery, uid: T) -> Result<T, PercolatorError>
+    where
+        T: Clone,
+    {
+        self.index_shared_query_uid_requiring(q, uid, &[])
+    }
+
+    fn index_shared_query_uid_requiring(
+        &mut self,
+        q: SharedQuery,
+        uid: T,
+        required_fields: &[&str],
+    ) -> Result<T, PercolatorError>
+    where
+        T: Clone,
+    {
+        let qid = self.perc.safe_add_query_requiring(q, required_fields)?;
+        if let bimap::Overwritten::Right(old_qid, _) = self.qid_uid.insert(qid, uid.clone()) {
+            // Remove old QID, as this was an overwrite.
+            self.perc.remove_qid(old_qid);
+            self.payloads.remove(&old_qid);
+            self.handlers.move_to(old_qid, qid);
+            if let Some(store) = self.uid_store.as_mut() {
+                store.remove(old_qid);
+            }
+        }
+        if let Some(store) = self.uid_store.as_mut() {
+            store.insert(qid, &uid);
+        }
+        Ok(uid)
+    }
+
+    /// Replaces the query with this ID by the new one, keeping its ID and
+    /// everything else about it: its required fields, expiry, payload, pack
+    /// and handlers. False if there is no such query, and nothing is added.
+    ///
+    /// This is all or nothing: if the new query cannot be added,
+    /// the old one stays in place.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = PercolatorUid::<String, &str>::default();
+    /// p.index_query_with_payload("colour".has_value("blue"), "rule".into(), "alice")
+    ///     .unwrap();
+    ///
+    /// assert!(p.replace_query("rule", "colour".has_value("red")).unwrap());
+    /// assert!(!p.replace_query("other", "colour".has_value("red")).unwrap());
+    ///
+    /// assert_eq!(p.get_query("rule").to_string(), "colour:red");
+    /// assert_eq!(p.payload("rule"), Some(&"alice"));
+    /// assert!(p.safe_get_query("other").is_none());
+    /// ```
+    pub fn replace_query<Q>(&mut self, uid: &Q, q: Query) -> Result<bool, PercolatorError>
+    where
+        T: Borrow<Q> + Clone,
+        Q: Eq + Hash + ?Sized,
+    {
+        let Some((qid, uid)) = self
+            .qid_uid
+            .get_by_right(uid)
+            .and_then(|qid| self.qid_uid.get_by_left(qid).map(|uid| (*qid, uid.clone())))
+        else {
+            return Ok(false);
+        };
+        self.perc.check_queries(std::slice::from_ref(&q))?;
+
+        let required = self.perc.required_fields(qid);
+        let required = required.iter().map(|f| f.as_ref()).collect::<Vec<_>>();
+        let expiry = self.perc.expiry(qid);
+        let payload = self.payloads.remove(&qid);
+        match self.index_shared_query_uid_requiring(q.into(), uid, &required) {
+            Ok(uid) => {
+                let new_qid = *self.qid_uid.get_by_right::<T>(&uid).expect("Just indexed");
+                if let Some(payload) = payload {
+                    self.payloads.insert(new_qid, payload);
+                }
+                if let Some(at) = expiry {
+                    self.perc.set_expiry(new_qid, at);
+                }
+                Ok(true)
+            }
+            Err(e) => {
+                if let Some(payload) = payload {
+                    self.payloads.insert(qid, payload);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Removes the given User provided ID from
+    /// this percolator. True if it was effectively removed.
     /// false if it was absent (already removed, or simply not present).
     ///
     /// Example:
@@ -307,58 +1165,852 @@ where
     ///   Err(e) => println!("Failed to add query: {:?}", e),
     /// }
     ///
-    /// assert!( p.remove_uid(1) ); // was removed.
-    /// assert!( ! p.remove_uid(1) ); // already removed.
+    /// assert!( p.remove_uid(&1) ); // was removed.
+    /// assert!( ! p.remove_uid(&1) ); // already removed.
     ///
     /// ```
-    pub fn remove_uid(&mut self, uid: T) -> bool {
-        if let Some((qid, _)) = self.qid_uid.remove_by_right(&uid) {
-            self.perc.remove_qid(qid)
+    pub fn remove_uid<Q>(&mut self, uid: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if let Some(&qid) = self.qid_uid.get_by_right(uid) {
+            self.remove_by_qid(qid);
+            true
         } else {
             false
         }
     }
 
-    pub fn get_query(&self, uid: T) -> &Query {
+    /// Removes the queries for which the predicate is true, given
+    /// their ID and query, and returns their IDs in the order of
+    /// their Qids. This is the order the queries were added, unless
+    /// the percolator recycles Qids or was compacted.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = PercolatorUid::<u64>::default();
+    /// p.index_query_uid("colour".has_value("blue"), 1).unwrap();
+    /// p.index_query_uid("old_colour".has_value("blue"), 2).unwrap();
+    /// p.index_query_uid("old_colour".has_value("red"), 3).unwrap();
+    ///
+    /// // Drop all queries on a deprecated field.
+    /// let removed = p.remove_where(|_, q| q.to_string().contains("old_colour:"));
+    /// assert_eq!(removed, vec![2, 3]);
+    /// assert!(p.safe_get_query(&1).is_some());
+    /// ```
+    pub fn remove_where(&mut self, mut predicate: impl FnMut(&T, &Query) -> bool) -> Vec<T> {
+        let qids = self
+            .perc
+            .queries()
+            .filter(|(qid, q)| {
+                self.qid_uid
+                    .get_by_left(qid)
+                    .is_some_and(|uid| predicate(uid, q))
+            })
+            .map(|(qid, _)| qid)
+            .collect::<Vec<_>>();
+        qids.into_iter()
+            .filter_map(|qid| self.remove_by_qid(qid))
+            .collect()
+    }
+
+    /// Removes all the queries equal to this one, and returns their IDs
+    /// in the order of their Qids, like [`PercolatorUid::remove_where`]. Queries are equal when they
+    /// have the same clauses, whichever way they were built.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = PercolatorUid::<u64>::default();
+    /// p.index_query_uid("colour".has_value("blue") & "taste".has_value("sweet"), 1).unwrap();
+    /// p.index_query_uid("colour".has_value("red"), 2).unwrap();
+    ///
+    /// let q = "taste".has_value("sweet") & "colour".has_value("blue");
+    /// assert_eq!(p.remove_matching(&q), vec![1]);
+    /// assert!(p.remove_matching(&q).is_empty());
+    /// ```
+    pub fn remove_matching(&mut self, q: &Query) -> Vec<T> {
+        // Stored queries may have had their terms normalised.
+        let q = self.perc.normalized(OurRc::new(q.clone()));
+        self.remove_where(|_, stored| stored == q.as_ref())
+    }
+
+    // Forgets everything about this qid, and returns its user ID.
+    fn remove_by_qid(&mut self, qid: Qid) -> Option<T> {
+        let (_, uid) = self.qid_uid.remove_by_left(&qid)?;
+        if let Some(store) = self.uid_store.as_mut() {
+            store.remove(qid);
+        }
+        self.payloads.remove(&qid);
+        self.handlers.remove(qid);
+        self.perc.remove_qid(qid);
+        Some(uid)
+    }
+
+    /// Writes the mapping between Qids and user IDs through to this
+    /// store from now on, like a persistent one. The store is first
+    /// cleared and given the current mapping, so it is complete.
+    ///
+    /// Compacting or optimising a percolator gives its queries new Qids,
+    /// and the new percolator no store: move the store to it with
+    /// [`PercolatorUid::take_uid_store`].
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use mokaccino::models::percolator::MemoryUidStore;
+    ///
+    /// let mut p = PercolatorUid::<String>::default();
+    /// p.index_query_uid("colour".has_value("blue"), "blue".to_string()).unwrap();
+    /// p.set_uid_store(Box::new(MemoryUidStore::default()));
+    /// p.index_query_uid("colour".has_value("red"), "red".to_string()).unwrap();
+    /// p.remove_uid("blue");
+    ///
+    /// let mappings = p.uid_store().unwrap().mappings();
+    /// assert_eq!(mappings, vec![(1, "red".to_string())]);
+    ///
+    /// let store = p.take_uid_store().unwrap();
+    /// let mut p = p.compacted();
+    /// p.set_uid_store(store);
+    /// assert_eq!(p.uid_store().unwrap().mappings(), vec![(0, "red".to_string())]);
+    /// ```
+    pub fn set_uid_store(&mut self, mut store: BoxedUidStore<T>) {
+        store.clear();
+        for (qid, uid) in self.qid_uid.iter() {
+            store.insert(*qid, uid);
+        }
+        self.uid_store = Some(store);
+    }
+
+    /// The store the mapping is written through to, if any.
+    /// See [`PercolatorUid::set_uid_store`]
+    pub fn uid_store(&self) -> Option<&BoxedUidStore<T>> {
+        self.uid_store.as_ref()
+    }
+
+    /// Stops writing the mapping through to the store, and returns it.
+    pub fn take_uid_store(&mut self) -> Option<BoxedUidStore<T>> {
+        self.uid_store.take()
+    }
+
+    /// The IDs and queries of this percolator, in the order of their Qids.
+    /// This is the order they were added, unless the percolator recycles Qids
+    /// (see [`PercBuilder::recycle_qids`]) or was compacted.
+    /// Removed queries are not there.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = PercolatorUid::<u64>::default();
+    /// p.index_query_uid("colour".has_value("blue"), 1).unwrap();
+    /// p.index_query_uid("colour".has_value("red"), 2).unwrap();
+    /// p.remove_uid(&1);
+    ///
+    /// assert_eq!(p.len(), 1);
+    /// assert_eq!(
+    ///     p.iter().collect::<Vec<_>>(),
+    ///     vec![(&2, &"colour".has_value("red"))]
+    /// );
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (&T, &Query)> {
+        self.perc
+            .queries()
+            .filter_map(|(qid, q)| Some((self.qid_uid.get_by_left(&qid)?, q)))
+    }
+
+    /// The number of queries in this percolator.
+    pub fn len(&self) -> usize {
+        self.qid_uid.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.qid_uid.is_empty()
+    }
+
+    /// The payload of the query with this ID, if it has one.
+    /// See [`PercolatorUid::index_query_with_payload`]
+    pub fn payload<Q>(&self, uid: &Q) -> Option<&P>
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.payloads.get(self.qid_uid.get_by_right(uid)?)
+    }
+
+    pub fn get_query<Q>(&self, uid: &Q) -> &Query
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
         self.safe_get_query(uid).unwrap()
     }
 
-    fn queries(&self) -> impl Iterator<Item = (T, &Query)>
+    /// The IDs of the queries of this pack, in the order they were loaded.
+    /// See [`PercolatorUid::load_pack`]
+    pub fn pack(&self, name: &str) -> Option<&[T]> {
+        self.packs.get(name).map(|uids| uids.as_slice())
+    }
+
+    /// The names of the loaded packs, in lexicographic order.
+    pub fn pack_names(&self) -> impl Iterator<Item = &str> {
+        self.packs.keys().map(|n| n.as_str())
+    }
+
+    /// Removes a pack and all its queries from this percolator.
+    /// True if there was such a pack.
+    pub fn remove_pack(&mut self, name: &str) -> bool {
+        match self.packs.remove(name) {
+            Some(uids) => {
+                uids.iter().for_each(|uid| {
+                    self.remove_uid(uid);
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Indexes all the live queries in the other percolator,
+    // with the same IDs and required fields, and retains the same documents.
+    fn reindex_into(&self, other: &mut Self)
     where
         T: Clone,
+        P: Clone,
+    {
+        other.perc.retain_from(&self.perc);
+        other.packs = self.packs.clone();
+        // In Qid order, so the queries keep their relative order.
+        for (qid, _) in self.perc.queries() {
+            let Some(uid) = self.qid_uid.get_by_left(&qid) else {
+                continue;
+            };
+            let required = self.perc.required_fields(qid);
+            let required = required.iter().map(|f| f.as_ref()).collect::<Vec<_>>();
+            // Sharing the same queries.
+            let q = self.perc.safe_get_shared_query(qid).expect("Live query");
+            other
+                .index_shared_query_uid_requiring(q.clone(), uid.clone(), &required)
+                .expect("Can index same query");
+            let new_qid = *other.qid_uid.get_by_right(uid).expect("Just indexed");
+            if let Some(payload) = self.payloads.get(&qid) {
+                other.payloads.insert(new_qid, payload.clone());
+            }
+            other.handlers.copy_from(&self.handlers, qid, new_qid);
+            if let Some(at) = self.perc.expiry(qid) {
+                other.perc.set_expiry(new_qid, at);
+            }
+        }
+    }
+
+    /// The fields the query with this ID requires, in lexicographic order.
+    /// Empty if there is no such query.
+    /// See [`PercolatorUid::index_query_uid_requiring`]
+    pub fn required_fields<Q>(&self, uid: &Q) -> Vec<String>
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
     {
         self.qid_uid
-            .iter()
-            .map(|(_, uid)| (uid.clone(), self.get_query(uid.clone())))
+            .get_by_right(uid)
+            .map(|qid| {
+                self.perc
+                    .required_fields(*qid)
+                    .iter()
+                    .map(|f| f.to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The ids of the preheaters the query with this ID added,
+    /// in lexicographic order. Empty if there is no such query.
+    ///
+    /// Preheaters expand percolated documents with synthetic values, for
+    /// queries like prefixes, comparisons or geographic ones.
+    /// See [`PercolatorUid::preheaters`]
+    pub fn preheaters_for<Q>(&self, uid: &Q) -> Vec<String>
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.qid_uid
+            .get_by_right(uid)
+            .map(|qid| {
+                self.perc
+                    .preheaters_for(*qid)
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The ids of all the preheaters percolation applies, with the number
+    /// of queries that added them. Removing the last query that added a
+    /// preheater removes it.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::default();
+    /// let q1 = p.add_query("name".has_prefix("bu") & "price".i64_lt(10));
+    /// let q2 = p.add_query("name".has_prefix("bu"));
+    /// p.add_query("colour".has_value("blue"));
+    ///
+    /// let ids = p.preheaters_for(&q1);
+    /// assert_eq!(ids.len(), 2);
+    /// assert_eq!(p.preheaters_for(&q2).len(), 1);
+    ///
+    /// assert_eq!(p.preheaters()[&p.preheaters_for(&q2)[0]], 2);
+    /// p.remove_qid(q1);
+    /// assert_eq!(p.preheaters().values().copied().collect::<Vec<_>>(), vec![1]);
+    /// ```
+    pub fn preheaters(&self) -> BTreeMap<String, usize> {
+        self.perc
+            .preheaters()
+            .into_iter()
+            .map(|(id, n)| (id.to_string(), n))
+            .collect()
+    }
+
+    /// The indexed vocabulary: all the (field, value)s queries are
+    /// indexed with, with the number of queries indexed with each,
+    /// in order. To export to dashboards, and find fields with more
+    /// values than expected.
+    ///
+    /// This includes the synthetic fields of the preheaters, which
+    /// start with `__`, like `__EXISTS__colour`.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::default();
+    /// p.add_query("colour".has_value("blue") | "colour".has_value("red"));
+    /// p.add_query("colour".has_value("blue"));
+    ///
+    /// let terms = p.terms_iter().collect::<Vec<_>>();
+    /// assert_eq!(
+    ///     terms,
+    ///     vec![
+    ///         ("colour".to_string(), "blue".to_string(), 2),
+    ///         ("colour".to_string(), "red".to_string(), 1),
+    ///     ]
+    /// );
+    /// ```
+    pub fn terms_iter(&self) -> impl Iterator<Item = (String, String, usize)> {
+        self.perc
+            .terms()
+            .into_iter()
+            .map(|((field, value), n)| (field.to_string(), value.to_string(), n))
     }
 
-    pub fn safe_get_query(&self, uid: T) -> Option<&Query> {
-        let qid = self.qid_uid.get_by_right(&uid)?;
+    pub fn safe_get_query<Q>(&self, uid: &Q) -> Option<&Query>
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let qid = self.qid_uid.get_by_right(uid)?;
         self.perc.safe_get_query(*qid)
     }
 
     ///
     /// An iterator of the matching ref of query IDs given the Document.
     ///
-    pub fn percolate_ref<'b>(&self, d: &'b Document) -> impl Iterator<Item = &T> + use<'b, '_, T> {
+    pub fn percolate_ref<'b>(
+        &self,
+        d: &'b Document,
+    ) -> impl Iterator<Item = &T> + use<'b, '_, T, P> {
         self.perc
             .percolate(d)
             .filter_map(|qid| self.qid_uid.get_by_left(&qid))
     }
 
+    /// The matching queries user provided IDs given the Document, among
+    /// the given ones only. Unknown IDs are ignored.
+    /// See [`PercolatorUid::percolate_subset`]
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = PercolatorUid::<String>::default();
+    /// p.index_query_uid("colour".has_value("blue"), "alice/blue".into()).unwrap();
+    /// p.index_query_uid("colour".has_prefix("bl"), "bob/bl".into()).unwrap();
+    ///
+    /// let d = [("colour", "blue")].into();
+    /// let matches = p.percolate_subset_uids(&d, ["bob/bl", "carol/red"]);
+    /// assert_eq!(matches, vec!["bob/bl"]);
+    /// ```
+    pub fn percolate_subset_uids<'a, Q>(
+        &self,
+        d: &Document,
+        uids: impl IntoIterator<Item = &'a Q>,
+    ) -> Vec<&T>
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized + 'a,
+    {
+        let qids = uids
+            .into_iter()
+            .filter_map(|uid| self.qid_uid.get_by_right(uid).copied())
+            .collect::<RoaringBitmap>();
+        self.perc
+            .percolate_subset(d, &qids)
+            .filter_map(|qid| self.qid_uid.get_by_left(&qid))
+            .collect()
+    }
+
+    /// Calls this handler with the user provided ID and each document
+    /// the query with this ID matches in [`PercolatorUid::percolate_dispatch`].
+    /// A query can have several handlers, called in the order they were added.
+    /// False if there is no such query.
+    ///
+    /// The handlers stay with the ID when its query is overwritten,
+    /// and go away with it when it is removed.
+    /// They are not serialised.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let mut p = PercolatorUid::<String>::default();
+    /// p.index_query_uid("colour".has_value("blue"), "blue".into()).unwrap();
+    /// p.index_query_uid("colour".has_value("red"), "red".into()).unwrap();
+    ///
+    /// let inbox = Arc::new(Mutex::new(vec![]));
+    /// let blue_inbox = Arc::clone(&inbox);
+    /// assert!(p.on_match("blue", move |uid: &String, d: &Document| {
+    ///     blue_inbox.lock().unwrap().push((uid.clone(), d.values("colour")));
+    /// }));
+    ///
+    /// assert_eq!(p.percolate_dispatch(&[("colour", "blue"), ("colour", "red")].into()), 1);
+    /// assert_eq!(inbox.lock().unwrap()[0].0, "blue");
+    /// ```
+    pub fn on_match<Q>(&mut self, uid: &Q, handler: impl MatchHandler<T> + 'static) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        match self.qid_uid.get_by_right(uid) {
+            Some(qid) => {
+                self.handlers.add(*qid, OurRc::new(handler));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Percolates the Document, and calls the handlers of the matching queries.
+    /// Returns the number of matching queries with handlers.
+    /// See [`PercolatorUid::on_match`]
+    pub fn percolate_dispatch(&self, d: &Document) -> usize {
+        self.perc
+            .percolate(d)
+            .filter(|qid| {
+                self.qid_uid
+                    .get_by_left(qid)
+                    .is_some_and(|uid| self.handlers.dispatch(*qid, uid, d))
+            })
+            .count()
+    }
+
+    /// True if any query matches the Document. Percolation stops at the first match,
+    /// for gating or routing documents.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::default();
+    /// p.add_query("colour".has_value("blue"));
+    ///
+    /// assert!(p.any_match(&[("colour", "blue")].into()));
+    /// assert!(!p.any_match(&[("colour", "red")].into()));
+    /// ```
+    pub fn any_match(&self, d: &Document) -> bool {
+        self.percolate_ref(d).next().is_some()
+    }
+
+    /// The matching queries user provided IDs given the Document, with
+    /// their payloads. The matching queries without payloads are left out.
+    /// See [`PercolatorUid::index_query_with_payload`]
+    pub fn percolate_with_payloads<'b>(
+        &self,
+        d: &'b Document,
+    ) -> impl Iterator<Item = (&T, &P)> + use<'b, '_, T, P> {
+        self.perc.percolate(d).filter_map(|qid| {
+            let uid = self.qid_uid.get_by_left(&qid)?;
+            Some((uid, self.payloads.get(&qid)?))
+        })
+    }
+
+    /// The matching queries user provided IDs given the Document,
+    /// with their [`Query::score`], best first.
+    /// Queries with the same score stay in percolation order.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::default();
+    /// let q1 = p.add_query("colour:blue OR taste:sweet".parse().unwrap());
+    /// let q2 = p.add_query("colour:blue^2 OR taste:sweet".parse().unwrap());
+    /// p.add_query("colour:green".parse().unwrap());
+    ///
+    /// assert_eq!(
+    ///     p.percolate_scored(&[("colour", "blue")].into()),
+    ///     vec![(q2, 2.0), (q1, 1.0)]
+    /// );
+    /// ```
+    pub fn percolate_scored(&self, d: &Document) -> Vec<(T, f32)>
+    where
+        T: Clone,
+    {
+        // Scores see the same values as the matching.
+        let d = self.perc.int_view(d);
+        let mut scored = self
+            .perc
+            .percolate(d.as_ref())
+            .filter_map(|qid| {
+                let uid = self.qid_uid.get_by_left(&qid)?;
+                let score = self.perc.safe_get_query(qid)?.score(&d)?;
+                Some((uid.clone(), score))
+            })
+            .collect::<Vec<_>>();
+        scored.sort_by(|(_, s1), (_, s2)| s2.total_cmp(s1));
+        scored
+    }
+
+    /// The matching queries user provided IDs given the Document, in
+    /// percolation order, with the (field, value)s of the document
+    /// satisfying their positive literals. See [`Query::highlights`]
+    ///
+    /// Use it to show why a document triggered a saved search.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::default();
+    /// let qid = p.add_query("colour:blue AND name:bu*".parse().unwrap());
+    ///
+    /// let d = [("colour", "blue"), ("name", "burger"), ("name", "salad")].into();
+    /// assert_eq!(
+    ///     p.percolate_highlighted(&d),
+    ///     vec![(
+    ///         qid,
+    ///         vec![
+    ///             ("colour".to_string(), "blue".to_string()),
+    ///             ("name".to_string(), "burger".to_string())
+    ///         ]
+    ///     )]
+    /// );
+    /// ```
+    pub fn percolate_highlighted(&self, d: &Document) -> Vec<(T, Vec<(String, String)>)>
+    where
+        T: Clone,
+    {
+        // Highlights see the same values as the matching.
+        let d = self.perc.int_view(d);
+        self.perc
+            .percolate(d.as_ref())
+            .filter_map(|qid| {
+                let uid = self.qid_uid.get_by_left(&qid)?;
+                let highlights = self.perc.safe_get_query(qid)?.highlights(&d);
+                Some((uid.clone(), highlights))
+            })
+            .collect()
+    }
+
     pub fn stats(&self) -> &PercolatorStats {
         self.perc.stats()
     }
+
+    // See crate::bench
+    pub(crate) fn index_heap_bytes(&self) -> usize {
+        self.perc.index_heap_bytes()
+    }
+
+    /// A report about the live queries of this percolator, like the fields
+    /// and operators they use, for capacity reviews and tuning.
+    /// With the `serde` feature, it can be turned into JSON.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::default();
+    /// p.add_query("colour:blue AND NOT taste:bitter".parse().unwrap());
+    /// p.add_query("colour:bl* OR colour:green".parse().unwrap());
+    ///
+    /// let report = p.analyze_corpus();
+    /// assert_eq!(report.n_queries(), 2);
+    /// assert_eq!(report.fields()["colour"].n_queries(), 2);
+    /// assert_eq!(report.operators()["prefix"], 1);
+    /// assert_eq!(report.negation_ratio(), 0.25);
+    /// ```
+    pub fn analyze_corpus(&self) -> CorpusReport {
+        self.perc.analyze_corpus()
+    }
+
+    /// Roughly how many bytes this percolator takes on the heap, by part:
+    /// stored queries, clause matcher indexes, other bitmaps and preheaters.
+    /// See [`MemoryReport`]
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::default();
+    /// let empty = p.memory_usage();
+    /// for i in 0..100 {
+    ///     p.add_query("url".has_prefix(format!("https://example.com/{}", i)));
+    /// }
+    ///
+    /// let report = p.memory_usage();
+    /// assert!(report.queries() > empty.queries() + 100 * "https://example.com/".len());
+    /// assert!(report.indexes() > empty.indexes());
+    /// assert!(report.preheaters() > empty.preheaters());
+    /// assert!(report.total() > report.queries() + report.indexes());
+    /// ```
+    pub fn memory_usage(&self) -> MemoryReport {
+        self.perc.memory_usage()
+    }
+
+    /// Why the query with this ID matches the document or not, clause by
+    /// clause: which literals match which values of the document, and whether
+    /// the clause matcher indexes decide alone or the query is checked
+    /// against the document (the must_filter fallback).
+    /// None if there is no such query. Nothing is recorded, unlike percolating.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use mokaccino::models::percolator_core::MatchSource;
+    /// let mut p = Percolator::default();
+    /// let qid = p.add_query("colour:blue AND NOT taste:bitter".parse().unwrap());
+    ///
+    /// let e = p.explain(&qid, &[("colour", "blue"), ("taste", "bitter")].into()).unwrap();
+    /// assert!(!e.is_match());
+    /// assert_eq!(e.source(), MatchSource::MustFilter);
+    /// assert_eq!(e.clauses()[0].literals()[0].values(), ["blue"]);
+    /// assert!(!e.clauses()[1].is_satisfied());
+    /// ```
+    pub fn explain<Q>(&self, uid: &Q, d: &Document) -> Option<Explanation>
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.perc.explain(*self.qid_uid.get_by_right(uid)?, d)
+    }
+
+    /// The recently percolated documents this query matches (oldest first),
+    /// when documents are retained with [`PercBuilder::retain_size`].
+    /// Empty otherwise.
+    ///
+    /// Use it to find out what a new query would have matched,
+    /// for instance to alert about recent documents when adding a rule.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::builder().retain_size(100).build();
+    /// let _ = p.percolate(&[("colour", "blue")].into());
+    /// let _ = p.percolate(&[("colour", "red")].into());
+    ///
+    /// let q: Query = "colour:blue".parse().unwrap();
+    /// assert_eq!(p.search(&q), vec![[("colour", "blue")].into()]);
+    /// ```
+    pub fn search(&self, q: &Query) -> Vec<Document> {
+        self.perc.search(q)
+    }
+
+    /// Replays the documents retained since the given time through the
+    /// query with this ID on a background thread, to get what it would have
+    /// matched had it been there. `None` if there is no such query.
+    ///
+    /// Like [`PercolatorUid::search`], this needs [`PercBuilder::retain_size`],
+    /// but streams the matches and reports its progress, which suits
+    /// large retention sizes and expensive queries.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use std::time::SystemTime;
+    ///
+    /// let mut p = Percolator::builder().retain_size(100).build();
+    /// let _ = p.percolate(&[("colour", "blue")].into());
+    /// let since = SystemTime::now();
+    /// let _ = p.percolate(&[("colour", "blue"), ("taste", "sweet")].into());
+    /// let _ = p.percolate(&[("colour", "red")].into());
+    ///
+    /// let qid = p.add_query("colour:blue".parse().unwrap());
+    /// let backfill = p.backfill(&qid, since).unwrap();
+    /// assert_eq!(backfill.n_documents(), 2);
+    /// let matches = backfill.map(|(_, d)| d).collect::<Vec<_>>();
+    /// assert_eq!(matches, vec![[("colour", "blue"), ("taste", "sweet")].into()]);
+    /// ```
+    #[cfg(feature = "send")]
+    pub fn backfill<Q>(&self, uid: &Q, since: std::time::SystemTime) -> Option<Backfill>
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let q = self.safe_get_query(uid)?.clone();
+        Some(Backfill::spawn(q, self.perc.retained_since(since)))
+    }
+
+    /// The last percolations (oldest first), when
+    /// tracing is enabled with [`PercBuilder::trace_size`].
+    /// Empty otherwise.
+    ///
+    /// A percolation is recorded when its iterator is exhausted
+    /// or dropped.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::builder().trace_size(10).build();
+    /// p.add_query("field".has_value("value"));
+    /// assert_eq!(p.percolate(&[("field", "value")].into()).count(), 1);
+    ///
+    /// let traces = p.recent_percolations();
+    /// assert_eq!(traces.len(), 1);
+    /// assert_eq!(traces[0].n_matches(), 1);
+    /// ```
+    pub fn recent_percolations(&self) -> Vec<PercolationTrace> {
+        self.perc.recent_percolations()
+    }
+
+    /// Checks the queries and the recent percolations are within the
+    /// thresholds, for instance after loading many queries, to catch
+    /// configurations that make percolation slow before it shows in production.
+    /// The error lists all the exceeded thresholds, one per line.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// use mokaccino::models::percolator_core::QualityThresholds;
+    ///
+    /// let mut p = Percolator::builder().trace_size(100).build();
+    /// p.add_query("colour".has_value("blue"));
+    /// // A regex always needs checking.
+    /// p.add_query("name".has_regex(&regex::Regex::new("jo.*n").unwrap()));
+    ///
+    /// let thresholds = QualityThresholds::default().max_must_filter_share(0.2);
+    /// assert_eq!(
+    ///     p.assert_quality(&thresholds).unwrap_err(),
+    ///     "50% of the queries need checking, more than 20%"
+    /// );
+    ///
+    /// let _ = p.percolate(&[("name", "john")].into()).count();
+    /// let thresholds = QualityThresholds::default().max_candidates_per_match(1.0);
+    /// assert!(p.assert_quality(&thresholds).is_ok());
+    /// ```
+    pub fn assert_quality(&self, thresholds: &QualityThresholds) -> Result<(), String> {
+        self.perc.check_quality(thresholds)
+    }
 }
 
-impl<T> PercolatorUid<T>
+impl<T, P> PercolatorUid<T, P>
 where
     T: std::cmp::Eq + std::hash::Hash + Copy,
 {
     ///
     /// An iterator of the matching queries user provided IDs given the Document.
     ///
-    pub fn percolate<'b>(&self, d: &'b Document) -> impl Iterator<Item = T> + use<'b, '_, T> {
+    pub fn percolate<'b>(&self, d: &'b Document) -> impl Iterator<Item = T> + use<'b, '_, T, P> {
         self.percolate_ref(d).copied()
     }
+
+    /// At most `n` of the matching queries user provided IDs given the Document.
+    /// Percolation stops there, without checking the remaining candidates
+    /// against the document.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::default();
+    /// let q1 = p.add_query("colour".has_value("blue"));
+    /// p.add_query(!"colour".has_value("red"));
+    ///
+    /// let d = [("colour", "blue")].into();
+    /// assert_eq!(p.percolate_limit(&d, 1).collect::<Vec<_>>(), vec![q1]);
+    /// assert_eq!(p.percolate_limit(&d, 10).count(), 2);
+    /// ```
+    pub fn percolate_limit<'b>(
+        &self,
+        d: &'b Document,
+        n: usize,
+    ) -> impl Iterator<Item = T> + use<'b, '_, T, P> {
+        self.percolate(d).take(n)
+    }
+
+    /// The matching queries user provided IDs given the Document, like
+    /// [`PercolatorUid::percolate`], with the work spread over rayon's threads.
+    /// This pays off with many queries needing checking against the document,
+    /// like hundreds of thousands of queries with regexes or ranges.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::default();
+    /// let qid = p.add_query("colour".has_value("blue") & "name".has_suffix("ob"));
+    /// assert_eq!(p.percolate_par(&[("colour", "blue"), ("name", "blob")].into()), vec![qid]);
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn percolate_par(&self, d: &Document) -> Vec<T> {
+        self.perc
+            .percolate_par(d)
+            .into_iter()
+            .filter_map(|qid| self.qid_uid.get_by_left(&qid).copied())
+            .collect()
+    }
+
+    /// The matching queries user provided IDs given the borrowed document.
+    /// Same as [`PercolatorUid::percolate`], matching its fields and values
+    /// where they are. It is only copied into an owned [`Document`] when the
+    /// percolator retains documents, caches recent matches, or clamps
+    /// overflowing integers it has.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    /// let mut p = Percolator::default();
+    /// let qid = p.add_query("colour".has_value("blue"));
+    ///
+    /// let (colour, size) = (String::from("blue"), String::from("42"));
+    /// let d = DocumentRef::from([("colour", colour.as_str()), ("size", size.as_str())]);
+    /// assert_eq!(p.percolate_borrowed(&d).collect::<Vec<_>>(), vec![qid]);
+    /// ```
+    pub fn percolate_borrowed<'b, 'a>(
+        &self,
+        d: &'b DocumentRef<'a>,
+    ) -> impl Iterator<Item = T> + use<'b, 'a, '_, T, P> {
+        self.perc
+            .percolate(d)
+            .filter_map(|qid| self.qid_uid.get_by_left(&qid).copied())
+    }
+}
+
+impl<T, P> Percolate<T> for PercolatorUid<T, P>
+where
+    T: std::cmp::Eq + std::hash::Hash + Clone,
+{
+    fn add(&mut self, q: Query, uid: T) -> Result<T, PercolatorError> {
+        self.index_query_uid(q, uid)
+    }
+
+    fn remove(&mut self, uid: &T) -> bool {
+        self.remove_uid(uid)
+    }
+
+    fn get(&self, uid: &T) -> Option<&Query> {
+        self.safe_get_query(uid)
+    }
+
+    fn percolate<'a>(&'a self, d: &'a Document) -> Box<dyn Iterator<Item = &'a T> + 'a> {
+        Box::new(self.percolate_ref(d))
+    }
+
+    fn stats(&self) -> &PercolatorStats {
+        self.perc.stats()
+    }
 }
@@ -0,0 +1,173 @@
+use std::hash::Hash;
+
+use tokio::sync::{mpsc, oneshot};
+
+use crate::models::percolator::PercolatorUid;
+use crate::models::percolator_core::PercolatorError;
+use crate::prelude::{Document, Qid, Query};
+
+// How many commands can wait for the percolator
+// before senders wait for room.
+const COMMANDS_BUFFER: usize = 64;
+
+type Job<T> = Box<dyn FnOnce(&mut PercolatorUid<T>) + Send>;
+
+enum Command<T> {
+    Run(Job<T>),
+    Shutdown(oneshot::Sender<PercolatorUid<T>>),
+}
+
+/// A percolator for async services, owned by a blocking task of the
+/// tokio runtime and driven through a channel of commands. Long
+/// percolations do not block the executor running the callers.
+///
+/// Commands run one at a time, in the order they are sent.
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+/// use mokaccino::models::percolator::AsyncPercolator;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let p = AsyncPercolator::new(Percolator::default());
+/// let qid = p.add_query("colour".has_value("blue")).await.unwrap();
+/// assert_eq!(p.percolate([("colour", "blue")].into()).await, vec![qid]);
+///
+/// // The percolator is back when done.
+/// let p = p.shutdown().await;
+/// assert_eq!(p.stats().n_queries(), 1);
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct AsyncPercolator<T> {
+    commands: mpsc::Sender<Command<T>>,
+}
+
+impl<T> AsyncPercolator<T>
+where
+    T: Eq + Hash + Clone + Send + Sync + 'static,
+{
+    /// Moves the percolator to a blocking task of the current tokio runtime.
+    ///
+    /// # Panics
+    ///
+    /// Outside of a tokio runtime.
+    pub fn new(mut p: PercolatorUid<T>) -> Self {
+        let (commands, mut receiver) = mpsc::channel(COMMANDS_BUFFER);
+        tokio::task::spawn_blocking(move || {
+            while let Some(command) = receiver.blocking_recv() {
+                match command {
+                    Command::Run(job) => job(&mut p),
+                    Command::Shutdown(answer) => {
+                        let _ = answer.send(p);
+                        return;
+                    }
+                }
+            }
+        });
+        Self { commands }
+    }
+
+    async fn send(&self, command: Command<T>) {
+        // The task only stops on shutdown, which takes the facade,
+        // or when the percolator panics.
+        if self.commands.send(command).await.is_err() {
+            panic!("The percolator task stopped");
+        }
+    }
+
+    // Runs the function on the percolator, in its task.
+    async fn run<R, F>(&self, f: F) -> R
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut PercolatorUid<T>) -> R + Send + 'static,
+    {
+        let (answer, receiver) = oneshot::channel();
+        self.send(Command::Run(Box::new(move |p| {
+            // Nobody waits for it when the caller's future was dropped.
+            let _ = answer.send(f(p));
+        })))
+        .await;
+        receiver.await.expect("The percolator task stopped")
+    }
+
+    /// Same as [`PercolatorUid::index_query_uid`].
+    pub async fn index_query_uid(&self, q: Query, uid: T) -> Result<T, PercolatorError> {
+        self.run(move |p| p.index_query_uid(q, uid)).await
+    }
+
+    /// Same as [`PercolatorUid::remove_uid`].
+    pub async fn remove_uid(&self, uid: T) -> bool {
+        self.run(move |p| p.remove_uid(&uid)).await
+    }
+
+    /// The matching queries user provided IDs given the Document.
+    pub async fn percolate(&self, d: Document) -> Vec<T> {
+        self.run(move |p| p.percolate_ref(&d).cloned().collect())
+            .await
+    }
+
+    /// Stops the task once the commands sent before are done,
+    /// giving the percolator back.
+    pub async fn shutdown(self) -> PercolatorUid<T> {
+        let (answer, receiver) = oneshot::channel();
+        self.send(Command::Shutdown(answer)).await;
+        receiver.await.expect("The percolator task stopped")
+    }
+}
+
+impl AsyncPercolator<Qid> {
+    /// Same as [`PercolatorUid::safe_add_query`].
+    pub async fn add_query(&self, q: Query) -> Result<Qid, PercolatorError> {
+        self.run(move |p| p.safe_add_query(q)).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::{CNFQueryable, Percolator};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_commands() {
+        let p = AsyncPercolator::new(PercolatorUid::<String>::default());
+        let blue = "blue".to_string();
+        assert_eq!(
+            p.index_query_uid("colour".has_value("blue"), blue.clone())
+                .await
+                .unwrap(),
+            blue
+        );
+        p.index_query_uid("colour".has_value("red"), "red".into())
+            .await
+            .unwrap();
+        assert_eq!(
+            p.percolate([("colour", "blue")].into()).await,
+            vec![blue.clone()]
+        );
+        assert!(p.remove_uid(blue.clone()).await);
+        assert!(!p.remove_uid(blue).await);
+        assert_eq!(
+            p.percolate([("colour", "blue")].into()).await,
+            Vec::<String>::new()
+        );
+
+        let p = p.shutdown().await;
+        assert_eq!(p.percolate_ref(&[("colour", "red")].into()).count(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_callers() {
+        let p = std::sync::Arc::new(AsyncPercolator::new(Percolator::default()));
+        let qid = p.add_query("colour".has_value("blue")).await.unwrap();
+        let tasks = (0..20)
+            .map(|_| {
+                let p = p.clone();
+                tokio::spawn(async move { p.percolate([("colour", "blue")].into()).await })
+            })
+            .collect::<Vec<_>>();
+        for t in tasks {
+            assert_eq!(t.await.unwrap(), vec![qid]);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::models::percolator::PercolatorUid;
+
+/// A read only snapshot of a percolator, for many reader threads to
+/// percolate documents concurrently, while a single writer prepares
+/// the next generation. Made with [`PercolatorUid::freeze`].
+///
+/// Clones share the same percolator, and all its methods
+/// taking `&self` work on the snapshot.
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+/// use std::thread;
+///
+/// let mut p = Percolator::default();
+/// let qid = p.add_query("colour".has_value("blue"));
+/// let frozen = p.freeze();
+///
+/// // The writer carries on, without the readers seeing it.
+/// p.add_query("colour".has_value("red"));
+///
+/// let readers = (0..4)
+///     .map(|_| {
+///         let frozen = frozen.clone();
+///         thread::spawn(move || frozen.percolate(&[("colour", "blue")].into()).collect::<Vec<_>>())
+///     })
+///     .collect::<Vec<_>>();
+/// for r in readers {
+///     assert_eq!(r.join().unwrap(), vec![qid]);
+/// }
+/// assert_eq!(frozen.percolate(&[("colour", "red")].into()).count(), 0);
+/// ```
+#[derive(Debug)]
+pub struct FrozenPercolator<T, P = ()> {
+    perc: Arc<PercolatorUid<T, P>>,
+}
+
+// Not derived, as the IDs and payloads do not need cloning.
+impl<T, P> Clone for FrozenPercolator<T, P> {
+    fn clone(&self) -> Self {
+        Self {
+            perc: Arc::clone(&self.perc),
+        }
+    }
+}
+
+impl<T, P> Deref for FrozenPercolator<T, P> {
+    type Target = PercolatorUid<T, P>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.perc
+    }
+}
+
+impl<T, P> From<PercolatorUid<T, P>> for FrozenPercolator<T, P> {
+    /// Freezes the percolator itself, without copying it.
+    fn from(p: PercolatorUid<T, P>) -> Self {
+        Self { perc: Arc::new(p) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::{CNFQueryable, Percolator};
+
+    fn is_send_sync<T: Send + Sync>(_: &T) -> bool {
+        true
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut p = PercolatorUid::<String>::default();
+        p.index_query_uid("colour".has_value("blue"), "blue".into())
+            .unwrap();
+        let frozen = p.freeze();
+        assert!(is_send_sync(&frozen));
+
+        p.remove_uid("blue");
+        assert_eq!(p.percolate_ref(&[("colour", "blue")].into()).count(), 0);
+        let clone = frozen.clone();
+        assert_eq!(
+            clone
+                .percolate_ref(&[("colour", "blue")].into())
+                .collect::<Vec<_>>(),
+            vec!["blue"]
+        );
+        assert_eq!(clone.get_query("blue").to_string(), "colour:blue");
+
+        let frozen = FrozenPercolator::from(Percolator::default());
+        assert_eq!(frozen.percolate(&[("colour", "blue")].into()).count(), 0);
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::models::document::Document;
+use crate::models::percolator_core::Qid;
+use crate::models::types::OurRc;
+
+/// A function called with the user ID of a matching query and the document.
+/// See [`super::PercolatorUid::on_match`]
+#[cfg(feature = "send")]
+pub trait MatchHandler<T>: Fn(&T, &Document) + Send + Sync {}
+#[cfg(feature = "send")]
+impl<T, F> MatchHandler<T> for F where F: Fn(&T, &Document) + Send + Sync {}
+
+/// A function called with the user ID of a matching query and the document.
+/// See [`super::PercolatorUid::on_match`]
+#[cfg(not(feature = "send"))]
+pub trait MatchHandler<T>: Fn(&T, &Document) {}
+#[cfg(not(feature = "send"))]
+impl<T, F> MatchHandler<T> for F where F: Fn(&T, &Document) {}
+
+// The handlers of each query that has some.
+pub(crate) struct Handlers<T> {
+    by_qid: BTreeMap<Qid, Vec<OurRc<dyn MatchHandler<T>>>>,
+}
+
+// Not derived, so T does not need to implement Default.
+impl<T> Default for Handlers<T> {
+    fn default() -> Self {
+        Self {
+            by_qid: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Handlers<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.by_qid.iter().map(|(qid, hs)| (qid, hs.len())))
+            .finish()
+    }
+}
+
+impl<T> Handlers<T> {
+    pub(crate) fn add(&mut self, qid: Qid, handler: OurRc<dyn MatchHandler<T>>) {
+        self.by_qid.entry(qid).or_default().push(handler);
+    }
+
+    pub(crate) fn remove(&mut self, qid: Qid) {
+        self.by_qid.remove(&qid);
+    }
+
+    // The same handlers, now for the other query.
+    pub(crate) fn copy_from(&mut self, from: &Self, qid: Qid, to_qid: Qid) {
+        if let Some(hs) = from.by_qid.get(&qid) {
+            self.by_qid.insert(to_qid, hs.clone());
+        }
+    }
+
+    pub(crate) fn move_to(&mut self, qid: Qid, to_qid: Qid) {
+        if let Some(hs) = self.by_qid.remove(&qid) {
+            self.by_qid.insert(to_qid, hs);
+        }
+    }
+
+    // The same handlers, for the new Qids of their queries.
+    pub(crate) fn remap(&mut self, remapping: &BTreeMap<Qid, Qid>) {
+        self.by_qid = std::mem::take(&mut self.by_qid)
+            .into_iter()
+            .filter_map(|(qid, hs)| remapping.get(&qid).map(|new_qid| (*new_qid, hs)))
+            .collect();
+    }
+
+    // True if the query had any handler.
+    pub(crate) fn dispatch(&self, qid: Qid, uid: &T, d: &Document) -> bool {
+        match self.by_qid.get(&qid) {
+            Some(hs) => {
+                hs.iter().for_each(|h| h(uid, d));
+                true
+            }
+            None => false,
+        }
+    }
+}
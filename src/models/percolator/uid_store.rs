@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+use crate::models::percolator_core::Qid;
+
+/// Somewhere to keep the mapping between the [`Qid`]s of a
+/// [`super::PercolatorUid`] and their user supplied IDs, like a
+/// sled tree or an sqlite table, so it survives restarts
+/// independently of full snapshots of the percolator.
+///
+/// The percolator keeps its own copy of the mapping for percolation,
+/// and writes each change through to its store.
+/// See [`super::PercolatorUid::set_uid_store`]
+pub trait UidMappingStore<T>: Debug {
+    /// The query `qid` now has the user ID `uid`.
+    fn insert(&mut self, qid: Qid, uid: &T);
+
+    /// The query `qid` is gone.
+    fn remove(&mut self, qid: Qid);
+
+    /// Forgets all the mappings.
+    fn clear(&mut self);
+
+    /// All the mappings, in any order.
+    fn mappings(&self) -> Vec<(Qid, T)>;
+}
+
+/// A store percolators can own, and move between threads
+/// with the `send` feature.
+#[cfg(feature = "send")]
+pub type BoxedUidStore<T> = Box<dyn UidMappingStore<T> + Send + Sync>;
+
+/// A store percolators can own.
+#[cfg(not(feature = "send"))]
+pub type BoxedUidStore<T> = Box<dyn UidMappingStore<T>>;
+
+/// A [`UidMappingStore`] in memory, for tests
+/// or as an example of implementation.
+#[derive(Debug, Clone)]
+pub struct MemoryUidStore<T> {
+    uids: BTreeMap<Qid, T>,
+}
+
+// Not derived, so T does not need to implement Default.
+impl<T> Default for MemoryUidStore<T> {
+    fn default() -> Self {
+        Self {
+            uids: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T: Clone + Debug> UidMappingStore<T> for MemoryUidStore<T> {
+    fn insert(&mut self, qid: Qid, uid: &T) {
+        self.uids.insert(qid, uid.clone());
+    }
+
+    fn remove(&mut self, qid: Qid) {
+        self.uids.remove(&qid);
+    }
+
+    fn clear(&mut self) {
+        self.uids.clear();
+    }
+
+    fn mappings(&self) -> Vec<(Qid, T)> {
+        self.uids
+            .iter()
+            .map(|(qid, uid)| (*qid, uid.clone()))
+            .collect()
+    }
+}
@@ -1,27 +1,74 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
 use std::num::{NonZeroU64, NonZeroUsize, TryFromIntError};
+use std::sync::atomic::{self, AtomicU64};
+use std::time::{Instant, SystemTime};
 use std::{fmt, iter};
 
-use hashbrown::HashSet;
+use h3o::Resolution;
+use hashbrown::{HashMap, HashSet};
 use hstats::Hstats;
-use itertools::Itertools;
+use itertools::{Either, Itertools};
 use num_traits::ToPrimitive;
 use roaring::RoaringBitmap;
 
 use crate::itertools::InPlaceReduce;
+use crate::logging::log_warn;
 
-use crate::models::types::OurStr;
+use crate::models::types::{OurRc, OurStr};
 use crate::models::{
     cnf::{Clause, Query},
-    document::Document,
+    document::{Document, DocumentView, MATCH_ALL},
     index::Index,
-    queries::term::TermQuery,
+    queries::ordered::clamped_int,
+    schema::Schema,
 };
 
 pub(crate) mod tools;
 use tools::*;
 
+mod report;
+pub use report::{CorpusReport, FieldReport};
+
+mod quality;
+pub use quality::QualityThresholds;
+
+#[cfg(feature = "send")]
+mod backfill;
+#[cfg(feature = "send")]
+pub use backfill::Backfill;
+
+#[cfg(feature = "rayon")]
+mod parallel;
+
+mod delta;
+pub use delta::PercolatorStatsDelta;
+
+mod explain;
+pub use explain::{ClauseExplanation, Explanation, LiteralMatch, MatchSource};
+
+mod fanout;
+use fanout::FanoutStats;
+
+mod memory;
+pub use fanout::PreheaterWarning;
+pub use memory::MemoryReport;
+
+mod recent;
+use recent::RecentMatches;
+
+mod retained;
+use retained::RetainedDocs;
+
+mod trace;
+pub use trace::PercolationTrace;
+use trace::{TraceRing, Traced};
+
 pub type Qid = u32;
 
+// Warn when there are this many preheaters, and at each doubling.
+const PREHEATERS_WARNING: usize = 256;
+
 // The docs Ids from the index mathing this clause
 // This is only used in the context of percolation,
 //    this clause will NOT have any negatives.
@@ -37,6 +84,15 @@ pub(crate) fn clause_docs_from_idx(c: &Clause, index: &Index) -> RoaringBitmap {
     ret
 }
 
+// The docs Ids from the index matching any of the (field,value) terms.
+fn terms_docs_from_idx(terms: &[(&str, &str)], index: &Index) -> RoaringBitmap {
+    let mut ret = RoaringBitmap::new();
+    for (field, value) in terms {
+        ret |= index.docs_from_fv(field, value);
+    }
+    ret
+}
+
 // For indexing clauses.
 fn clause_to_mi(c: &Clause, conf: &PercolatorConfig) -> MatchItem {
     let lits = c.literals().iter();
@@ -84,16 +140,102 @@ fn cnf_to_matchitems(q: &Query, conf: &PercolatorConfig) -> impl Iterator<Item =
 struct ClauseMatcher {
     positive_index: Index,
     preheaters: Vec<PreHeater>,
-    preheaters_names: HashSet<OurStr>,
+    // For each preheater, the queries that added it.
+    preheaters_qids: HashMap<OurStr, RoaringBitmap>,
 }
 
 impl ClauseMatcher {
-    fn add_preheater(&mut self, ph: PreHeater) {
-        if !self.preheaters_names.contains(&ph.id) {
-            self.preheaters_names.insert(ph.id.clone());
-            self.preheaters.push(ph);
+    fn from_config(config: &PercolatorConfig) -> Self {
+        Self {
+            positive_index: if config.prefix_compression {
+                Index::with_prefix_compression()
+            } else {
+                Index::default()
+            },
+            ..Default::default()
         }
     }
+
+    fn add_preheater(&mut self, ph: PreHeater, qid: Qid) {
+        self.preheaters_qids
+            .entry(ph.id.clone())
+            .or_insert_with(|| {
+                self.preheaters.push(ph);
+                RoaringBitmap::new()
+            })
+            .insert(qid);
+    }
+
+    // The queries of the document terms, expanded with the preheaters.
+    fn candidates(
+        &self,
+        terms: &[(&str, &str)],
+        fanouts: &mut Vec<(OurStr, u64, u64)>,
+    ) -> RoaringBitmap {
+        let mut docs = terms_docs_from_idx(terms, &self.positive_index);
+        // Expand the terms with all clause matcher pre-heaters.
+        // Before trying to match them against the index.
+        for ph in &self.preheaters {
+            let ph_terms = ph.expand(terms);
+            let mut ph_docs = RoaringBitmap::new();
+            for tq in &ph_terms {
+                ph_docs |= tq.docs_from_idx(&self.positive_index);
+            }
+            if ph.must_filter && !ph_terms.is_empty() {
+                let n_queries = self.preheaters_qids.get(&ph.id).map_or(0, |q| q.len());
+                fanouts.push((ph.id.clone(), ph_docs.len(), n_queries));
+            }
+            docs |= ph_docs;
+        }
+        docs
+    }
+
+    // Drops the preheaters only this query needed, returning their ids.
+    fn remove_preheaters_of(&mut self, qid: Qid) -> Vec<OurStr> {
+        let mut dropped = vec![];
+        self.preheaters_qids.retain(|id, qids| {
+            if qids.remove(qid) && qids.is_empty() {
+                dropped.push(id.clone());
+            }
+            !qids.is_empty()
+        });
+        self.preheaters.retain(|ph| !dropped.contains(&ph.id));
+        dropped
+    }
+}
+
+/// What integer comparisons and ranges make of document values
+/// too large or too small for an `i64`, like `18446744073709551615`.
+/// Values that are not integers at all never match them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IntOverflow {
+    /// They do not match. The default.
+    #[default]
+    NonMatching,
+    /// They compare as `i64::MAX` or `i64::MIN`, so they
+    /// match `field>100` or `field<100`.
+    Clamp,
+    /// They do not match, and are counted in
+    /// [`PercolatorStats::n_int_overflows`].
+    Report,
+}
+
+/// What percolating an empty document, without any field, matches.
+/// Event streams can emit empty payloads, which usually mean nothing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EmptyDocuments {
+    /// Like any other document. The default. Queries about missing
+    /// values match it, like `NOT colour:blue` or `"colour".lacks_field()`,
+    /// and all their preheaters run.
+    #[default]
+    Percolate,
+    /// No query matches it, and nothing runs.
+    NoMatch,
+    /// Only the queries matching any document, without any clause
+    /// like `Query::from_and(vec![])`, match it. Nothing else runs.
+    MatchAllOnly,
 }
 
 #[derive(Debug, Clone)]
@@ -101,6 +243,42 @@ impl ClauseMatcher {
 pub struct PercolatorConfig {
     pub(crate) n_clause_matchers: NonZeroUsize,
     pub(crate) prefix_sizes: Vec<usize>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) trace_size: usize,
+    #[cfg_attr(feature = "serde", serde(default = "default_h3_max_resolution"))]
+    pub(crate) h3_max_resolution: Resolution,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) linear_threshold: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) prefix_compression: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) retain_size: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) max_clauses: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) dedup_values: bool,
+    #[cfg_attr(feature = "serde", serde(default = "default_ngram_size"))]
+    pub(crate) ngram_size: NonZeroUsize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) int_overflow: IntOverflow,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) normalize_terms: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) empty_documents: EmptyDocuments,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) schema: Option<Schema>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) match_cache_size: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) recycle_qids: bool,
+}
+
+fn default_h3_max_resolution() -> Resolution {
+    Resolution::Fifteen
+}
+
+fn default_ngram_size() -> NonZeroUsize {
+    NonZeroUsize::new(3).unwrap()
 }
 
 impl Default for PercolatorConfig {
@@ -108,6 +286,20 @@ impl Default for PercolatorConfig {
         Self {
             n_clause_matchers: NonZeroUsize::new(3).unwrap(),
             prefix_sizes: vec![2, 10, 100, 1000, 2000],
+            trace_size: 0,
+            h3_max_resolution: default_h3_max_resolution(),
+            linear_threshold: 0,
+            prefix_compression: false,
+            retain_size: 0,
+            max_clauses: 0,
+            dedup_values: false,
+            ngram_size: default_ngram_size(),
+            int_overflow: IntOverflow::default(),
+            normalize_terms: false,
+            empty_documents: EmptyDocuments::default(),
+            schema: None,
+            match_cache_size: 0,
+            recycle_qids: false,
         }
     }
 }
@@ -131,6 +323,116 @@ impl PercolatorConfig {
     pub fn prefix_sizes(&self) -> &[usize] {
         &self.prefix_sizes
     }
+
+    /// The number of recent percolations to keep
+    /// for postmortem analysis.
+    ///
+    /// The default is 0 (no tracing).
+    pub fn trace_size(&self) -> usize {
+        self.trace_size
+    }
+
+    /// The finest H3 resolution geographic queries
+    /// are indexed at. Finer query cells are indexed
+    /// as their ancestor at this resolution.
+    ///
+    /// The default is 15 (no clamping).
+    pub fn h3_max_resolution(&self) -> Resolution {
+        self.h3_max_resolution
+    }
+
+    /// Below this number of live queries, documents are
+    /// percolated by checking every query instead of using
+    /// the index.
+    ///
+    /// The default is 0 (always use the index).
+    pub fn linear_threshold(&self) -> usize {
+        self.linear_threshold
+    }
+
+    /// Whether the index stores its values prefix compressed.
+    ///
+    /// The default is false.
+    pub fn prefix_compression(&self) -> bool {
+        self.prefix_compression
+    }
+
+    /// The number of recent percolated documents to keep,
+    /// so they can be searched for new queries.
+    ///
+    /// The default is 0 (no documents kept).
+    pub fn retain_size(&self) -> usize {
+        self.retain_size
+    }
+
+    /// The maximum number of clauses of the queries
+    /// added to the percolator.
+    ///
+    /// The default is 0 (no maximum).
+    pub fn max_clauses(&self) -> usize {
+        self.max_clauses
+    }
+
+    /// Whether duplicate (field,value) tuples of percolated
+    /// documents are skipped before matching them.
+    ///
+    /// The default is false.
+    pub fn dedup_values(&self) -> bool {
+        self.dedup_values
+    }
+
+    /// The length in characters of the n-grams substring
+    /// queries are indexed with. Percolated values are
+    /// split in all their n-grams of this length, so longer
+    /// n-grams mean fewer candidates, but shorter substrings
+    /// only get their own n-grams.
+    ///
+    /// The default is 3.
+    pub fn ngram_size(&self) -> NonZeroUsize {
+        self.ngram_size
+    }
+
+    /// What integer comparisons make of document values
+    /// overflowing an `i64`.
+    ///
+    /// The default is [`IntOverflow::NonMatching`].
+    pub fn int_overflow(&self) -> IntOverflow {
+        self.int_overflow
+    }
+
+    /// Whether term queries match the trimmed and case
+    /// folded document values.
+    ///
+    /// The default is false.
+    pub fn normalize_terms(&self) -> bool {
+        self.normalize_terms
+    }
+
+    /// What percolating an empty document matches.
+    ///
+    /// The default is [`EmptyDocuments::Percolate`].
+    pub fn empty_documents(&self) -> EmptyDocuments {
+        self.empty_documents
+    }
+
+    /// The schema queries must stick to, if any.
+    pub fn schema(&self) -> Option<&Schema> {
+        self.schema.as_ref()
+    }
+
+    /// How many distinct documents to remember the matches of.
+    ///
+    /// The default is 0 (no cache).
+    pub fn match_cache_size(&self) -> usize {
+        self.match_cache_size
+    }
+
+    /// Whether new queries reuse the Qids of removed ones.
+    ///
+    /// The default is false.
+    pub fn recycle_qids(&self) -> bool {
+        self.recycle_qids
+    }
 }
 
 ///
@@ -146,6 +448,9 @@ pub struct PercolatorStats {
     clauses_per_query: Hstats<f64>,
     preheaters_per_query: Hstats<f64>,
     prefix_lengths: Hstats<f64>,
+    fanouts: FanoutStats,
+    // Percolations only have a shared reference.
+    n_int_overflows: AtomicU64,
 }
 
 impl Default for PercolatorStats {
@@ -161,6 +466,24 @@ impl Default for PercolatorStats {
             clauses_per_query: proto_hstat.clone(),
             preheaters_per_query: proto_hstat.clone(),
             prefix_lengths,
+            fanouts: Default::default(),
+            n_int_overflows: Default::default(),
+        }
+    }
+}
+
+// A snapshot, to compare with later ones.
+impl Clone for PercolatorStats {
+    fn clone(&self) -> Self {
+        Self {
+            n_queries: self.n_queries,
+            n_queries_removed: self.n_queries_removed,
+            n_preheaters: self.n_preheaters,
+            clauses_per_query: self.clauses_per_query.clone(),
+            preheaters_per_query: self.preheaters_per_query.clone(),
+            prefix_lengths: self.prefix_lengths.clone(),
+            fanouts: self.fanouts.clone(),
+            n_int_overflows: AtomicU64::new(self.n_int_overflows()),
         }
     }
 }
@@ -183,7 +506,14 @@ impl std::fmt::Display for PercolatorStats {
             self.clauses_per_query,
             self.preheaters_per_query,
             self.prefix_lengths,
-        )
+        )?;
+        if self.n_int_overflows() > 0 {
+            write!(f, "\n🔢 Integer overflows={}", self.n_int_overflows())?;
+        }
+        for warning in self.preheater_warnings() {
+            write!(f, "\n⚠️ Preheater {}", warning)?;
+        }
+        Ok(())
     }
 }
 
@@ -245,11 +575,30 @@ impl PercolatorStats {
             .collect()
     }
 
+    // For percolators that do not index queries.
+    pub(crate) fn record_addition(&mut self, q: &Query) {
+        self.n_queries += 1;
+        self.clauses_per_query.add(q.clauses().len() as f64);
+        for prefix_query in q.prefix_queries() {
+            self.prefix_lengths.add(prefix_query.prefix().len() as f64);
+        }
+    }
+
+    pub(crate) fn record_removal(&mut self) {
+        self.n_queries_removed += 1;
+    }
+
     /// The number of queries removed from the percolator
     pub fn n_queries_removed(&self) -> usize {
         self.n_queries_removed
     }
 
+    /// The number of percolated values too large or too small for
+    /// the integer comparisons of their fields, with [`IntOverflow::Report`].
+    pub fn n_int_overflows(&self) -> u64 {
+        self.n_int_overflows.load(atomic::Ordering::Relaxed)
+    }
+
     /// The number of distinct pre heating functions
     /// coming from indexed queries for the percolator.
     pub fn n_preheaters(&self) -> usize {
@@ -265,6 +614,52 @@ impl PercolatorStats {
     pub fn preheaters_per_query(&self) -> &Hstats<f64> {
         &self.preheaters_per_query
     }
+
+    /// The inexact preheaters that consistently make most of the queries
+    /// needing them candidates when percolating, so they all need checking.
+    /// Each comes with a suggested configuration change.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// // Long prefixes clipped to the same 2 characters.
+    /// let mut p = Percolator::builder().prefix_sizes(vec![2]).build();
+    /// for i in 0..20 {
+    ///     p.add_query("name".has_prefix(format!("john{}", i)));
+    /// }
+    /// for _ in 0..100 {
+    ///     let _ = p.percolate(&[("name", "johnny")].into()).count();
+    /// }
+    /// let warnings = p.stats().preheater_warnings();
+    /// assert_eq!(warnings[0].id(), "__PREFIX2__name");
+    /// assert_eq!(warnings[0].mean_fanout(), 1.0);
+    /// ```
+    pub fn preheater_warnings(&self) -> Vec<PreheaterWarning> {
+        self.fanouts.warnings()
+    }
+
+    /// What changed since an `earlier` snapshot of these statistics,
+    /// like the queries added in between. Periodic monitoring can
+    /// report rates with it, rather than lifetime totals.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let mut p = Percolator::default();
+    /// p.add_query("colour".has_value("blue"));
+    /// let earlier = p.stats().clone();
+    ///
+    /// p.add_query("name".has_prefix("jo") & "colour".has_value("red"));
+    /// let delta = p.stats().delta(&earlier);
+    /// assert_eq!(delta.n_queries_added(), 1);
+    /// assert_eq!(delta.n_preheaters_change(), 1);
+    /// assert_eq!(delta.clauses_per_query().mean(), 2.0);
+    /// ```
+    pub fn delta(&self, earlier: &PercolatorStats) -> PercolatorStatsDelta {
+        PercolatorStatsDelta::new(self, earlier)
+    }
 }
 
 #[cfg(test)]
@@ -326,10 +721,17 @@ pub enum PercolatorError {
     TooManyQueries,
     /// A prefix in a prefix query is too long (length exceeds u32::MAX)
     PrefixTooLong(usize),
-    /// A query has too many clauses (exceeds u32::MAX)
+    /// A query has too many clauses (exceeds the configured
+    /// maximum, see [`PercolatorConfig::max_clauses`], or u32::MAX)
     TooManyClauses,
     /// A query has too many non pure-term query atoms (exceeds u32::MAX)
     TooManyPreheaters,
+    /// A pack of queries with this name is already loaded
+    /// (see [`crate::prelude::Percolator::load_pack`])
+    DuplicatePack(String),
+    /// A query is about a field that is not in the schema
+    /// (see [`PercolatorConfig::schema`])
+    UnknownField(String),
 }
 
 /// This is the primary object you need to keep to percolate documents
@@ -351,8 +753,19 @@ pub enum PercolatorError {
 pub(crate) struct PercolatorCore {
     // Serialisable data.
     pub(crate) config: PercolatorConfig,
-    cnf_queries: Vec<Query>,
+    // Shared with the callers that keep their own queries.
+    cnf_queries: Vec<OurRc<Query>>,
     unindexed_qids: RoaringBitmap,
+    // For each required field, the queries requiring it.
+    required_fields: BTreeMap<OurStr, RoaringBitmap>,
+    // When the queries that expire do.
+    expiries: BTreeMap<Qid, SystemTime>,
+    // The same, by time, to find the expired queries.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    expiring: BTreeMap<SystemTime, RoaringBitmap>,
+    // The fields of integer comparisons, to look for overflowing values.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    int_fields: BTreeSet<OurStr>,
 
     // Only when the serde feature is on, add the serde(skip) attribute
     // so this does not get serialised.
@@ -367,6 +780,12 @@ pub(crate) struct PercolatorCore {
     must_filter: RoaringBitmap,
     #[cfg_attr(feature = "serde", serde(skip))]
     stats: PercolatorStats,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    traces: TraceRing,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    retained_docs: RetainedDocs,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    recent_matches: RecentMatches,
 }
 
 #[cfg(feature = "serde")]
@@ -382,6 +801,10 @@ impl<'de> serde::Deserialize<'de> for PercolatorCore {
             config: PercolatorConfig,
             cnf_queries: Vec<Query>,
             unindexed_qids: RoaringBitmap,
+            #[serde(default)]
+            required_fields: BTreeMap<OurStr, RoaringBitmap>,
+            #[serde(default)]
+            expiries: BTreeMap<Qid, SystemTime>,
         }
 
         let helper = Helper::deserialize(deserializer)?;
@@ -397,6 +820,10 @@ impl<'de> serde::Deserialize<'de> for PercolatorCore {
         for qid in helper.unindexed_qids {
             p.remove_qid(qid);
         }
+        p.required_fields = helper.required_fields;
+        for (qid, at) in helper.expiries {
+            p.set_expiry(qid, at);
+        }
 
         Ok(p)
     }
@@ -419,6 +846,11 @@ impl fmt::Display for PercolatorCore {
     }
 }
 
+// Bitmaps count for their serialized size.
+fn bitmaps_bytes<'a>(bs: impl IntoIterator<Item = &'a RoaringBitmap>) -> usize {
+    bs.into_iter().map(|b| b.serialized_size()).sum()
+}
+
 fn usize_to_f64(u: usize) -> Result<f64, TryFromIntError> {
     let u: u32 = u.try_into()?;
     Ok(f64::from(u))
@@ -429,13 +861,20 @@ impl PercolatorCore {
         Self {
             cnf_queries: Vec::new(),
             unindexed_qids: RoaringBitmap::new(),
+            required_fields: BTreeMap::new(),
+            expiries: BTreeMap::new(),
+            expiring: BTreeMap::new(),
+            int_fields: BTreeSet::new(),
 
             seen_preheaters: HashSet::new(),
             clause_matchers: (0..config.n_clause_matchers().get())
-                .map(|_| ClauseMatcher::default())
+                .map(|_| ClauseMatcher::from_config(&config))
                 .collect(),
             must_filter: RoaringBitmap::new(),
             stats: Default::default(),
+            traces: TraceRing::new(config.trace_size),
+            retained_docs: RetainedDocs::new(config.retain_size),
+            recent_matches: RecentMatches::new(config.match_cache_size),
 
             config,
         }
@@ -452,17 +891,238 @@ impl PercolatorCore {
         &self.stats
     }
 
-    pub(crate) fn safe_add_query(&mut self, q: Query) -> Result<Qid, PercolatorError> {
+    /// Roughly how many bytes the clause matcher indexes take on the heap.
+    pub(crate) fn index_heap_bytes(&self) -> usize {
+        self.clause_matchers
+            .iter()
+            .map(|cm| cm.positive_index.heap_bytes())
+            .sum()
+    }
+
+    pub(crate) fn memory_usage(&self) -> MemoryReport {
+        let queries = self.cnf_queries.capacity() * size_of::<OurRc<Query>>()
+            + self
+                .cnf_queries
+                .iter()
+                .map(|q| size_of::<Query>() + q.heap_bytes())
+                .sum::<usize>();
+        let bitmaps = bitmaps_bytes(
+            [&self.unindexed_qids, &self.must_filter]
+                .into_iter()
+                .chain(self.required_fields.values())
+                .chain(self.expiring.values()),
+        ) + self.required_fields.keys().map(|f| f.len()).sum::<usize>()
+            + self.expiries.len() * size_of::<(Qid, SystemTime)>();
+        let preheaters = self
+            .clause_matchers
+            .iter()
+            .map(|cm| {
+                cm.preheaters.capacity() * size_of::<PreHeater>()
+                    + cm.preheaters.iter().map(|ph| ph.id.len()).sum::<usize>()
+                    + cm.preheaters_qids.capacity() * size_of::<(OurStr, RoaringBitmap)>()
+                    + cm.preheaters_qids.keys().map(|id| id.len()).sum::<usize>()
+                    + bitmaps_bytes(cm.preheaters_qids.values())
+            })
+            .sum::<usize>()
+            + self.seen_preheaters.capacity() * size_of::<OurStr>()
+            + self
+                .seen_preheaters
+                .iter()
+                .map(|id| id.len())
+                .sum::<usize>();
+        MemoryReport {
+            queries,
+            indexes: self.index_heap_bytes(),
+            bitmaps,
+            preheaters,
+        }
+    }
+
+    pub(crate) fn safe_add_query(
+        &mut self,
+        q: impl Into<OurRc<Query>>,
+    ) -> Result<Qid, PercolatorError> {
+        self.safe_add_query_requiring(q, &[])
+    }
+
+    // Checks these queries can all be added, without changing anything.
+    pub(crate) fn check_queries(&self, qs: &[Query]) -> Result<(), PercolatorError> {
+        if self.config.max_clauses > 0
+            && qs
+                .iter()
+                .any(|q| q.clauses().len() > self.config.max_clauses)
+        {
+            return Err(PercolatorError::TooManyClauses);
+        }
+        if let Some(schema) = &self.config.schema
+            && let Some(field) = qs
+                .iter()
+                .flat_map(|q| q.fields())
+                .find(|f| !schema.has_field(f))
+        {
+            return Err(PercolatorError::UnknownField(field.to_string()));
+        }
+        // The last one needs a Qid too.
+        Qid::try_from(self.cnf_queries.len() + qs.len().saturating_sub(1))
+            .map_err(|_| PercolatorError::TooManyQueries)?;
+        Ok(())
+    }
+
+    // Documents without all the required fields will
+    // never match this query, whatever the query says.
+    pub(crate) fn safe_add_query_requiring(
+        &mut self,
+        q: impl Into<OurRc<Query>>,
+        required_fields: &[&str],
+    ) -> Result<Qid, PercolatorError> {
+        let q = self.normalized(q.into());
+        // Before changing anything.
+        self.check_queries(std::slice::from_ref(q.as_ref()))?;
+        self.add_checked_query(q, required_fields)
+    }
+
+    /// Adds all these queries, or none of them if any of them fails
+    /// `check_queries`, which checks them all before indexing any.
+    /// New qids are indexed in one batch per clause matcher.
+    pub(crate) fn safe_add_queries(&mut self, qs: Vec<Query>) -> Result<Vec<Qid>, PercolatorError> {
+        // Normalising terms changes neither the clauses nor the fields.
+        self.check_queries(&qs)?;
+        let mut qs = qs
+            .into_iter()
+            .map(|q| self.normalized(q.into()))
+            .collect_vec();
+        // Recycled qids are scattered, so their queries are indexed one by one.
+        let n_recycled = if self.config.recycle_qids {
+            qs.len().min(self.unindexed_qids.len() as usize)
+        } else {
+            0
+        };
+        let mut qids = qs
+            .drain(..n_recycled)
+            .map(|q| self.add_checked_query(q, &[]))
+            .collect::<Result<Vec<_>, _>>()?;
+        qids.extend(self.add_checked_queries(qs)?);
+        Ok(qids)
+    }
+
+    // With the terms trimmed and case folded, if configured so.
+    pub(crate) fn normalized(&self, q: OurRc<Query>) -> OurRc<Query> {
+        if self.config.normalize_terms
+            && let Some(normalized) = q.with_normalized_terms()
+        {
+            OurRc::new(normalized)
+        } else {
+            q
+        }
+    }
+
+    fn add_checked_query(
+        &mut self,
+        q: OurRc<Query>,
+        required_fields: &[&str],
+    ) -> Result<Qid, PercolatorError> {
         // Get the document from the query
         // and index in the query index
         // The Clause index is controlling the zip.
         let expected_index_len = self.cnf_queries.len() + 1;
+        self.recent_matches.clear();
+
+        // The lowest removed qid, if configured so.
+        let recycled = self
+            .config
+            .recycle_qids
+            .then(|| self.unindexed_qids.min())
+            .flatten();
+        let new_doc_id = match recycled {
+            Some(qid) => qid,
+            None => self
+                .cnf_queries
+                .len()
+                .try_into()
+                .map_err(|_| PercolatorError::TooManyQueries)?,
+        };
+        let docs = self.register_query(&q, new_doc_id, required_fields)?;
+
+        for (clause_matcher, doc) in self.clause_matchers.iter_mut().zip(docs) {
+            if recycled.is_some() {
+                clause_matcher
+                    .positive_index
+                    .index_document_at(new_doc_id, &doc);
+            } else {
+                clause_matcher.positive_index.index_document(&doc);
+
+                assert_eq!(
+                    clause_matcher.positive_index.len(),
+                    expected_index_len,
+                    "Index length mismatch after adding query {}",
+                    new_doc_id
+                );
+            }
+        }
+
+        if recycled.is_some() {
+            self.cnf_queries[new_doc_id as usize] = q;
+            self.unindexed_qids.remove(new_doc_id);
+        } else {
+            self.cnf_queries.push(q);
+        }
+        Ok(new_doc_id)
+    }
 
-        let new_doc_id = self
+    // Adds these queries with new consecutive qids. Each clause matcher
+    // indexes the documents of all the queries at once.
+    // On error, the queries before the failing one are added.
+    fn add_checked_queries(&mut self, qs: Vec<OurRc<Query>>) -> Result<Vec<Qid>, PercolatorError> {
+        self.recent_matches.clear();
+        let first_qid: Qid = self
             .cnf_queries
             .len()
             .try_into()
             .map_err(|_| PercolatorError::TooManyQueries)?;
+
+        let mut batches = self
+            .clause_matchers
+            .iter()
+            .map(|cm| cm.positive_index.batch())
+            .collect_vec();
+        let mut failed = None;
+        let mut added = Vec::with_capacity(qs.len());
+        for (qid, q) in (first_qid..).zip(qs) {
+            match self.register_query(&q, qid, &[]) {
+                Ok(docs) => {
+                    for (batch, doc) in batches.iter_mut().zip(docs) {
+                        batch.add_document(&doc);
+                    }
+                    added.push(q);
+                }
+                Err(e) => {
+                    failed = Some(e);
+                    break;
+                }
+            }
+        }
+
+        for (clause_matcher, batch) in self.clause_matchers.iter_mut().zip(batches) {
+            clause_matcher.positive_index.index_batch(batch);
+        }
+
+        let qids = (first_qid..).take(added.len()).collect();
+        self.cnf_queries.extend(added);
+        match failed {
+            Some(e) => Err(e),
+            None => Ok(qids),
+        }
+    }
+
+    // Everything about adding a query with this qid but indexing it:
+    // its stats, fields, preheaters and required fields.
+    // Returns the document each clause matcher must index for it.
+    fn register_query(
+        &mut self,
+        q: &Query,
+        new_doc_id: Qid,
+        required_fields: &[&str],
+    ) -> Result<Vec<Document>, PercolatorError> {
         self.stats.n_queries += 1;
 
         // For stats only.
@@ -473,19 +1133,29 @@ impl PercolatorCore {
             );
         }
 
-        let mis = cnf_to_matchitems(&q, &self.config).collect_vec();
+        self.int_fields.extend(q.int_fields());
+
+        let mis = cnf_to_matchitems(q, &self.config).collect_vec();
 
         self.stats
             .clauses_per_query
             .add(usize_to_f64(mis.len()).map_err(|_| PercolatorError::TooManyClauses)?);
 
         if mis.len() > self.clause_matchers.len() {
+            log_warn!(
+                "Query {} has {} clauses, more than the {} clause matchers. \
+                 Its candidates will need checking (see n_clause_matchers).",
+                new_doc_id,
+                mis.len(),
+                self.clause_matchers.len()
+            );
             self.must_filter.insert(new_doc_id);
         }
 
         // Preheaters count per query.
         let mut n_preheaters: usize = 0;
         let mut seen_preheaters = std::mem::take(&mut self.seen_preheaters);
+        let mut docs = Vec::with_capacity(self.clause_matchers.len());
 
         let cms = self.clause_matchers.iter_mut();
         for (clause_matcher, mut match_item) in
@@ -514,21 +1184,22 @@ impl PercolatorCore {
                     seen_preheaters.insert(ph.id.clone());
                     // Maintain count of all preheaters.
                     self.stats.n_preheaters += 1;
+                    let n = self.stats.n_preheaters;
+                    if n >= PREHEATERS_WARNING && n.is_power_of_two() {
+                        log_warn!(
+                            "{} preheaters, all run on each percolated document. \
+                             Query {} added {}. See prefix_sizes and the stats.",
+                            n,
+                            new_doc_id,
+                            ph.id
+                        );
+                    }
                 }
 
-                clause_matcher.add_preheater(ph);
+                clause_matcher.add_preheater(ph, new_doc_id);
             }
 
-            clause_matcher
-                .positive_index
-                .index_document(&match_item.doc);
-
-            assert_eq!(
-                clause_matcher.positive_index.len(),
-                expected_index_len,
-                "Index length mismatch after adding query {}",
-                new_doc_id
-            );
+            docs.push(match_item.doc);
         }
 
         // Save the seen preheaters
@@ -538,8 +1209,13 @@ impl PercolatorCore {
             .preheaters_per_query
             .add(usize_to_f64(n_preheaters).map_err(|_| PercolatorError::TooManyPreheaters)?);
 
-        self.cnf_queries.push(q);
-        Ok(new_doc_id)
+        for field in required_fields {
+            self.required_fields
+                .entry((*field).into())
+                .or_default()
+                .insert(new_doc_id);
+        }
+        Ok(docs)
     }
 
     /// Removes a query from this percolator by Query ID.
@@ -553,19 +1229,201 @@ impl PercolatorCore {
             // Value was already marked as unindexed.
             return false;
         }
+        self.recent_matches.clear();
 
         for cm in self.clause_matchers.iter_mut() {
             cm.positive_index.unindex_docid(qid);
         }
 
+        // Preheaters no other query needs would only slow down percolation.
+        let dropped = self
+            .clause_matchers
+            .iter_mut()
+            .flat_map(|cm| cm.remove_preheaters_of(qid))
+            .collect_vec();
+        for id in dropped {
+            let still_used = self
+                .clause_matchers
+                .iter()
+                .any(|cm| cm.preheaters_qids.contains_key(&id));
+            if !still_used && self.seen_preheaters.remove(&id) {
+                self.stats.n_preheaters -= 1;
+                self.stats.fanouts.forget(&id);
+            }
+        }
+
         // must_filter is now useless.
         self.must_filter.remove(qid);
+        self.required_fields.retain(|_, qids| {
+            qids.remove(qid);
+            !qids.is_empty()
+        });
+        self.clear_expiry(qid);
         self.stats.n_queries_removed += 1;
         true
     }
 
+    // Rebuilds the indexes in place with the queries not removed only, and
+    // returns their new Qids by old Qid. The configuration, the traces and the
+    // retained documents stay, but the stats start again.
+    pub(crate) fn compact(&mut self) -> BTreeMap<Qid, Qid> {
+        let queries = std::mem::take(&mut self.cnf_queries);
+        let unindexed_qids = std::mem::take(&mut self.unindexed_qids);
+        let required_fields = std::mem::take(&mut self.required_fields);
+        let expiries = std::mem::take(&mut self.expiries);
+        self.expiring.clear();
+        self.int_fields.clear();
+        self.seen_preheaters.clear();
+        self.clause_matchers = (0..self.config.n_clause_matchers().get())
+            .map(|_| ClauseMatcher::from_config(&self.config))
+            .collect();
+        self.must_filter.clear();
+        self.stats = Default::default();
+        self.recent_matches.clear();
+
+        let mut remapping = BTreeMap::new();
+        // In Qid order, so the queries keep their relative order.
+        for (q, qid) in queries.into_iter().zip(0..) {
+            if unindexed_qids.contains(qid) {
+                continue;
+            }
+            let required = required_fields
+                .iter()
+                .filter(|(_, qids)| qids.contains(qid))
+                .map(|(field, _)| field.as_ref())
+                .collect_vec();
+            let new_qid = self
+                .add_checked_query(q, &required)
+                .expect("Can index same query");
+            if let Some(at) = expiries.get(&qid) {
+                self.set_expiry(new_qid, *at);
+            }
+            remapping.insert(qid, new_qid);
+        }
+        remapping
+    }
+
+    // From this time on, the query is skipped at percolation.
+    pub(crate) fn set_expiry(&mut self, qid: Qid, at: SystemTime) {
+        self.clear_expiry(qid);
+        self.recent_matches.clear();
+        self.expiries.insert(qid, at);
+        self.expiring.entry(at).or_default().insert(qid);
+    }
+
+    fn clear_expiry(&mut self, qid: Qid) {
+        if let Some(at) = self.expiries.remove(&qid)
+            && let Some(qids) = self.expiring.get_mut(&at)
+        {
+            qids.remove(qid);
+            if qids.is_empty() {
+                self.expiring.remove(&at);
+            }
+        }
+    }
+
+    pub(crate) fn expiry(&self, qid: Qid) -> Option<SystemTime> {
+        self.expiries.get(&qid).copied()
+    }
+
+    // The queries expiring strictly before this time.
+    pub(crate) fn expiring_before(&self, at: SystemTime) -> RoaringBitmap {
+        let mut qids = RoaringBitmap::new();
+        for expiring in self.expiring.range(..at).map(|(_, qids)| qids) {
+            qids |= expiring;
+        }
+        qids
+    }
+
+    // The queries expired by now.
+    fn expired_qids(&self) -> RoaringBitmap {
+        let mut qids = RoaringBitmap::new();
+        if !self.expiring.is_empty() {
+            for expired in self
+                .expiring
+                .range(..=SystemTime::now())
+                .map(|(_, qids)| qids)
+            {
+                qids |= expired;
+            }
+        }
+        qids
+    }
+
+    // The fields the query requires.
+    pub(crate) fn required_fields(&self, qid: Qid) -> Vec<OurStr> {
+        self.required_fields
+            .iter()
+            .filter(|(_, qids)| qids.contains(qid))
+            .map(|(field, _)| field.clone())
+            .collect()
+    }
+
+    // The ids of the preheaters the query added, in lexicographic order.
+    // Clauses beyond the clause matchers add none, as they are not indexed.
+    pub(crate) fn preheaters_for(&self, qid: Qid) -> Vec<OurStr> {
+        self.clause_matchers
+            .iter()
+            .flat_map(|cm| &cm.preheaters_qids)
+            .filter(|(_, qids)| qids.contains(qid))
+            .map(|(id, _)| id.clone())
+            .sorted()
+            .dedup()
+            .collect()
+    }
+
+    // All the preheaters applied at percolation time, with the number of
+    // live queries that added them.
+    pub(crate) fn preheaters(&self) -> BTreeMap<OurStr, usize> {
+        let mut qids: BTreeMap<OurStr, RoaringBitmap> = BTreeMap::new();
+        for (id, cm_qids) in self
+            .clause_matchers
+            .iter()
+            .flat_map(|cm| &cm.preheaters_qids)
+        {
+            *qids.entry(id.clone()).or_default() |= cm_qids;
+        }
+        qids.into_iter()
+            .map(|(id, qids)| (id, qids.len() as usize))
+            .collect()
+    }
+
+    // All the indexed (field, value)s, with the number of live
+    // queries indexed with them. Without the match all padding.
+    pub(crate) fn terms(&self) -> BTreeMap<(OurStr, OurStr), usize> {
+        let mut qids: BTreeMap<(OurStr, OurStr), RoaringBitmap> = BTreeMap::new();
+        for (field, value, cm_qids) in self
+            .clause_matchers
+            .iter()
+            .flat_map(|cm| cm.positive_index.terms_iter())
+            .filter(|(f, v, _)| (f.as_ref(), v.as_ref()) != MATCH_ALL)
+        {
+            *qids.entry((field, value)).or_default() |= cm_qids;
+        }
+        qids.into_iter()
+            .map(|(fv, qids)| (fv, qids.len() as usize))
+            .collect()
+    }
+
+    // The queries requiring a field the document does not have,
+    // and the expired ones.
+    fn skipped_qids(&self, d: &impl DocumentView) -> RoaringBitmap {
+        let mut skipped = self.expired_qids();
+        for (field, qids) in &self.required_fields {
+            if !d.has_field(field) {
+                skipped |= qids;
+            }
+        }
+        skipped
+    }
+
     /// Safe version of get_query. Will be None if no such query exists.
     pub(crate) fn safe_get_query(&self, qid: Qid) -> Option<&Query> {
+        self.safe_get_shared_query(qid).map(|q| q.as_ref())
+    }
+
+    // The stored query itself, to share it again.
+    pub(crate) fn safe_get_shared_query(&self, qid: Qid) -> Option<&OurRc<Query>> {
         if !self.unindexed_qids.contains(qid) {
             self.cnf_queries.get(qid as usize)
         } else {
@@ -575,34 +1433,313 @@ impl PercolatorCore {
 
     ///
     /// Percolate a document through this, returning an iterator
-    /// of the matching query IDs
+    /// of the matching query IDs. The document can be borrowed,
+    /// like a `DocumentRef`, and is only copied to be kept.
     ///
-    pub(crate) fn percolate<'b>(&self, d: &'b Document) -> impl Iterator<Item = Qid> + use<'b, '_> {
-        self.bs_from_document(d).into_iter().filter(move |&qid| {
-            !self.must_filter.contains(qid) || self.cnf_queries[qid as usize].matches(d)
-        })
+    pub(crate) fn percolate<'b, D: DocumentView>(
+        &self,
+        d: &'b D,
+    ) -> impl Iterator<Item = Qid> + use<'b, '_, D> {
+        match self.empty_document_qids(d) {
+            Some(qids) => Either::Left(qids.into_iter()),
+            None => Either::Right(self.percolate_non_empty(d)),
+        }
+    }
+
+    /// Percolate a document through the given queries only.
+    /// The match cache holds the matches of all the queries,
+    /// so this does not use it.
+    pub(crate) fn percolate_subset<'b>(
+        &self,
+        d: &'b Document,
+        subset: &RoaringBitmap,
+    ) -> impl Iterator<Item = Qid> + use<'b, '_> {
+        match self.empty_document_qids(d) {
+            Some(qids) => Either::Left((qids & subset).into_iter()),
+            None => Either::Right(self.percolate_any(d, Some(subset))),
+        }
+    }
+
+    fn percolate_non_empty<'b, D: DocumentView>(
+        &self,
+        d: &'b D,
+    ) -> impl Iterator<Item = Qid> + use<'b, '_, D> {
+        if !self.recent_matches.is_enabled() {
+            return Either::Right(self.percolate_any(d, None));
+        }
+        let qids = match self.recent_matches.get(d) {
+            Some(mut qids) => {
+                self.record_percolation(d);
+                // Some queries can have expired since.
+                let expired = self.expired_qids();
+                if !expired.is_empty() {
+                    qids.retain(|qid| !expired.contains(*qid));
+                }
+                qids
+            }
+            None => {
+                let qids = self.percolate_any(d, None).collect_vec();
+                self.recent_matches.put(&d.to_owned_document(), &qids);
+                qids
+            }
+        };
+        Either::Left(qids.into_iter())
+    }
+
+    // What percolating a document leaves behind, even when its matches are cached.
+    fn record_percolation(&self, d: &impl DocumentView) {
+        if self.retained_docs.is_enabled() {
+            self.retained_docs
+                .push(&d.to_owned_document(), SystemTime::now());
+        }
+        if self.config.int_overflow == IntOverflow::Report {
+            let n_overflows = self.int_overflows(d).count() as u64;
+            self.stats
+                .n_int_overflows
+                .fetch_add(n_overflows, atomic::Ordering::Relaxed);
+        }
+    }
+
+    // The matches of an empty document, when they do not need percolating it.
+    fn empty_document_qids(&self, d: &impl DocumentView) -> Option<RoaringBitmap> {
+        if !d.is_empty() {
+            return None;
+        }
+        match self.config.empty_documents {
+            EmptyDocuments::Percolate => None,
+            EmptyDocuments::NoMatch => Some(RoaringBitmap::new()),
+            // Queries without clauses are indexed at the match all
+            // of every clause matcher, and need no checking.
+            EmptyDocuments::MatchAllOnly => {
+                let match_all = Clause::match_all();
+                let mut qids = self
+                    .clause_matchers
+                    .iter()
+                    .map(|cm| clause_docs_from_idx(&match_all, &cm.positive_index))
+                    .reduce_inplace(|acc, b| {
+                        *acc &= b;
+                        acc.is_empty()
+                    })
+                    .unwrap_or_default();
+                qids -= &self.must_filter;
+                qids -= self.skipped_qids(d);
+                Some(qids)
+            }
+        }
+    }
+
+    // Only through the queries of the subset, if any.
+    fn percolate_any<'b, D: DocumentView>(
+        &self,
+        d: &'b D,
+        subset: Option<&RoaringBitmap>,
+    ) -> impl Iterator<Item = Qid> + use<'b, '_, D> {
+        let start = self.traces.is_enabled().then(Instant::now);
+        self.record_percolation(d);
+        let n_live = self.cnf_queries.len() as u64 - self.unindexed_qids.len();
+        let skipped = self.skipped_qids(d);
+        let digest = start.map(|_| d.content_hash());
+        let d = self.int_view_of(d);
+
+        let (matches, n_candidates) = if n_live < self.config.linear_threshold as u64 {
+            // Few enough queries to just check them all.
+            let qids = match subset {
+                Some(subset) => Either::Left(subset.clone().into_iter()),
+                None => Either::Right(0..self.cnf_queries.len() as Qid),
+            };
+            let matches = qids.filter(move |&qid| {
+                !self.unindexed_qids.contains(qid)
+                    && !skipped.contains(qid)
+                    && self
+                        .cnf_queries
+                        .get(qid as usize)
+                        .is_some_and(|q| q.matches_view(&d))
+            });
+            (Either::Left(matches), n_live)
+        } else {
+            let mut candidates = self.bs_from_document(&d);
+            candidates -= skipped;
+            if let Some(subset) = subset {
+                candidates &= subset;
+            }
+            let n_candidates = candidates.len();
+            let matches = candidates.into_iter().filter(move |&qid| {
+                !self.must_filter.contains(qid) || self.cnf_queries[qid as usize].matches_view(&d)
+            });
+            (Either::Right(matches), n_candidates)
+        };
+
+        match start.zip(digest) {
+            Some((start, digest)) => Either::Left(Traced::new(
+                matches,
+                &self.traces,
+                start,
+                digest,
+                n_candidates,
+            )),
+            None => Either::Right(matches),
+        }
+    }
+
+    // The clamped values of the document overflowing the integer
+    // comparisons of their fields.
+    fn int_overflows<'a>(&'a self, d: &'a impl DocumentView) -> impl Iterator<Item = i64> + 'a {
+        self.int_fields
+            .iter()
+            .flat_map(move |field| d.str_values(field).into_iter().flatten())
+            .filter_map(clamped_int)
+    }
+
+    // The document as the integer comparisons see it: with its
+    // overflowing values clamped, if configured so.
+    pub(crate) fn int_view<'b>(&self, d: &'b Document) -> Cow<'b, Document> {
+        self.clamped(d).map_or(Cow::Borrowed(d), Cow::Owned)
+    }
+
+    // Same, for any document. Only clamping copies it.
+    fn int_view_of<'b, D: DocumentView>(&self, d: &'b D) -> Either<&'b D, Document> {
+        self.clamped(d).map_or(Either::Left(d), Either::Right)
+    }
+
+    // The copy of the document with its overflowing values clamped, if any.
+    fn clamped(&self, d: &impl DocumentView) -> Option<Document> {
+        if self.config.int_overflow != IntOverflow::Clamp || self.int_overflows(d).next().is_none()
+        {
+            return None;
+        }
+        let mut clamped = Document::new();
+        for (field, value) in d.str_field_values() {
+            match clamped_int(value).filter(|_| self.int_fields.contains(field)) {
+                Some(i) => clamped.with_value_mut(field, i.to_string()),
+                None => clamped.with_value_mut(field, value),
+            }
+        }
+        Some(clamped)
+    }
+
+    // Same as in the corpus report, without going through the queries.
+    pub(crate) fn must_filter_share(&self) -> f64 {
+        let n_live = self.cnf_queries.len() as u64 - self.unindexed_qids.len();
+        if n_live == 0 {
+            0.0
+        } else {
+            self.must_filter.len() as f64 / n_live as f64
+        }
+    }
+
+    pub(crate) fn check_quality(&self, thresholds: &QualityThresholds) -> Result<(), String> {
+        // None when percolations are not traced.
+        let traces = self.traces.is_enabled().then(|| self.traces.traces());
+        thresholds.check(self.must_filter_share(), traces.as_deref())
+    }
+
+    pub(crate) fn analyze_corpus(&self) -> CorpusReport {
+        let live_queries = self.queries().map(|(_, q)| q);
+        CorpusReport::from_queries(live_queries, self.must_filter.len() as usize)
+    }
+
+    // The queries not removed, in Qid order.
+    pub(crate) fn queries(&self) -> impl Iterator<Item = (Qid, &Query)> {
+        self.cnf_queries
+            .iter()
+            .zip(0..)
+            .filter(|(_, qid)| !self.unindexed_qids.contains(*qid))
+            .map(|(q, qid)| (qid, q.as_ref()))
+    }
+
+    // Retains the documents the other percolator retains.
+    pub(crate) fn retain_from(&self, other: &PercolatorCore) {
+        if self.retained_docs.is_enabled() {
+            for (at, d) in other.retained_docs.docs_since(SystemTime::UNIX_EPOCH) {
+                self.retained_docs.push(&d, at);
+            }
+        }
+    }
+
+    /// The retained documents matching the query, oldest first.
+    pub(crate) fn search(&self, q: &Query) -> Vec<Document> {
+        self.retained_docs.search(q)
+    }
+
+    /// The retained documents percolated at or after the given time, oldest first.
+    #[cfg(feature = "send")]
+    pub(crate) fn retained_since(&self, since: SystemTime) -> Vec<(SystemTime, Document)> {
+        self.retained_docs.docs_since(since)
+    }
+
+    /// The last percolations, oldest first.
+    pub(crate) fn recent_percolations(&self) -> Vec<PercolationTrace> {
+        self.traces.traces()
+    }
+
+    // Why the query matches the document or not, without percolating it.
+    pub(crate) fn explain(&self, qid: Qid, d: &Document) -> Option<Explanation> {
+        let q = self.safe_get_query(qid)?;
+        let is_expired = self.expired_qids().contains(qid);
+        let missing_fields = self
+            .required_fields(qid)
+            .iter()
+            .filter(|f| !d.has_field(f))
+            .map(|f| f.to_string())
+            .collect_vec();
+        let n_live = self.cnf_queries.len() as u64 - self.unindexed_qids.len();
+        let d = self.int_view(d);
+
+        let (source, is_candidate) = if n_live < self.config.linear_threshold as u64 {
+            (MatchSource::Linear, true)
+        } else {
+            let source = if self.must_filter.contains(qid) {
+                MatchSource::MustFilter
+            } else {
+                MatchSource::Index
+            };
+            (source, self.candidates(d.as_ref()).0.contains(qid))
+        };
+        let is_match = match self.empty_document_qids(d.as_ref()) {
+            Some(qids) => qids.contains(qid),
+            None => {
+                !is_expired
+                    && missing_fields.is_empty()
+                    && is_candidate
+                    && (source == MatchSource::Index || q.matches(&d))
+            }
+        };
+        Some(Explanation::new(
+            q,
+            &d,
+            is_match,
+            is_candidate,
+            source,
+            missing_fields,
+        ))
     }
 
     // Get a RoaringBitMap from the document, using the clause matchers.
-    fn bs_from_document(&self, d: &Document) -> RoaringBitmap {
+    fn bs_from_document(&self, d: &impl DocumentView) -> RoaringBitmap {
+        let (docs, fanouts) = self.candidates(d);
+        if !fanouts.is_empty() {
+            self.stats.fanouts.record(fanouts);
+        }
+        docs
+    }
+
+    // The candidate queries of the document, with the fanouts
+    // of the inexact preheaters for the stats.
+    fn candidates(&self, d: &impl DocumentView) -> (RoaringBitmap, Vec<(OurStr, u64, u64)>) {
         // This is where the magic happens.
-        // A clause is a disjunction of litterals.
-        let mut doc_clause = d.to_clause();
+        // The terms of the document, borrowed from it.
+        let mut terms = d.terms(self.config.dedup_values);
         // Add the match all to match all queries
-        doc_clause.add_termquery(TermQuery::match_all());
+        terms.push(MATCH_ALL);
 
-        self.clause_matchers
-            .iter()
-            .map(|ms| {
-                // Expand clause with all clause matcher pre-heaters.
-                // Before trying to match it against the index.
-                doc_clause = ms
-                    .preheaters
-                    .iter()
-                    .fold(std::mem::take(&mut doc_clause), |c, ph| ph.expand_clause(c));
+        // For the stats, how many of their queries the inexact
+        // preheaters make candidates.
+        let mut fanouts = vec![];
 
-                clause_docs_from_idx(&doc_clause, &ms.positive_index)
-            })
+        let docs = self
+            .clause_matchers
+            .iter()
+            .map(|ms| ms.candidates(&terms, &mut fanouts))
             .reduce_inplace(|acc, b| {
                 if acc.is_empty() {
                     true // Already empty. Stop the reduction.
@@ -611,12 +1748,15 @@ impl PercolatorCore {
                     acc.is_empty()
                 }
             })
-            .unwrap_or(RoaringBitmap::new())
+            .unwrap_or(RoaringBitmap::new());
+        (docs, fanouts)
     }
 }
 
 mod tests_cnf {
     use crate::models::percolator_core::MatchItem;
+    #[allow(unused_imports)]
+    use crate::models::queries::term::TermQuery;
 
     #[allow(dead_code)]
     fn is_match_all(mi: &MatchItem) -> bool {
@@ -669,10 +1809,7 @@ mod tests_cnf {
         let cnf_query2 = Query::from_termquery(term_query2);
         let combined = Query::from_and(vec![cnf_query1, cnf_query2]);
         // Structure would be:
-        assert_eq!(
-            combined.to_string(),
-            "(AND (OR field1=value1) (OR field2=value2))"
-        );
+        assert_eq!(combined.to_string(), "field1:value1 AND field2:value2");
         let mut mis = cnf_to_matchitems(&combined, &config);
         assert_eq!(
             mis.next().unwrap().doc,
@@ -705,7 +1842,7 @@ mod tests_cnf {
         // (x AND Y) OR Z:
         // The Z
         let q = ("X".has_value("x") & "Y".has_value("y")) | "Z".has_value("z");
-        assert_eq!(q.to_string(), "(AND (OR X=x Z=z) (OR Y=y Z=z))");
+        assert_eq!(q.to_string(), "(X:x OR Z:z) AND (Y:y OR Z:z)");
         let mut mis = cnf_to_matchitems(&q, &config);
         assert_eq!(
             mis.next().unwrap().doc,
@@ -0,0 +1,102 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::SystemTime;
+
+use crate::models::{cnf::Query, document::Document};
+
+/// The retained documents a query matches, replayed on a background thread.
+/// Iterate over it to get the matching documents with when they were
+/// percolated, oldest first, as soon as they are found.
+///
+/// Dropping it stops the replay.
+/// See [`crate::models::percolator::PercolatorUid::backfill`]
+#[derive(Debug)]
+pub struct Backfill {
+    matches: mpsc::Receiver<(SystemTime, Document)>,
+    n_replayed: Arc<AtomicUsize>,
+    n_documents: usize,
+}
+
+impl Backfill {
+    pub(crate) fn spawn(q: Query, docs: Vec<(SystemTime, Document)>) -> Self {
+        let (sender, matches) = mpsc::channel();
+        let n_replayed = Arc::new(AtomicUsize::new(0));
+        let n_documents = docs.len();
+
+        let progress = Arc::clone(&n_replayed);
+        thread::spawn(move || {
+            for (at, d) in docs {
+                // Nobody listens anymore.
+                if q.matches(&d) && sender.send((at, d)).is_err() {
+                    return;
+                }
+                progress.fetch_add(1, Ordering::Release);
+            }
+        });
+
+        Self {
+            matches,
+            n_replayed,
+            n_documents,
+        }
+    }
+
+    /// The number of documents replayed so far.
+    pub fn n_replayed(&self) -> usize {
+        self.n_replayed.load(Ordering::Acquire)
+    }
+
+    /// The number of documents to replay.
+    pub fn n_documents(&self) -> usize {
+        self.n_documents
+    }
+
+    /// True when all the documents were replayed.
+    /// Some matches can still be waiting to be iterated over.
+    pub fn is_done(&self) -> bool {
+        self.n_replayed() == self.n_documents
+    }
+}
+
+impl Iterator for Backfill {
+    type Item = (SystemTime, Document);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Fails once the thread is over and all matches were received.
+        self.matches.recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::CNFQueryable;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_backfill() {
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        let docs: Vec<(SystemTime, Document)> = vec![
+            (at(0), [("colour", "blue")].into()),
+            (at(1), [("colour", "red")].into()),
+            (at(2), [("colour", "blue"), ("taste", "sweet")].into()),
+        ];
+
+        let b = Backfill::spawn("colour".has_value("blue"), docs.clone());
+        assert_eq!(b.n_documents(), 3);
+        assert_eq!(
+            b.collect::<Vec<_>>(),
+            vec![docs[0].clone(), docs[2].clone()]
+        );
+
+        let mut b = Backfill::spawn("colour".has_value("green"), docs);
+        assert_eq!(b.next(), None);
+        assert!(b.is_done());
+        assert_eq!(b.n_replayed(), 3);
+
+        let mut b = Backfill::spawn("colour".has_value("blue"), vec![]);
+        assert!(b.is_done());
+        assert_eq!(b.next(), None);
+    }
+}
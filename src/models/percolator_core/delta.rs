@@ -0,0 +1,143 @@
+use std::fmt;
+
+use hstats::Hstats;
+
+use crate::models::percolator_core::PercolatorStats;
+
+/// What changed between two snapshots of [`PercolatorStats`],
+/// to report rates rather than lifetime totals.
+/// See [`PercolatorStats::delta`]
+#[derive(Debug, Clone)]
+pub struct PercolatorStatsDelta {
+    n_queries_added: usize,
+    n_queries_removed: usize,
+    n_preheaters_change: i64,
+    n_int_overflows: u64,
+    clauses_per_query: Hstats<f64>,
+    preheaters_per_query: Hstats<f64>,
+    prefix_lengths: Hstats<f64>,
+}
+
+impl PercolatorStatsDelta {
+    pub(crate) fn new(later: &PercolatorStats, earlier: &PercolatorStats) -> Self {
+        Self {
+            n_queries_added: later.n_queries.saturating_sub(earlier.n_queries),
+            n_queries_removed: later
+                .n_queries_removed
+                .saturating_sub(earlier.n_queries_removed),
+            n_preheaters_change: later.n_preheaters as i64 - earlier.n_preheaters as i64,
+            n_int_overflows: later
+                .n_int_overflows()
+                .saturating_sub(earlier.n_int_overflows()),
+            clauses_per_query: hstats_delta(&later.clauses_per_query, &earlier.clauses_per_query),
+            preheaters_per_query: hstats_delta(
+                &later.preheaters_per_query,
+                &earlier.preheaters_per_query,
+            ),
+            prefix_lengths: hstats_delta(&later.prefix_lengths, &earlier.prefix_lengths),
+        }
+    }
+
+    /// The number of queries added in between.
+    pub fn n_queries_added(&self) -> usize {
+        self.n_queries_added
+    }
+
+    /// The number of queries removed in between.
+    pub fn n_queries_removed(&self) -> usize {
+        self.n_queries_removed
+    }
+
+    /// The preheaters created in between, minus the ones that went
+    /// away with their last query.
+    pub fn n_preheaters_change(&self) -> i64 {
+        self.n_preheaters_change
+    }
+
+    /// The number of integer overflows in between.
+    pub fn n_int_overflows(&self) -> u64 {
+        self.n_int_overflows
+    }
+
+    /// Distribution of number of clauses per query added in between.
+    pub fn clauses_per_query(&self) -> &Hstats<f64> {
+        &self.clauses_per_query
+    }
+
+    /// Distribution of number of pre heating function per query added in between.
+    pub fn preheaters_per_query(&self) -> &Hstats<f64> {
+        &self.preheaters_per_query
+    }
+
+    /// Distribution of the prefix lengths of the queries added in between.
+    pub fn prefix_lengths(&self) -> &Hstats<f64> {
+        &self.prefix_lengths
+    }
+}
+
+impl fmt::Display for PercolatorStatsDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "🔎 N queries added={}, removed={}
+🔥 Preheaters change={:+}
+🔢 Integer overflows={}
+❓ Clauses per query:
+{}
+🔥 Preheaters per query:
+{}
+📏 Prefix lengths:
+{}",
+            self.n_queries_added,
+            self.n_queries_removed,
+            self.n_preheaters_change,
+            self.n_int_overflows,
+            self.clauses_per_query,
+            self.preheaters_per_query,
+            self.prefix_lengths,
+        )
+    }
+}
+
+// The values added to later since earlier, one per count of their bin.
+// Histograms only keep counts, so the values are the lower bounds of
+// their bins, which are exact for bins one integer wide.
+fn hstats_delta(later: &Hstats<f64>, earlier: &Hstats<f64>) -> Hstats<f64> {
+    let mut delta = Hstats::new(later.start(), later.end(), later.bin_count());
+    for ((lower, upper, n_later), (_, _, n_earlier)) in later.bins().into_iter().zip(earlier.bins())
+    {
+        // The underflow bin has no finite lower bound.
+        let value = if lower.is_finite() {
+            lower
+        } else {
+            upper - 1.0
+        };
+        for _ in 0..n_later.saturating_sub(n_earlier) {
+            delta.add(value);
+        }
+    }
+    delta
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hstats_delta() {
+        let mut earlier = Hstats::new(0.0, 50.0, 50);
+        earlier.add(1.0);
+        earlier.add(2.0);
+        let mut later = earlier.clone();
+        later.add(2.0);
+        later.add(3.0);
+        later.add(100.0);
+
+        let delta = hstats_delta(&later, &earlier);
+        assert_eq!(delta.count(), 3);
+        assert_eq!(delta.min(), 2.0);
+        // The overflow is at the end.
+        assert_eq!(delta.max(), 50.0);
+        assert_eq!(hstats_delta(&earlier, &earlier).count(), 0);
+    }
+}
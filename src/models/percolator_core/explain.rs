@@ -0,0 +1,251 @@
+use std::fmt;
+
+use crate::models::cnf::{Clause, Query};
+use crate::models::document::Document;
+
+/// How the percolator came to check a query against a document.
+/// See [`Explanation::source`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum MatchSource {
+    /// The clause matcher indexes alone decide whether it matches.
+    Index,
+    /// The clause matcher indexes only make it a candidate, which
+    /// is then checked against the document. Like queries
+    /// with negations, or with inexact preheaters.
+    MustFilter,
+    /// It is checked against the document, as the percolator has
+    /// fewer live queries than its linear threshold.
+    Linear,
+}
+
+impl fmt::Display for MatchSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchSource::Index => write!(f, "index"),
+            MatchSource::MustFilter => write!(f, "must_filter"),
+            MatchSource::Linear => write!(f, "linear"),
+        }
+    }
+}
+
+/// A literal of a clause matching a document.
+/// See [`ClauseExplanation::literals`]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LiteralMatch {
+    literal: String,
+    field: String,
+    values: Vec<String>,
+}
+
+impl LiteralMatch {
+    /// The literal, like `colour:blue` or `NOT price<10`.
+    pub fn literal(&self) -> &str {
+        &self.literal
+    }
+
+    /// The document field the literal is about.
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// The values of the field the literal matches on their own.
+    /// Empty for negated literals, and for literals that need
+    /// several values together, like field counts.
+    pub fn values(&self) -> &[String] {
+        &self.values
+    }
+}
+
+/// How one clause of a query matches a document.
+/// See [`Explanation::clauses`]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ClauseExplanation {
+    clause: String,
+    literals: Vec<LiteralMatch>,
+}
+
+impl ClauseExplanation {
+    fn new(c: &Clause, d: &Document) -> Self {
+        Self {
+            clause: c.to_string(),
+            literals: c
+                .literals()
+                .iter()
+                .filter(|l| l.matches(d))
+                .map(|l| LiteralMatch {
+                    literal: l.to_string(),
+                    field: l.query().sort_field().to_string(),
+                    values: l.matching_values(d).iter().map(|v| v.to_string()).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The clause, a disjunction of literals.
+    pub fn clause(&self) -> &str {
+        &self.clause
+    }
+
+    /// The literals of the clause matching the document.
+    pub fn literals(&self) -> &[LiteralMatch] {
+        &self.literals
+    }
+
+    /// True when any of its literals matches the document.
+    pub fn is_satisfied(&self) -> bool {
+        !self.literals.is_empty()
+    }
+}
+
+/// Why a query matches a document or not, clause by clause.
+/// See [`crate::models::percolator::PercolatorUid::explain`]
+///
+/// Clauses are the ones of the Conjunctive Normal Form of the query.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Explanation {
+    is_match: bool,
+    is_candidate: bool,
+    source: MatchSource,
+    missing_fields: Vec<String>,
+    clauses: Vec<ClauseExplanation>,
+}
+
+impl Explanation {
+    pub(crate) fn new(
+        q: &Query,
+        d: &Document,
+        is_match: bool,
+        is_candidate: bool,
+        source: MatchSource,
+        missing_fields: Vec<String>,
+    ) -> Self {
+        Self {
+            is_match,
+            is_candidate,
+            source,
+            missing_fields,
+            clauses: q
+                .clauses()
+                .iter()
+                .map(|c| ClauseExplanation::new(c, d))
+                .collect(),
+        }
+    }
+
+    /// True when percolating the document gives this query.
+    pub fn is_match(&self) -> bool {
+        self.is_match
+    }
+
+    /// True when the clause matcher indexes found this query for the document.
+    /// Always true with [`MatchSource::Linear`].
+    pub fn is_candidate(&self) -> bool {
+        self.is_candidate
+    }
+
+    /// How the percolator came to check this query.
+    pub fn source(&self) -> MatchSource {
+        self.source
+    }
+
+    /// The fields the query requires that the document does not have,
+    /// in lexicographic order. The query does not match when there are any.
+    pub fn missing_fields(&self) -> &[String] {
+        &self.missing_fields
+    }
+
+    /// All the clauses of the query, satisfied or not.
+    pub fn clauses(&self) -> &[ClauseExplanation] {
+        &self.clauses
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} ({})",
+            if self.is_match { "match" } else { "no match" },
+            self.source
+        )?;
+        if !self.missing_fields.is_empty() {
+            writeln!(f, "  missing fields: {}", self.missing_fields.join(", "))?;
+        }
+        for c in &self.clauses {
+            write!(f, "  {}: ", c.clause)?;
+            if c.literals.is_empty() {
+                writeln!(f, "unsatisfied")?;
+                continue;
+            }
+            let literals = c
+                .literals
+                .iter()
+                .map(|l| match l.values.as_slice() {
+                    [] => l.literal.clone(),
+                    vs => format!("{} <- {}={}", l.literal, l.field, vs.join("|")),
+                })
+                .collect::<Vec<_>>();
+            writeln!(f, "{}", literals.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::percolator_core::PercolatorCore;
+    use crate::prelude::{CNFQueryable, Percolator};
+
+    #[test]
+    fn test_explain() {
+        let mut p = PercolatorCore::default();
+        let qid = p
+            .safe_add_query("colour".has_value("blue") & "name".has_prefix("bu"))
+            .unwrap();
+        let d: Document = [("colour", "blue"), ("name", "burger"), ("name", "salad")].into();
+
+        let e = p.explain(qid, &d).unwrap();
+        assert!(e.is_match());
+        assert!(e.is_candidate());
+        assert_eq!(e.source(), MatchSource::Index);
+        assert_eq!(e.clauses().len(), 2);
+        assert!(e.clauses().iter().all(|c| c.is_satisfied()));
+        let prefix = &e.clauses()[1].literals()[0];
+        assert_eq!(prefix.field(), "name");
+        assert_eq!(prefix.values(), ["burger"]);
+
+        let e = p.explain(qid, &[("colour", "blue")].into()).unwrap();
+        assert!(!e.is_match());
+        assert!(!e.is_candidate());
+        assert!(!e.clauses()[1].is_satisfied());
+        assert!(e.to_string().contains("unsatisfied"));
+
+        assert!(p.explain(qid + 1, &d).is_none());
+    }
+
+    #[test]
+    fn test_explain_linear_required() {
+        let mut p = Percolator::builder().linear_threshold(10).build();
+        let qid = p
+            .index_query_uid_requiring(!"colour".has_value("red"), 0, &["taste"])
+            .unwrap();
+
+        let e = p.explain(&qid, &[("colour", "blue")].into()).unwrap();
+        assert_eq!(e.source(), MatchSource::Linear);
+        assert!(!e.is_match());
+        assert_eq!(e.missing_fields(), ["taste"]);
+        assert!(e.clauses()[0].is_satisfied());
+        assert!(e.clauses()[0].literals()[0].values().is_empty());
+
+        let e = p
+            .explain(&qid, &[("colour", "blue"), ("taste", "sweet")].into())
+            .unwrap();
+        assert!(e.is_match());
+        assert!(p.explain(&1, &[("colour", "blue")].into()).is_none());
+    }
+}
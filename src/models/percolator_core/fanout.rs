@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, TryLockError};
+
+use crate::models::types::OurStr;
+
+// A preheater is not selective enough when this share of the
+// queries needing it are candidates on average,
+const WARNING_FANOUT: f64 = 0.9;
+// after expanding at least this many documents,
+const WARNING_MIN_SAMPLES: u64 = 100;
+// for this many queries on average. Below, it hardly matters.
+const WARNING_MIN_QUERIES: f64 = 10.0;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Fanout {
+    n_samples: u64,
+    n_candidates: u64,
+    n_queries: u64,
+}
+
+/// A preheater whose synthetic values make most of the queries
+/// needing it candidates, which then all need checking.
+/// See [`crate::models::percolator_core::PercolatorStats::preheater_warnings`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreheaterWarning {
+    id: String,
+    mean_fanout: f64,
+    n_samples: u64,
+}
+
+impl PreheaterWarning {
+    /// The preheater id, like in [`crate::models::percolator::PercolatorUid::preheaters`]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The average share of the queries needing the preheater
+    /// that were candidates when it expanded a document.
+    pub fn mean_fanout(&self) -> f64 {
+        self.mean_fanout
+    }
+
+    /// The number of documents the preheater expanded.
+    pub fn n_samples(&self) -> u64 {
+        self.n_samples
+    }
+
+    /// A configuration change that could make the preheater more selective.
+    pub fn suggestion(&self) -> &'static str {
+        if self.id.starts_with("__PREFIX") || self.id.starts_with("__SUFFIX") {
+            "Add a prefix size closer to the length of its prefixes (see prefix_sizes)"
+        } else if self.id.starts_with("__NGRAM") {
+            "Use longer n-grams (see ngram_size)"
+        } else {
+            "Its queries may be too broad to gain from being indexed (see linear_threshold)"
+        }
+    }
+}
+
+impl fmt::Display for PreheaterWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {:.0}% of its queries are candidates over {} documents. {}.",
+            self.id,
+            self.mean_fanout * 100.0,
+            self.n_samples,
+            self.suggestion()
+        )
+    }
+}
+
+// How many of their queries inexact preheaters make candidates,
+// recorded while percolating.
+#[derive(Debug, Default)]
+pub(crate) struct FanoutStats {
+    fanouts: Mutex<HashMap<OurStr, Fanout>>,
+}
+
+impl Clone for FanoutStats {
+    fn clone(&self) -> Self {
+        let fanouts = self.fanouts.lock().unwrap_or_else(|e| e.into_inner());
+        Self {
+            fanouts: Mutex::new(fanouts.clone()),
+        }
+    }
+}
+
+impl FanoutStats {
+    // Records (preheater id, candidates, queries needing it) samples.
+    // Skipped when another percolation is recording, as a few
+    // samples more or less do not change the averages.
+    pub(crate) fn record(&self, samples: Vec<(OurStr, u64, u64)>) {
+        let mut fanouts = match self.fanouts.try_lock() {
+            Ok(fanouts) => fanouts,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return,
+        };
+        for (id, n_candidates, n_queries) in samples {
+            let fanout = fanouts.entry(id).or_default();
+            fanout.n_samples += 1;
+            fanout.n_candidates += n_candidates;
+            fanout.n_queries += n_queries;
+        }
+    }
+
+    // When the preheater is gone.
+    pub(crate) fn forget(&mut self, id: &OurStr) {
+        self.fanouts
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(id);
+    }
+
+    // Sorted by preheater id.
+    pub(crate) fn warnings(&self) -> Vec<PreheaterWarning> {
+        let fanouts = self.fanouts.lock().unwrap_or_else(|e| e.into_inner());
+        let mut warnings = fanouts
+            .iter()
+            .filter(|(_, f)| {
+                f.n_samples >= WARNING_MIN_SAMPLES
+                    && f.n_queries as f64 / f.n_samples as f64 >= WARNING_MIN_QUERIES
+            })
+            .map(|(id, f)| PreheaterWarning {
+                id: id.to_string(),
+                // Other preheaters can add the same synthetic values.
+                mean_fanout: (f.n_candidates as f64 / f.n_queries as f64).min(1.0),
+                n_samples: f.n_samples,
+            })
+            .filter(|w| w.mean_fanout >= WARNING_FANOUT)
+            .collect::<Vec<_>>();
+        warnings.sort_by(|a, b| a.id.cmp(&b.id));
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_warnings() {
+        let mut stats = FanoutStats::default();
+        let prefix: OurStr = "__PREFIX2__name".into();
+        let ngram: OurStr = "__NGRAM3__title".into();
+        let range: OurStr = "INT_RANGE_1_10__price".into();
+        let few: OurStr = "__PREFIX2__colour".into();
+        for _ in 0..WARNING_MIN_SAMPLES - 1 {
+            stats.record(vec![
+                (prefix.clone(), 19, 20),
+                (ngram.clone(), 2, 20),
+                (range.clone(), 50, 50),
+                (few.clone(), 2, 2),
+            ]);
+        }
+        // Not enough samples yet.
+        assert!(stats.warnings().is_empty());
+
+        stats.record(vec![
+            (prefix.clone(), 20, 20),
+            (ngram.clone(), 20, 20),
+            (range.clone(), 50, 50),
+            (few.clone(), 2, 2),
+        ]);
+        let warnings = stats.warnings();
+        assert_eq!(
+            warnings.iter().map(|w| w.id()).collect::<Vec<_>>(),
+            vec!["INT_RANGE_1_10__price", "__PREFIX2__name"]
+        );
+        assert_eq!(warnings[0].mean_fanout(), 1.0);
+        assert_eq!(warnings[1].n_samples(), WARNING_MIN_SAMPLES);
+        assert!(warnings[1].suggestion().contains("prefix_sizes"));
+        assert_eq!(
+            warnings[1].to_string(),
+            "__PREFIX2__name: 95% of its queries are candidates over 100 documents. \
+             Add a prefix size closer to the length of its prefixes (see prefix_sizes)."
+        );
+
+        stats.forget(&prefix);
+        assert_eq!(stats.warnings().len(), 1);
+    }
+}
@@ -0,0 +1,52 @@
+/// Roughly how many bytes a percolator takes on the heap, by part,
+/// to plan capacity.
+/// See [`crate::models::percolator::PercolatorUid::memory_usage`]
+///
+/// Bitmaps count for their serialized size, and query values for the
+/// length of their text. The IDs and payloads of the queries are not counted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MemoryReport {
+    pub(crate) queries: usize,
+    pub(crate) indexes: usize,
+    pub(crate) bitmaps: usize,
+    pub(crate) preheaters: usize,
+}
+
+impl MemoryReport {
+    /// The stored queries, including the removed ones until
+    /// the percolator is compacted. Shared queries count in full,
+    /// even if the application keeps them too.
+    pub fn queries(&self) -> usize {
+        self.queries
+    }
+
+    /// The clause matcher indexes, from the indexed values
+    /// to the queries having them.
+    pub fn indexes(&self) -> usize {
+        self.indexes
+    }
+
+    /// The other sets of queries, like the removed ones, the ones
+    /// requiring fields or the ones to check against the documents.
+    pub fn bitmaps(&self) -> usize {
+        self.bitmaps
+    }
+
+    /// The preheaters and the queries needing them. What their
+    /// expansion functions capture, like cells, is not counted.
+    pub fn preheaters(&self) -> usize {
+        self.preheaters
+    }
+
+    /// All of the above.
+    pub fn total(&self) -> usize {
+        self.queries + self.indexes + self.bitmaps + self.preheaters
+    }
+
+    /// This report as pretty printed JSON.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("A report is always valid JSON")
+    }
+}
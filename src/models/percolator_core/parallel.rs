@@ -0,0 +1,133 @@
+use std::time::Instant;
+
+use itertools::Itertools;
+use rayon::prelude::*;
+use roaring::RoaringBitmap;
+
+use crate::models::document::{Document, DocumentView, MATCH_ALL};
+use crate::models::percolator_core::{PercolatorCore, Qid};
+
+use super::trace::Traced;
+
+impl PercolatorCore {
+    /// Same as percolate, with the clause matchers and
+    /// the final checks of the candidates spread over rayon's threads.
+    pub(crate) fn percolate_par(&self, d: &Document) -> Vec<Qid> {
+        if let Some(qids) = self.empty_document_qids(d) {
+            return qids.into_iter().collect();
+        }
+        if self.recent_matches.is_enabled()
+            && let Some(qids) = self.recent_matches.get(d)
+        {
+            self.record_percolation(d);
+            return qids;
+        }
+
+        let start = self.traces.is_enabled().then(Instant::now);
+        self.record_percolation(d);
+        let n_live = self.cnf_queries.len() as u64 - self.unindexed_qids.len();
+        let skipped = self.skipped_qids(d);
+        let view = self.int_view(d);
+
+        let (qids, n_candidates) = if n_live < self.config.linear_threshold as u64 {
+            let qids = (0..self.cnf_queries.len() as Qid)
+                .into_par_iter()
+                .filter(|&qid| {
+                    !self.unindexed_qids.contains(qid)
+                        && !skipped.contains(qid)
+                        && self.cnf_queries[qid as usize].matches(&view)
+                })
+                .collect::<Vec<_>>();
+            (qids, n_live)
+        } else {
+            let mut candidates = self.bs_from_document_par(view.as_ref());
+            candidates -= skipped;
+            let n_candidates = candidates.len();
+            // Collecting keeps the order of the candidates.
+            let qids = candidates
+                .iter()
+                .collect_vec()
+                .into_par_iter()
+                .filter(|&qid| {
+                    !self.must_filter.contains(qid) || self.cnf_queries[qid as usize].matches(&view)
+                })
+                .collect::<Vec<_>>();
+            (qids, n_candidates)
+        };
+
+        if let Some(start) = start {
+            Traced::new(
+                qids.iter().copied(),
+                &self.traces,
+                start,
+                d.content_hash(),
+                n_candidates,
+            )
+            .for_each(drop);
+        }
+        if self.recent_matches.is_enabled() {
+            self.recent_matches.put(d, &qids);
+        }
+        qids
+    }
+
+    // Like bs_from_document, with the clause matchers
+    // expanding the document terms side by side.
+    fn bs_from_document_par(&self, d: &impl DocumentView) -> RoaringBitmap {
+        let mut terms = d.terms(self.config.dedup_values);
+        terms.push(MATCH_ALL);
+
+        let (docs, fanouts): (Vec<_>, Vec<_>) = self
+            .clause_matchers
+            .par_iter()
+            .map(|ms| {
+                let mut fanouts = vec![];
+                let docs = ms.candidates(&terms, &mut fanouts);
+                (docs, fanouts)
+            })
+            .unzip();
+
+        let fanouts = fanouts.into_iter().flatten().collect_vec();
+        if !fanouts.is_empty() {
+            self.stats.fanouts.record(fanouts);
+        }
+        docs.into_iter()
+            .reduce(|mut acc, b| {
+                acc &= b;
+                acc
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::percolator_core::PercolatorConfig;
+    use crate::prelude::Query;
+
+    #[test]
+    fn test_same_as_percolate() {
+        let mut rng = rand::rng();
+        for linear_threshold in [0, 1000] {
+            let mut p = PercolatorCore::from_config(PercolatorConfig {
+                linear_threshold,
+                ..Default::default()
+            });
+            let queries = (0..300).map(|_| Query::random(&mut rng)).collect_vec();
+            for q in &queries {
+                p.safe_add_query(q.clone()).unwrap();
+            }
+            p.remove_qid(3);
+            for q in &queries {
+                for d in q
+                    .example_documents(&mut rng, 2)
+                    .into_iter()
+                    .chain(q.example_near_misses(&mut rng, 2))
+                {
+                    assert_eq!(p.percolate_par(&d), p.percolate(&d).collect_vec());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use crate::models::percolator_core::PercolationTrace;
+
+/// Limits for [`crate::models::percolator::PercolatorUid::assert_quality`], to
+/// catch configuration regressions after loading queries.
+/// There are no limits by default.
+///
+/// Example:
+/// ```
+/// use mokaccino::models::percolator_core::QualityThresholds;
+///
+/// let thresholds = QualityThresholds::default()
+///     .max_must_filter_share(0.2)
+///     .max_candidates_per_match(3.0);
+/// assert_eq!(thresholds.must_filter_share(), Some(0.2));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QualityThresholds {
+    must_filter_share: Option<f64>,
+    candidates_per_match: Option<f64>,
+}
+
+impl QualityThresholds {
+    /// The highest share of live queries, between 0 and 1, that can need
+    /// checking against the documents after being found by the index.
+    /// See [`crate::models::percolator_core::CorpusReport::must_filter_share`]
+    pub fn max_must_filter_share(mut self, share: f64) -> Self {
+        self.must_filter_share = Some(share);
+        self
+    }
+
+    /// The highest number of candidate queries per actual match, over the
+    /// recent percolations. This needs tracing, see
+    /// [`crate::models::percolator::PercBuilder::trace_size`]
+    pub fn max_candidates_per_match(mut self, n: f64) -> Self {
+        self.candidates_per_match = Some(n);
+        self
+    }
+
+    /// The must filter share limit, if any.
+    pub fn must_filter_share(&self) -> Option<f64> {
+        self.must_filter_share
+    }
+
+    /// The candidates per match limit, if any.
+    pub fn candidates_per_match(&self) -> Option<f64> {
+        self.candidates_per_match
+    }
+
+    // All the exceeded limits, one per line.
+    pub(crate) fn check(
+        &self,
+        must_filter_share: f64,
+        traces: Option<&[PercolationTrace]>,
+    ) -> Result<(), String> {
+        let mut errors = vec![];
+        if let Some(max) = self
+            .must_filter_share
+            .filter(|max| must_filter_share > *max)
+        {
+            errors.push(format!(
+                "{:.0}% of the queries need checking, more than {:.0}%",
+                must_filter_share * 100.0,
+                max * 100.0
+            ));
+        }
+        if let Some(max) = self.candidates_per_match {
+            match traces {
+                None => errors.push(
+                    "Cannot check the candidates per match without tracing (see trace_size)"
+                        .to_string(),
+                ),
+                Some(traces) => {
+                    let n_candidates = traces.iter().map(|t| t.n_candidates()).sum::<u64>();
+                    // Candidates without any match are the worst.
+                    let n_matches = traces.iter().map(|t| t.n_matches()).sum::<usize>().max(1);
+                    let per_match = n_candidates as f64 / n_matches as f64;
+                    if per_match > max {
+                        errors.push(format!(
+                            "{:.1} candidates per match over {} percolations, more than {:.1}",
+                            per_match,
+                            traces.len(),
+                            max
+                        ));
+                    }
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::{CNFQueryable, Percolator};
+
+    #[test]
+    fn test_assert_quality() {
+        let none = QualityThresholds::default();
+        let strict = QualityThresholds::default()
+            .max_must_filter_share(0.0)
+            .max_candidates_per_match(1.0);
+
+        // Nothing to judge.
+        let p = Percolator::builder().trace_size(10).build();
+        assert!(p.assert_quality(&strict).is_ok());
+
+        // Regexes always need checking.
+        let re = |s: &str| "name".has_regex(&regex::Regex::new(s).unwrap());
+        let mut p = Percolator::builder().trace_size(10).build();
+        p.add_query("name".has_value("john"));
+        p.add_query(re("jo.*n"));
+        p.add_query("name".has_value("zz"));
+        p.add_query(re("zz.*"));
+        assert!(p.assert_quality(&none).is_ok());
+        assert_eq!(
+            p.assert_quality(&strict).unwrap_err(),
+            "50% of the queries need checking, more than 0%"
+        );
+        assert!(
+            p.assert_quality(&QualityThresholds::default().max_must_filter_share(0.5))
+                .is_ok()
+        );
+
+        // 2 candidates and matches, then 1 more candidate without a match.
+        assert_eq!(p.percolate(&[("name", "john")].into()).count(), 2);
+        assert_eq!(p.percolate(&[("name", "jo")].into()).count(), 0);
+        let err = p
+            .assert_quality(&QualityThresholds::default().max_candidates_per_match(1.2))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "1.5 candidates per match over 2 percolations, more than 1.2"
+        );
+        assert!(
+            p.assert_quality(&QualityThresholds::default().max_candidates_per_match(1.5))
+                .is_ok()
+        );
+
+        // Both, one per line.
+        assert_eq!(p.assert_quality(&strict).unwrap_err().lines().count(), 2);
+
+        let p = Percolator::default();
+        assert_eq!(
+            p.assert_quality(&strict).unwrap_err(),
+            "Cannot check the candidates per match without tracing (see trace_size)"
+        );
+    }
+}
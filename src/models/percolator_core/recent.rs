@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use hashbrown::HashMap;
+
+use crate::models::document::{Document, DocumentView};
+use crate::models::percolator_core::Qid;
+
+// The cached matches of the documents, by content hash,
+// and the least recently used ones first.
+#[derive(Debug, Default)]
+struct Recent {
+    // The document is kept to rule out hash collisions.
+    entries: HashMap<u64, (Document, Vec<Qid>, u64)>,
+    by_use: BTreeMap<u64, u64>,
+    clock: u64,
+}
+
+// The matches of the last percolated documents, so percolating
+// the same document again does not find them again.
+// It must be cleared when the queries change.
+#[derive(Debug, Default)]
+pub(crate) struct RecentMatches {
+    size: usize,
+    recent: Mutex<Recent>,
+}
+
+impl RecentMatches {
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            size,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.size > 0
+    }
+
+    // The matches of a document with the same content, if cached.
+    pub(crate) fn get(&self, d: &impl DocumentView) -> Option<Vec<Qid>> {
+        // A poisoned lock only means a panic happened while caching.
+        let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        let recent = &mut *recent;
+        let hash = d.content_hash();
+        let (cached, qids, last_use) = recent.entries.get_mut(&hash)?;
+        if !cached.same_content(d) {
+            return None;
+        }
+        recent.by_use.remove(last_use);
+        recent.clock += 1;
+        *last_use = recent.clock;
+        recent.by_use.insert(recent.clock, hash);
+        Some(qids.clone())
+    }
+
+    pub(crate) fn put(&self, d: &Document, qids: &[Qid]) {
+        let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        let recent = &mut *recent;
+        let hash = d.content_hash();
+        recent.clock += 1;
+        if let Some((_, _, last_use)) = recent
+            .entries
+            .insert(hash, (d.clone(), qids.to_vec(), recent.clock))
+        {
+            recent.by_use.remove(&last_use);
+        }
+        recent.by_use.insert(recent.clock, hash);
+        while recent.entries.len() > self.size {
+            let Some((_, oldest)) = recent.by_use.pop_first() else {
+                break;
+            };
+            recent.entries.remove(&oldest);
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        let recent = self.recent.get_mut().unwrap_or_else(|e| e.into_inner());
+        *recent = Recent::default();
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.recent
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lru() {
+        let doc = |v: &str| Document::from([("colour", v)]);
+        let mut recent = RecentMatches::new(2);
+        assert!(recent.is_enabled());
+        assert!(!RecentMatches::new(0).is_enabled());
+
+        assert_eq!(recent.get(&doc("blue")), None);
+        recent.put(&doc("blue"), &[1]);
+        recent.put(&doc("red"), &[2, 3]);
+        assert_eq!(recent.get(&doc("blue")), Some(vec![1]));
+        // Red is now the least recently used.
+        recent.put(&doc("green"), &[]);
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent.get(&doc("red")), None);
+        assert_eq!(recent.get(&doc("blue")), Some(vec![1]));
+        assert_eq!(recent.get(&doc("green")), Some(vec![]));
+
+        // Replacing keeps the size.
+        recent.put(&doc("green"), &[4]);
+        assert_eq!(recent.get(&doc("green")), Some(vec![4]));
+        assert_eq!(recent.len(), 2);
+
+        recent.clear();
+        assert_eq!(recent.len(), 0);
+        assert_eq!(recent.get(&doc("blue")), None);
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+
+use hashbrown::HashSet;
+
+use crate::models::cnf::Query;
+use crate::models::types::OurStr;
+
+/// How one field is used by the queries of a percolator.
+/// See [`CorpusReport::fields`]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FieldReport {
+    n_queries: usize,
+    n_literals: usize,
+    n_distinct_literals: usize,
+}
+
+impl FieldReport {
+    /// The number of queries using this field.
+    pub fn n_queries(&self) -> usize {
+        self.n_queries
+    }
+
+    /// The number of literals on this field, across all queries.
+    pub fn n_literals(&self) -> usize {
+        self.n_literals
+    }
+
+    /// The number of different literals on this field.
+    /// `field:a`, `field:a*` and `NOT field:a` are three literals.
+    pub fn n_distinct_literals(&self) -> usize {
+        self.n_distinct_literals
+    }
+}
+
+/// A summary of the live queries of a percolator, to review its
+/// capacity and tune its configuration.
+/// See [`crate::models::percolator::PercolatorUid::analyze_corpus`]
+///
+/// Literals are counted in the Conjunctive Normal Form of the queries.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CorpusReport {
+    n_queries: usize,
+    n_clauses: usize,
+    n_literals: usize,
+    negation_ratio: f64,
+    n_geo_queries: usize,
+    must_filter_share: f64,
+    operators: BTreeMap<String, usize>,
+    fields: BTreeMap<String, FieldReport>,
+}
+
+// Zero when there is nothing to divide.
+fn ratio(n: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        n as f64 / total as f64
+    }
+}
+
+impl CorpusReport {
+    pub(crate) fn from_queries<'a>(
+        queries: impl Iterator<Item = &'a Query>,
+        n_must_filter: usize,
+    ) -> Self {
+        let mut report = Self::default();
+        let mut n_negated = 0;
+        let mut distinct_literals: BTreeMap<OurStr, HashSet<(&'static str, bool, OurStr)>> =
+            BTreeMap::new();
+
+        for q in queries {
+            report.n_queries += 1;
+            report.n_clauses += q.clauses().len();
+
+            let mut query_fields = HashSet::new();
+            let mut is_geo = false;
+            for l in q.clauses().iter().flat_map(|c| c.literals()) {
+                let lq = l.query();
+                report.n_literals += 1;
+                n_negated += usize::from(l.is_negated());
+                is_geo |= lq.is_geo();
+                *report.operators.entry(lq.kind().to_string()).or_default() += 1;
+
+                let field = lq.sort_field();
+                report
+                    .fields
+                    .entry(field.to_string())
+                    .or_default()
+                    .n_literals += 1;
+                distinct_literals.entry(field.clone()).or_default().insert((
+                    lq.kind(),
+                    l.is_negated(),
+                    lq.sort_term(),
+                ));
+                query_fields.insert(field);
+            }
+
+            for field in query_fields {
+                if let Some(f) = report.fields.get_mut(field.as_ref()) {
+                    f.n_queries += 1;
+                }
+            }
+            report.n_geo_queries += usize::from(is_geo);
+        }
+
+        for (field, literals) in distinct_literals {
+            if let Some(f) = report.fields.get_mut(field.as_ref()) {
+                f.n_distinct_literals = literals.len();
+            }
+        }
+        report.negation_ratio = ratio(n_negated, report.n_literals);
+        report.must_filter_share = ratio(n_must_filter, report.n_queries);
+        report
+    }
+
+    /// The number of live queries.
+    pub fn n_queries(&self) -> usize {
+        self.n_queries
+    }
+
+    /// The number of clauses of all the queries.
+    pub fn n_clauses(&self) -> usize {
+        self.n_clauses
+    }
+
+    /// The number of literals of all the queries.
+    pub fn n_literals(&self) -> usize {
+        self.n_literals
+    }
+
+    /// The share of negated literals, between 0 and 1.
+    pub fn negation_ratio(&self) -> f64 {
+        self.negation_ratio
+    }
+
+    /// The number of queries with at least one geographic literal.
+    pub fn n_geo_queries(&self) -> usize {
+        self.n_geo_queries
+    }
+
+    /// The share of queries that must be checked against the
+    /// documents after being found by the index, between 0 and 1.
+    /// The higher, the slower percolation gets.
+    pub fn must_filter_share(&self) -> f64 {
+        self.must_filter_share
+    }
+
+    /// The number of literals of each kind, like `term`, `prefix` or `llwithin`.
+    pub fn operators(&self) -> &BTreeMap<String, usize> {
+        &self.operators
+    }
+
+    /// How each field is used, by field name.
+    pub fn fields(&self) -> &BTreeMap<String, FieldReport> {
+        &self.fields
+    }
+
+    /// This report as pretty printed JSON.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("A report is always valid JSON")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::{CNFQueryable, Meters};
+
+    #[test]
+    fn test_report() {
+        let queries: Vec<Query> = vec![
+            "colour".has_value("blue") & !"taste".has_value("bitter"),
+            "colour".has_value("blue") | "colour".has_prefix("bl"),
+            "colour".has_value("green")
+                & "position".latlng_within(h3o::LatLng::new(48.86, 2.34).unwrap(), Meters(1000)),
+        ];
+        let report = CorpusReport::from_queries(queries.iter(), 2);
+
+        assert_eq!(report.n_queries(), 3);
+        assert_eq!(report.n_clauses(), 5);
+        assert_eq!(report.n_literals(), 6);
+        assert_eq!(report.negation_ratio(), 1.0 / 6.0);
+        assert_eq!(report.n_geo_queries(), 1);
+        assert_eq!(report.must_filter_share(), 2.0 / 3.0);
+        assert_eq!(
+            report.operators().iter().collect::<Vec<_>>(),
+            vec![
+                (&"llwithin".to_string(), &1),
+                (&"prefix".to_string(), &1),
+                (&"term".to_string(), &4)
+            ]
+        );
+
+        let colour = &report.fields()["colour"];
+        assert_eq!(colour.n_queries(), 3);
+        assert_eq!(colour.n_literals(), 4);
+        assert_eq!(colour.n_distinct_literals(), 3);
+        assert_eq!(report.fields()["taste"].n_queries(), 1);
+        assert_eq!(report.fields().len(), 3);
+
+        assert_eq!(
+            CorpusReport::from_queries(std::iter::empty(), 0),
+            CorpusReport::default()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json() {
+        let q = "colour".has_value("blue");
+        let json: serde_json::Value =
+            serde_json::from_str(&CorpusReport::from_queries([&q].into_iter(), 0).to_json())
+                .unwrap();
+        assert_eq!(json["n_queries"], 1);
+        assert_eq!(json["operators"]["term"], 1);
+        assert_eq!(json["fields"]["colour"]["n_distinct_literals"], 1);
+    }
+}
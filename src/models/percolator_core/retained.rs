@@ -0,0 +1,199 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use roaring::RoaringBitmap;
+
+use crate::models::cnf::Query;
+use crate::models::document::Document;
+use crate::models::index::{DocId, Index};
+
+// The last percolated documents with when they were percolated,
+// and an index of their values.
+#[derive(Debug, Default)]
+struct Retained {
+    docs: VecDeque<(SystemTime, Document)>,
+    // The DocId of the oldest document. The next ones follow.
+    first_id: DocId,
+    index: Index,
+}
+
+impl Retained {
+    fn doc_id(&self, i: usize) -> DocId {
+        self.first_id + i as DocId
+    }
+
+    // All the documents still there.
+    fn live_ids(&self) -> RoaringBitmap {
+        match self.docs.len() {
+            0 => RoaringBitmap::new(),
+            n => RoaringBitmap::from_sorted_iter(self.first_id..=self.doc_id(n - 1))
+                .expect("A range is sorted"),
+        }
+    }
+
+    // Starts over with fresh DocIds, for when they run out.
+    fn reindex(&mut self) {
+        self.index = Index::default();
+        self.first_id = 0;
+        for (_, d) in &self.docs {
+            self.index.index_document(d);
+        }
+    }
+}
+
+// A fixed size ring of the last percolated documents,
+// so they can be searched for the queries matching them.
+#[derive(Debug, Default)]
+pub(crate) struct RetainedDocs {
+    size: usize,
+    retained: Mutex<Retained>,
+}
+
+impl RetainedDocs {
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            size,
+            retained: Mutex::new(Retained {
+                docs: VecDeque::with_capacity(size),
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.size > 0
+    }
+
+    pub(crate) fn push(&self, d: &Document, at: SystemTime) {
+        // A poisoned lock only means a panic happened while pushing.
+        // The ring is still usable.
+        let mut retained = self.retained.lock().unwrap_or_else(|e| e.into_inner());
+        if retained.docs.len() == self.size
+            && let Some((_, oldest)) = retained.docs.pop_front()
+        {
+            let oldest_id = retained.first_id;
+            retained.index.unindex_document(oldest_id, &oldest);
+            retained.first_id += 1;
+        }
+        if retained.index.len() == DocId::MAX as usize {
+            retained.reindex();
+        }
+        retained.index.index_document(d);
+        retained.docs.push_back((at, d.clone()));
+    }
+
+    /// The retained documents percolated at or after the given time,
+    /// with when they were percolated, oldest first.
+    pub(crate) fn docs_since(&self, since: SystemTime) -> Vec<(SystemTime, Document)> {
+        let retained = self.retained.lock().unwrap_or_else(|e| e.into_inner());
+        retained
+            .docs
+            .iter()
+            .filter(|(at, _)| *at >= since)
+            .cloned()
+            .collect()
+    }
+
+    /// The retained documents matching the query, oldest first.
+    pub(crate) fn search(&self, q: &Query) -> Vec<Document> {
+        let retained = self.retained.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Only clauses of plain terms and term sets can be looked up
+        // in the index. The others are left to the final check.
+        let mut candidates = retained.live_ids();
+        for c in q.clauses() {
+            let fvs = c
+                .literals()
+                .iter()
+                .map(|l| l.query().exact_field_values().filter(|_| !l.is_negated()))
+                .collect::<Option<Vec<_>>>();
+            if let Some(fvs) = fvs {
+                let mut docs = RoaringBitmap::new();
+                for (field, term) in fvs.into_iter().flatten() {
+                    docs |= retained.index.docs_from_fv(&field, &term);
+                }
+                candidates &= docs;
+            }
+        }
+
+        candidates
+            .iter()
+            .map(|id| &retained.docs[(id - retained.first_id) as usize].1)
+            .filter(|d| q.matches(d))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::CNFQueryable;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_retained_docs() {
+        let ring = RetainedDocs::new(3);
+        assert!(ring.is_enabled());
+        assert!(!RetainedDocs::new(0).is_enabled());
+
+        let docs: Vec<Document> = vec![
+            [("colour", "blue")].into(),
+            [("colour", "red"), ("taste", "sweet")].into(),
+            [("colour", "blue"), ("taste", "bitter")].into(),
+            [("taste", "sweet")].into(),
+        ];
+        for (secs, d) in (0..).zip(&docs) {
+            ring.push(d, at(secs));
+        }
+
+        // The first one is gone.
+        assert_eq!(
+            ring.search(&"colour".has_value("blue")),
+            vec![docs[2].clone()]
+        );
+        assert_eq!(
+            ring.search(&"taste".has_value("sweet")),
+            vec![docs[1].clone(), docs[3].clone()]
+        );
+        assert_eq!(
+            ring.search(&!"colour".has_value("blue")),
+            vec![docs[1].clone(), docs[3].clone()]
+        );
+        assert_eq!(
+            ring.search(&("colour".has_prefix("re") | "taste".has_value("bitter"))),
+            vec![docs[1].clone(), docs[2].clone()]
+        );
+        assert!(ring.search(&"colour".has_value("green")).is_empty());
+        assert_eq!(
+            ring.search(&("colour".has_any_value(["red", "green"]) & "taste".has_field())),
+            vec![docs[1].clone()]
+        );
+
+        assert_eq!(
+            ring.docs_since(at(2)),
+            vec![(at(2), docs[2].clone()), (at(3), docs[3].clone())]
+        );
+        assert_eq!(ring.docs_since(UNIX_EPOCH).len(), 3);
+        assert!(ring.docs_since(at(4)).is_empty());
+    }
+
+    #[test]
+    fn test_reindex() {
+        let ring = RetainedDocs::new(2);
+        ring.push(&[("colour", "blue")].into(), at(0));
+        ring.push(&[("colour", "red")].into(), at(1));
+        ring.retained.lock().unwrap().reindex();
+        ring.push(&[("colour", "green")].into(), at(2));
+        assert_eq!(
+            ring.search(&"colour".has_value("red")),
+            vec![[("colour", "red")].into()]
+        );
+        assert!(ring.search(&"colour".has_value("blue")).is_empty());
+    }
+}
@@ -1,17 +1,18 @@
 use std::fmt;
 
-use crate::models::cnf::Clause;
 use crate::models::document::Document;
+use crate::models::queries::term::TermQuery;
 use crate::models::types::{OurRc, OurStr};
 
 #[cfg(feature = "send")]
-pub(crate) type ExpanderF = OurRc<dyn Fn(Clause) -> Clause + Send + Sync>;
+pub(crate) type ExpanderF = OurRc<dyn Fn(&[(&str, &str)]) -> Vec<TermQuery> + Send + Sync>;
 
 #[cfg(not(feature = "send"))]
-pub(crate) type ExpanderF = OurRc<dyn Fn(Clause) -> Clause>;
+pub(crate) type ExpanderF = OurRc<dyn Fn(&[(&str, &str)]) -> Vec<TermQuery>>;
 
 #[derive(Clone)]
-// Extends Clauses comming from percolated document with extra termqueries.
+// The extra termqueries of the (field,value) terms of a percolated document.
+// They are borrowed from it, so percolating never copies them.
 pub(crate) struct ClauseExpander(pub(crate) ExpanderF);
 impl std::fmt::Debug for ClauseExpander {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -33,6 +34,17 @@ pub(crate) struct PreHeater {
     pub(crate) must_filter: bool, // must_filter MUST be true when the clause expander is not exact.
 }
 
+// The values of the field among the terms of a document.
+pub(crate) fn field_terms<'a>(
+    terms: &'a [(&'a str, &'a str)],
+    field: &'a str,
+) -> impl Iterator<Item = &'a str> + 'a {
+    terms
+        .iter()
+        .filter(move |(f, _)| *f == field)
+        .map(|(_, v)| *v)
+}
+
 impl PreHeater {
     pub(crate) fn new(id: OurStr, ce: ClauseExpander) -> Self {
         Self {
@@ -43,8 +55,8 @@ impl PreHeater {
     }
 
     /// Shortcut to calling the contained clause expander.
-    pub(crate) fn expand_clause(&self, c: Clause) -> Clause {
-        self.expand_clause.0(c)
+    pub(crate) fn expand(&self, terms: &[(&str, &str)]) -> Vec<TermQuery> {
+        self.expand_clause.0(terms)
     }
 
     pub(crate) fn with_must_filter(mut self, new_bool: bool) -> Self {
@@ -95,7 +107,7 @@ mod tests {
 
     #[test]
     fn clause_expander() {
-        let e = ClauseExpander::new(OurRc::new(|c| c));
+        let e = ClauseExpander::new(OurRc::new(|_: &[(&str, &str)]| vec![]));
         let _ = format!("{:?}", e);
     }
 }
@@ -106,14 +118,14 @@ mod tests_tools {
 
     #[test]
     fn test_clause_expander() {
-        let e = ClauseExpander::new(OurRc::new(|c| c));
+        let e = ClauseExpander::new(OurRc::new(|_: &[(&str, &str)]| vec![]));
         let debug = format!("{:?}", e);
         assert!(debug.contains("_OPAQUE FUNCTION_"));
     }
 
     #[test]
     fn test_preheater_methods() {
-        let expander = ClauseExpander::new(OurRc::new(|c| c));
+        let expander = ClauseExpander::new(OurRc::new(|_: &[(&str, &str)]| vec![]));
         let ph = PreHeater::new("id".into(), expander.clone());
 
         assert!(!ph.must_filter);
@@ -135,7 +147,7 @@ mod tests_tools {
         assert!(mi.preheaters.is_empty());
         assert_eq!(mi.cost, 10);
 
-        let expander = ClauseExpander::new(OurRc::new(|c| c));
+        let expander = ClauseExpander::new(OurRc::new(|_: &[(&str, &str)]| vec![]));
         let ph = PreHeater::new("id".into(), expander);
 
         let mi2 = mi.with_preheater(ph);
@@ -0,0 +1,172 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::models::percolator_core::Qid;
+
+/// What happened during one percolation.
+/// See [`crate::models::percolator::PercolatorUid::recent_percolations`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PercolationTrace {
+    doc_digest: u64,
+    n_candidates: u64,
+    n_matches: usize,
+    duration: Duration,
+}
+
+impl PercolationTrace {
+    /// A digest of the percolated document, its
+    /// [`Document::content_hash`](crate::prelude::Document::content_hash).
+    /// The same (field,value) tuples give the same digest,
+    /// regardless of their insertion order.
+    pub fn doc_digest(&self) -> u64 {
+        self.doc_digest
+    }
+
+    /// The number of candidate queries found by the clause matchers,
+    /// before the final filtering.
+    pub fn n_candidates(&self) -> u64 {
+        self.n_candidates
+    }
+
+    /// The number of queries that were actually yielded.
+    /// This can be lower than the real number of matches if
+    /// the iterator was not consumed until the end.
+    pub fn n_matches(&self) -> usize {
+        self.n_matches
+    }
+
+    /// From the start of the percolation to the iterator
+    /// being exhausted (or dropped).
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+// A fixed size ring of the last percolations.
+#[derive(Debug, Default)]
+pub(crate) struct TraceRing {
+    size: usize,
+    traces: Mutex<VecDeque<PercolationTrace>>,
+}
+
+impl TraceRing {
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            size,
+            traces: Mutex::new(VecDeque::with_capacity(size)),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.size > 0
+    }
+
+    fn push(&self, trace: PercolationTrace) {
+        // A poisoned lock only means a panic happened while pushing.
+        // The ring is still usable.
+        let mut traces = self.traces.lock().unwrap_or_else(|e| e.into_inner());
+        if traces.len() == self.size {
+            traces.pop_front();
+        }
+        traces.push_back(trace);
+    }
+
+    /// Oldest first.
+    pub(crate) fn traces(&self) -> Vec<PercolationTrace> {
+        self.traces
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .cloned()
+            .collect()
+    }
+}
+
+// Wraps a percolation iterator to record it in the ring
+// when it's exhausted or dropped.
+pub(crate) struct Traced<'a, I> {
+    inner: I,
+    ring: &'a TraceRing,
+    doc_digest: u64,
+    n_candidates: u64,
+    n_matches: usize,
+    start: Instant,
+    end: Option<Instant>,
+}
+
+impl<'a, I> Traced<'a, I> {
+    pub(crate) fn new(
+        inner: I,
+        ring: &'a TraceRing,
+        start: Instant,
+        doc_digest: u64,
+        n_candidates: u64,
+    ) -> Self {
+        Self {
+            inner,
+            ring,
+            doc_digest,
+            n_candidates,
+            n_matches: 0,
+            start,
+            end: None,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Qid>> Iterator for Traced<'_, I> {
+    type Item = Qid;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.inner.next();
+        match next {
+            Some(_) => self.n_matches += 1,
+            None => {
+                self.end.get_or_insert_with(Instant::now);
+            }
+        }
+        next
+    }
+}
+
+impl<I> Drop for Traced<'_, I> {
+    fn drop(&mut self) {
+        let end = self.end.unwrap_or_else(Instant::now);
+        self.ring.push(PercolationTrace {
+            doc_digest: self.doc_digest,
+            n_candidates: self.n_candidates,
+            n_matches: self.n_matches,
+            duration: end - self.start,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring() {
+        let ring = TraceRing::new(2);
+        assert!(ring.is_enabled());
+        assert!(!TraceRing::new(0).is_enabled());
+
+        for i in 0..3 {
+            let mut t = Traced::new(vec![1, 2].into_iter(), &ring, Instant::now(), i, 10);
+            assert_eq!(t.next(), Some(1));
+        }
+
+        let traces = ring.traces();
+        assert_eq!(traces.len(), 2);
+        assert_eq!(traces[0].doc_digest(), 1);
+        assert_eq!(traces[1].doc_digest(), 2);
+        assert_eq!(traces[1].n_candidates(), 10);
+        // Only one was consumed.
+        assert_eq!(traces[1].n_matches(), 1);
+
+        let t = Traced::new(vec![1, 2].into_iter(), &ring, Instant::now(), 3, 2);
+        assert_eq!(t.count(), 2);
+        assert_eq!(ring.traces()[1].n_matches(), 2);
+    }
+}
@@ -0,0 +1,293 @@
+use std::{borrow::Borrow, fmt::Display};
+
+use crate::{
+    models::percolator::Percolate,
+    models::percolator_core::{PercolatorError, PercolatorStats},
+    prelude::{Document, Qid, Query},
+};
+
+/// A percolator that simply checks every query against each document.
+///
+/// It does not build any index, which makes it lighter and faster
+/// to build than a [`crate::prelude::PercolatorUid`] for small sets of queries,
+/// say less than a hundred. Its API mirrors the one of
+/// [`crate::prelude::PercolatorUid`], and queries are percolated in
+/// the order they were indexed in.
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+///
+/// let mut p = LinearPercolator::<&str>::default();
+/// p.index_query_uid("colour".has_value("blue"), "blue_things").unwrap();
+/// p.index_query_uid("colour".has_prefix("bl"), "bl_things").unwrap();
+///
+/// assert_eq!(
+///     p.percolate(&[("colour", "black")].into()).collect::<Vec<_>>(),
+///     vec!["bl_things"]
+/// );
+/// ```
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LinearPercolator<T> {
+    queries: Vec<(T, Query)>,
+    // Only used to hand out Qids.
+    next_qid: Qid,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    stats: PercolatorStats,
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for LinearPercolator<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(serde::Deserialize)]
+        struct Helper<T> {
+            queries: Vec<(T, Query)>,
+            next_qid: Qid,
+        }
+
+        // Rebuild the stats from the queries.
+        let helper = Helper::<T>::deserialize(deserializer)?;
+        let mut stats = PercolatorStats::default();
+        helper
+            .queries
+            .iter()
+            .for_each(|(_, q)| stats.record_addition(q));
+        Ok(Self {
+            queries: helper.queries,
+            next_qid: helper.next_qid,
+            stats,
+        })
+    }
+}
+
+// We cannot derive Default, because we dont
+// want to force T to implement Default.
+impl<T> Default for LinearPercolator<T> {
+    fn default() -> Self {
+        Self {
+            queries: Vec::new(),
+            next_qid: 0,
+            stats: PercolatorStats::default(),
+        }
+    }
+}
+
+impl<T> Display for LinearPercolator<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LinearPerc-{}Qs", self.queries.len())
+    }
+}
+
+/// When the type used is Qid, just use this
+/// and keep the same interface as the [`crate::prelude::Percolator`]
+impl LinearPercolator<Qid> {
+    // The unsafe version of `safe_add_query`
+    pub fn add_query(&mut self, q: Query) -> Qid {
+        self.safe_add_query(q).unwrap()
+    }
+
+    /// Adds a query to this percolator, reporting an error
+    /// when there are too many queries.
+    pub fn safe_add_query(&mut self, q: Query) -> Result<Qid, PercolatorError> {
+        let qid = self.next_qid;
+        self.next_qid = qid.checked_add(1).ok_or(PercolatorError::TooManyQueries)?;
+        self.stats.record_addition(&q);
+        self.queries.push((qid, q));
+        Ok(qid)
+    }
+
+    // Remove the given Qid from this Percolator.
+    // This is just a shortcut to remove_uid where T = Qid
+    pub fn remove_qid(&mut self, qid: Qid) -> bool {
+        self.remove_uid(&qid)
+    }
+}
+
+impl<T> LinearPercolator<T>
+where
+    T: std::cmp::Eq,
+{
+    /// Index the given query with the user provided ID.
+    ///
+    /// You can supply the same ID to override an existing query.
+    /// This never fails, but returns a `Result` like
+    /// [`crate::prelude::PercolatorUid::index_query_uid`].
+    pub fn index_query_uid(&mut self, q: Query, uid: T) -> Result<T, PercolatorError>
+    where
+        T: Clone,
+    {
+        self.remove_uid(&uid);
+        self.stats.record_addition(&q);
+        self.queries.push((uid.clone(), q));
+        Ok(uid)
+    }
+
+    /// Removes the given User provided ID from this percolator.
+    /// True if it was effectively removed, false if it was absent.
+    pub fn remove_uid<Q>(&mut self, uid: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        match self.queries.iter().position(|(u, _)| u.borrow() == uid) {
+            Some(i) => {
+                self.queries.remove(i);
+                self.stats.record_removal();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_query<Q>(&self, uid: &Q) -> &Query
+    where
+        T: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.safe_get_query(uid).unwrap()
+    }
+
+    pub fn safe_get_query<Q>(&self, uid: &Q) -> Option<&Query>
+    where
+        T: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.queries
+            .iter()
+            .find(|(u, _)| u.borrow() == uid)
+            .map(|(_, q)| q)
+    }
+
+    /// The number of queries in this percolator.
+    pub fn len(&self) -> usize {
+        self.queries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queries.is_empty()
+    }
+
+    pub fn stats(&self) -> &PercolatorStats {
+        &self.stats
+    }
+
+    ///
+    /// An iterator of the matching ref of query IDs given the Document.
+    ///
+    pub fn percolate_ref<'b>(&self, d: &'b Document) -> impl Iterator<Item = &T> + use<'b, '_, T> {
+        self.queries
+            .iter()
+            .filter(|(_, q)| q.matches(d))
+            .map(|(uid, _)| uid)
+    }
+}
+
+impl<T> LinearPercolator<T>
+where
+    T: std::cmp::Eq + Copy,
+{
+    ///
+    /// An iterator of the matching queries user provided IDs given the Document.
+    ///
+    pub fn percolate<'b>(&self, d: &'b Document) -> impl Iterator<Item = T> + use<'b, '_, T> {
+        self.percolate_ref(d).copied()
+    }
+}
+
+impl<T> Percolate<T> for LinearPercolator<T>
+where
+    T: std::cmp::Eq + Clone,
+{
+    fn add(&mut self, q: Query, uid: T) -> Result<T, PercolatorError> {
+        self.index_query_uid(q, uid)
+    }
+
+    fn remove(&mut self, uid: &T) -> bool {
+        self.remove_uid(uid)
+    }
+
+    fn get(&self, uid: &T) -> Option<&Query> {
+        self.safe_get_query(uid)
+    }
+
+    fn percolate<'a>(&'a self, d: &'a Document) -> Box<dyn Iterator<Item = &'a T> + 'a> {
+        Box::new(self.percolate_ref(d))
+    }
+
+    fn stats(&self) -> &PercolatorStats {
+        &self.stats
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::CNFQueryable;
+
+    #[test]
+    fn test_linear_percolator() {
+        let mut p = LinearPercolator::default();
+        let q0 = p.add_query("A".has_value("a"));
+        let q1 = p.add_query(!"A".has_value("a"));
+        let q2 = p.add_query("A".has_prefix("a") | "B".has_value("b"));
+        assert_eq!(p.len(), 3);
+        assert_eq!(p.to_string(), "LinearPerc-3Qs");
+
+        let d: Document = [("A", "a")].into();
+        assert_eq!(p.percolate(&d).collect::<Vec<_>>(), vec![q0, q2]);
+        assert_eq!(
+            p.percolate(&[("B", "b")].into()).collect::<Vec<_>>(),
+            vec![q1, q2]
+        );
+
+        assert!(p.remove_qid(q0));
+        assert!(!p.remove_qid(q0));
+        assert!(p.safe_get_query(&q0).is_none());
+        assert_eq!(p.percolate(&d).collect::<Vec<_>>(), vec![q2]);
+        // Qids are not reused.
+        assert_eq!(p.add_query("A".has_value("a")), 3);
+
+        assert_eq!(p.stats().n_queries(), 4);
+        assert_eq!(p.stats().n_queries_removed(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serialisation() {
+        let mut p = LinearPercolator::default();
+        p.add_query("A".has_value("a"));
+        p.add_query("A".has_prefix("a"));
+        p.remove_qid(0);
+
+        let json = serde_json::to_string(&p).unwrap();
+        let mut p2: LinearPercolator<Qid> = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            p2.percolate(&[("A", "a")].into()).collect::<Vec<_>>(),
+            vec![1]
+        );
+        assert_eq!(p2.stats().n_queries(), 1);
+        assert_eq!(p2.add_query("B".has_value("b")), 2);
+    }
+
+    #[test]
+    fn test_overwrite() {
+        let mut p = LinearPercolator::<String>::default();
+        p.index_query_uid("A".has_value("a"), "x".into()).unwrap();
+        p.index_query_uid("B".has_value("b"), "y".into()).unwrap();
+        p.index_query_uid("A".has_prefix("a"), "x".into()).unwrap();
+        assert_eq!(p.len(), 2);
+        assert_eq!(p.get_query("x"), &"A".has_prefix("a"));
+        assert_eq!(
+            p.percolate_ref(&[("A", "aa"), ("B", "b")].into())
+                .collect::<Vec<_>>(),
+            vec!["y", "x"]
+        );
+    }
+}
@@ -1,7 +1,24 @@
 // Submodules.
 pub(crate) mod common;
+pub(crate) mod contains;
+pub(crate) mod count;
+pub(crate) mod exists;
 pub(crate) mod h3_inside;
+pub(crate) mod h3_inside_any;
+pub(crate) mod ip_in;
+pub(crate) mod latlng_between;
+pub(crate) mod latlng_in_polygon;
 pub(crate) mod latlng_within;
+pub(crate) mod latlng_within_any;
+pub(crate) mod missing;
+pub(crate) mod nested;
 pub(crate) mod ordered;
 pub(crate) mod prefix;
+pub(crate) mod range;
+pub(crate) mod regex;
+pub(crate) mod semver;
+pub(crate) mod str_range;
+pub(crate) mod suffix;
 pub(crate) mod term;
+pub(crate) mod term_set;
+pub(crate) mod text_match;
@@ -1,5 +1,31 @@
-use crate::models::document::Document;
+use crate::models::document::DocumentView;
 
 pub(crate) trait DocMatcher {
-    fn matches(&self, d: &Document) -> bool;
+    fn matches(&self, d: &impl DocumentView) -> bool;
+}
+
+// Unicode case folding. Upper casing first folds the
+// lower case letters without an upper case of their own,
+// like ß (to ss).
+pub(crate) fn case_fold(s: &str) -> String {
+    s.to_uppercase().to_lowercase()
+}
+
+// What messy values, like " Blue\n", are compared as.
+pub(crate) fn normalize(s: &str) -> String {
+    case_fold(s.trim())
+}
+
+#[cfg(test)]
+mod test_common {
+    use super::*;
+
+    #[test]
+    fn test_case_fold() {
+        assert_eq!(case_fold("Blue"), "blue");
+        assert_eq!(case_fold("blue"), "blue");
+        assert_eq!(case_fold("Straße"), "strasse");
+        assert_eq!(case_fold("ΣΊΣΥΦΟΣ"), case_fold("σίσυφος"));
+        assert_eq!(case_fold(""), "");
+    }
 }
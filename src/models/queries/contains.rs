@@ -0,0 +1,67 @@
+use crate::models::types::OurStr;
+use crate::models::{document::DocumentView, queries::common::DocMatcher};
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct ContainsQuery {
+    field: OurStr,
+    substring: OurStr,
+}
+
+impl ContainsQuery {
+    /// Constructor
+    pub(crate) fn new<T: Into<OurStr>, U: Into<OurStr>>(field: T, substring: U) -> Self {
+        ContainsQuery {
+            field: field.into(),
+            substring: substring.into(),
+        }
+    }
+
+    /// The field
+    pub(crate) fn field(&self) -> OurStr {
+        self.field.clone()
+    }
+
+    /// The substring
+    pub(crate) fn substring(&self) -> OurStr {
+        self.substring.clone()
+    }
+}
+
+impl DocMatcher for ContainsQuery {
+    /// Does this match the document?
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.str_values(&self.field)
+            .is_some_and(|mut i| i.any(|v| v.contains(self.substring.as_ref())))
+    }
+}
+
+#[cfg(test)]
+mod test_contains {
+    use super::*;
+    use crate::models::document::Document;
+
+    #[test]
+    fn test_new_and_getters() {
+        let q = ContainsQuery::new("title", "cat");
+        assert_eq!(q.field(), "title".into());
+        assert_eq!(q.substring(), "cat".into());
+    }
+
+    #[test]
+    fn test_matching() {
+        let q = ContainsQuery::new("title", "cat");
+
+        assert!(!q.matches(&Document::default()));
+        assert!(!q.matches(&Document::from([("some", "cat")])));
+        assert!(q.matches(&Document::from([("title", "cat")])));
+        assert!(q.matches(&Document::from([("title", "concatenate")])));
+        assert!(q.matches(&Document::from([("title", "dog"), ("title", "a cat")])));
+        assert!(!q.matches(&Document::from([("title", "ca t")])));
+        assert!(!q.matches(&Document::from([("title", "")])));
+
+        let q = ContainsQuery::new("title", "");
+        assert!(q.matches(&Document::from([("title", "")])));
+        assert!(!q.matches(&Document::from([("some", "")])));
+    }
+}
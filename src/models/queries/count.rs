@@ -0,0 +1,82 @@
+use crate::models::types::OurStr;
+use crate::models::{document::DocumentView, queries::common::DocMatcher};
+
+use itertools::Itertools;
+
+/// A query about a field having at least `min` distinct values,
+/// like at least 3 tags.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct CountGeQuery {
+    field: OurStr,
+    min: usize,
+}
+
+impl CountGeQuery {
+    /// Constructor
+    pub(crate) fn new<T: Into<OurStr>>(field: T, min: usize) -> Self {
+        CountGeQuery {
+            field: field.into(),
+            min,
+        }
+    }
+
+    /// The field
+    pub(crate) fn field(&self) -> OurStr {
+        self.field.clone()
+    }
+
+    /// The minimum number of distinct values.
+    pub(crate) fn min(&self) -> usize {
+        self.min
+    }
+
+    // The power of two the query is indexed at, at most min.
+    // Queries with close minimums share it.
+    pub(crate) fn bucket(&self) -> usize {
+        match self.min {
+            0 => 0,
+            min => 1 << min.ilog2(),
+        }
+    }
+}
+
+impl DocMatcher for CountGeQuery {
+    /// Does this match the document?
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        // Repeated values count once, so deduplicated documents match the same.
+        d.str_values(&self.field).map_or(0, |i| i.unique().count()) >= self.min
+    }
+}
+
+#[cfg(test)]
+mod test_count {
+    use super::*;
+    use crate::models::document::Document;
+
+    #[test]
+    fn test_matching() {
+        let q = CountGeQuery::new("tags", 2);
+        assert_eq!(q.field(), "tags".into());
+        assert_eq!(q.min(), 2);
+
+        assert!(!q.matches(&Document::default()));
+        assert!(!q.matches(&Document::from([("tags", "a")])));
+        assert!(!q.matches(&Document::from([("tags", "a"), ("tags", "a")])));
+        assert!(!q.matches(&Document::from([("tags", "a"), ("colour", "b")])));
+        assert!(q.matches(&Document::from([("tags", "a"), ("tags", "b")])));
+        assert!(q.matches(&Document::from([
+            ("tags", "a"),
+            ("tags", "b"),
+            ("tags", "c")
+        ])));
+
+        assert!(CountGeQuery::new("tags", 0).matches(&Document::default()));
+    }
+
+    #[test]
+    fn test_bucket() {
+        let buckets = [0, 1, 2, 3, 4, 7, 8, 100].map(|min| CountGeQuery::new("tags", min).bucket());
+        assert_eq!(buckets, [0, 1, 2, 2, 4, 4, 8, 64]);
+    }
+}
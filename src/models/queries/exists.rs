@@ -0,0 +1,47 @@
+use crate::models::types::OurStr;
+use crate::models::{document::DocumentView, queries::common::DocMatcher};
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct ExistsQuery {
+    field: OurStr,
+}
+
+impl ExistsQuery {
+    /// Constructor
+    pub(crate) fn new<T: Into<OurStr>>(field: T) -> Self {
+        ExistsQuery {
+            field: field.into(),
+        }
+    }
+
+    /// The field
+    pub(crate) fn field(&self) -> OurStr {
+        self.field.clone()
+    }
+}
+
+impl DocMatcher for ExistsQuery {
+    /// Does this match the document?
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.has_field(&self.field)
+    }
+}
+
+#[cfg(test)]
+mod test_exists {
+    use super::*;
+    use crate::models::document::Document;
+
+    #[test]
+    fn test_matching() {
+        let q = ExistsQuery::new("colour");
+        assert_eq!(q.field(), "colour".into());
+
+        assert!(!q.matches(&Document::default()));
+        assert!(!q.matches(&Document::from([("taste", "sweet")])));
+        assert!(q.matches(&Document::from([("colour", "blue")])));
+        assert!(q.matches(&Document::from([("colour", "")])));
+        assert!(q.matches(&Document::from([("taste", "sweet"), ("colour", "blue")])));
+    }
+}
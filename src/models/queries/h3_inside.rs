@@ -1,10 +1,10 @@
 use std::fmt::{self, Display};
 
-use h3o::CellIndex;
+use h3o::{CellIndex, Resolution};
 
-use crate::{
-    models::{queries::common::DocMatcher, types::OurStr},
-    prelude::Document,
+use crate::models::{
+    cnf::parsing::escape_quote_field, document::DocumentView, queries::common::DocMatcher,
+    types::OurStr,
 };
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -16,7 +16,12 @@ pub(crate) struct H3InsideQuery {
 
 impl Display for H3InsideQuery {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}=H3IN={}", self.field(), self.cell())
+        write!(
+            f,
+            "{} H3IN {}",
+            escape_quote_field(&self.field),
+            self.cell()
+        )
     }
 }
 
@@ -38,12 +43,18 @@ impl H3InsideQuery {
     pub(crate) fn cell(&self) -> CellIndex {
         self.cell
     }
+
+    /// The H3 CellIndex, or its ancestor at max_res
+    /// if the cell is finer than that.
+    pub(crate) fn cell_upto(&self, max_res: Resolution) -> CellIndex {
+        self.cell.parent(max_res).unwrap_or(self.cell)
+    }
 }
 
 /// Free function to test a string from a potential string CellIndex to
 /// a parent.
-fn _has_parent(cell_str: &OurStr, parent: CellIndex) -> bool {
-    cell_str.as_ref().parse::<CellIndex>().is_ok_and(|cell| {
+pub(crate) fn has_parent(cell_str: &str, parent: CellIndex) -> bool {
+    cell_str.parse::<CellIndex>().is_ok_and(|cell| {
         cell.parent(parent.resolution())
             .is_some_and(|ancestor| ancestor.eq(&parent))
     })
@@ -51,9 +62,9 @@ fn _has_parent(cell_str: &OurStr, parent: CellIndex) -> bool {
 
 impl DocMatcher for H3InsideQuery {
     /// Does this match the document?
-    fn matches(&self, d: &Document) -> bool {
-        d.values_iter(&self.field)
-            .is_some_and(|mut i| i.any(|v| _has_parent(&v, self.cell())))
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.str_values(&self.field)
+            .is_some_and(|mut i| i.any(|v| has_parent(v, self.cell())))
     }
 }
 
@@ -71,7 +82,19 @@ mod test_h3_inside {
         let q = H3InsideQuery::new("location", cell);
         assert_eq!(q.field(), "location".into());
         assert_eq!(q.cell(), cell);
-        assert_eq!(format!("{}", q), format!("location=H3IN={}", cell));
+        assert_eq!(format!("{}", q), format!("location H3IN {}", cell));
+    }
+
+    #[test]
+    fn test_cell_upto() {
+        let cell = "89194d10693ffff".parse::<CellIndex>().unwrap();
+        let q = H3InsideQuery::new("location", cell);
+        assert_eq!(q.cell_upto(Resolution::Fifteen), cell);
+        assert_eq!(q.cell_upto(Resolution::Nine), cell);
+        assert_eq!(
+            q.cell_upto(Resolution::Seven),
+            "87194d106ffffff".parse::<CellIndex>().unwrap()
+        );
     }
 
     #[test]
@@ -79,47 +102,47 @@ mod test_h3_inside {
         let q = H3InsideQuery::new("location", "87194d106ffffff".parse::<CellIndex>().unwrap());
         assert!(q.field().eq(&"location".into()));
         assert!(!q.matches(&Document::default()));
-        assert!(!q.matches(&[("some", "thing")].into()));
-        assert!(!q.matches(&[("field", "pr")].into()));
+        assert!(!q.matches(&Document::from([("some", "thing")])));
+        assert!(!q.matches(&Document::from([("field", "pr")])));
 
-        assert!(q.matches(&[("location", "87194d106ffffff")].into()));
-        assert!(q.matches(&[("location", "88194d1069fffff")].into()));
-        assert!(q.matches(&[("location", "89194d10693ffff")].into()));
+        assert!(q.matches(&Document::from([("location", "87194d106ffffff")])));
+        assert!(q.matches(&Document::from([("location", "88194d1069fffff")])));
+        assert!(q.matches(&Document::from([("location", "89194d10693ffff")])));
     }
 
     #[test]
     fn test_has_parent() {
         // Find some examples there:
         // https://observablehq.com/@nrabinowitz/h3-index-inspector?collection=@nrabinowitz/h3
-        assert!(_has_parent(
-            &"87194d106ffffff".into(),
+        assert!(has_parent(
+            "87194d106ffffff",
             "87194d106ffffff".parse::<CellIndex>().unwrap()
         ));
-        assert!(!_has_parent(
-            &"sausage".into(),
+        assert!(!has_parent(
+            "sausage",
             "87194d106ffffff".parse::<CellIndex>().unwrap()
         ));
 
-        assert!(_has_parent(
-            &"87194d106ffffff".into(),
+        assert!(has_parent(
+            "87194d106ffffff",
             "86194d107ffffff".parse::<CellIndex>().unwrap()
         ));
 
         // But not the other way around:
-        assert!(!_has_parent(
-            &"86194d107ffffff".into(),
+        assert!(!has_parent(
+            "86194d107ffffff",
             "87194d106ffffff".parse::<CellIndex>().unwrap()
         ));
 
         // Test parent that is not a direct parent but an ancestor
-        assert!(_has_parent(
-            &"88194d1069fffff".into(),                       // Res 8
+        assert!(has_parent(
+            "88194d1069fffff",                               // Res 8
             "86194d107ffffff".parse::<CellIndex>().unwrap()  // Res 6
         ));
 
         // Test with different valid cell that is NOT a child
-        assert!(!_has_parent(
-            &"87194d106ffffff".into(),
+        assert!(!has_parent(
+            "87194d106ffffff",
             "87195d106ffffff".parse::<CellIndex>().unwrap() // Different cell
         ));
     }
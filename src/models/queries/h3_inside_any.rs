@@ -0,0 +1,109 @@
+use std::fmt::{self, Display};
+
+use h3o::{CellIndex, Resolution};
+use itertools::Itertools;
+
+use crate::models::{
+    cnf::parsing::escape_quote_field,
+    document::DocumentView,
+    queries::{common::DocMatcher, h3_inside::has_parent},
+    types::OurStr,
+};
+
+///
+/// Represents a query about a field being an H3 cell inside any
+/// of a set of cells, for instance a compacted covering of an area.
+///
+/// It is one literal, however many cells there are, and each cell
+/// is indexed at its own resolution, like an H3Inside query.
+///
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct H3InsideAnyQuery {
+    field: OurStr,
+    // Sorted and without duplicates.
+    cells: Vec<CellIndex>,
+}
+
+impl Display for H3InsideAnyQuery {
+    // Like `location H3IN {cell1, cell2}`, so it can be parsed back.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} H3IN {{{}}}",
+            escape_quote_field(&self.field),
+            self.cells.iter().join(", ")
+        )
+    }
+}
+
+impl H3InsideAnyQuery {
+    /// Constructor
+    pub(crate) fn new<T: Into<OurStr>>(
+        field: T,
+        cells: impl IntoIterator<Item = CellIndex>,
+    ) -> Self {
+        H3InsideAnyQuery {
+            field: field.into(),
+            cells: cells.into_iter().sorted().dedup().collect(),
+        }
+    }
+
+    /// The field
+    pub(crate) fn field(&self) -> OurStr {
+        self.field.clone()
+    }
+
+    /// The H3 cells, in order.
+    pub(crate) fn cells(&self) -> &[CellIndex] {
+        &self.cells
+    }
+
+    /// The H3 cells, or their ancestors at max_res
+    /// for the ones finer than that.
+    pub(crate) fn cells_upto(&self, max_res: Resolution) -> Vec<CellIndex> {
+        self.cells
+            .iter()
+            .map(|cell| cell.parent(max_res).unwrap_or(*cell))
+            .sorted()
+            .dedup()
+            .collect()
+    }
+}
+
+impl DocMatcher for H3InsideAnyQuery {
+    /// Does this match the document?
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.str_values(&self.field)
+            .is_some_and(|mut i| i.any(|v| self.cells.iter().any(|cell| has_parent(v, *cell))))
+    }
+}
+
+#[cfg(test)]
+mod test_h3_inside_any {
+    use super::*;
+    use crate::models::document::Document;
+
+    #[test]
+    fn test_h3_inside_any() {
+        let c7 = "87194d106ffffff".parse::<CellIndex>().unwrap();
+        let c9 = "89194d10693ffff".parse::<CellIndex>().unwrap();
+        let other = "87195d106ffffff".parse::<CellIndex>().unwrap();
+
+        let q = H3InsideAnyQuery::new("location", [other, c9, other]);
+        assert_eq!(q.field(), "location".into());
+        assert_eq!(q.cells(), &[c9, other]);
+        assert_eq!(
+            q.to_string(),
+            "location H3IN {89194d10693ffff, 87195d106ffffff}"
+        );
+        assert_eq!(q.cells_upto(Resolution::Seven), vec![c7, other]);
+
+        assert!(!q.matches(&Document::default()));
+        assert!(!q.matches(&Document::from([("location", "sausage")])));
+        // Too coarse.
+        assert!(!q.matches(&Document::from([("location", "87194d106ffffff")])));
+        assert!(q.matches(&Document::from([("location", "89194d10693ffff")])));
+        assert!(q.matches(&Document::from([("location", "87195d106ffffff")])));
+    }
+}
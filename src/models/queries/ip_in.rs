@@ -0,0 +1,75 @@
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+
+use crate::models::types::OurStr;
+use crate::models::{document::DocumentView, queries::common::DocMatcher};
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct IpInQuery {
+    field: OurStr,
+    net: IpNet,
+}
+
+impl IpInQuery {
+    /// Constructor. The host bits of the network are ignored.
+    pub(crate) fn new<T: Into<OurStr>>(field: T, net: IpNet) -> Self {
+        IpInQuery {
+            field: field.into(),
+            net: net.trunc(),
+        }
+    }
+
+    /// The field
+    pub(crate) fn field(&self) -> OurStr {
+        self.field.clone()
+    }
+
+    /// The network, without host bits.
+    pub(crate) fn net(&self) -> IpNet {
+        self.net
+    }
+}
+
+impl DocMatcher for IpInQuery {
+    /// Does this match the document?
+    /// Values that are not IP addresses never match.
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.str_values(&self.field).is_some_and(|mut i| {
+            i.any(|v| v.parse::<IpAddr>().is_ok_and(|a| self.net.contains(&a)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_ip_in {
+    use super::*;
+    use crate::models::document::Document;
+
+    #[test]
+    fn test_new_and_getters() {
+        let q = IpInQuery::new("ip", "10.1.2.3/8".parse().unwrap());
+        assert_eq!(q.field(), "ip".into());
+        assert_eq!(q.net().to_string(), "10.0.0.0/8");
+    }
+
+    #[test]
+    fn test_matching() {
+        let q = IpInQuery::new("ip", "10.0.0.0/8".parse().unwrap());
+        assert!(!q.matches(&Document::default()));
+        assert!(!q.matches(&Document::from([("other", "10.0.0.1")])));
+        assert!(q.matches(&Document::from([("ip", "10.0.0.1")])));
+        assert!(q.matches(&Document::from([("ip", "10.255.255.255")])));
+        assert!(q.matches(&Document::from([("ip", "blabla"), ("ip", "10.2.3.4")])));
+        assert!(!q.matches(&Document::from([("ip", "11.0.0.1")])));
+        assert!(!q.matches(&Document::from([("ip", "10.0.0.0/8")])));
+        assert!(!q.matches(&Document::from([("ip", "::ffff:10.0.0.1")])));
+
+        let q = IpInQuery::new("ip", "2001:db8::/32".parse().unwrap());
+        assert!(q.matches(&Document::from([("ip", "2001:db8::1")])));
+        assert!(q.matches(&Document::from([("ip", "2001:DB8:ffff::")])));
+        assert!(!q.matches(&Document::from([("ip", "2001:db9::1")])));
+        assert!(!q.matches(&Document::from([("ip", "10.0.0.1")])));
+    }
+}
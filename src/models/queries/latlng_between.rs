@@ -0,0 +1,193 @@
+use std::{fmt::Display, hash::Hash};
+
+use nonempty::NonEmpty;
+
+use h3o::{CellIndex, LatLng, Resolution};
+
+use crate::{
+    geotools::{Meters, compact_cells, disk_covering, resolution_within_k},
+    models::{
+        cnf::parsing::escape_quote_field,
+        document::DocumentView,
+        queries::{
+            common::DocMatcher,
+            latlng_within::{parse_latlng, parse_latlng_within},
+        },
+        types::OurStr,
+    },
+};
+
+/// A query about a lat,lng field being in a ring around a center:
+/// further than `min` from it, and within `max` of it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct LatLngBetweenQuery {
+    field: OurStr,
+    latlng: LatLng,
+    min: Meters,
+    max: Meters,
+}
+
+impl Display for LatLngBetweenQuery {
+    // Like `position LLBETWEEN lat,lng,min,max`, so it can be parsed back.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} LLBETWEEN {},{},{},{}",
+            escape_quote_field(&self.field),
+            self.latlng.lat(),
+            self.latlng.lng(),
+            self.min.0,
+            self.max.0
+        )
+    }
+}
+
+// Use the string representation for hashing.
+impl Hash for LatLngBetweenQuery {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.to_string().hash(state);
+    }
+}
+
+impl LatLngBetweenQuery {
+    /// Constructor
+    pub(crate) fn new<T: Into<OurStr>>(field: T, latlng: LatLng, min: Meters, max: Meters) -> Self {
+        LatLngBetweenQuery {
+            field: field.into(),
+            latlng,
+            min,
+            max,
+        }
+    }
+
+    /// The field
+    pub(crate) fn field(&self) -> OurStr {
+        self.field.clone()
+    }
+
+    pub(crate) fn latlng(&self) -> LatLng {
+        self.latlng
+    }
+
+    pub(crate) fn min(&self) -> Meters {
+        self.min
+    }
+
+    pub(crate) fn max(&self) -> Meters {
+        self.max
+    }
+
+    // The resolution of the h3 cells covering the outer disk,
+    // never finer than max_res.
+    pub(crate) fn resolution_upto(&self, max_res: Resolution) -> Resolution {
+        resolution_within_k(self.max, 4).min(max_res)
+    }
+
+    // The compacted h3 cells covering the outer disk, at most at max_res,
+    // without the ones entirely inside the inner disk.
+    pub(crate) fn h3_cells(&self, max_res: Resolution) -> NonEmpty<CellIndex> {
+        let covering = disk_covering(self.latlng, self.max, self.resolution_upto(max_res));
+        let ring = covering
+            .iter()
+            .copied()
+            .filter(|cell| {
+                !cell
+                    .boundary()
+                    .iter()
+                    .all(|ll| ll.distance_m(self.latlng) <= self.min.0 as f64)
+            })
+            .collect();
+        // A ring thinner than the cells leaves the covering as it is.
+        compact_cells(NonEmpty::from_vec(ring).unwrap_or(covering))
+    }
+}
+
+fn _latlng_between(doc_value: &str, q: &LatLngBetweenQuery) -> bool {
+    parse_latlng(doc_value).is_some_and(|ll| {
+        let distance = ll.distance_m(q.latlng);
+        distance > q.min.0 as f64 && distance <= q.max.0 as f64
+    })
+}
+
+impl DocMatcher for LatLngBetweenQuery {
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.str_values(&self.field)
+            .is_some_and(|mut i| i.any(|v| _latlng_between(v, self)))
+    }
+}
+
+// Silently fails to parse a lat,lng,min,max where min is less than max.
+pub(crate) fn parse_latlng_between(input: &str) -> Option<(LatLng, Meters, Meters)> {
+    let (within, max) = input.rsplit_once(',')?;
+    let (ll, min) = parse_latlng_within(within)?;
+    let max = max.parse::<u64>().ok().map(Meters)?;
+    (min.0 < max.0).then_some((ll, min, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::Document;
+
+    use super::*;
+
+    #[test]
+    fn test_latlng_between() {
+        let center = LatLng::new(48.864716, 2.349014).unwrap();
+        let q = LatLngBetweenQuery::new("position", center, Meters(1000), Meters(3000));
+        assert_eq!(q.field(), "position".into());
+        assert_eq!(q.latlng(), center);
+        assert_eq!((q.min(), q.max()), (Meters(1000), Meters(3000)));
+        assert_eq!(
+            q.to_string(),
+            "position LLBETWEEN 48.864716,2.349014,1000,3000"
+        );
+
+        assert!(!q.matches(&Document::default()));
+        assert!(!q.matches(&Document::from([("position", "sausage")])));
+        // The center, about 640m, 2.3km and 4.6km away.
+        assert!(!q.matches(&Document::from([("position", "48.864716,2.349014")])));
+        assert!(!q.matches(&Document::from([("position", "48.859430,2.354946")])));
+        assert!(q.matches(&Document::from([("position", "48.880000,2.370000")])));
+        assert!(!q.matches(&Document::from([("position", "48.85,2.29")])));
+        // Any value can be in the ring.
+        assert!(q.matches(&Document::from([
+            ("position", "48.864716,2.349014"),
+            ("position", "48.880000,2.370000")
+        ])));
+    }
+
+    #[test]
+    fn test_parse_latlng_between() {
+        let center = LatLng::new(48.86, -2.34).unwrap();
+        assert_eq!(
+            parse_latlng_between("48.86,-2.34,1000,3000"),
+            Some((center, Meters(1000), Meters(3000)))
+        );
+        assert_eq!(parse_latlng_between("48.86,-2.34,3000,1000"), None);
+        assert_eq!(parse_latlng_between("48.86,-2.34,1000,1000"), None);
+        assert_eq!(parse_latlng_between("48.86,-2.34,1000"), None);
+        assert_eq!(parse_latlng_between("48.86,-2.34,1000,3e3"), None);
+    }
+
+    #[test]
+    fn test_h3_cells() {
+        let center = LatLng::new(48.864716, 2.349014).unwrap();
+        let disk = LatLngBetweenQuery::new("position", center, Meters(0), Meters(3000));
+        let ring = LatLngBetweenQuery::new("position", center, Meters(2000), Meters(3000));
+        let res = disk.resolution_upto(Resolution::Fifteen);
+
+        let n_cells = |q: &LatLngBetweenQuery| {
+            CellIndex::uncompact_size(q.h3_cells(Resolution::Fifteen).iter().copied(), res)
+        };
+        assert!(n_cells(&ring) < n_cells(&disk));
+        // The center is not covered by the ring.
+        let center_cell = center.to_cell(res);
+        assert!(
+            !ring
+                .h3_cells(Resolution::Fifteen)
+                .iter()
+                .any(|c| center_cell.parent(c.resolution()) == Some(*c))
+        );
+    }
+}
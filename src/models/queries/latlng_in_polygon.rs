@@ -0,0 +1,118 @@
+use std::{fmt::Display, hash::Hash};
+
+use h3o::{CellIndex, LatLng, Resolution};
+use itertools::Itertools;
+use nonempty::NonEmpty;
+
+use crate::{
+    geotools::{compact_cells, ring_contains, ring_covering},
+    models::{
+        cnf::parsing::escape_quote_field,
+        document::DocumentView,
+        queries::{common::DocMatcher, latlng_within::parse_latlng},
+        types::OurStr,
+    },
+};
+
+// A lat,lng inside a simple ring.
+// Polygons with holes and multi polygons
+// are built as CNF queries from this.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct LatLngInPolygonQuery {
+    field: OurStr,
+    ring: Vec<LatLng>,
+}
+
+impl Eq for LatLngInPolygonQuery {}
+
+impl Display for LatLngInPolygonQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} LLINPOLYGON {}",
+            escape_quote_field(&self.field),
+            self.ring
+                .iter()
+                .map(|ll| format!("{},{}", ll.lat(), ll.lng()))
+                .join(";")
+        )
+    }
+}
+
+// Use the string representation for hashing.
+impl Hash for LatLngInPolygonQuery {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.to_string().hash(state);
+    }
+}
+
+// Silently fails to parse a ring of at least 3 lat,lng separated by ';'
+pub(crate) fn parse_ring(input: &str) -> Option<Vec<LatLng>> {
+    input
+        .split(';')
+        .map(|ll| parse_latlng(ll.trim()))
+        .collect::<Option<Vec<_>>>()
+        .filter(|ring| ring.len() >= 3)
+}
+
+impl LatLngInPolygonQuery {
+    /// Constructor
+    pub(crate) fn new<T: Into<OurStr>>(field: T, ring: Vec<LatLng>) -> Self {
+        LatLngInPolygonQuery {
+            field: field.into(),
+            ring,
+        }
+    }
+
+    /// The field
+    pub(crate) fn field(&self) -> OurStr {
+        self.field.clone()
+    }
+
+    pub(crate) fn ring(&self) -> &[LatLng] {
+        &self.ring
+    }
+
+    // The compacted h3 cells covering this ring, at most at max_res.
+    pub(crate) fn h3_cells(&self, max_res: Resolution) -> NonEmpty<CellIndex> {
+        compact_cells(ring_covering(&self.ring, max_res))
+    }
+}
+
+impl DocMatcher for LatLngInPolygonQuery {
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.str_values(&self.field).is_some_and(|mut i| {
+            i.any(|v| parse_latlng(v).is_some_and(|ll| ring_contains(&self.ring, ll)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::Document;
+
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let ll = |lat, lng| LatLng::new(lat, lng).unwrap();
+        let q = LatLngInPolygonQuery::new(
+            "position",
+            vec![ll(0.0, 0.0), ll(0.0, 1.0), ll(1.0, 1.0), ll(1.0, 0.0)],
+        );
+        assert_eq!(q.field(), "position".into());
+        assert_eq!(q.ring().len(), 4);
+        assert_eq!(q.to_string(), "position LLINPOLYGON 0,0;0,1;1,1;1,0");
+
+        assert!(!q.matches(&Document::default()));
+        assert!(!q.matches(&Document::from([("position", "bla")])));
+        assert!(!q.matches(&Document::from([("position", "2,0.5")])));
+        assert!(!q.matches(&Document::from([("other", "0.5,0.5")])));
+        assert!(q.matches(&Document::from([("position", "0.5,0.5")])));
+        assert!(q.matches(&Document::from([
+            ("position", "2,0.5"),
+            ("position", "0.5,0.5")
+        ])));
+    }
+}
@@ -2,11 +2,14 @@ use std::{fmt::Display, hash::Hash};
 
 use nonempty::NonEmpty;
 
-use h3o::{LatLng, Resolution};
+use h3o::{CellIndex, LatLng, Resolution};
 
 use crate::{
-    geotools::{Meters, disk_covering, resolution_within_k},
-    models::{queries::common::DocMatcher, types::OurStr},
+    geotools::{Meters, compact_cells, disk_covering, resolution_within_k},
+    models::{
+        cnf::parsing::escape_quote_field, document::DocumentView, queries::common::DocMatcher,
+        types::OurStr,
+    },
 };
 
 use chumsky::prelude::*;
@@ -23,8 +26,11 @@ impl Display for LatLngWithinQuery {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{} LATLNG_WITHIN {},{}",
-            self.field, self.latlng, self.within.0
+            "{} LLWITHIN {},{},{}",
+            escape_quote_field(&self.field),
+            self.latlng.lat(),
+            self.latlng.lng(),
+            self.within.0
         )
     }
 }
@@ -64,9 +70,19 @@ impl LatLngWithinQuery {
         resolution_within_k(self.within, 4)
     }
 
-    // The h3 cells covering this disk
-    pub(crate) fn h3_cells(&self) -> NonEmpty<h3o::CellIndex> {
-        disk_covering(self.latlng, self.within, self.resolution())
+    // The resolution of the h3 cells covering this disk,
+    // never finer than max_res.
+    pub(crate) fn resolution_upto(&self, max_res: Resolution) -> Resolution {
+        self.resolution().min(max_res)
+    }
+
+    // The compacted h3 cells covering this disk, at most at max_res.
+    pub(crate) fn h3_cells(&self, max_res: Resolution) -> NonEmpty<CellIndex> {
+        compact_cells(disk_covering(
+            self.latlng,
+            self.within,
+            self.resolution_upto(max_res),
+        ))
     }
 }
 
@@ -99,22 +115,20 @@ pub(crate) fn parse_latlng_within(input: &str) -> Option<(LatLng, Meters)> {
         .parse(input)
         .into_result()
         .ok()
-        .and_then(|((lat, lng), m)| {
-            LatLng::new(lat, lng).ok().map(|ll| (ll, Meters(m)))
-        })
+        .and_then(|((lat, lng), m)| LatLng::new(lat, lng).ok().map(|ll| (ll, Meters(m))))
 }
 
 // The cell value must be a valid double,double representing
 // a latitude,longitude pair.
-fn _latlng_within(doc_value: &OurStr, q: &LatLngWithinQuery) -> bool {
+fn _latlng_within(doc_value: &str, q: &LatLngWithinQuery) -> bool {
     parse_latlng(doc_value).is_some_and(|ll| ll.distance_m(q.latlng) <= q.within.0 as f64)
 }
 
 impl DocMatcher for LatLngWithinQuery {
-    fn matches(&self, d: &crate::prelude::Document) -> bool {
+    fn matches(&self, d: &impl DocumentView) -> bool {
         // Try parsing all the d fields into LatLng
-        d.values_iter(&self.field)
-            .is_some_and(|mut i| i.any(|v| _latlng_within(&v, self)))
+        d.str_values(&self.field)
+            .is_some_and(|mut i| i.any(|v| _latlng_within(v, self)))
     }
 }
 
@@ -155,10 +169,7 @@ mod tests {
         );
         assert_eq!(q.latlng(), q.latlng);
         assert_eq!(q.within(), q.within);
-        assert_eq!(
-            q.to_string(),
-            "location LATLNG_WITHIN (48.8647160000, 2.3490140000),1000"
-        );
+        assert_eq!(q.to_string(), "location LLWITHIN 48.864716,2.349014,1000");
 
         let d = Document::default();
         assert!(!q.matches(&d));
@@ -0,0 +1,134 @@
+use std::{fmt::Display, hash::Hash};
+
+use itertools::Itertools;
+use nonempty::NonEmpty;
+
+use h3o::{CellIndex, LatLng, Resolution};
+
+use crate::{
+    geotools::Meters,
+    models::{
+        cnf::parsing::escape_quote_field,
+        document::DocumentView,
+        queries::{common::DocMatcher, latlng_within::LatLngWithinQuery},
+        types::OurStr,
+    },
+};
+
+/// A query about a lat,lng field being within any of a set of disks,
+/// for instance around each of a set of places.
+///
+/// It is one literal, however many disks there are, indexed
+/// as the union of the disk coverings.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct LatLngWithinAnyQuery {
+    field: OurStr,
+    disks: Vec<(LatLng, Meters)>,
+}
+
+impl Display for LatLngWithinAnyQuery {
+    // Like `position LLWITHIN {lat,lng,meters; lat,lng,meters}`, so it can be parsed back.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} LLWITHIN {{{}}}",
+            escape_quote_field(&self.field),
+            self.disks
+                .iter()
+                .map(|(ll, radius)| format!("{},{},{}", ll.lat(), ll.lng(), radius.0))
+                .join("; ")
+        )
+    }
+}
+
+// Use the string representation for hashing.
+impl Hash for LatLngWithinAnyQuery {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.to_string().hash(state);
+    }
+}
+
+impl LatLngWithinAnyQuery {
+    /// Constructor
+    pub(crate) fn new<T: Into<OurStr>>(field: T, disks: &[(LatLng, Meters)]) -> Self {
+        LatLngWithinAnyQuery {
+            field: field.into(),
+            disks: disks.to_vec(),
+        }
+    }
+
+    /// The field
+    pub(crate) fn field(&self) -> OurStr {
+        self.field.clone()
+    }
+
+    /// The centers and radii of the disks.
+    pub(crate) fn disks(&self) -> &[(LatLng, Meters)] {
+        &self.disks
+    }
+
+    // One LatLngWithin query per disk.
+    fn within_queries(&self) -> impl Iterator<Item = LatLngWithinQuery> + '_ {
+        self.disks
+            .iter()
+            .map(|(ll, within)| LatLngWithinQuery::new(self.field.clone(), *ll, *within))
+    }
+
+    // The h3 cells covering any of the disks, at most at max_res.
+    // Each disk is compacted on its own.
+    pub(crate) fn h3_cells(&self, max_res: Resolution) -> NonEmpty<CellIndex> {
+        let cells = self
+            .within_queries()
+            .flat_map(|q| q.h3_cells(max_res))
+            .unique()
+            .collect();
+        NonEmpty::from_vec(cells).expect("At least one disk")
+    }
+}
+
+impl DocMatcher for LatLngWithinAnyQuery {
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        self.within_queries().any(|q| q.matches(d))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::Document;
+
+    use super::*;
+
+    #[test]
+    fn test_latlng_within_any() {
+        let paris = LatLng::new(48.864716, 2.349014).unwrap();
+        let gdansk = LatLng::new(54.35499723397377, 18.662987684795226).unwrap();
+        let q =
+            LatLngWithinAnyQuery::new("position", &[(paris, Meters(1000)), (gdansk, Meters(500))]);
+        assert_eq!(q.field(), "position".into());
+        assert_eq!(q.disks().len(), 2);
+        assert_eq!(
+            q.to_string(),
+            "position LLWITHIN {48.864716,2.349014,1000; \
+             54.35499723397377,18.662987684795226,500}"
+        );
+
+        assert!(!q.matches(&Document::default()));
+        assert!(!q.matches(&Document::from([("position", "sausage")])));
+        assert!(q.matches(&Document::from([("position", "48.859430,2.354946")])));
+        assert!(q.matches(&Document::from([("position", "54.355,18.663")])));
+        assert!(!q.matches(&Document::from([("position", "48.85,2.29")])));
+
+        // Both coverings.
+        let cells = q.h3_cells(Resolution::Fifteen);
+        let paris_cell = paris.to_cell(Resolution::Fifteen);
+        let gdansk_cell = gdansk.to_cell(Resolution::Fifteen);
+        for c in [paris_cell, gdansk_cell] {
+            assert!(
+                cells
+                    .iter()
+                    .any(|cell| c.parent(cell.resolution()) == Some(*cell))
+            );
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use crate::models::types::OurStr;
+use crate::models::{document::DocumentView, queries::common::DocMatcher};
+
+// The negation of an ExistsQuery.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct MissingQuery {
+    field: OurStr,
+}
+
+impl MissingQuery {
+    /// Constructor
+    pub(crate) fn new<T: Into<OurStr>>(field: T) -> Self {
+        MissingQuery {
+            field: field.into(),
+        }
+    }
+
+    /// The field
+    pub(crate) fn field(&self) -> OurStr {
+        self.field.clone()
+    }
+}
+
+impl DocMatcher for MissingQuery {
+    /// Does this match the document?
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        !d.has_field(&self.field)
+    }
+}
+
+#[cfg(test)]
+mod test_missing {
+    use super::*;
+    use crate::models::document::Document;
+
+    #[test]
+    fn test_matching() {
+        let q = MissingQuery::new("colour");
+        assert_eq!(q.field(), "colour".into());
+
+        assert!(q.matches(&Document::default()));
+        assert!(q.matches(&Document::from([("taste", "sweet")])));
+        assert!(!q.matches(&Document::from([("colour", "blue")])));
+        assert!(!q.matches(&Document::from([("colour", "")])));
+        assert!(!q.matches(&Document::from([("taste", "sweet"), ("colour", "blue")])));
+    }
+}
@@ -0,0 +1,90 @@
+use std::hash::{Hash, Hasher};
+
+use crate::models::cnf::Query;
+use crate::models::types::OurStr;
+use crate::models::{document::DocumentView, queries::common::DocMatcher};
+
+/// A query about the elements of a nested path of documents, like the items
+/// of an order. One element must match the whole query, whose fields are
+/// relative to the path.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct NestedQuery {
+    path: OurStr,
+    query: Query,
+}
+
+impl NestedQuery {
+    /// Constructor
+    pub(crate) fn new<T: Into<OurStr>>(path: T, query: Query) -> Self {
+        NestedQuery {
+            path: path.into(),
+            query,
+        }
+    }
+
+    pub(crate) fn path(&self) -> OurStr {
+        self.path.clone()
+    }
+
+    pub(crate) fn query(&self) -> &Query {
+        &self.query
+    }
+
+    // The fields of the query, flattened under the path like in documents.
+    pub(crate) fn fields(&self) -> Vec<OurStr> {
+        self.query
+            .fields()
+            .map(|f| format!("{}.{}", self.path, f).into())
+            .collect()
+    }
+}
+
+// Queries are not hashable, but equal ones have the same digest.
+impl Hash for NestedQuery {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.path.hash(state);
+        self.query.digest().hash(state);
+    }
+}
+
+impl DocMatcher for NestedQuery {
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.nested(&self.path).iter().any(|e| self.query.matches(e))
+    }
+}
+
+#[cfg(test)]
+mod test_nested {
+    use super::*;
+    use crate::models::document::Document;
+    use crate::prelude::CNFQueryable;
+
+    #[test]
+    fn test_matching() {
+        let q = NestedQuery::new("items", "colour".has_value("blue") & "price".i64_lt(10));
+        assert_eq!(q.path(), "items".into());
+        assert_eq!(
+            q.fields(),
+            vec!["items.colour".into(), "items.price".into()]
+        );
+
+        let order = |items: &[(&str, &str)]| {
+            items
+                .iter()
+                .fold(Document::default(), |d, (colour, price)| {
+                    d.with_nested("items", [("colour", *colour), ("price", *price)].into())
+                })
+        };
+        assert!(!q.matches(&Document::default()));
+        assert!(q.matches(&order(&[("blue", "5")])));
+        assert!(q.matches(&order(&[("red", "5"), ("blue", "5")])));
+        // Both values, but not in the same item.
+        assert!(!q.matches(&order(&[("red", "5"), ("blue", "15")])));
+        // The flattened values are not elements.
+        assert!(!q.matches(&Document::from([
+            ("items.colour", "blue"),
+            ("items.price", "5")
+        ])));
+    }
+}
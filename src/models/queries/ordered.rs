@@ -1,8 +1,12 @@
 use std::{
     fmt::{self, Display},
+    hash::{Hash, Hasher},
+    num::IntErrorKind,
     str::FromStr,
 };
 
+use crate::models::cnf::parsing::escape_quote_field;
+use crate::models::document::DocumentView;
 use crate::models::queries::common::DocMatcher;
 use crate::models::types::OurStr;
 
@@ -22,7 +26,7 @@ impl Display for Ordering {
             Ordering::LT => f.write_str("<"),
             Ordering::GE => f.write_str(">="),
             Ordering::LE => f.write_str("<="),
-            Ordering::EQ => f.write_str("=="),
+            Ordering::EQ => f.write_str("="),
         }
     }
 }
@@ -39,15 +43,71 @@ impl Ordering {
     }
 }
 
+/// Comparison points that can be compared for equality and hashed.
+/// This is what allows ordered queries on floats to be used as literals.
+pub(crate) trait HashablePoint {
+    fn eq_point(&self, other: &Self) -> bool;
+    fn hash_point<H: Hasher>(&self, state: &mut H);
+}
+
+impl HashablePoint for i64 {
+    fn eq_point(&self, other: &Self) -> bool {
+        self == other
+    }
+
+    fn hash_point<H: Hasher>(&self, state: &mut H) {
+        self.hash(state);
+    }
+}
+
+impl HashablePoint for i128 {
+    fn eq_point(&self, other: &Self) -> bool {
+        self == other
+    }
+
+    fn hash_point<H: Hasher>(&self, state: &mut H) {
+        self.hash(state);
+    }
+}
+
+impl HashablePoint for f64 {
+    // Unlike ==, any NaN equals any other, so equality is reflexive.
+    fn eq_point(&self, other: &Self) -> bool {
+        self == other || (self.is_nan() && other.is_nan())
+    }
+
+    fn hash_point<H: Hasher>(&self, state: &mut H) {
+        // 0.0 and -0.0 are equal, so they must hash the same. So are NaNs.
+        if *self == 0.0 {
+            0.0f64.to_bits().hash(state)
+        } else if self.is_nan() {
+            f64::NAN.to_bits().hash(state)
+        } else {
+            self.to_bits().hash(state)
+        }
+    }
+}
+
+/// The i64 an integer too large or too small for one clamps to.
+/// None for values that are i64s, or not integers at all.
+pub(crate) fn clamped_int(v: &str) -> Option<i64> {
+    match v.parse::<i64>().map_err(|e| *e.kind()) {
+        Err(IntErrorKind::PosOverflow) => Some(i64::MAX),
+        Err(IntErrorKind::NegOverflow) => Some(i64::MIN),
+        _ => None,
+    }
+}
+
 ///
 /// Represents a query about partially ordered elements.
 ///
 /// The fields it compares to are strings as usual and are parsed as the
 /// comparison type on exact comparisons.
 ///
-/// Over/Underflow values will NOT match.
+/// Over/Underflow values will NOT match, unless the percolator
+/// clamps them (see `IntOverflow`).
 ///
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) struct OrderedQuery<T: PartialOrd + FromStr + num_traits::Zero> {
     field: OurStr,
@@ -57,6 +117,29 @@ pub(crate) struct OrderedQuery<T: PartialOrd + FromStr + num_traits::Zero> {
 
 /// Aliases for convenience.
 pub(crate) type I64Query = OrderedQuery<i64>;
+/// For integers out of the i64 range, like u64 ids.
+pub(crate) type I128Query = OrderedQuery<i128>;
+pub(crate) type F64Query = OrderedQuery<f64>;
+
+// Manual PartialEq, Eq and Hash, as f64 implements neither Eq nor Hash,
+// and NaN is not equal to itself.
+impl<T: PartialOrd + FromStr + num_traits::Zero + HashablePoint> PartialEq for OrderedQuery<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.field == other.field
+            && self.cmp_point.eq_point(&other.cmp_point)
+            && self.cmp_ord == other.cmp_ord
+    }
+}
+
+impl<T: PartialOrd + FromStr + num_traits::Zero + HashablePoint> Eq for OrderedQuery<T> {}
+
+impl<T: PartialOrd + FromStr + num_traits::Zero + HashablePoint> Hash for OrderedQuery<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.field.hash(state);
+        self.cmp_point.hash_point(state);
+        self.cmp_ord.hash(state);
+    }
+}
 
 impl<T: PartialOrd + FromStr + num_traits::Zero> OrderedQuery<T> {
     pub(crate) fn new<F: Into<OurStr>>(field: F, cmp_point: T, cmp_ord: Ordering) -> Self {
@@ -79,17 +162,18 @@ impl<T: PartialOrd + FromStr + num_traits::Zero> OrderedQuery<T> {
     }
 }
 
-impl<T: Display + PartialOrd + FromStr + num_traits::Zero> Display for OrderedQuery<T> {
+// Debug keeps a dot in floats, so they are not parsed back as integers.
+impl<T: fmt::Debug + PartialOrd + FromStr + num_traits::Zero> Display for OrderedQuery<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_str(self.field.as_ref())?;
+        f.write_str(&escape_quote_field(&self.field))?;
         write!(f, "{}", self.cmp_ord)?;
-        write!(f, "{}", self.cmp_point())
+        write!(f, "{:?}", self.cmp_point())
     }
 }
 
 impl<T: PartialOrd + FromStr + num_traits::Zero> DocMatcher for OrderedQuery<T> {
-    fn matches(&self, d: &crate::prelude::Document) -> bool {
-        d.values_iter(&self.field).is_some_and(|mut i| {
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.str_values(&self.field).is_some_and(|mut i| {
             i.any(|v| {
                 v.parse()
                     .is_ok_and(|iv: T| self.cmp_ord.compare(&iv, &self.cmp_point))
@@ -109,7 +193,7 @@ mod test_ordered {
         assert_eq!(q.field(), "field".into());
         assert_eq!(*q.cmp_point(), 123);
         assert_eq!(q.cmp_ord(), Ordering::EQ);
-        assert_eq!(format!("{}", q), "field==123");
+        assert_eq!(format!("{}", q), "field=123");
 
         let q = I64Query::new("field", 123, Ordering::GT);
         assert_eq!(format!("{}", q), "field>123");
@@ -124,13 +208,23 @@ mod test_ordered {
         assert_eq!(format!("{}", q), "field<=123");
     }
 
+    #[test]
+    fn test_clamped_int() {
+        assert_eq!(clamped_int("18446744073709551615"), Some(i64::MAX));
+        assert_eq!(clamped_int("-9223372036854775809"), Some(i64::MIN));
+        assert_eq!(clamped_int("9223372036854775807"), None);
+        assert_eq!(clamped_int("12"), None);
+        assert_eq!(clamped_int("1e30"), None);
+        assert_eq!(clamped_int("sausage"), None);
+    }
+
     #[test]
     fn test_ordering_display() {
         assert_eq!(format!("{}", Ordering::GT), ">");
         assert_eq!(format!("{}", Ordering::LT), "<");
         assert_eq!(format!("{}", Ordering::GE), ">=");
         assert_eq!(format!("{}", Ordering::LE), "<=");
-        assert_eq!(format!("{}", Ordering::EQ), "==");
+        assert_eq!(format!("{}", Ordering::EQ), "=");
     }
 
     #[test]
@@ -169,7 +263,12 @@ mod test_ordered {
         let q2 = serde_json::from_str(&json).unwrap();
         assert_eq!(q, q2);
 
-        assert_eq!(format!("{}", q), "field==123");
+        assert_eq!(format!("{}", q), "field=123");
+
+        // Floats come back exactly.
+        let q = F64Query::new("field", 948.8294363245093, Ordering::LT);
+        let json = serde_json::to_string(&q).unwrap();
+        assert_eq!(serde_json::from_str::<F64Query>(&json).unwrap(), q);
     }
 
     #[test]
@@ -177,12 +276,15 @@ mod test_ordered {
         let q = I64Query::new("field", 123, Ordering::EQ);
 
         assert!(!q.matches(&Document::default()));
-        assert!(!q.matches(&[("some", "thing")].into()));
-        assert!(!q.matches(&[("field", "not number")].into()));
-        assert!(q.matches(&[("field", "123")].into()));
-        assert!(q.matches(&[("field", "0000000123"), ("field", "not number")].into()));
-        assert!(!q.matches(&[("field", "foo")].into()));
-        assert!(!q.matches(&[("field", "")].into()));
+        assert!(!q.matches(&Document::from([("some", "thing")])));
+        assert!(!q.matches(&Document::from([("field", "not number")])));
+        assert!(q.matches(&Document::from([("field", "123")])));
+        assert!(q.matches(&Document::from([
+            ("field", "0000000123"),
+            ("field", "not number")
+        ])));
+        assert!(!q.matches(&Document::from([("field", "foo")])));
+        assert!(!q.matches(&Document::from([("field", "")])));
     }
 
     #[test]
@@ -190,25 +292,57 @@ mod test_ordered {
         let q = I64Query::new("field", 123, Ordering::LT);
         assert_eq!(format!("{}", q), "field<123");
         assert!(!q.matches(&Document::default()));
-        assert!(q.matches(&[("field", "122")].into()));
-        assert!(!q.matches(&[("field", "123")].into()));
-        assert!(!q.matches(&[("field", "124")].into()));
+        assert!(q.matches(&Document::from([("field", "122")])));
+        assert!(!q.matches(&Document::from([("field", "123")])));
+        assert!(!q.matches(&Document::from([("field", "124")])));
 
-        assert!(q.matches(&[("field", "0000000122"), ("field", "not number")].into()));
-        assert!(!q.matches(&[("field", "foo")].into()));
-        assert!(!q.matches(&[("field", "")].into()));
+        assert!(q.matches(&Document::from([
+            ("field", "0000000122"),
+            ("field", "not number")
+        ])));
+        assert!(!q.matches(&Document::from([("field", "foo")])));
+        assert!(!q.matches(&Document::from([("field", "")])));
 
         let q = OrderedQuery::<f64>::new("field", 1.23, Ordering::LT);
         assert_eq!(format!("{}", q), "field<1.23");
 
         assert!(!q.matches(&Document::default()));
-        assert!(q.matches(&[("field", "1.22000")].into()));
-        assert!(!q.matches(&[("field", "1.23")].into()));
-        assert!(!q.matches(&[("field", "1.24")].into()));
+        assert!(q.matches(&Document::from([("field", "1.22000")])));
+        assert!(!q.matches(&Document::from([("field", "1.23")])));
+        assert!(!q.matches(&Document::from([("field", "1.24")])));
+
+        assert!(q.matches(&Document::from([
+            ("field", "00000001.22000000000"),
+            ("field", "not number")
+        ])));
+        assert!(!q.matches(&Document::from([("field", "foo")])));
+        assert!(!q.matches(&Document::from([("field", "")])));
+    }
 
-        assert!(q.matches(&[("field", "00000001.22000000000"), ("field", "not number")].into()));
-        assert!(!q.matches(&[("field", "foo")].into()));
-        assert!(!q.matches(&[("field", "")].into()));
+    #[test]
+    fn test_float_hash_eq() {
+        use std::collections::HashSet;
+        let qs: HashSet<F64Query> = [
+            F64Query::new("field", 0.0, Ordering::LT),
+            F64Query::new("field", -0.0, Ordering::LT),
+            F64Query::new("field", 1.5, Ordering::LT),
+            F64Query::new("field", 1.5, Ordering::GT),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(qs.len(), 3);
+
+        let nan = F64Query::new("field", f64::NAN, Ordering::LT);
+        assert_eq!(nan, nan.clone());
+        assert_eq!(nan, F64Query::new("field", -f64::NAN, Ordering::LT));
+        assert_ne!(nan, F64Query::new("field", 1.5, Ordering::LT));
+
+        let q = F64Query::new("field", 1.5, Ordering::GE);
+        assert_eq!(format!("{}", q), "field>=1.5");
+        assert!(q.matches(&Document::from([("field", "1.5")])));
+        assert!(q.matches(&Document::from([("field", "2")])));
+        assert!(!q.matches(&Document::from([("field", "1.49")])));
+        assert!(!q.matches(&Document::from([("field", "NaN")])));
     }
 
     #[test]
@@ -216,13 +350,16 @@ mod test_ordered {
         let q = I64Query::new("field", 123, Ordering::LE);
 
         assert!(!q.matches(&Document::default()));
-        assert!(q.matches(&[("field", "122")].into()));
-        assert!(q.matches(&[("field", "123")].into()));
-        assert!(!q.matches(&[("field", "124")].into()));
-
-        assert!(q.matches(&[("field", "0000000122"), ("field", "not number")].into()));
-        assert!(!q.matches(&[("field", "foo")].into()));
-        assert!(!q.matches(&[("field", "")].into()));
+        assert!(q.matches(&Document::from([("field", "122")])));
+        assert!(q.matches(&Document::from([("field", "123")])));
+        assert!(!q.matches(&Document::from([("field", "124")])));
+
+        assert!(q.matches(&Document::from([
+            ("field", "0000000122"),
+            ("field", "not number")
+        ])));
+        assert!(!q.matches(&Document::from([("field", "foo")])));
+        assert!(!q.matches(&Document::from([("field", "")])));
     }
 
     #[test]
@@ -230,14 +367,20 @@ mod test_ordered {
         let q = I64Query::new("field", 123, Ordering::GT);
 
         assert!(!q.matches(&Document::default()));
-        assert!(!q.matches(&[("field", "122")].into()));
-        assert!(!q.matches(&[("field", "123")].into()));
-        assert!(q.matches(&[("field", "124")].into()));
-
-        assert!(!q.matches(&[("field", "not number"), ("field", "0000000122")].into()));
-        assert!(q.matches(&[("field", "not number"), ("field", "0000000124")].into()));
-        assert!(!q.matches(&[("field", "foo")].into()));
-        assert!(!q.matches(&[("field", "")].into()));
+        assert!(!q.matches(&Document::from([("field", "122")])));
+        assert!(!q.matches(&Document::from([("field", "123")])));
+        assert!(q.matches(&Document::from([("field", "124")])));
+
+        assert!(!q.matches(&Document::from([
+            ("field", "not number"),
+            ("field", "0000000122")
+        ])));
+        assert!(q.matches(&Document::from([
+            ("field", "not number"),
+            ("field", "0000000124")
+        ])));
+        assert!(!q.matches(&Document::from([("field", "foo")])));
+        assert!(!q.matches(&Document::from([("field", "")])));
     }
 
     #[test]
@@ -245,15 +388,24 @@ mod test_ordered {
         let q = OrderedQuery::<u64>::new("field", 123, Ordering::GE);
 
         assert!(!q.matches(&Document::default()));
-        assert!(!q.matches(&[("field", "122")].into()));
-        assert!(q.matches(&[("field", "123")].into()));
-        assert!(q.matches(&[("field", "124")].into()));
-
-        assert!(!q.matches(&[("field", "not number"), ("field", "0000000122")].into()));
-        assert!(q.matches(&[("field", "not number"), ("field", "0000000123")].into()));
-        assert!(q.matches(&[("field", "not number"), ("field", "0000000124")].into()));
-        assert!(!q.matches(&[("field", "foo")].into()));
-        assert!(!q.matches(&[("field", "")].into()));
+        assert!(!q.matches(&Document::from([("field", "122")])));
+        assert!(q.matches(&Document::from([("field", "123")])));
+        assert!(q.matches(&Document::from([("field", "124")])));
+
+        assert!(!q.matches(&Document::from([
+            ("field", "not number"),
+            ("field", "0000000122")
+        ])));
+        assert!(q.matches(&Document::from([
+            ("field", "not number"),
+            ("field", "0000000123")
+        ])));
+        assert!(q.matches(&Document::from([
+            ("field", "not number"),
+            ("field", "0000000124")
+        ])));
+        assert!(!q.matches(&Document::from([("field", "foo")])));
+        assert!(!q.matches(&Document::from([("field", "")])));
     }
 
     #[test]
@@ -261,17 +413,26 @@ mod test_ordered {
         let q = OrderedQuery::<u8>::new("field", 123, Ordering::GE);
 
         assert!(!q.matches(&Document::default()));
-        assert!(!q.matches(&[("field", "122")].into()));
-        assert!(q.matches(&[("field", "123")].into()));
-        assert!(q.matches(&[("field", "124")].into()));
-        assert!(q.matches(&[("field", "255")].into()));
+        assert!(!q.matches(&Document::from([("field", "122")])));
+        assert!(q.matches(&Document::from([("field", "123")])));
+        assert!(q.matches(&Document::from([("field", "124")])));
+        assert!(q.matches(&Document::from([("field", "255")])));
         // an overflow value does not match
-        assert!(!q.matches(&[("field", "256")].into()));
-
-        assert!(!q.matches(&[("field", "not number"), ("field", "0000000122")].into()));
-        assert!(q.matches(&[("field", "not number"), ("field", "0000000123")].into()));
-        assert!(q.matches(&[("field", "not number"), ("field", "0000000124")].into()));
-        assert!(!q.matches(&[("field", "foo")].into()));
-        assert!(!q.matches(&[("field", "")].into()));
+        assert!(!q.matches(&Document::from([("field", "256")])));
+
+        assert!(!q.matches(&Document::from([
+            ("field", "not number"),
+            ("field", "0000000122")
+        ])));
+        assert!(q.matches(&Document::from([
+            ("field", "not number"),
+            ("field", "0000000123")
+        ])));
+        assert!(q.matches(&Document::from([
+            ("field", "not number"),
+            ("field", "0000000124")
+        ])));
+        assert!(!q.matches(&Document::from([("field", "foo")])));
+        assert!(!q.matches(&Document::from([("field", "")])));
     }
 }
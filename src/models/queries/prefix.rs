@@ -1,11 +1,20 @@
 use crate::models::types::OurStr;
-use crate::models::{document::Document, queries::common::DocMatcher};
+use crate::models::{
+    document::DocumentView,
+    queries::common::{DocMatcher, case_fold},
+};
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) struct PrefixQuery {
     field: OurStr,
     prefix: OurStr,
+    // Like in term queries.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "std::ops::Not::not")
+    )]
+    case_fold: bool,
 }
 
 impl PrefixQuery {
@@ -14,6 +23,15 @@ impl PrefixQuery {
         PrefixQuery {
             field: field.into(),
             prefix: prefix.into(),
+            case_fold: false,
+        }
+    }
+
+    /// A case insensitive prefix query
+    pub(crate) fn case_folded<T: Into<OurStr>, U: AsRef<str>>(field: T, prefix: U) -> Self {
+        PrefixQuery {
+            case_fold: true,
+            ..Self::new(field, case_fold(prefix.as_ref()))
         }
     }
 
@@ -22,23 +40,34 @@ impl PrefixQuery {
         self.field.clone()
     }
 
-    /// The prefix
+    /// The prefix. Folded if this is case insensitive.
     pub(crate) fn prefix(&self) -> OurStr {
         self.prefix.clone()
     }
+
+    /// Is this case insensitive?
+    pub(crate) fn is_case_folded(&self) -> bool {
+        self.case_fold
+    }
 }
 
 impl DocMatcher for PrefixQuery {
     /// Does this match the document?
-    fn matches(&self, d: &Document) -> bool {
-        d.values_iter(&self.field)
-            .is_some_and(|mut i| i.any(|v| v.starts_with(self.prefix.as_ref())))
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.str_values(&self.field).is_some_and(|mut i| {
+            if self.case_fold {
+                i.any(|v| case_fold(v).starts_with(self.prefix.as_ref()))
+            } else {
+                i.any(|v| v.starts_with(self.prefix.as_ref()))
+            }
+        })
     }
 }
 
 #[cfg(test)]
 mod test_prefix {
     use super::*;
+    use crate::models::document::Document;
 
     #[test]
     fn test_new_and_getters() {
@@ -55,11 +84,24 @@ mod test_prefix {
         let q = PrefixQuery::new("field", "pre");
 
         assert!(!q.matches(&Document::default()));
-        assert!(!q.matches(&[("some", "thing")].into()));
-        assert!(!q.matches(&[("field", "pr")].into()));
-        assert!(q.matches(&[("field", "pre")].into()));
-        assert!(q.matches(&[("field", "prescience")].into()));
-        assert!(!q.matches(&[("field", "foo")].into()));
-        assert!(!q.matches(&[("field", "")].into()));
+        assert!(!q.matches(&Document::from([("some", "thing")])));
+        assert!(!q.matches(&Document::from([("field", "pr")])));
+        assert!(q.matches(&Document::from([("field", "pre")])));
+        assert!(q.matches(&Document::from([("field", "prescience")])));
+        assert!(!q.matches(&Document::from([("field", "foo")])));
+        assert!(!q.matches(&Document::from([("field", "")])));
+        assert!(!q.matches(&Document::from([("field", "Prescience")])));
+    }
+
+    #[test]
+    fn test_case_folded() {
+        let q = PrefixQuery::case_folded("field", "PRE");
+        assert!(q.is_case_folded());
+        assert_eq!(q.prefix(), "pre".into());
+
+        assert!(q.matches(&Document::from([("field", "prescience")])));
+        assert!(q.matches(&Document::from([("field", "Prescience")])));
+        assert!(q.matches(&Document::from([("field", "PRE")])));
+        assert!(!q.matches(&Document::from([("field", "Pr")])));
     }
 }
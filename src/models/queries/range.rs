@@ -0,0 +1,141 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+use crate::models::cnf::parsing::escape_quote_field;
+use crate::models::document::DocumentView;
+use crate::models::queries::{common::DocMatcher, ordered::HashablePoint};
+use crate::models::types::OurStr;
+
+///
+/// Represents a query about a value between two bounds, both included.
+///
+/// Like ordered queries, the field values are parsed as the type
+/// of the bounds, and values that do not parse will NOT match.
+///
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct RangeQuery<T: PartialOrd + FromStr> {
+    field: OurStr,
+    low: T,
+    high: T,
+}
+
+/// Aliases for convenience.
+pub(crate) type I64RangeQuery = RangeQuery<i64>;
+pub(crate) type F64RangeQuery = RangeQuery<f64>;
+
+// Manual PartialEq, Eq and Hash, as f64 implements neither Eq nor Hash,
+// and NaN is not equal to itself.
+impl<T: PartialOrd + FromStr + HashablePoint> PartialEq for RangeQuery<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.field == other.field
+            && self.low.eq_point(&other.low)
+            && self.high.eq_point(&other.high)
+    }
+}
+
+impl<T: PartialOrd + FromStr + HashablePoint> Eq for RangeQuery<T> {}
+
+impl<T: PartialOrd + FromStr + HashablePoint> Hash for RangeQuery<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.field.hash(state);
+        self.low.hash_point(state);
+        self.high.hash_point(state);
+    }
+}
+
+impl<T: PartialOrd + FromStr> RangeQuery<T> {
+    pub(crate) fn new<F: Into<OurStr>>(field: F, low: T, high: T) -> Self {
+        RangeQuery {
+            field: field.into(),
+            low,
+            high,
+        }
+    }
+
+    pub(crate) fn field(&self) -> OurStr {
+        self.field.clone()
+    }
+
+    pub(crate) fn low(&self) -> &T {
+        &self.low
+    }
+
+    pub(crate) fn high(&self) -> &T {
+        &self.high
+    }
+}
+
+// Debug keeps a dot in floats, so they are not parsed back as integers.
+impl<T: fmt::Debug + PartialOrd + FromStr> fmt::Display for RangeQuery<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} BETWEEN {:?} AND {:?}",
+            escape_quote_field(&self.field),
+            self.low,
+            self.high
+        )
+    }
+}
+
+impl<T: PartialOrd + FromStr> DocMatcher for RangeQuery<T> {
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.str_values(&self.field).is_some_and(|mut i| {
+            i.any(|v| v.parse().is_ok_and(|v: T| self.low <= v && v <= self.high))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_range {
+    use super::*;
+    use crate::prelude::Document;
+
+    #[test]
+    fn test_range() {
+        let q = I64RangeQuery::new("price", 10, 100);
+        assert_eq!(q.field(), "price".into());
+        assert_eq!((*q.low(), *q.high()), (10, 100));
+        assert_eq!(q.to_string(), "price BETWEEN 10 AND 100");
+
+        assert!(!q.matches(&Document::default()));
+        assert!(q.matches(&Document::from([("price", "10")])));
+        assert!(q.matches(&Document::from([("price", "100")])));
+        assert!(q.matches(&Document::from([("price", "foo"), ("price", "42")])));
+        assert!(!q.matches(&Document::from([("price", "9")])));
+        assert!(!q.matches(&Document::from([("price", "101")])));
+        assert!(!q.matches(&Document::from([("price", "50.5")])));
+
+        let q = F64RangeQuery::new("price", -0.5, 2.0);
+        assert_eq!(q.to_string(), "price BETWEEN -0.5 AND 2.0");
+        assert!(q.matches(&Document::from([("price", "-0.5")])));
+        assert!(q.matches(&Document::from([("price", "1")])));
+        assert!(!q.matches(&Document::from([("price", "2.01")])));
+        assert!(!q.matches(&Document::from([("price", "NaN")])));
+
+        // Empty ranges match nothing.
+        assert!(!I64RangeQuery::new("price", 2, 1).matches(&Document::from([("price", "1")])));
+    }
+
+    #[test]
+    fn test_float_hash_eq() {
+        use std::collections::HashSet;
+        let qs: HashSet<F64RangeQuery> = [
+            F64RangeQuery::new("f", 0.0, 1.0),
+            F64RangeQuery::new("f", -0.0, 1.0),
+            F64RangeQuery::new("f", 0.0, 2.0),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(qs.len(), 2);
+
+        // Like the ranges of parsed bounds that are not numbers.
+        let nan = F64RangeQuery::new("f", f64::NAN, f64::NAN);
+        assert_eq!(nan, nan.clone());
+        assert_ne!(nan, F64RangeQuery::new("f", f64::NAN, 1.0));
+    }
+}
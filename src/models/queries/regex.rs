@@ -0,0 +1,238 @@
+use std::fmt::{self, Display};
+use std::hash::Hash;
+
+use rand::seq::IndexedRandom;
+use regex::Regex;
+use regex_syntax::hir::literal::{ExtractKind, Extractor};
+use regex_syntax::hir::{Class, Hir, HirKind, Literal, Look};
+
+use crate::models::cnf::parsing::escape_quote_field;
+use crate::models::types::OurStr;
+use crate::models::{document::DocumentView, queries::common::DocMatcher};
+
+// A regex that must match the whole value of a field.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "RegexQueryDef", into = "RegexQueryDef")
+)]
+pub(crate) struct RegexQuery {
+    field: OurStr,
+    pattern: OurStr,
+    // Anchored version of the pattern.
+    regex: Regex,
+}
+
+// Only the pattern is serialised, as regexes are not.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct RegexQueryDef {
+    field: OurStr,
+    pattern: OurStr,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RegexQueryDef> for RegexQuery {
+    type Error = regex::Error;
+
+    fn try_from(def: RegexQueryDef) -> Result<Self, Self::Error> {
+        RegexQuery::new(def.field, def.pattern)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<RegexQuery> for RegexQueryDef {
+    fn from(q: RegexQuery) -> Self {
+        RegexQueryDef {
+            field: q.field,
+            pattern: q.pattern,
+        }
+    }
+}
+
+impl PartialEq for RegexQuery {
+    fn eq(&self, other: &Self) -> bool {
+        self.field == other.field && self.pattern == other.pattern
+    }
+}
+
+impl Eq for RegexQuery {}
+
+impl Hash for RegexQuery {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.field.hash(state);
+        self.pattern.hash(state);
+    }
+}
+
+impl Display for RegexQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:/{}/",
+            escape_quote_field(&self.field),
+            self.pattern.replace('/', "\\/")
+        )
+    }
+}
+
+impl RegexQuery {
+    /// Constructor. Fails if the pattern is not a valid regex.
+    pub(crate) fn new<T: Into<OurStr>, U: Into<OurStr>>(
+        field: T,
+        pattern: U,
+    ) -> Result<Self, regex::Error> {
+        let pattern = pattern.into();
+        Regex::new(&pattern)?;
+        // Anchored around the parsed pattern, as flags like
+        // verbose mode `(?x)` change how its text ends.
+        let hir = regex_syntax::parse(&pattern).expect("Valid regex");
+        let anchored = Hir::concat(vec![Hir::look(Look::Start), hir, Hir::look(Look::End)]);
+        let regex = Regex::new(&anchored.to_string())?;
+        Ok(RegexQuery {
+            field: field.into(),
+            pattern,
+            regex,
+        })
+    }
+
+    /// The field
+    pub(crate) fn field(&self) -> OurStr {
+        self.field.clone()
+    }
+
+    /// The pattern, as given
+    pub(crate) fn pattern(&self) -> OurStr {
+        self.pattern.clone()
+    }
+
+    /// The literal prefix any matching value must start with.
+    /// Can be empty.
+    pub(crate) fn literal_prefix(&self) -> String {
+        regex_syntax::parse(&self.pattern)
+            .ok()
+            .and_then(|hir| {
+                Extractor::new()
+                    .kind(ExtractKind::Prefix)
+                    .extract(&hir)
+                    .longest_common_prefix()
+                    .map(|bytes| match std::str::from_utf8(bytes) {
+                        Ok(s) => s.to_string(),
+                        // Do not cut a character in half.
+                        Err(e) => String::from_utf8_lossy(&bytes[..e.valid_up_to()]).into_owned(),
+                    })
+            })
+            .unwrap_or_default()
+    }
+
+    /// A random value matching the pattern, if one is found.
+    pub(crate) fn example_value<R: rand::Rng>(&self, rng: &mut R) -> Option<String> {
+        let hir = regex_syntax::parse(&self.pattern).ok()?;
+        let mut value = String::new();
+        sample_hir(&hir, rng, &mut value)?;
+        self.regex.is_match(&value).then_some(value)
+    }
+}
+
+// Appends a random string the regex can match, with short unbounded
+// repetitions. None for what cannot be sampled, like empty classes.
+fn sample_hir<R: rand::Rng>(hir: &Hir, rng: &mut R, s: &mut String) -> Option<()> {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => {}
+        HirKind::Literal(Literal(bytes)) => s.push_str(std::str::from_utf8(bytes).ok()?),
+        HirKind::Class(Class::Unicode(c)) => {
+            let r = c.ranges().choose(rng)?;
+            s.push(rng.random_range(r.start()..=r.end()));
+        }
+        HirKind::Class(Class::Bytes(c)) => {
+            let r = c.ranges().choose(rng)?;
+            let b = rng.random_range(r.start()..=r.end());
+            s.push(char::from(b).is_ascii().then_some(char::from(b))?);
+        }
+        HirKind::Repetition(rep) => {
+            let max = rep.max.unwrap_or(u32::MAX).min(rep.min.saturating_add(3));
+            for _ in 0..rng.random_range(rep.min..=max) {
+                sample_hir(&rep.sub, rng, s)?;
+            }
+        }
+        HirKind::Capture(cap) => sample_hir(&cap.sub, rng, s)?,
+        HirKind::Concat(hirs) => {
+            for h in hirs {
+                sample_hir(h, rng, s)?;
+            }
+        }
+        HirKind::Alternation(hirs) => sample_hir(hirs.choose(rng)?, rng, s)?,
+    }
+    Some(())
+}
+
+impl DocMatcher for RegexQuery {
+    /// Does this match the document?
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.str_values(&self.field)
+            .is_some_and(|mut i| i.any(|v| self.regex.is_match(v)))
+    }
+}
+
+#[cfg(test)]
+mod test_regex {
+    use super::*;
+    use crate::models::document::Document;
+
+    #[test]
+    fn test_new_and_getters() {
+        let q = RegexQuery::new("field", "foo.*bar").unwrap();
+        assert_eq!(q.field(), "field".into());
+        assert_eq!(q.pattern(), "foo.*bar".into());
+        assert_eq!(q.to_string(), "field:/foo.*bar/");
+        assert_eq!(
+            RegexQuery::new("field", "a/b").unwrap().to_string(),
+            "field:/a\\/b/"
+        );
+        assert_eq!(q, RegexQuery::new("field", "foo.*bar").unwrap());
+        assert_ne!(q, RegexQuery::new("field", "foo.+bar").unwrap());
+
+        assert!(RegexQuery::new("field", "foo(").is_err());
+    }
+
+    #[test]
+    fn test_literal_prefix() {
+        let prefix = |p: &str| RegexQuery::new("f", p).unwrap().literal_prefix();
+        assert_eq!(prefix("foo.*bar"), "foo");
+        assert_eq!(prefix("foo"), "foo");
+        assert_eq!(prefix("fo+"), "fo");
+        assert_eq!(prefix("foo|fab"), "f");
+        assert_eq!(prefix("foo|bar"), "");
+        assert_eq!(prefix(".*bar"), "");
+        assert_eq!(prefix("(?i)foo"), "");
+        assert_eq!(prefix("caf[éè]"), "caf");
+    }
+
+    #[test]
+    fn test_matching() {
+        let q = RegexQuery::new("field", "foo.*bar").unwrap();
+
+        assert!(!q.matches(&Document::default()));
+        assert!(!q.matches(&Document::from([("some", "foobar")])));
+        assert!(q.matches(&Document::from([("field", "foobar")])));
+        assert!(q.matches(&Document::from([("field", "foo and bar")])));
+        // Whole values only
+        assert!(!q.matches(&Document::from([("field", "a foobar")])));
+        assert!(!q.matches(&Document::from([("field", "foobar!")])));
+
+        // Alternations are anchored as a whole
+        let q = RegexQuery::new("field", "a|ab").unwrap();
+        assert!(q.matches(&Document::from([("field", "ab")])));
+        assert!(!q.matches(&Document::from([("field", "abc")])));
+
+        // Even when the pattern ends in a verbose mode comment.
+        let q = RegexQuery::new("field", "(?x)a b # comment").unwrap();
+        assert!(q.matches(&Document::from([("field", "ab")])));
+        assert!(!q.matches(&Document::from([("field", "abc")])));
+        assert!(!q.matches(&Document::from([("field", "a b")])));
+        let q = RegexQuery::new("field", "(?i)ab|c").unwrap();
+        assert!(q.matches(&Document::from([("field", "AB")])));
+        assert!(!q.matches(&Document::from([("field", "ABc")])));
+    }
+}
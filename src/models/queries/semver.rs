@@ -0,0 +1,83 @@
+use std::cmp::Ordering;
+
+use semver::Version;
+
+use crate::models::types::OurStr;
+use crate::models::{document::DocumentView, queries::common::DocMatcher};
+
+/// A query about a field being a semantic version at least
+/// as recent as a given one, like 2.3.0. Versions compare by
+/// precedence, so "10.0.0" is after "9.0.0", and "2.3.0-beta" before "2.3.0".
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct SemverGeQuery {
+    field: OurStr,
+    version: Version,
+}
+
+impl SemverGeQuery {
+    /// Constructor
+    pub(crate) fn new<T: Into<OurStr>>(field: T, version: Version) -> Self {
+        SemverGeQuery {
+            field: field.into(),
+            version,
+        }
+    }
+
+    /// The field
+    pub(crate) fn field(&self) -> OurStr {
+        self.field.clone()
+    }
+
+    /// The oldest matching version.
+    pub(crate) fn version(&self) -> &Version {
+        &self.version
+    }
+}
+
+// Is the version at least as recent as the other one?
+fn semver_ge(version: &Version, other: &Version) -> bool {
+    version.cmp_precedence(other) != Ordering::Less
+}
+
+impl DocMatcher for SemverGeQuery {
+    /// Does this match the document?
+    /// Values that are not semantic versions never match.
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.str_values(&self.field).is_some_and(|mut i| {
+            i.any(|v| Version::parse(v).is_ok_and(|v| semver_ge(&v, &self.version)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_semver {
+    use super::*;
+    use crate::models::document::Document;
+
+    #[test]
+    fn test_matching() {
+        let q = SemverGeQuery::new("app_version", Version::parse("2.3.0").unwrap());
+        assert_eq!(q.field(), "app_version".into());
+        assert_eq!(q.version(), &Version::new(2, 3, 0));
+
+        assert!(!q.matches(&Document::default()));
+        assert!(!q.matches(&Document::from([("app_version", "sausage")])));
+        assert!(!q.matches(&Document::from([("app_version", "v2.3.0")])));
+        assert!(!q.matches(&Document::from([("app_version", "2.2.9")])));
+        assert!(!q.matches(&Document::from([("app_version", "2.3.0-beta.1")])));
+        assert!(q.matches(&Document::from([("app_version", "2.3.0")])));
+        assert!(q.matches(&Document::from([("app_version", "2.3.0+build.5")])));
+        assert!(q.matches(&Document::from([("app_version", "2.10.0")])));
+        assert!(q.matches(&Document::from([("app_version", "10.0.0")])));
+        assert!(q.matches(&Document::from([
+            ("app_version", "1.0.0"),
+            ("app_version", "3.0.0")
+        ])));
+
+        // Before its release.
+        let q = SemverGeQuery::new("app_version", Version::parse("3.0.0-alpha").unwrap());
+        assert!(q.matches(&Document::from([("app_version", "3.0.0-beta")])));
+        assert!(!q.matches(&Document::from([("app_version", "2.99.0")])));
+    }
+}
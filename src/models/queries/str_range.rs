@@ -0,0 +1,78 @@
+use crate::models::types::OurStr;
+use crate::models::{document::DocumentView, queries::common::DocMatcher};
+
+/// A query about a field being between two strings, both included,
+/// like names from "a" to "f". Strings compare lexicographically by
+/// Unicode code point, so this is case sensitive and "fa" is after "f".
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct StrRangeQuery {
+    field: OurStr,
+    low: OurStr,
+    high: OurStr,
+}
+
+impl StrRangeQuery {
+    /// Constructor
+    pub(crate) fn new<T: Into<OurStr>, U: Into<OurStr>, V: Into<OurStr>>(
+        field: T,
+        low: U,
+        high: V,
+    ) -> Self {
+        StrRangeQuery {
+            field: field.into(),
+            low: low.into(),
+            high: high.into(),
+        }
+    }
+
+    /// The field
+    pub(crate) fn field(&self) -> OurStr {
+        self.field.clone()
+    }
+
+    pub(crate) fn low(&self) -> OurStr {
+        self.low.clone()
+    }
+
+    pub(crate) fn high(&self) -> OurStr {
+        self.high.clone()
+    }
+
+    /// Is this value in the range?
+    pub(crate) fn contains(&self, v: &str) -> bool {
+        *self.low <= *v && *v <= *self.high
+    }
+}
+
+impl DocMatcher for StrRangeQuery {
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.str_values(&self.field)
+            .is_some_and(|mut i| i.any(|v| self.contains(v)))
+    }
+}
+
+#[cfg(test)]
+mod test_str_range {
+    use super::*;
+    use crate::models::document::Document;
+
+    #[test]
+    fn test_matching() {
+        let q = StrRangeQuery::new("name", "b", "f");
+        assert_eq!(q.field(), "name".into());
+
+        assert!(!q.matches(&Document::default()));
+        assert!(q.matches(&Document::from([("name", "b")])));
+        assert!(q.matches(&Document::from([("name", "bob")])));
+        assert!(q.matches(&Document::from([("name", "f")])));
+        assert!(!q.matches(&Document::from([("name", "fa")])));
+        assert!(!q.matches(&Document::from([("name", "alice")])));
+        // Upper case letters are before lower case ones.
+        assert!(!q.matches(&Document::from([("name", "Bob")])));
+        assert!(q.matches(&Document::from([("name", "zoe"), ("name", "carol")])));
+
+        // An empty range matches nothing.
+        assert!(!StrRangeQuery::new("name", "f", "b").matches(&Document::from([("name", "c")])));
+    }
+}
@@ -0,0 +1,63 @@
+use crate::models::types::OurStr;
+use crate::models::{document::DocumentView, queries::common::DocMatcher};
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct SuffixQuery {
+    field: OurStr,
+    suffix: OurStr,
+}
+
+impl SuffixQuery {
+    /// Constructor
+    pub(crate) fn new<T: Into<OurStr>, U: Into<OurStr>>(field: T, suffix: U) -> Self {
+        SuffixQuery {
+            field: field.into(),
+            suffix: suffix.into(),
+        }
+    }
+
+    /// The field
+    pub(crate) fn field(&self) -> OurStr {
+        self.field.clone()
+    }
+
+    /// The suffix
+    pub(crate) fn suffix(&self) -> OurStr {
+        self.suffix.clone()
+    }
+}
+
+impl DocMatcher for SuffixQuery {
+    /// Does this match the document?
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.str_values(&self.field)
+            .is_some_and(|mut i| i.any(|v| v.ends_with(self.suffix.as_ref())))
+    }
+}
+
+#[cfg(test)]
+mod test_suffix {
+    use super::*;
+    use crate::models::document::Document;
+
+    #[test]
+    fn test_new_and_getters() {
+        let q = SuffixQuery::new("file", ".jpg");
+        assert_eq!(q.field(), "file".into());
+        assert_eq!(q.suffix(), ".jpg".into());
+    }
+
+    #[test]
+    fn test_matching() {
+        let q = SuffixQuery::new("file", ".jpg");
+
+        assert!(!q.matches(&Document::default()));
+        assert!(!q.matches(&Document::from([("some", "cat.jpg")])));
+        assert!(!q.matches(&Document::from([("file", "jpg")])));
+        assert!(q.matches(&Document::from([("file", ".jpg")])));
+        assert!(q.matches(&Document::from([("file", "cat.png"), ("file", "cat.jpg")])));
+        assert!(!q.matches(&Document::from([("file", "cat.jpg.gz")])));
+        assert!(!q.matches(&Document::from([("file", "")])));
+    }
+}
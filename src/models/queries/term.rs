@@ -1,9 +1,9 @@
 use roaring::RoaringBitmap;
 
-use crate::models::document::Document;
+use crate::models::document::DocumentView;
 use crate::models::document::MATCH_ALL;
 use crate::models::index::*;
-use crate::models::queries::common::DocMatcher;
+use crate::models::queries::common::{DocMatcher, case_fold, normalize};
 
 use crate::models::types::OurStr;
 
@@ -12,6 +12,18 @@ use crate::models::types::OurStr;
 pub(crate) struct TermQuery {
     field: OurStr,
     term: OurStr,
+    // The term is folded, and so are the values it is compared to.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "std::ops::Not::not")
+    )]
+    case_fold: bool,
+    // Same, but the values are trimmed too.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "std::ops::Not::not")
+    )]
+    normalized: bool,
 }
 
 impl TermQuery {
@@ -20,6 +32,25 @@ impl TermQuery {
         TermQuery {
             field: field.into(),
             term: term.into(),
+            case_fold: false,
+            normalized: false,
+        }
+    }
+
+    /// A case insensitive term query
+    pub(crate) fn case_folded<T: Into<OurStr>, U: AsRef<str>>(field: T, term: U) -> Self {
+        TermQuery {
+            case_fold: true,
+            ..Self::new(field, case_fold(term.as_ref()))
+        }
+    }
+
+    /// A term query trimming and case folding the values,
+    /// and the term.
+    pub(crate) fn normalized<T: Into<OurStr>, U: AsRef<str>>(field: T, term: U) -> Self {
+        TermQuery {
+            normalized: true,
+            ..Self::new(field, normalize(term.as_ref()))
         }
     }
 
@@ -33,11 +64,22 @@ impl TermQuery {
         self.field.clone()
     }
 
-    /// The term
+    /// The term. Folded if this is case insensitive, and trimmed
+    /// too if normalized.
     pub fn term(&self) -> OurStr {
         self.term.clone()
     }
 
+    /// Is this case insensitive?
+    pub(crate) fn is_case_folded(&self) -> bool {
+        self.case_fold
+    }
+
+    /// Does this trim and case fold the values?
+    pub(crate) fn is_normalized(&self) -> bool {
+        self.normalized
+    }
+
     /// Bitmap of matching documents from the given index.
     pub(crate) fn docs_from_idx<'a>(&self, index: &'a Index) -> &'a RoaringBitmap {
         index.docs_from_fv(self.field.as_ref(), self.term.as_ref())
@@ -46,16 +88,23 @@ impl TermQuery {
 
 impl DocMatcher for TermQuery {
     /// Does this match the document?
-    fn matches(&self, d: &Document) -> bool {
-        d.values_iter(&self.field)
-            .is_some_and(|mut i| i.any(|v| v == self.term))
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.str_values(&self.field).is_some_and(|mut i| {
+            if self.normalized {
+                i.any(|v| normalize(v) == self.term.as_ref())
+            } else if self.case_fold {
+                i.any(|v| case_fold(v) == self.term.as_ref())
+            } else {
+                i.any(|v| v == self.term.as_ref())
+            }
+        })
     }
 }
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::document::MATCH_ALL;
     use crate::models::document::Document;
+    use crate::models::document::MATCH_ALL;
 
     #[test]
     fn test_new_and_getters() {
@@ -115,5 +164,42 @@ mod tests {
         // No match - empty doc
         let doc_empty = Document::default();
         assert!(!query.matches(&doc_empty));
+
+        // No match - different case
+        let doc_upper = Document::default().with_value("field", "Value");
+        assert!(!query.matches(&doc_upper));
+    }
+
+    #[test]
+    fn test_case_folded() {
+        let query = TermQuery::case_folded("field", "VaLue");
+        assert!(query.is_case_folded());
+        assert!(!TermQuery::new("field", "value").is_case_folded());
+        assert_eq!(query.term(), "value".into());
+        assert_ne!(query, TermQuery::new("field", "value"));
+
+        assert!(query.matches(&Document::from([("field", "value")])));
+        assert!(query.matches(&Document::from([("field", "other"), ("field", "VALUE")])));
+        assert!(!query.matches(&Document::from([("field", "values")])));
+        assert!(!query.matches(&Document::from([("other_field", "Value")])));
+    }
+
+    #[test]
+    fn test_normalized() {
+        let query = TermQuery::normalized("field", " VaLue\t");
+        assert!(query.is_normalized());
+        assert!(!query.is_case_folded());
+        assert_eq!(query.term(), "value".into());
+
+        assert!(query.matches(&Document::from([("field", "value")])));
+        assert!(query.matches(&Document::from([
+            ("field", "other"),
+            ("field", "  VALUE\n")
+        ])));
+        assert!(!query.matches(&Document::from([("field", "va lue")])));
+        assert!(
+            !TermQuery::case_folded("field", "value")
+                .matches(&Document::from([("field", " value")]))
+        );
     }
 }
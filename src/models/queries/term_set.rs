@@ -0,0 +1,101 @@
+use std::fmt;
+
+use itertools::Itertools;
+
+use crate::models::cnf::parsing::{escape_quote_field, escape_quote_set_term};
+use crate::models::types::OurStr;
+use crate::models::{document::DocumentView, queries::common::DocMatcher};
+
+///
+/// Represents a query about a field having any of a set of values.
+///
+/// It is one literal, however many values there are, and each value
+/// is indexed like a term query.
+///
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct TermSetQuery {
+    field: OurStr,
+    // Sorted and without duplicates.
+    terms: Vec<OurStr>,
+}
+
+impl TermSetQuery {
+    /// Constructor
+    pub(crate) fn new<T: Into<OurStr>, U: Into<OurStr>>(
+        field: T,
+        terms: impl IntoIterator<Item = U>,
+    ) -> Self {
+        TermSetQuery {
+            field: field.into(),
+            terms: terms
+                .into_iter()
+                .map(|t| t.into())
+                .sorted()
+                .dedup()
+                .collect(),
+        }
+    }
+
+    /// The field
+    pub(crate) fn field(&self) -> OurStr {
+        self.field.clone()
+    }
+
+    /// The terms, in lexicographic order
+    pub(crate) fn terms(&self) -> &[OurStr] {
+        &self.terms
+    }
+}
+
+impl fmt::Display for TermSetQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} IN {{{}}}",
+            escape_quote_field(&self.field),
+            self.terms
+                .iter()
+                .map(|t| escape_quote_set_term(t))
+                .join(", ")
+        )
+    }
+}
+
+impl DocMatcher for TermSetQuery {
+    /// Does this match the document?
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.str_values(&self.field).is_some_and(|mut i| {
+            i.any(|v| self.terms.binary_search_by(|t| t.as_ref().cmp(v)).is_ok())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_term_set {
+    use super::*;
+    use crate::models::document::Document;
+
+    #[test]
+    fn test_term_set() {
+        let q = TermSetQuery::new("colour", ["red", "blue", "red", "light green"]);
+        assert_eq!(q.field(), "colour".into());
+        assert_eq!(
+            q.terms(),
+            &["blue".into(), "light green".into(), "red".into()]
+        );
+        assert_eq!(q.to_string(), "colour IN {blue, \"light green\", red}");
+
+        assert!(!q.matches(&Document::default()));
+        assert!(!q.matches(&Document::from([("colour", "green")])));
+        assert!(!q.matches(&Document::from([("taste", "red")])));
+        assert!(q.matches(&Document::from([("colour", "red")])));
+        assert!(q.matches(&Document::from([
+            ("colour", "green"),
+            ("colour", "light green")
+        ])));
+
+        let q = TermSetQuery::new("colour", Vec::<&str>::new());
+        assert!(!q.matches(&Document::from([("colour", "")])));
+    }
+}
@@ -0,0 +1,151 @@
+use std::fmt;
+
+use crate::models::queries::common::case_fold;
+use crate::models::types::OurStr;
+use crate::models::{document::DocumentView, queries::common::DocMatcher};
+
+/// How free text values are split into the words
+/// [`crate::prelude::Query::words`] queries match.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Analyzer {
+    /// Splits on whitespace and folds the case of the words.
+    #[default]
+    Lowercase,
+    /// Splits on whitespace only.
+    Whitespace,
+    /// Splits on anything that is not a letter or a digit,
+    /// like punctuation, and folds the case of the words.
+    Alphanumeric,
+}
+
+impl Analyzer {
+    /// The words of the text, in order.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::Analyzer;
+    ///
+    /// assert_eq!(Analyzer::default().tokens("The Quick fox."), vec!["the", "quick", "fox."]);
+    /// assert_eq!(Analyzer::Whitespace.tokens("The Quick fox."), vec!["The", "Quick", "fox."]);
+    /// assert_eq!(Analyzer::Alphanumeric.tokens("The Quick fox."), vec!["the", "quick", "fox"]);
+    /// ```
+    pub fn tokens(&self, text: &str) -> Vec<String> {
+        match self {
+            Analyzer::Lowercase => text.split_whitespace().map(case_fold).collect(),
+            Analyzer::Whitespace => text.split_whitespace().map(String::from).collect(),
+            Analyzer::Alphanumeric => text
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|w| !w.is_empty())
+                .map(case_fold)
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for Analyzer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Analyzer::Lowercase => write!(f, "lowercase"),
+            Analyzer::Whitespace => write!(f, "whitespace"),
+            Analyzer::Alphanumeric => write!(f, "alphanumeric"),
+        }
+    }
+}
+
+// One word of the analyzed values of a field.
+// Queries on several words are conjunctions or disjunctions of these.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct MatchQuery {
+    field: OurStr,
+    token: OurStr,
+    #[cfg_attr(feature = "serde", serde(default))]
+    analyzer: Analyzer,
+}
+
+impl MatchQuery {
+    /// Constructor. The token must be one the analyzer produces.
+    pub(crate) fn new<T: Into<OurStr>, U: Into<OurStr>>(
+        field: T,
+        token: U,
+        analyzer: Analyzer,
+    ) -> Self {
+        MatchQuery {
+            field: field.into(),
+            token: token.into(),
+            analyzer,
+        }
+    }
+
+    /// The field
+    pub(crate) fn field(&self) -> OurStr {
+        self.field.clone()
+    }
+
+    /// The token
+    pub(crate) fn token(&self) -> OurStr {
+        self.token.clone()
+    }
+
+    /// The analyzer
+    pub(crate) fn analyzer(&self) -> Analyzer {
+        self.analyzer
+    }
+}
+
+impl DocMatcher for MatchQuery {
+    /// Does this match the document?
+    fn matches(&self, d: &impl DocumentView) -> bool {
+        d.str_values(&self.field).is_some_and(|mut i| {
+            i.any(|v| self.analyzer.tokens(v).iter().any(|t| *t == *self.token))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_text_match {
+    use super::*;
+    use crate::models::document::Document;
+
+    #[test]
+    fn test_tokens() {
+        assert!(Analyzer::default().tokens("").is_empty());
+        assert!(Analyzer::Alphanumeric.tokens(" -- ").is_empty());
+        assert_eq!(
+            Analyzer::default().tokens("  Straße\tund\nWEG "),
+            vec!["strasse", "und", "weg"]
+        );
+        assert_eq!(
+            Analyzer::Alphanumeric.tokens("l'été, 2024!"),
+            vec!["l", "été", "2024"]
+        );
+        assert_eq!(Analyzer::Whitespace.to_string(), "whitespace");
+    }
+
+    #[test]
+    fn test_matching() {
+        let q = MatchQuery::new("title", "fox", Analyzer::default());
+        assert_eq!(q.field(), "title".into());
+        assert_eq!(q.token(), "fox".into());
+        assert_eq!(q.analyzer(), Analyzer::Lowercase);
+
+        assert!(!q.matches(&Document::default()));
+        assert!(!q.matches(&Document::from([("body", "fox")])));
+        assert!(q.matches(&Document::from([("title", "The quick brown Fox")])));
+        assert!(q.matches(&Document::from([("title", "dog"), ("title", "fox")])));
+        assert!(!q.matches(&Document::from([("title", "foxes")])));
+        assert!(!q.matches(&Document::from([("title", "fox.")])));
+
+        let q = MatchQuery::new("title", "fox", Analyzer::Alphanumeric);
+        assert!(q.matches(&Document::from([("title", "The Fox.")])));
+
+        let q = MatchQuery::new("title", "fox", Analyzer::Whitespace);
+        assert!(!q.matches(&Document::from([("title", "The Fox")])));
+        assert!(q.matches(&Document::from([("title", "the fox")])));
+    }
+}
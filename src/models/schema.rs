@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+
+use h3o::CellIndex;
+
+use crate::models::cnf::parsing::{FieldType, ParserOptions};
+use crate::models::document::Document;
+use crate::models::queries::latlng_within::parse_latlng;
+
+/// How many values a field of a [`Schema`] can have in a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Multiplicity {
+    /// No value or one.
+    #[default]
+    Optional,
+    /// Exactly one value.
+    Required,
+    /// Any number of values.
+    Many,
+    /// At least one value.
+    AtLeastOne,
+}
+
+impl Multiplicity {
+    fn accepts(&self, n: usize) -> bool {
+        match self {
+            Multiplicity::Optional => n <= 1,
+            Multiplicity::Required => n == 1,
+            Multiplicity::Many => true,
+            Multiplicity::AtLeastOne => n >= 1,
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Multiplicity::Optional => "at most one value",
+            Multiplicity::Required => "exactly one value",
+            Multiplicity::Many => "any number of values",
+            Multiplicity::AtLeastOne => "at least one value",
+        }
+    }
+}
+
+/// The fields documents can have, with the type and number of their values.
+/// Use it to check documents with [`Document::validate`], and to reject
+/// queries on unknown fields with the percolator builder's `schema`,
+/// to catch typos like `color` instead of `colour` early.
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+/// use mokaccino::prelude::parsing::FieldType;
+///
+/// let schema = Schema::default()
+///     .field("colour", FieldType::Keyword, Multiplicity::Many)
+///     .field("price", FieldType::Integer, Multiplicity::Required);
+///
+/// let d: Document = [("colour", "blue"), ("colour", "red"), ("price", "12")].into();
+/// assert!(d.validate(&schema).is_ok());
+///
+/// let d: Document = [("color", "blue"), ("price", "cheap")].into();
+/// assert_eq!(
+///     d.validate(&schema),
+///     Err("Unknown field color; Field price: not a valid integer: cheap".to_string())
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Schema {
+    fields: BTreeMap<String, (FieldType, Multiplicity)>,
+}
+
+impl Schema {
+    /// This schema with a new field, replacing any field with the same name.
+    pub fn field<T: Into<String>>(
+        mut self,
+        field: T,
+        field_type: FieldType,
+        multiplicity: Multiplicity,
+    ) -> Self {
+        self.fields.insert(field.into(), (field_type, multiplicity));
+        self
+    }
+
+    /// Is this field in the schema?
+    pub fn has_field(&self, field: &str) -> bool {
+        self.fields.contains_key(field)
+    }
+
+    /// The type of the field, if it is in the schema.
+    pub fn field_type(&self, field: &str) -> Option<FieldType> {
+        self.fields.get(field).map(|(t, _)| *t)
+    }
+
+    /// Parser options giving the fields their types, so `price:10`
+    /// is a number for an integer field. See [`ParserOptions::field_type`].
+    pub fn parser_options(&self) -> ParserOptions {
+        self.fields
+            .iter()
+            .fold(ParserOptions::default(), |o, (f, (t, _))| {
+                o.field_type(f.clone(), *t)
+            })
+    }
+
+    // Everything wrong with the document, in the order of the fields.
+    pub(crate) fn problems(&self, d: &Document) -> Vec<String> {
+        let mut unknown = d
+            .fields()
+            .filter(|f| !self.has_field(f))
+            .map(|f| format!("Unknown field {}", f))
+            .collect::<Vec<_>>();
+        unknown.sort();
+
+        let invalid = self.fields.iter().flat_map(|(field, (field_type, m))| {
+            let values = d.values_ref(field).map(|vs| vs.as_slice()).unwrap_or(&[]);
+            let count = (!m.accepts(values.len())).then(|| {
+                format!(
+                    "Field {}: expected {}, got {}",
+                    field,
+                    m.describe(),
+                    values.len()
+                )
+            });
+            count
+                .into_iter()
+                .chain(
+                    values
+                        .iter()
+                        .filter(|v| !is_valid(*field_type, v))
+                        .map(move |v| {
+                            format!(
+                                "Field {}: not a valid {}: {}",
+                                field,
+                                describe(*field_type),
+                                v
+                            )
+                        }),
+                )
+        });
+
+        unknown.into_iter().chain(invalid).collect()
+    }
+}
+
+fn is_valid(field_type: FieldType, v: &str) -> bool {
+    match field_type {
+        FieldType::Keyword => true,
+        // Like integer comparisons, which go beyond i64.
+        FieldType::Integer => v.parse::<i128>().is_ok(),
+        FieldType::Float => v.parse::<f64>().is_ok(),
+        FieldType::LatLng => parse_latlng(v).is_some(),
+        FieldType::H3Cell => v.parse::<CellIndex>().is_ok(),
+    }
+}
+
+fn describe(field_type: FieldType) -> &'static str {
+    match field_type {
+        FieldType::Keyword => "keyword",
+        FieldType::Integer => "integer",
+        FieldType::Float => "float",
+        FieldType::LatLng => "latitude,longitude",
+        FieldType::H3Cell => "H3 cell",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn schema() -> Schema {
+        Schema::default()
+            .field("name", FieldType::Keyword, Multiplicity::Required)
+            .field("tag", FieldType::Keyword, Multiplicity::Many)
+            .field("price", FieldType::Integer, Multiplicity::Optional)
+            .field("weight", FieldType::Float, Multiplicity::Optional)
+            .field("position", FieldType::LatLng, Multiplicity::AtLeastOne)
+            .field("cell", FieldType::H3Cell, Multiplicity::Optional)
+    }
+
+    #[test]
+    fn test_valid() {
+        let d: Document = [
+            ("name", "bob"),
+            ("position", "48.86,2.34"),
+            ("position", "48.0,2.0"),
+            ("price", "18446744073709551615"),
+            ("weight", "1.5"),
+            ("cell", "861f09b27ffffff"),
+        ]
+        .into();
+        assert_eq!(schema().problems(&d), Vec::<String>::new());
+        assert!(d.validate(&schema()).is_ok());
+    }
+
+    #[test]
+    fn test_problems() {
+        let d: Document = [
+            ("name", "bob"),
+            ("name", "alice"),
+            ("price", "12.5"),
+            ("weight", "heavy"),
+            ("cell", "nowhere"),
+            ("zzz", "a"),
+            ("colour", "blue"),
+        ]
+        .into();
+        assert_eq!(
+            schema().problems(&d),
+            vec![
+                "Unknown field colour",
+                "Unknown field zzz",
+                "Field cell: not a valid H3 cell: nowhere",
+                "Field name: expected exactly one value, got 2",
+                "Field position: expected at least one value, got 0",
+                "Field price: not a valid integer: 12.5",
+                "Field weight: not a valid float: heavy",
+            ]
+        );
+        assert_eq!(Schema::default().problems(&Document::default()).len(), 0);
+    }
+
+    #[test]
+    fn test_accessors() {
+        let s = schema();
+        assert!(s.has_field("tag"));
+        assert!(!s.has_field("colour"));
+        assert_eq!(s.field_type("price"), Some(FieldType::Integer));
+        assert_eq!(s.field_type("colour"), None);
+        // The last definition wins.
+        let s = s.field("price", FieldType::Float, Multiplicity::Optional);
+        assert_eq!(s.field_type("price"), Some(FieldType::Float));
+    }
+}
@@ -5,3 +5,11 @@ pub(crate) type OurRc<T> = std::sync::Arc<T>;
 pub(crate) type OurRc<T> = std::rc::Rc<T>;
 
 pub(crate) type OurStr = OurRc<str>;
+
+#[cfg(feature = "deterministic")]
+pub(crate) type OurHasher = foldhash::fast::FixedState;
+
+#[cfg(not(feature = "deterministic"))]
+pub(crate) type OurHasher = hashbrown::DefaultHashBuilder;
+
+pub(crate) type OurHashMap<K, V> = hashbrown::HashMap<K, V, OurHasher>;
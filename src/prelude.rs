@@ -1,6 +1,11 @@
-pub use crate::geotools::Meters;
+pub use crate::geotools::{Meters, Polygon};
 pub use crate::models::cnf::parsing;
-pub use crate::models::cnf::{CNFQueryable, Query};
-pub use crate::models::document::Document;
-pub use crate::models::percolator::{Percolator, PercolatorUid};
+pub use crate::models::cnf::patterns;
+pub use crate::models::cnf::template::QueryTemplate;
+pub use crate::models::cnf::{CNFQueryable, Query, SharedQuery};
+pub use crate::models::document::{Document, DocumentRef};
+pub use crate::models::percolator::{Percolate, Percolator, PercolatorUid};
 pub use crate::models::percolator_core::Qid;
+pub use crate::models::percolator_linear::LinearPercolator;
+pub use crate::models::queries::text_match::Analyzer;
+pub use crate::models::schema::{Multiplicity, Schema};
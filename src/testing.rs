@@ -1,5 +1,15 @@
-/// Testing oriented utilities.
-use crate::prelude::{Query, parsing};
+//! Testing oriented utilities.
+//!
+//! With the `testing` feature, this also has the random generators
+//! of queries and documents used by this crate's own tests, for
+//! property testing code built around the percolator.
+
+use rand::seq::IndexedRandom;
+
+use crate::prelude::{Document, Query, parsing};
+
+// How many random documents to try for each one asked for.
+const EXAMPLE_ATTEMPTS: usize = 100;
 
 impl Query {
     /// Builds a random query. This is mainly useful for testing and benchmarking.
@@ -28,4 +38,297 @@ impl Query {
     pub fn random_string<U: rand::Rng>(rng: &mut U) -> String {
         parsing::random_query(rng, 3).to_string()
     }
+
+    /// Generates up to `n` random documents matching this query,
+    /// for instance to test an application against its queries.
+    ///
+    /// Documents get a value for one literal of each clause, and only
+    /// the ones matching the query are kept. There can be less than `n`
+    /// of them, and none at all for queries no document can match,
+    /// like `a:x AND NOT a:x`.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let mut rng = rand::rng();
+    /// let q: Query = "colour:blue AND (price < 10 OR name:bur*) AND NOT taste:bitter"
+    ///     .parse()
+    ///     .unwrap();
+    /// let docs = q.example_documents(&mut rng, 5);
+    /// assert_eq!(docs.len(), 5);
+    /// assert!(docs.iter().all(|d| q.matches(d)));
+    /// ```
+    pub fn example_documents<U: rand::Rng>(&self, rng: &mut U, n: usize) -> Vec<Document> {
+        (0..n * EXAMPLE_ATTEMPTS)
+            .filter_map(|_| self.example_document(rng))
+            .take(n)
+            .collect()
+    }
+
+    /// Generates up to `n` random documents NOT matching this query,
+    /// but only one field away from a matching one. They either lack
+    /// a field of a matching document, or have a value a negated
+    /// part of the query excludes.
+    ///
+    /// Like [`Query::example_documents`], there can be less than `n` of them.
+    ///
+    /// Example:
+    /// ```
+    /// use mokaccino::prelude::*;
+    ///
+    /// let mut rng = rand::rng();
+    /// let q: Query = "colour:blue AND NOT taste:bitter".parse().unwrap();
+    /// let docs = q.example_near_misses(&mut rng, 5);
+    /// assert_eq!(docs.len(), 5);
+    /// assert!(docs.iter().all(|d| !q.matches(d)));
+    /// ```
+    pub fn example_near_misses<U: rand::Rng>(&self, rng: &mut U, n: usize) -> Vec<Document> {
+        let negated = self
+            .clauses()
+            .iter()
+            .flat_map(|c| c.literals())
+            .filter(|l| l.is_negated())
+            .collect::<Vec<_>>();
+        (0..n * EXAMPLE_ATTEMPTS)
+            .filter_map(|_| {
+                let d = self.example_document(rng)?;
+                let fields = d.fields().collect::<Vec<_>>();
+                let miss = if negated.is_empty() || (!fields.is_empty() && rng.random_bool(0.5)) {
+                    let dropped = fields.choose(rng)?;
+                    d.field_values()
+                        .filter(|(f, _)| f != dropped)
+                        .fold(Document::new(), |d, (f, v)| d.with_value(f, v))
+                } else {
+                    let (f, v) = negated.choose(rng)?.query().example_value(rng)?;
+                    d.with_value(f, v)
+                };
+                (!self.matches(&miss)).then_some(miss)
+            })
+            .take(n)
+            .collect()
+    }
+
+    // A random document, if it matches.
+    fn example_document<U: rand::Rng>(&self, rng: &mut U) -> Option<Document> {
+        let mut d = Document::new();
+        for c in self.clauses() {
+            if let Some((f, v)) = c.literals().choose(rng)?.example_value(rng) {
+                d.with_value_mut(f, v);
+            }
+        }
+        self.matches(&d).then_some(d)
+    }
+}
+
+/// A random query, with up to `max_depth` levels of nested `AND`, `OR`
+/// and `NOT`. Like [`Query::random`], with a choice of depth.
+/// The Conjunctive Normal Form of queries grows exponentially
+/// with their depth, so keep it small.
+#[cfg(feature = "testing")]
+pub fn random_query<U: rand::Rng>(rng: &mut U, max_depth: usize) -> Query {
+    parsing::random_query(rng, max_depth).to_cnf()
+}
+
+/// A random query string, with up to `max_depth` levels of nesting.
+/// It always parses. Like [`Query::random_string`], with a choice of depth.
+#[cfg(feature = "testing")]
+pub fn random_query_string<U: rand::Rng>(rng: &mut U, max_depth: usize) -> String {
+    parsing::random_query(rng, max_depth).to_string()
+}
+
+/// A random query with a single literal, like `field:value`,
+/// `field<12` or `field H3IN cell`, on a random field.
+#[cfg(feature = "testing")]
+pub fn random_atom<U: rand::Rng>(rng: &mut U) -> Query {
+    parsing::random_atom(rng).to_cnf()
+}
+
+/// A random document with a value of the given type for each field,
+/// written the way queries on such fields expect to read it.
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::parsing::FieldType;
+/// use mokaccino::testing::random_document_for_schema;
+///
+/// let mut rng = rand::rng();
+/// let d = random_document_for_schema(
+///     &mut rng,
+///     &[("name", FieldType::Keyword), ("price", FieldType::Integer)],
+/// );
+/// assert!(d.values("price")[0].parse::<i64>().is_ok());
+/// ```
+#[cfg(feature = "testing")]
+pub fn random_document_for_schema<U: rand::Rng>(
+    rng: &mut U,
+    schema: &[(&str, parsing::FieldType)],
+) -> Document {
+    use parsing::FieldType;
+    schema
+        .iter()
+        .fold(Document::new(), |d, (field, field_type)| match field_type {
+            FieldType::Keyword => {
+                let len = rng.random_range(1..20);
+                let value = (0..len)
+                    .map(|_| char::from(rng.sample(rand::distr::Alphanumeric)))
+                    .collect::<String>();
+                d.with_value(*field, value)
+            }
+            FieldType::Integer => d.with_value(*field, rng.random_range(-1000..1000).to_string()),
+            FieldType::Float => d.with_value(*field, rng.random_range(-1000.0..1000.0).to_string()),
+            FieldType::LatLng => {
+                let ll = h3o::LatLng::from(parsing::random_h3cell(rng));
+                d.with_latlng(*field, ll.lat(), ll.lng())
+            }
+            FieldType::H3Cell => d.with_h3_cell(*field, parsing::random_h3cell(rng)),
+        })
+}
+
+/// Asserts a percolator matches a document with exactly the given IDs,
+/// in any order. The percolator can be anything implementing
+/// [`crate::prelude::Percolate`].
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+/// use mokaccino::assert_matches;
+///
+/// let mut p = PercolatorUid::<&str>::default();
+/// p.index_query_uid("colour".has_value("blue"), "blue");
+/// p.index_query_uid("taste".has_value("sweet"), "sweet");
+///
+/// let d: Document = [("colour", "blue"), ("taste", "sweet")].into();
+/// assert_matches!(p, d, ["sweet", "blue"]);
+/// assert_matches!(p, Document::new(), []);
+/// ```
+#[cfg(feature = "testing")]
+#[macro_export]
+macro_rules! assert_matches {
+    ($percolator:expr, $doc:expr, [$($uid:expr),* $(,)?]) => {
+        if let Err(e) = $crate::testing::check_matches(
+            &$percolator,
+            &$doc,
+            vec![$($uid),*],
+        ) {
+            panic!("{}", e);
+        }
+    };
+}
+
+/// Checks a percolator matches a document with exactly the `expected` IDs,
+/// in any order. The error lists the missing and unexpected IDs.
+/// See [`crate::assert_matches`] for a panicking version.
+#[cfg(feature = "testing")]
+pub fn check_matches<T, P>(percolator: &P, d: &Document, expected: Vec<T>) -> Result<(), String>
+where
+    T: std::fmt::Debug + Eq + std::hash::Hash,
+    P: crate::prelude::Percolate<T> + ?Sized,
+{
+    let matched = percolator
+        .percolate(d)
+        .collect::<std::collections::HashSet<_>>();
+    let expected = expected.iter().collect::<std::collections::HashSet<_>>();
+    if matched == expected {
+        return Ok(());
+    }
+    let missing = expected.difference(&matched).collect::<Vec<_>>();
+    let unexpected = matched.difference(&expected).collect::<Vec<_>>();
+    Err(format!(
+        "Unexpected matches for {:?}. Missing: {:?}, unexpected: {:?}",
+        d, missing, unexpected
+    ))
+}
+
+/// Asserts the [`Display`](std::fmt::Display) of the statistics of a percolator
+/// is the content of the golden file at `path`, to notice changes in the shape
+/// of a rule set, like more clauses per query or new preheaters.
+///
+/// The file is written when it does not exist, or when the environment
+/// variable `MOKACCINO_UPDATE_SNAPSHOTS` is set, to accept the changes.
+/// Trailing whitespace is ignored.
+///
+/// # Panics
+///
+/// If the statistics are different, or the file cannot be read or written.
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+/// use mokaccino::testing::assert_stats_snapshot;
+///
+/// let mut p = Percolator::default();
+/// p.add_query("colour".has_value("blue") & "taste".has_value("sweet"));
+///
+/// let path = std::env::temp_dir().join("mokaccino_doc_stats.txt");
+/// # let _ = std::fs::remove_file(&path);
+/// assert_stats_snapshot(p.stats(), &path);
+/// // The same statistics later.
+/// assert_stats_snapshot(p.stats(), &path);
+/// ```
+#[cfg(feature = "testing")]
+#[track_caller]
+pub fn assert_stats_snapshot<P: AsRef<std::path::Path>>(
+    stats: &crate::models::percolator_core::PercolatorStats,
+    path: P,
+) {
+    let path = path.as_ref();
+    let actual = stats.to_string();
+    if !path.exists() || std::env::var_os("MOKACCINO_UPDATE_SNAPSHOTS").is_some() {
+        if let Err(e) = std::fs::write(path, format!("{}\n", actual.trim_end())) {
+            panic!("Cannot write snapshot {}: {}", path.display(), e);
+        }
+        return;
+    }
+    let expected = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("Cannot read snapshot {}: {}", path.display(), e));
+    assert!(
+        expected.trim_end() == actual.trim_end(),
+        "Statistics differ from snapshot {}. Set MOKACCINO_UPDATE_SNAPSHOTS to accept them.\n\
+         Expected:\n{}\nActual:\n{}",
+        path.display(),
+        expected.trim_end(),
+        actual.trim_end()
+    );
+}
+
+/// `n` random queries with up to `max_depth` levels of nesting,
+/// like [`random_query`], to fill a percolator.
+#[cfg(feature = "testing")]
+pub fn random_queries<U: rand::Rng>(rng: &mut U, n: usize, max_depth: usize) -> Vec<Query> {
+    (0..n).map(|_| random_query(rng, max_depth)).collect()
+}
+
+/// Up to `n` random documents for a corpus of queries, each either
+/// matching a random one of them or a near miss of it. See
+/// [`Query::example_documents`] and [`Query::example_near_misses`].
+/// There are less than `n` for queries few documents can match.
+///
+/// Example:
+/// ```
+/// use mokaccino::prelude::*;
+/// use mokaccino::testing::{random_documents_for, random_queries};
+///
+/// let mut rng = rand::rng();
+/// let queries = random_queries(&mut rng, 10, 2);
+/// let docs = random_documents_for(&mut rng, &queries, 20);
+/// assert!(docs.len() <= 20);
+/// ```
+#[cfg(feature = "testing")]
+pub fn random_documents_for<U: rand::Rng>(
+    rng: &mut U,
+    queries: &[Query],
+    n: usize,
+) -> Vec<Document> {
+    (0..n * EXAMPLE_ATTEMPTS)
+        .filter_map(|_| {
+            let q = queries.choose(rng)?;
+            if rng.random_bool(0.5) {
+                q.example_document(rng)
+            } else {
+                q.example_near_misses(rng, 1).pop()
+            }
+        })
+        .take(n)
+        .collect()
 }
@@ -1,4 +1,10 @@
-use mokaccino::models::{cnf::*, document::Document, percolator::Percolator, percolator_core::Qid};
+use mokaccino::models::{
+    cnf::*,
+    document::{Document, DocumentRef},
+    percolator::{MemoryUidStore, Percolator, PercolatorUid},
+    percolator_core::{EmptyDocuments, IntOverflow, PercolatorError, Qid},
+};
+use mokaccino::prelude::Analyzer;
 use num_traits::Zero;
 
 use h3o::CellIndex;
@@ -11,8 +17,8 @@ fn test_percolator_vanilla() {
     let q1_id = mp.add_query(q1);
     assert_eq!(q1_id, 0);
     assert!(!mp.to_string().is_empty());
-    assert!(!mp.get_query(q1_id).to_string().is_empty());
-    assert_eq!(Some(mp.get_query(q1_id)), mp.safe_get_query(q1_id));
+    assert!(!mp.get_query(&q1_id).to_string().is_empty());
+    assert_eq!(Some(mp.get_query(&q1_id)), mp.safe_get_query(&q1_id));
 
     let d = Document::new().with_value("colour", "blue");
 
@@ -62,8 +68,8 @@ fn test_percolator_core() {
     let q1_id = mp.add_query(q1);
     assert_eq!(q1_id, 0);
     assert!(!mp.to_string().is_empty());
-    assert!(!mp.get_query(q1_id).to_string().is_empty());
-    assert_eq!(Some(mp.get_query(q1_id)), mp.safe_get_query(q1_id));
+    assert!(!mp.get_query(&q1_id).to_string().is_empty());
+    assert_eq!(Some(mp.get_query(&q1_id)), mp.safe_get_query(&q1_id));
 
     let d = Document::new().with_value("colour", "blue");
 
@@ -107,3 +113,784 @@ fn test_percolator_core() {
     assert_eq!(stats.n_preheaters(), 0);
     assert_eq!(stats.n_queries(), 3);
 }
+
+#[test]
+fn test_terms_iter() {
+    for compression in [false, true] {
+        let mut p = Percolator::builder()
+            .prefix_compression(compression)
+            .build();
+        let q1 = p.add_query("tag".has_value("a") & "colour".has_field());
+        p.add_query("tag".has_value("b"));
+        p.add_query("tag".has_value("b") | "tag".has_value("c"));
+        p.remove_qid(q1);
+
+        let terms = p.terms_iter().collect::<Vec<_>>();
+        assert_eq!(
+            terms,
+            vec![
+                ("tag".to_string(), "b".to_string(), 2),
+                ("tag".to_string(), "c".to_string(), 1),
+            ]
+        );
+    }
+
+    let mut p = Percolator::default();
+    p.add_query("colour".has_field());
+    assert_eq!(
+        p.terms_iter().collect::<Vec<_>>(),
+        vec![("__EXISTS__colour".to_string(), "true".to_string(), 1)]
+    );
+}
+
+#[test]
+fn test_normalize_terms() {
+    let mut p = Percolator::builder().normalize_terms(true).build();
+    let q1 = p.add_query("colour".has_value("Blue") & !"taste".has_value("bitter"));
+    let q2 = p.add_query("name".has_prefix("Jo"));
+    assert_eq!(
+        p.get_query(&q1).to_string(),
+        "NORM(colour):blue AND NOT NORM(taste):bitter"
+    );
+    // Which parses back to the same query.
+    assert_eq!(
+        p.get_query(&q1).to_string().parse::<Query>().unwrap(),
+        *p.get_query(&q1)
+    );
+
+    let perc = |d: Document| p.percolate(&d).collect::<Vec<_>>();
+    assert_eq!(perc([("colour", " BLUE\n")].into()), vec![q1]);
+    assert_eq!(
+        perc([("colour", "blue"), ("taste", "Bitter ")].into()),
+        Vec::<Qid>::new()
+    );
+    // Other queries are as they were.
+    assert_eq!(perc([("name", "John")].into()), vec![q2]);
+    assert_eq!(perc([("name", "john")].into()), Vec::<Qid>::new());
+
+    // Not by default.
+    let mut p = Percolator::default();
+    p.add_query("colour".has_value("Blue"));
+    assert_eq!(p.percolate(&[("colour", " BLUE\n")].into()).count(), 0);
+}
+
+#[test]
+fn test_stats_delta() {
+    let mut p = Percolator::default();
+    let q1 = p.add_query("name".has_prefix("jo"));
+    let earlier = p.stats().clone();
+    assert_eq!(p.stats().delta(&earlier).n_queries_added(), 0);
+
+    p.remove_qid(q1);
+    p.add_query("colour".has_value("blue") | "taste".has_value("sweet"));
+    let delta = p.stats().delta(&earlier);
+    assert_eq!(delta.n_queries_added(), 1);
+    assert_eq!(delta.n_queries_removed(), 1);
+    assert_eq!(delta.n_preheaters_change(), -1);
+    assert_eq!(delta.clauses_per_query().count(), 1);
+    assert_eq!(delta.clauses_per_query().mean(), 1.0);
+    assert!(delta.prefix_lengths().count().is_zero());
+    assert!(delta.to_string().contains("Preheaters change=-1"));
+}
+
+#[test]
+fn test_preheaters() {
+    let mut p = Percolator::builder()
+        .n_clause_matchers(NonZeroUsize::new(2).unwrap())
+        .build();
+    let north_london = CellIndex::from_str("85195da7fffffff").unwrap();
+
+    let q1 = p.add_query("location".h3in(north_london) & "name".has_prefix("bur"));
+    let q2 = p.add_query("location".h3in(north_london));
+    // Negated clauses add no preheaters.
+    let q3 = p.add_query(!"name".has_prefix("bur"));
+    let q4 = p.add_query("colour".has_value("blue"));
+
+    assert_eq!(p.preheaters_for(&q1).len(), 2);
+    assert_eq!(p.preheaters_for(&q2).len(), 1);
+    assert!(p.preheaters_for(&q3).is_empty());
+    assert!(p.preheaters_for(&q4).is_empty());
+    assert!(p.preheaters_for(&1000).is_empty());
+
+    let h3_id = &p.preheaters_for(&q2)[0];
+    assert!(p.preheaters_for(&q1).contains(h3_id));
+    assert_eq!(p.preheaters().len(), 2);
+    assert_eq!(p.preheaters()[h3_id], 2);
+    assert_eq!(p.preheaters().len(), p.stats().n_preheaters());
+
+    // Preheaters go away with the last query that needs them.
+    p.remove_qid(q1);
+    assert!(p.preheaters_for(&q1).is_empty());
+    assert_eq!(p.preheaters().len(), 1);
+    assert_eq!(p.preheaters()[h3_id], 1);
+    assert_eq!(p.stats().n_preheaters(), 1);
+    let in_london = Document::default()
+        .with_value("location", "85195da7fffffff")
+        .with_value("name", "burger");
+    assert_eq!(p.percolate(&in_london).collect::<Vec<_>>(), vec![q2]);
+
+    p.remove_qid(q2);
+    assert!(p.preheaters().is_empty());
+    assert_eq!(p.stats().n_preheaters(), 0);
+    assert!(p.compacted().preheaters().is_empty());
+
+    // Clauses beyond the clause matchers are not indexed,
+    // so they add no preheaters.
+    let q5 = p.add_query(
+        "colour".has_value("blue") & "taste".has_value("sweet") & "location".h3in(north_london),
+    );
+    assert!(p.preheaters_for(&q5).is_empty());
+}
+
+#[test]
+fn test_max_clauses() {
+    let mut p = Percolator::builder().max_clauses(2).build();
+    let q1 = p.add_query("colour".has_value("blue") & "taste".has_value("sweet"));
+    let too_big = "colour".has_value("blue") & "taste".has_value("sweet") & "price".i64_lt(10);
+    assert!(matches!(
+        p.safe_add_query(too_big),
+        Err(PercolatorError::TooManyClauses)
+    ));
+    // Nothing was indexed.
+    assert_eq!(p.stats().n_queries(), 1);
+    let q2 = p.add_query("colour".has_value("red"));
+    assert_eq!(
+        p.percolate(&[("colour", "red")].into()).collect::<Vec<_>>(),
+        vec![q2]
+    );
+    assert_eq!(
+        p.percolate(&[("colour", "blue"), ("taste", "sweet")].into())
+            .collect::<Vec<_>>(),
+        vec![q1]
+    );
+}
+
+#[test]
+fn test_int_overflows() {
+    let huge = "18446744073709551615";
+    let tiny = "-18446744073709551615";
+    let doc = |v: &str| -> Document { [("bytes", v), ("id", v)].into() };
+    let queries = || {
+        [
+            "bytes".i64_gt(1000),
+            "bytes".i64_lt(-1000),
+            "bytes".i64_range(std::ops::Bound::Included(0), std::ops::Bound::Unbounded),
+            "id".has_value(huge),
+        ]
+    };
+
+    for (semantics, n_clauses) in [(IntOverflow::NonMatching, 3), (IntOverflow::Clamp, 1)] {
+        let mut p = Percolator::builder()
+            .int_overflow(semantics)
+            .n_clause_matchers(NonZeroUsize::new(n_clauses).unwrap())
+            .build();
+        let q = queries().map(|q| p.add_query(q));
+        let perc = |d: &Document| {
+            let mut qids = p.percolate(d).collect::<Vec<_>>();
+            qids.sort();
+            qids
+        };
+
+        // Whatever the semantics, other queries see the value as it is.
+        if semantics == IntOverflow::Clamp {
+            assert_eq!(perc(&doc(huge)), vec![q[0], q[2], q[3]]);
+            assert_eq!(perc(&doc(tiny)), vec![q[1]]);
+        } else {
+            assert_eq!(perc(&doc(huge)), vec![q[3]]);
+            assert_eq!(perc(&doc(tiny)), Vec::<Qid>::new());
+        }
+        assert_eq!(perc(&doc("2000")), vec![q[0], q[2]]);
+        assert_eq!(perc(&doc("1e30")), Vec::<Qid>::new());
+        assert_eq!(p.stats().n_int_overflows(), 0);
+    }
+
+    let mut p = Percolator::builder()
+        .int_overflow(IntOverflow::Report)
+        .build();
+    for q in queries() {
+        p.add_query(q);
+    }
+    assert_eq!(p.percolate(&doc(huge)).count(), 1);
+    assert_eq!(p.percolate(&doc(tiny)).count(), 0);
+    assert_eq!(p.percolate(&doc("2000")).count(), 2);
+    // Only the values of compared fields count.
+    assert_eq!(p.stats().n_int_overflows(), 2);
+    assert!(p.stats().to_string().contains("Integer overflows=2"));
+}
+
+#[test]
+fn test_empty_documents() {
+    let queries = || {
+        [
+            Query::from_and(vec![]),
+            !"colour".has_value("blue"),
+            "colour".lacks_field(),
+            "colour".has_value("blue"),
+        ]
+    };
+    for (semantics, expected) in [
+        (EmptyDocuments::Percolate, vec![0, 1, 2]),
+        (EmptyDocuments::NoMatch, vec![]),
+        (EmptyDocuments::MatchAllOnly, vec![0]),
+    ] {
+        let mut p = Percolator::builder().empty_documents(semantics).build();
+        for q in queries() {
+            p.add_query(q);
+        }
+        let mut qids = p.percolate(&Document::new()).collect::<Vec<_>>();
+        qids.sort();
+        assert_eq!(qids, expected);
+        // Other documents are percolated as usual.
+        let mut qids = p
+            .percolate(&[("taste", "sweet")].into())
+            .collect::<Vec<_>>();
+        qids.sort();
+        assert_eq!(qids, vec![0, 1, 2]);
+
+        // Borrowed documents too, even when no query is about their fields.
+        let mut qids = p
+            .percolate_borrowed(&DocumentRef::new())
+            .collect::<Vec<_>>();
+        qids.sort();
+        assert_eq!(qids, expected);
+        let mut qids = p
+            .percolate_borrowed(&[("taste", "sweet")].into())
+            .collect::<Vec<_>>();
+        qids.sort();
+        assert_eq!(qids, vec![0, 1, 2]);
+    }
+}
+
+#[test]
+fn test_percolate_borrowed() {
+    let mut rng = rand::rng();
+    for retain_size in [0, 10] {
+        let mut p = Percolator::builder().retain_size(retain_size).build();
+        let queries = (0..200)
+            .map(|_| Query::random(&mut rng))
+            .collect::<Vec<_>>();
+        for q in &queries {
+            p.add_query(q.clone());
+        }
+        // No query is about the required field.
+        let requiring = p
+            .index_query_uid_requiring("nobody_asks".lacks_field(), 10_000, &["stock"])
+            .unwrap();
+
+        for q in &queries {
+            for d in q
+                .example_documents(&mut rng, 2)
+                .into_iter()
+                .chain(q.example_near_misses(&mut rng, 2))
+            {
+                let d = d.with_value("stock", "12").with_value("unasked", "me");
+                let fvs = d.field_values().collect::<Vec<_>>();
+                let d_ref = fvs
+                    .iter()
+                    .map(|(f, v)| (f.as_ref(), v.as_ref()))
+                    .collect::<DocumentRef>();
+                let mut expected = p.percolate(&d).collect::<Vec<_>>();
+                expected.sort();
+                let mut qids = p.percolate_borrowed(&d_ref).collect::<Vec<_>>();
+                qids.sort();
+                assert_eq!(qids, expected, "{:?}", d);
+                assert!(qids.contains(&requiring));
+            }
+        }
+        let d = DocumentRef::from([("unasked", "me")]);
+        assert!(p.percolate_borrowed(&d).all(|qid| qid != requiring));
+    }
+}
+
+#[test]
+fn test_match_all_none() {
+    let blue = || "colour".has_value("blue");
+    let mut p = Percolator::default();
+    let all = p.add_query(Query::match_all());
+    let none = p.add_query(Query::match_none());
+    let not_none = p.add_query(!Query::match_none());
+    let not_all = p.add_query(!Query::match_all());
+    let blue_or_none = p.add_query(blue() | Query::match_none());
+    let blue_and_all = p.add_query(blue() & Query::match_all());
+    let blue_and_none = p.add_query(blue() & Query::match_none());
+    let blue_or_all = p.add_query(blue() | Query::match_all());
+
+    let perc = |d: Document| {
+        let mut qids = p.percolate(&d).collect::<Vec<_>>();
+        qids.sort();
+        qids
+    };
+    assert_eq!(
+        perc([("colour", "blue")].into()),
+        vec![all, not_none, blue_or_none, blue_and_all, blue_or_all]
+    );
+    assert_eq!(
+        perc([("colour", "red")].into()),
+        vec![all, not_none, blue_or_all]
+    );
+    assert_eq!(perc(Document::new()), vec![all, not_none, blue_or_all]);
+    assert!(
+        ![none, not_all, blue_and_none]
+            .iter()
+            .any(|q| perc([("colour", "blue")].into()).contains(q))
+    );
+}
+
+#[test]
+fn test_schema() {
+    use mokaccino::prelude::parsing::FieldType;
+    use mokaccino::prelude::{Multiplicity, Schema};
+
+    let schema = Schema::default()
+        .field("colour", FieldType::Keyword, Multiplicity::Many)
+        .field("price", FieldType::Integer, Multiplicity::Optional);
+    let options = schema.parser_options();
+    let builder = Percolator::builder().schema(schema);
+    assert!(builder.config().schema().unwrap().has_field("price"));
+    let mut p = builder.build();
+
+    let q = Query::parse_with("colour:blue AND price:10", &options).unwrap();
+    assert_eq!(q, "colour".has_value("blue") & "price".i64_eq(10));
+    let qid = p.safe_add_query(q).unwrap();
+
+    for typo in [
+        "color".has_value("blue"),
+        "colour".has_value("blue") | "prize".i64_lt(3),
+        !"color".has_field(),
+    ] {
+        assert!(matches!(
+            p.safe_add_query(typo),
+            Err(PercolatorError::UnknownField(f)) if f == "color" || f == "prize"
+        ));
+    }
+    // Nothing was indexed.
+    assert_eq!(
+        p.percolate(&[("colour", "blue"), ("price", "10")].into())
+            .collect::<Vec<_>>(),
+        vec![qid]
+    );
+}
+
+#[test]
+fn test_nested() {
+    let mut p = Percolator::default();
+    let cheap_blue = p.add_query(Query::nested(
+        "items",
+        "colour".has_value("blue") & "price".i64_lt(10),
+    ));
+    let flat = p.add_query("items.colour".has_value("blue") & "items.price".i64_lt(10));
+    let no_red = p.add_query(
+        "customer".has_value("bob") & !Query::nested("items", "colour".has_value("red")),
+    );
+    let big_order = p.add_query(
+        "customer".has_value("bob")
+            & Query::nested("items", "quantity".i64_ge(10) | "price".i64_ge(100)),
+    );
+
+    let order = |items: &[(&str, &str, &str)]| {
+        items.iter().fold(
+            Document::default().with_value("customer", "bob"),
+            |d, (colour, price, quantity)| {
+                d.with_nested(
+                    "items",
+                    [
+                        ("colour", *colour),
+                        ("price", *price),
+                        ("quantity", *quantity),
+                    ]
+                    .into(),
+                )
+            },
+        )
+    };
+    let perc = |d: Document| {
+        let mut qids = p.percolate(&d).collect::<Vec<_>>();
+        qids.sort();
+        qids
+    };
+
+    assert_eq!(
+        perc(order(&[("blue", "5", "1")])),
+        vec![cheap_blue, flat, no_red]
+    );
+    assert_eq!(
+        perc(order(&[("blue", "15", "1"), ("red", "5", "1")])),
+        vec![flat]
+    );
+    assert_eq!(
+        perc(order(&[("blue", "15", "10"), ("green", "5", "1")])),
+        vec![flat, no_red, big_order]
+    );
+    assert_eq!(perc(order(&[])), vec![no_red]);
+    assert_eq!(
+        p.get_query(&cheap_blue).to_string(),
+        "NESTED(items)(colour:blue AND price<10)"
+    );
+}
+
+#[test]
+fn test_match_cache() {
+    let builder = Percolator::builder().match_cache_size(2).retain_size(10);
+    assert_eq!(builder.config().match_cache_size(), 2);
+    let mut p = builder.build();
+    let blue = p.add_query("colour".has_value("blue"));
+    let perc = |p: &Percolator, d: Document| {
+        let mut qids = p.percolate(&d).collect::<Vec<_>>();
+        qids.sort();
+        qids
+    };
+    let d = || Document::from([("colour", "blue"), ("taste", "sweet")]);
+
+    assert_eq!(perc(&p, d()), vec![blue]);
+    assert_eq!(perc(&p, d()), vec![blue]);
+    // Cached percolations are retained too.
+    assert_eq!(p.search(&"taste".has_value("sweet")).len(), 2);
+
+    // New and removed queries are seen.
+    let sweet = p.add_query("taste".has_value("sweet"));
+    assert_eq!(perc(&p, d()), vec![blue, sweet]);
+    p.remove_qid(blue);
+    assert_eq!(perc(&p, d()), vec![sweet]);
+    assert_eq!(perc(&p, Document::new()), Vec::<Qid>::new());
+}
+
+#[test]
+fn test_bytes() {
+    let ids: Vec<Vec<u8>> = vec![vec![0xfb, 0xff, 0xfe], vec![0; 16], (0..=255).collect()];
+    let mut p = Percolator::default();
+    let qids = ids
+        .iter()
+        .map(|id| p.add_query("id".has_bytes(id)))
+        .collect::<Vec<_>>();
+    for (id, qid) in ids.iter().zip(qids) {
+        let d = Document::default().with_bytes("id", id);
+        assert_eq!(p.percolate(&d).collect::<Vec<_>>(), vec![qid]);
+        assert_eq!(d.bytes_values("id"), vec![id.clone()]);
+        // Query strings give the same queries back.
+        let q = p.get_query(&qid);
+        assert_eq!(&q.to_string().parse::<Query>().unwrap(), q);
+    }
+    assert_eq!(
+        p.percolate(&Document::default().with_bytes("id", &[0xfb, 0xff]))
+            .count(),
+        0
+    );
+    assert!(
+        Document::from([("id", "not bytes!")])
+            .bytes_values("id")
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_big_ints() {
+    let mut p = Percolator::default();
+    let above = p.add_query("id".u64_gt(u64::MAX / 2));
+    let below = p.add_query("id".i128_le(-(1 << 100)));
+    let parsed = p.add_query(format!("id>={}", u64::MAX).parse::<Query>().unwrap());
+    let small = p.add_query("id".i64_gt(10));
+    let perc = |v: String| {
+        let mut qids = p
+            .percolate(&[("id", v.as_str())].into())
+            .collect::<Vec<_>>();
+        qids.sort();
+        qids
+    };
+
+    assert_eq!(perc(u64::MAX.to_string()), vec![above, parsed]);
+    assert_eq!(perc((-(1i128 << 101)).to_string()), vec![below]);
+    assert_eq!(perc("12".to_string()), vec![small]);
+    assert_eq!(perc("1e30".to_string()), Vec::<Qid>::new());
+}
+
+#[test]
+fn test_uid_store() {
+    let mut p = PercolatorUid::<String>::default();
+    let uid = |s: &str| s.to_string();
+    p.index_query_uid("colour".has_value("blue"), uid("blue"))
+        .unwrap();
+    p.set_uid_store(Box::new(MemoryUidStore::default()));
+    let mappings = |p: &PercolatorUid<String>| {
+        let mut m = p.uid_store().unwrap().mappings();
+        m.sort();
+        m
+    };
+    assert_eq!(mappings(&p), vec![(0, uid("blue"))]);
+
+    // Overwriting a query gives it a new Qid.
+    p.index_query_uid("colour".has_value("navy"), uid("blue"))
+        .unwrap();
+    p.index_query_uid("colour".has_value("red"), uid("red"))
+        .unwrap();
+    assert_eq!(mappings(&p), vec![(1, uid("blue")), (2, uid("red"))]);
+
+    p.remove_uid("red");
+    assert_eq!(mappings(&p), vec![(1, uid("blue"))]);
+
+    // Percolators without a store are unaffected.
+    let store = p.take_uid_store().unwrap();
+    p.remove_uid("blue");
+    assert!(p.uid_store().is_none());
+    assert_eq!(store.mappings(), vec![(1, uid("blue"))]);
+}
+
+#[test]
+fn test_packs() {
+    let mut p = Percolator::builder().max_clauses(2).build();
+    let other = p.add_query("colour".has_value("green"));
+    let v1 = p
+        .load_pack(
+            "rules",
+            vec!["colour".has_value("blue"), "taste".has_prefix("swe")],
+        )
+        .unwrap();
+    assert_eq!(p.pack("rules"), Some(v1.as_slice()));
+    assert!(matches!(
+        p.load_pack("rules", vec!["colour".has_value("red")]),
+        Err(PercolatorError::DuplicatePack(name)) if name == "rules"
+    ));
+
+    let perc = |p: &Percolator, field: &str, value: &str| {
+        let mut qids = p.percolate(&[(field, value)].into()).collect::<Vec<_>>();
+        qids.sort();
+        qids
+    };
+    assert_eq!(perc(&p, "colour", "blue"), vec![v1[0]]);
+
+    // A failed swap keeps the old version.
+    let too_big = "colour".has_value("blue") & "taste".has_value("sweet") & "price".i64_lt(10);
+    assert!(matches!(
+        p.swap_pack("rules", vec!["colour".has_value("red"), too_big]),
+        Err(PercolatorError::TooManyClauses)
+    ));
+    assert_eq!(p.pack("rules"), Some(v1.as_slice()));
+    assert_eq!(perc(&p, "colour", "red"), Vec::<Qid>::new());
+    assert_eq!(perc(&p, "taste", "sweeter"), vec![v1[1]]);
+
+    let v2 = p
+        .swap_pack("rules", vec!["colour".has_value("red")])
+        .unwrap();
+    assert_eq!(perc(&p, "colour", "red"), v2);
+    assert_eq!(perc(&p, "colour", "blue"), Vec::<Qid>::new());
+    assert_eq!(perc(&p, "taste", "sweeter"), Vec::<Qid>::new());
+
+    // Packs survive compaction, and a swap loads new packs.
+    let mut p = p.compacted();
+    assert_eq!(p.pack("rules"), Some(v2.as_slice()));
+    let more = p
+        .swap_pack("more", vec!["colour".has_value("red")])
+        .unwrap();
+    assert_eq!(p.pack_names().collect::<Vec<_>>(), vec!["more", "rules"]);
+    let mut reds = [v2.as_slice(), more.as_slice()].concat();
+    reds.sort();
+    assert_eq!(perc(&p, "colour", "red"), reds);
+
+    assert!(p.remove_pack("rules"));
+    assert!(!p.remove_pack("rules"));
+    assert_eq!(p.pack("rules"), None);
+    assert_eq!(perc(&p, "colour", "red"), more);
+    assert_eq!(perc(&p, "colour", "green"), vec![other]);
+}
+
+#[test]
+fn test_preheater_warnings() {
+    let names = (0..20)
+        .map(|i| format!("a{:02}john", i))
+        .collect::<Vec<_>>();
+    let percolate_names = |p: &Percolator| {
+        for name in names.iter().cycle().take(200) {
+            let _ = p.percolate(&[("name", name.as_str())].into()).count();
+        }
+    };
+
+    // Clipped to "a", all the queries are candidates.
+    let mut p = Percolator::builder().prefix_sizes(vec![1]).build();
+    let qids = names
+        .iter()
+        .map(|name| p.add_query("name".has_prefix(&name[..6])))
+        .collect::<Vec<_>>();
+    percolate_names(&p);
+    let warnings = p.stats().preheater_warnings();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].id(), "__PREFIX1__name");
+    assert_eq!(warnings[0].n_samples(), 200);
+    assert_eq!(warnings[0].mean_fanout(), 1.0);
+    assert!(p.stats().to_string().contains("__PREFIX1__name"));
+
+    // Gone with the preheater.
+    for qid in qids {
+        p.remove_qid(qid);
+    }
+    assert!(p.stats().preheater_warnings().is_empty());
+
+    // Clipped to "a00", "a01".., only one query is a candidate.
+    let mut p = Percolator::builder().prefix_sizes(vec![1, 3]).build();
+    for name in &names {
+        p.add_query("name".has_prefix(&name[..6]));
+    }
+    assert_eq!(
+        p.preheaters().keys().collect::<Vec<_>>(),
+        vec!["__PREFIX3__name"]
+    );
+    percolate_names(&p);
+    assert!(p.stats().preheater_warnings().is_empty());
+}
+
+#[test]
+fn test_dedup_values() {
+    let d: Document = [
+        ("colour", "blue"),
+        ("colour", "blue"),
+        ("taste", "sweet"),
+        ("colour", "blue"),
+    ]
+    .into();
+    let mut deduped = d.clone();
+    deduped.dedup();
+    assert_eq!(deduped.fv_count(), 2);
+
+    for dedup in [false, true] {
+        let mut p = Percolator::builder().dedup_values(dedup).build();
+        let q1 = p.add_query("colour".has_prefix("bl"));
+        let q2 = p.add_query("colour".has_value("blue") & !"taste".has_value("bitter"));
+        let _q3 = p.add_query("colour".has_value("red"));
+        assert_eq!(p.percolate(&d).collect::<Vec<_>>(), vec![q1, q2]);
+        assert_eq!(p.percolate(&deduped).collect::<Vec<_>>(), vec![q1, q2]);
+    }
+}
+
+#[test]
+fn test_case_insensitive() {
+    let mut p = Percolator::builder().prefix_sizes(vec![2, 4]).build();
+    let q1 = p.add_query("colour".has_value_ci("Blue"));
+    let q2 = p.add_query("colour".has_value("blue"));
+    let q3 = p.add_query("name".has_prefix_ci("STRASS"));
+    let q4 = p.add_query("name".has_prefix("Str"));
+    let q5 = p.add_query(!"colour".has_value_ci("RED"));
+
+    let percolate = |d: &Document| p.percolate(d).collect::<Vec<_>>();
+    assert_eq!(percolate(&[("colour", "BLUE")].into()), vec![q1, q5]);
+    assert_eq!(percolate(&[("colour", "blue")].into()), vec![q1, q2, q5]);
+    assert_eq!(percolate(&[("colour", "Red")].into()), Vec::<Qid>::new());
+    assert_eq!(percolate(&[("name", "Straße")].into()), vec![q3, q4, q5]);
+    assert_eq!(percolate(&[("name", "strasbourg")].into()), vec![q5]);
+}
+
+#[test]
+fn test_words() {
+    let mut p = Percolator::default();
+    let q1 = p.add_query("title".has_words("Brown fox"));
+    let q2 = p.add_query("title".has_any_word("cat DOG"));
+    let q3 = p.add_query(Query::words("title", "fox, jumps!", Analyzer::Alphanumeric));
+    let q4 = p.add_query("title".has_words("  ") & "lang".has_value("en"));
+    // One per analyzer.
+    assert_eq!(p.stats().n_preheaters(), 3);
+
+    let percolate = |d: &Document| p.percolate(d).collect::<Vec<_>>();
+    assert_eq!(
+        percolate(&[("title", "The quick brown fox jumps over the lazy dog")].into()),
+        vec![q1, q2, q3]
+    );
+    assert_eq!(
+        percolate(&[("title", "The FOX"), ("title", "is brown"), ("lang", "en")].into()),
+        vec![q1, q4]
+    );
+    assert_eq!(percolate(&[("title", "Jumps, the fox")].into()), vec![q3]);
+    assert_eq!(
+        percolate(&[("title", "dogs and cats")].into()),
+        Vec::<Qid>::new()
+    );
+    assert_eq!(
+        percolate(&[("body", "brown fox")].into()),
+        Vec::<Qid>::new()
+    );
+}
+
+#[test]
+fn test_substrings() {
+    let mut p = Percolator::default();
+    let q1 = p.add_query("title".has_substring("cat"));
+    let q2 = p.add_query("title".has_substring("category") & "lang".has_value("en"));
+    let q3 = p.add_query("title".has_substring("at"));
+    let q4 = p.add_query(!"title".has_substring("dog"));
+    // One per n-gram length.
+    assert_eq!(p.stats().n_preheaters(), 2);
+
+    let percolate = |d: &Document| p.percolate(d).collect::<Vec<_>>();
+    assert_eq!(
+        percolate(&[("title", "concatenate")].into()),
+        vec![q1, q3, q4]
+    );
+    assert_eq!(
+        percolate(&[("title", "subcategory"), ("lang", "en")].into()),
+        vec![q1, q2, q3, q4]
+    );
+    assert_eq!(
+        percolate(&[("title", "cat and dog"), ("lang", "en")].into()),
+        vec![q1, q3]
+    );
+    assert_eq!(percolate(&[("title", "ca")].into()), vec![q4]);
+    assert_eq!(percolate(&[("name", "cat")].into()), vec![q4]);
+
+    let mut p = Percolator::builder()
+        .ngram_size(NonZeroUsize::new(5).unwrap())
+        .build();
+    let q1 = p.add_query("title".has_substring("category"));
+    let q2 = p.add_query("title".has_substring("cat"));
+    assert_eq!(p.stats().n_preheaters(), 2);
+    assert_eq!(
+        p.percolate(&[("title", "subcategory")].into())
+            .collect::<Vec<_>>(),
+        vec![q1, q2]
+    );
+    assert_eq!(
+        p.percolate(&[("title", "categor")].into())
+            .collect::<Vec<_>>(),
+        vec![q2]
+    );
+}
+
+#[test]
+fn test_term_sets() {
+    let mut p = Percolator::default();
+    let colours = "colour".has_any_value(["red", "blue", "green"]);
+    assert_eq!(colours.to_string(), "colour IN {blue, green, red}");
+    let q1 = p.add_query(colours.clone());
+    let q2 = p.add_query(colours & "taste".has_value("sweet"));
+    let q3 = p.add_query(!"colour".has_any_value(["red", "blue"]));
+
+    let percolate = |d: &Document| p.percolate(d).collect::<Vec<_>>();
+    assert_eq!(percolate(&[("colour", "blue")].into()), vec![q1]);
+    assert_eq!(
+        percolate(&[("colour", "green"), ("taste", "sweet")].into()),
+        vec![q1, q2, q3]
+    );
+    assert_eq!(
+        percolate(&[("colour", "red"), ("colour", "blue")].into()),
+        vec![q1]
+    );
+    assert_eq!(percolate(&[("colour", "yellow")].into()), vec![q3]);
+    assert_eq!(p.stats().n_preheaters(), 0);
+}
+
+#[test]
+fn test_suffixes() {
+    let mut p = Percolator::builder().prefix_sizes(vec![2, 4]).build();
+    let q1 = p.add_query("file".has_suffix(".jpg"));
+    let q2 = p.add_query("file".has_suffix("_thumb.jpg") & "user".has_value("bob"));
+    let q3 = p.add_query("file".has_suffix(""));
+    assert_eq!(p.stats().n_preheaters(), 2);
+
+    let percolate = |d: &Document| p.percolate(d).collect::<Vec<_>>();
+    assert_eq!(percolate(&[("file", "cat.jpg")].into()), vec![q1, q3]);
+    assert_eq!(
+        percolate(&[("file", "cat_thumb.jpg"), ("user", "bob")].into()),
+        vec![q1, q2, q3]
+    );
+    assert_eq!(
+        percolate(&[("file", "cat.jpg"), ("user", "bob")].into()),
+        vec![q1, q3]
+    );
+    assert_eq!(percolate(&[("file", "cat.jpeg")].into()), vec![q3]);
+    assert!(percolate(&[("name", "cat.jpg")].into()).is_empty());
+}
@@ -64,6 +64,41 @@ where
     );
 }
 
+#[test]
+fn test_shared_queries() {
+    let rules: Vec<SharedQuery> = vec![
+        "colour".has_value("blue").into(),
+        "name".has_prefix("mok").into(),
+    ];
+
+    let mut p = PercolatorUid::<String>::default();
+    for (i, q) in rules.iter().enumerate() {
+        p.index_shared_query_uid(q.clone(), format!("rule{}", i))
+            .unwrap();
+    }
+    let mut p2 = Percolator::default();
+    let qid = p2.add_shared_query(rules[1].clone());
+    assert_eq!(SharedQuery::strong_count(&rules[1]), 3);
+
+    assert_eq!(
+        p.percolate_ref(&[("name", "mokaccino")].into())
+            .collect::<Vec<_>>(),
+        vec!["rule1"]
+    );
+    assert_eq!(
+        p2.percolate(&[("name", "mokaccino")].into())
+            .collect::<Vec<_>>(),
+        vec![qid]
+    );
+
+    // Still the same queries once compacted.
+    p.remove_uid("rule0");
+    p = p.compacted();
+    assert_eq!(p.get_query("rule1"), rules[1].as_ref());
+    assert_eq!(SharedQuery::strong_count(&rules[0]), 1);
+    assert_eq!(SharedQuery::strong_count(&rules[1]), 3);
+}
+
 #[test]
 fn test_percolator() {
     for optimized in [false, true] {
@@ -77,6 +112,1300 @@ fn test_percolator() {
     }
 }
 
+#[test]
+fn test_float_comparisons() {
+    let mut p = Percolator::default();
+    let q = [
+        p.add_query("price".f64_lt(1.5)),
+        p.add_query("price".f64_ge(1.5)),
+        p.add_query("price<=-0.5".parse().unwrap()),
+        p.add_query("score".i64_lt(5)),
+        p.add_query("score".i64_gt(5)),
+    ];
+
+    assert_eq!(
+        p.percolate(&[("price", "1.49")].into()).collect::<Vec<_>>(),
+        vec![q[0]]
+    );
+    assert_eq!(
+        p.percolate(&[("price", "1.5")].into()).collect::<Vec<_>>(),
+        vec![q[1]]
+    );
+    assert_eq!(
+        p.percolate(&[("price", "-3")].into()).collect::<Vec<_>>(),
+        vec![q[0], q[2]]
+    );
+    assert_eq!(
+        p.percolate(&[("price", "not a price")].into())
+            .collect::<Vec<_>>(),
+        Vec::<Qid>::new()
+    );
+
+    // Same comparison point, opposite directions.
+    assert_eq!(
+        p.percolate(&[("score", "7")].into()).collect::<Vec<_>>(),
+        vec![q[4]]
+    );
+    assert_eq!(
+        p.percolate(&[("score", "3")].into()).collect::<Vec<_>>(),
+        vec![q[3]]
+    );
+}
+
+#[test]
+fn test_ranges() {
+    let mut p = Percolator::default();
+    let q = [
+        p.add_query("price BETWEEN 10 AND 100".parse().unwrap()),
+        p.add_query("price".f64_between(-0.5, 9.5)),
+        p.add_query("price BETWEEN 50 AND 60 AND colour:blue".parse().unwrap()),
+        p.add_query(!"price".i64_between(0, 1000)),
+    ];
+
+    let percolate = |price: &str| {
+        p.percolate(&[("price", price), ("colour", "blue")].into())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(percolate("10"), vec![q[0]]);
+    assert_eq!(percolate("100"), vec![q[0]]);
+    assert_eq!(percolate("55"), vec![q[0], q[2]]);
+    assert_eq!(percolate("9"), vec![q[1]]);
+    // Not an integer, so not in any integer range.
+    assert_eq!(percolate("9.5"), vec![q[1], q[3]]);
+    assert_eq!(percolate("-1"), vec![q[3]]);
+    // Within the indexed buckets, but out of the ranges.
+    assert_eq!(percolate("101"), Vec::<Qid>::new());
+    assert_eq!(percolate("1001"), vec![q[3]]);
+}
+
+#[test]
+fn test_required_fields() {
+    let mut p = PercolatorUid::<u64>::default();
+    p.index_query_uid_requiring(!"colour".has_value("blue"), 1, &["colour"])
+        .unwrap();
+    p.index_query_uid_requiring("taste".has_value("sweet"), 2, &["colour", "size"])
+        .unwrap();
+    p.index_query_uid("taste".has_value("sweet"), 3).unwrap();
+
+    let percolate = |p: &PercolatorUid<u64>, d: &Document| {
+        let mut uids = p.percolate(d).collect::<Vec<_>>();
+        uids.sort();
+        uids
+    };
+    let sparse: Document = [("taste", "sweet")].into();
+    let full: Document = [("taste", "sweet"), ("colour", "red"), ("size", "L")].into();
+    assert_eq!(percolate(&p, &sparse), vec![3]);
+    assert_eq!(percolate(&p, &full), vec![1, 2, 3]);
+    assert_eq!(p.required_fields(&2), vec!["colour", "size"]);
+    assert!(p.required_fields(&3).is_empty());
+    assert!(p.required_fields(&4).is_empty());
+
+    // Kept by compaction, and forgotten with the query.
+    p.remove_uid(&3);
+    let mut p = p.compacted();
+    assert_eq!(percolate(&p, &sparse), Vec::<u64>::new());
+    assert_eq!(percolate(&p, &full), vec![1, 2]);
+    p.index_query_uid("taste".has_value("sweet"), 2).unwrap();
+    assert!(p.required_fields(&2).is_empty());
+    assert_eq!(percolate(&p, &sparse), vec![2]);
+
+    // Same without the index.
+    let mut linear = PercolatorUid::<u64>::builder().linear_threshold(10).build();
+    linear
+        .index_query_uid_requiring(!"colour".has_value("blue"), 1, &["colour"])
+        .unwrap();
+    assert_eq!(percolate(&linear, &sparse), Vec::<u64>::new());
+    assert_eq!(percolate(&linear, &full), vec![1]);
+
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_string(&p).unwrap();
+        let p2: PercolatorUid<u64> = serde_json::from_str(&json).unwrap();
+        assert_eq!(p2.required_fields(&1), vec!["colour"]);
+        assert_eq!(
+            percolate(&p2, &[("taste", "bitter")].into()),
+            Vec::<u64>::new()
+        );
+    }
+}
+
+#[test]
+fn test_search() {
+    let mut p = Percolator::builder().retain_size(2).build();
+    p.add_query("colour".has_value("blue"));
+    let docs: Vec<Document> = vec![
+        [("colour", "blue"), ("price", "10")].into(),
+        [("colour", "red"), ("price", "20")].into(),
+        [("colour", "blue"), ("price", "30")].into(),
+    ];
+    for d in &docs {
+        let _ = p.percolate(d);
+    }
+
+    // The first document is forgotten.
+    let search = |p: &Percolator, q: &str| p.search(&q.parse().unwrap());
+    assert_eq!(search(&p, "colour:blue"), vec![docs[2].clone()]);
+    assert_eq!(
+        search(&p, "price BETWEEN 15 AND 35"),
+        vec![docs[1].clone(), docs[2].clone()]
+    );
+    assert_eq!(search(&p, "NOT colour:red"), vec![docs[2].clone()]);
+
+    // Compaction keeps the documents.
+    let p = p.compacted();
+    assert_eq!(
+        search(&p, "colour:*"),
+        vec![docs[1].clone(), docs[2].clone()]
+    );
+
+    // Nothing is kept by default.
+    let p = Percolator::default();
+    let _ = p.percolate(&docs[0]);
+    assert!(search(&p, "colour:blue").is_empty());
+}
+
+#[test]
+fn test_exists() {
+    let mut p = Percolator::default();
+    let q = [
+        p.add_query("colour".has_field()),
+        p.add_query("colour:* AND taste:sweet".parse().unwrap()),
+        p.add_query("colour:* OR taste:*".parse().unwrap()),
+    ];
+
+    assert_eq!(
+        p.percolate(&[("colour", "blue")].into())
+            .sorted()
+            .collect::<Vec<_>>(),
+        vec![q[0], q[2]]
+    );
+    assert_eq!(
+        p.percolate(&[("colour", ""), ("taste", "sweet")].into())
+            .sorted()
+            .collect::<Vec<_>>(),
+        vec![q[0], q[1], q[2]]
+    );
+    assert_eq!(
+        p.percolate(&[("taste", "bitter")].into())
+            .collect::<Vec<_>>(),
+        vec![q[2]]
+    );
+    assert_eq!(
+        p.percolate(&[("shape", "round")].into())
+            .collect::<Vec<_>>(),
+        Vec::<Qid>::new()
+    );
+}
+
+#[test]
+fn test_missing() {
+    let mut p = Percolator::default();
+    let q = [
+        p.add_query("colour".lacks_field()),
+        p.add_query("NOT colour:* AND taste:sweet".parse().unwrap()),
+        p.add_query("NOT (colour:* AND taste:*)".parse().unwrap()),
+        p.add_query(!"colour".lacks_field()),
+    ];
+
+    assert_eq!(
+        p.percolate(&[("colour", "blue")].into())
+            .sorted()
+            .collect::<Vec<_>>(),
+        vec![q[2], q[3]]
+    );
+    assert_eq!(
+        p.percolate(&[("taste", "sweet")].into())
+            .sorted()
+            .collect::<Vec<_>>(),
+        vec![q[0], q[1], q[2]]
+    );
+    assert_eq!(
+        p.percolate(&[("colour", "blue"), ("taste", "sweet")].into())
+            .collect::<Vec<_>>(),
+        vec![q[3]]
+    );
+}
+
+#[test]
+fn test_regexes() {
+    let mut p = Percolator::default();
+    let q = [
+        p.add_query("name".has_regex(&regex::Regex::new("foo.*bar").unwrap())),
+        p.add_query("name:/fo+/".parse().unwrap()),
+        p.add_query("name:/.*bar/ AND colour:blue".parse().unwrap()),
+        p.add_query("name:/fo+/ OR name:foobaz".parse().unwrap()),
+    ];
+    let perc = |name: &str| {
+        p.percolate(&[("name", name), ("colour", "blue")].into())
+            .sorted()
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(perc("foobar"), vec![q[0], q[2]]);
+    assert_eq!(perc("fooo"), vec![q[1], q[3]]);
+    assert_eq!(perc("foobaz"), vec![q[3]]);
+    assert_eq!(perc("bar"), vec![q[2]]);
+    assert_eq!(perc("f"), Vec::<Qid>::new());
+}
+
+#[test]
+fn test_polygons() {
+    let ll = |lat, lng| LatLng::new(lat, lng).unwrap();
+    // Paris, around Les Halles, with Les Halles as a hole.
+    let donut = Polygon::new(vec![
+        ll(48.855, 2.335),
+        ll(48.855, 2.355),
+        ll(48.868, 2.355),
+        ll(48.868, 2.335),
+    ])
+    .with_hole(vec![
+        ll(48.860, 2.342),
+        ll(48.860, 2.348),
+        ll(48.864, 2.348),
+        ll(48.864, 2.342),
+    ]);
+    // Somewhere in Gdansk
+    let gdansk = Polygon::new(vec![ll(54.34, 18.64), ll(54.34, 18.67), ll(54.36, 18.66)]);
+
+    let mut p = Percolator::default();
+    let q = [
+        p.add_query("position".latlng_in_polygon(&donut)),
+        p.add_query("position".latlng_in_multipolygon(&[donut.clone(), gdansk.clone()])),
+        p.add_query("position".latlng_in_polygon(&gdansk) & "colour".has_value("blue")),
+    ];
+
+    let perc = |lat_lng: &str| {
+        p.percolate(&[("position", lat_lng), ("colour", "blue")].into())
+            .sorted()
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(perc("48.857,2.337"), vec![q[0], q[1]]);
+    // In the hole
+    assert_eq!(perc("48.862,2.345"), Vec::<Qid>::new());
+    assert_eq!(perc("54.345,18.655"), vec![q[1], q[2]]);
+    assert_eq!(perc("54.359,18.641"), Vec::<Qid>::new());
+    assert_eq!(perc("48.85,2.30"), Vec::<Qid>::new());
+    assert_eq!(perc("not a position"), Vec::<Qid>::new());
+}
+
+#[test]
+fn test_within_any_disk() {
+    let ll = |lat, lng| LatLng::new(lat, lng).unwrap();
+    let shops = [
+        (ll(48.864716, 2.349014), Meters(500)),
+        (ll(48.85, 2.29), Meters(300)),
+        (ll(54.35499723397377, 18.662987684795226), Meters(1000)),
+    ];
+    let mut p = Percolator::default();
+    let any = p.add_query("position".latlng_within_any(&shops));
+    let ors = p.add_query(Query::from_or(
+        shops
+            .iter()
+            .map(|(center, radius)| "position".latlng_within(*center, *radius))
+            .collect(),
+    ));
+
+    let perc = |lat_lng: &str| {
+        p.percolate(&[("position", lat_lng)].into())
+            .sorted()
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(perc("48.864716,2.349014"), vec![any, ors]);
+    assert_eq!(perc("48.8501,2.2901"), vec![any, ors]);
+    assert_eq!(perc("54.355,18.663"), vec![any, ors]);
+    assert_eq!(perc("48.859430,2.354946"), Vec::<Qid>::new());
+    assert_eq!(perc("48.88,2.37"), Vec::<Qid>::new());
+}
+
+#[test]
+fn test_rings() {
+    let center = LatLng::new(48.864716, 2.349014).unwrap();
+    let mut p = Percolator::default();
+    let q = [
+        p.add_query("position".latlng_between(center, Meters(1000), Meters(3000))),
+        p.add_query("position".latlng_between(center, Meters(0), Meters(1000))),
+    ];
+
+    let perc = |lat_lng: &str| {
+        p.percolate(&[("position", lat_lng)].into())
+            .sorted()
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(perc("48.864716,2.349014"), Vec::<Qid>::new());
+    assert_eq!(perc("48.859430,2.354946"), vec![q[1]]);
+    assert_eq!(perc("48.88,2.37"), vec![q[0]]);
+    assert_eq!(perc("48.85,2.29"), Vec::<Qid>::new());
+}
+
+#[test]
+#[should_panic]
+fn test_ring_inside_out() {
+    let center = LatLng::new(48.864716, 2.349014).unwrap();
+    "position".latlng_between(center, Meters(3000), Meters(1000));
+}
+
+#[test]
+fn test_bboxes() {
+    let ll = |lat, lng| LatLng::new(lat, lng).unwrap();
+    let mut p = Percolator::default();
+    let q = [
+        // Central Paris
+        p.add_query("position".latlng_in_bbox(ll(48.85, 2.33), ll(48.87, 2.36))),
+        // Around Fiji, across the antimeridian.
+        p.add_query("position".latlng_in_bbox(ll(-20.0, 175.0), ll(-15.0, -178.0))),
+    ];
+
+    let perc = |lat_lng: &str| {
+        p.percolate(&[("position", lat_lng)].into())
+            .sorted()
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(perc("48.86,2.34"), vec![q[0]]);
+    assert_eq!(perc("48.86,2.37"), Vec::<Qid>::new());
+    assert_eq!(perc("48.88,2.34"), Vec::<Qid>::new());
+    assert_eq!(perc("-17.5,178.4"), vec![q[1]]);
+    assert_eq!(perc("-16.2,-179.9"), vec![q[1]]);
+    assert_eq!(perc("-17.5,170.0"), Vec::<Qid>::new());
+    assert_eq!(perc("-17.5,-170.0"), Vec::<Qid>::new());
+    assert_eq!(perc("-14.0,178.4"), Vec::<Qid>::new());
+}
+
+#[test]
+fn test_wide_bboxes() {
+    let ll = |lat, lng| LatLng::new(lat, lng).unwrap();
+    let queries = [
+        "position".latlng_in_bbox(ll(-60.0, -170.0), ll(60.0, 170.0)),
+        "position".latlng_in_bbox(ll(-80.0, -179.0), ll(80.0, 179.0)),
+        // Everything but a band around the antimeridian.
+        "position".latlng_in_bbox(ll(70.0, -175.0), ll(85.0, 175.0)),
+    ];
+    let mut p = Percolator::default();
+    let qids = queries.iter().map(|q| p.add_query(q.clone())).collect_vec();
+
+    // The percolator finds the queries matching any point.
+    for lat in (-88..=88).step_by(4) {
+        for lng in (-178..=178).step_by(4) {
+            let d: Document = [("position", format!("{},{}", lat, lng))].into();
+            let expected = qids
+                .iter()
+                .zip(&queries)
+                .filter(|(_, q)| q.matches(&d))
+                .map(|(qid, _)| *qid)
+                .collect_vec();
+            assert_eq!(p.percolate(&d).sorted().collect_vec(), expected, "{:?}", d);
+        }
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_bbox_upside_down() {
+    let ll = |lat, lng| LatLng::new(lat, lng).unwrap();
+    "position".latlng_in_bbox(ll(1.0, 0.0), ll(0.0, 1.0));
+}
+
+#[test]
+fn test_ip_networks() {
+    let mut p = Percolator::default();
+    let q = [
+        p.add_query("client_ip".ip_in("10.0.0.0/8".parse().unwrap())),
+        p.add_query("client_ip".ip_in("10.1.128.0/20".parse().unwrap())),
+        p.add_query("client_ip IPIN \"2001:db8::/32\"".parse().unwrap()),
+        p.add_query("client_ip IPIN 192.168.1.1 OR colour:blue".parse().unwrap()),
+    ];
+
+    let perc = |ip: &str| {
+        p.percolate(&[("client_ip", ip)].into())
+            .sorted()
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(perc("10.2.3.4"), vec![q[0]]);
+    assert_eq!(perc("10.1.130.1"), vec![q[0], q[1]]);
+    // Same /16, but outside the /20.
+    assert_eq!(perc("10.1.200.1"), vec![q[0]]);
+    assert_eq!(perc("2001:db8:ffff::1"), vec![q[2]]);
+    assert_eq!(perc("2001:db9::1"), Vec::<Qid>::new());
+    assert_eq!(perc("192.168.1.1"), vec![q[3]]);
+    assert_eq!(perc("192.168.1.2"), Vec::<Qid>::new());
+    assert_eq!(perc("not an ip"), Vec::<Qid>::new());
+}
+
+#[test]
+fn test_semantic_versions() {
+    let mut p = Percolator::default();
+    let q = [
+        p.add_query(Query::semver_ge("app_version", "2.3.0").unwrap()),
+        p.add_query(Query::semver_ge("app_version", "9.0.0").unwrap()),
+        p.add_query(Query::semver_ge("app_version", "2.10.0").unwrap()),
+    ];
+    // One preheater per major version.
+    assert_eq!(p.preheaters().len(), 2);
+
+    let perc = |v: &str| {
+        p.percolate(&[("app_version", v)].into())
+            .sorted()
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(perc("1.0.0"), Vec::<Qid>::new());
+    assert_eq!(perc("2.9.0"), vec![q[0]]);
+    assert_eq!(perc("2.10.0"), vec![q[0], q[2]]);
+    assert_eq!(perc("10.0.0"), vec![q[0], q[1], q[2]]);
+    assert_eq!(perc("9.0.0-alpha"), vec![q[0], q[2]]);
+    assert_eq!(perc("latest"), Vec::<Qid>::new());
+}
+
+#[test]
+fn test_string_ranges() {
+    let mut p = Percolator::default();
+    let q = [
+        p.add_query("name".str_between("a", "f")),
+        p.add_query("name".str_between("g", "m")),
+        p.add_query("name".str_between("a", "f") & "team".has_value("blue")),
+    ];
+    // One preheater per range.
+    assert_eq!(p.preheaters().len(), 2);
+
+    let perc = |d: Document| p.percolate(&d).sorted().collect::<Vec<_>>();
+    assert_eq!(perc([("name", "carol")].into()), vec![q[0]]);
+    assert_eq!(
+        perc([("name", "carol"), ("team", "blue")].into()),
+        vec![q[0], q[2]]
+    );
+    assert_eq!(perc([("name", "f")].into()), vec![q[0]]);
+    // Between "f" and "g", so in neither.
+    assert_eq!(perc([("name", "frank")].into()), Vec::<Qid>::new());
+    assert_eq!(perc([("name", "george")].into()), vec![q[1]]);
+    assert_eq!(perc([("name", "zoe")].into()), Vec::<Qid>::new());
+    assert_eq!(perc([("name", "zoe"), ("name", "bob")].into()), vec![q[0]]);
+}
+
+#[test]
+fn test_value_counts() {
+    let mut p = Percolator::default();
+    let q = [
+        p.add_query(Query::field_count_ge("tags", 3)),
+        p.add_query(Query::field_count_ge("tags", 4)),
+        p.add_query(Query::field_count_ge("tags", 2) & "colour".has_value("blue")),
+    ];
+    // Not indexed as match all.
+    assert!(q.iter().all(|qid| !p.preheaters_for(qid).is_empty()));
+
+    let perc = |tags: &[&str]| {
+        let d = tags
+            .iter()
+            .fold(Document::new().with_value("colour", "blue"), |d, t| {
+                d.with_value("tags", *t)
+            });
+        p.percolate(&d).sorted().collect::<Vec<_>>()
+    };
+    assert_eq!(perc(&[]), Vec::<Qid>::new());
+    assert_eq!(perc(&["a"]), Vec::<Qid>::new());
+    assert_eq!(perc(&["a", "a", "a"]), Vec::<Qid>::new());
+    assert_eq!(perc(&["a", "b"]), vec![q[2]]);
+    assert_eq!(perc(&["a", "b", "c"]), vec![q[0], q[2]]);
+    assert_eq!(perc(&["a", "b", "c", "d"]), vec![q[0], q[1], q[2]]);
+}
+
+#[test]
+fn test_h3_cell_sets() {
+    let center: h3o::CellIndex = "87194d106ffffff".parse().unwrap();
+    let far: h3o::CellIndex = "87195d106ffffff".parse().unwrap();
+    // A ring of cells around the center, and a finer cell far away.
+    let cells = center
+        .grid_disk::<Vec<_>>(1)
+        .into_iter()
+        .filter(|c| *c != center)
+        .chain(far.children(h3o::Resolution::Eight).take(1))
+        .collect::<Vec<_>>();
+
+    let mut p = Percolator::default();
+    let any = p.add_query("position".h3in_any(&cells));
+    let ors = p.add_query(Query::from_or(
+        cells.iter().map(|c| "position".h3in(*c)).collect(),
+    ));
+
+    // The same documents match the single literal and the disjunction.
+    for doc_cell in center.grid_disk::<Vec<_>>(2).into_iter().chain([far]) {
+        for child in doc_cell.children(h3o::Resolution::Nine).step_by(7) {
+            let d: Document = [("position", child.to_string())].into();
+            let expected = if cells
+                .iter()
+                .any(|c| child.parent(c.resolution()) == Some(*c))
+            {
+                vec![any, ors]
+            } else {
+                vec![]
+            };
+            assert_eq!(p.percolate(&d).sorted().collect::<Vec<_>>(), expected);
+        }
+    }
+    assert_eq!(
+        p.percolate(&[("position", center.to_string())].into())
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn test_h3_max_resolution() {
+    let queries = [
+        "position".h3in("89194d10693ffff".parse().unwrap()),
+        "latlng".latlng_within(LatLng::new(48.864716, 2.349014).unwrap(), Meters(3)),
+        "latlng".latlng_within(LatLng::new(48.864716, 2.349014).unwrap(), Meters(1000)),
+    ];
+    let docs: Vec<Document> = vec![
+        [("position", "89194d10693ffff")].into(),
+        [("position", "8a194d106927fff")].into(),
+        // A sibling of the query cell.
+        [("position", "89194d10697ffff")].into(),
+        [("position", "87194d106ffffff")].into(),
+        [("latlng", "48.864716,2.349014")].into(),
+        [("latlng", "48.864730,2.349014")].into(),
+        [("latlng", "48.859430,2.354946")].into(),
+        [("latlng", "48.85,2.30")].into(),
+    ];
+
+    let mut fine = Percolator::default();
+    let mut coarse = Percolator::builder()
+        .h3_max_resolution(h3o::Resolution::Six)
+        .build();
+    for q in queries {
+        fine.add_query(q.clone());
+        coarse.add_query(q);
+    }
+
+    // The same results, whatever the indexing resolution.
+    for d in docs.iter() {
+        assert_eq!(
+            fine.percolate(d).sorted().collect::<Vec<_>>(),
+            coarse.percolate(d).sorted().collect::<Vec<_>>()
+        );
+    }
+    assert_eq!(fine.percolate(&docs[1]).collect::<Vec<_>>(), vec![0]);
+    assert_eq!(fine.percolate(&docs[2]).count(), 0);
+    assert_eq!(
+        fine.percolate(&docs[5]).sorted().collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert_eq!(fine.percolate(&docs[6]).collect::<Vec<_>>(), vec![2]);
+}
+
+#[test]
+fn test_linear_percolation() {
+    let queries = [
+        "A:a",
+        "A:a OR B:b",
+        "NOT A:a AND B:b",
+        "C:multi* AND NOT C:multimeter",
+        "W<10 OR W>=2000",
+        "NOT A:*",
+    ];
+    let docs: [Document; 5] = [
+        [("A", "a")].into(),
+        [("B", "b"), ("W", "3")].into(),
+        [("C", "multipla"), ("W", "2000")].into(),
+        [("C", "multimeter"), ("A", "b")].into(),
+        Document::default(),
+    ];
+
+    let mut indexed = Percolator::default();
+    let mut linear = LinearPercolator::default();
+    // Linear until 4 live queries.
+    let mut switching = Percolator::builder().linear_threshold(4).build();
+    for q in queries {
+        let qid = indexed.add_query(q.parse().unwrap());
+        assert_eq!(linear.add_query(q.parse().unwrap()), qid);
+        assert_eq!(switching.add_query(q.parse().unwrap()), qid);
+    }
+    indexed.remove_qid(1);
+    linear.remove_qid(1);
+    switching.remove_qid(1);
+
+    for n_removed in 0..3 {
+        for d in &docs {
+            let expected = indexed.percolate(d).sorted().collect::<Vec<_>>();
+            assert_eq!(linear.percolate(d).collect::<Vec<_>>(), expected);
+            assert_eq!(
+                switching.percolate(d).sorted().collect::<Vec<_>>(),
+                expected
+            );
+        }
+        // Go below the threshold.
+        indexed.remove_qid(2 + n_removed);
+        linear.remove_qid(2 + n_removed);
+        switching.remove_qid(2 + n_removed);
+    }
+}
+
+#[test]
+fn test_prefix_compression() {
+    let mut plain = Percolator::default();
+    let mut compressed = Percolator::builder().prefix_compression(true).build();
+    for i in 0..100 {
+        let q = "url".has_value(format!("https://example.com/{}/page", i % 30))
+            | ("path".has_prefix(format!("/usr/lib/{}", i)) & !"url".has_field())
+            | "W".i64_gt(i);
+        assert_eq!(plain.add_query(q.clone()), compressed.add_query(q));
+    }
+    plain.remove_qid(3);
+    compressed.remove_qid(3);
+
+    let docs: [Document; 4] = [
+        [("url", "https://example.com/3/page")].into(),
+        [("url", "https://example.com/3/page/")].into(),
+        [("path", "/usr/lib/42/x"), ("W", "12")].into(),
+        Document::default(),
+    ];
+    for d in &docs {
+        assert_eq!(
+            compressed.percolate(d).sorted().collect::<Vec<_>>(),
+            plain.percolate(d).sorted().collect::<Vec<_>>()
+        );
+    }
+    assert_eq!(compressed.percolate(&docs[0]).count(), 3);
+}
+
+#[test]
+fn test_percolate_scored() {
+    let mut p = PercolatorUid::<&str>::default();
+    p.index_query_uid("colour:blue".parse().unwrap(), "blue")
+        .unwrap();
+    p.index_query_uid(
+        "(colour:blue^3 OR colour:green) AND shape:round^0.5"
+            .parse()
+            .unwrap(),
+        "round",
+    )
+    .unwrap();
+    p.index_query_uid("colour:blue^2 OR taste:sweet^4".parse().unwrap(), "sweet")
+        .unwrap();
+    p.index_query_uid("NOT colour:blue^9".parse().unwrap(), "not_blue")
+        .unwrap();
+
+    assert_eq!(
+        p.percolate_scored(&[("colour", "blue"), ("shape", "round")].into()),
+        vec![("round", 3.5), ("sweet", 2.0), ("blue", 1.0)]
+    );
+    assert_eq!(
+        p.percolate_scored(&[("colour", "blue"), ("taste", "sweet")].into()),
+        vec![("sweet", 4.0), ("blue", 1.0)]
+    );
+    assert_eq!(
+        p.percolate_scored(&[("colour", "green"), ("shape", "round")].into()),
+        vec![("not_blue", 9.0), ("round", 1.5)]
+    );
+    assert_eq!(
+        p.percolate_scored(&[("taste", "bitter")].into()),
+        vec![("not_blue", 9.0)]
+    );
+}
+
+fn check_percolate<P: Percolate<String>>(mut p: P) {
+    assert_eq!(p.add("A".has_value("a"), "a".into()).unwrap(), "a");
+    p.add("B".has_prefix("b"), "b".into()).unwrap();
+    p.add("A".has_value("b"), "a".into()).unwrap();
+    assert_eq!(p.get(&"a".to_string()), Some(&"A".has_value("b")));
+
+    let d: Document = [("A", "b"), ("B", "bb")].into();
+    assert_eq!(p.percolate(&d).sorted().collect::<Vec<_>>(), vec!["a", "b"]);
+
+    assert!(p.remove(&"b".to_string()));
+    assert!(!p.remove(&"b".to_string()));
+    assert!(p.get(&"b".to_string()).is_none());
+    assert_eq!(p.percolate(&d).collect::<Vec<_>>(), vec!["a"]);
+    assert_eq!(p.stats().n_queries(), 3);
+    assert_eq!(p.stats().n_queries_removed(), 2);
+}
+
+#[test]
+fn test_percolate_trait() {
+    check_percolate(PercolatorUid::<String>::default());
+    check_percolate(LinearPercolator::<String>::default());
+
+    let ps: Vec<Box<dyn Percolate<u64>>> = vec![
+        Box::new(PercolatorUid::<u64>::default()),
+        Box::new(LinearPercolator::<u64>::default()),
+    ];
+    for mut p in ps {
+        p.add("A".has_value("a"), 42).unwrap();
+        assert_eq!(
+            p.percolate(&[("A", "a")].into()).collect::<Vec<_>>(),
+            vec![&42]
+        );
+    }
+}
+
+#[test]
+fn test_borrowed_uids() {
+    let mut p = PercolatorUid::<String>::default();
+    p.index_query_uid_requiring("A".has_value("a"), "a".to_string(), &["A"])
+        .unwrap();
+    p.index_query_uid("B".has_prefix("b"), "b".to_string())
+        .unwrap();
+    // Looked up by &str, without making a String.
+    assert_eq!(p.get_query("a"), &"A".has_value("a"));
+    assert_eq!(p.required_fields("a"), vec!["A"]);
+    assert_eq!(p.preheaters_for("b").len(), 1);
+    assert!(p.safe_get_query("c").is_none());
+    assert!(p.remove_uid("b"));
+    assert!(!p.remove_uid("b"));
+
+    let mut p = LinearPercolator::<String>::default();
+    p.index_query_uid("A".has_value("a"), "a".to_string())
+        .unwrap();
+    assert_eq!(p.get_query("a"), &"A".has_value("a"));
+    assert!(p.remove_uid("a"));
+    assert!(p.safe_get_query("a").is_none());
+}
+
+#[test]
+fn test_percolate_subset() {
+    use mokaccino::models::percolator_core::EmptyDocuments;
+    use roaring::RoaringBitmap;
+
+    for threshold in [0, 100] {
+        let mut p = Percolator::builder()
+            .linear_threshold(threshold)
+            .empty_documents(EmptyDocuments::MatchAllOnly)
+            .build();
+        let q0 = p.add_query("A".has_value("a"));
+        let q1 = p.add_query(!"B".has_value("b"));
+        let q2 = p.add_query(Query::match_all());
+        let q3 = p.add_query("A".has_prefix("a"));
+        p.remove_qid(q3);
+
+        let subset = RoaringBitmap::from_iter([q1, q2, q3, 42]);
+        let d = [("A", "a")].into();
+        assert_eq!(
+            p.percolate_subset(&d, &subset).collect::<Vec<_>>(),
+            vec![q1, q2]
+        );
+        assert_eq!(
+            p.percolate_subset(&d, &RoaringBitmap::from_iter([q0]))
+                .collect::<Vec<_>>(),
+            vec![q0]
+        );
+        assert_eq!(
+            p.percolate_subset(&Document::default(), &subset)
+                .collect::<Vec<_>>(),
+            vec![q2]
+        );
+        assert_eq!(p.percolate_subset(&d, &RoaringBitmap::new()).count(), 0);
+    }
+}
+
+#[test]
+fn test_percolate_limit() {
+    let mut p = Percolator::builder().trace_size(10).build();
+    for i in 0..10 {
+        p.add_query(!"A".has_value(i.to_string()));
+    }
+    let d = [("A", "a")].into();
+    assert_eq!(p.percolate_limit(&d, 3).count(), 3);
+    assert!(p.any_match(&d));
+    // Percolations stopped early.
+    let traces = p.recent_percolations();
+    assert_eq!(traces[0].n_matches(), 3);
+    assert_eq!(traces[1].n_matches(), 1);
+    assert_eq!(p.percolate_limit(&d, 0).count(), 0);
+    assert!(!Percolator::default().any_match(&d));
+}
+
+#[test]
+fn test_match_handlers() {
+    use std::sync::{Arc, Mutex};
+
+    let mut p = PercolatorUid::<String>::default();
+    p.index_query_uid("A".has_value("a"), "a".to_string())
+        .unwrap();
+    p.index_query_uid("B".has_value("b"), "b".to_string())
+        .unwrap();
+    p.index_query_uid("C".has_value("c"), "c".to_string())
+        .unwrap();
+
+    let calls = Arc::new(Mutex::new(vec![]));
+    for uid in ["a", "b", "b"] {
+        let calls = Arc::clone(&calls);
+        assert!(p.on_match(uid, move |uid: &String, _: &Document| {
+            calls.lock().unwrap().push(uid.clone())
+        }));
+    }
+    assert!(!p.on_match("z", |_: &String, _: &Document| ()));
+
+    // Overwritten queries keep their handlers, removed ones lose them.
+    p.index_query_uid("A".has_value("aa"), "a".to_string())
+        .unwrap();
+    p.remove_uid("b");
+    p.index_query_uid("B".has_value("b"), "b".to_string())
+        .unwrap();
+    let p = p.compacted();
+
+    let d = [("A", "aa"), ("B", "b"), ("C", "c")].into();
+    assert_eq!(p.percolate_dispatch(&d), 1);
+    assert_eq!(*calls.lock().unwrap(), vec!["a"]);
+    assert!(format!("{:?}", p).contains("handlers"));
+
+    // Without the send feature, handlers need not be Send.
+    #[cfg(not(feature = "send"))]
+    {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut p = Percolator::default();
+        let qid = p.add_query("A".has_value("a"));
+        let seen = Rc::new(RefCell::new(0));
+        let counter = Rc::clone(&seen);
+        p.on_match(&qid, move |_: &Qid, _: &Document| {
+            *counter.borrow_mut() += 1
+        });
+        p.percolate_dispatch(&[("A", "a")].into());
+        assert_eq!(*seen.borrow(), 1);
+    }
+}
+
+#[test]
+fn test_query_expiry() {
+    use std::time::{Duration, SystemTime};
+
+    let now = SystemTime::now();
+    let past = now - Duration::from_secs(60);
+    let future = now + Duration::from_secs(3600);
+
+    let mut p = PercolatorUid::<u64>::builder().match_cache_size(10).build();
+    p.index_query_uid_expiring("A".has_value("a"), 1, future)
+        .unwrap();
+    p.index_query_uid_expiring("A".has_value("a"), 2, future)
+        .unwrap();
+    p.index_query_uid("A".has_value("a"), 3).unwrap();
+    let d = [("A", "a")].into();
+    // Compacting gives new Qids, in any order.
+    let matches = |p: &PercolatorUid<u64>| p.percolate(&d).sorted().collect::<Vec<_>>();
+    assert_eq!(matches(&p), vec![1, 2, 3]);
+
+    // Expired, even if the matches are cached.
+    assert!(p.set_expiry(&2, past));
+    assert!(!p.set_expiry(&4, past));
+    assert_eq!(matches(&p), vec![1, 3]);
+    assert!(!p.explain(&2, &d).unwrap().is_match());
+
+    let p = p.compacted();
+    assert_eq!(p.expiry(&1), Some(future));
+    assert_eq!(p.expiry(&3), None);
+
+    #[cfg(feature = "serde")]
+    let mut p = {
+        let json = serde_json::to_string(&p).unwrap();
+        serde_json::from_str::<PercolatorUid<u64>>(&json).unwrap()
+    };
+    #[cfg(not(feature = "serde"))]
+    let mut p = p;
+    assert_eq!(matches(&p), vec![1, 3]);
+
+    assert_eq!(p.expire_before(now), vec![2]);
+    assert!(p.expire_before(now).is_empty());
+    assert_eq!(p.expire_before(future + Duration::from_secs(1)), vec![1]);
+    assert_eq!(matches(&p), vec![3]);
+}
+
+#[test]
+fn test_replace_query() {
+    use std::time::{Duration, SystemTime};
+
+    let mut p = Percolator::builder().max_clauses(2).build();
+    let qids = p.load_pack("rules", vec!["A".has_value("a")]).unwrap();
+    let qid = qids[0];
+    p.index_query_uid_requiring("B".has_value("b"), qid, &["C"])
+        .unwrap();
+    let later = SystemTime::now() + Duration::from_secs(3600);
+    p.set_expiry(&qid, later);
+
+    // Too many clauses: the old query stays.
+    let too_big = "A".has_value("1") & "B".has_value("2") & "C".has_value("3");
+    assert!(p.replace_query(&qid, too_big).is_err());
+    assert_eq!(p.get_query(&qid), &"B".has_value("b"));
+
+    assert!(p.replace_query(&qid, "B".has_value("bb")).unwrap());
+    assert_eq!(p.get_query(&qid), &"B".has_value("bb"));
+    assert_eq!(p.required_fields(&qid), vec!["C"]);
+    assert_eq!(p.expiry(&qid), Some(later));
+    assert_eq!(p.pack("rules"), Some(qids.as_slice()));
+    assert_eq!(p.percolate(&[("B", "bb")].into()).count(), 0);
+    assert_eq!(
+        p.percolate(&[("B", "bb"), ("C", "c")].into())
+            .collect::<Vec<_>>(),
+        vec![qid]
+    );
+}
+
+#[test]
+fn test_add_queries() {
+    let queries = vec![
+        "A".has_value("a"),
+        "A".has_value("a") | "B".has_prefix("b"),
+        "A".has_value("a") & !"B".has_value("b"),
+        "C".i64_gt(10) & "A".has_value("a") & "B".has_value("b"),
+        Query::match_all(),
+    ];
+    let mut one_by_one = Percolator::builder().normalize_terms(true).build();
+    let mut bulk = Percolator::builder().normalize_terms(true).build();
+    one_by_one.add_query("X".has_value("x"));
+    bulk.add_query("X".has_value("x"));
+    for q in queries.clone() {
+        one_by_one.add_query(q);
+    }
+    let qids = bulk.add_queries(queries);
+    assert_eq!(qids, vec![1, 2, 3, 4, 5]);
+    assert_eq!(bulk.add_queries(vec![]), Vec::<Qid>::new());
+
+    for d in [
+        [("A", " A ")].into(),
+        [("A", "a"), ("B", "bb")].into(),
+        [("A", "a"), ("B", "b"), ("C", "11")].into(),
+        Document::default(),
+    ] {
+        assert_eq!(
+            bulk.percolate(&d).collect::<Vec<_>>(),
+            one_by_one.percolate(&d).collect::<Vec<_>>()
+        );
+    }
+    assert_eq!(bulk.stats().n_queries(), one_by_one.stats().n_queries());
+
+    // All or nothing.
+    let mut p = Percolator::builder().max_clauses(2).build();
+    let too_big = "A".has_value("1") & "B".has_value("2") & "C".has_value("3");
+    assert!(p.safe_add_queries([Query::match_all(), too_big]).is_err());
+    assert_eq!(p.percolate(&[("A", "1")].into()).count(), 0);
+}
+
+#[test]
+fn test_add_queries_batches() {
+    let queries = (0..200)
+        .map(|i| {
+            let v = (i % 7).to_string();
+            match i % 4 {
+                0 => "A".has_value(v),
+                1 => "A".has_value(v) | "B".has_prefix("b"),
+                2 => "A".has_value(v) & !"B".has_value("b") & "C".i64_gt(i),
+                _ => "B".has_value(v) & "A".has_value("1") & "C".i64_lt(i) & "D".has_field(),
+            }
+        })
+        .collect_vec();
+    let docs: Vec<Document> = vec![
+        [("A", "1"), ("B", "bb"), ("C", "50"), ("D", "d")].into(),
+        [("A", "3"), ("B", "b"), ("C", "150")].into(),
+        [("B", "1"), ("A", "1"), ("C", "5"), ("D", "")].into(),
+        Document::default(),
+    ];
+
+    for prefix_compression in [false, true] {
+        let builder = || {
+            Percolator::builder()
+                .prefix_compression(prefix_compression)
+                .recycle_qids(true)
+                .n_clause_matchers(NonZeroUsize::new(2).unwrap())
+        };
+        let (mut one_by_one, mut bulk) = (builder().build(), builder().build());
+        for p in [&mut one_by_one, &mut bulk] {
+            let qids = p.add_queries(["X".has_value("x"), "Y".has_value("y"), "Z".has_value("z")]);
+            p.remove_qid(qids[0]);
+            p.remove_qid(qids[2]);
+        }
+        let expected = queries
+            .iter()
+            .map(|q| one_by_one.add_query(q.clone()))
+            .collect_vec();
+        // The removed qids first, then new ones.
+        let qids = bulk.add_queries(queries.clone());
+        assert_eq!(qids, expected);
+        assert_eq!(qids[..3], [0, 2, 3]);
+
+        for d in &docs {
+            assert_eq!(
+                bulk.percolate(d).sorted().collect_vec(),
+                one_by_one.percolate(d).sorted().collect_vec()
+            );
+        }
+        assert_eq!(bulk.stats().n_queries(), one_by_one.stats().n_queries());
+    }
+}
+
+#[test]
+fn test_remove_where() {
+    let mut p = PercolatorUid::<String, &str>::builder()
+        .normalize_terms(true)
+        .build();
+    p.index_query_with_payload("A".has_value("a"), "a".into(), "alice")
+        .unwrap();
+    p.index_query_uid("A".has_value(" Aa ") & "B".has_value("b"), "ab".into())
+        .unwrap();
+    p.index_query_uid("B".has_value("b"), "b".into()).unwrap();
+    let d = [("A", "a"), ("A", "aa"), ("B", "b")].into();
+    assert_eq!(p.percolate(&d).count(), 3);
+
+    // Normalised like the stored query.
+    let ab = "B".has_value("b") & "A".has_value("AA");
+    assert_eq!(p.remove_matching(&ab), vec!["ab".to_string()]);
+    assert!(p.remove_matching(&ab).is_empty());
+
+    assert_eq!(p.remove_where(|uid, _| uid == "a"), vec!["a".to_string()]);
+    assert_eq!(p.payload("a"), None);
+    assert!(p.remove_where(|_, _| false).is_empty());
+    assert_eq!(p.percolate(&d).collect::<Vec<_>>(), vec![&"b".to_string()]);
+}
+
+#[test]
+fn test_iter() {
+    let mut p = Percolator::default();
+    assert!(p.is_empty());
+    let qids = p.add_queries(["A".has_value("a"), "B".has_value("b"), "C".has_value("c")]);
+    p.remove_qid(qids[1]);
+    assert_eq!(p.len(), 2);
+    assert_eq!(
+        p.iter().collect::<Vec<_>>(),
+        vec![
+            (&qids[0], &"A".has_value("a")),
+            (&qids[2], &"C".has_value("c"))
+        ]
+    );
+
+    // Replacing a query keeps its ID.
+    p.index_query_uid("D".has_value("d"), qids[0]).unwrap();
+    assert_eq!(p.len(), 2);
+    let mut queries = p
+        .iter()
+        .map(|(qid, q)| (*qid, q.to_string()))
+        .collect::<Vec<_>>();
+    queries.sort();
+    assert_eq!(
+        queries,
+        vec![(qids[0], "D:d".to_string()), (qids[2], "C:c".to_string())]
+    );
+}
+
+#[test]
+fn test_compact() {
+    use mokaccino::models::percolator::MemoryUidStore;
+    use std::sync::{Arc, Mutex};
+
+    let mut p = PercolatorUid::<String, u8>::builder()
+        .trace_size(10)
+        .build();
+    p.index_query_with_payload("A".has_value("a"), "a".into(), 1)
+        .unwrap();
+    p.index_query_uid("B".has_value("b"), "b".into()).unwrap();
+    p.index_query_uid_requiring("C".has_value("c"), "c".into(), &["D"])
+        .unwrap();
+    p.set_uid_store(Box::new(MemoryUidStore::default()));
+    let calls = Arc::new(Mutex::new(vec![]));
+    let handler_calls = Arc::clone(&calls);
+    p.on_match("c", move |uid: &String, _: &Document| {
+        handler_calls.lock().unwrap().push(uid.clone())
+    });
+    p.percolate(&[("A", "a")].into()).count();
+    p.remove_uid("b");
+
+    // In place: the handlers and the recent percolations stay.
+    let remapping = p.compact();
+    assert_eq!(
+        remapping.into_iter().collect::<Vec<_>>(),
+        vec![(0, 0), (2, 1)]
+    );
+    assert_eq!(p.holes_ratio(), 0.0);
+    assert_eq!(p.len(), 2);
+    assert_eq!(p.payload("a"), Some(&1));
+    assert_eq!(p.required_fields("c"), vec!["D"]);
+    assert_eq!(
+        p.uid_store().unwrap().mappings(),
+        vec![(0, "a".to_string()), (1, "c".to_string())]
+    );
+    let d = [("A", "a"), ("B", "b"), ("C", "c"), ("D", "d")].into();
+    assert_eq!(
+        p.percolate(&d).collect::<Vec<_>>(),
+        vec![&"a".to_string(), &"c".to_string()]
+    );
+    assert_eq!(p.recent_percolations().len(), 2);
+    assert_eq!(p.percolate_dispatch(&d), 1);
+    assert_eq!(*calls.lock().unwrap(), vec!["c"]);
+
+    // Nothing to compact.
+    let remapping = p.compact();
+    assert!(remapping.iter().all(|(old, new)| old == new));
+}
+
+#[test]
+fn test_compact_then_add() {
+    use roaring::RoaringBitmap;
+
+    let mut p = Percolator::default();
+    let q0 = p.add_query("A".has_value("a"));
+    let q1 = p.add_query("B".has_value("b"));
+    p.remove_qid(q0);
+    p.compact();
+
+    // The new query gets the Qid q1 had, but not its ID.
+    let q2 = p.add_query("C".has_value("c"));
+    let q3 = p.add_queries(["D".has_value("d")])[0];
+    assert_ne!(q2, q1);
+    assert_ne!(q3, q1);
+    assert_ne!(q3, q2);
+    assert_eq!(p.len(), 3);
+    assert_eq!(p.get_query(&q1), &"B".has_value("b"));
+    assert_eq!(p.get_query(&q2), &"C".has_value("c"));
+
+    let d = [("B", "b"), ("C", "c"), ("D", "d")].into();
+    let mut matches = p.percolate(&d).collect::<Vec<_>>();
+    matches.sort();
+    assert_eq!(matches, vec![q1, q2, q3]);
+    let subset = RoaringBitmap::from_iter([q1, q3]);
+    assert_eq!(
+        p.percolate_subset(&d, &subset).collect::<Vec<_>>(),
+        vec![q1, q3]
+    );
+}
+
+#[test]
+fn test_recycle_qids() {
+    let mut recycling = PercolatorUid::<u64>::builder().recycle_qids(true).build();
+    let mut growing = PercolatorUid::<u64>::default();
+    let queries = |i: u64| {
+        [
+            "zone".has_value(i.to_string()),
+            "name".has_prefix(format!("n{}", i)),
+            "A".has_value("a") & "B".has_value("b") & "C".has_value("c") & "D".i64_gt(i as i64),
+        ]
+    };
+    // Short lived queries, three at a time.
+    for i in 0..50 {
+        for (n, q) in queries(i).into_iter().enumerate() {
+            let uid = i * 3 + n as u64;
+            for p in [&mut recycling, &mut growing] {
+                p.index_query_uid(q.clone(), uid).unwrap();
+                if i > 0 {
+                    p.remove_uid(&(uid - 3));
+                }
+            }
+        }
+    }
+    assert_eq!(recycling.to_string(), "MultiPerc-4Qs/3IDXs");
+    assert_eq!(growing.to_string(), "MultiPerc-150Qs/3IDXs");
+
+    for i in [0, 48, 49] {
+        let d = [
+            ("zone", i.to_string()),
+            ("name", format!("n{}x", i)),
+            ("A", "a".into()),
+            ("B", "b".into()),
+            ("C", "c".into()),
+            ("D", "1000".into()),
+        ]
+        .into();
+        let mut expected = growing.percolate(&d).collect::<Vec<_>>();
+        expected.sort();
+        let mut matches = recycling.percolate(&d).collect::<Vec<_>>();
+        matches.sort();
+        assert_eq!(matches, expected);
+    }
+}
+
+#[test]
+fn test_memory_usage() {
+    let mut p = PercolatorUid::<u64>::default();
+    let empty = p.memory_usage();
+    assert_eq!(
+        empty.total(),
+        empty.queries() + empty.indexes() + empty.bitmaps() + empty.preheaters()
+    );
+
+    for i in 0..100 {
+        p.index_query_uid_requiring(
+            "name".has_prefix(format!("n{}", i)) & "zone".has_value(i.to_string()),
+            i,
+            &["zone"],
+        )
+        .unwrap();
+    }
+    let full = p.memory_usage();
+    assert!(full.queries() > empty.queries());
+    assert!(full.indexes() > empty.indexes());
+    assert!(full.bitmaps() > empty.bitmaps());
+    assert!(full.preheaters() > empty.preheaters());
+
+    // Removed queries take memory until compaction.
+    for i in 0..90 {
+        p.remove_uid(&i);
+    }
+    assert!(p.memory_usage().queries() >= full.queries());
+    p.compact();
+    assert!(p.memory_usage().queries() < full.queries() / 5);
+    assert!(p.memory_usage().total() < full.total());
+}
+
+#[test]
+fn test_payloads() {
+    let mut p = PercolatorUid::<String, u32>::default();
+    p.index_query_with_payload("A".has_value("a"), "a".to_string(), 1)
+        .unwrap();
+    p.index_query_with_payload("B".has_value("b"), "b".to_string(), 2)
+        .unwrap();
+    p.index_query_with_payload("C".has_value("c"), "c".to_string(), 3)
+        .unwrap();
+    // Overwriting drops the payload, removing drops the query.
+    p.index_query_uid("A".has_value("a"), "a".to_string())
+        .unwrap();
+    assert!(p.remove_uid("c"));
+    assert_eq!(p.payload("a"), None);
+    assert_eq!(p.payload("c"), None);
+
+    // Compacting gives new Qids, with the same payloads.
+    let p = p.compacted();
+    assert_eq!(p.payload("b"), Some(&2));
+    let d = [("A", "a"), ("B", "b"), ("C", "c")].into();
+    assert_eq!(
+        p.percolate_with_payloads(&d).collect::<Vec<_>>(),
+        vec![(&"b".to_string(), &2)]
+    );
+
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_string(&p).unwrap();
+        let p2: PercolatorUid<String, u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(p2.payload("b"), Some(&2));
+    }
+}
+
+#[test]
+fn test_recent_percolations() {
+    let mut p = Percolator::default();
+    p.add_query("A".has_value("a"));
+    p.percolate(&[("A", "a")].into()).for_each(drop);
+    // Tracing is off by default.
+    assert!(p.recent_percolations().is_empty());
+
+    let mut p = Percolator::builder().trace_size(2).build();
+    p.add_query("A".has_value("a"));
+    p.add_query("A".has_prefix("a") & "B".has_value("b"));
+
+    let d1: Document = [("A", "a")].into();
+    let d2: Document = [("A", "aa"), ("B", "b")].into();
+    let d3: Document = [("A", "a"), ("B", "c")].into();
+    for d in [&d1, &d2, &d3] {
+        p.percolate(d).for_each(drop);
+    }
+
+    // Only the last two are kept, oldest first.
+    let traces = p.recent_percolations();
+    assert_eq!(traces.len(), 2);
+    assert_ne!(traces[0].doc_digest(), traces[1].doc_digest());
+    assert!(traces.iter().all(|t| t.n_matches() == 1));
+    assert!(traces.iter().all(|t| t.n_candidates() >= 1));
+
+    // The same document gives the same digest.
+    let d2_digest = traces[0].doc_digest();
+    p.percolate(&[("B", "b"), ("A", "aa")].into())
+        .for_each(drop);
+    assert_eq!(p.recent_percolations()[1].doc_digest(), d2_digest);
+}
+
 #[test]
 #[cfg(feature = "serde")]
 fn test_serialisation() {
@@ -86,6 +1415,7 @@ fn test_serialisation() {
         p.add_query("A".has_value("a") | "B".has_value("b")), //1
         p.add_query("A".has_value("a") & "B".has_value("b")), //2
         p.add_query(!"A".has_value("a")),                     //3
+        p.add_query("A:/a+/".parse().unwrap()),               //4
         p.add_query("A".i64_lt(10000)),
     ];
 
@@ -97,10 +1427,73 @@ fn test_serialisation() {
     let p2: Percolator = serde_json::from_str(&json).unwrap();
     for qid in qids {
         // No crash. Query is still there!
-        assert!(p2.safe_get_query(qid).is_some());
+        assert!(p2.safe_get_query(&qid).is_some());
     }
     // Check some bonkers qid returns none.
-    assert!(p2.safe_get_query(to_remove).is_none());
+    assert!(p2.safe_get_query(&to_remove).is_none());
+}
+
+#[test]
+#[cfg(feature = "log")]
+fn test_log_warnings() {
+    use std::sync::Mutex;
+
+    struct Recorder(Mutex<Vec<String>>);
+    impl log::Log for Recorder {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= log::Level::Warn
+        }
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                self.0.lock().unwrap().push(record.args().to_string());
+            }
+        }
+        fn flush(&self) {}
+    }
+    static RECORDER: Recorder = Recorder(Mutex::new(vec![]));
+    log::set_logger(&RECORDER).unwrap();
+    log::set_max_level(log::LevelFilter::Warn);
+    let warnings = || std::mem::take(&mut *RECORDER.0.lock().unwrap());
+
+    // Silently a term query.
+    let q: Query = "location H3IN notacell".parse().unwrap();
+    assert_eq!(q, "location".has_value("notacell"));
+    let w = warnings();
+    assert_eq!(w.len(), 1);
+    assert!(w[0].starts_with("Invalid H3 cell \"notacell\" for location"));
+
+    let _: Query = "position LLWITHIN 12 OR name:/a(/".parse().unwrap();
+    let w = warnings();
+    assert_eq!(w.len(), 2);
+    assert!(w[0].starts_with("Number 12 for position LLWITHIN is not"));
+    assert!(w[1].starts_with("Invalid regex /a(/ for name"));
+
+    let mut p = Percolator::builder()
+        .n_clause_matchers(NonZeroUsize::new(2).unwrap())
+        .build();
+    p.add_query("A".has_value("a") & "B".has_value("b"));
+    assert!(warnings().is_empty());
+    p.add_query("A".has_value("a") & "B".has_value("b") & "C".has_value("c"));
+    assert_eq!(
+        warnings(),
+        vec![
+            "Query 1 has 3 clauses, more than the 2 clause matchers. \
+             Its candidates will need checking (see n_clause_matchers)."
+        ]
+    );
+
+    for i in 0..255 {
+        p.add_query(format!("F{}", i).has_prefix("abc"));
+    }
+    assert!(warnings().is_empty());
+    p.add_query("F255".has_prefix("abc"));
+    assert_eq!(
+        warnings(),
+        vec![
+            "256 preheaters, all run on each percolated document. \
+              Query 257 added __PREFIX2__F255. See prefix_sizes and the stats."
+        ]
+    );
 }
 
 fn test_nclause_percolator(n: NonZeroUsize, optimized: bool) {
@@ -135,9 +1528,9 @@ fn test_nclause_percolator(n: NonZeroUsize, optimized: bool) {
         p.add_query("P".has_prefix("")), // 20
     ];
 
-    p.remove_uid(q[18]);
-    p.remove_uid(q[19]);
-    p.remove_uid(q[20]);
+    p.remove_uid(&q[18]);
+    p.remove_uid(&q[19]);
+    p.remove_uid(&q[20]);
 
     // Optimized doesn't break anything
     if optimized {
@@ -10,20 +10,20 @@ fn test_query() {
 
     let q = "colour".has_value("blue");
     assert!(q.matches(&d));
-    assert_eq!(q.to_string(), "(AND (OR colour=blue))");
+    assert_eq!(q.to_string(), "colour:blue");
 
     let q2 = "colour".has_value("red");
     assert!(!q2.matches(&d));
-    assert_eq!(q2.to_string(), "(AND (OR colour=red))");
+    assert_eq!(q2.to_string(), "colour:red");
 
     let q3 = "another_key".has_value("sausage");
     assert!(!q3.matches(&d));
-    assert_eq!(q3.to_string(), "(AND (OR another_key=sausage))");
+    assert_eq!(q3.to_string(), "another_key:sausage");
 
     let q_and_q2 = q & q2;
 
     assert!(!q_and_q2.matches(&d));
-    assert!(q_and_q2.to_string() == "(AND (OR colour=blue) (OR colour=red))");
+    assert!(q_and_q2.to_string() == "colour:blue AND colour:red");
 }
 
 #[test]
@@ -36,11 +36,13 @@ fn test_negation_queries() {
     // Vacuous truth
     assert!(q.matches(&Document::default()));
     assert!(q.matches(&Document::default().with_value("A", "a")));
-    assert!(q.matches(
-        &Document::default()
-            .with_value("A", "a")
-            .with_value("B", "x")
-    ));
+    assert!(
+        q.matches(
+            &Document::default()
+                .with_value("A", "a")
+                .with_value("B", "x")
+        )
+    );
 
     // Vacuous truth.
     assert!(!q.matches(&Document::default().with_value("B", "x")));
@@ -56,16 +58,13 @@ fn test_conjunction_disjunction_query() {
 
     let green_and_sweet = "colour".has_value("green") & "taste".has_value("sweet");
     assert!(green_and_sweet.matches(&d));
-    assert_eq!(
-        green_and_sweet.to_string(),
-        "(AND (OR colour=green) (OR taste=sweet))"
-    );
+    assert_eq!(green_and_sweet.to_string(), "colour:green AND taste:sweet");
 
     let green_or_bitter = "colour".has_value("green") | "taste".has_value("bitter");
     assert!(green_or_bitter.matches(&d));
     assert_eq!(
         green_or_bitter.to_string(),
-        "(AND (OR colour=green taste=bitter))"
+        "(colour:green OR taste:bitter)"
     );
 
     // a disjunction of conjunctions
@@ -75,7 +74,7 @@ fn test_conjunction_disjunction_query() {
     assert!(gab_or_bas.matches(&d));
     assert_eq!(
         gab_or_bas.to_string(),
-        "(AND (OR colour=blue colour=green) (OR colour=green taste=sweet) (OR colour=blue taste=bitter) (OR taste=bitter taste=sweet))"
+        "(colour:blue OR colour:green) AND (colour:blue OR taste:bitter) AND (colour:green OR taste:sweet) AND (taste:bitter OR taste:sweet)"
     );
 
     let gob_and_b = green_or_bitter & "colour".has_value("blue");
@@ -103,6 +102,6 @@ fn test_conjunction_disjunction_query() {
 
     assert_eq!(
         gob_and_b.to_string(),
-        "(AND (OR colour=green taste=bitter) (OR colour=blue))"
+        "colour:blue AND (colour:green OR taste:bitter)"
     );
 }
@@ -29,3 +29,149 @@ fn test_random_is_not_default() {
         assert_ne!(q, Query::default());
     }
 }
+
+#[test]
+fn test_example_documents() {
+    let mut rng = rand::rng();
+    let queries = [
+        "colour:blue OR colour:red",
+        "name:bur* AND name:/bu.ger/",
+        "price > 10 AND price <= 20 AND 0.5 < weight < 1.5",
+        "location H3IN 861f09b27ffffff AND position LLWITHIN 48.86,2.34,1000",
+        "position LLINPOLYGON 0,0;0,1;1,1;1,0 AND NOT position LLWITHIN 0.5,0.5,1000",
+        "colour:* AND NOT taste:* AND NOT name:burger",
+    ];
+    for s in queries {
+        let q: Query = s.parse().unwrap();
+        let docs = q.example_documents(&mut rng, 10);
+        assert_eq!(docs.len(), 10, "{}", s);
+        assert!(docs.iter().all(|d| q.matches(d)), "{}", s);
+
+        let misses = q.example_near_misses(&mut rng, 10);
+        assert_eq!(misses.len(), 10, "{}", s);
+        assert!(misses.iter().all(|d| !q.matches(d)), "{}", s);
+    }
+
+    // Nothing matches this.
+    let q: Query = "a:x AND NOT a:x".parse().unwrap();
+    assert!(q.example_documents(&mut rng, 10).is_empty());
+
+    for _ in 0..100 {
+        let q = Query::random(&mut rng);
+        assert!(
+            q.example_documents(&mut rng, 3)
+                .iter()
+                .all(|d| q.matches(d))
+        );
+        assert!(
+            !q.example_near_misses(&mut rng, 3)
+                .iter()
+                .any(|d| q.matches(d))
+        );
+    }
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn test_public_generators() {
+    use mokaccino::prelude::parsing::{FieldType, ParserOptions};
+    use mokaccino::testing::*;
+
+    let mut rng = rand::rng();
+    for _ in 0..100 {
+        assert!(random_query_string(&mut rng, 3).parse::<Query>().is_ok());
+        assert_ne!(random_query(&mut rng, 0), Query::default());
+        assert_ne!(random_atom(&mut rng), Query::default());
+    }
+
+    let schema = [
+        ("name", FieldType::Keyword),
+        ("price", FieldType::Integer),
+        ("weight", FieldType::Float),
+        ("position", FieldType::LatLng),
+        ("location", FieldType::H3Cell),
+    ];
+    let d = random_document_for_schema(&mut rng, &schema);
+    assert_eq!(d.fv_count(), 5);
+    assert!(d.values("price")[0].parse::<i64>().is_ok());
+    assert!(d.values("weight")[0].parse::<f64>().is_ok());
+    let options = schema
+        .iter()
+        .fold(ParserOptions::default(), |o, (f, t)| o.field_type(*f, *t));
+    // The document is where it says.
+    let position = d.values("position")[0].to_string();
+    let q = Query::parse_with(&format!("position:{},10", position), &options).unwrap();
+    assert!(q.matches(&d));
+    let location = d.values("location")[0].to_string();
+    let q = Query::parse_with(&format!("location:{}", location), &options).unwrap();
+    assert!(q.matches(&d));
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn test_assertion_helpers() {
+    use mokaccino::assert_matches;
+    use mokaccino::prelude::*;
+    use mokaccino::testing::*;
+
+    let mut p = Percolator::default();
+    let blue = p.add_query("colour".has_value("blue"));
+    let sweet = p.add_query("taste".has_value("sweet"));
+    assert_matches!(
+        p,
+        [("colour", "blue"), ("taste", "sweet")].into(),
+        [sweet, blue]
+    );
+    assert_matches!(p, Document::new(), []);
+    assert_eq!(
+        check_matches(&p, &[("colour", "blue")].into(), vec![sweet]),
+        Err(format!(
+            "Unexpected matches for {:?}. Missing: [{}], unexpected: [{}]",
+            Document::from([("colour", "blue")]),
+            sweet,
+            blue
+        ))
+    );
+
+    let mut lp = LinearPercolator::<&str>::default();
+    lp.add("colour".has_value("blue"), "blue").unwrap();
+    assert_matches!(lp, [("colour", "blue")].into(), ["blue"]);
+
+    let path = std::env::temp_dir().join(format!("mokaccino_stats_{}.txt", std::process::id()));
+    assert_stats_snapshot(p.stats(), &path);
+    assert_stats_snapshot(p.stats(), &path);
+    p.add_query("colour".has_value("red") | "colour".has_prefix("gr"));
+    let changed = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        assert_stats_snapshot(p.stats(), &path)
+    }));
+    std::fs::remove_file(&path).unwrap();
+    assert!(changed.is_err());
+}
+
+#[cfg(feature = "testing")]
+#[test]
+#[should_panic(expected = "Missing: [1]")]
+fn test_assert_matches_fails() {
+    use mokaccino::assert_matches;
+    use mokaccino::prelude::*;
+
+    let mut p = Percolator::default();
+    p.add_query("colour".has_value("blue"));
+    p.add_query("taste".has_value("sweet"));
+    assert_matches!(p, [("colour", "blue")].into(), [0, 1]);
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn test_corpus_generators() {
+    use mokaccino::testing::*;
+
+    let mut rng = rand::rng();
+    let queries = random_queries(&mut rng, 20, 2);
+    assert_eq!(queries.len(), 20);
+    let docs = random_documents_for(&mut rng, &queries, 50);
+    assert!(docs.len() <= 50);
+    // Mostly matches and near misses of the corpus.
+    assert!(docs.iter().any(|d| queries.iter().any(|q| q.matches(d))));
+    assert!(random_documents_for(&mut rng, &[], 5).is_empty());
+}